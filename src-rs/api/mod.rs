@@ -0,0 +1,136 @@
+//! Rust API over sessions and workspace state, independent of Node.
+//!
+//! Everything here returns `anyhow::Result` and plain Rust types. The napi
+//! bindings in `ffi` are thin adapters over it; Rust hosts (a CLI, a server)
+//! can build the crate with `--no-default-features` and use it directly.
+
+mod session;
+mod session_util;
+
+pub use session::confirmation::{answer_question, cancel_tool};
+pub use session::{AgentResult, Session};
+pub use crate::session::store::DEFAULT_NAMESPACE;
+pub use session_util::{
+    accept_turn_changes, apply_proposals, close_session, delete_session, delete_sessions,
+    discard_changes, discard_proposals, dispatch_command, duplicate_session, flush_sessions, get_allowed_tools,
+    get_auto_accept_paths, get_core_status, get_lsp_status, get_output_limit, get_overlay_changes, get_overlay_mode,
+    get_pinned, get_proposals, get_propose_mode, get_saved_sessions, get_session_events, get_session_skills,
+    get_session_vars, get_sessions, get_shell_state, get_sync_status, get_tool_stats, get_turn_timings,
+    get_workspace_trust, list_saved_sessions_for_workspace, list_trash, lock_file, materialize_changes, pin_message,
+    pull_files, purge_trash, push_files, query_saved_sessions, rename_session, restore_from_trash, revert_turn_changes,
+    run_preflight, set_auto_accept_paths, set_output_limit, set_overlay_mode, set_propose_mode, set_session_notes,
+    set_session_skills, set_session_var, set_theme, shutdown, trust_workspace, unlock_file, unpin_message,
+    AllowedToolsInfo, AutoModeOptions, AutoRunResult, AvailableModel, CommandResult, CoreStatus, DeniedTool,
+    InterruptedTurnInfo, LatencyInfo, LspServerStatus, McpServerStatus, ModelAlias, OutputLimitSettings,
+    OverlayChangeInfo, OverlayMaterializeResult, PinnedMessage, PlanRunResult, PreflightFinding, ProviderMessage,
+    SavedSessionInfo, SavedSessionPage, SavedSessionQuery, SessionSkill, SessionStatusInfo, SessionVar, ShellStateInfo,
+    SubsystemError, SyncStatusInfo, ToolChoiceSettings, TrashEntryInfo, WorkspaceTrustInfo,
+};
+
+pub use crate::session::protocol::ProtocolInfo;
+pub use crate::settings_bundle::SettingsBundleInfo;
+pub use crate::telemetry::{TelemetryCounter, TelemetryMetrics};
+
+use crate::config::AppConfig;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Opt in to (or out of) anonymous usage metrics; the choice is saved
+pub fn set_telemetry_enabled(enabled: bool) -> Result<()> {
+    crate::telemetry::set_enabled(enabled).context("Failed to save telemetry setting")
+}
+
+/// Usage counts recorded since the process started
+pub fn get_telemetry_metrics() -> TelemetryMetrics {
+    crate::telemetry::metrics()
+}
+
+/// Write the user config, runtime config and skills to one bundle file at
+/// `path`. API keys and other secrets are included, encrypted, only with a
+/// passphrase; workspace trust is left out
+pub fn export_settings(path: &str, passphrase: Option<String>) -> Result<SettingsBundleInfo> {
+    crate::settings_bundle::export(Path::new(path), passphrase.as_deref()).context("Failed to export settings")
+}
+
+/// Install the settings of a bundle written by `export_settings`; secrets are
+/// restored only with the passphrase it was exported with
+pub fn import_settings(path: &str, passphrase: Option<String>) -> Result<SettingsBundleInfo> {
+    crate::settings_bundle::import(Path::new(path), passphrase.as_deref()).context("Failed to import settings")
+}
+
+/// Event protocol versions and features the core supports, and those negotiated
+pub fn get_protocol_info() -> ProtocolInfo {
+    crate::session::protocol::info()
+}
+
+/// Send events at `version` with `features` (all of that version's if None),
+/// so a frontend built for an older protocol keeps working
+pub fn negotiate_protocol(version: u32, features: Option<Vec<String>>) -> Result<ProtocolInfo> {
+    let version = u16::try_from(version).context("Invalid event protocol version")?;
+    crate::session::protocol::negotiate(version, features.as_deref())
+}
+
+/// Whether the user is looking at the app; `[notify] only_when_away` holds
+/// notifications back while they are
+pub fn set_user_present(present: bool) {
+    crate::notifier::set_user_present(present);
+}
+
+/// The effective configuration, as JSON
+pub fn app_config_json() -> Result<String> {
+    let config = AppConfig::load().context("Failed to load config")?;
+    Ok(serde_json::to_string(&config)?)
+}
+
+/// Every configured provider/model pair
+pub fn list_available_models() -> Result<Vec<AvailableModel>> {
+    crate::init_logger();
+    let cfg = AppConfig::load().context("Failed to load config")?;
+    let mut out = Vec::new();
+    for p in cfg.providers {
+        for m in p.models {
+            out.push(AvailableModel {
+                provider: p.name.clone(),
+                model: m,
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Aliases from `[models.aliases]` that resolve to a configured model
+pub fn list_model_aliases() -> Result<Vec<ModelAlias>> {
+    crate::init_logger();
+    let cfg = AppConfig::load().context("Failed to load config")?;
+    let mut out: Vec<ModelAlias> = cfg
+        .models
+        .aliases
+        .keys()
+        .filter_map(|name| {
+            cfg.resolve_model(name).map(|(provider, model)| ModelAlias {
+                name: name.clone(),
+                provider,
+                model,
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(out)
+}
+
+/// `default_model` from the config as "provider:model", when it can be resolved
+pub fn get_default_model() -> Result<Option<String>> {
+    crate::init_logger();
+    let cfg = AppConfig::load().context("Failed to load config")?;
+    let Some(raw) = cfg.default_model.clone() else {
+        return Ok(None);
+    };
+    let raw = raw.trim().to_string();
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    if let Some((provider, model)) = cfg.resolve_model(&raw) {
+        return Ok(Some(format!("{}:{}", provider, model)));
+    }
+    Ok(Some(raw))
+}
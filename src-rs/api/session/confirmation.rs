@@ -0,0 +1,520 @@
+//! What a tool call needs from the user before it runs, or instead of running:
+//! their approval, a proposal they review after the turn, an answer to an
+//! ask_user question. Also cancelling one call from the frontend.
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::oneshot::{self, error::RecvError};
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+use crate::llm::tools::tool_trait::{Tool, ToolKind, ToolOperation as CoreToolOperation, ToolResult};
+use crate::llm::utils::tool_access::{with_tool_access, with_tool_session, ToolAccessLevel};
+use crate::session::auto_accept::AutoAcceptScope;
+use crate::session::context::{ApprovalMode, PendingQuestion};
+use crate::session::manager::session_key;
+use crate::session::proposals;
+use crate::session::skills::Skill;
+use crate::session::types::{
+    CoreConfirmDecision, CoreConfirmationRequest, CoreEvent, CoreEventType, CoreUserQuestion,
+};
+use crate::session::{
+    approval_policy, emit_control_event, generate_request_id, tool_key_path, ConfirmDecision, SESSION_MANAGER,
+};
+
+use super::super::session_util::{log_session_event, now_ms, truncate_utf8_with_ellipsis, with_proposals};
+use super::executor::{run_tool_in_session, ToolCall};
+
+pub(crate) struct PendingConfirmation {
+    pub(crate) request_id: String,
+    pub(crate) sender: oneshot::Sender<ConfirmDecision>,
+}
+
+pub(crate) async fn confirm_tool(
+    session_id: &str,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    decision: CoreConfirmDecision,
+) -> Result<()> {
+    let request_id = decision.request_id.clone();
+    let decided = decision.to_decision();
+    log_session_event(
+        session_id,
+        "confirm_tool_called",
+        json!({ "decision": decided.as_str(), "request_id": request_id }),
+    );
+
+    let mut sender_guard = confirmation_sender.lock().await;
+    // A stale or duplicate decision leaves the pending confirmation in place
+    if let Some(pending) = sender_guard.take_if(|pending| pending.request_id == decision.request_id) {
+        pending
+            .sender
+            .send(decided)
+            .map_err(|_| anyhow!("Failed to send confirmation"))?;
+    } else if let Some(pending) = sender_guard.as_ref() {
+        log_session_event(
+            session_id,
+            "confirm_tool_ignored",
+            json!({ "reason": "request_id_mismatch", "pending_request_id": pending.request_id, "request_id": decision.request_id }),
+        );
+    } else {
+        log_session_event(
+            session_id,
+            "confirm_tool_ignored",
+            json!({ "reason": "no_active_request" }),
+        );
+    }
+    Ok(())
+}
+
+/// Whether a call waits for the user's approval before running, unless they
+/// allowed it for the session. `approval_mode` decides for ordinary calls;
+/// destructive calls, sensitive reads and shell commands acting outside the
+/// workspace ask in every mode
+pub(crate) fn call_requires_confirmation(
+    approval_mode: &ApprovalMode,
+    auto_accept: Option<&AutoAcceptScope>,
+    tool: &dyn Tool,
+    tool_name: &str,
+    args: &str,
+    key_path: &str,
+    safe_commands: &[String],
+) -> bool {
+    let kind = tool.kind();
+    let access_level = if matches!(approval_mode, ApprovalMode::AgentFull) {
+        ToolAccessLevel::Full
+    } else {
+        ToolAccessLevel::Workspace
+    };
+    let risk = approval_policy::command_risk(tool_name, args, safe_commands);
+    (approval_policy::requires_confirmation(approval_mode, kind, risk)
+        && !auto_accept.is_some_and(|scope| scope.accepts(kind, key_path)))
+        || tool.is_destructive(args)
+        || with_tool_access(access_level, || {
+            approval_policy::is_destructive_call(tool_name, args) || approval_policy::is_sensitive_read(tool_name, args)
+        })
+}
+
+/// Ask the user to approve `call` and wait for their decision; an error if
+/// the confirmation was dropped without one
+pub(crate) async fn request_confirmation(
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    call: &ToolCall,
+    preview: Option<String>,
+) -> Result<ConfirmDecision, RecvError> {
+    let kind = call.tool.kind();
+    log_session_event(
+        &call.session_id,
+        "confirm_requested",
+        json!({
+            "tool_name": call.tool_name.clone(),
+            "key_path": call.key_path.clone(),
+            "kind": format!("{:?}", kind),
+            "args_summary": call.args_summary.clone()
+        }),
+    );
+
+    let (tx, rx) = oneshot::channel();
+    *confirmation_sender.lock().await = Some(PendingConfirmation {
+        request_id: call.id.clone(),
+        sender: tx,
+    });
+
+    emit_control_event(
+        &call.session_id,
+        CoreEvent {
+            session_id: call.session_id.clone(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::ConfirmationRequested,
+            args_summary: Some(call.args_summary.clone()),
+            confirm: Some(CoreConfirmationRequest {
+                request_id: call.id.clone(),
+                tool_name: call.tool_name.clone(),
+                tool_display_name: call.display_name.clone(),
+                arguments: call.args.clone(),
+                summary: call.args_summary.clone(),
+                kind: format!("{:?}", kind),
+                key_path: call.key_path.clone(),
+                preview,
+            }),
+            tool_call_id: Some(call.id.clone()),
+            ..Default::default()
+        },
+    );
+
+    let decision = rx.await;
+    log_session_event(
+        &call.session_id,
+        "confirm_decision",
+        json!({
+            "tool_name": call.tool_name.clone(),
+            "key_path": call.key_path.clone(),
+            "decision": decision.as_ref().map_or("closed", ConfirmDecision::as_str)
+        }),
+    );
+    decision
+}
+
+/// Preview of an unconfirmed ToolResult that is waiting for confirmation
+pub(crate) fn confirmation_preview(raw_result: &str) -> Option<String> {
+    let tr: serde_json::Value = serde_json::from_str(raw_result).ok()?;
+    let pending = tr.get("success").and_then(|v| v.as_bool()) == Some(true)
+        && tr.get("requires_confirmation").and_then(|v| v.as_bool()) == Some(true);
+    if !pending {
+        return None;
+    }
+    tr.pointer("/data/preview")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+/// Hold a mutating call back as a proposal instead of running it. Tools that
+/// preview their changes run unconfirmed for the diff; write and edit calls
+/// are diffed here. The model gets a result saying the change is not applied.
+pub(crate) async fn propose_call(
+    session_id: &str,
+    access_level: ToolAccessLevel,
+    tool: &dyn Tool,
+    args: &str,
+    effective_args: &str,
+    summary: &str,
+    timeout: Option<Duration>,
+) -> anyhow::Result<String> {
+    let (tool_name, kind, op) = (tool.name().to_string(), tool.kind(), tool.operation());
+    let (preview, change) = if approval_policy::previews_before_confirmation(&tool_name) {
+        let raw = run_tool_in_session(session_id, access_level, tool, args, timeout).await?;
+        match confirmation_preview(&raw) {
+            Some(preview) => (Some(preview), None),
+            // Failed or nothing to apply: report to the model as is
+            None => return Ok(raw),
+        }
+    } else {
+        let staged = |path: &Path| with_proposals(session_id, |p| p.staged(path).map(str::to_string)).flatten();
+        let change = with_tool_access(access_level, || {
+            with_tool_session(session_id, || proposals::preview(&tool_name, args, staged))
+        })?;
+        (None, change)
+    };
+    let diff_stats = change.as_ref().map(|c| c.diff_stats.clone());
+    let key_path = tool_key_path(Some(tool), &tool_name, args);
+    let id = with_proposals(session_id, |p| p.add(&tool_name, effective_args, summary, &key_path, preview, change))
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    log_session_event(
+        session_id,
+        "proposal_added",
+        json!({ "id": id.clone(), "tool_name": tool_name.clone(), "key_path": key_path.clone() }),
+    );
+    let mut stdout = format!(
+        "Proposed as {}; NOT applied yet. The user reviews the proposed changes when the turn ends \
+         and applies the ones they approve. Files keep their current content until then; \
+         continue the task as if this change were made.",
+        id
+    );
+    if let Some(stats) = &diff_stats {
+        stdout.push_str("\n\n");
+        stdout.push_str(&stats.unified_diff);
+    }
+    let mut result = ToolResult::proposed(
+        &tool_name,
+        kind,
+        op,
+        &id,
+        stdout,
+        json!({ "proposal_id": id, "diff_stats": diff_stats }),
+    );
+    result.key_path = key_path;
+    Ok(serde_json::to_string_pretty(&result).unwrap_or_default())
+}
+
+/// Run an ask_user call and pause the turn until `answer_question` answers
+/// it; the answer becomes the call's result. A cancelled turn gets no answer.
+pub(crate) async fn ask_user(session_id: &str, tool: &dyn Tool, args: &str) -> anyhow::Result<String> {
+    let raw = run_tool_in_session(session_id, ToolAccessLevel::Workspace, tool, args, None).await?;
+    let Ok(mut result) = serde_json::from_str::<ToolResult>(&raw) else {
+        return Ok(raw);
+    };
+    if !result.success || result.data["awaiting_user_input"] != true {
+        return Ok(raw);
+    }
+    let question = result.data["question"].as_str().unwrap_or_default().to_string();
+    let choices: Vec<String> = serde_json::from_value(result.data["choices"].clone()).unwrap_or_default();
+
+    let (tx, rx) = oneshot::channel();
+    let request_id = generate_request_id();
+    let cancel_token = {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.pending_question = Some(PendingQuestion {
+            request_id: request_id.clone(),
+            sender: tx,
+        });
+        ctx.cancel_token.clone()
+    };
+    log_session_event(
+        session_id,
+        "user_input_requested",
+        json!({ "request_id": request_id.clone(), "choices": choices.len() }),
+    );
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::UserInputRequested,
+            tool_name: Some(result.tool_name.clone()),
+            display_text: Some(question.clone()),
+            question: Some(CoreUserQuestion {
+                request_id: request_id.clone(),
+                question: question.clone(),
+                choices: choices.clone(),
+            }),
+            ..Default::default()
+        },
+    );
+
+    let answer = tokio::select! {
+        answer = rx => answer.ok(),
+        _ = cancel_token.cancelled() => None,
+    };
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(session_id) {
+            if ctx.pending_question.as_ref().is_some_and(|p| p.request_id == request_id) {
+                ctx.pending_question = None;
+            }
+        }
+    }
+    log_session_event(
+        session_id,
+        "user_input_answered",
+        json!({ "request_id": request_id, "answered": answer.is_some() }),
+    );
+    let Some(answer) = answer else {
+        return Ok(serde_json::to_string(&crate::llm::tools::tool_trait::ToolOutput::error(
+            format!("tool call {} {}", result.tool_name, args),
+            "The user did not answer the question.",
+        ))
+        .unwrap());
+    };
+    result.executed = true;
+    result.stdout = format!("User answered: {}", answer);
+    result.response_summary = Some(truncate_utf8_with_ellipsis(&answer, 80));
+    result.data = json!({ "question": question, "choices": choices, "answer": answer });
+    Ok(serde_json::to_string_pretty(&result).unwrap_or_default())
+}
+
+/// Answer the question of a paused ask_user call. Returns false if the
+/// session has no question with `request_id` waiting.
+pub fn answer_question(namespace: &str, session_id: &str, request_id: &str, answer: &str) -> Result<bool> {
+    let session_id = &session_key(namespace, session_id);
+    let pending = {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        if ctx.pending_question.as_ref().is_none_or(|p| p.request_id != request_id) {
+            return Ok(false);
+        }
+        ctx.pending_question.take()
+    };
+    Ok(pending.is_some_and(|p| p.sender.send(answer.to_string()).is_ok()))
+}
+
+/// Cancel one tool call, waiting for confirmation or running, by the
+/// `tool_call_id` of its events. The model is told the user cancelled it and
+/// the turn goes on. Returns false if that call is not in progress.
+pub fn cancel_tool(namespace: &str, session_id: &str, request_id: &str) -> Result<bool> {
+    let session_id = &session_key(namespace, session_id);
+    let cancel = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager.get(session_id).ok_or_else(|| anyhow!("Session not found"))?;
+        match ctx.running_tool.as_ref().filter(|call| call.request_id == request_id) {
+            Some(call) => call.cancel.clone(),
+            None => return Ok(false),
+        }
+    };
+    cancel.cancel();
+    log_session_event(session_id, "tool_cancel_requested", json!({ "request_id": request_id }));
+    Ok(true)
+}
+
+/// Result of a call the user denied. A reason they gave is passed on as an
+/// instruction, so the model changes course rather than retrying the call.
+pub(crate) fn denied_tool_result(
+    tool_name: &str,
+    kind: ToolKind,
+    op: CoreToolOperation,
+    key_path: String,
+    reason: Option<&str>,
+) -> String {
+    let stderr = match reason {
+        Some(reason) => format!(
+            "The user denied this call and said: \"{}\". Follow that instead of retrying the call.",
+            reason
+        ),
+        None => "The user denied this call. Take a different approach or ask them how to proceed.".to_string(),
+    };
+    let mut result = ToolResult::err(tool_name, kind, op, stderr, json!({ "denied": true, "reason": reason }))
+        .with_summary("denied");
+    result.executed = false;
+    result.key_path = key_path;
+    serde_json::to_string_pretty(&result).unwrap_or_default()
+}
+
+/// Result of a call an active skill does not allow; says which tools are,
+/// so the model does not try the call again
+pub(crate) fn skill_denied_tool_result(
+    tool_name: &str,
+    kind: ToolKind,
+    op: CoreToolOperation,
+    key_path: String,
+    skill: &Skill,
+) -> String {
+    let allowed = skill.allowed_tools.clone().unwrap_or_default();
+    let stderr = format!(
+        "The active skill '{}' does not allow the {} tool. Use only these tools while it is active: {}",
+        skill.name,
+        tool_name,
+        allowed.join(", ")
+    );
+    let mut result = ToolResult::err(
+        tool_name,
+        kind,
+        op,
+        stderr,
+        json!({ "denied": true, "skill": skill.name, "allowed_tools": allowed }),
+    )
+    .with_summary("denied by skill");
+    result.executed = false;
+    result.key_path = key_path;
+    serde_json::to_string_pretty(&result).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::executor::{cancelled_tool_result, set_running_tool};
+    use crate::config::{AppConfig, ProviderConfig};
+    use crate::llm::agents::agent::Agent as RustAgent;
+    use crate::llm::agents::cancel::CancelToken;
+    use crate::llm::tools::tool_trait::ToolOperation;
+    use crate::llm::tools::{BashTool, ToolAdapter, WriteTool};
+    use crate::session::context::RunningToolCall;
+    use crate::session::skills::SkillSource;
+    use crate::session::store::DEFAULT_NAMESPACE;
+    use crate::session::types::CoreConfirmDecisionKind;
+
+    fn embedded_config() -> AppConfig {
+        toml::from_str(include_str!("../../../Config.toml")).expect("embedded Config.toml should parse")
+    }
+
+    #[test]
+    fn cancel_tool_stops_only_the_named_call() {
+        let session_id = "cancel-tool-session".to_string();
+        let key = session_key(DEFAULT_NAMESPACE, &session_id);
+        let provider = ProviderConfig {
+            name: "openai".to_string(),
+            base_url: "http://127.0.0.1:1".to_string(),
+            api_key: "k".to_string(),
+            models: vec!["m1".to_string()],
+            ..Default::default()
+        };
+        let agent = RustAgent::new("openai".to_string(), "m1".to_string(), None, vec![provider], Vec::new()).unwrap();
+        SESSION_MANAGER.lock().unwrap().add(session_id.clone(), agent);
+        assert!(!cancel_tool(DEFAULT_NAMESPACE, &session_id, "call-1").unwrap());
+
+        let cancel = CancelToken::new();
+        let call = RunningToolCall { request_id: "call-1".to_string(), cancel: cancel.clone() };
+        set_running_tool(&key, Some(call));
+        assert!(!cancel_tool(DEFAULT_NAMESPACE, &session_id, "call-0").unwrap());
+        assert!(cancel_tool("alice", &session_id, "call-1").is_err());
+        assert!(!cancel.is_cancelled());
+        assert!(cancel_tool(DEFAULT_NAMESPACE, &session_id, "call-1").unwrap());
+        assert!(cancel.is_cancelled());
+        assert!(cancel_tool(DEFAULT_NAMESPACE, "no-such-session", "call-1").is_err());
+
+        let raw = cancelled_tool_result("bash", ToolKind::Execute, ToolOperation::Other, "ls".to_string());
+        let v: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!((v["success"].clone(), v["data"]["cancelled"].clone()), (json!(false), json!(true)));
+        SESSION_MANAGER.lock().unwrap().remove(&key);
+    }
+
+    #[tokio::test]
+    async fn confirm_tool_keeps_the_pending_request_on_a_mismatched_decision() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let sender = Arc::new(Mutex::new(Some(PendingConfirmation { request_id: "call-2".to_string(), sender: tx })));
+        let decision = |request_id: &str| CoreConfirmDecision {
+            request_id: request_id.to_string(),
+            decision: CoreConfirmDecisionKind::AllowOnce,
+            message: None,
+        };
+
+        confirm_tool("confirm-mismatch-session", &sender, decision("call-1")).await.unwrap();
+        assert!(sender.lock().await.is_some());
+        confirm_tool("confirm-mismatch-session", &sender, decision("call-2")).await.unwrap();
+        assert!(sender.lock().await.is_none());
+        assert!(matches!(rx.await, Ok(ConfirmDecision::AllowOnce)));
+    }
+
+    #[test]
+    fn denial_reasons_reach_the_model() {
+        let denied = |reason| {
+            denied_tool_result("bash", ToolKind::Execute, ToolOperation::Bash, "npm install".to_string(), reason)
+        };
+        let raw = denied(Some("use pnpm"));
+        let v: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!((v["success"].clone(), v["executed"].clone()), (json!(false), json!(false)));
+        assert_eq!(v["data"], json!({ "denied": true, "reason": "use pnpm" }));
+        assert!(v["stderr"].as_str().unwrap().contains("\"use pnpm\""));
+
+        let raw = denied(None);
+        let v: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(v["data"]["reason"], serde_json::Value::Null);
+        assert_eq!(v["response_summary"], "denied");
+    }
+
+    #[test]
+    fn shell_commands_ask_for_approval_by_what_they_do() {
+        let config = embedded_config();
+        let bash = ToolAdapter(BashTool::from_config(&config));
+        let safe = &config.tool_bash.safe_read_only_commands;
+        let asks = |mode: ApprovalMode, command: &str| {
+            let args = json!({ "command": command }).to_string();
+            call_requires_confirmation(&mode, None, &bash, "bash", &args, "*", safe)
+        };
+
+        assert!(!asks(ApprovalMode::ReadOnly, "ls -la | grep src"));
+        assert!(asks(ApprovalMode::ReadOnly, "touch notes.txt"));
+        assert!(!asks(ApprovalMode::Agent, "touch notes.txt"));
+        assert!(asks(ApprovalMode::Agent, "ls; rm -rf /"));
+        assert!(asks(ApprovalMode::AgentFull, "echo key >> ~/.ssh/authorized_keys"));
+        assert!(asks(ApprovalMode::AgentFull, "cd $HOME && rm -rf *"));
+        assert!(asks(ApprovalMode::ReadOnly, "npm install left-pad"));
+        assert!(asks(ApprovalMode::ReadOnly, "kill -9 -1"));
+
+        // Other tools still go by their kind
+        let write = ToolAdapter(WriteTool::new());
+        let args = json!({ "file_path": "notes.txt", "content": "x" }).to_string();
+        assert!(call_requires_confirmation(&ApprovalMode::ReadOnly, None, &write, "write", &args, "notes.txt", safe));
+        assert!(!call_requires_confirmation(&ApprovalMode::Agent, None, &write, "write", &args, "notes.txt", safe));
+    }
+
+    #[test]
+    fn skill_denials_name_the_skill_and_its_tools() {
+        let skill = Skill::parse("review", "---\nallowed-tools: view, grep\n---\nReview only.", SkillSource::User);
+        let raw =
+            skill_denied_tool_result("edit", ToolKind::Edit, ToolOperation::Edited, "src/a.rs".to_string(), &skill);
+        let v: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!((v["success"].clone(), v["executed"].clone()), (json!(false), json!(false)));
+        assert_eq!(v["data"], json!({ "denied": true, "skill": "review", "allowed_tools": ["view", "grep"] }));
+        assert!(v["stderr"].as_str().unwrap().ends_with("while it is active: view, grep"));
+    }
+}
@@ -0,0 +1,537 @@
+//! Events a turn sends the frontend: the streamed response, each tool call and
+//! what its result turned up, and the review the turn leaves when it ends.
+
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::config::AnswerFormatConfig;
+use crate::llm::agents::agent::{StreamEvent, StreamStage, Truncation};
+use crate::llm::models::provider_base::{Citation, StopDetails};
+use crate::llm::tools::tool_trait::ToolKind;
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::file_lock::FileConflict;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::prompt_guard::InjectionFinding;
+use crate::session::context::AgentMode;
+use crate::session::turn_journal::TurnJournal;
+use crate::session::turn_timing::TurnClock;
+use crate::session::types::{
+    CoreCitation, CoreDiffStats, CoreEvent, CoreEventType, CoreProposal, CoreStopDetails, CoreTurnFileDiff,
+    CoreWarning, CoreWarningItem,
+};
+use crate::session::{
+    emit_control_event, emit_stream_text, set_response_stage, ResponseStage, SessionToolOperation, SESSION_MANAGER,
+};
+
+use super::super::session_util::{format_answer, log_session_event, now_ms, truncate_utf8_with_ellipsis, with_proposals};
+use super::executor::ToolCall;
+
+/// The stream callback of a turn: the response goes to the frontend as it
+/// arrives, and what the model answers (not its thinking) to the turn journal
+pub(crate) fn stream_callback(
+    session_id: String,
+    clock: Arc<TurnClock>,
+    journal: Option<Arc<TurnJournal>>,
+    answer_config: AnswerFormatConfig,
+    root: PathBuf,
+) -> impl Fn(StreamEvent) + Send + Sync + 'static {
+    let answering = AtomicBool::new(false);
+    move |event: StreamEvent| match event {
+        StreamEvent::Text(text) => {
+            if !text.is_empty() {
+                clock.mark_token();
+                if let Some(journal) = journal.as_ref().filter(|_| answering.load(Ordering::SeqCst)) {
+                    journal.text(&text);
+                }
+                emit_stream_text(&session_id, text);
+            }
+        }
+        StreamEvent::StageStart(stage) => {
+            let (stage_str, stage_state) = match stage {
+                StreamStage::Thinking => ("__THINKING__", ResponseStage::Thinking),
+                StreamStage::Answering => ("__ANSWERING__", ResponseStage::Answering),
+            };
+            set_response_stage(&session_id, stage_state);
+            answering.store(matches!(stage, StreamStage::Answering), Ordering::SeqCst);
+            log_session_event(&session_id, "stage_changed", json!({ "stage": format!("{:?}", stage_state) }));
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::StageStart,
+                    stage: Some(stage_str.to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+        StreamEvent::StageEnd(stage) => {
+            let stage_str = match stage {
+                StreamStage::Thinking => "__THINKING__",
+                StreamStage::Answering => "__ANSWERING__",
+            };
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::StageEnd,
+                    stage: Some(stage_str.to_string()),
+                    ..Default::default()
+                },
+            );
+        }
+        StreamEvent::Resuming { attempt, continued } => {
+            log_session_event(
+                &session_id,
+                "stream_resuming",
+                json!({ "attempt": attempt, "continued": continued }),
+            );
+            if let Some(journal) = journal.as_ref().filter(|_| !continued) {
+                journal.restart();
+            }
+            let (code, message) = if continued {
+                ("stream_continued", "Connection to the model dropped; continuing the response")
+            } else {
+                ("stream_restarted", "Connection to the model dropped; restarting the response")
+            };
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::Resuming,
+                    display_text: Some(format!("{} (attempt {})", message, attempt)),
+                    warning: Some(CoreWarning {
+                        code: code.to_string(),
+                        message: message.to_string(),
+                        items: Vec::new(),
+                    }),
+                    ..Default::default()
+                },
+            );
+        }
+        StreamEvent::Blocked(details) => {
+            log_session_event(
+                &session_id,
+                "response_blocked",
+                json!({ "reason": details.reason, "prompt_blocked": details.prompt_blocked }),
+            );
+            let stop = core_stop_details(&details);
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::Blocked,
+                    display_text: Some(stop.message.clone()),
+                    stop_details: Some(stop),
+                    ..Default::default()
+                },
+            );
+        }
+        StreamEvent::RoundEnd(messages) => {
+            if let Some(journal) = &journal {
+                journal.round(messages);
+            }
+        }
+        StreamEvent::Citations(citations) => {
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::Citations,
+                    display_text: Some(format!("{} source(s) cited", citations.len())),
+                    citations: Some(core_citations(&citations)),
+                    ..Default::default()
+                },
+            );
+        }
+        StreamEvent::PartialResponse { chars, truncation } => {
+            log_session_event(
+                &session_id,
+                "partial_response",
+                json!({ "chars": chars, "code": truncation.code(), "reason": truncation.reason() }),
+            );
+            let display_text = match truncation {
+                Truncation::StreamFailed(_) => format!(
+                    "The model stopped responding; its answer so far ({} characters) was kept",
+                    chars
+                ),
+                Truncation::OutputLimit { max_chars } => format!(
+                    "The response passed the output limit of {} characters and was stopped",
+                    max_chars
+                ),
+            };
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::PartialResponse,
+                    display_text: Some(display_text),
+                    success: Some(false),
+                    error_message: Some(truncation.reason()),
+                    warning: Some(CoreWarning {
+                        code: truncation.code().to_string(),
+                        message: truncation.reason(),
+                        items: Vec::new(),
+                    }),
+                    ..Default::default()
+                },
+            );
+        }
+        StreamEvent::End(content) => {
+            set_response_stage(&session_id, ResponseStage::End);
+            let answer = answer_config.enabled.then(|| format_answer(&answer_config, &root, &content));
+            let (text, file_references) = match answer {
+                Some(answer) => (Some(answer.content), Some(answer.references).filter(|r| !r.is_empty())),
+                None => (None, None),
+            };
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::End,
+                    text,
+                    stage: Some("__END__".to_string()),
+                    file_references,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+}
+
+fn session_op_str(op: SessionToolOperation) -> &'static str {
+    match op {
+        SessionToolOperation::Explored => "__EXPLORED__",
+        SessionToolOperation::Edited => "__EDITED__",
+        SessionToolOperation::Todo => "__TODO__",
+        SessionToolOperation::Bash => "__BASH__",
+    }
+}
+
+pub(crate) fn emit_tool_start(call: &ToolCall, op: SessionToolOperation) {
+    emit_control_event(
+        &call.session_id,
+        CoreEvent {
+            session_id: call.session_id.clone(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::ToolStart,
+            tool_operation: Some(session_op_str(op).to_string()),
+            tool_name: Some(call.tool_name.clone()),
+            tool_display_name: Some(call.display_name.clone()),
+            key_path: Some(call.key_path.clone()),
+            kind: Some(format!("{:?}", call.tool.kind())),
+            args_summary: Some(call.args_summary.clone()),
+            tool_call_id: Some(call.id.clone()),
+            ..Default::default()
+        },
+    );
+}
+
+/// Emit the output and end of a finished call, with a warning first for a
+/// file conflict or a prompt injection its result reports
+pub(crate) fn emit_tool_finished(call: &ToolCall, op: SessionToolOperation, result: &anyhow::Result<String>) {
+    let status_for_log = if result.is_ok() { "ok" } else { "error" };
+    let response_summary_for_log = match result {
+        Ok(s) => truncate_utf8_with_ellipsis(s, 200),
+        Err(e) => truncate_utf8_with_ellipsis(&e.to_string(), 200),
+    };
+    let kind = call.tool.kind();
+    let is_todo_tool = matches!(kind, ToolKind::Todo);
+
+    let (response_summary, stdout, diff_stats) = match result {
+        Ok(raw) if is_todo_tool => (raw.clone(), None, None),
+        Ok(raw) => {
+            let v = serde_json::from_str::<serde_json::Value>(raw).ok();
+            let summary = v
+                .as_ref()
+                .and_then(|v| v.get("response_summary").and_then(|s| s.as_str()).map(|s| s.to_string()))
+                .unwrap_or_else(|| response_summary_for_log.clone());
+            let out = v
+                .as_ref()
+                .and_then(|v| v.get("stdout").and_then(|s| s.as_str()))
+                .map(|s| s.to_string());
+            let diff_stats = v.as_ref().and_then(core_diff_stats);
+            if let Some(conflict) = v.as_ref().and_then(file_conflict) {
+                emit_file_conflict(&call.session_id, &call.tool_name, &conflict);
+            }
+            let findings = v.as_ref().map(injection_findings).unwrap_or_default();
+            if !findings.is_empty() {
+                emit_injection_warning(&call.session_id, &call.tool_name, &call.key_path, &findings);
+            }
+            (summary, out, diff_stats)
+        }
+        Err(_) => (response_summary_for_log.clone(), None, None),
+    };
+
+    let display_text = if is_todo_tool {
+        None
+    } else {
+        let mut text = format!("{:?}({}) -> {}", kind, call.key_path, response_summary);
+        if matches!(kind, ToolKind::Edit) {
+            if let Some(diff) = stdout {
+                text.push('\n');
+                text.push_str(&diff);
+            }
+        }
+        Some(text)
+    };
+
+    emit_control_event(
+        &call.session_id,
+        CoreEvent {
+            session_id: call.session_id.clone(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::ToolOutput,
+            tool_operation: Some(session_op_str(op).to_string()),
+            tool_name: Some(call.tool_name.clone()),
+            tool_display_name: Some(call.display_name.clone()),
+            key_path: Some(call.key_path.clone()),
+            kind: Some(format!("{:?}", kind)),
+            args_summary: Some(call.args_summary.clone()),
+            response_summary: Some(response_summary.clone()),
+            display_text,
+            success: Some(result.is_ok()),
+            diff_stats,
+            tool_call_id: Some(call.id.clone()),
+            ..Default::default()
+        },
+    );
+
+    emit_control_event(
+        &call.session_id,
+        CoreEvent {
+            session_id: call.session_id.clone(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::ToolEnd,
+            tool_operation: Some(session_op_str(op).to_string()),
+            tool_name: Some(call.tool_name.clone()),
+            tool_display_name: Some(call.display_name.clone()),
+            key_path: Some(call.key_path.clone()),
+            response_summary: Some(response_summary),
+            success: Some(result.is_ok()),
+            timeout_ms: result.as_ref().ok().and_then(|raw| super::executor::result_timeout_ms(raw)),
+            tool_call_id: Some(call.id.clone()),
+            ..Default::default()
+        },
+    );
+
+    log_session_event(
+        &call.session_id,
+        "tool_finished",
+        json!({
+            "tool_name": call.tool_name.clone(),
+            "key_path": call.key_path.clone(),
+            "tool_operation": format!("{:?}", op),
+            "status": status_for_log,
+            "response_summary": response_summary_for_log
+        }),
+    );
+}
+
+fn file_conflict(tool_result: &serde_json::Value) -> Option<FileConflict> {
+    let conflict = tool_result.get("data")?.get("conflict")?;
+    serde_json::from_value(conflict.clone()).ok()
+}
+
+fn injection_findings(tool_result: &serde_json::Value) -> Vec<InjectionFinding> {
+    tool_result
+        .pointer("/data/injection")
+        .and_then(|f| serde_json::from_value(f.clone()).ok())
+        .unwrap_or_default()
+}
+
+fn emit_injection_warning(session_id: &str, tool_name: &str, key_path: &str, findings: &[InjectionFinding]) {
+    log_session_event(
+        session_id,
+        "prompt_injection_detected",
+        json!({ "tool_name": tool_name, "key_path": key_path, "findings": findings }),
+    );
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::Security,
+            tool_name: Some(tool_name.to_string()),
+            key_path: Some(key_path.to_string()),
+            warning: Some(CoreWarning {
+                code: "prompt_injection".to_string(),
+                message: format!("{} returned text that looks like instructions to the assistant", tool_name),
+                items: findings
+                    .iter()
+                    .map(|f| CoreWarningItem {
+                        subject: f.rule.clone(),
+                        reason: f.excerpt.clone(),
+                    })
+                    .collect(),
+            }),
+            ..Default::default()
+        },
+    );
+}
+
+fn emit_file_conflict(session_id: &str, tool_name: &str, conflict: &FileConflict) {
+    log_session_event(
+        session_id,
+        "file_conflict",
+        json!({ "path": conflict.path.clone(), "owner": conflict.owner.clone() }),
+    );
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::FileConflict,
+            tool_name: Some(tool_name.to_string()),
+            key_path: Some(conflict.path.clone()),
+            success: Some(false),
+            warning: Some(CoreWarning {
+                code: "file_conflict".to_string(),
+                message: format!("{} is locked by another editor", conflict.path),
+                items: vec![CoreWarningItem {
+                    subject: conflict.path.clone(),
+                    reason: format!("locked by {}", conflict.owner),
+                }],
+            }),
+            ..Default::default()
+        },
+    );
+}
+
+pub(crate) fn emit_tool_denied_by_skill(call: &ToolCall, skill: &str) {
+    log_session_event(
+        &call.session_id,
+        "tool_denied_by_skill",
+        json!({ "tool_name": call.tool_name, "skill": skill }),
+    );
+    emit_control_event(
+        &call.session_id,
+        CoreEvent {
+            session_id: call.session_id.clone(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::ToolDeniedBySkill,
+            tool_name: Some(call.tool_name.clone()),
+            tool_display_name: Some(call.display_name.clone()),
+            display_text: Some(format!("{} is not allowed by the skill {}", call.display_name, skill)),
+            success: Some(false),
+            tool_call_id: Some(call.id.clone()),
+            skill: Some(skill.to_string()),
+            ..Default::default()
+        },
+    );
+}
+
+/// Emit the pending proposals of the session, if it has any, as the turn ends
+pub(crate) fn emit_pending_proposals(session_id: &str) {
+    let pending: Vec<CoreProposal> =
+        with_proposals(session_id, |p| p.pending().map(|p| p.to_core()).collect()).unwrap_or_default();
+    if pending.is_empty() {
+        return;
+    }
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::Proposals,
+            display_text: Some(format!("{} proposed change(s) waiting for review", pending.len())),
+            proposals: Some(pending),
+            ..Default::default()
+        },
+    );
+}
+
+/// End the turn's checkpoint and, after a build-mode turn, emit the diffs of
+/// the changed files not yet reviewed, file by file
+pub(crate) fn emit_turn_diff(session_id: &str) {
+    let workspace = PathPolicy::for_session(session_id)
+        .map(|p| p.root().to_path_buf())
+        .unwrap_or_default();
+    let changes = checkpoint::end_turn(session_id, &workspace);
+    let build_mode = SESSION_MANAGER
+        .lock()
+        .ok()
+        .and_then(|manager| manager.get(session_id).map(|ctx| matches!(ctx.agent_mode, AgentMode::Build)))
+        .unwrap_or(false);
+    if changes.is_empty() || !build_mode {
+        return;
+    }
+    let turn_diff: Vec<CoreTurnFileDiff> = changes
+        .into_iter()
+        .map(|change| CoreTurnFileDiff {
+            path: change.diff_stats.file_path.clone(),
+            change: change.change.as_str().to_string(),
+            diff_stats: CoreDiffStats {
+                file_path: change.diff_stats.file_path.clone(),
+                additions: change.diff_stats.additions as u32,
+                removals: change.diff_stats.removals as u32,
+                hunks: change.diff_stats.hunks as u32,
+                label: change.diff_stats.label(),
+            },
+            diff: change.diff_stats.unified_diff,
+        })
+        .collect();
+    log_session_event(
+        session_id,
+        "turn_diff",
+        json!({ "files": turn_diff.iter().map(|f| f.path.clone()).collect::<Vec<_>>() }),
+    );
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::TurnDiffReady,
+            display_text: Some(format!("{} changed file(s) to review", turn_diff.len())),
+            turn_diff: Some(turn_diff),
+            ..Default::default()
+        },
+    );
+}
+
+pub(crate) fn core_stop_details(details: &StopDetails) -> CoreStopDetails {
+    CoreStopDetails {
+        reason: details.reason.clone(),
+        prompt_blocked: details.prompt_blocked,
+        categories: details.categories.clone(),
+        message: details.describe(),
+    }
+}
+
+pub(crate) fn core_citations(citations: &[Citation]) -> Vec<CoreCitation> {
+    citations
+        .iter()
+        .map(|c| CoreCitation {
+            uri: c.uri.clone(),
+            title: c.title.clone(),
+            license: c.license.clone(),
+            start_index: c.start_index,
+            end_index: c.end_index,
+        })
+        .collect()
+}
+
+/// Diff stats from an edit/write ToolResult, for the tool output event
+fn core_diff_stats(tool_result: &serde_json::Value) -> Option<CoreDiffStats> {
+    let data = tool_result.get("data")?;
+    let stats = data
+        .get("diff_stats")
+        .or_else(|| data.pointer("/metadata/diff_stats"))?;
+    let stats: DiffStats = serde_json::from_value(stats.clone()).ok()?;
+    Some(CoreDiffStats {
+        file_path: stats.file_path.clone(),
+        additions: stats.additions as u32,
+        removals: stats.removals as u32,
+        hunks: stats.hunks as u32,
+        label: stats.label(),
+    })
+}
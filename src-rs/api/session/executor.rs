@@ -0,0 +1,418 @@
+//! The tool executor of a turn. Each call the model makes is checked against
+//! the active skills, propose mode and the approval mode before it runs with
+//! the session's access level, and can be cancelled on its own.
+
+use anyhow::anyhow;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tokio::time::Duration;
+
+use crate::config::{AppConfig, ToolTimeoutsConfig};
+use crate::llm::agents::agent::ToolExecutorCallback;
+use crate::llm::agents::cancel::CancelToken;
+use crate::llm::tools::bash::cancel_running_command;
+use crate::llm::tools::tool_trait::{Tool, ToolKind, ToolOperation as CoreToolOperation, ToolOutput, ToolResult};
+use crate::llm::utils::tool_access::{with_tool_session, ToolAccessLevel, ToolScope};
+use crate::session::context::{ApprovalMode, RunningToolCall};
+use crate::session::skills;
+use crate::session::turn_timing::TurnClock;
+use crate::session::{
+    approval_policy, generate_request_id, get_confirmation_status, set_confirmation_status, set_tool_operation,
+    tool_key_path, ConfirmDecision, ConfirmationStatus, SessionToolOperation, SESSION_MANAGER,
+};
+
+use super::super::session_util::{log_session_event, truncate_utf8_with_ellipsis, with_tool_stats};
+use super::confirmation::{self, PendingConfirmation};
+use super::events;
+
+/// A tool call in progress, as its events describe it
+pub(crate) struct ToolCall {
+    pub(crate) session_id: String,
+    /// The `tool_call_id` of its events and the request id of its confirmation
+    pub(crate) id: String,
+    pub(crate) tool: Box<dyn Tool>,
+    pub(crate) tool_name: String,
+    pub(crate) display_name: String,
+    pub(crate) args: String,
+    pub(crate) args_summary: String,
+    pub(crate) key_path: String,
+}
+
+/// What the tool calls of one turn share
+struct TurnTools {
+    confirmation_sender: Arc<Mutex<Option<PendingConfirmation>>>,
+    tool_timeouts: ToolTimeoutsConfig,
+    safe_commands: Vec<String>,
+    clock: Arc<TurnClock>,
+}
+
+/// The executor the agent runs the turn's tool calls through
+pub(crate) fn tool_executor(
+    session_id: &str,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    config: &AppConfig,
+    clock: Arc<TurnClock>,
+) -> ToolExecutorCallback {
+    let session_id = session_id.to_string();
+    let turn = Arc::new(TurnTools {
+        confirmation_sender: Arc::clone(confirmation_sender),
+        tool_timeouts: config.tool_timeouts.clone(),
+        safe_commands: config.tool_bash.safe_read_only_commands.clone(),
+        clock,
+    });
+    Arc::new(move |tool: &dyn Tool, tool_name: &str, args: &str| {
+        let call = ToolCall {
+            session_id: session_id.clone(),
+            id: generate_request_id(),
+            tool: tool.clone_box(),
+            tool_name: tool_name.to_string(),
+            display_name: tool.display_name(),
+            args: args.to_string(),
+            args_summary: tool.summarize_args(args).unwrap_or_else(|| truncate_utf8_with_ellipsis(args, 200)),
+            key_path: tool_key_path(Some(tool), tool_name, args),
+        };
+        Box::pin(execute_call(Arc::clone(&turn), call))
+    })
+}
+
+/// Run one call from its start event to its end event, or until the user cancels it
+async fn execute_call(turn: Arc<TurnTools>, call: ToolCall) -> anyhow::Result<String> {
+    let executor_started = Instant::now();
+    let call_cancel = CancelToken::new();
+    set_running_tool(
+        &call.session_id,
+        Some(RunningToolCall { request_id: call.id.clone(), cancel: call_cancel.clone() }),
+    );
+
+    let op = map_tool_operation(call.tool.operation());
+    set_tool_operation(&call.session_id, Some(op));
+    log_session_event(
+        &call.session_id,
+        "tool_executor_op_set",
+        json!({
+            "tool_name": call.tool_name.clone(),
+            "key_path": call.key_path.clone(),
+            "tool_operation": format!("{:?}", op),
+            "args_summary": call.args_summary.clone()
+        }),
+    );
+    events::emit_tool_start(&call, op);
+
+    let outcome = tokio::select! {
+        biased;
+        _ = call_cancel.cancelled() => None,
+        result = run_call(&turn, &call) => Some(result),
+    };
+    set_running_tool(&call.session_id, None);
+    let result = match outcome {
+        Some(result) => result,
+        None => Ok(abandon_call(&turn, &call).await),
+    };
+
+    events::emit_tool_finished(&call, op, &result);
+    with_tool_stats(&call.session_id, |stats| {
+        stats.record_call(&call.tool_name, tool_call_succeeded(&result))
+    });
+    turn.clock.add_executor_time(executor_started.elapsed());
+    set_tool_operation(&call.session_id, None);
+    log_session_event(
+        &call.session_id,
+        "tool_executor_op_cleared",
+        json!({ "tool_name": call.tool_name.clone(), "key_path": call.key_path.clone() }),
+    );
+    result
+}
+
+/// Run a call as the session allows: straight away, as a proposal, or once
+/// the user approves it
+async fn run_call(turn: &TurnTools, call: &ToolCall) -> anyhow::Result<String> {
+    let session_id = call.session_id.as_str();
+    let tool = call.tool.as_ref();
+    let tool_name = call.tool_name.as_str();
+
+    // Questions need no approval and run in every mode, propose mode included
+    if tool_name == "ask_user" {
+        return confirmation::ask_user(session_id, tool, &call.args).await;
+    }
+
+    // Skills limit the tools in every approval mode, before anything asks the user
+    let denying_skill = SESSION_MANAGER.lock().ok().and_then(|m| {
+        let ctx = m.get(session_id)?;
+        skills::check_tool_permission(&ctx.active_skills, tool_name, Some(&call.args)).err().cloned()
+    });
+    if let Some(skill) = denying_skill {
+        events::emit_tool_denied_by_skill(call, &skill.name);
+        let (kind, op) = (tool.kind(), tool.operation());
+        return Ok(confirmation::skill_denied_tool_result(tool_name, kind, op, call.key_path.clone(), &skill));
+    }
+
+    let (approval_mode, auto_accept, proposing) = SESSION_MANAGER
+        .lock()
+        .ok()
+        .and_then(|m| {
+            m.get(session_id)
+                .map(|ctx| (ctx.approval_mode.clone(), ctx.auto_accept.clone(), ctx.proposals.enabled))
+        })
+        .unwrap_or_default();
+    let kind = tool.kind();
+    let tool_timeout = turn.tool_timeouts.for_kind(kind.as_str());
+    let access_level = if matches!(approval_mode, ApprovalMode::AgentFull) {
+        ToolAccessLevel::Full
+    } else {
+        ToolAccessLevel::Workspace
+    };
+
+    // These tools gate risky calls themselves; once the call reaches this
+    // point the session-level confirmation below is authoritative.
+    let mut effective_args = call.args.clone();
+    if matches!(
+        tool_name,
+        "bash" | "move" | "delete" | "rename_symbol" | "code_action" | "view" | "grep" | "fetch"
+    ) {
+        if let Ok(mut v) = serde_json::from_str::<serde_json::Value>(&effective_args) {
+            if let Some(obj) = v.as_object_mut() {
+                obj.insert("confirmed".to_string(), serde_json::Value::Bool(true));
+                effective_args = serde_json::to_string(&v).unwrap_or_else(|_| call.args.clone());
+            }
+        }
+    }
+    let run = || run_tool_in_session(session_id, access_level, tool, &effective_args, tool_timeout);
+
+    if proposing && approval_policy::is_proposable(kind) {
+        return confirmation::propose_call(
+            session_id,
+            access_level,
+            tool,
+            &call.args,
+            &effective_args,
+            &call.args_summary,
+            tool_timeout,
+        )
+        .await;
+    }
+
+    let requires_user_confirmation = with_tool_session(session_id, || {
+        confirmation::call_requires_confirmation(
+            &approval_mode,
+            auto_accept.as_deref(),
+            tool,
+            tool_name,
+            &call.args,
+            &call.key_path,
+            &turn.safe_commands,
+        )
+    });
+    if !requires_user_confirmation
+        || get_confirmation_status(session_id, tool_name, &call.key_path) == Some(ConfirmationStatus::AllowForSession)
+    {
+        return run().await;
+    }
+
+    let preview = if approval_policy::previews_before_confirmation(tool_name) {
+        let raw = run_tool_in_session(session_id, access_level, tool, &call.args, tool_timeout).await?;
+        match confirmation::confirmation_preview(&raw) {
+            Some(preview) => Some(preview),
+            // Failed or nothing to apply: report to the model without asking
+            None => return Ok(raw),
+        }
+    } else {
+        None
+    };
+
+    let reason = match confirmation::request_confirmation(&turn.confirmation_sender, call, preview).await {
+        Ok(ConfirmDecision::AllowOnce) => return run().await,
+        Ok(ConfirmDecision::AllowForSession) => {
+            set_confirmation_status(session_id, tool_name, &call.key_path, ConfirmationStatus::AllowForSession);
+            log_session_event(
+                session_id,
+                "confirm_allow_for_session_set",
+                json!({ "tool_name": tool_name, "key_path": call.key_path.clone() }),
+            );
+            return run().await;
+        }
+        Ok(ConfirmDecision::Deny) => None,
+        Ok(ConfirmDecision::DenyWithMessage { text }) => Some(text),
+        Err(_) => {
+            return Ok(serde_json::to_string(&ToolOutput::error(
+                format!("tool call {} {}", tool_name, call.args),
+                "Confirmation channel closed.",
+            ))
+            .unwrap());
+        }
+    };
+    Ok(confirmation::denied_tool_result(tool_name, kind, tool.operation(), call.key_path.clone(), reason.as_deref()))
+}
+
+/// Stop a call the user cancelled and drop whatever it was waiting on from
+/// them; returns the result the model gets instead
+async fn abandon_call(turn: &TurnTools, call: &ToolCall) -> String {
+    let kind = call.tool.kind();
+    if matches!(kind, ToolKind::Execute) {
+        cancel_running_command(&call.session_id);
+    }
+    {
+        let mut sender_guard = turn.confirmation_sender.lock().await;
+        if sender_guard.as_ref().is_some_and(|p| p.request_id == call.id) {
+            *sender_guard = None;
+        }
+    }
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(&call.session_id) {
+            ctx.pending_question = None;
+        }
+    }
+    log_session_event(
+        &call.session_id,
+        "tool_cancelled",
+        json!({ "tool_name": call.tool_name.clone(), "key_path": call.key_path.clone() }),
+    );
+    cancelled_tool_result(&call.tool_name, kind, call.tool.operation(), call.key_path.clone())
+}
+
+fn map_tool_operation(op: CoreToolOperation) -> SessionToolOperation {
+    match op {
+        CoreToolOperation::Bash => SessionToolOperation::Bash,
+        CoreToolOperation::Explored => SessionToolOperation::Explored,
+        CoreToolOperation::Edited => SessionToolOperation::Edited,
+        CoreToolOperation::Todo => SessionToolOperation::Todo,
+        CoreToolOperation::Other => SessionToolOperation::Explored,
+    }
+}
+
+/// Execute a tool call with the session's access level and id in scope: async
+/// tools on the runtime, sync ones on the blocking pool. A call still running
+/// after `timeout` is abandoned and reported as a timed-out result.
+pub(crate) async fn run_tool_in_session(
+    session_id: &str,
+    access_level: ToolAccessLevel,
+    tool: &dyn Tool,
+    args: &str,
+    timeout: Option<Duration>,
+) -> anyhow::Result<String> {
+    let (tool_name, kind, op) = (tool.name().to_string(), tool.kind(), tool.operation());
+    let task = {
+        let scope = ToolScope {
+            access: access_level,
+            session_id: Some(session_id.to_string()),
+        };
+        tokio::spawn(scope.run(tool.execute_async(args)))
+    };
+    let started = Instant::now();
+    let joined = match timeout {
+        Some(limit) => match tokio::time::timeout(limit, task).await {
+            Ok(joined) => joined,
+            Err(_) => {
+                with_tool_stats(session_id, |stats| stats.record_time(&tool_name, started.elapsed()));
+                log::warn!("Tool '{}' timed out after {:?}", tool_name, limit);
+                if matches!(kind, ToolKind::Execute) {
+                    cancel_running_command(session_id);
+                }
+                let key_path = tool_key_path(Some(tool), &tool_name, args);
+                return Ok(timed_out_tool_result(&tool_name, kind, op, key_path, limit));
+            }
+        },
+        None => task.await,
+    };
+    with_tool_stats(session_id, |stats| stats.record_time(&tool_name, started.elapsed()));
+    joined.map_err(|e| anyhow!("Tool '{}' panicked: {}", tool_name, e))?
+}
+
+pub(crate) fn set_running_tool(session_id: &str, call: Option<RunningToolCall>) {
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(session_id) {
+            ctx.running_tool = call;
+        }
+    }
+}
+
+/// Whether a tool call did what was asked: its ToolResult says so, or it
+/// produced output without an error
+pub(crate) fn tool_call_succeeded(result: &anyhow::Result<String>) -> bool {
+    let Ok(raw) = result else {
+        return false;
+    };
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(raw) else {
+        return true;
+    };
+    if let Some(success) = v.get("success").and_then(|s| s.as_bool()) {
+        return success;
+    }
+    let is_empty = |field: &str| v.get(field).and_then(|s| s.as_str()).is_none_or(str::is_empty);
+    is_empty("stderr") || !is_empty("stdout")
+}
+
+fn timed_out_tool_result(
+    tool_name: &str,
+    kind: ToolKind,
+    op: CoreToolOperation,
+    key_path: String,
+    limit: Duration,
+) -> String {
+    let timeout_ms = limit.as_millis().min(u32::MAX as u128) as u32;
+    let mut result = ToolResult::err(
+        tool_name,
+        kind,
+        op,
+        format!(
+            "Timed out after {}s; the call was abandoned and its effects, if any, are unknown. \
+             Try a narrower request or another approach.",
+            limit.as_secs()
+        ),
+        json!({ "timed_out": true, "timeout_ms": timeout_ms }),
+    )
+    .with_summary("timed out");
+    result.key_path = key_path;
+    serde_json::to_string_pretty(&result).unwrap_or_default()
+}
+
+pub(crate) fn cancelled_tool_result(tool_name: &str, kind: ToolKind, op: CoreToolOperation, key_path: String) -> String {
+    let mut result = ToolResult::err(
+        tool_name,
+        kind,
+        op,
+        "Cancelled by the user; its effects, if any, are unknown. The rest of the turn continues, so \
+         go on without this call or try another approach.",
+        json!({ "cancelled": true }),
+    )
+    .with_summary("cancelled");
+    result.key_path = key_path;
+    serde_json::to_string_pretty(&result).unwrap_or_default()
+}
+
+/// The limit recorded in a timed-out tool result
+pub(crate) fn result_timeout_ms(raw: &str) -> Option<u32> {
+    let v: serde_json::Value = serde_json::from_str(raw).ok()?;
+    if v.pointer("/data/timed_out").and_then(|t| t.as_bool()) != Some(true) {
+        return None;
+    }
+    v.pointer("/data/timeout_ms").and_then(|t| t.as_u64()).map(|t| t as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::tools::tool_trait::ToolOperation;
+
+    #[test]
+    fn tool_timeouts_come_from_config_and_mark_the_result() {
+        let cfg: AppConfig =
+            toml::from_str(include_str!("../../../Config.toml")).expect("embedded Config.toml should parse");
+        assert_eq!(cfg.tool_timeouts.for_kind("read"), Some(Duration::from_secs(300)));
+        assert_eq!(cfg.tool_timeouts.for_kind("execute"), None);
+
+        let raw = timed_out_tool_result(
+            "fetch",
+            ToolKind::Fetch,
+            ToolOperation::Other,
+            "https://example.com".to_string(),
+            Duration::from_secs(120),
+        );
+        let v: serde_json::Value = serde_json::from_str(&raw).unwrap();
+        assert_eq!(v["success"], false);
+        assert_eq!(v["response_summary"], "timed out");
+        assert_eq!(result_timeout_ms(&raw), Some(120_000));
+        assert_eq!(result_timeout_ms(r#"{"success":true,"data":{}}"#), None);
+    }
+}
@@ -0,0 +1,255 @@
+pub mod confirmation;
+pub mod events;
+pub mod executor;
+
+use anyhow::{bail, Result};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::llm::agents::agent::Agent as RustAgent;
+use crate::session::events::SessionEventSink;
+use crate::session::idempotency;
+use crate::session::types::{CoreCitation, CoreConfirmDecision, CoreStopDetails};
+use crate::session::{clear_event_sink, flush_events, set_event_sink};
+
+use super::session_util::{
+    self, AutoModeOptions, AutoRunResult, AvailableModel, InterruptedTurnInfo, LatencyInfo, PlanRunResult,
+    ProviderMessage, ToolChoiceSettings,
+};
+use confirmation::PendingConfirmation;
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Clone)]
+pub struct AgentResult {
+    pub content: String,
+    pub tools_used: bool,
+    pub cancelled: bool,
+    /// Set when the provider blocked the prompt or cut the response short
+    pub stop_details: Option<CoreStopDetails>,
+    /// The provider stream failed and `content` is the partial answer it had produced
+    pub truncated: bool,
+    pub citations: Vec<CoreCitation>,
+}
+
+/// An open session: the agent, its history and its pending confirmation
+pub struct Session {
+    inner: Arc<Mutex<RustAgent>>,
+    confirmation_sender: Arc<Mutex<Option<PendingConfirmation>>>,
+    /// What the core knows the session by, see `session_key`
+    key: String,
+    session_id: String,
+    namespace: String,
+}
+
+impl Session {
+    /// Open a session owned by `namespace` (`DEFAULT_NAMESPACE` for a single
+    /// user), restoring its saved history, or reuse it if already open. Its
+    /// snapshots are stored under `sessions/<namespace>/` in the data directory;
+    /// the same id in another namespace is another session.
+    pub fn open(namespace: String, session_id: String) -> Result<Self> {
+        let parts = session_util::open_session(namespace.clone(), session_id.clone())?;
+        Ok(Self {
+            inner: parts.inner,
+            confirmation_sender: Arc::new(Mutex::new(None)),
+            key: parts.session_id,
+            session_id,
+            namespace,
+        })
+    }
+
+    /// The id the session was opened with, as the other session APIs take it
+    /// next to `namespace()`
+    pub fn id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    /// Answer a `ConfirmationRequested` event
+    pub async fn confirm_tool(&self, decision: CoreConfirmDecision) -> Result<()> {
+        self.confirm_tool_with_key(decision, None).await
+    }
+
+    /// `confirm_tool`, ignoring a repeat of the call with the same key
+    pub async fn confirm_tool_with_key(
+        &self,
+        decision: CoreConfirmDecision,
+        idempotency_key: Option<String>,
+    ) -> Result<()> {
+        idempotency::run_once(&self.key, idempotency_key.as_deref(), || {
+            confirmation::confirm_tool(&self.key, &self.confirmation_sender, decision)
+        })
+        .await
+    }
+
+    /// Deliver the session's events to `sink`, replacing any previous one
+    pub fn subscribe(&self, sink: SessionEventSink) -> Result<()> {
+        if !set_event_sink(&self.key, sink) {
+            bail!("Session not found");
+        }
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self) {
+        clear_event_sink(&self.key);
+    }
+
+    /// Wait until the events emitted so far have been delivered to the subscriber
+    pub async fn flush_events(&self) {
+        flush_events(&self.key).await;
+    }
+
+    pub async fn execute(&self, prompt: String) -> Result<AgentResult> {
+        self.execute_with_key(prompt, None).await
+    }
+
+    /// `execute`, returning the first call's result to a repeat with the same
+    /// key instead of running the turn again
+    pub async fn execute_with_key(&self, prompt: String, idempotency_key: Option<String>) -> Result<AgentResult> {
+        idempotency::run_once(&self.key, idempotency_key.as_deref(), || async {
+            let result =
+                session_util::execute_session(&self.key, &self.inner, &self.confirmation_sender, prompt).await?;
+            Ok(AgentResult {
+                content: result.content,
+                tools_used: result.tools_used,
+                cancelled: result.cancelled,
+                stop_details: result.stop_details.as_ref().map(events::core_stop_details),
+                truncated: result.truncated,
+                citations: events::core_citations(&result.citations),
+            })
+        })
+        .await
+    }
+
+    /// Run a prompt autonomously within a wall-clock budget
+    pub async fn execute_auto(&self, prompt: String, options: AutoModeOptions) -> Result<AutoRunResult> {
+        session_util::execute_auto(&self.key, &self.inner, &self.confirmation_sender, prompt, options).await
+    }
+
+    /// Steer a running autonomous turn at its next checkpoint
+    pub fn steer(&self, message: String) -> Result<bool> {
+        session_util::steer_session(&self.key, message)
+    }
+
+    /// Plan, ask for approval once, then execute the plan step by step
+    pub async fn execute_plan_then_build(&self, prompt: String) -> Result<PlanRunResult> {
+        session_util::execute_plan_then_build(&self.key, &self.inner, &self.confirmation_sender, prompt).await
+    }
+
+    /// Continue a plan paused on a failed or cancelled step
+    pub async fn resume_plan(&self) -> Result<PlanRunResult> {
+        session_util::resume_plan(&self.key, &self.inner, &self.confirmation_sender).await
+    }
+
+    /// The turn the process stopped in the middle of, if the session had one when opened
+    pub fn get_interrupted_turn(&self) -> Result<Option<InterruptedTurnInfo>> {
+        session_util::get_interrupted_turn(&self.key)
+    }
+
+    /// Restore what the interrupted turn did and let the model finish it
+    pub async fn resume_interrupted_turn(&self) -> Result<AgentResult> {
+        let result =
+            session_util::resume_interrupted_turn(&self.key, &self.inner, &self.confirmation_sender).await?;
+        Ok(AgentResult {
+            content: result.content,
+            tools_used: result.tools_used,
+            cancelled: result.cancelled,
+            stop_details: result.stop_details.as_ref().map(events::core_stop_details),
+            truncated: result.truncated,
+            citations: events::core_citations(&result.citations),
+        })
+    }
+
+    /// Drop the interrupted turn; false if there was none
+    pub fn discard_interrupted_turn(&self) -> Result<bool> {
+        session_util::discard_interrupted_turn(&self.key)
+    }
+
+    /// Cancel the running turn, including a running bash command
+    pub async fn cancel(&self) -> Result<bool> {
+        session_util::cancel_session(&self.key, &self.confirmation_sender).await
+    }
+
+    /// Close the session and stop its MCP servers; false if it was already closed
+    pub fn close(&self) -> Result<bool> {
+        session_util::close_session(&self.namespace, &self.session_id)
+    }
+
+    pub async fn clear_history(&self) -> Result<()> {
+        session_util::clear_history(&self.key, &self.inner).await
+    }
+
+    pub async fn get_history(&self) -> Result<Vec<ProviderMessage>> {
+        session_util::get_history(&self.inner).await
+    }
+
+    pub async fn get_available_models(&self) -> Result<Vec<AvailableModel>> {
+        session_util::get_available_models(&self.inner).await
+    }
+
+    pub async fn set_model(&self, provider: String, model: String) -> Result<()> {
+        session_util::set_model(&self.inner, provider, model).await
+    }
+
+    /// Which tool, if any, the model must call; see `ToolChoiceSettings`
+    pub async fn set_tool_choice(&self, settings: ToolChoiceSettings) -> Result<()> {
+        session_util::set_tool_choice(&self.inner, settings).await
+    }
+
+    pub async fn get_tool_choice(&self) -> ToolChoiceSettings {
+        session_util::get_tool_choice(&self.inner).await
+    }
+
+    pub async fn check_latency(&self) -> Result<LatencyInfo> {
+        session_util::check_latency(&self.inner).await
+    }
+
+    /// Short title for the conversation, from the auxiliary model
+    pub async fn generate_title(&self) -> Result<String> {
+        session_util::generate_title(&self.inner).await
+    }
+
+    /// Summary of the conversation so far, from the auxiliary model
+    pub async fn summarize_history(&self) -> Result<String> {
+        session_util::summarize_history(&self.inner).await
+    }
+
+    /// Fold the history into a summary, keeping pinned messages verbatim; returns
+    /// how many messages were summarized away
+    pub async fn compact_history(&self) -> Result<u32> {
+        session_util::compact_history(&self.key, &self.inner)
+            .await
+            .map(|n| n as u32)
+    }
+
+    /// Commit message for the workspace's uncommitted changes, from the auxiliary model
+    pub async fn generate_commit_message(&self) -> Result<String> {
+        session_util::generate_commit_message(&self.inner).await
+    }
+
+    pub fn get_agent_mode(&self) -> Result<String> {
+        session_util::get_agent_mode(&self.key)
+    }
+
+    pub async fn set_agent_mode(&self, mode: String) -> Result<()> {
+        session_util::set_agent_mode(&self.key, &self.inner, mode).await
+    }
+
+    pub fn get_approval_mode(&self) -> Result<String> {
+        session_util::get_approval_mode(&self.key)
+    }
+
+    pub fn set_approval_mode(&self, mode: String) -> Result<()> {
+        session_util::set_approval_mode(&self.key, mode)
+    }
+
+    pub fn get_execution_target(&self) -> Result<String> {
+        session_util::get_execution_target(&self.key)
+    }
+
+    pub fn set_execution_target(&self, target: String) -> Result<()> {
+        session_util::set_execution_target(&self.key, target)
+    }
+}
@@ -0,0 +1,3923 @@
+use anyhow::{anyhow, bail, Context, Result};
+
+use crate::config::{
+    workspace_key, AnswerFormatConfig, AppConfig, ProviderConfig, RuntimeSessionConfig, WorkspaceMismatchMode,
+    TRUST_LEVEL_TRUSTED, TRUST_LEVEL_UNTRUSTED,
+};
+use crate::health::{self, Subsystem};
+use crate::notifier;
+use crate::preflight;
+use crate::telemetry;
+use crate::session::context::{AgentMode, ApprovalMode, SessionContext};
+use crate::llm::agents::agent::Agent as RustAgent;
+use crate::llm::auxiliary::{self, AuxTask};
+use crate::llm::backend::{self, sync};
+use crate::llm::agents::agent::AgentResult as RustAgentResult;
+use crate::llm::agents::agent::{
+    CheckpointCallback, CheckpointDecision, ToolExecutionResult,
+};
+use crate::llm::mcps::{load_mcp_tools, process as mcp_process};
+use crate::llm::models::provider_error::{self, ProviderErrorKind};
+use crate::llm::models::provider_base::{ToolChoice, ToolUseOptions};
+use crate::llm::models::provider_handle::Message;
+use crate::llm::tools::bash::{self, cancel_running_command, shell_alive, shell_state, ShellState};
+use crate::llm::tools::list_available_tools;
+use crate::llm::utils::artifacts;
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::file_lock;
+use crate::llm::utils::file_tracker::PathSecurity;
+use crate::llm::utils::mentions::resolve_mentions;
+use crate::llm::utils::network::measure_latency;
+use crate::llm::utils::path_policy::{self, PathPolicy};
+use crate::llm::utils::sensitive_files;
+use crate::llm::utils::session_vars::{self, SessionVars};
+use crate::llm::tools::tool_trait::Tool;
+use crate::llm::utils::overlay::Overlay;
+use crate::llm::utils::trash::{Trash, TrashEntry};
+use crate::llm::utils::tool_access::{with_tool_access, with_tool_session, ToolAccessLevel};
+use crate::session::answer_format;
+use crate::session::auto_accept::AutoAcceptScope;
+use crate::session::context_header;
+use crate::session::context_providers;
+use crate::session::environment_probe;
+use crate::session::event_log;
+use crate::session::eviction::{self, EvictedSession};
+use crate::session::idempotency;
+use crate::session::skills::{self, Skill};
+use crate::session::snapshot_migration::NewerSnapshotError;
+use crate::session::command::{parse_slash_command, SlashCommand, SLASH_COMMAND_HELP};
+use crate::session::auto_mode::{AutoRun, AutoRunConfig, CheckpointStatus};
+use crate::session::plan::{parse_plan_steps, PlanRun, PLAN_FORMAT_INSTRUCTIONS};
+use crate::session::proposals::Proposals;
+use crate::session::{
+    emit_control_event,
+    generate_request_id,
+    generate_session_id,
+    ResponseStage,
+    SessionToolOperation,
+    crypto,
+    snapshot_writer,
+    store,
+    SESSION_MANAGER,
+};
+use crate::session::manager::{session_key, split_session_key};
+use crate::session::types::{
+    CoreConfirmationRequest,
+    CoreEvent,
+    CoreError,
+    CoreErrorCode,
+    CoreErrorSuggestion,
+    CoreEventFilter,
+    CoreEventType,
+    CorePlanStep,
+    CoreProposal,
+    CoreToolStat,
+    CoreTurnTiming,
+    CoreWarning,
+    CoreWarningItem,
+    CORE_EVENT_PROTOCOL_VERSION,
+};
+use crate::session::output_limit::OutputLimit;
+use crate::session::tool_stats::ToolStats;
+use crate::session::turn_journal::{self, InterruptedTurn, TurnJournal};
+use crate::session::turn_timing::{self, TurnClock, TurnTiming};
+
+use super::session::confirmation::PendingConfirmation;
+use super::session::events::{self, emit_pending_proposals, emit_turn_diff};
+use super::session::executor::{self, run_tool_in_session, tool_call_succeeded};
+
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Once};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::time::{sleep, Duration};
+
+pub(crate) struct SessionOpenParts {
+    pub(crate) inner: Arc<Mutex<RustAgent>>,
+    /// Its key, see `session_key`
+    pub(crate) session_id: String,
+}
+
+pub(crate) fn truncate_utf8_with_ellipsis(s: &str, max_bytes: usize) -> String {
+    let s = s.trim();
+    if s.len() <= max_bytes {
+        return s.to_string();
+    }
+
+    let mut end = 0usize;
+    for (i, ch) in s.char_indices() {
+        let next = i + ch.len_utf8();
+        if next <= max_bytes {
+            end = next;
+        } else {
+            break;
+        }
+    }
+
+    if end == 0 {
+        let first_end = s.chars().next().map(|c| c.len_utf8()).unwrap_or(0);
+        end = std::cmp::min(first_end, s.len());
+    }
+
+    format!("{}...", &s[..end])
+}
+
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+fn session_snapshot(session_id: &str) -> serde_json::Value {
+    if let Ok(manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get(session_id) {
+            let stage = ctx
+                .response_stage
+                .lock()
+                .ok()
+                .map(|v| *v)
+                .map(|v| format!("{:?}", v))
+                .unwrap_or_else(|| "Unknown".to_string());
+            let tool_operation = ctx
+                .tool_operation
+                .lock()
+                .ok()
+                .and_then(|v| *v)
+                .map(|v| format!("{:?}", v));
+            let tool_confirm_len = ctx.tool_confirm.lock().ok().map(|m| m.len()).unwrap_or(0);
+
+            return json!({
+                "created_at": ctx.created_at,
+                "updated_at": ctx.updated_at,
+                "evicted": ctx.evicted.is_some(),
+                "response_stage": stage,
+                "tool_operation": tool_operation,
+                "tool_confirm_len": tool_confirm_len
+            });
+        }
+    }
+
+    json!({})
+}
+
+pub(crate) fn log_session_event(session_id: &str, event: &str, extra: serde_json::Value) {
+    let payload = json!({
+        "ts": now_ms(),
+        "event": event,
+        "session_id": session_id,
+        "session": session_snapshot(session_id),
+        "extra": extra
+    });
+    log::info!(target: "carrycode_session", "{}", payload);
+}
+
+fn system_prompt_for_agent_mode(config: &AppConfig, agent_mode: &AgentMode) -> Option<String> {
+    match agent_mode {
+        AgentMode::Plan => config
+            .prompt_plan
+            .clone()
+            .and_then(|p| if p.enabled { Some(p.prompt_template) } else { None })
+            .or_else(|| Some("You are a planning assistant.".to_string())),
+        AgentMode::Build => config
+            .prompt_build
+            .clone()
+            .and_then(|p| if p.enabled { Some(p.prompt_template) } else { None })
+            .or_else(|| Some("You are a helpful coding assistant.".to_string())),
+    }
+}
+
+/// The mode's system prompt and the instructions of the active skills, with
+/// the session's variables filled in, followed by the session's probed capabilities
+fn system_prompt_for_session(
+    config: &AppConfig,
+    agent_mode: &AgentMode,
+    environment: Option<&str>,
+    vars: &SessionVars,
+    active_skills: &[Skill],
+) -> Option<String> {
+    let skills = skills::prompt_section(active_skills);
+    let prompt = system_prompt_for_agent_mode(config, agent_mode).map(|p| match skills {
+        Some(skills) => session_vars::interpolate(&format!("{}\n\n{}", p, skills), vars),
+        None => session_vars::interpolate(&p, vars),
+    });
+    prompt.map(|prompt| match environment {
+        Some(environment) => format!("{}\n\n{}", prompt, environment),
+        None => prompt,
+    })
+}
+
+/// The saved session, if any. A damaged snapshot starts the session afresh, but
+/// one that cannot be decrypted or was saved by a newer version is an error so
+/// it is not overwritten.
+fn load_persisted_snapshot(namespace: &str, session_id: &str) -> Result<Option<store::SessionSnapshot>> {
+    snapshot_writer::flush();
+    match store::load_snapshot(namespace, session_id) {
+        Ok(snapshot) => Ok(snapshot),
+        Err(e) if e.is::<crypto::DecryptError>() || e.is::<NewerSnapshotError>() => {
+            Err(e.context("Failed to restore the saved session"))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// The workspace a saved session was saved in, when it is not `workspace` and
+/// `mode` asks for a warning; an error when `mode` refuses to open it
+fn check_saved_workspace(
+    session_id: &str,
+    saved: Option<&str>,
+    workspace: &str,
+    mode: WorkspaceMismatchMode,
+) -> Result<Option<String>> {
+    let Some(saved) = saved.filter(|saved| *saved != workspace) else {
+        return Ok(None);
+    };
+    log_session_event(session_id, "workspace_mismatch", json!({ "saved": saved, "current": workspace }));
+    match mode {
+        WorkspaceMismatchMode::Ignore => Ok(None),
+        WorkspaceMismatchMode::Warn => Ok(Some(saved.to_string())),
+        WorkspaceMismatchMode::Error => {
+            bail!("Session {} was saved in workspace {}, not {}", split_session_key(session_id).1, saved, workspace)
+        }
+    }
+}
+
+/// Warn, once, that the session was saved in another workspace
+fn warn_workspace_mismatch(session_id: &str) {
+    let saved = SESSION_MANAGER
+        .lock()
+        .ok()
+        .and_then(|mut m| m.get_mut(session_id).and_then(|ctx| ctx.workspace_mismatch.take()));
+    let Some(saved) = saved else {
+        return;
+    };
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::Warning,
+            warning: Some(CoreWarning {
+                code: "workspace_mismatch".to_string(),
+                message: format!(
+                    "This session was saved in {}; paths in its history may not exist in {}",
+                    saved,
+                    workspace_key(Path::new("."))
+                ),
+                items: Vec::new(),
+            }),
+            ..Default::default()
+        },
+    );
+}
+
+/// Queue the session's snapshot for the background writer. A session closed
+/// (or deleted) before its turn ended keeps the snapshot written when it closed.
+fn persist_session_snapshot(session_id: &str, messages: Vec<Message>) {
+    let saved = SESSION_MANAGER.lock().ok().and_then(|manager| {
+        manager.get(session_id).map(|ctx| {
+            (
+                (ctx.namespace.clone(), ctx.session_id.clone()),
+                (ctx.agent_mode.to_string(), ctx.approval_mode.to_string()),
+                ctx.pinned.clone(),
+                (ctx.title.clone(), ctx.notes.clone(), ctx.vars.clone(), ctx.skills.clone()),
+            )
+        })
+    });
+    let Some(((namespace, public_id), (agent_mode, approval_mode), pinned, (title, notes, vars, skills))) = saved else {
+        log::debug!("Session {} is no longer open; its snapshot is not written", session_id);
+        return;
+    };
+    let pinned = pinned.into_iter().filter(|&i| i < messages.len()).collect();
+
+    snapshot_writer::schedule(store::SessionSnapshot {
+        version: store::SESSION_SNAPSHOT_VERSION,
+        session_id: public_id,
+        namespace,
+        created_at_ms: 0,
+        updated_at_ms: 0,
+        agent_mode,
+        approval_mode,
+        messages,
+        pinned,
+        workspace: Some(workspace_key(Path::new("."))),
+        title,
+        notes,
+        vars,
+        skills,
+    });
+}
+
+/// Write session snapshots still waiting in the background writer; call before exiting
+pub fn flush_sessions() {
+    snapshot_writer::flush();
+    event_log::flush();
+}
+
+/// Close an open session: cancel its turn, write its snapshot, release its
+/// file locks and stop its MCP servers. Returns false if it was not open.
+pub fn close_session(namespace: &str, session_id: &str) -> Result<bool> {
+    let session_id = &session_key(namespace, session_id);
+    let ctx = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?
+        .remove(session_id);
+    let Some(ctx) = ctx else {
+        return Ok(false);
+    };
+    ctx.cancel_token.cancel();
+    flush_sessions();
+    file_lock::release_all(session_id);
+    bash::close_shell(session_id);
+    backend::unregister(session_id);
+    session_vars::unregister(session_id);
+    path_policy::unregister(session_id);
+    sensitive_files::unregister(session_id);
+    checkpoint::forget(session_id);
+    artifacts::forget(session_id);
+    file_activity::forget(session_id);
+    idempotency::forget(session_id);
+    let stopped = mcp_process::stop_owned_by(session_id);
+    log_session_event(session_id, "close", json!({ "mcp_servers_stopped": stopped }));
+    Ok(true)
+}
+
+/// The builtin tools, the tools of the session's MCP servers, and a tool for
+/// each skill the model may invoke
+fn session_tools(config: &AppConfig, session_id: &str) -> Vec<Box<dyn Tool>> {
+    let mut tools: Vec<Box<dyn Tool>> = list_available_tools();
+    let builtin_names: HashSet<String> = tools.iter().map(|t| t.name().to_string()).collect();
+    tools.extend(load_mcp_tools(config, session_id, &builtin_names));
+    let taken: Vec<String> = tools.iter().map(|t| t.name().to_string()).collect();
+    tools.extend(skills::tools(skills::discover(&skills::dirs(config)).values(), &taken));
+    tools
+}
+
+/// Lock the session's agent, loading its history and tools back first if
+/// they were evicted
+pub(crate) async fn lock_agent(inner: &Arc<Mutex<RustAgent>>) -> Result<MutexGuard<'_, RustAgent>> {
+    let mut agent = inner.lock().await;
+    let evicted = {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        manager.find_by_agent_mut(inner).and_then(|ctx| {
+            ctx.last_used_ms = now_ms();
+            ctx.evicted.as_ref().map(|e| (ctx.namespace.clone(), ctx.session_id.clone(), e.message_count))
+        })
+    };
+    let Some((namespace, public_id, message_count)) = evicted else {
+        return Ok(agent);
+    };
+    let session_id = session_key(&namespace, &public_id);
+    let config = AppConfig::load().context("Failed to load config")?;
+    let snapshot = load_persisted_snapshot(&namespace, &public_id)?;
+    let messages = match snapshot {
+        Some(snapshot) => snapshot.messages,
+        None if message_count == 0 => Vec::new(),
+        None => bail!("The saved history of session {} is missing", session_id),
+    };
+    agent.restore(messages, session_tools(&config, &session_id));
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(&session_id) {
+            ctx.evicted = None;
+            ctx.memory_bytes = agent.history_bytes() as u64;
+        }
+    }
+    log_session_event(&session_id, "rehydrated", json!({ "messages": agent.message_count() }));
+    Ok(agent)
+}
+
+/// Save the session and drop its agent's history and tools, stopping its MCP
+/// servers. Skipped, returning false, while the agent is in use or if the
+/// saved snapshot does not hold the whole history.
+fn evict_session(session_id: &str) -> bool {
+    let Some(inner) = SESSION_MANAGER
+        .lock()
+        .ok()
+        .and_then(|m| m.get(session_id).map(|ctx| Arc::clone(&ctx.inner)))
+    else {
+        return false;
+    };
+    let Ok(mut agent) = inner.try_lock() else {
+        return false;
+    };
+    // Checked under the agent lock, which eviction and reloading both hold
+    let Some((namespace, public_id)) = SESSION_MANAGER.lock().ok().and_then(|m| {
+        m.get(session_id)
+            .filter(|ctx| ctx.evicted.is_none())
+            .map(|ctx| (ctx.namespace.clone(), ctx.session_id.clone()))
+    }) else {
+        return false;
+    };
+    let message_count = agent.message_count();
+    persist_session_snapshot(session_id, agent.export_messages());
+    let saved = load_persisted_snapshot(&namespace, &public_id).ok().flatten().map(|s| s.messages.len());
+    if message_count > 0 && saved != Some(message_count) {
+        log::warn!("Not evicting session {}: its snapshot was not saved", session_id);
+        return false;
+    }
+    agent.evict();
+    let stopped = mcp_process::stop_owned_by(session_id);
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(session_id) {
+            ctx.evicted = Some(EvictedSession { message_count, evicted_at_ms: now_ms() });
+            ctx.memory_bytes = 0;
+        }
+    }
+    drop(agent);
+    log_session_event(
+        session_id,
+        "evicted",
+        json!({ "messages": message_count, "mcp_servers_stopped": stopped }),
+    );
+    true
+}
+
+/// Evict the sessions that are idle or over the memory ceiling of `[sessions]`
+fn enforce_session_limits() {
+    let residents = match SESSION_MANAGER.lock() {
+        Ok(manager) => manager.residents(),
+        Err(_) => return,
+    };
+    for session_id in eviction::victims(&residents, now_ms()) {
+        evict_session(&session_id);
+    }
+}
+
+/// Check the limits every `SWEEP_INTERVAL_SECS` for as long as the process runs
+fn start_eviction_sweep() {
+    static SWEEP: Once = Once::new();
+    SWEEP.call_once(|| {
+        let spawned = std::thread::Builder::new()
+            .name("session-eviction".to_string())
+            .spawn(|| loop {
+                std::thread::sleep(std::time::Duration::from_secs(eviction::SWEEP_INTERVAL_SECS));
+                enforce_session_limits();
+            });
+        if let Err(e) = spawned {
+            log::warn!("Failed to start the session eviction thread: {}", e);
+        }
+    });
+}
+
+/// Check the environment in the background when the first session opens
+fn start_preflight(config: &AppConfig) {
+    static PREFLIGHT: Once = Once::new();
+    PREFLIGHT.call_once(|| {
+        let config = config.clone();
+        let spawned = std::thread::Builder::new()
+            .name("preflight".to_string())
+            .spawn(move || {
+                preflight::run_once(&config);
+            });
+        if let Err(e) = spawned {
+            log::warn!("Failed to start the preflight thread: {}", e);
+        }
+    });
+}
+
+/// Write pending snapshots and stop every MCP server process; hosts call this on exit
+pub fn shutdown() {
+    flush_sessions();
+    let stopped = mcp_process::stop_all();
+    if stopped > 0 {
+        log::info!("Stopped {} MCP server process(es) on shutdown", stopped);
+    }
+}
+
+fn is_retryable_llm_error(e: &anyhow::Error) -> bool {
+    let msg = e.to_string().to_lowercase();
+    msg.contains("failed to initiate llm stream")
+        || msg.contains("failed to send request to llm api")
+        || msg.contains("error sending request")
+}
+
+/// Models suggested in place of one whose provider failed
+const MAX_SUGGESTED_MODELS: usize = 5;
+
+/// A provider error the user has to act on, and what they can do about it;
+/// `models` are the configured (provider, model) pairs that could be switched to
+fn classify_provider_error(e: &anyhow::Error, provider: &str, models: &[(String, String)]) -> Option<CoreError> {
+    let classified = provider_error::classify(&format!("{:#}", e))?;
+    let (code, message, action) = match classified.kind {
+        ProviderErrorKind::QuotaExceeded => (
+            CoreErrorCode::QuotaExceeded,
+            format!("The {} API key has used up its quota", provider),
+            "add_credits",
+        ),
+        ProviderErrorKind::CreditExhausted => (
+            CoreErrorCode::CreditExhausted,
+            format!("The {} account is out of credit", provider),
+            "add_credits",
+        ),
+        ProviderErrorKind::InvalidApiKey => (
+            CoreErrorCode::InvalidApiKey,
+            format!("The {} API key was rejected", provider),
+            "update_api_key",
+        ),
+    };
+    let mut suggestions = vec![CoreErrorSuggestion { action: action.to_string(), models: Vec::new() }];
+    // The provider's other models use the same key and account
+    let others: Vec<String> = models
+        .iter()
+        .filter(|(p, _)| p != provider)
+        .map(|(p, m)| format!("{}:{}", p, m))
+        .take(MAX_SUGGESTED_MODELS)
+        .collect();
+    if !others.is_empty() {
+        suggestions.push(CoreErrorSuggestion { action: "switch_model".to_string(), models: others });
+    }
+    Some(CoreError {
+        code,
+        message,
+        provider: provider.to_string(),
+        detail: classified.detail,
+        suggestions,
+    })
+}
+
+async fn execute_agent_with_retry(agent: &mut RustAgent) -> anyhow::Result<RustAgentResult> {
+    const MAX_ATTEMPTS: usize = 3;
+    for attempt in 1..=MAX_ATTEMPTS {
+        match agent.execute().await {
+            Ok(result) => return Ok(result),
+            Err(e) => {
+                if attempt < MAX_ATTEMPTS && is_retryable_llm_error(&e) {
+                    log::warn!(
+                        "Agent execution failed (attempt {}/{}): {}",
+                        attempt,
+                        MAX_ATTEMPTS,
+                        e
+                    );
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                return Err(e);
+            }
+        }
+    }
+    unreachable!("loop returns on success or final failure")
+}
+
+/// Expand `@path` mentions in the prompt and warn about the ones that could not be attached
+fn preprocess_prompt(session_id: &str, prompt: String) -> String {
+    warn_workspace_mismatch(session_id);
+    let access_level = SESSION_MANAGER
+        .lock()
+        .ok()
+        .and_then(|m| m.get(session_id).map(|ctx| ctx.approval_mode.clone()))
+        .map(|mode| {
+            if matches!(mode, ApprovalMode::AgentFull) {
+                ToolAccessLevel::Full
+            } else {
+                ToolAccessLevel::Workspace
+            }
+        })
+        .unwrap_or(ToolAccessLevel::Workspace);
+    let policy = match with_tool_access(access_level, PathPolicy::new) {
+        Ok(p) => p,
+        Err(e) => {
+            log::warn!("Skipping mention resolution: {}", e);
+            return prompt;
+        }
+    };
+
+    let resolution = resolve_mentions(&prompt, &policy);
+    log_session_event(
+        session_id,
+        "mentions_resolved",
+        json!({
+            "resolved": resolution
+                .resolved
+                .iter()
+                .map(|r| json!({
+                    "mention": r.mention,
+                    "path": r.path.to_string_lossy(),
+                    "kind": format!("{:?}", r.kind)
+                }))
+                .collect::<Vec<_>>(),
+            "unresolved": resolution.unresolved.len()
+        }),
+    );
+
+    if !resolution.unresolved.is_empty() {
+        emit_control_event(
+            session_id,
+            CoreEvent {
+                session_id: session_id.to_string(),
+                ts_ms: now_ms(),
+                event_type: CoreEventType::Warning,
+                warning: Some(CoreWarning {
+                    code: "unresolved_mentions".to_string(),
+                    message: format!(
+                        "{} file reference(s) could not be attached",
+                        resolution.unresolved.len()
+                    ),
+                    items: resolution
+                        .unresolved
+                        .iter()
+                        .map(|u| CoreWarningItem {
+                            subject: format!("@{}", u.mention),
+                            reason: u.reason.clone(),
+                        })
+                        .collect(),
+                }),
+                ..Default::default()
+            },
+        );
+    }
+
+    resolution.prompt
+}
+
+/// Open `public_id` in `namespace`. The parts carry the session's key, which
+/// the functions taking a session id inside the core expect.
+pub(crate) fn open_session(namespace: String, public_id: String) -> Result<SessionOpenParts> {
+    store::validate_namespace(&namespace)?;
+    let session_id = session_key(&namespace, &public_id);
+    {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        if let Some(ctx) = manager.get(&session_id) {
+            let inner = Arc::clone(&ctx.inner);
+            drop(manager);
+            log_session_event(&session_id, "open_reuse", json!({}));
+            return Ok(SessionOpenParts {
+                inner,
+                session_id,
+            });
+        }
+    }
+
+    crate::init_logger();
+    let mut config = AppConfig::load().context("Failed to load config")?;
+    crypto::set_enabled(config.privacy.encrypt_sessions);
+    event_log::set_enabled(config.privacy.event_log);
+    telemetry::configure(&config.telemetry, config.runtime.telemetry_enabled);
+    notifier::configure(&config.notify);
+    eviction::configure(&config.sessions);
+    start_eviction_sweep();
+    start_preflight(&config);
+
+    let snapshot = load_persisted_snapshot(&namespace, &public_id)?;
+    let workspace_mismatch = check_saved_workspace(
+        &session_id,
+        snapshot.as_ref().and_then(|s| s.workspace.as_deref()),
+        &workspace_key(Path::new(".")),
+        config.security.workspace_mismatch,
+    )?;
+
+    // Tools of a session with a remote target run there; everything else stays local
+    let saved_config = config.runtime.sessions.iter().find(|s| s.namespace == namespace && s.session_id == public_id);
+    let target = match saved_config.and_then(|s| s.execution_target.as_deref()) {
+        Some(saved) => backend::parse_target(saved)?,
+        None => backend::default_target(&config.remote),
+    };
+    match backend::create(target, &config.remote, Path::new(".")).context("Invalid [remote] configuration")? {
+        Some(target) => {
+            log_session_event(&session_id, "remote_backend", json!({ "target": target.name() }));
+            backend::register(&session_id, target);
+        }
+        None => backend::unregister(&session_id),
+    }
+
+    // Determine AgentMode and ApprovalMode
+    // 1. Try to find in runtime config
+    let (agent_mode, approval_mode) = if let Some(session_config) = saved_config {
+        (
+            AgentMode::from(session_config.agent_mode.clone()),
+            ApprovalMode::from(session_config.approval_mode.clone()),
+        )
+    } else {
+        (AgentMode::default(), ApprovalMode::default())
+    };
+
+    // Save new session to runtime config if it doesn't exist
+    if saved_config.is_none() {
+        if config.runtime.theme.is_none() {
+            config.runtime.theme = config
+                .theme
+                .clone()
+                .or_else(|| config.welcome.as_ref().and_then(|w| w.theme.clone()));
+        }
+        config.runtime.sessions.push(RuntimeSessionConfig {
+            session_id: public_id.clone(),
+            namespace: namespace.clone(),
+            agent_mode: agent_mode.to_string(),
+            approval_mode: approval_mode.to_string(),
+            execution_target: None,
+        });
+        let _ = config.save_runtime();
+        if config.security.overlay_by_default {
+            if let Err(e) = workspace_overlay(&session_id).and_then(|o| {
+                o.set_enabled(true)
+            }) {
+                log::warn!("Failed to enable overlay for session {}: {}", session_id, e);
+            }
+        }
+    }
+
+    // Registered before the MCP servers start, so they get them in their environment
+    let vars = snapshot.as_ref().map(|s| s.vars.clone()).unwrap_or_default();
+    session_vars::register(&session_id, vars.clone());
+    path_policy::register(&session_id, &config);
+    sensitive_files::register(&session_id, &config);
+    let enabled_skills = snapshot.as_ref().map(|s| s.skills.clone()).unwrap_or_default();
+    let active_skills = skills::active(&config, &agent_mode, &enabled_skills);
+    let environment = environment_probe::probe(&config.environment_probe);
+    let system_prompt =
+        system_prompt_for_session(&config, &agent_mode, environment.as_deref(), &vars, &active_skills);
+
+    if let Some(legacy) = &config.llm_provider {
+        if let Some(existing) = config
+            .providers
+            .iter_mut()
+            .find(|p| p.name == legacy.provider_name)
+        {
+            if !existing.models.contains(&legacy.model_name) {
+                existing.models.push(legacy.model_name.clone());
+            }
+        } else {
+            config.providers.push(ProviderConfig {
+                name: legacy.provider_name.clone(),
+                base_url: legacy.base_url.clone(),
+                api_key: legacy.api_key.clone(),
+                models: vec![legacy.model_name.clone()],
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut resolved = config
+        .default_model
+        .as_deref()
+        .and_then(|m| config.resolve_model(m));
+
+    if resolved.is_none() {
+        if let Some(p) = &config.llm_provider {
+            resolved = Some((p.provider_name.clone(), p.model_name.clone()));
+        } else if let Some(p) = config.providers.first() {
+            if let Some(m) = p.models.first() {
+                resolved = Some((p.name.clone(), m.clone()));
+            }
+        }
+    }
+
+    let (provider_name, model_name) = resolved.ok_or_else(|| anyhow!("No provider configured"))?;
+
+    if !config.workspace_trusted {
+        let trust = workspace_trust_info(&config, Path::new("."));
+        if trust.has_project_config {
+            log_session_event(
+                &session_id,
+                "workspace_untrusted",
+                json!({ "path": trust.path, "level": trust.level }),
+            );
+        }
+    }
+
+    let tools = session_tools(&config, &session_id);
+
+    let mut agent = RustAgent::new(
+        provider_name,
+        model_name,
+        system_prompt,
+        config.providers.clone(),
+        tools,
+    )
+    .context("Failed to create agent")?;
+    agent.set_artifact_scope(&session_id);
+
+    let (mut pinned, mut title, mut notes) = (Vec::new(), None, None);
+    if let Some(snapshot) = snapshot {
+        agent.import_messages(snapshot.messages);
+        (pinned, title, notes) = (snapshot.pinned, snapshot.title, snapshot.notes);
+    }
+    let memory_bytes = agent.history_bytes() as u64;
+    let interrupted_turn = turn_journal::recover(&namespace, &public_id, agent.message_count()).unwrap_or_else(|e| {
+        log::warn!("Failed to recover the interrupted turn of session {}: {:#}", session_id, e);
+        None
+    });
+
+    let (inner, interrupted) = {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        manager.add_with_context(namespace, public_id, agent, agent_mode, approval_mode);
+        let ctx = manager.get_mut(&session_id).expect("Just inserted");
+        ctx.pinned = pinned;
+        (ctx.title, ctx.notes, ctx.vars) = (title, notes, vars);
+        (ctx.skills, ctx.active_skills) = (enabled_skills, active_skills);
+        ctx.memory_bytes = memory_bytes;
+        ctx.environment = environment;
+        ctx.workspace_mismatch = workspace_mismatch;
+        let interrupted = interrupted_turn.is_some();
+        ctx.interrupted_turn = interrupted_turn;
+        (Arc::clone(&ctx.inner), interrupted)
+    };
+    log_session_event(&session_id, "open_create", json!({ "interrupted_turn": interrupted }));
+
+    Ok(SessionOpenParts { inner, session_id })
+}
+
+pub(crate) async fn execute_session(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    prompt: String,
+) -> Result<RustAgentResult> {
+    log_session_event(
+        session_id,
+        "execute_called",
+        json!({ "prompt_chars": prompt.chars().count() }),
+    );
+
+    let agent_clone = Arc::clone(inner);
+    let session_id = session_id.to_string();
+    let _file_locks = TurnFileLocks(session_id.clone());
+
+    let turn_config = AppConfig::load().context("Failed to load config")?;
+    path_policy::register(&session_id, &turn_config);
+    sensitive_files::register(&session_id, &turn_config);
+    let workspace_root = PathPolicy::for_session(&session_id)
+        .map(|policy| policy.root().to_path_buf())
+        .unwrap_or_else(|_| PathBuf::from("."));
+    let (result, messages_after, journal) = {
+        let mut agent = lock_agent(&agent_clone).await?;
+        let clock = Arc::new(TurnClock::start(now_ms()));
+        let prompt = preprocess_prompt(&session_id, prompt);
+        let journal = start_turn_journal(&session_id, agent.message_count(), &prompt);
+
+        agent.set_stream_callback(events::stream_callback(
+            session_id.clone(),
+            Arc::clone(&clock),
+            journal.clone(),
+            turn_config.answer_format.clone(),
+            workspace_root.clone(),
+        ));
+        agent.set_checkpoint_callback(auto_run_checkpoint_callback(&session_id));
+        agent.set_tool_executor_callback(executor::tool_executor(
+            &session_id,
+            confirmation_sender,
+            &turn_config,
+            Arc::clone(&clock),
+        ));
+
+        let context = {
+            let (config, prompt, id) = (turn_config.context_providers.clone(), prompt.clone(), session_id.clone());
+            tokio::task::spawn_blocking(move || {
+                with_tool_session(&id, || context_providers::gather(&config, Path::new("."), &prompt))
+            })
+            .await
+            .unwrap_or(None)
+        };
+        agent.add_user_message(prompt);
+        agent.set_tool_definitions_config(turn_config.tool_definitions.clone());
+        let output_limit = SESSION_MANAGER
+            .lock()
+            .ok()
+            .and_then(|m| m.get(&session_id).and_then(|ctx| ctx.output_limit))
+            .unwrap_or_else(|| OutputLimit::from_config(&turn_config.sessions));
+        agent.set_max_output_chars(output_limit.max_response_chars());
+        let header = [context_header::build(&turn_config.context_header, Path::new(".")), context]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("\n");
+        agent.set_context_header((!header.is_empty()).then_some(header));
+        telemetry::record_turn(&agent.get_base_url());
+        let stats_at_start = with_tool_stats(&session_id, |stats| stats.clone()).unwrap_or_default();
+        checkpoint::begin_turn(&session_id);
+        let result = execute_agent_with_retry(&mut agent).await;
+        finish_turn(&session_id, &stats_at_start, &clock, agent.get_provider_name(), agent.get_model_name());
+        let mut result = result.map_err(|e| {
+            telemetry::record_error(&e);
+            let raw = format!("{:#}", e);
+            health::record_error(Subsystem::Llm, raw.clone());
+            log::error!("Agent execution failed: {:?}", e);
+            let error = classify_provider_error(&e, &agent.get_provider_name(), &agent.get_available_models());
+            // A classified error is reported without the provider's raw response
+            let msg = match &error {
+                Some(error) => match &error.detail {
+                    Some(detail) => format!("{}: {}", error.message, detail),
+                    None => error.message.clone(),
+                },
+                None => raw,
+            };
+            emit_control_event(
+                &session_id,
+                CoreEvent {
+                    session_id: session_id.clone(),
+                    ts_ms: now_ms(),
+                    event_type: CoreEventType::Error,
+                    success: Some(false),
+                    error_message: Some(msg.clone()),
+                    error,
+                    ..Default::default()
+                },
+            );
+            anyhow!("Agent execution failed: {}", msg)
+        })?;
+        result.content = format_answer(&turn_config.answer_format, &workspace_root, &result.content).content;
+        let messages_after = agent.export_messages();
+        if let Ok(mut manager) = SESSION_MANAGER.lock() {
+            if let Some(ctx) = manager.get_mut(&session_id) {
+                ctx.memory_bytes = agent.history_bytes() as u64;
+            }
+        }
+        (result, messages_after, journal)
+    };
+
+    persist_session_snapshot(&session_id, messages_after);
+    if let Some(journal) = journal {
+        // The journal goes once the snapshot holding the turn is on disk
+        snapshot_writer::flush();
+        journal.finish();
+    }
+    enforce_session_limits();
+    Ok(result)
+}
+
+/// The final answer of a turn, post-processed per `[answer_format]`
+pub(crate) fn format_answer(config: &AnswerFormatConfig, root: &Path, content: &str) -> answer_format::FormattedAnswer {
+    answer_format::process(content, root, config)
+}
+
+/// Start the write-ahead journal of a turn, replacing the interrupted turn the
+/// session may have had. Without a journal the turn is only lost in a crash.
+fn start_turn_journal(session_id: &str, base_messages: usize, prompt: &str) -> Option<Arc<TurnJournal>> {
+    let (namespace, public_id) = {
+        let mut manager = SESSION_MANAGER.lock().ok()?;
+        let ctx = manager.get_mut(session_id)?;
+        ctx.interrupted_turn = None;
+        (ctx.namespace.clone(), ctx.session_id.clone())
+    };
+    match TurnJournal::start(&namespace, &public_id, now_ms(), base_messages, prompt) {
+        Ok(journal) => Some(Arc::new(journal)),
+        Err(e) => {
+            log::warn!("Failed to start the turn journal of session {}: {:#}", session_id, e);
+            None
+        }
+    }
+}
+
+/// Releases the file locks taken during a turn when the turn ends
+struct TurnFileLocks(String);
+
+impl Drop for TurnFileLocks {
+    fn drop(&mut self) {
+        let released = file_lock::release_all(&self.0);
+        if released > 0 {
+            log_session_event(&self.0, "file_locks_released", json!({ "count": released }));
+        }
+    }
+}
+
+/// Take an advisory lock on a file for a non-session editor such as the
+/// frontend's file watcher. Returns false if a session holds the lock.
+pub fn lock_file(path: &str, owner: &str) -> Result<bool> {
+    let path = PathSecurity::to_absolute_path(path)?;
+    Ok(file_lock::acquire(&path, owner).is_ok())
+}
+
+pub fn unlock_file(path: &str, owner: &str) -> Result<bool> {
+    let path = PathSecurity::to_absolute_path(path)?;
+    Ok(file_lock::release(&path, owner))
+}
+
+/// Run `f` on the session's tool stats; None if the session is not open
+pub(crate) fn with_tool_stats<T>(session_id: &str, f: impl FnOnce(&mut ToolStats) -> T) -> Option<T> {
+    let mut manager = SESSION_MANAGER.lock().ok()?;
+    manager.get_mut(session_id).map(|ctx| f(&mut ctx.tool_stats))
+}
+
+/// Run `f` on the session's proposals; None if the session is not open
+pub(crate) fn with_proposals<T>(session_id: &str, f: impl FnOnce(&mut Proposals) -> T) -> Option<T> {
+    let mut manager = SESSION_MANAGER.lock().ok()?;
+    manager.get_mut(session_id).map(|ctx| f(&mut ctx.proposals))
+}
+
+/// Keep the unreviewed changes to `files` (all of them when empty); returns the files accepted
+pub fn accept_turn_changes(namespace: &str, session_id: &str, files: Vec<String>) -> Result<Vec<String>> {
+    let session_id = &session_key(namespace, session_id);
+    let workspace = PathPolicy::for_session(session_id)?.root().to_path_buf();
+    let accepted = checkpoint::accept(session_id, &resolve_workspace_paths(session_id, &files)?);
+    let accepted = relative_paths(&workspace, &accepted);
+    log_session_event(session_id, "turn_changes_accepted", json!({ "files": accepted }));
+    Ok(accepted)
+}
+
+/// Restore `files` (all of them when empty) to their content before the
+/// first turn that changed them since they were last reviewed; returns the
+/// files reverted. Nothing is reverted if one of them changed after the last
+/// turn ended.
+pub fn revert_turn_changes(namespace: &str, session_id: &str, files: Vec<String>) -> Result<Vec<String>> {
+    let session_id = &session_key(namespace, session_id);
+    let workspace = PathPolicy::for_session(session_id)?.root().to_path_buf();
+    let reverted = checkpoint::revert(session_id, &resolve_workspace_paths(session_id, &files)?)?;
+    let reverted = relative_paths(&workspace, &reverted);
+    log_session_event(session_id, "turn_changes_reverted", json!({ "files": reverted }));
+    Ok(reverted)
+}
+
+fn relative_paths(workspace: &Path, paths: &[PathBuf]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|p| p.strip_prefix(workspace).unwrap_or(p).to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Record the turn's timing and emit the end-of-turn Usage event
+fn finish_turn(session_id: &str, stats_at_start: &ToolStats, clock: &TurnClock, provider: String, model: String) {
+    let recorded = SESSION_MANAGER.lock().ok().and_then(|mut manager| {
+        manager.get_mut(session_id).map(|ctx| {
+            let turn = ctx.tool_stats.since(stats_at_start);
+            let timing = clock.finish(provider, model, turn.total_time());
+            turn_timing::push(&mut ctx.turn_timings, timing.clone());
+            (turn, timing)
+        })
+    });
+    let Some((turn, timing)) = recorded else {
+        return;
+    };
+    emit_pending_proposals(session_id);
+    emit_turn_diff(session_id);
+    log_session_event(
+        session_id,
+        "turn_timing",
+        json!({
+            "first_token_ms": timing.first_token.map(|d| d.as_millis() as u64),
+            "duration_ms": timing.duration.as_millis() as u64,
+            "model_ms": timing.model_time.as_millis() as u64,
+            "tool_ms": timing.tool_time.as_millis() as u64
+        }),
+    );
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::Usage,
+            display_text: Some(usage_summary(&turn, &timing)),
+            tool_stats: Some(turn.to_core()),
+            turn_timing: Some(timing.to_core()),
+            ..Default::default()
+        },
+    );
+}
+
+/// "12.3s (first token 0.8s, model 9.1s, tools 2.9s) · Tools: bash ×2 2.9s"
+fn usage_summary(turn: &ToolStats, timing: &TurnTiming) -> String {
+    let secs = |d: Duration| format!("{:.1}s", d.as_secs_f64());
+    let mut parts = Vec::new();
+    if let Some(first_token) = timing.first_token {
+        parts.push(format!("first token {}", secs(first_token)));
+    }
+    parts.push(format!("model {}", secs(timing.model_time)));
+    parts.push(format!("tools {}", secs(timing.tool_time)));
+    let mut text = format!("{} ({})", secs(timing.duration), parts.join(", "));
+    if let Some(tools) = turn.summary() {
+        text.push_str(&format!(" · Tools: {}", tools));
+    }
+    text
+}
+
+/// Timing of the session's latest turns, oldest first
+pub fn get_turn_timings(namespace: &str, session_id: &str) -> Result<Vec<CoreTurnTiming>> {
+    let session_id = &session_key(namespace, session_id);
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx.turn_timings.iter().map(TurnTiming::to_core).collect())
+}
+
+/// Events logged for a session in `namespace`, open or saved, oldest first
+pub fn get_session_events(namespace: &str, session_id: &str, filter: &CoreEventFilter) -> Result<Vec<CoreEvent>> {
+    event_log::read(namespace, session_id, filter).context("Failed to read session events")
+}
+
+/// Calls, failures and time of every tool the session has run, longest first
+pub fn get_tool_stats(namespace: &str, session_id: &str) -> Result<Vec<CoreToolStat>> {
+    let session_id = &session_key(namespace, session_id);
+    with_tool_stats(session_id, |stats| stats.to_core()).ok_or_else(|| anyhow!("Session not found"))
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct AutoModeOptions {
+    /// Wall-clock budget for the run
+    pub budget_minutes: u32,
+    /// Minimum minutes between progress checkpoints (default 5)
+    pub checkpoint_minutes: Option<u32>,
+    /// Seconds a checkpoint waits for a steer message (default 30)
+    pub steer_wait_seconds: Option<u32>,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct AutoRunResult {
+    pub content: String,
+    pub tools_used: bool,
+    pub cancelled: bool,
+    /// The run was stopped because its budget ran out
+    pub budget_exhausted: bool,
+    pub checkpoints: u32,
+    pub elapsed_ms: i64,
+}
+
+fn emit_checkpoint(session_id: &str, summary: &str, budget_exhausted: bool) {
+    log_session_event(
+        session_id,
+        "auto_checkpoint",
+        json!({ "summary": summary, "budget_exhausted": budget_exhausted }),
+    );
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::Checkpoint,
+            response_summary: Some(if budget_exhausted { "budget_exhausted" } else { "checkpoint" }.to_string()),
+            display_text: Some(summary.to_string()),
+            ..Default::default()
+        },
+    );
+}
+
+/// Checkpoint callback for the session's autonomous run, if one is active
+fn auto_run_checkpoint_callback(session_id: &str) -> Option<CheckpointCallback> {
+    let (run, cancel_token) = {
+        let manager = SESSION_MANAGER.lock().ok()?;
+        let ctx = manager.get(session_id)?;
+        (Arc::clone(ctx.auto_run.as_ref()?), ctx.cancel_token.clone())
+    };
+    let session_id = session_id.to_string();
+    Some(Arc::new(move |results: &[ToolExecutionResult]| {
+        let status = run.checkpoint(results, Instant::now());
+        let run = Arc::clone(&run);
+        let cancel_token = cancel_token.clone();
+        let session_id = session_id.clone();
+        Box::pin(async move {
+            match status {
+                CheckpointStatus::Skip => CheckpointDecision::Continue,
+                CheckpointStatus::BudgetExhausted(summary) => {
+                    emit_checkpoint(&session_id, &summary, true);
+                    CheckpointDecision::Stop
+                }
+                CheckpointStatus::Report(summary) => {
+                    emit_checkpoint(&session_id, &summary, false);
+                    let steer = tokio::select! {
+                        steer = run.wait_for_steer() => steer,
+                        _ = cancel_token.cancelled() => None,
+                    };
+                    match steer {
+                        Some(message) => {
+                            log_session_event(&session_id, "auto_steer_applied", json!({ "chars": message.chars().count() }));
+                            CheckpointDecision::Steer(format!("Steering from the user at this checkpoint:\n{}", message))
+                        }
+                        None => CheckpointDecision::Continue,
+                    }
+                }
+            }
+        }) as std::pin::Pin<Box<dyn std::future::Future<Output = CheckpointDecision> + Send>>
+    }))
+}
+
+fn set_auto_run(session_id: &str, run: Option<Arc<AutoRun>>) -> Result<()> {
+    let mut manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get_mut(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    ctx.auto_run = run;
+    Ok(())
+}
+
+/// Run a turn autonomously: a progress checkpoint every `checkpoint_minutes`
+/// that waits briefly for a steer, and a hard stop once the budget is used up,
+/// cancelling whatever the turn is doing at the time. Calls are approved as
+/// the session's approval mode says; the run asks for what the mode asks for.
+pub(crate) async fn execute_auto(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    prompt: String,
+    options: AutoModeOptions,
+) -> Result<AutoRunResult> {
+    if options.budget_minutes == 0 {
+        bail!("budget_minutes must be greater than 0");
+    }
+    let config = AutoRunConfig {
+        budget: Duration::from_secs(u64::from(options.budget_minutes) * 60),
+        checkpoint_interval: Duration::from_secs(u64::from(options.checkpoint_minutes.unwrap_or(5).max(1)) * 60),
+        steer_window: Duration::from_secs(u64::from(options.steer_wait_seconds.unwrap_or(30))),
+    };
+    log_session_event(
+        session_id,
+        "auto_run_started",
+        json!({ "budget_minutes": options.budget_minutes, "checkpoint_minutes": options.checkpoint_minutes }),
+    );
+    run_auto(session_id, inner, confirmation_sender, prompt, config).await
+}
+
+async fn run_auto(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    prompt: String,
+    config: AutoRunConfig,
+) -> Result<AutoRunResult> {
+    let run = Arc::new(AutoRun::new(config));
+    set_auto_run(session_id, Some(Arc::clone(&run)))?;
+    let turn = execute_session(session_id, inner, confirmation_sender, prompt);
+    tokio::pin!(turn);
+    let (result, out_of_budget) = tokio::select! {
+        result = &mut turn => (result, false),
+        _ = tokio::time::sleep(run.config.budget) => {
+            // The budget ran out mid-round: stop the turn instead of waiting for the next checkpoint
+            if let CheckpointStatus::BudgetExhausted(summary) = run.checkpoint(&[], Instant::now()) {
+                emit_checkpoint(session_id, &summary, true);
+            }
+            let _ = cancel_session(session_id, confirmation_sender).await;
+            (turn.await, true)
+        }
+    };
+    let _ = set_auto_run(session_id, None);
+    let result = result?;
+    let budget_exhausted = result.stopped || out_of_budget;
+
+    let elapsed_ms = run.started_at.elapsed().as_millis() as i64;
+    log_session_event(
+        session_id,
+        "auto_run_finished",
+        json!({ "elapsed_ms": elapsed_ms, "budget_exhausted": budget_exhausted, "checkpoints": run.checkpoints() }),
+    );
+    Ok(AutoRunResult {
+        content: result.content,
+        tools_used: result.tools_used,
+        cancelled: result.cancelled && !out_of_budget,
+        budget_exhausted,
+        checkpoints: run.checkpoints(),
+        elapsed_ms,
+    })
+}
+
+/// Queue a steer message for the next checkpoint of the running autonomous
+/// run. Returns false when no autonomous run is active.
+pub(crate) fn steer_session(session_id: &str, message: String) -> Result<bool> {
+    let run = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.auto_run.clone()
+    };
+    match run {
+        Some(run) => {
+            run.steer(message);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct PlanRunResult {
+    /// "completed" | "paused" | "rejected" | "cancelled" | "no_plan"
+    pub status: String,
+    /// Response of the planning turn (empty when resuming)
+    pub plan: String,
+    pub steps: Vec<String>,
+    pub completed_steps: u32,
+    /// 1-based step the run paused on
+    pub failed_step: Option<u32>,
+    pub error_message: Option<String>,
+}
+
+fn emit_plan_step(session_id: &str, event_type: CoreEventType, run: &PlanRun, index: usize, success: Option<bool>, error_message: Option<String>) {
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type,
+            display_text: Some(format!("Step {}/{}: {}", index + 1, run.steps.len(), run.steps[index])),
+            success,
+            error_message,
+            plan_step: Some(CorePlanStep {
+                index: (index + 1) as u32,
+                total: run.steps.len() as u32,
+                title: run.steps[index].clone(),
+            }),
+            ..Default::default()
+        },
+    );
+}
+
+/// Ask the user to approve a plan through the regular confirmation flow.
+/// Either allow decision approves; a denial rejects.
+async fn request_plan_approval(
+    session_id: &str,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    steps: &[String],
+) -> bool {
+    let (tx, rx) = oneshot::channel();
+    let request_id = generate_request_id();
+    *confirmation_sender.lock().await = Some(PendingConfirmation {
+        request_id: request_id.clone(),
+        sender: tx,
+    });
+    log_session_event(session_id, "plan_approval_requested", json!({ "steps": steps.len() }));
+
+    emit_control_event(
+        session_id,
+        CoreEvent {
+            session_id: session_id.to_string(),
+            ts_ms: now_ms(),
+            event_type: CoreEventType::ConfirmationRequested,
+            confirm: Some(CoreConfirmationRequest {
+                request_id,
+                tool_name: "plan".to_string(),
+                tool_display_name: "plan".to_string(),
+                arguments: json!({ "steps": steps }).to_string(),
+                summary: format!("{} step plan", steps.len()),
+                kind: "Plan".to_string(),
+                key_path: String::new(),
+                preview: None,
+            }),
+            ..Default::default()
+        },
+    );
+
+    let approved = rx.await.is_ok_and(|decision| decision.is_allowed());
+    log_session_event(session_id, "plan_approval_decided", json!({ "approved": approved }));
+    approved
+}
+
+fn set_active_plan(session_id: &str, run: Option<PlanRun>) {
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(session_id) {
+            ctx.active_plan = run;
+        }
+    }
+}
+
+/// Execute the remaining steps of `run`, one Build-mode turn per step,
+/// pausing on the first step that fails or is cancelled
+async fn run_plan_steps(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    mut run: PlanRun,
+    plan: String,
+) -> PlanRunResult {
+    let mut result = PlanRunResult {
+        status: "completed".to_string(),
+        plan,
+        steps: run.steps.clone(),
+        completed_steps: run.next_step as u32,
+        failed_step: None,
+        error_message: None,
+    };
+
+    while !run.is_finished() {
+        let index = run.next_step;
+        emit_plan_step(session_id, CoreEventType::PlanStepStart, &run, index, None, None);
+        log_session_event(session_id, "plan_step_started", json!({ "step": index + 1 }));
+
+        let outcome = execute_session(session_id, inner, confirmation_sender, run.step_prompt(index)).await;
+        let (success, status, error_message) = match outcome {
+            Ok(r) if r.cancelled => (false, "cancelled", Some("Cancelled by the user".to_string())),
+            Ok(_) => (true, "completed", None),
+            Err(e) => (false, "paused", Some(format!("{:#}", e))),
+        };
+        emit_plan_step(session_id, CoreEventType::PlanStepEnd, &run, index, Some(success), error_message.clone());
+        log_session_event(
+            session_id,
+            "plan_step_finished",
+            json!({ "step": index + 1, "success": success }),
+        );
+
+        if !success {
+            result.status = status.to_string();
+            result.failed_step = Some((index + 1) as u32);
+            result.error_message = error_message;
+            set_active_plan(session_id, Some(run));
+            return result;
+        }
+        run.next_step += 1;
+        result.completed_steps = run.next_step as u32;
+    }
+
+    set_active_plan(session_id, None);
+    result
+}
+
+/// Plan in Plan mode, get the plan approved once, then switch to Build mode
+/// and execute it step by step. A failing step pauses the run; continue it
+/// with `resume_plan`.
+pub(crate) async fn execute_plan_then_build(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+    prompt: String,
+) -> Result<PlanRunResult> {
+    set_agent_mode(session_id, inner, AgentMode::Plan.to_string()).await?;
+    let planned = execute_session(
+        session_id,
+        inner,
+        confirmation_sender,
+        format!("{}{}", prompt, PLAN_FORMAT_INSTRUCTIONS),
+    )
+    .await?;
+
+    let steps = parse_plan_steps(&planned.content);
+    let mut result = PlanRunResult {
+        status: String::new(),
+        plan: planned.content.clone(),
+        steps: steps.clone(),
+        completed_steps: 0,
+        failed_step: None,
+        error_message: None,
+    };
+    if planned.cancelled {
+        result.status = "cancelled".to_string();
+        return Ok(result);
+    }
+    if steps.is_empty() {
+        result.status = "no_plan".to_string();
+        return Ok(result);
+    }
+    if !request_plan_approval(session_id, confirmation_sender, &steps).await {
+        result.status = "rejected".to_string();
+        return Ok(result);
+    }
+
+    set_agent_mode(session_id, inner, AgentMode::Build.to_string()).await?;
+    Ok(run_plan_steps(session_id, inner, confirmation_sender, PlanRun::new(steps), planned.content).await)
+}
+
+/// Continue a paused plan from the step that failed
+pub(crate) async fn resume_plan(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+) -> Result<PlanRunResult> {
+    let run = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.active_plan.clone()
+    }
+    .ok_or_else(|| anyhow!("No paused plan to resume"))?;
+
+    set_agent_mode(session_id, inner, AgentMode::Build.to_string()).await?;
+    Ok(run_plan_steps(session_id, inner, confirmation_sender, run, String::new()).await)
+}
+
+/// A turn the process stopped in the middle of, found when the session was opened
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct InterruptedTurnInfo {
+    pub started_at_ms: i64,
+    pub prompt: String,
+    /// Rounds of tool calls the turn finished
+    pub completed_rounds: u32,
+    /// Answer text streamed after the last of them
+    pub partial_text: String,
+}
+
+const RESUME_INTERRUPTED_TURN_PROMPT: &str = "The previous response was interrupted before it finished. \
+Continue the task from where it stopped, without repeating the work already done.";
+
+pub(crate) fn get_interrupted_turn(session_id: &str) -> Result<Option<InterruptedTurnInfo>> {
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx.interrupted_turn.as_ref().map(|turn| InterruptedTurnInfo {
+        started_at_ms: turn.started_at_ms,
+        prompt: turn.prompt.clone(),
+        completed_rounds: turn.completed_rounds().min(u32::MAX as usize) as u32,
+        partial_text: turn.partial_text.clone(),
+    }))
+}
+
+/// The session's namespace and public id, and its interrupted turn
+fn take_interrupted_turn(session_id: &str) -> Result<((String, String), Option<InterruptedTurn>)> {
+    let mut manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get_mut(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(((ctx.namespace.clone(), ctx.session_id.clone()), ctx.interrupted_turn.take()))
+}
+
+/// Forget the interrupted turn; false if there was none
+pub(crate) fn discard_interrupted_turn(session_id: &str) -> Result<bool> {
+    let ((namespace, public_id), turn) = take_interrupted_turn(session_id)?;
+    if turn.is_none() {
+        return Ok(false);
+    }
+    turn_journal::discard(&namespace, &public_id)?;
+    log_session_event(session_id, "interrupted_turn_discarded", json!({}));
+    Ok(true)
+}
+
+/// Put what the interrupted turn did back in the history and let the model
+/// finish it
+pub(crate) async fn resume_interrupted_turn(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+) -> Result<RustAgentResult> {
+    let (_, turn) = take_interrupted_turn(session_id)?;
+    let turn = turn.ok_or_else(|| anyhow!("No interrupted turn to resume"))?;
+    let messages = {
+        let mut agent = lock_agent(inner).await?;
+        let mut messages = agent.export_messages();
+        if messages.len() != turn.base_messages {
+            bail!("The session has changed since the turn was interrupted");
+        }
+        messages.extend(turn.messages());
+        agent.import_messages(messages.clone());
+        messages
+    };
+    log_session_event(
+        session_id,
+        "interrupted_turn_resumed",
+        json!({ "completed_rounds": turn.completed_rounds(), "partial_chars": turn.partial_text.chars().count() }),
+    );
+    persist_session_snapshot(session_id, messages);
+    execute_session(session_id, inner, confirmation_sender, RESUME_INTERRUPTED_TURN_PROMPT.to_string()).await
+}
+
+/// Cancel the running turn: stop streaming, kill a running bash command and
+/// cancel the running tool call, so a call waiting for confirmation gets the
+/// cancelled tool result rather than a denial. Returns false when nothing was
+/// running.
+pub(crate) async fn cancel_session(
+    session_id: &str,
+    confirmation_sender: &Arc<Mutex<Option<PendingConfirmation>>>,
+) -> Result<bool> {
+    let (cancel_token, tool_call, tool_operation, stage) = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        let tool_operation = ctx.tool_operation.lock().ok().and_then(|v| *v);
+        let stage = ctx.response_stage.lock().ok().map(|v| *v);
+        let tool_call = ctx.running_tool.as_ref().map(|call| call.cancel.clone());
+        (ctx.cancel_token.clone(), tool_call, tool_operation, stage)
+    };
+
+    let running = tool_operation.is_some() || !matches!(stage, Some(ResponseStage::End));
+    cancel_token.cancel();
+
+    if tool_operation == Some(SessionToolOperation::Bash) {
+        cancel_running_command(session_id);
+    }
+
+    if let Some(tool_call) = tool_call {
+        tool_call.cancel();
+    }
+    // Dropping the sender closes the channel; the call sees its own cancellation first
+    confirmation_sender.lock().await.take();
+
+    log_session_event(
+        session_id,
+        "cancel_requested",
+        json!({
+            "running": running,
+            "tool_operation": tool_operation.map(|op| format!("{:?}", op))
+        }),
+    );
+    Ok(running)
+}
+
+pub(crate) async fn clear_history(session_id: &str, inner: &Arc<Mutex<RustAgent>>) -> Result<()> {
+    log_session_event(session_id, "history_cleared", json!({}));
+    if let Ok(mut manager) = SESSION_MANAGER.lock() {
+        if let Some(ctx) = manager.get_mut(session_id) {
+            ctx.pinned.clear();
+        }
+    }
+    let mut agent = lock_agent(inner).await?;
+    agent.clear_history();
+    let messages_after = agent.export_messages();
+    drop(agent);
+    persist_session_snapshot(session_id, messages_after);
+    Ok(())
+}
+
+fn session_agent(session_id: &str) -> Result<Arc<Mutex<RustAgent>>> {
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(Arc::clone(&ctx.inner))
+}
+
+fn pinned_indices(session_id: &str) -> Result<Vec<usize>> {
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx.pinned.clone())
+}
+
+fn set_pinned_indices(session_id: &str, pinned: Vec<usize>) -> Result<()> {
+    let mut manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get_mut(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    ctx.pinned = pinned;
+    Ok(())
+}
+
+/// Tool calls and results only make sense next to their counterpart
+fn is_pinnable(message: &Message) -> bool {
+    (message.role == "user" || message.role == "assistant")
+        && !message.content.starts_with("ToolResult")
+        && !message.content.contains("ToolCallsJSON:")
+}
+
+/// Pin the message at `index` so compaction keeps it verbatim
+pub async fn pin_message(namespace: &str, session_id: &str, index: u32) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    set_message_pinned(session_id, index, true).await
+}
+
+pub async fn unpin_message(namespace: &str, session_id: &str, index: u32) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    set_message_pinned(session_id, index, false).await
+}
+
+async fn set_message_pinned(session_id: &str, index: u32, pinned: bool) -> Result<()> {
+    let inner = session_agent(session_id)?;
+    let index = index as usize;
+    let messages = lock_agent(&inner).await?.export_messages();
+    let message = messages
+        .get(index)
+        .ok_or_else(|| anyhow!("No message at index {}", index))?;
+    if pinned && !is_pinnable(message) {
+        bail!("Only user and assistant text messages can be pinned");
+    }
+
+    let mut indices = pinned_indices(session_id)?;
+    indices.retain(|&i| i != index);
+    if pinned {
+        indices.push(index);
+        indices.sort_unstable();
+    }
+    set_pinned_indices(session_id, indices)?;
+    log_session_event(session_id, "message_pinned", json!({ "index": index, "pinned": pinned }));
+    persist_session_snapshot(session_id, messages);
+    Ok(())
+}
+
+/// Longest session title, in characters
+const MAX_TITLE_CHARS: usize = 120;
+/// Longest session notes, in characters
+const MAX_NOTES_CHARS: usize = 4_000;
+
+/// Trimmed `text`, or None when it is blank; too long is an error
+fn session_text(kind: &str, text: &str, max_chars: usize) -> Result<Option<String>> {
+    let text = text.trim();
+    if text.chars().count() > max_chars {
+        bail!("The {} is longer than {} characters", kind, max_chars);
+    }
+    Ok(Some(text.to_string()).filter(|t| !t.is_empty()))
+}
+
+/// Give the session a title, shown in the saved sessions list; an empty title removes it
+pub async fn rename_session(namespace: &str, session_id: &str, title: &str) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    let title = session_text("title", title, MAX_TITLE_CHARS)?;
+    set_session_metadata(session_id, "title", |ctx| ctx.title = title).await
+}
+
+/// Keep notes with the session, e.g. what is left to do; empty notes remove them
+pub async fn set_session_notes(namespace: &str, session_id: &str, text: &str) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    let notes = session_text("notes", text, MAX_NOTES_CHARS)?;
+    set_session_metadata(session_id, "notes", |ctx| ctx.notes = notes).await
+}
+
+async fn set_session_metadata(session_id: &str, field: &str, update: impl FnOnce(&mut SessionContext)) -> Result<()> {
+    let inner = {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        update(ctx);
+        Arc::clone(&ctx.inner)
+    };
+    log_session_event(session_id, "session_metadata_changed", json!({ "field": field }));
+    let messages = lock_agent(&inner).await?.export_messages();
+    persist_session_snapshot(session_id, messages);
+    Ok(())
+}
+
+/// Set a variable on the session, e.g. a ticket id, for its prompt and tools
+/// (see `session_vars`); an empty value removes it
+pub async fn set_session_var(namespace: &str, session_id: &str, key: &str, value: &str) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    session_vars::validate_name(key)?;
+    if value.chars().count() > session_vars::MAX_VALUE_CHARS {
+        bail!("The value of {} is longer than {} characters", key, session_vars::MAX_VALUE_CHARS);
+    }
+    let (inner, agent_mode, environment, vars, active_skills) = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        let mut vars = ctx.vars.clone();
+        if value.is_empty() {
+            vars.remove(key);
+        } else {
+            vars.insert(key.to_string(), value.to_string());
+        }
+        if vars.len() > session_vars::MAX_VARS {
+            bail!("A session can have at most {} variables", session_vars::MAX_VARS);
+        }
+        (Arc::clone(&ctx.inner), ctx.agent_mode.clone(), ctx.environment.clone(), vars, ctx.active_skills.clone())
+    };
+    let config = AppConfig::load().context("Failed to load config")?;
+    let system_prompt =
+        system_prompt_for_session(&config, &agent_mode, environment.as_deref(), &vars, &active_skills);
+    // Under the agent lock, so a turn does not start between the prompt and the tools seeing them
+    let mut agent = inner.lock().await;
+    agent.set_system_prompt(system_prompt)?;
+    session_vars::register(session_id, vars.clone());
+    drop(agent);
+    // The value may be private, so only the name is logged
+    set_session_metadata(session_id, &format!("var:{}", key), |ctx| ctx.vars = vars).await
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SessionVar {
+    pub key: String,
+    pub value: String,
+}
+
+/// The session's variables, by name
+pub fn get_session_vars(namespace: &str, session_id: &str) -> Result<Vec<SessionVar>> {
+    let session_id = &session_key(namespace, session_id);
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx
+        .vars
+        .iter()
+        .map(|(key, value)| SessionVar {
+            key: key.clone(),
+            value: value.clone(),
+        })
+        .collect())
+}
+
+/// Enable `names` in the session, replacing the skills the user enabled
+/// before; the workspace's default skills stay active either way
+pub async fn set_session_skills(namespace: &str, session_id: &str, names: Vec<String>) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    let config = AppConfig::load().context("Failed to load config")?;
+    let available = skills::discover(&skills::dirs(&config));
+    let mut enabled: Vec<String> = Vec::new();
+    for name in names.iter().map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        if !available.contains_key(name) {
+            bail!("Skill '{}' not found", name);
+        }
+        if !enabled.iter().any(|e| e == name) {
+            enabled.push(name.to_string());
+        }
+    }
+    if enabled.len() > skills::MAX_ENABLED {
+        bail!("A session can enable at most {} skills", skills::MAX_ENABLED);
+    }
+    let (inner, agent_mode, environment, vars) = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        (Arc::clone(&ctx.inner), ctx.agent_mode.clone(), ctx.environment.clone(), ctx.vars.clone())
+    };
+    let active = skills::active(&config, &agent_mode, &enabled);
+    let system_prompt = system_prompt_for_session(&config, &agent_mode, environment.as_deref(), &vars, &active);
+    inner.lock().await.set_system_prompt(system_prompt)?;
+    set_session_metadata(session_id, "skills", |ctx| {
+        ctx.skills = enabled;
+        ctx.active_skills = active;
+    })
+    .await
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SessionSkill {
+    pub name: String,
+    pub description: String,
+    /// "user" or "workspace", where its SKILL.md is
+    pub source: String,
+    /// Whether its instructions are in the session's prompt
+    pub active: bool,
+    /// Whether the workspace config enables it, so the user cannot turn it off
+    pub default: bool,
+}
+
+/// The skills available to the session, by name
+pub fn get_session_skills(namespace: &str, session_id: &str) -> Result<Vec<SessionSkill>> {
+    let session_id = &session_key(namespace, session_id);
+    let (agent_mode, enabled) = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        (ctx.agent_mode.clone(), ctx.skills.clone())
+    };
+    let config = AppConfig::load().context("Failed to load config")?;
+    let defaults = skills::defaults(&config.skills, &agent_mode);
+    Ok(skills::discover(&skills::dirs(&config))
+        .into_values()
+        .map(|skill| {
+            let default = defaults.contains(&skill.name);
+            SessionSkill {
+                active: default || enabled.contains(&skill.name),
+                default,
+                source: skill.source.as_str().to_string(),
+                name: skill.name,
+                description: skill.description,
+            }
+        })
+        .collect())
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct DeniedTool {
+    pub tool_name: String,
+    /// The first active skill that does not allow it
+    pub skill: String,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct AllowedToolsInfo {
+    /// Whether an active skill limits the tools; when not, `allowed` has them all
+    pub restricted: bool,
+    /// The session's tools that can be called, in registration order
+    pub allowed: Vec<String>,
+    pub denied: Vec<DeniedTool>,
+}
+
+/// The session's tools that the active skills allow, and those they do not
+pub async fn get_allowed_tools(namespace: &str, session_id: &str) -> Result<AllowedToolsInfo> {
+    let session_id = &session_key(namespace, session_id);
+    let (inner, active_skills) = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        (Arc::clone(&ctx.inner), ctx.active_skills.clone())
+    };
+    let tool_names = lock_agent(&inner).await?.tool_names();
+    let (mut allowed, mut denied) = (Vec::new(), Vec::new());
+    for tool_name in tool_names {
+        match skills::check_tool_permission(&active_skills, &tool_name, None) {
+            Ok(()) => allowed.push(tool_name),
+            Err(skill) => denied.push(DeniedTool {
+                tool_name,
+                skill: skill.name.clone(),
+            }),
+        }
+    }
+    Ok(AllowedToolsInfo {
+        restricted: active_skills.iter().any(|s| s.allowed_tools.is_some()),
+        allowed,
+        denied,
+    })
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct PinnedMessage {
+    pub index: u32,
+    pub role: String,
+    pub content: String,
+}
+
+/// The pinned messages, in conversation order
+pub async fn get_pinned(namespace: &str, session_id: &str) -> Result<Vec<PinnedMessage>> {
+    let session_id = &session_key(namespace, session_id);
+    let inner = session_agent(session_id)?;
+    let indices = pinned_indices(session_id)?;
+    let agent = lock_agent(&inner).await?;
+    let messages = agent.get_messages();
+    Ok(indices
+        .into_iter()
+        .filter_map(|i| {
+            messages.get(i).map(|m| PinnedMessage {
+                index: i as u32,
+                role: m.role.clone(),
+                content: m.content.clone(),
+            })
+        })
+        .collect())
+}
+
+/// Replace the history with a summary from the auxiliary model, keeping the
+/// pinned messages verbatim ahead of it. Returns how many messages were folded in.
+pub(crate) async fn compact_history(session_id: &str, inner: &Arc<Mutex<RustAgent>>) -> Result<usize> {
+    let summarized = lock_agent(inner).await?.message_count();
+    let summary = summarize_history(inner).await?;
+
+    let pinned = pinned_indices(session_id)?;
+    let mut agent = lock_agent(inner).await?;
+    let messages = agent.export_messages();
+    if messages.len() < summarized {
+        bail!("The history changed while it was being summarized");
+    }
+    let mut compacted: Vec<Message> = pinned
+        .iter()
+        .filter(|&&i| i < summarized)
+        .map(|&i| messages[i].clone())
+        .collect();
+    let kept = compacted.len();
+    let moved_by = |i: usize| i - summarized + kept + 1;
+    let pinned_after: Vec<usize> = pinned.iter().filter(|&&i| i >= summarized).map(|&i| moved_by(i)).collect();
+    compacted.push(Message {
+        role: "user".to_string(),
+        content: format!("Summary of the conversation so far:\n\n{}", summary),
+    });
+    // Messages added by a turn that ran meanwhile are kept as they are
+    compacted.extend(messages[summarized..].iter().cloned());
+    agent.import_messages(compacted.clone());
+    drop(agent);
+
+    set_pinned_indices(session_id, (0..kept).chain(pinned_after).collect())?;
+    log_session_event(
+        session_id,
+        "history_compacted",
+        json!({ "summarized": summarized, "pinned": kept }),
+    );
+    persist_session_snapshot(session_id, compacted);
+    Ok(summarized - kept)
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct ProviderMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct AvailableModel {
+    pub provider: String,
+    pub model: String,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct ModelAlias {
+    pub name: String,
+    pub provider: String,
+    pub model: String,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SavedSessionInfo {
+    pub session_id: String,
+    pub created_at_ms: i64,
+    pub updated_at_ms: i64,
+    pub message_count: u32,
+    /// Workspace the session works in; None if saved before it was recorded
+    pub workspace: Option<String>,
+    /// Set with `rename_session`
+    pub title: Option<String>,
+    /// Set with `set_session_notes`
+    pub notes: Option<String>,
+}
+
+impl From<store::SessionMeta> for SavedSessionInfo {
+    fn from(m: store::SessionMeta) -> Self {
+        Self {
+            session_id: m.session_id,
+            created_at_ms: m.created_at_ms,
+            updated_at_ms: m.updated_at_ms,
+            message_count: m.message_count as u32,
+            workspace: m.workspace,
+            title: m.title,
+            notes: m.notes,
+        }
+    }
+}
+
+/// Which saved sessions `query_saved_sessions` returns, and in what order
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Clone, Default)]
+pub struct SavedSessionQuery {
+    /// "updated" (default), "created", "title" or "message_count"
+    pub sort_by: Option<String>,
+    /// Reverse the order: oldest, shortest or Z to A first
+    pub reverse: Option<bool>,
+    /// Only sessions that work in the workspace at this path
+    pub workspace: Option<String>,
+    /// Sessions to skip, for paging
+    pub offset: Option<u32>,
+    /// Most sessions to return; all by default
+    pub limit: Option<u32>,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SavedSessionPage {
+    pub sessions: Vec<SavedSessionInfo>,
+    /// Sessions matching the query, on all pages
+    pub total: u32,
+}
+
+/// One page of the saved sessions owned by `namespace`
+pub fn query_saved_sessions(namespace: &str, query: &SavedSessionQuery) -> Result<SavedSessionPage> {
+    let sort = query.sort_by.as_deref().map(store::SessionSort::parse).transpose()?.unwrap_or_default();
+    snapshot_writer::flush();
+    let mut metas = match &query.workspace {
+        Some(path) => store::list_saved_sessions_for_workspace(namespace, &workspace_key(Path::new(path))),
+        None => store::list_saved_sessions(namespace),
+    }
+    .context("Failed to list saved sessions")?;
+    store::sort_sessions(&mut metas, sort, query.reverse.unwrap_or(false));
+    let total = metas.len() as u32;
+    let sessions = metas
+        .into_iter()
+        .skip(query.offset.unwrap_or(0) as usize)
+        .take(query.limit.map_or(usize::MAX, |limit| limit as usize))
+        .map(SavedSessionInfo::from)
+        .collect();
+    Ok(SavedSessionPage { sessions, total })
+}
+
+/// Saved sessions owned by `namespace`
+pub fn get_saved_sessions(namespace: &str) -> Result<Vec<SavedSessionInfo>> {
+    snapshot_writer::flush();
+    let metas = store::list_saved_sessions(namespace)
+        .context("Failed to list saved sessions")?;
+    Ok(metas.into_iter().map(SavedSessionInfo::from).collect())
+}
+
+/// Saved sessions owned by `namespace` that were saved in the workspace at `path`
+pub fn list_saved_sessions_for_workspace(namespace: &str, path: &str) -> Result<Vec<SavedSessionInfo>> {
+    snapshot_writer::flush();
+    let metas = store::list_saved_sessions_for_workspace(namespace, &workspace_key(Path::new(path)))
+        .context("Failed to list saved sessions")?;
+    Ok(metas.into_iter().map(SavedSessionInfo::from).collect())
+}
+
+/// Delete a session for good: it is closed if open, its saved files (history,
+/// events, turn journal) removed, and its trash and overlay in the workspace it
+/// worked in cleared. Returns false if it was neither open nor saved.
+pub fn delete_session(namespace: &str, session_id: &str) -> Result<bool> {
+    store::validate_session_id(session_id)?;
+    let closed = close_session(namespace, session_id)?;
+    let key = session_key(namespace, session_id);
+    flush_sessions();
+    let workspace = store::load_meta(namespace, session_id).ok().flatten().and_then(|m| m.workspace);
+    let removed = store::delete_session(namespace, session_id).context("Failed to delete session")?;
+    let workspace = match workspace {
+        Some(workspace) => PathBuf::from(workspace),
+        None => std::env::current_dir().context("Failed to determine workspace root")?,
+    };
+    if workspace.is_dir() {
+        let trashed = Trash::for_workspace(&workspace).purge(Some(&key))?;
+        let overlay = Overlay::for_session(&workspace, &key);
+        if overlay.is_enabled() {
+            overlay.discard()?;
+            overlay.set_enabled(false)?;
+        }
+        log_session_event(&key, "session_trash_purged", json!({ "entries": trashed }));
+    }
+    log_session_event(&key, "session_deleted", json!({ "was_open": closed, "was_saved": removed }));
+    Ok(closed || removed)
+}
+
+/// Delete each of `session_ids` as `delete_session` does, going on past
+/// failures. Returns how many were deleted; fails if any could not be.
+pub fn delete_sessions(namespace: &str, session_ids: Vec<String>) -> Result<u32> {
+    let mut deleted = 0;
+    let mut failed = Vec::new();
+    for session_id in &session_ids {
+        match delete_session(namespace, session_id) {
+            Ok(true) => deleted += 1,
+            Ok(false) => {}
+            Err(e) => failed.push(format!("{}: {:#}", session_id, e)),
+        }
+    }
+    if !failed.is_empty() {
+        bail!("Failed to delete {} of {} sessions: {}", failed.len(), session_ids.len(), failed.join("; "));
+    }
+    Ok(deleted)
+}
+
+/// Save a copy of a session's history, modes, pins and notes under a new id,
+/// e.g. to try another approach from the same point
+pub fn duplicate_session(namespace: &str, session_id: &str) -> Result<SavedSessionInfo> {
+    snapshot_writer::flush();
+    let copy = store::duplicate_session(namespace, session_id, &generate_session_id())
+        .with_context(|| format!("Failed to duplicate session {}", session_id))?;
+    log_session_event(&session_key(namespace, session_id), "session_duplicated", json!({ "copy": copy.session_id }));
+    Ok(SavedSessionInfo::from(copy))
+}
+
+pub(crate) async fn get_history(inner: &Arc<Mutex<RustAgent>>) -> Result<Vec<ProviderMessage>> {
+    let agent = lock_agent(inner).await?;
+    Ok(agent
+        .export_messages()
+        .into_iter()
+        .map(|m| ProviderMessage {
+            role: m.role,
+            content: m.content,
+        })
+        .collect())
+}
+
+pub(crate) async fn get_available_models(inner: &Arc<Mutex<RustAgent>>) -> Result<Vec<AvailableModel>> {
+    let agent = inner.lock().await;
+    let models = agent.get_available_models();
+    Ok(models
+        .into_iter()
+        .map(|(provider, model)| AvailableModel { provider, model })
+        .collect())
+}
+
+pub(crate) async fn set_model(
+    inner: &Arc<Mutex<RustAgent>>,
+    provider: String,
+    model: String,
+) -> Result<()> {
+    let mut config =
+        AppConfig::load().context("Failed to load config")?;
+    // With no provider, `model` is a reference: an alias or a model name.
+    // It is saved as given, so an alias keeps following its config entry.
+    let (provider, model, saved) = if provider.is_empty() {
+        let (provider, resolved) = config
+            .resolve_model(&model)
+            .ok_or_else(|| anyhow!("Unknown model: {}", model))?;
+        (provider, resolved, model)
+    } else {
+        let saved = format!("{}:{}", provider, model);
+        (provider, model, saved)
+    };
+    {
+        let mut agent = inner.lock().await;
+        agent.set_model(&provider, &model)
+            ?;
+    }
+
+    config.runtime.default_model = Some(saved);
+    config
+        .save_runtime()
+        .context("Failed to save runtime config")?;
+    Ok(())
+}
+
+/// Which tool, if any, the session's model must call (Claude models only;
+/// OpenAI-compatible ones take `disable_parallel_tool_use`)
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct ToolChoiceSettings {
+    /// "auto", "any" (some tool), "tool" (`tool_name`) or "none"; a forced
+    /// call applies to the reply to each prompt, not to later rounds
+    pub mode: String,
+    pub tool_name: Option<String>,
+    /// At most one tool call per response
+    pub disable_parallel_tool_use: bool,
+}
+
+pub(crate) async fn set_tool_choice(inner: &Arc<Mutex<RustAgent>>, settings: ToolChoiceSettings) -> Result<()> {
+    let choice = ToolChoice::parse(&settings.mode, settings.tool_name.as_deref())?;
+    inner.lock().await.set_tool_use(ToolUseOptions {
+        choice,
+        disable_parallel_tool_use: settings.disable_parallel_tool_use,
+    })
+}
+
+pub(crate) async fn get_tool_choice(inner: &Arc<Mutex<RustAgent>>) -> ToolChoiceSettings {
+    let agent = inner.lock().await;
+    let tool_use = agent.tool_use();
+    ToolChoiceSettings {
+        mode: tool_use.choice.mode().to_string(),
+        tool_name: match &tool_use.choice {
+            ToolChoice::Tool(name) => Some(name.clone()),
+            _ => None,
+        },
+        disable_parallel_tool_use: tool_use.disable_parallel_tool_use,
+    }
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct LatencyInfo {
+    pub latency_ms: u32,
+    pub model_name: String,
+}
+
+pub(crate) async fn check_latency(inner: &Arc<Mutex<RustAgent>>) -> Result<LatencyInfo> {
+    let (base_url, model_name) = {
+        let agent = inner.lock().await;
+        (agent.get_base_url(), agent.get_model_name())
+    };
+    
+    let ms = measure_latency(&base_url).await?;
+    Ok(LatencyInfo {
+        latency_ms: ms as u32,
+        model_name,
+    })
+}
+
+/// Run an internal task on the model routed to it (see `llm::auxiliary`)
+async fn run_auxiliary(inner: &Arc<Mutex<RustAgent>>, task: AuxTask, input: String) -> Result<String> {
+    let (providers, session_model) = {
+        let agent = inner.lock().await;
+        (
+            agent.provider_configs().to_vec(),
+            (agent.get_provider_name(), agent.get_model_name()),
+        )
+    };
+    let mut config = AppConfig::load().context("Failed to load config")?;
+    // The agent's providers include a legacy `[llm_provider]`
+    config.providers = providers;
+    let model = auxiliary::model_for(&config, task, session_model);
+    auxiliary::complete(&config.providers, model, task, &input).await
+}
+
+/// The user and assistant messages as "role: content" lines, the first `limit` of them
+async fn conversation_text(inner: &Arc<Mutex<RustAgent>>, limit: usize) -> Result<String> {
+    let agent = lock_agent(inner).await?;
+    Ok(agent
+        .get_messages()
+        .iter()
+        .filter(|m| (m.role == "user" || m.role == "assistant") && !m.content.trim().is_empty())
+        .take(limit)
+        .map(|m| format!("{}: {}", m.role, m.content.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n"))
+}
+
+pub(crate) async fn generate_title(inner: &Arc<Mutex<RustAgent>>) -> Result<String> {
+    let text = conversation_text(inner, 4).await?;
+    if text.is_empty() {
+        bail!("The conversation is empty");
+    }
+    let title = run_auxiliary(inner, AuxTask::Title, text).await?;
+    let title = title.lines().next().unwrap_or_default();
+    Ok(title.trim_matches(|c: char| c == '"' || c == '\'' || c == '.').trim().to_string())
+}
+
+pub(crate) async fn summarize_history(inner: &Arc<Mutex<RustAgent>>) -> Result<String> {
+    let text = conversation_text(inner, usize::MAX).await?;
+    if text.is_empty() {
+        bail!("The conversation is empty");
+    }
+    run_auxiliary(inner, AuxTask::Summary, text).await
+}
+
+/// Commit message for the staged changes, or for all uncommitted ones if nothing is staged
+pub(crate) async fn generate_commit_message(inner: &Arc<Mutex<RustAgent>>) -> Result<String> {
+    let mut diff = git_diff(&["diff", "--cached"]).await?;
+    if diff.trim().is_empty() {
+        diff = git_diff(&["diff", "HEAD"]).await?;
+    }
+    if diff.trim().is_empty() {
+        bail!("There are no changes to commit");
+    }
+    run_auxiliary(inner, AuxTask::CommitMessage, diff).await
+}
+
+async fn git_diff(args: &[&str]) -> Result<String> {
+    let output = tokio::process::Command::new("git")
+        .args(args)
+        .output()
+        .await
+        .context("Failed to run git")?;
+    if !output.status.success() {
+        bail!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Open sessions owned by `namespace`
+pub fn get_sessions(namespace: &str) -> Result<Vec<String>> {
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    Ok(manager.list_ids_in(namespace))
+}
+
+pub(crate) fn get_agent_mode(session_id: &str) -> Result<String> {
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx.agent_mode.to_string())
+}
+
+pub(crate) async fn set_agent_mode(
+    session_id: &str,
+    inner: &Arc<Mutex<RustAgent>>,
+    mode: String,
+) -> Result<()> {
+    let agent_mode = AgentMode::from(mode);
+    let (approval_mode, environment, vars, enabled_skills) = {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.agent_mode = agent_mode.clone();
+        (ctx.approval_mode.clone(), ctx.environment.clone(), ctx.vars.clone(), ctx.skills.clone())
+    };
+
+    let mut config =
+        AppConfig::load().context("Failed to load config")?;
+    // The mode's default skills change with it
+    let active_skills = skills::active(&config, &agent_mode, &enabled_skills);
+    let system_prompt =
+        system_prompt_for_session(&config, &agent_mode, environment.as_deref(), &vars, &active_skills);
+    {
+        let mut agent = inner.lock().await;
+        agent
+            .set_system_prompt(system_prompt)
+            ?;
+    }
+    if let Some(ctx) = SESSION_MANAGER.lock().ok().as_mut().and_then(|m| m.get_mut(session_id)) {
+        ctx.active_skills = active_skills;
+    }
+
+    let saved = runtime_session(&mut config, session_id);
+    saved.agent_mode = agent_mode.to_string();
+    saved.approval_mode = approval_mode.to_string();
+    config
+        .save_runtime()
+        .context("Failed to save runtime config")?;
+    Ok(())
+}
+
+/// The session's entry in the runtime config, added with the default modes if missing
+fn runtime_session<'a>(config: &'a mut AppConfig, session_id: &str) -> &'a mut RuntimeSessionConfig {
+    let (namespace, public_id) = split_session_key(session_id);
+    let sessions = &mut config.runtime.sessions;
+    let index = match sessions.iter().position(|s| s.namespace == namespace && s.session_id == public_id) {
+        Some(index) => index,
+        None => {
+            sessions.push(RuntimeSessionConfig {
+                session_id: public_id.to_string(),
+                namespace: namespace.to_string(),
+                agent_mode: AgentMode::default().to_string(),
+                approval_mode: ApprovalMode::default().to_string(),
+                execution_target: None,
+            });
+            sessions.len() - 1
+        }
+    };
+    &mut sessions[index]
+}
+
+pub fn set_theme(theme: String) -> Result<()> {
+    let mut config = AppConfig::load().context("Failed to load config")?;
+    config.runtime.theme = Some(theme);
+    config.save_runtime().context("Failed to save runtime config")?;
+    Ok(())
+}
+
+pub(crate) fn get_approval_mode(session_id: &str) -> Result<String> {
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx.approval_mode.to_string())
+}
+
+pub(crate) fn set_approval_mode(session_id: &str, mode: String) -> Result<()> {
+    let mode = ApprovalMode::from(mode);
+    {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.approval_mode = mode.clone();
+    }
+
+    let mut config =
+        AppConfig::load().context("Failed to load config")?;
+    runtime_session(&mut config, session_id).approval_mode = mode.to_string();
+    config
+        .save_runtime()
+        .context("Failed to save runtime config")?;
+    Ok(())
+}
+
+/// Where the session's tools run: "local", "ssh", "docker" or "devcontainer"
+pub(crate) fn get_execution_target(session_id: &str) -> Result<String> {
+    SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(backend::for_session(session_id)
+        .map(|target| target.kind())
+        .unwrap_or("local")
+        .to_string())
+}
+
+/// Move the session's tools to another target, using the `[remote]` settings for it
+pub(crate) fn set_execution_target(session_id: &str, target: String) -> Result<()> {
+    SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    let kind = backend::parse_target(&target)?;
+    let mut config = AppConfig::load().context("Failed to load config")?;
+    match backend::create(kind, &config.remote, Path::new("."))? {
+        Some(backend) => {
+            log_session_event(session_id, "remote_backend", json!({ "target": backend.name() }));
+            backend::register(session_id, backend);
+        }
+        None => backend::unregister(session_id),
+    }
+
+    let target = target.trim().to_string();
+    runtime_session(&mut config, session_id).execution_target = Some(target);
+    config
+        .save_runtime()
+        .context("Failed to save runtime config")?;
+    Ok(())
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SyncStatusInfo {
+    /// Path relative to the workspace
+    pub path: String,
+    /// "in_sync" | "local_ahead" | "remote_ahead" | "conflict"
+    pub state: String,
+    /// When the file was last pushed or pulled
+    pub synced_at_ms: Option<i64>,
+}
+
+impl From<sync::SyncStatus> for SyncStatusInfo {
+    fn from(status: sync::SyncStatus) -> Self {
+        Self {
+            path: status.path,
+            state: status.state.as_str().to_string(),
+            synced_at_ms: status.synced_at_ms,
+        }
+    }
+}
+
+/// Copy files from the workspace to the session's execution target. A file
+/// changed on the target since it was last synced is refused unless `force`.
+pub async fn push_files(
+    namespace: &str,
+    session_id: &str,
+    paths: Vec<String>,
+    force: bool,
+) -> Result<Vec<SyncStatusInfo>> {
+    let session_id = &session_key(namespace, session_id);
+    sync_files(session_id, paths, move |target, path| sync::push(target, path, force)).await
+}
+
+/// Copy files from the session's execution target into the workspace. A file
+/// changed locally since it was last synced is refused unless `force`.
+pub async fn pull_files(
+    namespace: &str,
+    session_id: &str,
+    paths: Vec<String>,
+    force: bool,
+) -> Result<Vec<SyncStatusInfo>> {
+    let session_id = &session_key(namespace, session_id);
+    sync_files(session_id, paths, move |target, path| sync::pull(target, path, force)).await
+}
+
+/// Sync state of the given files, or of every file synced so far when `paths` is empty
+pub async fn get_sync_status(namespace: &str, session_id: &str, paths: Vec<String>) -> Result<Vec<SyncStatusInfo>> {
+    let session_id = &session_key(namespace, session_id);
+    let target = session_target(session_id)?;
+    let paths = resolve_workspace_paths(session_id, &paths)?;
+    let statuses = tokio::task::spawn_blocking(move || {
+        let paths: Vec<&Path> = paths.iter().map(PathBuf::as_path).collect();
+        sync::status(target.as_ref(), &paths)
+    })
+    .await??;
+    Ok(statuses.into_iter().map(SyncStatusInfo::from).collect())
+}
+
+/// Run `op` on each path with the session's target, off the async runtime.
+/// Stops at the first failure; the files before it stay synced.
+async fn sync_files(
+    session_id: &str,
+    paths: Vec<String>,
+    op: impl Fn(&dyn backend::ExecBackend, &Path) -> Result<sync::SyncStatus> + Send + 'static,
+) -> Result<Vec<SyncStatusInfo>> {
+    let target = session_target(session_id)?;
+    let paths = resolve_workspace_paths(session_id, &paths)?;
+    let statuses = tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| op(target.as_ref(), path))
+            .collect::<Result<Vec<_>>>()
+    })
+    .await??;
+    log_session_event(
+        session_id,
+        "files_synced",
+        json!({ "paths": statuses.iter().map(|s| s.path.clone()).collect::<Vec<_>>() }),
+    );
+    Ok(statuses.into_iter().map(SyncStatusInfo::from).collect())
+}
+
+fn session_target(session_id: &str) -> Result<Arc<dyn backend::ExecBackend>> {
+    backend::for_session(session_id)
+        .ok_or_else(|| anyhow!("Session {} runs on the local workspace; there is no target to sync with", session_id))
+}
+
+fn resolve_workspace_paths(session_id: &str, paths: &[String]) -> Result<Vec<PathBuf>> {
+    let policy = PathPolicy::for_session(session_id)?;
+    paths.iter().map(|p| policy.resolve(p)).collect()
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct TrashEntryInfo {
+    pub id: String,
+    pub original_path: String,
+    pub is_dir: bool,
+    pub deleted_at_ms: i64,
+}
+
+impl From<TrashEntry> for TrashEntryInfo {
+    fn from(entry: TrashEntry) -> Self {
+        Self {
+            id: entry.id,
+            original_path: entry.original_path,
+            is_dir: entry.is_dir,
+            deleted_at_ms: entry.deleted_at_ms,
+        }
+    }
+}
+
+fn workspace_trash() -> Result<Trash> {
+    Trash::current()
+}
+
+/// Paths deleted in a session, oldest first
+pub fn list_trash(namespace: &str, session_id: &str) -> Result<Vec<TrashEntryInfo>> {
+    let session_id = &session_key(namespace, session_id);
+    Ok(workspace_trash()?
+        .list(session_id)
+        .into_iter()
+        .map(TrashEntryInfo::from)
+        .collect())
+}
+
+/// Move a deleted path back to its original location
+pub fn restore_from_trash(namespace: &str, session_id: &str, entry_id: &str) -> Result<TrashEntryInfo> {
+    let session_id = &session_key(namespace, session_id);
+    let entry = workspace_trash()?
+        .restore(session_id, entry_id)
+        ?;
+    log_session_event(
+        session_id,
+        "trash_restored",
+        json!({ "id": entry.id.clone(), "original_path": entry.original_path.clone() }),
+    );
+    Ok(entry.into())
+}
+
+/// Permanently remove trashed paths of a session, or of every session of
+/// `namespace` when `session_id` is `None`. Returns the number of entries removed.
+pub fn purge_trash(namespace: &str, session_id: Option<&str>) -> Result<u32> {
+    store::validate_namespace(namespace)?;
+    let target = match session_id {
+        Some(session_id) => session_key(namespace, session_id),
+        None => namespace.to_string(),
+    };
+    let removed = workspace_trash()?
+        .purge(Some(&target))
+        ?;
+    log_session_event(
+        &target,
+        "trash_purged",
+        json!({ "entries": removed }),
+    );
+    Ok(removed as u32)
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct OverlayChangeInfo {
+    /// Path relative to the workspace
+    pub path: String,
+    /// "write" | "delete" | "mkdir"
+    pub change: String,
+    /// Unified diff against the workspace, for text file writes
+    pub diff: Option<String>,
+    /// The workspace file changed since the overlay copied it
+    pub conflict: bool,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct OverlayMaterializeResult {
+    pub applied: Vec<String>,
+    /// Changes kept in the overlay because the workspace changed meanwhile
+    pub conflicts: Vec<String>,
+}
+
+fn workspace_overlay(session_id: &str) -> Result<Overlay> {
+    let workspace = std::env::current_dir()
+        .and_then(std::fs::canonicalize)
+        .context("Failed to determine workspace")?;
+    Ok(Overlay::for_session(&workspace, session_id))
+}
+
+/// Switch copy-on-write overlay mode on or off for a session
+pub fn set_overlay_mode(namespace: &str, session_id: &str, enabled: bool) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    workspace_overlay(session_id)?
+        .set_enabled(enabled)
+        ?;
+    log_session_event(session_id, "overlay_mode", json!({ "enabled": enabled }));
+    Ok(())
+}
+
+/// Let the session's edits of paths matching `globs` run without confirmation
+/// in read-only mode; an empty list clears the scope
+pub fn set_auto_accept_paths(namespace: &str, session_id: &str, globs: Vec<String>) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    let scope = if globs.is_empty() {
+        None
+    } else {
+        let policy = PathPolicy::for_session(session_id)?;
+        Some(Arc::new(AutoAcceptScope::new(policy.root(), globs.clone())?))
+    };
+    {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get_mut(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.auto_accept = scope;
+    }
+    log_session_event(session_id, "auto_accept_paths", json!({ "globs": globs }));
+    Ok(())
+}
+
+pub fn get_auto_accept_paths(namespace: &str, session_id: &str) -> Result<Vec<String>> {
+    let session_id = &session_key(namespace, session_id);
+    let manager = SESSION_MANAGER
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock session manager"))?;
+    let ctx = manager
+        .get(session_id)
+        .ok_or_else(|| anyhow!("Session not found"))?;
+    Ok(ctx.auto_accept.as_ref().map(|s| s.globs().to_vec()).unwrap_or_default())
+}
+
+/// Length limits of one model response, in characters and estimated tokens; 0 is no limit
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct OutputLimitSettings {
+    pub max_chars: u32,
+    pub max_tokens: u32,
+}
+
+/// Stop the session's responses past these limits instead of `[sessions]`
+/// `max_output_chars` / `max_output_tokens`; None goes back to those
+pub fn set_output_limit(namespace: &str, session_id: &str, limit: Option<OutputLimitSettings>) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    let limit = limit.map(|l| OutputLimit {
+        max_chars: u64::from(l.max_chars),
+        max_tokens: u64::from(l.max_tokens),
+    });
+    {
+        let mut manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager.get_mut(session_id).ok_or_else(|| anyhow!("Session not found"))?;
+        ctx.output_limit = limit;
+    }
+    log_session_event(
+        session_id,
+        "output_limit",
+        json!({ "max_chars": limit.map(|l| l.max_chars), "max_tokens": limit.map(|l| l.max_tokens) }),
+    );
+    Ok(())
+}
+
+/// The limits the session's next turn applies
+pub fn get_output_limit(namespace: &str, session_id: &str) -> Result<OutputLimitSettings> {
+    let session_id = &session_key(namespace, session_id);
+    let limit = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        manager.get(session_id).ok_or_else(|| anyhow!("Session not found"))?.output_limit
+    };
+    let limit = match limit {
+        Some(limit) => limit,
+        None => OutputLimit::from_config(&AppConfig::load().context("Failed to load config")?.sessions),
+    };
+    Ok(OutputLimitSettings {
+        max_chars: u32::try_from(limit.max_chars).unwrap_or(u32::MAX),
+        max_tokens: u32::try_from(limit.max_tokens).unwrap_or(u32::MAX),
+    })
+}
+
+/// Hold the session's edit, delete and move calls back as proposals, or run
+/// them again. Proposals already made stay pending either way.
+pub fn set_propose_mode(namespace: &str, session_id: &str, enabled: bool) -> Result<()> {
+    let session_id = &session_key(namespace, session_id);
+    with_proposals(session_id, |p| p.enabled = enabled).ok_or_else(|| anyhow!("Session not found"))?;
+    log_session_event(session_id, "propose_mode", json!({ "enabled": enabled }));
+    Ok(())
+}
+
+pub fn get_propose_mode(namespace: &str, session_id: &str) -> Result<bool> {
+    let session_id = &session_key(namespace, session_id);
+    with_proposals(session_id, |p| p.enabled).ok_or_else(|| anyhow!("Session not found"))
+}
+
+/// Proposals of the session waiting for review, oldest first
+pub fn get_proposals(namespace: &str, session_id: &str) -> Result<Vec<CoreProposal>> {
+    let session_id = &session_key(namespace, session_id);
+    with_proposals(session_id, |p| p.pending().map(|p| p.to_core()).collect()).ok_or_else(|| anyhow!("Session not found"))
+}
+
+/// Apply pending proposals, all of them if `ids` is empty, in the order they
+/// were proposed. A proposal that fails is reported and the rest still run.
+pub async fn apply_proposals(namespace: &str, session_id: &str, ids: Vec<String>) -> Result<Vec<CoreProposal>> {
+    let session_id = &session_key(namespace, session_id);
+    let (selected, access_level) = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let ctx = manager
+            .get(session_id)
+            .ok_or_else(|| anyhow!("Session not found"))?;
+        let access_level = if matches!(ctx.approval_mode, ApprovalMode::AgentFull) {
+            ToolAccessLevel::Full
+        } else {
+            ToolAccessLevel::Workspace
+        };
+        (ctx.proposals.select(&ids)?, access_level)
+    };
+    let inner = session_agent(session_id)?;
+    let mut outcomes = Vec::with_capacity(selected.len());
+    for proposal in selected {
+        let tool = lock_agent(&inner).await?.find_tool(&proposal.tool_name).map(|t| t.clone_box());
+        let outcome = match tool {
+            None => Err(format!("Tool '{}' is no longer available", proposal.tool_name)),
+            Some(tool) => {
+                let result = run_tool_in_session(session_id, access_level, tool.as_ref(), &proposal.arguments, None).await;
+                if tool_call_succeeded(&result) {
+                    Ok(())
+                } else {
+                    Err(proposal_error(&result))
+                }
+            }
+        };
+        let resolved = with_proposals(session_id, |p| p.resolve(&proposal.id, outcome)).flatten();
+        outcomes.push(resolved.unwrap_or(proposal).to_core());
+    }
+    log_session_event(
+        session_id,
+        "proposals_applied",
+        json!({
+            "applied": outcomes.iter().filter(|p| p.status == "applied").map(|p| p.id.clone()).collect::<Vec<_>>(),
+            "failed": outcomes.iter().filter(|p| p.status == "failed").map(|p| p.id.clone()).collect::<Vec<_>>()
+        }),
+    );
+    Ok(outcomes)
+}
+
+/// Why applying a proposal failed, from its tool result
+fn proposal_error(result: &anyhow::Result<String>) -> String {
+    let raw = match result {
+        Ok(raw) => raw,
+        Err(e) => return format!("{:#}", e),
+    };
+    serde_json::from_str::<serde_json::Value>(raw)
+        .ok()
+        .and_then(|v| v.get("stderr").and_then(|s| s.as_str()).map(str::to_string))
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| truncate_utf8_with_ellipsis(raw, 200))
+}
+
+/// Drop pending proposals, all of them if `ids` is empty. Returns the number discarded.
+pub fn discard_proposals(namespace: &str, session_id: &str, ids: Vec<String>) -> Result<u32> {
+    let session_id = &session_key(namespace, session_id);
+    let discarded = with_proposals(session_id, |p| p.discard(&ids)).ok_or_else(|| anyhow!("Session not found"))?;
+    log_session_event(session_id, "proposals_discarded", json!({ "ids": ids, "discarded": discarded }));
+    Ok(discarded as u32)
+}
+
+pub fn get_overlay_mode(namespace: &str, session_id: &str) -> Result<bool> {
+    let session_id = &session_key(namespace, session_id);
+    Ok(workspace_overlay(session_id)?.is_enabled())
+}
+
+/// Changes a session has made in its overlay
+pub fn get_overlay_changes(namespace: &str, session_id: &str) -> Result<Vec<OverlayChangeInfo>> {
+    let session_id = &session_key(namespace, session_id);
+    Ok(workspace_overlay(session_id)?
+        .changes()
+        .into_iter()
+        .map(|c| OverlayChangeInfo {
+            path: c.path,
+            change: serde_json::to_value(c.change)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            diff: c.diff,
+            conflict: c.conflict,
+        })
+        .collect())
+}
+
+/// Apply a session's overlay changes to the workspace
+pub fn materialize_changes(namespace: &str, session_id: &str) -> Result<OverlayMaterializeResult> {
+    let session_id = &session_key(namespace, session_id);
+    let report = workspace_overlay(session_id)?
+        .materialize()
+        ?;
+    log_session_event(
+        session_id,
+        "overlay_materialized",
+        json!({ "applied": report.applied.clone(), "conflicts": report.conflicts.clone() }),
+    );
+    Ok(OverlayMaterializeResult {
+        applied: report.applied,
+        conflicts: report.conflicts,
+    })
+}
+
+/// Drop a session's overlay changes. Returns the number discarded.
+pub fn discard_changes(namespace: &str, session_id: &str) -> Result<u32> {
+    let session_id = &session_key(namespace, session_id);
+    let discarded = workspace_overlay(session_id)?
+        .discard()
+        ?;
+    log_session_event(session_id, "overlay_discarded", json!({ "changes": discarded }));
+    Ok(discarded as u32)
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct WorkspaceTrustInfo {
+    /// Canonical workspace path the decision is stored under
+    pub path: String,
+    /// "trusted" | "untrusted", or None if the workspace was never decided
+    pub level: Option<String>,
+    /// Whether the workspace has a project config (`.carry/carrycode.json`)
+    pub has_project_config: bool,
+}
+
+fn workspace_trust_info(config: &AppConfig, workspace: &Path) -> WorkspaceTrustInfo {
+    WorkspaceTrustInfo {
+        path: workspace_key(workspace),
+        level: config.runtime.workspace_trust(workspace).map(str::to_string),
+        has_project_config: workspace.join(".carry").join("carrycode.json").exists(),
+    }
+}
+
+/// Trust decision for a workspace (defaults to the current working directory)
+pub fn get_workspace_trust(path: Option<&str>) -> Result<WorkspaceTrustInfo> {
+    let config = AppConfig::load().context("Failed to load config")?;
+    Ok(workspace_trust_info(&config, Path::new(path.unwrap_or("."))))
+}
+
+/// Record whether a workspace is trusted
+///
+/// Project config, and the MCP servers it declares, are only loaded for
+/// trusted workspaces. The decision applies to sessions opened afterwards.
+pub fn trust_workspace(path: &str, level: &str) -> Result<WorkspaceTrustInfo> {
+    if level != TRUST_LEVEL_TRUSTED && level != TRUST_LEVEL_UNTRUSTED {
+        bail!(
+            "Invalid trust level '{}', expected '{}' or '{}'",
+            level, TRUST_LEVEL_TRUSTED, TRUST_LEVEL_UNTRUSTED
+        );
+    }
+    let workspace = Path::new(path);
+    if !workspace.is_dir() {
+        bail!("Workspace not found: {}", path);
+    }
+    let mut config = AppConfig::load().context("Failed to load config")?;
+    config.runtime.set_workspace_trust(workspace, level);
+    config.save_runtime().context("Failed to save runtime config")?;
+    log::info!("Workspace {} marked as {}", workspace_key(workspace), level);
+    Ok(workspace_trust_info(&config, workspace))
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct LspServerStatus {
+    /// Workspace root the server was started for
+    pub workspace: String,
+    pub name: String,
+    /// "running" | "unresponsive" | "exited" | "restarting" | "failed"
+    pub state: String,
+    /// Restart attempts so far
+    pub restarts: u32,
+    /// Why the server last failed to start or was restarted
+    pub last_error: Option<String>,
+    /// Documents open in the server
+    pub open_files: u32,
+}
+
+/// Health of the language servers started by the LSP tools
+///
+/// Servers start on the first LSP tool call, so this is empty before that.
+pub async fn get_lsp_status() -> Vec<LspServerStatus> {
+    crate::lsp::LspManager::shared_statuses()
+        .await
+        .into_iter()
+        .flat_map(|(workspace, servers)| {
+            servers.into_iter().map(move |server| LspServerStatus {
+                workspace: workspace.clone(),
+                name: server.name,
+                state: server.state,
+                restarts: server.restarts,
+                last_error: server.last_error,
+                open_files: server.open_files as u32,
+            })
+        })
+        .collect()
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SessionStatusInfo {
+    pub session_id: String,
+    pub namespace: String,
+    /// Whether a host is subscribed to the session's events
+    pub subscribed: bool,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct McpServerStatus {
+    pub name: String,
+    /// "running" | "failed"
+    pub state: String,
+    pub tool_count: u32,
+    pub last_error: Option<String>,
+    /// Restarts after a crash or timeout
+    pub restarts: u32,
+    /// Live processes of the server, one per session that started it
+    pub processes: u32,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct SubsystemError {
+    /// "llm" | "mcp" | "lsp" | "shell" | "persistence" | "telemetry" | "notifier"
+    pub subsystem: String,
+    pub message: String,
+    pub ts_ms: i64,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct CoreStatus {
+    /// Version of the core library
+    pub version: String,
+    pub event_protocol_version: u32,
+    pub snapshot_version: u32,
+    pub active_sessions: u32,
+    pub sessions: Vec<SessionStatusInfo>,
+    pub mcp_servers: Vec<McpServerStatus>,
+    pub lsp_servers: Vec<LspServerStatus>,
+    /// Whether any session's bash shell has been started, and whether they all still run
+    pub shell_started: bool,
+    pub shell_alive: bool,
+    /// Most recent error of each subsystem that has reported one
+    pub last_errors: Vec<SubsystemError>,
+    /// Set when a server failed, or the shell died, so the host can flag it
+    pub degraded: bool,
+}
+
+/// Versions, sessions and the health of each subsystem, for a diagnostics view
+pub async fn get_core_status() -> Result<CoreStatus> {
+    let sessions: Vec<SessionStatusInfo> = {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        let mut sessions: Vec<SessionStatusInfo> = manager
+            .all()
+            .map(|ctx| SessionStatusInfo {
+                session_id: ctx.session_id.clone(),
+                namespace: ctx.namespace.clone(),
+                subscribed: ctx.event_sink.lock().map(|s| s.is_some()).unwrap_or(false),
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.session_id.cmp(&b.session_id));
+        sessions
+    };
+
+    let processes = mcp_process::running_by_server();
+    let mcp_servers: Vec<McpServerStatus> = crate::llm::mcps::server_states()
+        .into_iter()
+        .map(|(name, s)| McpServerStatus {
+            processes: processes.get(&name).copied().unwrap_or(0) as u32,
+            name,
+            state: s.state.to_string(),
+            tool_count: s.tool_count as u32,
+            last_error: s.last_error,
+            restarts: s.restarts,
+        })
+        .collect();
+    let lsp_servers = get_lsp_status().await;
+    let shell = shell_alive();
+    let last_errors: Vec<SubsystemError> = health::last_errors()
+        .into_iter()
+        .map(|(subsystem, e)| SubsystemError {
+            subsystem: subsystem.as_str().to_string(),
+            message: e.message,
+            ts_ms: e.ts_ms,
+        })
+        .collect();
+
+    let degraded = mcp_servers.iter().any(|s| s.state != "running")
+        || lsp_servers.iter().any(|s| s.state != "running")
+        || shell == Some(false);
+    Ok(CoreStatus {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        event_protocol_version: CORE_EVENT_PROTOCOL_VERSION as u32,
+        snapshot_version: store::SESSION_SNAPSHOT_VERSION as u32,
+        active_sessions: sessions.len() as u32,
+        sessions,
+        mcp_servers,
+        lsp_servers,
+        shell_started: shell.is_some(),
+        shell_alive: shell.unwrap_or(false),
+        last_errors,
+        degraded,
+    })
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct PreflightFinding {
+    /// "config_dir" | "data_dir" | "log_dir" | "executable:<name>" | "provider" | "clock" | "disk"
+    pub check: String,
+    /// "ok" | "warning" | "error" | "skipped"
+    pub status: String,
+    pub message: String,
+    /// What the user can do about it
+    pub remediation: Option<String>,
+}
+
+/// Environment checks: writable data directories, bash/rg/git, provider
+/// reachability, clock skew and free disk space
+///
+/// The checks run once per process, when the first session opens; this
+/// returns that run's findings, waiting for it or running it if needed.
+pub async fn run_preflight() -> Result<Vec<PreflightFinding>> {
+    let config = AppConfig::load().context("Failed to load config")?;
+    let findings = tokio::task::spawn_blocking(move || preflight::run_once(&config))
+        .await
+        .context("Preflight checks failed")?;
+    Ok(findings
+        .into_iter()
+        .map(|f| PreflightFinding {
+            check: f.check,
+            status: f.status.as_str().to_string(),
+            message: f.message,
+            remediation: f.remediation,
+        })
+        .collect())
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct ShellStateInfo {
+    /// Working directory of the session's persistent bash shell
+    pub cwd: String,
+    /// Exported variables added or changed since the shell was started
+    pub env_changes: HashMap<String, String>,
+}
+
+/// Working directory and environment changes of the session's bash shell
+///
+/// Before the session's first bash command runs, this reports the process cwd.
+pub fn get_shell_state(namespace: &str, session_id: &str) -> Result<ShellStateInfo> {
+    let session_id = &session_key(namespace, session_id);
+    {
+        let manager = SESSION_MANAGER
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock session manager"))?;
+        if manager.get(session_id).is_none() {
+            bail!("Session not found");
+        }
+    }
+
+    let state = match shell_state(session_id) {
+        Some(state) => state,
+        None => ShellState {
+            cwd: std::env::current_dir()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            ..Default::default()
+        },
+    };
+    Ok(ShellStateInfo {
+        cwd: state.cwd,
+        env_changes: state.env_changes.into_iter().collect(),
+    })
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct CommandResult {
+    /// Whether the input was recognized as a slash command
+    pub handled: bool,
+    pub command: Option<String>,
+    pub success: bool,
+    pub message: String,
+    /// Frontend-only action to perform (e.g. "exit")
+    pub action: Option<String>,
+    /// Command-specific payload as a JSON string
+    pub data: Option<String>,
+}
+
+impl CommandResult {
+    fn not_a_command() -> Self {
+        Self {
+            handled: false,
+            command: None,
+            success: false,
+            message: String::new(),
+            action: None,
+            data: None,
+        }
+    }
+
+    fn ok(command: &str, message: impl Into<String>) -> Self {
+        Self {
+            handled: true,
+            command: Some(command.to_string()),
+            success: true,
+            message: message.into(),
+            action: None,
+            data: None,
+        }
+    }
+
+    fn fail(command: &str, message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            ..Self::ok(command, message)
+        }
+    }
+
+    fn with_data(mut self, data: serde_json::Value) -> Self {
+        self.data = Some(data.to_string());
+        self
+    }
+}
+
+fn resolve_model_arg(models: &[(String, String)], arg: &str) -> Option<(String, String)> {
+    if let Some((provider, model)) = arg.split_once(':') {
+        return models
+            .iter()
+            .find(|(p, m)| p == provider && m == model)
+            .cloned();
+    }
+    models.iter().find(|(_, m)| m == arg).cloned()
+}
+
+pub async fn dispatch_command(namespace: &str, session_id: &str, input: &str) -> Result<CommandResult> {
+    let (public_id, session_id) = (session_id, &session_key(namespace, session_id));
+    let Some(command) = parse_slash_command(input) else {
+        return Ok(CommandResult::not_a_command());
+    };
+    let inner = session_agent(session_id)?;
+    let name = command.name().to_string();
+    log_session_event(session_id, "command_dispatched", json!({ "command": name }));
+
+    let result = match command {
+        SlashCommand::Model(None) => {
+            let (current, models) = {
+                let agent = inner.lock().await;
+                (
+                    format!("{}:{}", agent.get_provider_name(), agent.get_model_name()),
+                    agent.get_available_models(),
+                )
+            };
+            CommandResult::ok(&name, format!("Current model: {}", current)).with_data(json!({
+                "current": current,
+                "models": models
+                    .iter()
+                    .map(|(p, m)| json!({ "provider": p, "model": m }))
+                    .collect::<Vec<_>>()
+            }))
+        }
+        SlashCommand::Model(Some(arg)) if AppConfig::load()?.models.aliases.contains_key(&arg) => {
+            match set_model(&inner, String::new(), arg.clone()).await {
+                Ok(()) => {
+                    let agent = inner.lock().await;
+                    CommandResult::ok(&name, format!("Switched model to {}", arg)).with_data(json!({
+                        "alias": arg,
+                        "provider": agent.get_provider_name(),
+                        "model": agent.get_model_name(),
+                    }))
+                }
+                Err(e) => CommandResult::fail(&name, format!("Failed to switch model: {}", e)),
+            }
+        }
+        SlashCommand::Model(Some(arg)) => {
+            let models = get_available_models(&inner).await?;
+            let models: Vec<(String, String)> =
+                models.into_iter().map(|m| (m.provider, m.model)).collect();
+            match resolve_model_arg(&models, &arg) {
+                Some((provider, model)) => match set_model(&inner, provider.clone(), model.clone()).await {
+                    Ok(()) => CommandResult::ok(&name, format!("Switched model to {}", model))
+                        .with_data(json!({ "provider": provider, "model": model })),
+                    Err(e) => CommandResult::fail(&name, format!("Failed to switch model: {}", e)),
+                },
+                None => CommandResult::fail(&name, format!("Unknown model: {}", arg)),
+            }
+        }
+        SlashCommand::Clear => match clear_history(session_id, &inner).await {
+            Ok(()) => CommandResult::ok(&name, "Conversation history cleared"),
+            Err(e) => CommandResult::fail(&name, format!("Failed to clear history: {}", e)),
+        },
+        SlashCommand::Mode(None) => {
+            let mode = get_agent_mode(session_id)?;
+            CommandResult::ok(&name, format!("Agent mode: {}", mode)).with_data(json!({ "mode": mode }))
+        }
+        SlashCommand::Mode(Some(arg)) => match arg.to_lowercase().as_str() {
+            "plan" | "build" => match set_agent_mode(session_id, &inner, arg.to_lowercase()).await {
+                Ok(()) => CommandResult::ok(&name, format!("Switched agent mode to {}", arg.to_lowercase()))
+                    .with_data(json!({ "mode": arg.to_lowercase() })),
+                Err(e) => CommandResult::fail(&name, format!("Failed to switch agent mode: {}", e)),
+            },
+            _ => CommandResult::fail(&name, format!("Unknown agent mode: {} (expected plan or build)", arg)),
+        },
+        SlashCommand::Approvals(None) => {
+            let mode = get_approval_mode(session_id)?;
+            CommandResult::ok(&name, format!("Approval mode: {}", mode)).with_data(json!({ "mode": mode }))
+        }
+        SlashCommand::Approvals(Some(arg)) => match arg.to_lowercase().as_str() {
+            "read-only" | "agent" | "agent-full" => match set_approval_mode(session_id, arg.to_lowercase()) {
+                Ok(()) => CommandResult::ok(&name, format!("Switched approval mode to {}", arg.to_lowercase()))
+                    .with_data(json!({ "mode": arg.to_lowercase() })),
+                Err(e) => CommandResult::fail(&name, format!("Failed to switch approval mode: {}", e)),
+            },
+            _ => CommandResult::fail(
+                &name,
+                format!("Unknown approval mode: {} (expected read-only, agent or agent-full)", arg),
+            ),
+        },
+        SlashCommand::Theme(None) => CommandResult::fail(&name, "Usage: /theme <name>"),
+        SlashCommand::Theme(Some(theme)) => match set_theme(theme.clone()) {
+            Ok(()) => CommandResult::ok(&name, format!("Switched theme to {}", theme))
+                .with_data(json!({ "theme": theme })),
+            Err(e) => CommandResult::fail(&name, format!("Failed to switch theme: {}", e)),
+        },
+        SlashCommand::Sessions => {
+            let sessions = get_saved_sessions(namespace)?;
+            CommandResult::ok(&name, format!("{} saved sessions", sessions.len())).with_data(json!(sessions
+                .iter()
+                .map(|s| json!({
+                    "sessionId": s.session_id,
+                    "createdAtMs": s.created_at_ms,
+                    "updatedAtMs": s.updated_at_ms,
+                    "messageCount": s.message_count,
+                    "title": s.title
+                }))
+                .collect::<Vec<_>>()))
+        }
+        SlashCommand::Compact => match compact_history(session_id, &inner).await {
+            Ok(folded) => CommandResult::ok(&name, format!("Compacted {} messages into a summary", folded))
+                .with_data(json!({ "summarized": folded })),
+            Err(e) => CommandResult::fail(&name, format!("Failed to compact history: {}", e)),
+        },
+        SlashCommand::Skills => match get_session_skills(namespace, public_id) {
+            Ok(list) if list.is_empty() => CommandResult::ok(&name, "No skills found"),
+            Ok(list) => {
+                let text = list
+                    .iter()
+                    .map(|s| {
+                        let state = match (s.default, s.active) {
+                            (true, _) => " (workspace default)",
+                            (false, true) => " (enabled)",
+                            (false, false) => "",
+                        };
+                        format!("{}{} - {}", s.name, state, s.description)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let data = list
+                    .iter()
+                    .map(|s| json!({ "name": s.name, "source": s.source, "active": s.active, "default": s.default }))
+                    .collect::<Vec<_>>();
+                CommandResult::ok(&name, text).with_data(json!(data))
+            }
+            Err(e) => CommandResult::fail(&name, format!("Failed to list skills: {}", e)),
+        },
+        SlashCommand::Exit => CommandResult {
+            action: Some("exit".to_string()),
+            ..CommandResult::ok(&name, "")
+        },
+        SlashCommand::Help => {
+            let text = SLASH_COMMAND_HELP
+                .iter()
+                .map(|(n, d)| format!("/{} - {}", n, d))
+                .collect::<Vec<_>>()
+                .join("\n");
+            CommandResult::ok(&name, text)
+        }
+        SlashCommand::Unknown(_) => CommandResult::fail(&name, format!("Unknown command: /{}", name)),
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve_model_arg, system_prompt_for_agent_mode, system_prompt_for_session};
+    use super::{cancel_session, classify_provider_error, run_auto, RustAgent, SessionVars};
+    use super::super::session::confirmation::PendingConfirmation;
+    use super::super::session::executor::set_running_tool;
+    use super::{
+        check_saved_workspace, compact_history, delete_session, delete_sessions, duplicate_session, flush_sessions,
+        list_saved_sessions_for_workspace, persist_session_snapshot, pin_message, pinned_indices, rename_session,
+        set_session_notes, warn_workspace_mismatch,
+    };
+    use super::{close_session, get_core_status};
+    use crate::session::crypto;
+    use crate::config::{workspace_key, WorkspaceMismatchMode};
+    use crate::llm::models::provider_handle::Message;
+    use crate::llm::utils::checkpoint;
+    use crate::llm::utils::tool_access::with_tool_session;
+    use crate::session::store;
+    use crate::testing::{FakeProvider, FakeResponse, FakeWorkspace, TestHome};
+    use crate::llm::agents::cancel::CancelToken;
+    use crate::config::{AppConfig, ProviderConfig};
+    use crate::session::events::{EventChannel, SessionEventSink};
+    use crate::session::types::{CoreErrorCode, CoreEvent, CoreEventType};
+    use crate::session::{
+        emit_control_event, emit_stream_text, get_confirmation_status, set_event_sink, set_response_stage,
+        ResponseStage, SESSION_MANAGER,
+    };
+    use crate::session::manager::session_key;
+    use crate::session::store::DEFAULT_NAMESPACE;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+    use crate::session::context::{AgentMode, ApprovalMode, RunningToolCall};
+    use crate::session::auto_mode::AutoRunConfig;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn embedded_config() -> AppConfig {
+        toml::from_str(include_str!("../../Config.toml")).expect("embedded Config.toml should parse")
+    }
+
+    #[test]
+    fn prompt_plan_uses_prompt_plan_template() {
+        let cfg = embedded_config();
+        let prompt = system_prompt_for_agent_mode(&cfg, &AgentMode::Plan).unwrap_or_default();
+        assert!(prompt.contains("read-only mode"));
+    }
+
+    #[test]
+    fn prompt_build_uses_prompt_build_template() {
+        let cfg = embedded_config();
+        let prompt = system_prompt_for_agent_mode(&cfg, &AgentMode::Build).unwrap_or_default();
+        assert!(prompt.contains("## New Applications"));
+    }
+
+    #[test]
+    fn disabled_prompt_build_falls_back_to_default() {
+        let mut cfg = embedded_config();
+        if let Some(pb) = cfg.prompt_build.as_mut() {
+            pb.enabled = false;
+        }
+        let prompt = system_prompt_for_agent_mode(&cfg, &AgentMode::Build);
+        assert_eq!(prompt, Some("You are a helpful coding assistant.".to_string()));
+    }
+
+    #[test]
+    fn session_prompt_ends_with_the_probed_environment() {
+        let cfg = embedded_config();
+        let environment = "<capabilities>\nAvailable: git\n</capabilities>";
+        let no_vars = SessionVars::new();
+        let prompt =
+            system_prompt_for_session(&cfg, &AgentMode::Plan, Some(environment), &no_vars, &[]).unwrap_or_default();
+        assert!(prompt.contains("read-only mode"));
+        assert!(prompt.ends_with(&format!("\n\n{}", environment)));
+        assert_eq!(
+            system_prompt_for_session(&cfg, &AgentMode::Plan, None, &no_vars, &[]),
+            system_prompt_for_agent_mode(&cfg, &AgentMode::Plan)
+        );
+
+        let mut cfg = cfg;
+        if let Some(plan) = cfg.prompt_plan.as_mut() {
+            plan.prompt_template = "Plan the work for {{TICKET}}.".to_string();
+        }
+        let vars: SessionVars = [("TICKET".to_string(), "PROJ-42".to_string())].into();
+        assert_eq!(
+            system_prompt_for_session(&cfg, &AgentMode::Plan, None, &vars, &[]).as_deref(),
+            Some("Plan the work for PROJ-42.")
+        );
+    }
+
+    #[test]
+    fn resolve_model_arg_accepts_qualified_and_bare_names() {
+        let models = vec![
+            ("openai".to_string(), "gpt-4o".to_string()),
+            ("anthropic".to_string(), "claude-sonnet".to_string()),
+        ];
+        assert_eq!(
+            resolve_model_arg(&models, "anthropic:claude-sonnet"),
+            Some(("anthropic".to_string(), "claude-sonnet".to_string()))
+        );
+        assert_eq!(
+            resolve_model_arg(&models, "gpt-4o"),
+            Some(("openai".to_string(), "gpt-4o".to_string()))
+        );
+        assert_eq!(resolve_model_arg(&models, "openai:claude-sonnet"), None);
+    }
+
+    #[tokio::test]
+    async fn cancel_session_cancels_a_call_waiting_for_confirmation() {
+        let session_id = "cancel-confirm-session".to_string();
+        let key = session_key(DEFAULT_NAMESPACE, &session_id);
+        let provider = ProviderConfig {
+            name: "openai".to_string(),
+            base_url: "http://127.0.0.1:1".to_string(),
+            api_key: "k".to_string(),
+            models: vec!["m1".to_string()],
+            ..Default::default()
+        };
+        let agent = RustAgent::new("openai".to_string(), "m1".to_string(), None, vec![provider], Vec::new()).unwrap();
+        SESSION_MANAGER.lock().unwrap().add(session_id.clone(), agent);
+
+        let cancel = CancelToken::new();
+        set_running_tool(&key, Some(RunningToolCall { request_id: "call-1".to_string(), cancel: cancel.clone() }));
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let sender = Arc::new(Mutex::new(Some(PendingConfirmation { request_id: "call-1".to_string(), sender: tx })));
+
+        assert!(cancel_session(&key, &sender).await.unwrap());
+        assert!(cancel.is_cancelled());
+        // No decision is sent, so the call reports itself cancelled rather than denied
+        assert!(rx.await.is_err());
+        assert!(sender.lock().await.is_none());
+        SESSION_MANAGER.lock().unwrap().remove(&key);
+    }
+
+    #[tokio::test]
+    async fn auto_runs_stop_when_the_budget_runs_out_mid_request() {
+        // Accepts connections and never answers, so the turn hangs in its first request
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let provider = ProviderConfig {
+            name: "openai".to_string(),
+            base_url: format!("http://{}", listener.local_addr().unwrap()),
+            api_key: "k".to_string(),
+            models: vec!["m1".to_string()],
+            ..Default::default()
+        };
+        let session_id = session_key(DEFAULT_NAMESPACE, "auto-run-budget-session");
+        let agent = RustAgent::new("openai".to_string(), "m1".to_string(), None, vec![provider], Vec::new()).unwrap();
+        let inner = SESSION_MANAGER.lock().unwrap().add("auto-run-budget-session".to_string(), agent).inner.clone();
+        let confirmation_sender: Arc<Mutex<Option<PendingConfirmation>>> = Arc::new(Mutex::new(None));
+
+        let config = AutoRunConfig {
+            budget: Duration::from_millis(300),
+            checkpoint_interval: Duration::from_secs(60),
+            steer_window: Duration::from_secs(0),
+        };
+        let started = std::time::Instant::now();
+        let result = tokio::time::timeout(
+            Duration::from_secs(20),
+            run_auto(&session_id, &inner, &confirmation_sender, "hi".to_string(), config),
+        )
+        .await
+        .expect("the budget should stop the turn")
+        .unwrap();
+        assert!(result.budget_exhausted);
+        assert!(!result.cancelled);
+        assert!(started.elapsed() < Duration::from_secs(20));
+        SESSION_MANAGER.lock().unwrap().remove(&session_id);
+        drop(listener);
+    }
+
+    #[test]
+    fn quota_errors_suggest_models_of_other_providers() {
+        let models = [("openai", "gpt-4o"), ("openai", "gpt-4o-mini"), ("deepseek", "deepseek-chat")]
+            .map(|(p, m)| (p.to_string(), m.to_string()));
+        let raw = anyhow::anyhow!(concat!(
+            "LLM API error (429 Too Many Requests): ",
+            r#"{"error":{"message":"You exceeded your current quota.","code":"insufficient_quota"}}"#
+        ));
+        let error = classify_provider_error(&raw, "openai", &models).unwrap();
+        assert!(matches!(error.code, CoreErrorCode::QuotaExceeded));
+        assert_eq!(error.detail.as_deref(), Some("You exceeded your current quota."));
+        let actions: Vec<_> = error.suggestions.iter().map(|s| s.action.as_str()).collect();
+        assert_eq!(actions, ["add_credits", "switch_model"]);
+        assert_eq!(error.suggestions[1].models, ["deepseek:deepseek-chat"]);
+
+        // With one provider there is nothing to switch to
+        let unauthorized = anyhow::anyhow!("LLM API error (401 Unauthorized): ");
+        let error = classify_provider_error(&unauthorized, "openai", &models[..2]).unwrap();
+        assert!(matches!(error.code, CoreErrorCode::InvalidApiKey));
+        assert_eq!(error.suggestions.len(), 1);
+        assert!(classify_provider_error(&anyhow::anyhow!("connection reset"), "openai", &models).is_none());
+    }
+
+    /// Hands events to a host thread through a queue of one, as a threadsafe
+    /// function does
+    struct HostQueue(std::sync::mpsc::SyncSender<CoreEvent>);
+
+    impl EventChannel<CoreEvent> for HostQueue {
+        fn send(&self, event: CoreEvent, blocking: bool) -> bool {
+            if blocking {
+                self.0.send(event).is_ok()
+            } else {
+                self.0.try_send(event).is_ok()
+            }
+        }
+    }
+
+    fn warning_event(session_id: &str) -> CoreEvent {
+        CoreEvent {
+            session_id: session_id.to_string(),
+            event_type: CoreEventType::Warning,
+            ..Default::default()
+        }
+    }
+
+    /// A host that calls into the core while handling each event, with turns,
+    /// cancels, model switches and resubscriptions running at the same time
+    #[test]
+    fn concurrent_execute_cancel_and_set_model_do_not_deadlock() {
+        let session_id = session_key(DEFAULT_NAMESPACE, "stress-concurrent-session");
+        let provider = ProviderConfig {
+            name: "openai".to_string(),
+            // Nothing listens there, so each turn fails at once
+            base_url: "http://127.0.0.1:1".to_string(),
+            api_key: "k".to_string(),
+            models: vec!["m1".to_string(), "m2".to_string()],
+            ..Default::default()
+        };
+        let agent = RustAgent::new("openai".to_string(), "m1".to_string(), None, vec![provider], Vec::new()).unwrap();
+        let inner = SESSION_MANAGER.lock().unwrap().add("stress-concurrent-session".to_string(), agent).inner.clone();
+        let confirmation_sender: Arc<Mutex<Option<PendingConfirmation>>> = Arc::new(Mutex::new(None));
+
+        let (tx, rx) = std::sync::mpsc::sync_channel::<CoreEvent>(1);
+        assert!(set_event_sink(&session_id, SessionEventSink::unified(HostQueue(tx.clone()))));
+        let host_session = session_id.clone();
+        let host = std::thread::spawn(move || {
+            let mut received = 0;
+            for _ in rx {
+                set_response_stage(&host_session, ResponseStage::Answering);
+                let _ = get_confirmation_status(&host_session, "edit", "a.rs");
+                received += 1;
+            }
+            received
+        });
+
+        let opened = session_id.clone();
+        let work = async move {
+            let executor = {
+                let inner = inner.clone();
+                tokio::spawn(async move {
+                    for _ in 0..20 {
+                        let mut agent = inner.lock().await;
+                        agent.add_user_message("hi".to_string());
+                        // Fails at once, unless a cancel stops it before the request goes out
+                        assert!(agent.execute().await.map_or(true, |r| r.cancelled));
+                    }
+                })
+            };
+            let canceller = {
+                let (session_id, sender) = (session_id.clone(), confirmation_sender.clone());
+                tokio::spawn(async move {
+                    for _ in 0..50 {
+                        cancel_session(&session_id, &sender).await.unwrap();
+                        tokio::task::yield_now().await;
+                    }
+                })
+            };
+            let switcher = {
+                let inner = inner.clone();
+                tokio::spawn(async move {
+                    for i in 0..50 {
+                        let model = if i % 2 == 0 { "m2" } else { "m1" };
+                        inner.lock().await.set_model("openai", model).unwrap();
+                    }
+                })
+            };
+            let emitters: Vec<_> = (0..4)
+                .map(|_| {
+                    let session_id = session_id.clone();
+                    tokio::spawn(async move {
+                        for _ in 0..100 {
+                            emit_control_event(&session_id, warning_event(&session_id));
+                            emit_stream_text(&session_id, "chunk".to_string());
+                            tokio::task::yield_now().await;
+                        }
+                    })
+                })
+                .collect();
+            let subscriber = {
+                let (session_id, tx) = (session_id.clone(), tx.clone());
+                tokio::task::spawn_blocking(move || {
+                    for _ in 0..50 {
+                        assert!(set_event_sink(&session_id, SessionEventSink::unified(HostQueue(tx.clone()))));
+                    }
+                })
+            };
+            for task in [executor, canceller, switcher, subscriber].into_iter().chain(emitters) {
+                task.await.unwrap();
+            }
+        };
+        // A deadlock blocks the runtime's workers, so wait from outside it
+        let (done_tx, done_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(4).enable_all().build().unwrap();
+            runtime.block_on(work);
+            let _ = done_tx.send(());
+        });
+        done_rx.recv_timeout(Duration::from_secs(60)).expect("session tasks deadlocked");
+
+        SESSION_MANAGER.lock().unwrap().remove(&opened);
+        assert!(host.join().unwrap() > 0);
+    }
+
+    fn messages(contents: &[&str]) -> Vec<Message> {
+        contents
+            .iter()
+            .enumerate()
+            .map(|(i, content)| Message {
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: content.to_string(),
+            })
+            .collect()
+    }
+
+    /// Open a session in `namespace` whose history is `contents`, and save it;
+    /// returns the session's key. Its model is served at `base_url`.
+    fn open_saved_session(namespace: &str, public_id: &str, base_url: &str, contents: &[&str]) -> String {
+        let provider = ProviderConfig {
+            name: "openai".to_string(),
+            base_url: base_url.to_string(),
+            api_key: "k".to_string(),
+            models: vec!["m1".to_string()],
+            ..Default::default()
+        };
+        let mut agent = RustAgent::new("openai".to_string(), "m1".to_string(), None, vec![provider], Vec::new()).unwrap();
+        agent.import_messages(messages(contents));
+        SESSION_MANAGER.lock().unwrap().add_with_context(
+            namespace.to_string(),
+            public_id.to_string(),
+            agent,
+            AgentMode::default(),
+            ApprovalMode::default(),
+        );
+        let session_id = session_key(namespace, public_id);
+        persist_session_snapshot(&session_id, messages(contents));
+        flush_sessions();
+        session_id
+    }
+
+    #[test]
+    fn deleted_sessions_leave_no_files_or_state_behind() {
+        let home = TestHome::new();
+        let workspace = FakeWorkspace::new().file("src/lib.rs", "fn a() {}\n").build().unwrap();
+        let namespace = "delete-test";
+        let session_id = open_saved_session(namespace, "original", "http://127.0.0.1:1", &["fix the build", "Done."]);
+        // Extending the history appends to the message log
+        let history = ["fix the build", "Done.", "and the tests?"];
+        persist_session_snapshot(&session_id, messages(&history));
+        flush_sessions();
+        let dir = home.session_dir(namespace, "original");
+        assert!(dir.join("messages.jsonl").exists());
+        // A turn leaves a change waiting for review
+        let file = workspace.join("src/lib.rs");
+        checkpoint::begin_turn(&session_id);
+        with_tool_session(&session_id, || checkpoint::record_before_change(&file));
+        std::fs::write(&file, "fn b() {}\n").unwrap();
+        assert_eq!(checkpoint::end_turn(&session_id, workspace.path()).len(), 1);
+
+        let copy = duplicate_session(namespace, "original").unwrap();
+        assert_eq!(copy.message_count, 3);
+        assert_eq!(delete_sessions(namespace, vec!["original".to_string(), "never-saved".to_string()]).unwrap(), 1);
+        assert!(!dir.exists());
+        assert!(SESSION_MANAGER.lock().unwrap().get(&session_id).is_none());
+        assert!(checkpoint::end_turn(&session_id, workspace.path()).is_empty());
+        assert!(store::load_snapshot(namespace, "original").unwrap().is_none());
+        assert!(!delete_session(namespace, "original").unwrap());
+        assert!(delete_sessions(namespace, vec!["../original".to_string()]).is_err());
+
+        // The copy is a session of its own and outlives the original
+        let saved = store::load_snapshot(namespace, &copy.session_id).unwrap().unwrap();
+        assert_eq!(saved.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), history);
+        assert_eq!(saved.title.as_deref(), Some("Copy of original"));
+        assert!(delete_session(namespace, &copy.session_id).unwrap());
+        assert!(!home.session_dir(namespace, &copy.session_id).exists());
+    }
+
+    #[tokio::test]
+    async fn pinned_messages_survive_compaction_verbatim() {
+        let _home = TestHome::new();
+        let fake = FakeProvider::start(vec![FakeResponse::Json(json!({
+            "choices": [{ "message": { "role": "assistant", "content": "The build was fixed." } }]
+        }))]);
+        let namespace = "pin-test";
+        let history = ["fix the build", "Which port?", "Always use port 8080, never 80", "Done."];
+        let session_id = open_saved_session(namespace, "pinned", fake.base_url(), &history);
+        assert!(pin_message(namespace, "pinned", 4).await.unwrap_err().to_string().contains("No message at index 4"));
+        pin_message(namespace, "pinned", 2).await.unwrap();
+
+        let inner = SESSION_MANAGER.lock().unwrap().get(&session_id).unwrap().inner.clone();
+        assert_eq!(compact_history(&session_id, &inner).await.unwrap(), 3);
+        let compacted = inner.lock().await.export_messages();
+        assert_eq!(compacted.len(), 2);
+        assert_eq!((compacted[0].role.as_str(), compacted[0].content.as_str()), ("user", history[2]));
+        assert_eq!(compacted[1].content, "Summary of the conversation so far:\n\nThe build was fixed.");
+        assert_eq!(pinned_indices(&session_id).unwrap(), [0]);
+        // The summary request carried the pinned message along with the rest
+        let requests = fake.requests();
+        assert!(requests[0].to_string().contains(history[2]));
+
+        flush_sessions();
+        let saved = store::load_snapshot(namespace, "pinned").unwrap().unwrap();
+        assert_eq!((saved.messages[0].content.as_str(), saved.pinned.as_slice()), (history[2], &[0][..]));
+        assert!(pin_message(namespace, "pinned", 2).await.is_err());
+        SESSION_MANAGER.lock().unwrap().remove(&session_id);
+    }
+
+    #[test]
+    fn saved_sessions_are_listed_for_their_workspace_however_its_path_is_written() {
+        let _home = TestHome::new();
+        let workspace = FakeWorkspace::new().dir("app/src").dir("lib").build().unwrap();
+        let namespace = "workspace-test";
+        for (id, dir) in [("in_app", "app"), ("in_lib", "lib")] {
+            store::save_snapshot(store::SessionSnapshot {
+                version: store::SESSION_SNAPSHOT_VERSION,
+                session_id: id.to_string(),
+                namespace: namespace.to_string(),
+                created_at_ms: 0,
+                updated_at_ms: 0,
+                agent_mode: "build".to_string(),
+                approval_mode: "agent".to_string(),
+                messages: messages(&["hello"]),
+                pinned: Vec::new(),
+                workspace: Some(workspace_key(&workspace.join(dir))),
+                title: None,
+                notes: None,
+                vars: Default::default(),
+                skills: Vec::new(),
+            })
+            .unwrap();
+        }
+        let listed = |path: std::path::PathBuf| -> Vec<String> {
+            list_saved_sessions_for_workspace(namespace, &path.to_string_lossy())
+                .unwrap()
+                .into_iter()
+                .map(|s| s.session_id)
+                .collect()
+        };
+
+        assert_eq!(listed(workspace.join("app")), ["in_app"]);
+        assert_eq!(listed(workspace.join("lib")), ["in_lib"]);
+        assert_eq!(listed(workspace.join("app/")), ["in_app"]);
+        assert_eq!(listed(workspace.join("./app/src/..")), ["in_app"]);
+        assert!(listed(workspace.join("app/src")).is_empty());
+        assert!(listed(workspace.path().to_path_buf()).is_empty());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(workspace.join("app"), workspace.join("link")).unwrap();
+            assert_eq!(listed(workspace.join("link")), ["in_app"]);
+        }
+    }
+
+    #[test]
+    fn sessions_saved_in_another_workspace_are_flagged_or_refused() {
+        let _home = TestHome::new();
+        let session_id = session_key(DEFAULT_NAMESPACE, "moved-session");
+        let check = |saved: Option<&str>, mode| check_saved_workspace(&session_id, saved, "/work/lib", mode);
+        assert_eq!(check(Some("/work/app"), WorkspaceMismatchMode::Warn).unwrap().as_deref(), Some("/work/app"));
+        assert_eq!(check(Some("/work/app"), WorkspaceMismatchMode::Ignore).unwrap(), None);
+        let refused = check(Some("/work/app"), WorkspaceMismatchMode::Error).unwrap_err().to_string();
+        assert_eq!(refused, "Session moved-session was saved in workspace /work/app, not /work/lib");
+        assert_eq!(check(Some("/work/lib"), WorkspaceMismatchMode::Error).unwrap(), None);
+        // Sessions saved before the workspace was recorded open anywhere
+        assert_eq!(check(None, WorkspaceMismatchMode::Error).unwrap(), None);
+
+        // A flagged session warns at its next turn, and only then
+        let agent = RustAgent::new("openai".to_string(), "m1".to_string(), None, vec![ProviderConfig {
+            name: "openai".to_string(),
+            models: vec!["m1".to_string()],
+            ..Default::default()
+        }], Vec::new())
+        .unwrap();
+        SESSION_MANAGER.lock().unwrap().add("moved-session".to_string(), agent);
+        SESSION_MANAGER.lock().unwrap().get_mut(&session_id).unwrap().workspace_mismatch = Some("/work/app".to_string());
+        let (tx, rx) = std::sync::mpsc::sync_channel::<CoreEvent>(8);
+        assert!(set_event_sink(&session_id, SessionEventSink::unified(HostQueue(tx))));
+        warn_workspace_mismatch(&session_id);
+        warn_workspace_mismatch(&session_id);
+        let warning = rx.recv_timeout(Duration::from_secs(10)).unwrap().warning.unwrap();
+        assert_eq!(warning.code, "workspace_mismatch");
+        assert!(warning.message.contains("saved in /work/app"), "{}", warning.message);
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+        SESSION_MANAGER.lock().unwrap().remove(&session_id);
+    }
+
+    #[tokio::test]
+    async fn titles_and_notes_are_saved_sealed_with_the_session() {
+        let home = TestHome::new();
+        let namespace = "title-test";
+        let session_id = open_saved_session(namespace, "titled", "http://127.0.0.1:1", &["fix the login", "Done."]);
+        std::env::set_var(crypto::PASSPHRASE_ENV, "correct horse battery staple");
+        crypto::set_enabled(true);
+        rename_session(namespace, "titled", "  Fix the login flow  ").await.unwrap();
+        set_session_notes(namespace, "titled", "Left: the password reset").await.unwrap();
+        flush_sessions();
+        // The key stays cached for reading the files back
+        crypto::set_enabled(false);
+        std::env::remove_var(crypto::PASSPHRASE_ENV);
+
+        let dir = home.session_dir(namespace, "titled");
+        for file in ["meta.json", "snapshot.json"] {
+            let on_disk = std::fs::read_to_string(dir.join(file)).unwrap();
+            assert!(on_disk.starts_with("carry-enc1:passphrase:"), "{} is not sealed", file);
+            assert!(!on_disk.contains("login flow") && !on_disk.contains("password reset"), "{}", file);
+        }
+        let meta = store::load_meta(namespace, "titled").unwrap().unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Fix the login flow"));
+        assert_eq!(meta.notes.as_deref(), Some("Left: the password reset"));
+
+        // Both come back when the session is loaded again
+        assert!(close_session(namespace, "titled").unwrap());
+        assert!(SESSION_MANAGER.lock().unwrap().get(&session_id).is_none());
+        let saved = store::load_snapshot(namespace, "titled").unwrap().unwrap();
+        assert_eq!(saved.title.as_deref(), Some("Fix the login flow"));
+        assert_eq!(saved.notes.as_deref(), Some("Left: the password reset"));
+        assert_eq!(saved.messages.len(), 2);
+
+        assert!(rename_session(namespace, "titled", &"x".repeat(121)).await.unwrap_err().to_string().contains("longer than 120"));
+        assert!(rename_session(namespace, "titled", "Again").await.unwrap_err().to_string().contains("Session not found"));
+    }
+
+    #[tokio::test]
+    async fn core_status_counts_the_open_sessions() {
+        let _home = TestHome::new();
+        let namespace = "status-test";
+        let listed = |status: &super::CoreStatus| {
+            status.sessions.iter().any(|s| s.namespace == namespace && s.session_id == "watched")
+        };
+
+        let before = get_core_status().await.unwrap();
+        assert_eq!(before.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(before.snapshot_version, u32::from(store::SESSION_SNAPSHOT_VERSION));
+        assert_eq!(before.active_sessions as usize, before.sessions.len());
+        assert!(!listed(&before));
+
+        open_saved_session(namespace, "watched", "http://127.0.0.1:1", &["hello"]);
+        let open = get_core_status().await.unwrap();
+        assert!(listed(&open));
+        assert_eq!(open.active_sessions as usize, open.sessions.len());
+        assert!(open.sessions.windows(2).all(|w| w[0].session_id <= w[1].session_id));
+
+        assert!(close_session(namespace, "watched").unwrap());
+        let closed = get_core_status().await.unwrap();
+        assert!(!listed(&closed));
+        assert_eq!(closed.active_sessions as usize, closed.sessions.len());
+    }
+}
@@ -0,0 +1,335 @@
+//! Terminal client over the Rust session API, for use without the Node host.
+//!
+//! Build with `cargo build --release --no-default-features --features cli`.
+//!
+//! ```text
+//! carrycode                      interactive session
+//! carrycode "fix the tests"      one-shot: run the prompt and exit
+//! echo "explain main.rs" | carrycode
+//! ```
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+
+use anyhow::{bail, Result};
+use carrycode_coreapi::api::{self, Session};
+use carrycode_coreapi::session::events::{EventChannel, SessionEventSink};
+use carrycode_coreapi::session::generate_session_id;
+use carrycode_coreapi::session::types::{
+    CoreConfirmDecision, CoreConfirmDecisionKind, CoreConfirmationRequest, CoreEvent, CoreEventType, CoreTextEvent,
+};
+use tokio::sync::mpsc;
+
+const USAGE: &str = "\
+Usage: carrycode [options] [prompt...]
+
+Runs the prompt and exits when one is given (or piped on stdin),
+otherwise starts an interactive session.
+
+Options:
+  -s, --session <id>        resume or create this session
+  -m, --mode <mode>         agent mode: plan | build
+  -a, --approval <mode>     approval mode: read-only | agent | agent-full
+  -y, --yes                 approve tool confirmations without asking
+  -h, --help                show this help
+";
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    session: Option<String>,
+    mode: Option<String>,
+    approval: Option<String>,
+    yes: bool,
+    help: bool,
+    prompt: Option<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options> {
+    let mut options = Options::default();
+    let mut words: Vec<String> = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| match args.next() {
+            Some(v) => Ok(v),
+            None => bail!("{} needs a value", name),
+        };
+        match arg.as_str() {
+            "-s" | "--session" => options.session = Some(value(&arg)?),
+            "-m" | "--mode" => options.mode = Some(value(&arg)?),
+            "-a" | "--approval" => options.approval = Some(value(&arg)?),
+            "-y" | "--yes" => options.yes = true,
+            "-h" | "--help" => options.help = true,
+            "--" => words.extend(args.by_ref()),
+            flag if flag.starts_with('-') && flag.len() > 1 => bail!("Unknown option '{}'", flag),
+            _ => words.push(arg),
+        }
+    }
+    if !words.is_empty() {
+        options.prompt = Some(words.join(" "));
+    }
+    Ok(options)
+}
+
+/// Writes streamed text to stdout as it arrives
+struct TextPrinter;
+
+impl EventChannel<CoreTextEvent> for TextPrinter {
+    fn send(&self, event: CoreTextEvent, _blocking: bool) -> bool {
+        let mut out = io::stdout().lock();
+        let _ = out.write_all(event.text.as_bytes());
+        let _ = out.flush();
+        true
+    }
+}
+
+/// Renders tool activity and problems on stderr; forwards confirmation requests
+struct ControlPrinter {
+    confirmations: mpsc::UnboundedSender<CoreConfirmationRequest>,
+}
+
+impl EventChannel<CoreEvent> for ControlPrinter {
+    fn send(&self, event: CoreEvent, _blocking: bool) -> bool {
+        let tool = event.tool_display_name.as_deref().or(event.tool_name.as_deref()).unwrap_or("tool");
+        match event.event_type {
+            CoreEventType::ToolStart => {
+                eprintln!("\n● {} {}", tool, event.args_summary.as_deref().unwrap_or(""));
+            }
+            CoreEventType::ToolEnd => {
+                let mark = if event.success == Some(false) { "✗" } else { "⎿" };
+                eprintln!("  {} {}", mark, event.response_summary.as_deref().unwrap_or(""));
+            }
+            CoreEventType::Error => {
+                eprintln!("\nerror: {}", event.error_message.as_deref().unwrap_or("unknown error"));
+                let switch = event.error.iter().flat_map(|e| &e.suggestions).find(|s| s.action == "switch_model");
+                if let Some(switch) = switch {
+                    eprintln!("  other configured models: {}", switch.models.join(", "));
+                }
+            }
+            CoreEventType::Warning | CoreEventType::Security | CoreEventType::FileConflict | CoreEventType::Resuming => {
+                if let Some(warning) = &event.warning {
+                    eprintln!("\nwarning: {}", warning.message);
+                    for item in &warning.items {
+                        eprintln!("  - {}: {}", item.subject, item.reason);
+                    }
+                }
+            }
+            CoreEventType::PartialResponse => {
+                eprintln!("\nwarning: {}", event.display_text.as_deref().unwrap_or("the response was cut short"));
+            }
+            CoreEventType::Blocked => {
+                if let Some(details) = &event.stop_details {
+                    eprintln!("\nblocked: {}", details.message);
+                }
+            }
+            CoreEventType::Usage => {
+                if let Some(summary) = &event.display_text {
+                    eprintln!("\n· {}", summary);
+                }
+            }
+            CoreEventType::ConfirmationRequested => {
+                if let Some(request) = event.confirm {
+                    let _ = self.confirmations.send(request);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+/// Blocking line read from stdin; None at end of input
+fn read_line(prompt: &str) -> Option<String> {
+    eprint!("{}", prompt);
+    let _ = io::stderr().flush();
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+/// Ask the user about a tool call. Anything typed other than the listed
+/// answers denies the call and is passed on to the model.
+async fn ask_confirmation(
+    request: &CoreConfirmationRequest,
+    auto_approve: bool,
+    interactive: bool,
+) -> CoreConfirmDecision {
+    let decide = |decision, message: Option<String>| CoreConfirmDecision {
+        request_id: request.request_id.clone(),
+        decision,
+        message,
+    };
+    eprintln!("\n? {} wants to run: {}", request.tool_display_name, request.summary);
+    if let Some(preview) = &request.preview {
+        eprintln!("{}", preview);
+    }
+    if auto_approve {
+        eprintln!("  approved (--yes)");
+        return decide(CoreConfirmDecisionKind::AllowOnce, None);
+    }
+    if !interactive {
+        eprintln!("  denied: stdin is not a terminal (use --yes to approve)");
+        return decide(CoreConfirmDecisionKind::Deny, None);
+    }
+    let answer = tokio::task::spawn_blocking(|| {
+        read_line("  [y]es / [a]lways this session / [n]o, or say what to do instead: ")
+    })
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => decide(CoreConfirmDecisionKind::AllowOnce, None),
+        "a" | "always" => decide(CoreConfirmDecisionKind::AllowForSession, None),
+        "" | "n" | "no" => decide(CoreConfirmDecisionKind::Deny, None),
+        _ => decide(CoreConfirmDecisionKind::DenyWithMessage, Some(answer.trim().to_string())),
+    }
+}
+
+/// Run one prompt, answering confirmations and cancelling on Ctrl-C
+async fn run_prompt(
+    session: &Session,
+    prompt: String,
+    confirmations: &mut mpsc::UnboundedReceiver<CoreConfirmationRequest>,
+    options: &Options,
+    interactive: bool,
+) -> Result<bool> {
+    let run = session.execute(prompt);
+    tokio::pin!(run);
+    loop {
+        tokio::select! {
+            result = &mut run => {
+                let result = result?;
+                session.flush_events().await;
+                println!();
+                if result.cancelled {
+                    eprintln!("(cancelled)");
+                }
+                return Ok(!result.cancelled);
+            }
+            Some(request) = confirmations.recv() => {
+                let decision = ask_confirmation(&request, options.yes, interactive).await;
+                session.confirm_tool(decision).await?;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                session.cancel().await?;
+            }
+        }
+    }
+}
+
+async fn repl(
+    session: &Session,
+    confirmations: &mut mpsc::UnboundedReceiver<CoreConfirmationRequest>,
+    options: &Options,
+) -> Result<()> {
+    eprintln!("carrycode session {} (/help for commands, /exit or Ctrl-D to quit)", session.id());
+    loop {
+        let Some(line) = tokio::task::spawn_blocking(|| read_line("\n> ")).await? else {
+            eprintln!();
+            return Ok(());
+        };
+        let line = line.trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        let command = api::dispatch_command(session.namespace(), session.id(), &line).await?;
+        if command.handled {
+            if !command.message.is_empty() {
+                eprintln!("{}", command.message);
+            }
+            if command.action.as_deref() == Some("exit") {
+                return Ok(());
+            }
+            continue;
+        }
+        if let Err(e) = run_prompt(session, line, confirmations, options, true).await {
+            eprintln!("error: {:#}", e);
+        }
+    }
+}
+
+async fn run(options: Options) -> Result<bool> {
+    carrycode_coreapi::init_logger();
+    let session = Session::open(
+        api::DEFAULT_NAMESPACE.to_string(),
+        options.session.clone().unwrap_or_else(generate_session_id),
+    )?;
+    if let Some(mode) = &options.mode {
+        session.set_agent_mode(mode.clone()).await?;
+    }
+    if let Some(approval) = &options.approval {
+        session.set_approval_mode(approval.clone())?;
+    }
+
+    let (tx, mut confirmations) = mpsc::unbounded_channel();
+    session.subscribe(SessionEventSink::split(TextPrinter, ControlPrinter { confirmations: tx }))?;
+
+    let stdin_is_terminal = io::stdin().is_terminal();
+    let prompt = match options.prompt.clone() {
+        Some(prompt) => Some(prompt),
+        None if !stdin_is_terminal => Some(io::read_to_string(io::stdin())?),
+        None => None,
+    };
+
+    let result = match prompt {
+        Some(prompt) if prompt.trim().is_empty() => bail!("Empty prompt"),
+        Some(prompt) => run_prompt(&session, prompt, &mut confirmations, &options, stdin_is_terminal).await,
+        None => repl(&session, &mut confirmations, &options).await.map(|_| true),
+    };
+    session.unsubscribe();
+    api::shutdown();
+    result
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("carrycode: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if options.help {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    match run(options).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(130),
+        Err(e) => {
+            eprintln!("carrycode: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<Options> {
+        parse_args(list.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn parses_options_and_prompt_words() {
+        let options = args(&["-s", "abc", "--mode", "plan", "-y", "fix", "the", "tests"]).unwrap();
+        assert_eq!(
+            options,
+            Options {
+                session: Some("abc".to_string()),
+                mode: Some("plan".to_string()),
+                yes: true,
+                prompt: Some("fix the tests".to_string()),
+                ..Default::default()
+            }
+        );
+        assert_eq!(args(&["--", "-not-a-flag"]).unwrap().prompt.as_deref(), Some("-not-a-flag"));
+        assert_eq!(args(&[]).unwrap(), Options::default());
+        assert!(args(&["--session"]).is_err());
+        assert!(args(&["--bogus"]).is_err());
+    }
+}
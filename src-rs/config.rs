@@ -5,6 +5,7 @@ use std::path::Path;
 use std::collections::HashMap;
 
 use crate::lsp::config::LspConfig;
+use crate::paths;
 
 /// User override configuration (restricted fields)
 #[derive(Deserialize)]
@@ -13,6 +14,19 @@ pub struct UserOverrideConfig {
     pub providers: Option<Vec<UserProviderConfig>>,
     #[serde(alias = "mcpServers")]
     pub mcp_servers: Option<HashMap<String, McpServerConfig>>,
+    pub security: Option<SecurityConfig>,
+    pub privacy: Option<PrivacyConfig>,
+    pub models: Option<ModelsConfig>,
+    pub auxiliary_model: Option<String>,
+    pub context_header: Option<ContextHeaderConfig>,
+    pub context_providers: Option<ContextProvidersConfig>,
+    pub tool_definitions: Option<ToolDefinitionsConfig>,
+    pub environment_probe: Option<EnvironmentProbeConfig>,
+    pub skills: Option<SkillsConfig>,
+    pub answer_format: Option<AnswerFormatConfig>,
+    pub notify: Option<NotifyConfig>,
+    pub remote: Option<RemoteConfig>,
+    pub mcp: Option<McpConfig>,
 }
 
 /// User provider configuration (matching user schema)
@@ -23,6 +37,24 @@ pub struct UserProviderConfig {
     pub model_name: String,
     pub base_url: String,
     pub api_key: String,
+    #[serde(default)]
+    pub auth_style: Option<AuthStyle>,
+    #[serde(default)]
+    pub auth_header: Option<String>,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub tool_calling: ToolCallingMode,
+    #[serde(default)]
+    pub strip_tokens: Option<Vec<String>>,
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    #[serde(default)]
+    pub max_system_prompt_chars: Option<usize>,
+    #[serde(default)]
+    pub strict_tools: bool,
+    #[serde(default)]
+    pub context_window: Option<u32>,
 }
 
 impl From<UserProviderConfig> for ProviderConfig {
@@ -31,7 +63,16 @@ impl From<UserProviderConfig> for ProviderConfig {
             name: c.provider_name,
             base_url: c.base_url,
             api_key: c.api_key,
+            context_windows: c.context_window.map(|n| HashMap::from([(c.model_name.clone(), n)])).unwrap_or_default(),
             models: vec![c.model_name],
+            auth_style: c.auth_style,
+            auth_header: c.auth_header,
+            headers: c.headers,
+            tool_calling: c.tool_calling,
+            strip_tokens: c.strip_tokens,
+            stop_sequences: c.stop_sequences,
+            max_system_prompt_chars: c.max_system_prompt_chars,
+            strict_tools: c.strict_tools,
         }
     }
 }
@@ -65,6 +106,8 @@ pub enum McpServerConfig {
         args: Vec<String>,
         #[serde(default)]
         env: HashMap<String, String>,
+        #[serde(default)]
+        key_paths: HashMap<String, String>,
         #[serde(flatten)]
         _extra: HashMap<String, serde_json::Value>,
     },
@@ -73,13 +116,25 @@ pub enum McpServerConfig {
         url: String,
         #[serde(default)]
         headers: HashMap<String, String>,
+        #[serde(default)]
+        key_paths: HashMap<String, String>,
         #[serde(flatten)]
         _extra: HashMap<String, serde_json::Value>,
     },
 }
 
+impl McpServerConfig {
+    /// Arguments that scope confirmations of the server's tools, by tool name
+    /// ("*" for every tool); see `McpTool::key_path`
+    pub fn key_paths(&self) -> &HashMap<String, String> {
+        match self {
+            McpServerConfig::Stdio { key_paths, .. } | McpServerConfig::Http { key_paths, .. } => key_paths,
+        }
+    }
+}
+
 /// Provider configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProviderConfig {
     /// Provider name (e.g., "zhipuai", "openai", "vllm")
     pub name: String,
@@ -93,6 +148,687 @@ pub struct ProviderConfig {
     /// List of supported models
     #[serde(default)]
     pub models: Vec<String>,
+
+    /// How the API key is sent; defaults to the provider's native scheme
+    #[serde(default)]
+    pub auth_style: Option<AuthStyle>,
+
+    /// Header carrying the key when `auth_style` is "header" (default "Authorization")
+    #[serde(default)]
+    pub auth_header: Option<String>,
+
+    /// Extra headers sent with every request, e.g. gateway routing headers
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+
+    /// How tools are offered to the models (OpenAI-compatible providers)
+    #[serde(default)]
+    pub tool_calling: ToolCallingMode,
+
+    /// Control tokens stripped from replies, e.g. `<|im_end|>`; unset uses the
+    /// provider's defaults, an empty list strips nothing
+    #[serde(default)]
+    pub strip_tokens: Option<Vec<String>>,
+
+    /// Sequences that end a reply, sent as the API's stop parameter (OpenAI
+    /// accepts at most 4, Gemini 5; codex has none and ignores them)
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+
+    /// Longest system prompt the provider accepts, in characters; a longer
+    /// prompt is cut short with a notice rather than rejected by the API
+    #[serde(default)]
+    pub max_system_prompt_chars: Option<usize>,
+
+    /// Send builtin tool schemas with `strict: true` so the arguments always
+    /// match them (OpenAI-compatible providers that support structured outputs)
+    #[serde(default)]
+    pub strict_tools: bool,
+
+    /// Context window in tokens of models the core does not know, by model
+    /// name; sizes how much of a tool result stays in the history
+    #[serde(default)]
+    pub context_windows: HashMap<String, u32>,
+}
+
+/// How tools are offered to an OpenAI-compatible provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ToolCallingMode {
+    /// `native` for known hosted APIs; for a custom base URL, probe once per
+    /// model whether the server returns native tool calls and use `prompt` if not
+    #[default]
+    Auto,
+    /// The API's `tools` parameter
+    Native,
+    /// Tool definitions rendered into the system prompt, calls parsed from the reply
+    Prompt,
+}
+
+/// `[security]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Handling of instruction-like text in fetched pages and MCP tool results
+    #[serde(default)]
+    pub prompt_injection: InjectionGuardMode,
+
+    /// Absolute directories outside the workspace that file tools may also
+    /// use (e.g. a shared schemas directory); `~/` is expanded
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+
+    /// Start new sessions in overlay mode, where file changes stay in
+    /// `.carry/overlay/` until they are materialized
+    #[serde(default)]
+    pub overlay_by_default: bool,
+
+    /// Files whose contents view, grep and fetch return only with the user's
+    /// confirmation, as gitignore-style patterns
+    #[serde(default = "crate::llm::utils::sensitive_files::default_patterns")]
+    pub sensitive_files: Vec<String>,
+
+    /// What happens when a saved session is opened in a workspace other than its own
+    #[serde(default)]
+    pub workspace_mismatch: WorkspaceMismatchMode,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            prompt_injection: InjectionGuardMode::default(),
+            allowed_roots: Vec::new(),
+            overlay_by_default: false,
+            sensitive_files: crate::llm::utils::sensitive_files::default_patterns(),
+            workspace_mismatch: WorkspaceMismatchMode::default(),
+        }
+    }
+}
+
+/// `[privacy]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    /// Encrypt session snapshots and message logs with AES-256-GCM; the key
+    /// comes from `CARRYCODE_SESSION_PASSPHRASE` or the OS keychain
+    #[serde(default)]
+    pub encrypt_sessions: bool,
+
+    /// Keep a log of each session's events (tool calls, confirmations,
+    /// errors) beside its snapshot, read with `get_session_events`
+    #[serde(default = "default_true")]
+    pub event_log: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        Self {
+            encrypt_sessions: false,
+            event_log: true,
+        }
+    }
+}
+
+/// `[sessions]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionsConfig {
+    /// Minutes an open session may go unused before its history and tools are
+    /// saved and dropped from memory, to be loaded again on next use; 0 keeps them
+    #[serde(default = "default_idle_evict_minutes")]
+    pub idle_evict_minutes: u64,
+
+    /// Estimated size of the open sessions' histories, in MB, over which the
+    /// least recently used ones are evicted; 0 is no limit
+    #[serde(default = "default_memory_ceiling_mb")]
+    pub memory_ceiling_mb: u64,
+
+    /// Characters of text and reasoning one model response may stream before
+    /// it is stopped and kept as truncated; 0 is no limit
+    #[serde(default = "default_max_output_chars")]
+    pub max_output_chars: u64,
+
+    /// The same limit in tokens, estimated from the characters; 0 is no limit
+    #[serde(default)]
+    pub max_output_tokens: u64,
+}
+
+fn default_idle_evict_minutes() -> u64 {
+    30
+}
+
+fn default_memory_ceiling_mb() -> u64 {
+    512
+}
+
+fn default_max_output_chars() -> u64 {
+    200_000
+}
+
+impl Default for SessionsConfig {
+    fn default() -> Self {
+        Self {
+            idle_evict_minutes: default_idle_evict_minutes(),
+            memory_ceiling_mb: default_memory_ceiling_mb(),
+            max_output_chars: default_max_output_chars(),
+            max_output_tokens: 0,
+        }
+    }
+}
+
+/// `[tool_timeouts]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolTimeoutsConfig {
+    /// Seconds a tool call may run before the turn goes on without its result; 0 is no limit
+    #[serde(default = "default_tool_timeout_secs")]
+    pub default: u64,
+
+    /// Limits by tool kind ("read", "edit", "search", "execute", "fetch", "other", ...)
+    #[serde(default)]
+    pub kinds: HashMap<String, u64>,
+}
+
+fn default_tool_timeout_secs() -> u64 {
+    300
+}
+
+impl Default for ToolTimeoutsConfig {
+    fn default() -> Self {
+        Self {
+            default: default_tool_timeout_secs(),
+            kinds: HashMap::new(),
+        }
+    }
+}
+
+impl ToolTimeoutsConfig {
+    /// Limit for a tool of `kind` (see `ToolKind::as_str`), None if there is none
+    pub fn for_kind(&self, kind: &str) -> Option<std::time::Duration> {
+        let secs = self.kinds.get(kind).copied().unwrap_or(self.default);
+        (secs > 0).then(|| std::time::Duration::from_secs(secs))
+    }
+}
+
+/// `[mcp]` section: supervision of stdio MCP server processes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpConfig {
+    /// Times a server that crashed or timed out is restarted before its tools fail for good
+    #[serde(default = "default_mcp_max_restarts")]
+    pub max_restarts: u32,
+
+    /// Seconds a server may take to answer one request before it is killed; 0 is no limit
+    #[serde(default = "default_mcp_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+
+    /// Address-space limit of a server process in MiB; 0 is no limit (Unix only)
+    #[serde(default)]
+    pub memory_limit_mb: u64,
+
+    /// CPU-time limit of a server process in seconds; 0 is no limit (Unix only)
+    #[serde(default)]
+    pub cpu_time_limit_secs: u64,
+
+    /// Offer a server's tool under its bare name when no other tool has it,
+    /// instead of always as `server__tool`
+    #[serde(default)]
+    pub flat_tool_names: bool,
+
+    /// Take a tool's `readOnlyHint` annotation at its word, treating it as a
+    /// read that needs no confirmation in read-only mode. `destructiveHint` is
+    /// always honored, since it only adds confirmations.
+    #[serde(default = "default_true")]
+    pub trust_read_only_hints: bool,
+}
+
+fn default_mcp_max_restarts() -> u32 {
+    3
+}
+
+fn default_mcp_request_timeout_secs() -> u64 {
+    300
+}
+
+impl Default for McpConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: default_mcp_max_restarts(),
+            request_timeout_secs: default_mcp_request_timeout_secs(),
+            memory_limit_mb: 0,
+            cpu_time_limit_secs: 0,
+            flat_tool_names: false,
+            trust_read_only_hints: true,
+        }
+    }
+}
+
+impl McpConfig {
+    pub fn request_timeout(&self) -> Option<std::time::Duration> {
+        (self.request_timeout_secs > 0).then(|| std::time::Duration::from_secs(self.request_timeout_secs))
+    }
+}
+
+/// `[context_header]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextHeaderConfig {
+    /// Send an environment header with every turn
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Local date, time and UTC offset
+    #[serde(default = "default_true")]
+    pub datetime: bool,
+
+    /// OS and architecture
+    #[serde(default = "default_true")]
+    pub os: bool,
+
+    /// Name of the workspace directory
+    #[serde(default = "default_true")]
+    pub workspace: bool,
+
+    /// Branch and counts of changed and untracked files
+    #[serde(default = "default_true")]
+    pub git: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ContextHeaderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            datetime: true,
+            os: true,
+            workspace: true,
+            git: true,
+        }
+    }
+}
+
+/// `[context_providers]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextProvidersConfig {
+    /// Providers whose context is sent with every turn, by name: the built-in
+    /// `git_status`, `diagnostics` and `recent_files`, or registered ones
+    #[serde(default = "default_context_providers")]
+    pub enabled: Vec<String>,
+
+    /// Most items each provider contributes to a turn
+    #[serde(default = "default_context_max_items")]
+    pub max_items: usize,
+
+    /// `recent_files` lists files modified on disk within this many hours,
+    /// after the files the session viewed or edited
+    #[serde(default = "default_recent_hours")]
+    pub recent_hours: u64,
+}
+
+fn default_context_providers() -> Vec<String> {
+    ["git_status", "diagnostics", "recent_files"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
+}
+
+fn default_context_max_items() -> usize {
+    15
+}
+
+fn default_recent_hours() -> u64 {
+    24
+}
+
+impl Default for ContextProvidersConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_context_providers(),
+            max_items: default_context_max_items(),
+            recent_hours: default_recent_hours(),
+        }
+    }
+}
+
+/// `[tool_definitions]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinitionsConfig {
+    /// Send tools the session has used successfully with their descriptions
+    /// cut to the first sentence
+    #[serde(default = "default_true")]
+    pub minify_used: bool,
+
+    /// Leave out tools not called in this many turns (0 never leaves tools out)
+    #[serde(default)]
+    pub omit_unused_after_turns: u32,
+
+    /// Tools never left out
+    #[serde(default = "default_kept_tools")]
+    pub keep: Vec<String>,
+
+    /// Add a note to a tool's description once a call of it has failed this
+    /// many times for the same reason (0 adds none)
+    #[serde(default = "default_hint_after_failures")]
+    pub hint_after_failures: u32,
+}
+
+fn default_hint_after_failures() -> u32 {
+    2
+}
+
+fn default_kept_tools() -> Vec<String> {
+    ["view", "edit", "write", "bash"].iter().map(|t| t.to_string()).collect()
+}
+
+impl Default for ToolDefinitionsConfig {
+    fn default() -> Self {
+        Self {
+            minify_used: true,
+            omit_unused_after_turns: 0,
+            keep: default_kept_tools(),
+            hint_after_failures: default_hint_after_failures(),
+        }
+    }
+}
+
+/// `[environment_probe]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentProbeConfig {
+    /// Append the capabilities of the machine to the system prompt
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Executables looked up on PATH when a session is opened
+    #[serde(default = "default_probed_executables")]
+    pub executables: Vec<String>,
+}
+
+fn default_probed_executables() -> Vec<String> {
+    ["git", "rg", "node", "npm", "python3", "cargo", "go", "docker", "make"]
+        .iter()
+        .map(|e| e.to_string())
+        .collect()
+}
+
+impl Default for EnvironmentProbeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            executables: default_probed_executables(),
+        }
+    }
+}
+
+/// `[skills]` section: skills every session enables, see `session::skills`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkillsConfig {
+    /// Skills enabled in every session
+    #[serde(default)]
+    pub default: Vec<String>,
+
+    /// Skills enabled in sessions in an agent mode, by mode ("build", "plan")
+    #[serde(default)]
+    pub modes: HashMap<String, Vec<String>>,
+}
+
+/// `[answer_format]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnswerFormatConfig {
+    /// Post-process the final answer of each turn
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Renumber headings to start at `top_heading_level` without gaps
+    #[serde(default = "default_true")]
+    pub normalize_headings: bool,
+
+    #[serde(default = "default_top_heading_level")]
+    pub top_heading_level: u8,
+
+    /// Write absolute paths into the workspace as workspace-relative
+    #[serde(default = "default_true")]
+    pub relative_paths: bool,
+
+    /// Report `file:line` references with the End event
+    #[serde(default = "default_true")]
+    pub file_references: bool,
+}
+
+fn default_top_heading_level() -> u8 {
+    2
+}
+
+impl Default for AnswerFormatConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            normalize_headings: true,
+            top_heading_level: default_top_heading_level(),
+            relative_paths: true,
+            file_references: true,
+        }
+    }
+}
+
+/// `[notify]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    /// Run the notification actions; off unless configured
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Shell command run for each notification, with the details in
+    /// `CARRYCODE_NOTIFY_*` environment variables; empty runs nothing
+    #[serde(default)]
+    pub command: String,
+
+    /// URL each notification is POSTed to as JSON; empty posts nothing
+    #[serde(default)]
+    pub webhook_url: String,
+
+    /// Notify when a turn at least `min_turn_secs` long finishes
+    #[serde(default = "default_true")]
+    pub on_turn_end: bool,
+
+    #[serde(default = "default_notify_min_turn_secs")]
+    pub min_turn_secs: u64,
+
+    /// Notify when a tool call waits for confirmation or the model asks a question
+    #[serde(default = "default_true")]
+    pub on_confirmation: bool,
+
+    /// Skip notifications while the host reports the user present
+    #[serde(default = "default_true")]
+    pub only_when_away: bool,
+}
+
+fn default_notify_min_turn_secs() -> u64 {
+    60
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: String::new(),
+            webhook_url: String::new(),
+            on_turn_end: true,
+            min_turn_secs: default_notify_min_turn_secs(),
+            on_confirmation: true,
+            only_when_away: true,
+        }
+    }
+}
+
+/// Where `[remote]` runs the tools
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RemoteBackendKind {
+    /// A host reached over ssh
+    #[default]
+    Ssh,
+    /// A running container, by name or id
+    Docker,
+    /// A container built and started from the workspace's devcontainer.json
+    Devcontainer,
+}
+
+impl RemoteBackendKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ssh => "ssh",
+            Self::Docker => "docker",
+            Self::Devcontainer => "devcontainer",
+        }
+    }
+}
+
+/// `[remote]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteConfig {
+    /// Run bash, ls, view and edit on the `backend` target instead of the local workspace
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default)]
+    pub backend: RemoteBackendKind,
+
+    /// Host name or ~/.ssh/config alias
+    #[serde(default)]
+    pub host: String,
+
+    /// Login user; empty leaves it to ssh
+    #[serde(default)]
+    pub user: String,
+
+    #[serde(default = "default_remote_port")]
+    pub port: u16,
+
+    /// Private key passed with `-i`; empty uses the agent and ssh's defaults
+    #[serde(default)]
+    pub identity_file: String,
+
+    /// Absolute directory on the host or in the container that mirrors the
+    /// workspace root; for a devcontainer it defaults to its `workspaceFolder`
+    #[serde(default)]
+    pub remote_root: String,
+
+    /// Extra `-o` options for ssh, e.g. "ConnectTimeout=10"
+    #[serde(default)]
+    pub ssh_options: Vec<String>,
+
+    /// Container the `docker` backend executes in
+    #[serde(default)]
+    pub container: String,
+}
+
+fn default_remote_port() -> u16 {
+    22
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: RemoteBackendKind::default(),
+            host: String::new(),
+            user: String::new(),
+            port: default_remote_port(),
+            identity_file: String::new(),
+            remote_root: String::new(),
+            ssh_options: Vec::new(),
+            container: String::new(),
+        }
+    }
+}
+
+/// `[models]` section
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelsConfig {
+    /// Named models, e.g. `fast = "openai:gpt-4o-mini"`; an alias can be
+    /// given anywhere a model is, and may name another alias
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+
+    /// Model for internal tasks such as `title` and `summary`, as an alias,
+    /// "provider:model" or a model name; unrouted tasks use the session's model
+    #[serde(default)]
+    pub routes: HashMap<String, String>,
+}
+
+/// Alias chains longer than this are taken for cycles
+const MAX_ALIAS_DEPTH: usize = 8;
+
+/// `[telemetry]` section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Count anonymous usage metrics; off unless opted in
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// URL that batches of counts are POSTed to; empty keeps them local
+    #[serde(default)]
+    pub endpoint: String,
+
+    #[serde(default = "default_telemetry_upload_interval_secs")]
+    pub upload_interval_secs: u64,
+}
+
+fn default_telemetry_upload_interval_secs() -> u64 {
+    3600
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: String::new(),
+            upload_interval_secs: default_telemetry_upload_interval_secs(),
+        }
+    }
+}
+
+/// How strictly untrusted tool output is screened for prompt injection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InjectionGuardMode {
+    /// No scanning
+    Off,
+    /// Report findings and prefix the output with a warning
+    Flag,
+    /// Report findings and wrap the output in a quarantine frame
+    #[default]
+    Quarantine,
+    /// As `quarantine`, and also cut the suspicious passages out
+    Strict,
+}
+
+/// Handling of a saved session opened in a workspace other than the one it was saved in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkspaceMismatchMode {
+    /// Open it without comment
+    Ignore,
+    /// Open it and warn at the start of its next turn
+    #[default]
+    Warn,
+    /// Refuse to open it
+    Error,
+}
+
+/// How a provider's API key is attached to requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthStyle {
+    /// `Authorization: Bearer <key>` (OpenAI-compatible)
+    Bearer,
+    /// `api-key: <key>` (Azure OpenAI and compatible gateways)
+    ApiKey,
+    /// `x-api-key: <key>` (Anthropic)
+    XApiKey,
+    /// `<auth_header>: <key>`, the key sent as-is
+    Header,
+    /// `?key=<key>` query parameter (Gemini)
+    Query,
+    /// No key is sent; authentication is expected in `headers`
+    None,
 }
 
 /// LLM Provider configuration from Config.toml
@@ -123,7 +859,7 @@ pub struct ToolLsConfig {
     #[serde(default = "default_tool_name")]
     pub tool_name: String,
 
-    /// Maximum number of files to return
+    /// Maximum number of tree lines in one page of a listing
     #[serde(default = "default_max_ls_files")]
     pub max_ls_files: usize,
 
@@ -218,7 +954,7 @@ pub struct ToolGlobConfig {
     #[serde(default = "default_glob_name")]
     pub tool_name: String,
 
-    /// Maximum number of results to return
+    /// Maximum number of matches in one page of results
     #[serde(default = "default_max_glob_results")]
     pub max_glob_results: usize,
 
@@ -298,8 +1034,6 @@ fn default_safe_read_only_commands() -> Vec<String> {
         "groups".to_string(),
         "env".to_string(),
         "printenv".to_string(),
-        "set".to_string(),
-        "unset".to_string(),
         "which".to_string(),
         "type".to_string(),
         "whereis".to_string(),
@@ -309,12 +1043,8 @@ fn default_safe_read_only_commands() -> Vec<String> {
         "df".to_string(),
         "du".to_string(),
         "free".to_string(),
-        "top".to_string(),
         "ps".to_string(),
-        "kill".to_string(),
-        "killall".to_string(),
         "nice".to_string(),
-        "nohup".to_string(),
         "time".to_string(),
         "timeout".to_string(),
         "git status".to_string(),
@@ -408,6 +1138,202 @@ fn default_edit_desc() -> String {
     "Edits files by replacing text, creating new files, or deleting content.".to_string()
 }
 
+/// Tool Mkdir configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolMkdirConfig {
+    /// Tool name identifier
+    #[serde(default = "default_mkdir_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_mkdir_desc")]
+    pub description: String,
+}
+
+fn default_mkdir_name() -> String {
+    "mkdir".to_string()
+}
+
+fn default_mkdir_desc() -> String {
+    "Creates a directory, including missing parent directories.".to_string()
+}
+
+/// Tool Move configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolMoveConfig {
+    /// Tool name identifier
+    #[serde(default = "default_move_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_move_desc")]
+    pub description: String,
+}
+
+fn default_move_name() -> String {
+    "move".to_string()
+}
+
+fn default_move_desc() -> String {
+    "Moves or renames a file or directory.".to_string()
+}
+
+/// Tool ReadArtifact configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolReadArtifactConfig {
+    /// Tool name identifier
+    #[serde(default = "default_read_artifact_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_read_artifact_desc")]
+    pub description: String,
+}
+
+fn default_read_artifact_name() -> String {
+    "read_artifact".to_string()
+}
+
+fn default_read_artifact_desc() -> String {
+    "Reads part of a large tool output stored as an artifact.".to_string()
+}
+
+/// Tool RepoStats configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolRepoStatsConfig {
+    /// Tool name identifier
+    #[serde(default = "default_repo_stats_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_repo_stats_desc")]
+    pub description: String,
+
+    /// Largest and most changed files listed by default
+    #[serde(default = "default_repo_stats_top_files")]
+    pub top_files: usize,
+
+    /// Files scanned before the walk stops
+    #[serde(default = "default_repo_stats_max_files")]
+    pub max_files: usize,
+}
+
+fn default_repo_stats_name() -> String {
+    "repo_stats".to_string()
+}
+
+fn default_repo_stats_desc() -> String {
+    "Summarizes a repository: languages, largest files, dependency manifests and recent git activity.".to_string()
+}
+
+fn default_repo_stats_top_files() -> usize {
+    10
+}
+
+fn default_repo_stats_max_files() -> usize {
+    20000
+}
+
+/// Tool Deps configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDepsConfig {
+    /// Tool name identifier
+    #[serde(default = "default_deps_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_deps_desc")]
+    pub description: String,
+}
+
+fn default_deps_name() -> String {
+    "deps".to_string()
+}
+
+fn default_deps_desc() -> String {
+    "Lists the direct dependencies and scripts declared by dependency manifests.".to_string()
+}
+
+/// Tool Hover configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolHoverConfig {
+    /// Tool name identifier
+    #[serde(default = "default_hover_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_hover_desc")]
+    pub description: String,
+}
+
+fn default_hover_name() -> String {
+    "hover".to_string()
+}
+
+fn default_hover_desc() -> String {
+    "Shows the type signature and documentation of a symbol using the language server.".to_string()
+}
+
+/// Tool CodeAction configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCodeActionConfig {
+    /// Tool name identifier
+    #[serde(default = "default_code_action_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_code_action_desc")]
+    pub description: String,
+}
+
+fn default_code_action_name() -> String {
+    "code_action".to_string()
+}
+
+fn default_code_action_desc() -> String {
+    "Lists and applies language server code actions such as quick fixes.".to_string()
+}
+
+/// Tool RenameSymbol configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolRenameSymbolConfig {
+    /// Tool name identifier
+    #[serde(default = "default_rename_symbol_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_rename_symbol_desc")]
+    pub description: String,
+}
+
+fn default_rename_symbol_name() -> String {
+    "rename_symbol".to_string()
+}
+
+fn default_rename_symbol_desc() -> String {
+    "Renames a symbol across the workspace using the language server.".to_string()
+}
+
+/// Tool Delete configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDeleteConfig {
+    /// Tool name identifier
+    #[serde(default = "default_delete_name")]
+    pub tool_name: String,
+
+    /// Description of what this tool does
+    #[serde(default = "default_delete_desc")]
+    pub description: String,
+}
+
+fn default_delete_name() -> String {
+    "delete".to_string()
+}
+
+fn default_delete_desc() -> String {
+    "Deletes a file or directory by moving it to the session trash.".to_string()
+}
+
 /// Tool TodoWrite configuration from config.toml
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolTodoWriteConfig {
@@ -425,6 +1351,23 @@ fn default_todo_write_desc() -> String {
     "Manage task lists and track progress".to_string()
 }
 
+/// Tool AskUser configuration from config.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolAskUserConfig {
+    #[serde(default = "default_ask_user_name")]
+    pub tool_name: String,
+    #[serde(default = "default_ask_user_desc")]
+    pub description: String,
+}
+
+fn default_ask_user_name() -> String {
+    "ask_user".to_string()
+}
+
+fn default_ask_user_desc() -> String {
+    "Ask the user a clarifying question and wait for the answer".to_string()
+}
+
 /// Welcome configuration from Config.toml
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WelcomeConfig {
@@ -439,12 +1382,22 @@ fn default_banner() -> Vec<String> {
     vec!["CARRY".to_string(), "CODE".to_string()]
 }
 
+fn default_session_namespace() -> String {
+    crate::session::store::DEFAULT_NAMESPACE.to_string()
+}
+
 /// Runtime session configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuntimeSessionConfig {
     pub session_id: String,
+    /// Namespace the session was opened in; ids are only unique within one
+    #[serde(default = "default_session_namespace")]
+    pub namespace: String,
     pub agent_mode: String, // "plan" | "build"
     pub approval_mode: String, // "read-only" | "agent" | "agent-full"
+    /// "local" | "ssh" | "docker" | "devcontainer"; unset follows `[remote]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution_target: Option<String>,
 }
 
 /// Runtime configuration
@@ -455,6 +1408,50 @@ pub struct RuntimeConfig {
     pub default_model: Option<String>,
     #[serde(default)]
     pub sessions: Vec<RuntimeSessionConfig>,
+    #[serde(default)]
+    pub workspaces: Vec<RuntimeWorkspaceTrust>,
+    /// Telemetry choice made with `set_telemetry_enabled`, over `[telemetry] enabled`
+    #[serde(default)]
+    pub telemetry_enabled: Option<bool>,
+}
+
+/// Trust decision for a workspace directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuntimeWorkspaceTrust {
+    pub path: String, // canonical absolute path
+    pub level: String, // "trusted" | "untrusted"
+}
+
+pub const TRUST_LEVEL_TRUSTED: &str = "trusted";
+pub const TRUST_LEVEL_UNTRUSTED: &str = "untrusted";
+
+/// Key under which the trust decision for `path` is stored
+pub fn workspace_key(path: &Path) -> String {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+impl RuntimeConfig {
+    /// Trust level recorded for `workspace`, or None if it was never decided
+    pub fn workspace_trust(&self, workspace: &Path) -> Option<&str> {
+        let key = workspace_key(workspace);
+        self.workspaces
+            .iter()
+            .find(|w| w.path == key)
+            .map(|w| w.level.as_str())
+    }
+
+    /// Record the trust level for `workspace`, replacing an earlier decision
+    pub fn set_workspace_trust(&mut self, workspace: &Path, level: &str) {
+        let key = workspace_key(workspace);
+        self.workspaces.retain(|w| w.path != key);
+        self.workspaces.push(RuntimeWorkspaceTrust {
+            path: key,
+            level: level.to_string(),
+        });
+    }
 }
 
 /// Global application configuration
@@ -464,6 +1461,11 @@ pub struct AppConfig {
     #[serde(skip)]
     pub runtime: RuntimeConfig,
 
+    /// Whether the current working directory is a trusted workspace.
+    /// Project config (and the MCP servers it declares) is only loaded when set.
+    #[serde(skip)]
+    pub workspace_trusted: bool,
+
     /// UI Theme
     #[serde(default)]
     pub theme: Option<String>,
@@ -484,6 +1486,11 @@ pub struct AppConfig {
     #[serde(default)]
     pub default_model: Option<String>,
 
+    /// Model for internal tasks (titles, summaries, commit messages) that
+    /// have no `[models.routes]` entry; unset uses the session's model
+    #[serde(default)]
+    pub auxiliary_model: Option<String>,
+
     /// Prompt plan configuration
     #[serde(default)]
     pub prompt_plan: Option<PromptPlanConfig>,
@@ -524,25 +1531,126 @@ pub struct AppConfig {
     #[serde(rename = "tool_edit")]
     pub tool_edit: ToolEditConfig,
 
+    /// Mkdir tool configuration
+    #[serde(rename = "tool_mkdir")]
+    pub tool_mkdir: ToolMkdirConfig,
+
+    /// Move tool configuration
+    #[serde(rename = "tool_move")]
+    pub tool_move: ToolMoveConfig,
+
+    /// ReadArtifact tool configuration
+    #[serde(rename = "tool_read_artifact")]
+    pub tool_read_artifact: ToolReadArtifactConfig,
+
+    /// RepoStats tool configuration
+    #[serde(rename = "tool_repo_stats")]
+    pub tool_repo_stats: ToolRepoStatsConfig,
+
+    /// Deps tool configuration
+    #[serde(rename = "tool_deps")]
+    pub tool_deps: ToolDepsConfig,
+
+    /// Hover tool configuration
+    #[serde(rename = "tool_hover")]
+    pub tool_hover: ToolHoverConfig,
+
+    /// CodeAction tool configuration
+    #[serde(rename = "tool_code_action")]
+    pub tool_code_action: ToolCodeActionConfig,
+
+    /// RenameSymbol tool configuration
+    #[serde(rename = "tool_rename_symbol")]
+    pub tool_rename_symbol: ToolRenameSymbolConfig,
+
+    /// Delete tool configuration
+    #[serde(rename = "tool_delete")]
+    pub tool_delete: ToolDeleteConfig,
+
     /// TodoWrite tool configuration
     #[serde(rename = "tool_todo_write")]
     pub tool_todo_write: ToolTodoWriteConfig,
 
+    /// AskUser tool configuration
+    #[serde(rename = "tool_ask_user")]
+    pub tool_ask_user: ToolAskUserConfig,
+
     /// LSP configuration
     #[serde(default)]
     pub lsp: LspConfig,
 
+    /// Security configuration
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    /// Privacy configuration
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+
+    /// Usage metrics configuration
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// Model aliases and routing
+    #[serde(default)]
+    pub models: ModelsConfig,
+
+    /// Per-turn environment header
+    #[serde(default)]
+    pub context_header: ContextHeaderConfig,
+
+    /// Extra context gathered for every turn
+    #[serde(default)]
+    pub context_providers: ContextProvidersConfig,
+
+    /// How tool definitions are sent as the session goes on
+    #[serde(default)]
+    pub tool_definitions: ToolDefinitionsConfig,
+
+    /// Machine capabilities added to the system prompt
+    #[serde(default)]
+    pub environment_probe: EnvironmentProbeConfig,
+
+    /// Skills enabled by default
+    #[serde(default)]
+    pub skills: SkillsConfig,
+
+    /// Post-processing of final answers
+    #[serde(default)]
+    pub answer_format: AnswerFormatConfig,
+
+    /// Actions run when a long turn ends or input is needed
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    /// Host the tools execute on instead of the local workspace
+    #[serde(default)]
+    pub remote: RemoteConfig,
+
+    /// Tool execution time limits
+    #[serde(default)]
+    pub tool_timeouts: ToolTimeoutsConfig,
+
+    /// MCP server process limits and restarts
+    #[serde(default)]
+    pub mcp: McpConfig,
+
     /// MCP servers configuration
     #[serde(default)]
     pub mcp_servers: HashMap<String, McpServerConfig>,
+
+    /// When open sessions are dropped from memory
+    #[serde(default)]
+    pub sessions: SessionsConfig,
 }
 
 impl AppConfig {
     /// Load configuration with layered strategy:
     /// 1. Defaults (Embedded Config.toml)
-    /// 2. User Config (~/.carry/carrycode.json) - Only theme/providers
-    /// 3. Project Config (./.carry/carrycode.json) - Only theme/providers
-    /// 4. Runtime Config (~/.carry/carrycode-runtime.json) - Runtime state
+    /// 2. User Config (`paths::user_config_file`) - Only theme/providers
+    /// 3. Runtime Config (`paths::runtime_config_file`) - Runtime state
+    /// 4. Project Config (./.carry/carrycode.json) - Only theme/providers,
+    ///    applied only if the workspace was trusted via the runtime config
     pub fn load() -> Result<Self> {
         // 1. Load Base Config (Embedded)
         let default_str = include_str!("../Config.toml");
@@ -550,24 +1658,18 @@ impl AppConfig {
             .context("Failed to parse embedded Config.toml")?;
 
         // 2. Apply User Config Patch
-        if let Some(home) = dirs::home_dir() {
-            let user_path = home.join(".carry").join("carrycode.json");
+        if let Some(user_path) = paths::user_config_file() {
             Self::apply_patch(&mut config, user_path);
         }
 
-        // 3. Apply Project Config Patch
-        let project_path = Path::new(".carry").join("carrycode.json");
-        Self::apply_patch(&mut config, project_path);
-
         if config.theme.is_none() {
             config.theme = config.welcome.as_ref().and_then(|w| w.theme.clone());
         }
 
-        // 4. Load Runtime Config
+        // 3. Load Runtime Config
         let mut runtime_needs_save = false;
         let mut runtime_file_exists = false;
-        if let Some(home) = dirs::home_dir() {
-            let runtime_path = home.join(".carry").join("carrycode-runtime.json");
+        if let Some(runtime_path) = paths::runtime_config_file() {
             if runtime_path.exists() {
                 runtime_file_exists = true;
                  if let Ok(content) = fs::read_to_string(&runtime_path) {
@@ -594,6 +1696,19 @@ impl AppConfig {
              runtime_needs_save = true;
         }
 
+        // 4. Apply Project Config Patch (trusted workspaces only)
+        config.workspace_trusted = config.runtime.workspace_trust(Path::new("."))
+            == Some(TRUST_LEVEL_TRUSTED);
+        let project_path = Path::new(".carry").join("carrycode.json");
+        if config.workspace_trusted {
+            Self::apply_patch(&mut config, project_path);
+        } else if project_path.exists() {
+            log::warn!(
+                "Workspace is not trusted, ignoring project config {}",
+                project_path.display()
+            );
+        }
+
         let (resolved_default_model, should_save_default_model) = resolve_default_model(
             runtime_file_exists,
             config.runtime.default_model.clone(),
@@ -612,13 +1727,41 @@ impl AppConfig {
         Ok(config)
     }
 
+    /// Resolve a model reference (an alias, "provider:model" or a model
+    /// name) to a configured (provider, model) pair
+    pub fn resolve_model(&self, reference: &str) -> Option<(String, String)> {
+        let mut reference = reference.trim();
+        for _ in 0..MAX_ALIAS_DEPTH {
+            match self.models.aliases.get(reference) {
+                Some(target) => reference = target.trim(),
+                None => break,
+            }
+        }
+        if reference.is_empty() {
+            return None;
+        }
+        if let Some((provider, model)) = reference.split_once(':') {
+            if self.providers.iter().any(|p| p.name == provider) {
+                return Some((provider.to_string(), model.to_string()));
+            }
+        }
+        // Model names may contain ':' themselves (e.g. "llama3:8b")
+        self.providers
+            .iter()
+            .find(|p| p.models.iter().any(|m| m == reference))
+            .map(|p| (p.name.clone(), reference.to_string()))
+    }
+
+    /// Model routed to an internal task in `[models.routes]`, if it resolves
+    pub fn route_model(&self, task: &str) -> Option<(String, String)> {
+        self.models.routes.get(task).and_then(|r| self.resolve_model(r))
+    }
+
     pub fn save_runtime(&self) -> Result<()> {
-        if let Some(home) = dirs::home_dir() {
-            let config_dir = home.join(".carry");
-            if !config_dir.exists() {
-                fs::create_dir_all(&config_dir)?;
+        if let Some(runtime_path) = paths::runtime_config_file() {
+            if let Some(config_dir) = runtime_path.parent() {
+                fs::create_dir_all(config_dir)?;
             }
-            let runtime_path = config_dir.join("carrycode-runtime.json");
             let content = serde_json::to_string_pretty(&self.runtime)?;
             fs::write(runtime_path, content)?;
         }
@@ -642,6 +1785,47 @@ impl AppConfig {
                                 config.mcp_servers.insert(name, server);
                             }
                         }
+                        if let Some(security) = patch.security {
+                            config.security = security;
+                        }
+                        if let Some(privacy) = patch.privacy {
+                            config.privacy = privacy;
+                        }
+                        if let Some(auxiliary_model) = patch.auxiliary_model {
+                            config.auxiliary_model = Some(auxiliary_model);
+                        }
+                        if let Some(context_header) = patch.context_header {
+                            config.context_header = context_header;
+                        }
+                        if let Some(context_providers) = patch.context_providers {
+                            config.context_providers = context_providers;
+                        }
+                        if let Some(tool_definitions) = patch.tool_definitions {
+                            config.tool_definitions = tool_definitions;
+                        }
+                        if let Some(environment_probe) = patch.environment_probe {
+                            config.environment_probe = environment_probe;
+                        }
+                        if let Some(skills) = patch.skills {
+                            config.skills = skills;
+                        }
+                        if let Some(answer_format) = patch.answer_format {
+                            config.answer_format = answer_format;
+                        }
+                        if let Some(notify) = patch.notify {
+                            config.notify = notify;
+                        }
+                        if let Some(remote) = patch.remote {
+                            config.remote = remote;
+                        }
+                        if let Some(mcp) = patch.mcp {
+                            config.mcp = mcp;
+                        }
+                        if let Some(models) = patch.models {
+                            // Merge aliases and routes
+                            config.models.aliases.extend(models.aliases);
+                            config.models.routes.extend(models.routes);
+                        }
                     }
                     Err(e) => {
                         eprintln!("Warning: Failed to parse config patch at {}: {}", path.display(), e);
@@ -678,7 +1862,9 @@ fn resolve_default_model(
 
 #[cfg(test)]
 mod tests {
-    use super::{resolve_default_model, ProviderConfig, RuntimeConfig};
+    use super::{
+        resolve_default_model, AppConfig, ProviderConfig, RuntimeConfig, TRUST_LEVEL_TRUSTED, TRUST_LEVEL_UNTRUSTED,
+    };
 
     #[test]
     fn runtime_config_deserializes_without_default_model() {
@@ -694,6 +1880,18 @@ mod tests {
         assert_eq!(cfg.default_model.as_deref(), Some("openai:gpt-4o-mini"));
     }
 
+    #[test]
+    fn workspace_trust_is_recorded_per_canonical_path() {
+        let dir = std::env::temp_dir();
+        let mut cfg = RuntimeConfig::default();
+        assert!(cfg.workspace_trust(&dir).is_none());
+
+        cfg.set_workspace_trust(&dir, TRUST_LEVEL_UNTRUSTED);
+        cfg.set_workspace_trust(&dir.join("."), TRUST_LEVEL_TRUSTED);
+        assert_eq!(cfg.workspaces.len(), 1);
+        assert_eq!(cfg.workspace_trust(&dir), Some(TRUST_LEVEL_TRUSTED));
+    }
+
     #[test]
     fn resolve_default_model_falls_back_when_runtime_missing() {
         let providers = vec![ProviderConfig {
@@ -701,6 +1899,7 @@ mod tests {
             base_url: "http://localhost:1234".to_string(),
             api_key: "k".to_string(),
             models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
         }];
         let (v, should_save) = resolve_default_model(false, None, &providers);
         assert_eq!(v.as_deref(), Some("openai:gpt-4o-mini"));
@@ -714,6 +1913,7 @@ mod tests {
             base_url: "http://localhost:1234".to_string(),
             api_key: "k".to_string(),
             models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
         }];
         let (v, should_save) =
             resolve_default_model(true, Some("   ".to_string()), &providers);
@@ -728,10 +1928,39 @@ mod tests {
             base_url: "http://localhost:1234".to_string(),
             api_key: "k".to_string(),
             models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
         }];
         let (v, should_save) =
             resolve_default_model(true, Some("openai:gpt-4o-mini".to_string()), &providers);
         assert_eq!(v.as_deref(), Some("openai:gpt-4o-mini"));
         assert!(!should_save);
     }
+
+    #[test]
+    fn resolve_model_follows_aliases_and_routes() {
+        let mut config: AppConfig = toml::from_str(include_str!("../Config.toml")).unwrap();
+        config.providers = vec![ProviderConfig {
+            name: "local".to_string(),
+            base_url: "http://localhost:11434".to_string(),
+            api_key: String::new(),
+            models: vec!["llama3:8b".to_string(), "qwen2.5-coder".to_string()],
+            ..Default::default()
+        }];
+        let aliases = &mut config.models.aliases;
+        aliases.insert("fast".to_string(), "local:llama3:8b".to_string());
+        aliases.insert("cheap".to_string(), "fast".to_string());
+        aliases.insert("smart".to_string(), "qwen2.5-coder".to_string());
+        aliases.insert("loop".to_string(), "loop".to_string());
+        config.models.routes.insert("title".to_string(), "cheap".to_string());
+
+        let pair = |p: &str, m: &str| Some((p.to_string(), m.to_string()));
+        assert_eq!(config.resolve_model("fast"), pair("local", "llama3:8b"));
+        assert_eq!(config.resolve_model("cheap"), pair("local", "llama3:8b"));
+        assert_eq!(config.resolve_model("smart"), pair("local", "qwen2.5-coder"));
+        assert_eq!(config.resolve_model("llama3:8b"), pair("local", "llama3:8b"));
+        assert_eq!(config.resolve_model("other:gpt-4o"), None);
+        assert_eq!(config.resolve_model("loop"), None);
+        assert_eq!(config.route_model("title"), pair("local", "llama3:8b"));
+        assert_eq!(config.route_model("summary"), None);
+    }
 }
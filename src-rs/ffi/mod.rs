@@ -1,4 +1,109 @@
-mod session_util;
 mod session;
 
 pub use session::*;
+
+use napi::bindgen_prelude::Result;
+use napi_derive::napi;
+
+use crate::api::{self, AvailableModel, ModelAlias};
+use session::namespace_or_default;
+
+/// Error for JS callers, with the context chain of `e` ("outer: inner")
+pub(crate) fn napi_error(e: anyhow::Error) -> napi::Error {
+    napi::Error::from_reason(format!("{:#}", e))
+}
+
+#[napi]
+pub fn get_app_config() -> String {
+    match api::app_config_json() {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to load config: {:?}", e);
+            "{}".to_string()
+        }
+    }
+}
+
+#[napi]
+pub fn list_available_models() -> Result<Vec<AvailableModel>> {
+    api::list_available_models().map_err(napi_error)
+}
+
+/// Opt in to (or out of) anonymous usage metrics; the choice is saved
+#[napi]
+pub fn set_telemetry_enabled(enabled: bool) -> Result<()> {
+    api::set_telemetry_enabled(enabled).map_err(napi_error)
+}
+
+/// Usage counts recorded since the process started
+#[napi]
+pub fn get_telemetry_metrics() -> api::TelemetryMetrics {
+    api::get_telemetry_metrics()
+}
+
+/// Write user config, runtime config and skills to one bundle file; API keys
+/// and other secrets are included, encrypted, only with a passphrase
+#[napi]
+pub fn export_settings(path: String, passphrase: Option<String>) -> Result<api::SettingsBundleInfo> {
+    api::export_settings(&path, passphrase).map_err(napi_error)
+}
+
+/// Install the settings of a bundle written by `export_settings`
+#[napi]
+pub fn import_settings(path: String, passphrase: Option<String>) -> Result<api::SettingsBundleInfo> {
+    api::import_settings(&path, passphrase).map_err(napi_error)
+}
+
+/// Event protocol versions and features the core supports, and those negotiated
+#[napi]
+pub fn get_protocol_info() -> api::ProtocolInfo {
+    api::get_protocol_info()
+}
+
+/// Send events at the frontend's protocol version, with only the features it handles
+#[napi]
+pub fn negotiate_protocol(version: u32, features: Option<Vec<String>>) -> Result<api::ProtocolInfo> {
+    api::negotiate_protocol(version, features).map_err(napi_error)
+}
+
+/// Whether the user is looking at the app; `[notify] only_when_away` holds
+/// notifications back while they are
+#[napi]
+pub fn set_user_present(present: bool) {
+    api::set_user_present(present);
+}
+
+/// Write pending session snapshots; the host calls this on exit
+#[napi]
+pub fn flush_sessions() {
+    api::flush_sessions();
+}
+
+/// Write pending snapshots and stop every MCP server process; the host calls this on exit
+#[napi]
+pub fn shutdown() {
+    api::shutdown();
+}
+
+/// Close an open session and stop its MCP servers; false if it was not open
+#[napi]
+pub fn close_session(session_id: String, namespace: Option<String>) -> Result<bool> {
+    api::close_session(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+#[napi]
+pub fn list_model_aliases() -> Result<Vec<ModelAlias>> {
+    api::list_model_aliases().map_err(napi_error)
+}
+
+#[napi]
+pub fn get_default_model() -> Result<Option<String>> {
+    api::get_default_model().map_err(napi_error)
+}
+
+// Keep legacy function for compatibility
+#[napi]
+pub fn generate_random_lines() -> Vec<String> {
+    crate::init_logger();
+    vec!["Legacy function kept for compatibility".to_string()]
+}
@@ -2,155 +2,580 @@ use napi::bindgen_prelude::*;
 use napi::JsFunction;
 use napi_derive::napi;
 
-use crate::llm::agents::agent::Agent as RustAgent;
+use crate::api::{
+    self, AgentResult, AllowedToolsInfo, AutoModeOptions, AutoRunResult, AvailableModel, CommandResult, CoreStatus,
+    InterruptedTurnInfo, LatencyInfo, LspServerStatus, OutputLimitSettings,
+    OverlayChangeInfo, OverlayMaterializeResult, PinnedMessage, PlanRunResult, PreflightFinding, ProviderMessage,
+    SavedSessionInfo, SessionSkill, SessionVar, ShellStateInfo,
+    SavedSessionPage, SavedSessionQuery, SyncStatusInfo, ToolChoiceSettings, TrashEntryInfo, WorkspaceTrustInfo,
+};
+use crate::session::events::SessionEventSink;
 use crate::session::generate_session_id;
-use crate::session::types::CoreConfirmDecision;
-use crate::session::{clear_event_sink, set_event_sink};
-use crate::session::context::SessionEventSink;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use crate::session::types::{
+    CoreConfirmDecision, CoreEvent, CoreEventFilter, CoreProposal, CoreTextEvent, CoreToolStat, CoreTurnTiming,
+};
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction};
 
-use super::session_util::{self, AvailableModel, ProviderMessage, SavedSessionInfo};
+use super::napi_error;
+
+/// `namespace`, or the one of a host with a single user
+pub(crate) fn namespace_or_default(namespace: Option<String>) -> String {
+    namespace.unwrap_or_else(|| api::DEFAULT_NAMESPACE.to_string())
+}
 
 #[napi]
 pub fn create_session_id() -> String {
     generate_session_id()
 }
 
+/// Parse and run a slash command (e.g. `/model`, `/clear`) against an open session
 #[napi]
-pub struct Session {
-    inner: Arc<Mutex<RustAgent>>,
-    confirmation_sender: Arc<Mutex<Option<session_util::PendingConfirmation>>>,
+pub async fn dispatch_command(session_id: String, input: String, namespace: Option<String>) -> Result<CommandResult> {
+    api::dispatch_command(&namespace_or_default(namespace), &session_id, &input).await.map_err(napi_error)
+}
+
+/// Pin a message so `/compact` keeps it verbatim
+#[napi]
+pub async fn pin_message(session_id: String, index: u32, namespace: Option<String>) -> Result<()> {
+    api::pin_message(&namespace_or_default(namespace), &session_id, index).await.map_err(napi_error)
+}
+
+#[napi]
+pub async fn unpin_message(session_id: String, index: u32, namespace: Option<String>) -> Result<()> {
+    api::unpin_message(&namespace_or_default(namespace), &session_id, index).await.map_err(napi_error)
+}
+
+#[napi]
+pub async fn get_pinned(session_id: String, namespace: Option<String>) -> Result<Vec<PinnedMessage>> {
+    api::get_pinned(&namespace_or_default(namespace), &session_id).await.map_err(napi_error)
+}
+
+/// Title the session; an empty title removes it
+#[napi]
+pub async fn rename_session(session_id: String, title: String, namespace: Option<String>) -> Result<()> {
+    api::rename_session(&namespace_or_default(namespace), &session_id, &title).await.map_err(napi_error)
+}
+
+#[napi]
+pub async fn set_session_notes(session_id: String, text: String, namespace: Option<String>) -> Result<()> {
+    api::set_session_notes(&namespace_or_default(namespace), &session_id, &text).await.map_err(napi_error)
+}
+
+#[napi]
+pub async fn set_session_var(session_id: String, key: String, value: String, namespace: Option<String>) -> Result<()> {
+    api::set_session_var(&namespace_or_default(namespace), &session_id, &key, &value).await.map_err(napi_error)
+}
+
+#[napi]
+pub fn get_session_vars(session_id: String, namespace: Option<String>) -> Result<Vec<SessionVar>> {
+    api::get_session_vars(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Enable skills in the session, replacing those enabled before
+#[napi]
+pub async fn set_session_skills(session_id: String, names: Vec<String>, namespace: Option<String>) -> Result<()> {
+    api::set_session_skills(&namespace_or_default(namespace), &session_id, names).await.map_err(napi_error)
+}
+
+#[napi]
+pub fn get_session_skills(session_id: String, namespace: Option<String>) -> Result<Vec<SessionSkill>> {
+    api::get_session_skills(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// The session's tools the active skills allow, and the skill denying each of the others
+#[napi]
+pub async fn get_allowed_tools(session_id: String, namespace: Option<String>) -> Result<AllowedToolsInfo> {
+    api::get_allowed_tools(&namespace_or_default(namespace), &session_id).await.map_err(napi_error)
+}
+
+/// Take an advisory edit lock on a file, e.g. while the user edits it.
+/// Returns false if a session currently holds the lock.
+#[napi]
+pub fn lock_file(path: String, owner: String) -> Result<bool> {
+    api::lock_file(&path, &owner).map_err(napi_error)
+}
+
+/// Release a lock taken with `lockFile`
+#[napi]
+pub fn unlock_file(path: String, owner: String) -> Result<bool> {
+    api::unlock_file(&path, &owner).map_err(napi_error)
+}
+
+/// Paths deleted by the delete tool in a session, oldest first
+#[napi]
+pub fn list_trash(session_id: String, namespace: Option<String>) -> Result<Vec<TrashEntryInfo>> {
+    api::list_trash(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Restore a deleted path from the session trash
+#[napi]
+pub fn restore_from_trash(session_id: String, entry_id: String, namespace: Option<String>) -> Result<TrashEntryInfo> {
+    api::restore_from_trash(&namespace_or_default(namespace), &session_id, &entry_id).map_err(napi_error)
+}
+
+/// Permanently empty the trash of a session, or of all sessions of the namespace
+#[napi]
+pub fn purge_trash(session_id: Option<String>, namespace: Option<String>) -> Result<u32> {
+    api::purge_trash(&namespace_or_default(namespace), session_id.as_deref()).map_err(napi_error)
+}
+
+/// Copy workspace files to the session's execution target
+#[napi]
+pub async fn push_files(
+    session_id: String,
+    paths: Vec<String>,
+    force: Option<bool>,
+    namespace: Option<String>,
+) -> Result<Vec<SyncStatusInfo>> {
+    api::push_files(&namespace_or_default(namespace), &session_id, paths, force.unwrap_or(false))
+        .await
+        .map_err(napi_error)
+}
+
+/// Copy files from the session's execution target into the workspace
+#[napi]
+pub async fn pull_files(
+    session_id: String,
+    paths: Vec<String>,
+    force: Option<bool>,
+    namespace: Option<String>,
+) -> Result<Vec<SyncStatusInfo>> {
+    api::pull_files(&namespace_or_default(namespace), &session_id, paths, force.unwrap_or(false))
+        .await
+        .map_err(napi_error)
+}
+
+/// Sync state of files between the workspace and the session's execution target
+#[napi]
+pub async fn get_sync_status(
+    session_id: String,
+    paths: Option<Vec<String>>,
+    namespace: Option<String>,
+) -> Result<Vec<SyncStatusInfo>> {
+    api::get_sync_status(&namespace_or_default(namespace), &session_id, paths.unwrap_or_default())
+        .await
+        .map_err(napi_error)
+}
+
+/// Keep the last turn's changes to the given files (all of them when empty)
+#[napi]
+pub fn accept_turn_changes(session_id: String, files: Vec<String>, namespace: Option<String>) -> Result<Vec<String>> {
+    api::accept_turn_changes(&namespace_or_default(namespace), &session_id, files).map_err(napi_error)
+}
+
+/// Put the given files (all of them when empty) back as they were before the last turn
+#[napi]
+pub fn revert_turn_changes(session_id: String, files: Vec<String>, namespace: Option<String>) -> Result<Vec<String>> {
+    api::revert_turn_changes(&namespace_or_default(namespace), &session_id, files).map_err(napi_error)
+}
+
+/// Make the session's file tools write to a copy-on-write overlay instead of
+/// the workspace. Switching off fails while the overlay holds changes.
+#[napi]
+pub fn set_overlay_mode(session_id: String, enabled: bool, namespace: Option<String>) -> Result<()> {
+    api::set_overlay_mode(&namespace_or_default(namespace), &session_id, enabled).map_err(napi_error)
+}
+
+/// Let the session's edits of paths matching the gitignore-style globs run
+/// without confirmation in read-only mode; an empty list clears them
+#[napi]
+pub fn set_auto_accept_paths(session_id: String, globs: Vec<String>, namespace: Option<String>) -> Result<()> {
+    api::set_auto_accept_paths(&namespace_or_default(namespace), &session_id, globs).map_err(napi_error)
+}
+
+#[napi]
+pub fn get_auto_accept_paths(session_id: String, namespace: Option<String>) -> Result<Vec<String>> {
+    api::get_auto_accept_paths(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Hold the session's edit, delete and move calls back as proposals for review
+#[napi]
+pub fn set_propose_mode(session_id: String, enabled: bool, namespace: Option<String>) -> Result<()> {
+    api::set_propose_mode(&namespace_or_default(namespace), &session_id, enabled).map_err(napi_error)
+}
+
+#[napi]
+pub fn get_propose_mode(session_id: String, namespace: Option<String>) -> Result<bool> {
+    api::get_propose_mode(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Stop the session's responses past these lengths; None goes back to `[sessions]`
+#[napi]
+pub fn set_output_limit(
+    session_id: String,
+    limit: Option<OutputLimitSettings>,
+    namespace: Option<String>,
+) -> Result<()> {
+    api::set_output_limit(&namespace_or_default(namespace), &session_id, limit).map_err(napi_error)
+}
+
+#[napi]
+pub fn get_output_limit(session_id: String, namespace: Option<String>) -> Result<OutputLimitSettings> {
+    api::get_output_limit(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+#[napi]
+pub fn get_proposals(session_id: String, namespace: Option<String>) -> Result<Vec<CoreProposal>> {
+    api::get_proposals(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Apply pending proposals (all if `ids` is empty) in the order they were made
+#[napi]
+pub async fn apply_proposals(
+    session_id: String,
+    ids: Vec<String>,
+    namespace: Option<String>,
+) -> Result<Vec<CoreProposal>> {
+    api::apply_proposals(&namespace_or_default(namespace), &session_id, ids).await.map_err(napi_error)
+}
+
+#[napi]
+pub fn discard_proposals(session_id: String, ids: Vec<String>, namespace: Option<String>) -> Result<u32> {
+    api::discard_proposals(&namespace_or_default(namespace), &session_id, ids).map_err(napi_error)
+}
+
+#[napi]
+pub fn answer_question(
+    session_id: String,
+    request_id: String,
+    answer: String,
+    namespace: Option<String>,
+) -> Result<bool> {
+    api::answer_question(&namespace_or_default(namespace), &session_id, &request_id, &answer).map_err(napi_error)
+}
+
+#[napi]
+pub fn cancel_tool(session_id: String, request_id: String, namespace: Option<String>) -> Result<bool> {
+    api::cancel_tool(&namespace_or_default(namespace), &session_id, &request_id).map_err(napi_error)
+}
+
+#[napi]
+pub fn get_overlay_mode(session_id: String, namespace: Option<String>) -> Result<bool> {
+    api::get_overlay_mode(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Pending overlay changes of a session, with diffs
+#[napi]
+pub fn get_overlay_changes(session_id: String, namespace: Option<String>) -> Result<Vec<OverlayChangeInfo>> {
+    api::get_overlay_changes(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Apply the overlay changes to the workspace; conflicting ones stay pending
+#[napi]
+pub fn materialize_changes(session_id: String, namespace: Option<String>) -> Result<OverlayMaterializeResult> {
+    api::materialize_changes(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Drop the overlay changes of a session
+#[napi]
+pub fn discard_changes(session_id: String, namespace: Option<String>) -> Result<u32> {
+    api::discard_changes(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Current working directory and exported environment changes of the bash shell
+#[napi]
+pub fn get_shell_state(session_id: String, namespace: Option<String>) -> Result<ShellStateInfo> {
+    api::get_shell_state(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Events logged for a session, open or saved, oldest first
+#[napi]
+pub fn get_session_events(
     session_id: String,
+    filter: Option<CoreEventFilter>,
+    namespace: Option<String>,
+) -> Result<Vec<CoreEvent>> {
+    api::get_session_events(&namespace_or_default(namespace), &session_id, &filter.unwrap_or_default())
+        .map_err(napi_error)
+}
+
+/// Calls, failures and time of every tool the session has run, longest first
+#[napi]
+pub fn get_tool_stats(session_id: String, namespace: Option<String>) -> Result<Vec<CoreToolStat>> {
+    api::get_tool_stats(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Timing of the session's latest turns, oldest first
+#[napi]
+pub fn get_turn_timings(session_id: String, namespace: Option<String>) -> Result<Vec<CoreTurnTiming>> {
+    api::get_turn_timings(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+}
+
+/// Trust decision for a workspace, defaulting to the current working directory.
+/// `level` is null until `trustWorkspace` has been called for it.
+#[napi]
+pub fn get_workspace_trust(path: Option<String>) -> Result<WorkspaceTrustInfo> {
+    api::get_workspace_trust(path.as_deref()).map_err(napi_error)
 }
 
-#[napi(object)]
-pub struct AgentResult {
-    pub content: String,
-    #[napi(js_name = "toolsUsed")]
-    pub tools_used: bool,
+/// Mark a workspace as "trusted" or "untrusted". Only trusted workspaces load
+/// their project config and the MCP servers it declares.
+#[napi]
+pub fn trust_workspace(path: String, level: String) -> Result<WorkspaceTrustInfo> {
+    api::trust_workspace(&path, &level).map_err(napi_error)
+}
+
+/// Health of the language servers used by the LSP tools: state, restart count
+/// and last error per server. Empty until an LSP tool has run.
+#[napi]
+pub async fn get_lsp_status() -> Result<Vec<LspServerStatus>> {
+    Ok(api::get_lsp_status().await)
+}
+
+/// Versions, sessions and subsystem health, for a diagnostics panel
+#[napi]
+pub async fn get_core_status() -> Result<CoreStatus> {
+    api::get_core_status().await.map_err(napi_error)
+}
+
+#[napi]
+pub async fn run_preflight() -> Result<Vec<PreflightFinding>> {
+    api::run_preflight().await.map_err(napi_error)
+}
+
+#[napi]
+pub struct Session {
+    core: api::Session,
 }
 
 #[napi]
 impl Session {
+    /// Open a session; `namespace` keeps different users' sessions apart
     #[napi(factory)]
-    pub fn open(session_id: String) -> Result<Self> {
-        let parts = session_util::open_session(session_id)?;
+    pub fn open(session_id: String, namespace: Option<String>) -> Result<Self> {
         Ok(Self {
-            inner: parts.inner,
-            confirmation_sender: Arc::new(Mutex::new(None)),
-            session_id: parts.session_id,
+            core: api::Session::open(namespace_or_default(namespace), session_id).map_err(napi_error)?,
         })
     }
 
+    #[napi(getter)]
+    pub fn namespace(&self) -> String {
+        self.core.namespace().to_string()
+    }
+
+    /// A repeat with the same `idempotency_key` is ignored
     #[napi]
-    pub async fn confirm_tool(&self, decision: CoreConfirmDecision) -> Result<()> {
-        session_util::confirm_tool(&self.session_id, &self.confirmation_sender, decision).await
+    pub async fn confirm_tool(&self, decision: CoreConfirmDecision, idempotency_key: Option<String>) -> Result<()> {
+        self.core.confirm_tool_with_key(decision, idempotency_key).await.map_err(napi_error)
     }
 
+    /// Receive every event, streamed text included, as `(err, event)`
     #[napi]
     pub fn subscribe(&self, on_event: JsFunction) -> Result<()> {
-        let tsfn = on_event.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
-
-        let sink = SessionEventSink {
-            handler: tsfn,
-        };
-
-        if !set_event_sink(&self.session_id, sink) {
-            return Err(Error::from_reason("Session not found"));
-        }
+        let tsfn: ThreadsafeFunction<CoreEvent, ErrorStrategy::CalleeHandled> =
+            on_event.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+        self.core.subscribe(SessionEventSink::unified(tsfn)).map_err(napi_error)
+    }
 
-        Ok(())
+    /// Receive streamed text and control events on separate callbacks.
+    /// Both share one `seq` counter; see `session::events` for the delivery rules.
+    #[napi(ts_args_type = "onText: (event: CoreTextEvent) => void, onControl: (event: CoreEvent) => void")]
+    pub fn subscribe_channels(&self, on_text: JsFunction, on_control: JsFunction) -> Result<()> {
+        let text: ThreadsafeFunction<CoreTextEvent, ErrorStrategy::Fatal> =
+            on_text.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+        let control: ThreadsafeFunction<CoreEvent, ErrorStrategy::Fatal> =
+            on_control.create_threadsafe_function(0, |ctx| Ok(vec![ctx.value]))?;
+        self.core.subscribe(SessionEventSink::split(text, control)).map_err(napi_error)
     }
 
     #[napi]
     pub fn unsubscribe(&self) -> Result<()> {
-        clear_event_sink(&self.session_id);
+        self.core.unsubscribe();
         Ok(())
     }
 
+    /// A repeat with the same `idempotency_key`, e.g. an IPC retry, gets the
+    /// first call's result instead of running another turn
     #[napi]
-    pub async fn execute(&self, prompt: String) -> Result<AgentResult> {
-        let result = session_util::execute_session(
-            &self.session_id,
-            &self.inner,
-            &self.confirmation_sender,
-            prompt,
-        )
-        .await?;
-        Ok(AgentResult {
-            content: result.content,
-            tools_used: result.tools_used,
-        })
+    pub async fn execute(&self, prompt: String, idempotency_key: Option<String>) -> Result<AgentResult> {
+        self.core.execute_with_key(prompt, idempotency_key).await.map_err(napi_error)
+    }
+
+    /// Run a prompt autonomously within a wall-clock budget, emitting
+    /// Checkpoint events that can be answered with `steer`
+    #[napi]
+    pub async fn execute_auto(&self, prompt: String, options: AutoModeOptions) -> Result<AutoRunResult> {
+        self.core.execute_auto(prompt, options).await.map_err(napi_error)
+    }
+
+    /// Steer a running autonomous turn at its next checkpoint
+    #[napi]
+    pub fn steer(&self, message: String) -> Result<bool> {
+        self.core.steer(message).map_err(napi_error)
+    }
+
+    /// Plan in Plan mode, ask for approval once (answered with `confirmTool`),
+    /// then execute the plan step by step in Build mode
+    #[napi]
+    pub async fn execute_plan_then_build(&self, prompt: String) -> Result<PlanRunResult> {
+        self.core.execute_plan_then_build(prompt).await.map_err(napi_error)
+    }
+
+    /// Continue a plan paused on a failed or cancelled step
+    #[napi]
+    pub async fn resume_plan(&self) -> Result<PlanRunResult> {
+        self.core.resume_plan().await.map_err(napi_error)
+    }
+
+    /// The turn the host process stopped in the middle of, if the session had
+    /// one when it was opened
+    #[napi]
+    pub fn get_interrupted_turn(&self) -> Result<Option<InterruptedTurnInfo>> {
+        self.core.get_interrupted_turn().map_err(napi_error)
+    }
+
+    /// Restore what the interrupted turn did and let the model finish it
+    #[napi]
+    pub async fn resume_interrupted_turn(&self) -> Result<AgentResult> {
+        self.core.resume_interrupted_turn().await.map_err(napi_error)
+    }
+
+    /// Drop the interrupted turn; false if there was none
+    #[napi]
+    pub fn discard_interrupted_turn(&self) -> Result<bool> {
+        self.core.discard_interrupted_turn().map_err(napi_error)
+    }
+
+    /// Cancel the running turn, including a running bash command
+    #[napi]
+    pub async fn cancel(&self) -> Result<bool> {
+        self.core.cancel().await.map_err(napi_error)
+    }
+
+    /// Close the session and stop its MCP servers; false if it was already closed
+    #[napi]
+    pub fn close(&self) -> Result<bool> {
+        self.core.close().map_err(napi_error)
     }
 
     #[napi]
     pub async fn clear_history(&self) -> Result<()> {
-        session_util::clear_history(&self.session_id, &self.inner).await
+        self.core.clear_history().await.map_err(napi_error)
     }
 
     #[napi]
     pub async fn get_history(&self) -> Result<Vec<ProviderMessage>> {
-        session_util::get_history(&self.inner).await
+        self.core.get_history().await.map_err(napi_error)
     }
 
     #[napi]
     pub async fn get_available_models(&self) -> Result<Vec<AvailableModel>> {
-        session_util::get_available_models(&self.inner).await
+        self.core.get_available_models().await.map_err(napi_error)
     }
 
     #[napi]
     pub async fn set_model(&self, provider: String, model: String) -> Result<()> {
-        session_util::set_model(&self.inner, provider, model).await
+        self.core.set_model(provider, model).await.map_err(napi_error)
+    }
+
+    /// Which tool, if any, the model must call; see `ToolChoiceSettings`
+    #[napi]
+    pub async fn set_tool_choice(&self, settings: ToolChoiceSettings) -> Result<()> {
+        self.core.set_tool_choice(settings).await.map_err(napi_error)
+    }
+
+    #[napi]
+    pub async fn get_tool_choice(&self) -> ToolChoiceSettings {
+        self.core.get_tool_choice().await
     }
 
     #[napi]
     pub async fn check_latency(&self) -> Result<LatencyInfo> {
-        let info = session_util::check_latency(&self.inner).await?;
-        Ok(LatencyInfo {
-            latency_ms: info.latency_ms,
-            model_name: info.model_name,
-        })
+        self.core.check_latency().await.map_err(napi_error)
+    }
+
+    #[napi]
+    pub async fn generate_title(&self) -> Result<String> {
+        self.core.generate_title().await.map_err(napi_error)
+    }
+
+    #[napi]
+    pub async fn summarize_history(&self) -> Result<String> {
+        self.core.summarize_history().await.map_err(napi_error)
     }
 
     #[napi]
-    pub fn get_sessions() -> Result<Vec<String>> {
-        session_util::get_sessions()
+    pub async fn compact_history(&self) -> Result<u32> {
+        self.core.compact_history().await.map_err(napi_error)
     }
 
     #[napi]
-    pub fn get_saved_sessions() -> Result<Vec<SavedSessionInfo>> {
-        session_util::get_saved_sessions()
+    pub async fn generate_commit_message(&self) -> Result<String> {
+        self.core.generate_commit_message().await.map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn get_sessions(namespace: Option<String>) -> Result<Vec<String>> {
+        api::get_sessions(&namespace_or_default(namespace)).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn get_saved_sessions(namespace: Option<String>) -> Result<Vec<SavedSessionInfo>> {
+        api::get_saved_sessions(&namespace_or_default(namespace)).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn list_saved_sessions_for_workspace(path: String, namespace: Option<String>) -> Result<Vec<SavedSessionInfo>> {
+        api::list_saved_sessions_for_workspace(&namespace_or_default(namespace), &path).map_err(napi_error)
+    }
+
+    /// A page of the saved sessions, sorted and filtered by `query`
+    #[napi]
+    pub fn query_saved_sessions(
+        query: Option<SavedSessionQuery>,
+        namespace: Option<String>,
+    ) -> Result<SavedSessionPage> {
+        api::query_saved_sessions(&namespace_or_default(namespace), &query.unwrap_or_default()).map_err(napi_error)
+    }
+
+    /// Delete a session, open or saved, with its event log, trash and overlay
+    #[napi]
+    pub fn delete_session(session_id: String, namespace: Option<String>) -> Result<bool> {
+        api::delete_session(&namespace_or_default(namespace), &session_id).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn delete_sessions(session_ids: Vec<String>, namespace: Option<String>) -> Result<u32> {
+        api::delete_sessions(&namespace_or_default(namespace), session_ids).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn duplicate_session(session_id: String, namespace: Option<String>) -> Result<SavedSessionInfo> {
+        api::duplicate_session(&namespace_or_default(namespace), &session_id).map_err(napi_error)
     }
 
     #[napi]
     pub fn set_theme(theme: String) -> Result<()> {
-        session_util::set_theme(theme)
+        api::set_theme(theme).map_err(napi_error)
     }
 
     #[napi]
     pub fn get_agent_mode(&self) -> Result<String> {
-        session_util::get_agent_mode(&self.session_id)
+        self.core.get_agent_mode().map_err(napi_error)
     }
 
     #[napi]
     pub async fn set_agent_mode(&self, mode: String) -> Result<()> {
-        session_util::set_agent_mode(&self.session_id, &self.inner, mode).await
+        self.core.set_agent_mode(mode).await.map_err(napi_error)
     }
 
     #[napi]
     pub fn get_approval_mode(&self) -> Result<String> {
-        session_util::get_approval_mode(&self.session_id)
+        self.core.get_approval_mode().map_err(napi_error)
     }
 
     #[napi]
     pub fn set_approval_mode(&self, mode: String) -> Result<()> {
-        session_util::set_approval_mode(&self.session_id, mode)
+        self.core.set_approval_mode(mode).map_err(napi_error)
+    }
+
+    #[napi]
+    pub fn get_execution_target(&self) -> Result<String> {
+        self.core.get_execution_target().map_err(napi_error)
     }
-}
 
-#[napi(object)]
-pub struct LatencyInfo {
-    pub latency_ms: u32,
-    pub model_name: String,
+    #[napi]
+    pub fn set_execution_target(&self, target: String) -> Result<()> {
+        self.core.set_execution_target(target).map_err(napi_error)
+    }
 }
@@ -0,0 +1,70 @@
+//! Last error reported by each subsystem, for `get_core_status`.
+//!
+//! Subsystems call `record_error` where they already log a failure that
+//! leaves them degraded; only the most recent error of each is kept.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex as StdMutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Subsystem {
+    /// Requests to the model provider
+    Llm,
+    Mcp,
+    Lsp,
+    /// The bash tool's persistent shell
+    Shell,
+    /// Session snapshots and message logs
+    Persistence,
+    Telemetry,
+    /// Actions run by `[notify]`
+    Notifier,
+}
+
+impl Subsystem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Subsystem::Llm => "llm",
+            Subsystem::Mcp => "mcp",
+            Subsystem::Lsp => "lsp",
+            Subsystem::Shell => "shell",
+            Subsystem::Persistence => "persistence",
+            Subsystem::Telemetry => "telemetry",
+            Subsystem::Notifier => "notifier",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedError {
+    pub message: String,
+    pub ts_ms: i64,
+}
+
+static LAST_ERRORS: StdMutex<BTreeMap<Subsystem, RecordedError>> = StdMutex::new(BTreeMap::new());
+
+pub fn record_error(subsystem: Subsystem, message: impl Into<String>) {
+    let ts_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    if let Ok(mut errors) = LAST_ERRORS.lock() {
+        errors.insert(
+            subsystem,
+            RecordedError {
+                message: message.into(),
+                ts_ms,
+            },
+        );
+    }
+}
+
+/// The last error of every subsystem that has reported one
+pub fn last_errors() -> Vec<(Subsystem, RecordedError)> {
+    LAST_ERRORS
+        .lock()
+        .map(|errors| errors.iter().map(|(s, e)| (*s, e.clone())).collect())
+        .unwrap_or_default()
+}
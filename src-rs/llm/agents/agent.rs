@@ -3,6 +3,7 @@ use crate::llm::models::provider_handle::{
     Message,
     ProviderClient,
     ProviderClientFactory,
+    ToolUseOptions,
 };
 use crate::llm::tools::tool_trait::{
     Tool,
@@ -12,7 +13,13 @@ use crate::llm::tools::tool_trait::{
     ToolResult,
     TOOL_RESULT_VERSION,
 };
-use crate::session::key_path_from_args;
+use crate::llm::agents::cancel::CancelToken;
+use crate::llm::agents::tool_definitions::ToolDefinitions;
+use crate::llm::models::provider_base::{ Citation, StopDetails };
+use crate::llm::models::model_limits::{self, ToolResultBudget};
+use crate::llm::models::prompt_limit;
+use crate::llm::utils::artifacts;
+use crate::session::tool_key_path;
 use anyhow::{ Context, Result };
 use serde::{ Deserialize, Serialize };
 use serde_json::{ json, Value };
@@ -22,12 +29,11 @@ use tokio_stream::StreamExt;
 
 fn tool_result_from_execution(
     tool_name: &str,
-    args: &str,
+    key_path: String,
     kind: ToolKind,
     operation: ToolOperation,
     execution_result: &Result<String>
 ) -> ToolResult {
-    let key_path = key_path_from_args(tool_name, args);
 
     match execution_result {
         Ok(result) => {
@@ -127,6 +133,60 @@ fn tool_result_from_execution(
     }
 }
 
+/// Keep a tool result over the model's budget out of the history
+///
+/// The full output is stored as an artifact; the history gets an excerpt and
+/// the artifact handle, which the model can pass to the read_artifact tool.
+/// A read_artifact result is only cut, since storing it again would send the
+/// model after another handle.
+fn compact_tool_result(
+    result: &ToolResult,
+    serialized: &str,
+    budget: ToolResultBudget,
+    artifact_scope: &str,
+) -> Option<String> {
+    if serialized.len() <= budget.max_chars {
+        return None;
+    }
+    let mut content = if result.stdout.is_empty() {
+        serde_json::to_string_pretty(&result.data).unwrap_or_default()
+    } else {
+        result.stdout.clone()
+    };
+    if !result.stderr.is_empty() {
+        content.push_str("\n[stderr]\n");
+        content.push_str(&result.stderr);
+    }
+    let total_chars = content.chars().count();
+    let excerpt = artifacts::excerpt(&content, budget.excerpt_chars).to_string();
+    let mut compacted = result.clone();
+    if let Some(source) = result.data.get("artifact").and_then(Value::as_str) {
+        compacted.stdout = format!(
+            "{}\n\n... [output truncated: {} of {} characters shown to fit this model's context; \
+             read fewer lines of {} with offset and limit]",
+            excerpt,
+            excerpt.chars().count(),
+            total_chars,
+            source
+        );
+        compacted.stderr = String::new();
+        compacted.data = json!({ "artifact": source, "total_chars": total_chars });
+        return serde_json::to_string_pretty(&compacted).ok();
+    }
+    let handle = artifacts::store(artifact_scope, &result.tool_name, content);
+
+    compacted.stdout = format!(
+        "{}\n\n... [output truncated: {} of {} characters shown. Full output stored as {}; use read_artifact with this handle to read more]",
+        excerpt,
+        excerpt.chars().count(),
+        total_chars,
+        handle
+    );
+    compacted.stderr = artifacts::excerpt(&result.stderr, budget.excerpt_chars / 4).to_string();
+    compacted.data = json!({ "artifact": handle, "total_chars": total_chars });
+    serde_json::to_string_pretty(&compacted).ok()
+}
+
 #[derive(Clone, Debug)]
 pub enum StreamStage {
     Thinking,
@@ -138,7 +198,117 @@ pub enum StreamEvent {
     Text(String),
     StageStart(StreamStage),
     StageEnd(StreamStage),
-    End,
+    /// The provider stream dropped and is being re-issued. With `continued`
+    /// the text streamed so far is kept and extended; otherwise the partial
+    /// response is discarded and streamed again from the start.
+    Resuming {
+        attempt: u32,
+        continued: bool,
+    },
+    /// The provider blocked the prompt or cut the response short
+    Blocked(StopDetails),
+    /// Sources the provider cited for the response just streamed
+    Citations(Vec<Citation>),
+    /// The response was cut short after `chars` characters of answer, which
+    /// are kept as the turn's response
+    PartialResponse {
+        chars: usize,
+        truncation: Truncation,
+    },
+    /// A round of tool calls finished; these messages (the response that made
+    /// the calls and their results) were added to the history
+    RoundEnd(Vec<Message>),
+    /// The turn finished with this final response
+    End(String),
+}
+
+/// Reconnects allowed per turn when a provider stream drops mid-response
+const MAX_STREAM_RESUMES: u32 = 3;
+
+/// Longest wait for the next chunk before a stream counts as stalled; a
+/// stalled stream is resumed like a dropped one
+const STREAM_STALL_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(90);
+
+/// Answer text a failed stream must have produced to be kept rather than discarded
+const SALVAGE_MIN_CHARS: usize = 200;
+
+/// Why a response was cut short and kept as it was
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Truncation {
+    /// The stream failed, after any resumes, with this error
+    StreamFailed(String),
+    /// The response passed the session's output limit and was stopped
+    OutputLimit { max_chars: usize },
+}
+
+impl Truncation {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Truncation::StreamFailed(_) => "stream_failed",
+            Truncation::OutputLimit { .. } => "output_limit",
+        }
+    }
+
+    pub fn reason(&self) -> String {
+        match self {
+            Truncation::StreamFailed(error) => error.clone(),
+            Truncation::OutputLimit { max_chars } => {
+                format!("the response passed the output limit of {} characters", max_chars)
+            }
+        }
+    }
+
+    /// Ends the kept answer in the history, so the model knows it was cut off
+    fn marker(&self) -> String {
+        match self {
+            Truncation::StreamFailed(_) => "[response truncated: the model stopped responding]".to_string(),
+            Truncation::OutputLimit { max_chars } => {
+                format!("[response truncated: it passed the output limit of {} characters]", max_chars)
+            }
+        }
+    }
+}
+
+/// No chunk arrived within `STREAM_STALL_TIMEOUT`
+#[derive(Debug)]
+struct StreamStalled;
+
+impl std::fmt::Display for StreamStalled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the LLM stream stalled: no data for {} s", STREAM_STALL_TIMEOUT.as_secs())
+    }
+}
+
+impl std::error::Error for StreamStalled {}
+
+/// Whether the answer streamed before a failure is worth keeping
+fn is_salvageable(content: &str) -> bool {
+    content.trim().chars().count() >= SALVAGE_MIN_CHARS
+}
+
+/// Whether a stream error is a dropped connection worth re-issuing the request for
+///
+/// Timeouts are not retried: the provider is slow rather than unreachable,
+/// and repeating the request would only multiply the wait.
+fn is_resumable_stream_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if cause.is::<StreamStalled>() {
+            return true;
+        }
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return !e.is_timeout() && (e.is_body() || e.is_request() || e.is_connect() || e.is_decode());
+        }
+        if let Some(e) = cause.downcast_ref::<std::io::Error>() {
+            return matches!(
+                e.kind(),
+                std::io::ErrorKind::ConnectionReset |
+                    std::io::ErrorKind::ConnectionAborted |
+                    std::io::ErrorKind::BrokenPipe |
+                    std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        false
+    })
 }
 
 pub type StreamCallback = Arc<dyn Fn(StreamEvent) + Send + Sync>;
@@ -148,7 +318,7 @@ pub type StreamCallback = Arc<dyn Fn(StreamEvent) + Send + Sync>;
 /// The callback receives the tool reference so it can execute it after confirmation
 pub type ToolExecutorCallback = Arc<
     dyn (Fn(
-        &Box<dyn Tool>,
+        &dyn Tool,
         &str,
         &str
     ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<String>> + Send>>) +
@@ -156,7 +326,26 @@ pub type ToolExecutorCallback = Arc<
         Sync
 >;
 
-use crate::config::ProviderConfig;
+/// What the agent does after a round of tool calls, as decided by a checkpoint callback
+#[derive(Clone, Debug)]
+pub enum CheckpointDecision {
+    Continue,
+    /// Add a user message before the next LLM call
+    Steer(String),
+    /// End the turn without another LLM call
+    Stop,
+}
+
+/// Called after every round of tool calls with all tool results of the turn so far
+pub type CheckpointCallback = Arc<
+    dyn (Fn(
+        &[ToolExecutionResult]
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = CheckpointDecision> + Send>>) +
+        Send +
+        Sync
+>;
+
+use crate::config::{ProviderConfig, ToolDefinitionsConfig};
 
 /// Main LLM Agent that orchestrates tool calls
 pub struct Agent {
@@ -169,12 +358,24 @@ pub struct Agent {
     provider_configs: Vec<ProviderConfig>,
     /// Registered tools
     tools: Vec<Box<dyn Tool>>,
+    /// How the tools are sent, from their use so far
+    tool_definitions: ToolDefinitions,
     /// Conversation history
     messages: Vec<Message>,
+    /// System context sent ahead of the history for the current turn only
+    context_header: Option<String>,
     /// Optional callback for streaming output
     stream_callback: Option<StreamCallback>,
     /// Optional callback for tool execution (for confirmation logic)
     tool_executor_callback: Option<ToolExecutorCallback>,
+    /// Optional callback between rounds of tool calls (autonomous runs)
+    checkpoint_callback: Option<CheckpointCallback>,
+    /// Cancellation flag for the running turn
+    cancel_token: CancelToken,
+    /// Characters of text and reasoning one response may stream before it is stopped
+    max_output_chars: Option<usize>,
+    /// Session the artifacts of oversized tool results are stored under
+    artifact_scope: String,
 }
 
 /// Agent execution result
@@ -184,6 +385,16 @@ pub struct AgentResult {
     pub content: String,
     /// Whether tools were called
     pub tools_used: bool,
+    /// Whether the turn was cancelled before completion
+    pub cancelled: bool,
+    /// Whether a checkpoint callback ended the turn early
+    pub stopped: bool,
+    /// Set when the provider blocked the prompt or cut the response short
+    pub stop_details: Option<StopDetails>,
+    /// Whether `content` is a partial answer: the stream failed or passed the output limit
+    pub truncated: bool,
+    /// Sources the provider cited, in order of first appearance
+    pub citations: Vec<Citation>,
     /// Tool execution results
     #[allow(dead_code)]
     pub tool_results: Vec<ToolExecutionResult>,
@@ -225,9 +436,15 @@ impl Agent {
             client_factory,
             provider_configs,
             tools,
+            tool_definitions: ToolDefinitions::default(),
             messages: Vec::new(),
+            context_header: None,
             stream_callback: None,
             tool_executor_callback: None,
+            checkpoint_callback: None,
+            cancel_token: CancelToken::new(),
+            max_output_chars: None,
+            artifact_scope: String::new(),
         })
     }
 
@@ -236,6 +453,11 @@ impl Agent {
         self.provider_configs = configs;
     }
 
+    /// Provider configurations the agent can switch between
+    pub fn provider_configs(&self) -> &[ProviderConfig] {
+        &self.provider_configs
+    }
+
     /// Get available models grouped by provider
     pub fn get_available_models(&self) -> Vec<(String, String)> {
         let mut models = Vec::new();
@@ -337,6 +559,11 @@ impl Agent {
         self.tool_executor_callback = Some(callback);
     }
 
+    /// Set or clear the callback run between rounds of tool calls
+    pub fn set_checkpoint_callback(&mut self, callback: Option<CheckpointCallback>) {
+        self.checkpoint_callback = callback;
+    }
+
     /// Clear the stream callback
     #[allow(dead_code)]
     pub fn clear_stream_callback(&mut self) {
@@ -359,6 +586,84 @@ impl Agent {
         });
     }
 
+    /// Set which tool, if any, the model must call, and whether it may call
+    /// several at once; only Claude models support it
+    pub fn set_tool_use(&mut self, tool_use: ToolUseOptions) -> Result<()> {
+        self.client_factory.set_tool_use(tool_use);
+        self.client = self.client_factory.get_or_create(
+            &self.provider_name,
+            &self.model_name,
+            &self.provider_configs,
+            self.system_prompt.clone()
+        )?;
+        Ok(())
+    }
+
+    pub fn tool_use(&self) -> &ToolUseOptions {
+        self.client_factory.tool_use()
+    }
+
+    /// Set how tool definitions are trimmed from the next turn on
+    pub fn set_tool_definitions_config(&mut self, config: ToolDefinitionsConfig) {
+        self.tool_definitions.set_config(config);
+    }
+
+    /// Store the artifacts of oversized tool results under this session
+    pub fn set_artifact_scope(&mut self, session_id: &str) {
+        self.artifact_scope = session_id.to_string();
+    }
+
+    /// Stop each response after this many characters of text and reasoning; None is no limit
+    pub fn set_max_output_chars(&mut self, max_chars: Option<usize>) {
+        self.max_output_chars = max_chars;
+    }
+
+    /// Set the header sent with the next requests; it is not added to the history
+    pub fn set_context_header(&mut self, header: Option<String>) {
+        self.context_header = header;
+    }
+
+    /// How much of a tool result the current model's context has room for
+    fn tool_result_budget(&self) -> ToolResultBudget {
+        let configured = self
+            .provider_configs
+            .iter()
+            .find(|c| c.name == self.provider_name)
+            .map(|c| c.context_windows.clone())
+            .unwrap_or_default();
+        model_limits::tool_result_budget(model_limits::context_tokens(&self.model_name, &configured))
+    }
+
+    /// The history as sent to the provider, after the context header if there is one
+    fn request_messages(&self) -> Vec<Message> {
+        let Some(header) = &self.context_header else {
+            return self.messages.clone();
+        };
+        // The header gets what the system prompt leaves of the provider's limit
+        let limit = self
+            .provider_configs
+            .iter()
+            .find(|c| c.name == self.provider_name)
+            .and_then(|c| c.max_system_prompt_chars);
+        let header = match limit {
+            Some(max) => {
+                let used = self.system_prompt.as_deref().map_or(0, |p| p.chars().count());
+                prompt_limit::fit(header, max.saturating_sub(used), "context header")
+            }
+            None => header.into(),
+        };
+        if header.is_empty() {
+            return self.messages.clone();
+        }
+        let mut messages = Vec::with_capacity(self.messages.len() + 1);
+        messages.push(Message {
+            role: "system".to_string(),
+            content: header.into_owned(),
+        });
+        messages.extend(self.messages.iter().cloned());
+        messages
+    }
+
     pub fn export_messages(&self) -> Vec<Message> {
         self.messages.clone()
     }
@@ -367,10 +672,50 @@ impl Agent {
         self.messages = messages;
     }
 
+    /// Drop the history and tools to free memory; `restore` puts them back
+    pub fn evict(&mut self) {
+        self.messages = Vec::new();
+        self.tools = Vec::new();
+    }
+
+    pub fn restore(&mut self, messages: Vec<Message>, tools: Vec<Box<dyn Tool>>) {
+        self.messages = messages;
+        self.tools = tools;
+    }
+
+    /// Rough size of the history in memory, in bytes
+    pub fn history_bytes(&self) -> usize {
+        self.messages.iter().map(|m| m.role.len() + m.content.len()).sum()
+    }
+
     pub fn message_count(&self) -> usize {
         self.messages.len()
     }
 
+    /// Get a handle that can cancel the running turn without locking the agent
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel_token.clone()
+    }
+
+    /// Record a tool result in the provider-specific message format
+    fn add_tool_result_message(&mut self, tool_call_id: Option<String>, tool_result_json: &str) {
+        match &*self.client {
+            AnyProviderClient::Claude(_) => {
+                let tool_use_id = tool_call_id.unwrap_or_default();
+                let result_value: Value = serde_json
+                    ::from_str(tool_result_json)
+                    .unwrap_or_else(|_| json!({ "raw": tool_result_json }));
+                let payload =
+                    json!({
+                    "tool_use_id": tool_use_id,
+                    "result": result_value
+                });
+                self.add_user_message(format!("ToolResultJSON:{}", payload));
+            }
+            _ => self.add_user_message(format!("ToolResult:\n{}", tool_result_json)),
+        }
+    }
+
     /// Execute the agent with streaming LLM calls
     ///
     /// This method:
@@ -382,20 +727,35 @@ impl Agent {
         let mut tool_results = Vec::new();
         let mut final_content = String::new();
         let mut tools_used = false;
+        let mut cancelled = false;
+        let mut stopped = false;
+        let mut stop_details: Option<StopDetails> = None;
+        let mut citations: Vec<Citation> = Vec::new();
+        // Why the response was cut short when its partial answer was kept
+        let mut truncated: Option<Truncation> = None;
+        let cancel_token = self.cancel_token.clone();
+        cancel_token.reset();
+        let mut resumes_used = 0;
 
         // Prepare tool definitions
-        let tools: Vec<Value> = self.tools
-            .iter()
-            .map(|tool| tool.to_tool_definition())
-            .collect();
+        let tools: Vec<Value> = self.tool_definitions.begin_turn(&self.tools, self.client.strict_tools());
 
         loop {
             log::info!("Calling LLM with {} messages", self.messages.len());
-
-            // Get streaming response from LLM
-            let mut stream = self.client
-                .stream_chat(self.messages.clone(), Some(tools.clone())).await
-                .context("Failed to initiate LLM stream")?;
+            let round_start = self.messages.len();
+
+            // Get streaming response from LLM; a provider that has not answered yet must not hold up a cancel
+            let mut stream = tokio::select! {
+                biased;
+                _ = cancel_token.cancelled() => {
+                    log::info!("Turn cancelled while waiting for the LLM");
+                    cancelled = true;
+                    break;
+                }
+                stream = self.client.stream_chat(self.request_messages(), Some(tools.clone())) => {
+                    stream.context("Failed to initiate LLM stream")?
+                }
+            };
 
             let mut current_content = String::new();
             let mut tool_calls_map: HashMap<
@@ -403,13 +763,100 @@ impl Agent {
                 (Option<String>, Option<String>, String)
             > = HashMap::new();
             let mut finish_reason: Option<String> = None;
+            let mut stream_stop: Option<StopDetails> = None;
+            let mut stream_citations: Vec<Citation> = Vec::new();
 
             let mut thinking_sent = false;
             let mut thinking_ended = false;
             let mut answering_sent = false;
+            // Text and reasoning streamed in this response, against `max_output_chars`
+            let mut streamed_chars = 0;
+
+            loop {
+                let chunk_result = tokio::select! {
+                    biased;
+                    _ = cancel_token.cancelled() => {
+                        log::info!("Turn cancelled while streaming");
+                        cancelled = true;
+                        break;
+                    }
+                    next = tokio::time::timeout(STREAM_STALL_TIMEOUT, stream.next()) => match next {
+                        Ok(Some(chunk_result)) => chunk_result,
+                        Ok(None) => break,
+                        Err(_) => Err(anyhow::Error::new(StreamStalled)),
+                    },
+                };
+                let chunk = match chunk_result {
+                    Ok(chunk) => chunk,
+                    Err(e) if resumes_used < MAX_STREAM_RESUMES && is_resumable_stream_error(&e) => {
+                        resumes_used += 1;
+                        // A half-streamed tool call cannot be used as a prefix
+                        let continued =
+                            self.client.supports_assistant_prefix() &&
+                            tool_calls_map.is_empty() &&
+                            !current_content.trim().is_empty();
+                        log::warn!(
+                            "LLM stream dropped ({:#}), resuming ({}/{}, {})",
+                            e,
+                            resumes_used,
+                            MAX_STREAM_RESUMES,
+                            if continued { "continue" } else { "restart" }
+                        );
 
-            while let Some(chunk_result) = stream.next().await {
-                let chunk = chunk_result.context("Error reading stream chunk")?;
+                        let mut request_messages = self.request_messages();
+                        if continued {
+                            // Providers reject a prefill ending in whitespace
+                            current_content.truncate(current_content.trim_end().len());
+                            request_messages.push(Message {
+                                role: "assistant".to_string(),
+                                content: current_content.clone(),
+                            });
+                        } else {
+                            current_content.clear();
+                            streamed_chars = 0;
+                            tool_calls_map.clear();
+                            finish_reason = None;
+                            stream_stop = None;
+                            stream_citations.clear();
+                        }
+                        if let Some(ref callback) = self.stream_callback {
+                            callback(StreamEvent::Resuming {
+                                attempt: resumes_used,
+                                continued,
+                            });
+                        }
+
+                        tokio::select! {
+                            biased;
+                            _ = cancel_token.cancelled() => {
+                                cancelled = true;
+                                break;
+                            }
+                            _ = tokio::time::sleep(
+                                std::time::Duration::from_millis(500 * u64::from(resumes_used))
+                            ) => {}
+                        }
+                        stream = match self.client.stream_chat(request_messages, Some(tools.clone())).await {
+                            Ok(stream) => stream,
+                            Err(e) if is_salvageable(&current_content) => {
+                                let e = e.context("Failed to resume LLM stream");
+                                truncated = Some(Truncation::StreamFailed(format!("{:#}", e)));
+                                break;
+                            }
+                            Err(e) => return Err(e.context("Failed to resume LLM stream")),
+                        };
+                        continue;
+                    }
+                    // Minutes of output are not thrown away for a stream that will not finish
+                    Err(e) if is_salvageable(&current_content) => {
+                        let e = e.context("Error reading stream chunk");
+                        truncated = Some(Truncation::StreamFailed(format!("{:#}", e)));
+                        break;
+                    }
+                    Err(e) => {
+                        return Err(e.context("Error reading stream chunk"));
+                    }
+                };
 
                 log::debug!("Received chunk: {}", chunk);
 
@@ -431,6 +878,7 @@ impl Agent {
                                         }
                                         thinking_sent = true;
                                     }
+                                    streamed_chars += reasoning.chars().count();
                                     if let Some(ref callback) = self.stream_callback {
                                         callback(StreamEvent::Text(reasoning.to_string()));
                                     }
@@ -454,6 +902,7 @@ impl Agent {
                                         answering_sent = true;
                                     }
                                     current_content.push_str(content);
+                                    streamed_chars += content.chars().count();
                                     if let Some(ref callback) = self.stream_callback {
                                         callback(StreamEvent::Text(content.to_string()));
                                     }
@@ -505,8 +954,32 @@ impl Agent {
                         if let Some(reason) = choice.get("finish_reason").and_then(|r| r.as_str()) {
                             finish_reason = Some(reason.to_string());
                         }
+                        if
+                            let Some(details) = choice
+                                .get("stop_details")
+                                .and_then(|d| serde_json::from_value::<StopDetails>(d.clone()).ok())
+                        {
+                            stream_stop = Some(details);
+                        }
+                        if
+                            let Some(items) = choice
+                                .get("citations")
+                                .and_then(|c| serde_json::from_value::<Vec<Citation>>(c.clone()).ok())
+                        {
+                            for citation in items {
+                                if !stream_citations.contains(&citation) {
+                                    stream_citations.push(citation);
+                                }
+                            }
+                        }
                     }
                 }
+
+                // A model repeating itself is stopped here rather than at the provider's limit
+                if let Some(max_chars) = self.max_output_chars.filter(|max| streamed_chars > *max) {
+                    truncated = Some(Truncation::OutputLimit { max_chars });
+                    break;
+                }
             }
 
             // Only log newline if using default stdout (not callback)
@@ -514,6 +987,36 @@ impl Agent {
                 println!();
             }
 
+            if !stream_citations.is_empty() {
+                if let Some(ref callback) = self.stream_callback {
+                    callback(StreamEvent::Citations(stream_citations.clone()));
+                }
+                for citation in stream_citations {
+                    if !citations.contains(&citation) {
+                        citations.push(citation);
+                    }
+                }
+            }
+            if let Some(details) = stream_stop {
+                log::warn!("{}", details.describe());
+                if let Some(ref callback) = self.stream_callback {
+                    callback(StreamEvent::Blocked(details.clone()));
+                }
+                stop_details = Some(details);
+            }
+
+            // Tool calls may be incomplete when the stream was cut short
+            if cancelled || truncated.is_some() {
+                tool_calls_map.clear();
+            }
+            if let Some(truncation) = &truncated {
+                log::warn!(
+                    "Keeping {} characters of a response that was cut short: {}",
+                    current_content.chars().count(),
+                    truncation.reason()
+                );
+            }
+
             // Prepare tool calls JSON
             let tool_calls_json_str = if !tool_calls_map.is_empty() {
                 let mut calls: Vec<_> = tool_calls_map.iter().collect();
@@ -534,8 +1037,14 @@ impl Agent {
             };
 
             // Save assistant response (Content + ToolCalls)
-            if !current_content.is_empty() || tool_calls_json_str.is_some() {
+            if !current_content.is_empty() || tool_calls_json_str.is_some() || truncated.is_some() {
                 let mut full_content = current_content.clone();
+                if let Some(truncation) = &truncated {
+                    if !full_content.is_empty() {
+                        full_content.push_str("\n\n");
+                    }
+                    full_content.push_str(&truncation.marker());
+                }
                 if let Some(json_str) = &tool_calls_json_str {
                     if !full_content.is_empty() {
                         full_content.push_str("\n\n");
@@ -571,11 +1080,34 @@ impl Agent {
                         arguments_acc.as_str()
                     };
 
-                    log::info!("Executing tool: {} with args: {}", tool_name, arguments);
-
                     let tool_ref = self.find_tool(tool_name);
                     let kind = tool_ref.map(|t| t.kind()).unwrap_or(ToolKind::Other);
                     let op = tool_ref.map(|t| t.operation()).unwrap_or(ToolOperation::Other);
+                    let key_path = tool_key_path(tool_ref, tool_name, arguments);
+
+                    // Every tool call still needs a result so the history stays valid
+                    if cancel_token.is_cancelled() {
+                        cancelled = true;
+                        let mut skipped = ToolResult::err(
+                            tool_name,
+                            kind,
+                            op,
+                            "Cancelled by the user before execution",
+                            json!({}),
+                        )
+                        .with_summary("cancelled");
+                        skipped.executed = false;
+                        skipped.key_path = key_path;
+                        let skipped_json = serde_json
+                            ::to_string_pretty(&skipped)
+                            .unwrap_or_else(|_|
+                                "{\"error\":\"failed to serialize ToolResult\"}".to_string()
+                            );
+                        self.add_tool_result_message(tool_call_id_opt, &skipped_json);
+                        continue;
+                    }
+
+                    log::info!("Executing tool: {} with args: {}", tool_name, arguments);
 
                     // Use tool_executor_callback if set, otherwise use default execute_tool
                     let execution_result = if let Some(ref callback) = self.tool_executor_callback {
@@ -591,9 +1123,10 @@ impl Agent {
                         self.execute_tool(tool_name, arguments).await
                     };
 
+                    crate::telemetry::record_tool_call(kind);
                     let tool_result = tool_result_from_execution(
                         tool_name,
-                        arguments,
+                        key_path,
                         kind,
                         op,
                         &execution_result
@@ -609,8 +1142,10 @@ impl Agent {
                         success: tool_result.success,
                         result: tool_result_json.clone(),
                     });
+                    self.tool_definitions.record_call(tool_name, tool_result.success);
 
                     if !tool_result.success {
+                        self.tool_definitions.record_failure(tool_name, &tool_result.stderr);
                         log::warn!(
                             "Tool '{}' execution failed. Error: {}. Arguments (first 500 chars): {:.500}",
                             tool_name,
@@ -619,22 +1154,35 @@ impl Agent {
                         );
                     }
 
-                    match &*self.client {
-                        AnyProviderClient::Claude(_) => {
-                            let tool_use_id = tool_call_id_opt.unwrap_or_default();
-                            let result_value: Value = serde_json
-                                ::from_str(&tool_result_json)
-                                .unwrap_or_else(|_| json!({ "raw": tool_result_json }));
-                            let payload =
-                                json!({
-                                "tool_use_id": tool_use_id,
-                                "result": result_value
-                            });
-                            self.add_user_message(
-                                format!("ToolResultJSON:{}", payload.to_string())
-                            );
+                    let budget = self.tool_result_budget();
+                    let scope = &self.artifact_scope;
+                    let history_json = compact_tool_result(&tool_result, &tool_result_json, budget, scope)
+                        .unwrap_or(tool_result_json);
+                    self.add_tool_result_message(tool_call_id_opt, &history_json);
+                }
+
+                if let Some(ref callback) = self.stream_callback {
+                    callback(StreamEvent::RoundEnd(self.messages[round_start..].to_vec()));
+                }
+
+                if cancel_token.is_cancelled() {
+                    cancelled = true;
+                    break;
+                }
+
+                if let Some(ref checkpoint) = self.checkpoint_callback {
+                    match checkpoint(&tool_results).await {
+                        CheckpointDecision::Continue => {}
+                        CheckpointDecision::Steer(message) => self.add_user_message(message),
+                        CheckpointDecision::Stop => {
+                            log::info!("Turn stopped at checkpoint");
+                            stopped = true;
+                            break;
                         }
-                        _ => self.add_user_message(format!("ToolResult:\n{}", tool_result_json)),
+                    }
+                    if cancel_token.is_cancelled() {
+                        cancelled = true;
+                        break;
                     }
                 }
 
@@ -649,6 +1197,12 @@ impl Agent {
                 if answering_sent {
                     callback(StreamEvent::StageEnd(StreamStage::Answering));
                 }
+                if let Some(truncation) = &truncated {
+                    callback(StreamEvent::PartialResponse {
+                        chars: final_content.chars().count(),
+                        truncation: truncation.clone(),
+                    });
+                }
             }
 
             // No more tool calls, we're done
@@ -663,12 +1217,17 @@ impl Agent {
         }
 
         if let Some(ref callback) = self.stream_callback {
-            callback(StreamEvent::End);
+            callback(StreamEvent::End(final_content.clone()));
         }
 
         Ok(AgentResult {
             content: final_content,
             tools_used,
+            cancelled,
+            stopped,
+            stop_details,
+            truncated: truncated.is_some(),
+            citations,
             tool_results,
         })
     }
@@ -684,12 +1243,7 @@ impl Agent {
             .position(|t| t.name() == tool_name)
             .ok_or_else(|| anyhow::anyhow!("Unknown tool: {}", tool_name))?;
 
-        // Clone the arguments for the blocking task
-        let arguments = arguments.to_string();
-
-        // Execute the tool
-        let tool = &self.tools[tool_index];
-        tool.execute(&arguments)
+        self.tools[tool_index].execute_async(arguments).await
     }
 
     /// Get conversation history
@@ -705,8 +1259,16 @@ impl Agent {
     ///
     /// # Returns
     /// * Option reference to the tool if found
-    pub fn find_tool(&self, tool_name: &str) -> Option<&Box<dyn Tool>> {
-        self.tools.iter().find(|t| t.name() == tool_name)
+    pub fn find_tool(&self, tool_name: &str) -> Option<&dyn Tool> {
+        self.tools
+            .iter()
+            .find(|t| t.name() == tool_name)
+            .map(|t| t.as_ref())
+    }
+
+    /// Names of the tools the model can call, in registration order
+    pub fn tool_names(&self) -> Vec<String> {
+        self.tools.iter().map(|t| t.name().to_string()).collect()
     }
 
     /// Clear conversation history
@@ -714,3 +1276,131 @@ impl Agent {
         self.messages.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeProvider, FakeResponse};
+    use std::sync::Mutex;
+
+    /// An agent on `fake` that records the text and resumes it streams
+    fn agent_on(provider: &str, fake: &FakeProvider) -> (Agent, Arc<Mutex<Vec<StreamEvent>>>) {
+        let config = ProviderConfig {
+            name: provider.to_string(),
+            base_url: fake.base_url().to_string(),
+            api_key: "k".to_string(),
+            models: vec!["m1".to_string()],
+            ..Default::default()
+        };
+        let mut agent = Agent::new(provider.to_string(), "m1".to_string(), None, vec![config], Vec::new()).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&events);
+        agent.set_stream_callback(move |event| {
+            if matches!(event, StreamEvent::Text(_) | StreamEvent::Resuming { .. }) {
+                recorded.lock().unwrap().push(event);
+            }
+        });
+        agent.add_user_message("Is the build fixed?".to_string());
+        (agent, events)
+    }
+
+    fn streamed_text(events: &[StreamEvent]) -> String {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn resumes(events: &[StreamEvent]) -> Vec<(u32, bool)> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                StreamEvent::Resuming { attempt, continued } => Some((*attempt, *continued)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn dropped_streams_continue_after_the_text_already_streamed() {
+        let text = |text: &str| json!({ "type": "content_block_delta", "index": 0, "delta": { "type": "text_delta", "text": text } });
+        let fake = FakeProvider::start(vec![
+            FakeResponse::Events { data: vec![text("The build"), text(" is")], dropped: true },
+            FakeResponse::Events { data: vec![text(" fixed."), json!({ "type": "message_stop" })], dropped: false },
+        ]);
+        let (mut agent, events) = agent_on("anthropic", &fake);
+
+        let result = agent.execute().await.unwrap();
+        assert_eq!(result.content, "The build is fixed.");
+        assert!(!result.truncated);
+        let events = events.lock().unwrap();
+        assert_eq!(resumes(&events), [(1, true)]);
+        // The continuation carries on from the streamed text rather than repeating it
+        assert_eq!(streamed_text(&events), "The build is fixed.");
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 2);
+        let prefill = requests[1]["messages"].as_array().unwrap().last().unwrap().clone();
+        assert_eq!(prefill, json!({ "role": "assistant", "content": "The build is" }));
+        assert_eq!(agent.get_messages().last().unwrap().content, "The build is fixed.");
+    }
+
+    #[tokio::test]
+    async fn dropped_streams_restart_when_the_provider_cannot_continue() {
+        let text = |text: &str| json!({ "choices": [{ "delta": { "content": text } }] });
+        let fake = FakeProvider::start(vec![
+            FakeResponse::Events { data: vec![text("The bui")], dropped: true },
+            FakeResponse::Events {
+                data: vec![text("The build"), json!({ "choices": [{ "delta": { "content": " is fixed." }, "finish_reason": "stop" }] })],
+                dropped: false,
+            },
+        ]);
+        let (mut agent, events) = agent_on("openai", &fake);
+
+        let result = agent.execute().await.unwrap();
+        // The partial response is replaced, not kept ahead of the new one
+        assert_eq!(result.content, "The build is fixed.");
+        let events = events.lock().unwrap();
+        assert_eq!(resumes(&events), [(1, false)]);
+        assert_eq!(streamed_text(&events), "The buiThe build is fixed.");
+        let requests = fake.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["messages"], requests[1]["messages"]);
+        assert_eq!(agent.get_messages().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn streams_that_keep_dropping_fail_once_the_resumes_run_out() {
+        let fake = FakeProvider::start(vec![FakeResponse::Events {
+            data: vec![json!({ "choices": [{ "delta": { "content": "The bui" } }] })],
+            dropped: true,
+        }]);
+        let (mut agent, events) = agent_on("openai", &fake);
+
+        let error = agent.execute().await.unwrap_err();
+        assert!(format!("{:#}", error).contains("Error reading stream chunk"), "{:#}", error);
+        assert_eq!(fake.requests().len(), 1 + MAX_STREAM_RESUMES as usize);
+        assert_eq!(resumes(&events.lock().unwrap()), [(1, false), (2, false), (3, false)]);
+        // Nothing of the failed response reaches the history
+        assert_eq!(agent.get_messages().len(), 1);
+    }
+
+    #[test]
+    fn stalled_streams_resume_and_long_answers_are_kept() {
+        let stalled = anyhow::Error::new(StreamStalled).context("Error reading stream chunk");
+        assert!(is_resumable_stream_error(&stalled));
+        assert!(!is_resumable_stream_error(&anyhow::anyhow!("LLM API error (400 Bad Request)")));
+        assert!(!is_salvageable(&format!("  {}  ", "x".repeat(SALVAGE_MIN_CHARS - 1))));
+        assert!(is_salvageable(&"x".repeat(SALVAGE_MIN_CHARS)));
+    }
+    #[test]
+    fn truncated_responses_say_why_in_the_history() {
+        let limit = Truncation::OutputLimit { max_chars: 1_000 };
+        assert_eq!(limit.code(), "output_limit");
+        assert!(limit.marker().contains("output limit of 1000 characters"));
+        let failed = Truncation::StreamFailed("the LLM stream stalled".to_string());
+        assert_eq!((failed.code(), failed.reason().as_str()), ("stream_failed", "the LLM stream stalled"));
+    }
+}
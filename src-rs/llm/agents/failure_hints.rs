@@ -0,0 +1,179 @@
+//! Notes on a session's repeated tool mistakes, added to the tools' descriptions.
+//!
+//! The error of a failed call tells the model what went wrong once; models
+//! still tend to make the same mistake again a few calls later, e.g. passing
+//! absolute paths outside the workspace or editing a file they have not
+//! viewed. Failures are classified by cause, and once a cause has come up
+//! `after` times for a tool, a one-line note about it is appended to that
+//! tool's description for the rest of the session. Only the most common
+//! causes of each tool get a note, so descriptions stay short. Failures whose
+//! cause already has a note are counted separately in telemetry, which shows
+//! whether the notes work.
+
+use std::collections::HashMap;
+
+/// Notes per tool, most common causes first
+const MAX_HINTS_PER_TOOL: usize = 2;
+
+/// Why a tool call failed, as far as its error says
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FailureCause {
+    OutsideWorkspace,
+    NotFound,
+    NotRead,
+    StaleRead,
+    NoMatch,
+    NotUnique,
+    /// The named argument was left out
+    MissingArgument(String),
+    WrongArgumentType,
+    InvalidJson,
+}
+
+impl FailureCause {
+    /// The cause of the error `stderr` of a failed call, if it is one a note can prevent
+    pub fn classify(stderr: &str) -> Option<Self> {
+        let lower = stderr.to_lowercase();
+        if let Some(field) = missing_field(stderr) {
+            return Some(FailureCause::MissingArgument(field));
+        }
+        let cause = if lower.contains("outside the workspace") {
+            FailureCause::OutsideWorkspace
+        } else if lower.contains("has not been read") {
+            FailureCause::NotRead
+        } else if lower.contains("changed on disk since it was last read") {
+            FailureCause::StaleRead
+        } else if lower.contains("old_string not found") {
+            FailureCause::NoMatch
+        } else if lower.contains("it must be unique") {
+            FailureCause::NotUnique
+        } else if lower.contains("failed to parse tool arguments") {
+            FailureCause::InvalidJson
+        } else if lower.contains("failed to deserialize tool arguments") || lower.contains("invalid type:") {
+            FailureCause::WrongArgumentType
+        } else if lower.contains("file not found") || lower.contains("no such file") {
+            FailureCause::NotFound
+        } else {
+            return None;
+        };
+        Some(cause)
+    }
+
+    /// Fixed label, for telemetry
+    pub fn label(&self) -> &'static str {
+        match self {
+            FailureCause::OutsideWorkspace => "outside_workspace",
+            FailureCause::NotFound => "not_found",
+            FailureCause::NotRead => "not_read",
+            FailureCause::StaleRead => "stale_read",
+            FailureCause::NoMatch => "no_match",
+            FailureCause::NotUnique => "not_unique",
+            FailureCause::MissingArgument(_) => "missing_argument",
+            FailureCause::WrongArgumentType => "wrong_argument_type",
+            FailureCause::InvalidJson => "invalid_json",
+        }
+    }
+
+    pub fn hint(&self) -> String {
+        match self {
+            FailureCause::OutsideWorkspace => {
+                "paths must be inside the workspace; pass them relative to the workspace root".to_string()
+            }
+            FailureCause::NotFound => "the path must exist; check it with ls or glob first".to_string(),
+            FailureCause::NotRead => "view a file before editing it".to_string(),
+            FailureCause::StaleRead => "the file changed since it was viewed; view it again before editing".to_string(),
+            FailureCause::NoMatch => {
+                "`old_string` must match the file exactly, including indentation and line breaks".to_string()
+            }
+            FailureCause::NotUnique => {
+                "`old_string` must occur once in the file; include surrounding lines to make it unique".to_string()
+            }
+            FailureCause::MissingArgument(field) => format!("`{}` is required", field),
+            FailureCause::WrongArgumentType => {
+                "each argument must have the type its parameter declares".to_string()
+            }
+            FailureCause::InvalidJson => "arguments must be a single JSON object".to_string(),
+        }
+    }
+}
+
+/// `path` of a serde "missing field `path`" error
+fn missing_field(stderr: &str) -> Option<String> {
+    let start = stderr.find("missing field `")? + "missing field `".len();
+    let len = stderr[start..].find('`')?;
+    Some(stderr[start..start + len].to_string())
+}
+
+/// Failure causes of a session's tool calls
+#[derive(Debug, Default)]
+pub struct FailureHints {
+    /// Failures by tool and cause
+    counts: HashMap<String, HashMap<FailureCause, u32>>,
+}
+
+impl FailureHints {
+    /// Count a failed call of `tool_name`, returning its cause
+    pub fn record(&mut self, tool_name: &str, stderr: &str) -> Option<FailureCause> {
+        let cause = FailureCause::classify(stderr)?;
+        *self
+            .counts
+            .entry(tool_name.to_string())
+            .or_default()
+            .entry(cause.clone())
+            .or_insert(0) += 1;
+        Some(cause)
+    }
+
+    /// Notes for `tool_name`: its causes seen at least `after` times (0 sends
+    /// none), most common first
+    pub fn hints(&self, tool_name: &str, after: u32) -> Vec<String> {
+        let Some(counts) = self.counts.get(tool_name).filter(|_| after > 0) else {
+            return Vec::new();
+        };
+        let mut common: Vec<(&FailureCause, u32)> =
+            counts.iter().filter(|(_, n)| **n >= after).map(|(c, n)| (c, *n)).collect();
+        // Ties by note, so the description does not change between turns
+        common.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.hint().cmp(&b.0.hint())));
+        common
+            .into_iter()
+            .take(MAX_HINTS_PER_TOOL)
+            .map(|(cause, _)| cause.hint())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_causes_become_notes() {
+        assert_eq!(
+            FailureCause::classify("Path '/etc/hosts' is outside the workspace '/work'"),
+            Some(FailureCause::OutsideWorkspace)
+        );
+        assert_eq!(
+            FailureCause::classify("Failed to deserialize tool arguments: missing field `file_path`"),
+            Some(FailureCause::MissingArgument("file_path".to_string()))
+        );
+        assert_eq!(FailureCause::classify("Command exited with status 1"), None);
+
+        let mut hints = FailureHints::default();
+        let outside = "Path '/tmp/a' is outside the workspace '/work'";
+        assert_eq!(hints.record("view", outside), Some(FailureCause::OutsideWorkspace));
+        assert!(hints.hints("view", 2).is_empty(), "one failure is left to the error itself");
+        hints.record("view", outside);
+        assert_eq!(hints.hints("view", 2), vec![FailureCause::OutsideWorkspace.hint()]);
+        assert!(hints.hints("view", 0).is_empty(), "0 turns the notes off");
+        assert!(hints.hints("edit", 2).is_empty(), "notes are per tool");
+
+        hints.record("view", outside);
+        for _ in 0..2 {
+            hints.record("view", "File not found: a.rs");
+            hints.record("view", "missing field `file_path`");
+        }
+        let notes = hints.hints("view", 2);
+        assert_eq!(notes.len(), MAX_HINTS_PER_TOOL);
+        assert_eq!(notes[0], FailureCause::OutsideWorkspace.hint());
+    }
+}
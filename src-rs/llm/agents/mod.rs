@@ -1,4 +1,7 @@
 // Agent logic and orchestration
 
 pub mod agent;
+pub mod cancel;
+pub mod failure_hints;
 pub mod mcp_tools;
+pub mod tool_definitions;
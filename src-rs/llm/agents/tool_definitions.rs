@@ -0,0 +1,285 @@
+//! Tool definitions sent with each request, trimmed as the session goes on.
+//!
+//! The full JSON schemas are sent on every call. Once the model has used a
+//! tool successfully it no longer needs the long explanations, so those
+//! tools are sent minified: descriptions cut to their first sentence and
+//! enum parameters without the prose repeating their values. With
+//! `[tool_definitions] omit_unused_after_turns`, tools not called in that
+//! many turns are left out altogether; the model can still call them.
+//! The set is fixed for a turn and changes between turns only. Providers
+//! that take strict schemas get those of the tools that support them.
+//! Tools the model keeps calling wrongly get notes on their description, see
+//! `failure_hints`.
+
+use crate::config::ToolDefinitionsConfig;
+use crate::llm::agents::failure_hints::FailureHints;
+use crate::llm::models::strict_schema;
+use crate::llm::tools::tool_trait::Tool;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// Tool usage of a session, deciding how each tool is sent
+#[derive(Debug, Default)]
+pub struct ToolDefinitions {
+    config: ToolDefinitionsConfig,
+    /// Turns started so far
+    turn: u32,
+    /// Turn of each tool's latest call
+    last_called: HashMap<String, u32>,
+    /// Tools that have succeeded at least once
+    succeeded: HashSet<String>,
+    failures: FailureHints,
+    /// Notes sent with each tool this turn
+    notes: HashMap<String, Vec<String>>,
+}
+
+impl ToolDefinitions {
+    pub fn set_config(&mut self, config: ToolDefinitionsConfig) {
+        self.config = config;
+    }
+
+    /// The definitions for a new turn, in strict form where possible if `strict`
+    pub fn begin_turn(&mut self, tools: &[Box<dyn Tool>], strict: bool) -> Vec<Value> {
+        self.turn += 1;
+        let after = self.config.hint_after_failures;
+        self.notes = tools
+            .iter()
+            .filter(|tool| !self.omitted(tool.name()))
+            .map(|tool| (tool.name().to_string(), self.failures.hints(tool.name(), after)))
+            .filter(|(_, notes)| !notes.is_empty())
+            .collect();
+        let mut full_size = 0;
+        let definitions: Vec<Value> = tools
+            .iter()
+            .filter(|tool| !self.omitted(tool.name()))
+            .map(|tool| {
+                let definition = tool.to_tool_definition();
+                full_size += definition.to_string().len();
+                let definition = if self.config.minify_used && self.succeeded.contains(tool.name()) {
+                    minify(&definition)
+                } else {
+                    definition
+                };
+                let definition = match self.notes.get(tool.name()) {
+                    Some(notes) => with_notes(definition, notes),
+                    None => definition,
+                };
+                if !strict || !tool.supports_strict_schema() {
+                    return definition;
+                }
+                strict_schema::tighten(&definition).unwrap_or_else(|e| {
+                    log::debug!("Sending {} without a strict schema: {}", tool.name(), e);
+                    definition
+                })
+            })
+            .collect();
+        let size: usize = definitions.iter().map(|d| d.to_string().len()).sum();
+        if definitions.len() < tools.len() || size < full_size {
+            log::info!(
+                "Sending {} of {} tools, definitions {} bytes instead of {}",
+                definitions.len(),
+                tools.len(),
+                size,
+                full_size
+            );
+        }
+        definitions
+    }
+
+    pub fn record_call(&mut self, tool_name: &str, success: bool) {
+        self.last_called.insert(tool_name.to_string(), self.turn);
+        if success {
+            self.succeeded.insert(tool_name.to_string());
+        }
+    }
+
+    /// Count the cause of a failed call; notes on it are sent from the next turn
+    pub fn record_failure(&mut self, tool_name: &str, stderr: &str) {
+        if let Some(cause) = self.failures.record(tool_name, stderr) {
+            let hinted = self.notes.get(tool_name).is_some_and(|notes| notes.contains(&cause.hint()));
+            crate::telemetry::record_tool_failure(cause.label(), hinted);
+        }
+    }
+
+    fn omitted(&self, tool_name: &str) -> bool {
+        let after = self.config.omit_unused_after_turns;
+        if after == 0 || self.config.keep.iter().any(|t| t == tool_name) {
+            return false;
+        }
+        let last = self.last_called.get(tool_name).copied().unwrap_or(0);
+        // `turn` is the one about to start
+        self.turn > after && self.turn - last > after
+    }
+}
+
+/// `definition` with `notes` appended to its description
+fn with_notes(mut definition: Value, notes: &[String]) -> Value {
+    if notes.is_empty() {
+        return definition;
+    }
+    if let Some(function) = definition.get_mut("function").and_then(Value::as_object_mut) {
+        let mut description = function
+            .get("description")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .trim_end()
+            .to_string();
+        for note in notes {
+            description.push_str(&format!("\nNote: {}.", note));
+        }
+        function.insert("description".to_string(), Value::String(description));
+    }
+    definition
+}
+
+/// An OpenAI-format tool definition with descriptions cut to their first
+/// sentence and enum parameters without descriptions of their values
+pub fn minify(definition: &Value) -> Value {
+    let mut definition = definition.clone();
+    if let Some(function) = definition.get_mut("function") {
+        shorten_description(function);
+        if let Some(parameters) = function.get_mut("parameters") {
+            minify_schema(parameters);
+        }
+    }
+    definition
+}
+
+fn minify_schema(schema: &mut Value) {
+    if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+        for property in properties.values_mut() {
+            if lists_enum_values(property) {
+                if let Some(property) = property.as_object_mut() {
+                    property.remove("description");
+                }
+            } else {
+                shorten_description(property);
+            }
+            minify_schema(property);
+        }
+    }
+    if let Some(items) = schema.get_mut("items") {
+        minify_schema(items);
+    }
+}
+
+/// Whether the property is an enum whose description names every value
+fn lists_enum_values(property: &Value) -> bool {
+    let (Some(values), Some(description)) = (
+        property.get("enum").and_then(Value::as_array),
+        property.get("description").and_then(Value::as_str),
+    ) else {
+        return false;
+    };
+    values.iter().all(|v| match v {
+        Value::String(s) => description.contains(s.as_str()),
+        other => description.contains(&other.to_string()),
+    })
+}
+
+fn shorten_description(value: &mut Value) {
+    if let Some(Value::String(description)) = value.get_mut("description") {
+        *description = first_sentence(description).to_string();
+    }
+}
+
+/// Text up to the first line break or sentence end
+fn first_sentence(text: &str) -> &str {
+    let text = text.trim();
+    let line = text.lines().next().unwrap_or_default();
+    let end = line
+        .match_indices(". ")
+        .map(|(i, _)| i + 1)
+        .next()
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::tools::{ToolAdapter, ViewTool};
+    use serde_json::json;
+
+    #[test]
+    fn minifies_descriptions_and_enum_prose() {
+        let definition = json!({
+            "type": "function",
+            "function": {
+                "name": "fetch",
+                "description": "Fetch a URL. Supports text, markdown and html.\nUse it for docs.",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "format": {
+                            "type": "string",
+                            "enum": ["text", "markdown", "html"],
+                            "description": "Output format: 'text' for plain text, 'markdown', or 'html'"
+                        },
+                        "headers": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "name": { "type": "string", "description": "Header name. Case-insensitive." }
+                                }
+                            }
+                        }
+                    },
+                    "required": ["format"]
+                }
+            }
+        });
+        let minified = minify(&definition);
+        assert_eq!(minified["function"]["description"], "Fetch a URL.");
+        let properties = &minified["function"]["parameters"]["properties"];
+        assert_eq!(properties["format"], json!({ "type": "string", "enum": ["text", "markdown", "html"] }));
+        assert_eq!(properties["headers"]["items"]["properties"]["name"]["description"], "Header name.");
+        assert_eq!(minified["function"]["parameters"]["required"], json!(["format"]));
+    }
+
+    #[test]
+    fn omits_tools_not_called_in_the_last_turns() {
+        let mut usage = ToolDefinitions::default();
+        usage.set_config(ToolDefinitionsConfig {
+            omit_unused_after_turns: 2,
+            keep: vec!["view".to_string()],
+            ..Default::default()
+        });
+        usage.begin_turn(&[], false);
+        usage.record_call("grep", true);
+        usage.begin_turn(&[], false);
+        assert!(!usage.omitted("bash"), "nothing is omitted in the first turns");
+        usage.begin_turn(&[], false);
+        assert!(usage.omitted("bash"));
+        assert!(!usage.omitted("view"));
+        assert!(!usage.omitted("grep"), "called two turns ago");
+        usage.begin_turn(&[], false);
+        assert!(usage.omitted("grep"));
+    }
+
+    #[test]
+    fn repeated_failures_add_notes_from_the_next_turn() {
+        let tools: Vec<Box<dyn Tool>> = vec![Box::new(ToolAdapter(ViewTool::new()))];
+        let mut usage = ToolDefinitions::default();
+        let description = |definitions: &[Value]| {
+            definitions[0]["function"]["description"].as_str().unwrap_or_default().to_string()
+        };
+        let plain = description(&usage.begin_turn(&tools, false));
+        let outside = "Path '/etc/hosts' is outside the workspace '/work'";
+        usage.record_failure("view", outside);
+        usage.record_failure("view", outside);
+        assert!(!usage.notes.contains_key("view"), "the set is fixed for the turn");
+
+        let noted = description(&usage.begin_turn(&tools, true));
+        assert!(noted.starts_with(plain.trim_end()));
+        assert!(noted.ends_with("Note: paths must be inside the workspace; pass them relative to the workspace root."));
+        assert_eq!(usage.notes["view"].len(), 1);
+
+        usage.set_config(ToolDefinitionsConfig {
+            hint_after_failures: 0,
+            ..Default::default()
+        });
+        assert_eq!(description(&usage.begin_turn(&tools, false)), plain);
+    }
+}
@@ -0,0 +1,134 @@
+//! One-shot completions for internal tasks: titles, history summaries and
+//! commit messages.
+//!
+//! A task runs on its `[models.routes]` entry if it has one, else on
+//! `auxiliary_model`, else on the session's own model, so housekeeping can
+//! go to a cheap, fast model whatever the user picked for the chat.
+
+use anyhow::{anyhow, Result};
+
+use crate::config::{AppConfig, ProviderConfig};
+use crate::llm::models::provider_handle::{create_client, Message, ProviderClient, RequestAuth};
+
+/// Longest input sent for a task, in characters; the start is kept
+const MAX_INPUT_CHARS: usize = 24_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuxTask {
+    Title,
+    Summary,
+    CommitMessage,
+}
+
+impl AuxTask {
+    /// Key of the task in `[models.routes]`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuxTask::Title => "title",
+            AuxTask::Summary => "summary",
+            AuxTask::CommitMessage => "commit_message",
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            AuxTask::Title => {
+                "Write a title of at most six words for the conversation below. \
+                 Reply with the title only, without quotes or trailing punctuation."
+            }
+            AuxTask::Summary => {
+                "Summarize the conversation below so it can replace the full history. \
+                 Keep the user's goals, decisions made, files touched, open problems and next steps. \
+                 Be concise; use short bullet points."
+            }
+            AuxTask::CommitMessage => {
+                "Write a git commit message for the diff below: a subject line of at most 72 \
+                 characters in the imperative mood, then a blank line and a short body if the \
+                 change needs explaining. Reply with the message only."
+            }
+        }
+    }
+}
+
+/// (provider, model) that runs `task`; `session_model` is the fallback
+pub fn model_for(config: &AppConfig, task: AuxTask, session_model: (String, String)) -> (String, String) {
+    config
+        .route_model(task.as_str())
+        .or_else(|| config.auxiliary_model.as_deref().and_then(|m| config.resolve_model(m)))
+        .unwrap_or(session_model)
+}
+
+/// Run `task` over `input` and return the model's reply, trimmed
+pub async fn complete(
+    providers: &[ProviderConfig],
+    (provider, model): (String, String),
+    task: AuxTask,
+    input: &str,
+) -> Result<String> {
+    let config = providers
+        .iter()
+        .find(|p| p.name == provider)
+        .ok_or_else(|| anyhow!("Provider not found: {}", provider))?;
+    let client = create_client(
+        &provider,
+        config.base_url.clone(),
+        config.api_key.clone(),
+        model,
+        Some(task.instructions().to_string()),
+        RequestAuth::from_config(config),
+    )
+    .with_tool_calling(config.tool_calling)
+    .with_strip_tokens(config.strip_tokens.clone())
+    .with_stop_sequences(config.stop_sequences.clone());
+
+    let input: String = input.chars().take(MAX_INPUT_CHARS).collect();
+    let response = client
+        .chat(
+            vec![Message {
+                role: "user".to_string(),
+                content: input,
+            }],
+            None,
+        )
+        .await?;
+    let text = response
+        .pointer("/choices/0/message/content")
+        .and_then(|c| c.as_str())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if text.is_empty() {
+        anyhow::bail!("The model returned an empty {}", task.as_str().replace('_', " "));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_take_precedence_over_auxiliary_model() {
+        let mut config: AppConfig = toml::from_str(include_str!("../../Config.toml")).unwrap();
+        config.providers = vec![ProviderConfig {
+            name: "openai".to_string(),
+            base_url: "http://localhost:1234".to_string(),
+            api_key: "k".to_string(),
+            models: vec!["gpt-4o".to_string(), "gpt-4o-mini".to_string(), "o3".to_string()],
+            ..Default::default()
+        }];
+        let session = ("openai".to_string(), "o3".to_string());
+        let pair = |m: &str| ("openai".to_string(), m.to_string());
+
+        assert_eq!(model_for(&config, AuxTask::Title, session.clone()), pair("o3"));
+
+        config.auxiliary_model = Some("gpt-4o-mini".to_string());
+        config.models.routes.insert("summary".to_string(), "openai:gpt-4o".to_string());
+        assert_eq!(model_for(&config, AuxTask::Title, session.clone()), pair("gpt-4o-mini"));
+        assert_eq!(model_for(&config, AuxTask::Summary, session.clone()), pair("gpt-4o"));
+
+        // An auxiliary model that does not resolve falls back to the session's
+        config.auxiliary_model = Some("missing".to_string());
+        assert_eq!(model_for(&config, AuxTask::CommitMessage, session), pair("o3"));
+    }
+}
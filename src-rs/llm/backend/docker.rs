@@ -0,0 +1,323 @@
+use super::{local_workspace, run_process, CommandOutput, ExecBackend};
+use crate::config::RemoteConfig;
+use crate::llm::utils::file_tracker::content_hash;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Time allowed for building a devcontainer image
+const BUILD_TIMEOUT: Duration = Duration::from_secs(1800);
+/// Time allowed for inspecting, starting and creating containers
+const CONTAINER_OP_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Runs the tools inside a container with `docker exec`: either a running
+/// container named in `[remote] container`, or one built from the
+/// workspace's devcontainer.json and started (with the workspace mounted at
+/// its `workspaceFolder`) on first use.
+pub struct DockerBackend {
+    container: String,
+    workspace: PathBuf,
+    root: String,
+    /// How to create the container; None for a container managed elsewhere
+    devcontainer: Option<DevcontainerSpec>,
+    started: Mutex<bool>,
+}
+
+/// The parts of devcontainer.json the backend uses
+#[derive(Debug, Clone, PartialEq)]
+struct DevcontainerSpec {
+    image: Option<String>,
+    dockerfile: Option<PathBuf>,
+    context: PathBuf,
+    workspace_folder: String,
+    container_env: BTreeMap<String, String>,
+    run_args: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DevcontainerJson {
+    image: Option<String>,
+    build: Option<DevcontainerBuild>,
+    /// Older spelling of `build.dockerfile`
+    docker_file: Option<String>,
+    workspace_folder: Option<String>,
+    #[serde(default)]
+    container_env: BTreeMap<String, String>,
+    #[serde(default)]
+    run_args: Vec<String>,
+    docker_compose_file: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct DevcontainerBuild {
+    dockerfile: Option<String>,
+    context: Option<String>,
+}
+
+impl DockerBackend {
+    /// Backend for the running container `[remote] container`
+    pub fn new(config: &RemoteConfig, workspace: &Path) -> Result<Self> {
+        let container = config.container.trim();
+        if container.is_empty() {
+            bail!("[remote] container is not set");
+        }
+        let root = config.remote_root.trim();
+        if !root.starts_with('/') {
+            bail!("[remote] remote_root must be an absolute path, got '{}'", root);
+        }
+        Ok(Self {
+            container: container.to_string(),
+            workspace: local_workspace(workspace),
+            root: root.to_string(),
+            devcontainer: None,
+            started: Mutex::new(true),
+        })
+    }
+
+    /// Backend for a container built from the workspace's devcontainer.json
+    pub fn devcontainer(config: &RemoteConfig, workspace: &Path) -> Result<Self> {
+        let workspace = local_workspace(workspace);
+        let config_path = [".devcontainer/devcontainer.json", ".devcontainer.json"]
+            .iter()
+            .map(|p| workspace.join(p))
+            .find(|p| p.is_file())
+            .context("No .devcontainer/devcontainer.json or .devcontainer.json in the workspace")?;
+        let content = fs::read_to_string(&config_path)
+            .with_context(|| format!("Failed to read {}", config_path.display()))?;
+        let spec = parse_devcontainer(&content, config_path.parent().unwrap_or(&workspace), &workspace)
+            .with_context(|| format!("Invalid {}", config_path.display()))?;
+        let root = match config.remote_root.trim() {
+            "" => spec.workspace_folder.clone(),
+            root => root.to_string(),
+        };
+        Ok(Self {
+            container: container_name(&workspace),
+            workspace,
+            root,
+            devcontainer: Some(spec),
+            started: Mutex::new(false),
+        })
+    }
+
+    /// Start the devcontainer, building its image first if needed
+    fn ensure_started(&self) -> Result<()> {
+        let mut started = self.started.lock().unwrap();
+        if *started {
+            return Ok(());
+        }
+        let Some(spec) = &self.devcontainer else {
+            return Ok(());
+        };
+        let state = docker(&["inspect", "-f", "{{.State.Running}}", &self.container], CONTAINER_OP_TIMEOUT)?;
+        match state.stdout_text().trim() {
+            "true" => {}
+            "false" => {
+                check(docker(&["start", &self.container], CONTAINER_OP_TIMEOUT)?, "docker start")?;
+            }
+            _ => self.create_container(spec)?,
+        }
+        *started = true;
+        Ok(())
+    }
+
+    fn create_container(&self, spec: &DevcontainerSpec) -> Result<()> {
+        let image = match (&spec.image, &spec.dockerfile) {
+            (Some(image), _) => image.clone(),
+            (None, Some(dockerfile)) => {
+                let tag = format!("{}-image", self.container);
+                let dockerfile = dockerfile.to_string_lossy();
+                let context = spec.context.to_string_lossy();
+                let output = docker(&["build", "-t", &tag, "-f", &dockerfile, &context], BUILD_TIMEOUT)?;
+                check(output, "docker build")?;
+                tag
+            }
+            (None, None) => bail!("devcontainer.json has neither image nor build.dockerfile"),
+        };
+        let mount = format!("{}:{}", self.workspace.display(), spec.workspace_folder);
+        let mut args = vec!["run", "-d", "--name", &self.container, "-v", &mount, "-w", &spec.workspace_folder];
+        let env: Vec<String> = spec.container_env.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        for var in &env {
+            args.extend(["-e", var.as_str()]);
+        }
+        args.extend(spec.run_args.iter().map(String::as_str));
+        args.extend([image.as_str(), "sleep", "infinity"]);
+        check(docker(&args, CONTAINER_OP_TIMEOUT)?, "docker run")
+    }
+}
+
+impl ExecBackend for DockerBackend {
+    fn kind(&self) -> &'static str {
+        if self.devcontainer.is_some() {
+            "devcontainer"
+        } else {
+            "docker"
+        }
+    }
+
+    fn name(&self) -> String {
+        format!("{} {}", self.kind(), self.container)
+    }
+
+    fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    fn root(&self) -> &str {
+        &self.root
+    }
+
+    fn exec(&self, script: &str, stdin: Option<&[u8]>, timeout: Duration) -> Result<CommandOutput> {
+        self.ensure_started()?;
+        let mut command = Command::new("docker");
+        command.arg("exec");
+        if stdin.is_some() {
+            command.arg("-i");
+        }
+        command.args([&self.container, "sh", "-c", script]);
+        let output = run_process(command, stdin, timeout)?;
+        // Failures of docker itself (no such container, stopped container, ...)
+        if output.stderr.starts_with("Error response from daemon") {
+            bail!("docker exec in {} failed: {}", self.container, output.stderr.trim());
+        }
+        Ok(output)
+    }
+}
+
+fn docker(args: &[&str], timeout: Duration) -> Result<CommandOutput> {
+    let mut command = Command::new("docker");
+    command.args(args);
+    run_process(command, None, timeout)
+}
+
+fn check(output: CommandOutput, what: &str) -> Result<()> {
+    if output.timed_out {
+        bail!("{} timed out", what);
+    }
+    if output.exit_code != 0 {
+        bail!("{} failed: {}", what, output.stderr.trim());
+    }
+    Ok(())
+}
+
+/// Container name stable for a workspace, e.g. "carrycode-app-1a2b3c4d"
+fn container_name(workspace: &Path) -> String {
+    let base: String = workspace
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+        .collect();
+    let hash = content_hash(workspace.to_string_lossy().as_bytes()) as u32;
+    format!("carrycode-{}-{:08x}", base.trim_matches('-'), hash)
+}
+
+/// Read a devcontainer.json; build paths are relative to the directory it is in
+fn parse_devcontainer(content: &str, config_dir: &Path, workspace: &Path) -> Result<DevcontainerSpec> {
+    let json: DevcontainerJson = serde_json::from_str(&strip_jsonc(content))?;
+    if json.docker_compose_file.is_some() {
+        bail!("dockerComposeFile devcontainers are not supported; use the docker backend with its container");
+    }
+    let build = json.build.unwrap_or(DevcontainerBuild { dockerfile: None, context: None });
+    let basename = workspace.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    Ok(DevcontainerSpec {
+        image: json.image,
+        dockerfile: build.dockerfile.or(json.docker_file).map(|f| config_dir.join(f)),
+        context: config_dir.join(build.context.as_deref().unwrap_or(".")),
+        workspace_folder: json.workspace_folder.unwrap_or_else(|| format!("/workspaces/{}", basename)),
+        container_env: json.container_env,
+        run_args: json.run_args,
+    })
+}
+
+/// JSON with comments and trailing commas, as devcontainer.json allows, made plain JSON
+fn strip_jsonc(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            (']' | '}', _) => {
+                let trimmed = out.trim_end().len();
+                if out[..trimmed].ends_with(',') {
+                    out.truncate(trimmed - 1);
+                }
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_devcontainer_json_with_comments() {
+        let content = r#"{
+            // Built from the repo's Dockerfile
+            "name": "app // not a comment",
+            "build": { "dockerfile": "Dockerfile", "context": ".." },
+            /* "image": "ignored", */
+            "containerEnv": { "RUST_LOG": "debug", },
+            "runArgs": ["--network=host"],
+        }"#;
+        let spec = parse_devcontainer(content, Path::new("/ws/app/.devcontainer"), Path::new("/ws/app")).unwrap();
+        assert_eq!(
+            spec,
+            DevcontainerSpec {
+                image: None,
+                dockerfile: Some(PathBuf::from("/ws/app/.devcontainer/Dockerfile")),
+                context: PathBuf::from("/ws/app/.devcontainer/.."),
+                workspace_folder: "/workspaces/app".to_string(),
+                container_env: BTreeMap::from([("RUST_LOG".to_string(), "debug".to_string())]),
+                run_args: vec!["--network=host".to_string()],
+            }
+        );
+        let compose = r#"{ "dockerComposeFile": "compose.yml", "service": "app" }"#;
+        assert!(parse_devcontainer(compose, Path::new("/ws"), Path::new("/ws")).is_err());
+
+        let name = container_name(Path::new("/ws/My App"));
+        assert!(name.starts_with("carrycode-my-app-"), "{}", name);
+        assert_eq!(name, container_name(Path::new("/ws/My App")));
+    }
+}
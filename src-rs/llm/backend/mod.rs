@@ -0,0 +1,387 @@
+//! Execution backends: where bash, ls, view and edit do their work.
+//!
+//! Without a backend the tools work on the local workspace. With `[remote]`
+//! configured, or a target picked with `set_execution_target`, the session
+//! registers a backend (an ssh host, a container or a devcontainer) and the
+//! tools run their commands and file operations on that target instead, while the model,
+//! the session and its history stay local. Paths are resolved against the
+//! local workspace as usual and then mapped onto the target's root.
+
+pub mod docker;
+pub mod ssh;
+pub mod sync;
+
+use crate::config::{RemoteBackendKind, RemoteConfig};
+use crate::llm::tools::bash::shell_quote;
+use crate::llm::utils::file_tracker::content_hash;
+use crate::llm::utils::tool_access::current_tool_session;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub use docker::DockerBackend;
+pub use ssh::SshBackend;
+
+/// Exit code the file scripts use for "no such file"
+const MISSING_EXIT_CODE: i32 = 44;
+/// Time allowed for the file operations of view, edit and ls
+pub const FILE_OP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Content hash and time of the last view of each target file, by backend and path
+type RemoteReads = HashMap<String, (u64, i64)>;
+
+static BACKENDS: LazyLock<Mutex<HashMap<String, Arc<dyn ExecBackend>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+/// Views of target files by session
+static REMOTE_READS: LazyLock<Mutex<HashMap<String, RemoteReads>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Output of a command run on a backend
+#[derive(Debug, Clone, Default)]
+pub struct CommandOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+    pub exit_code: i32,
+    pub timed_out: bool,
+}
+
+impl CommandOutput {
+    pub fn stdout_text(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+}
+
+/// A target the tools execute against
+pub trait ExecBackend: Send + Sync {
+    /// "ssh" | "docker" | "devcontainer"
+    fn kind(&self) -> &'static str;
+
+    /// Short description for messages, e.g. "ssh dev@build-box"
+    fn name(&self) -> String;
+
+    /// Local workspace root the target root stands for
+    fn workspace(&self) -> &Path;
+
+    /// Directory on the target that mirrors the workspace
+    fn root(&self) -> &str;
+
+    /// Run `script` with `sh -c` on the target
+    fn exec(&self, script: &str, stdin: Option<&[u8]>, timeout: Duration) -> Result<CommandOutput>;
+
+    /// Target path of a resolved local path. A path outside the workspace has
+    /// no counterpart on the target, so it is refused rather than used as is.
+    fn target_path(&self, local: &Path) -> Result<String> {
+        match local.strip_prefix(self.workspace()) {
+            Ok(rel) if rel.as_os_str().is_empty() => Ok(self.root().to_string()),
+            Ok(rel) => Ok(format!("{}/{}", self.root().trim_end_matches('/'), rel.to_string_lossy())),
+            Err(_) => bail!("{} is outside the workspace mapped onto {}", local.display(), self.name()),
+        }
+    }
+
+    /// Run a user command from the target root, or from `workdir`
+    fn run(&self, command: &str, workdir: Option<&str>, timeout: Duration) -> Result<CommandOutput> {
+        let dir = workdir.unwrap_or(self.root());
+        self.exec(&format!("cd {} && {}", shell_quote(dir), command), None, timeout)
+    }
+
+    /// Contents of a file on the target; None if there is no such file
+    fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let quoted = shell_quote(path);
+        let script = format!("[ -f {q} ] || exit {code}; cat {q}", q = quoted, code = MISSING_EXIT_CODE);
+        let output = self.exec(&script, None, FILE_OP_TIMEOUT)?;
+        match output.exit_code {
+            0 => Ok(Some(output.stdout)),
+            MISSING_EXIT_CODE => Ok(None),
+            code => bail!("Failed to read {} on {} (exit code {}): {}", path, self.name(), code, output.stderr.trim()),
+        }
+    }
+
+    /// Write a file on the target, creating its parent directories
+    fn write_file(&self, path: &str, content: &[u8]) -> Result<()> {
+        let quoted = shell_quote(path);
+        let script = format!("mkdir -p \"$(dirname {q})\" && cat > {q}", q = quoted);
+        let output = self.exec(&script, Some(content), FILE_OP_TIMEOUT)?;
+        if output.exit_code != 0 {
+            bail!("Failed to write {} on {}: {}", path, self.name(), output.stderr.trim());
+        }
+        Ok(())
+    }
+
+    /// Remove a file on the target; a missing file is not an error
+    fn remove_file(&self, path: &str) -> Result<()> {
+        let output = self.exec(&format!("rm -f {}", shell_quote(path)), None, FILE_OP_TIMEOUT)?;
+        if output.exit_code != 0 {
+            bail!("Failed to remove {} on {}: {}", path, self.name(), output.stderr.trim());
+        }
+        Ok(())
+    }
+
+    /// Entries under a target directory up to `max_depth`, as (relative path, is_dir);
+    /// None if the directory does not exist
+    fn list_tree(&self, dir: &str, max_depth: usize) -> Result<Option<Vec<(String, bool)>>> {
+        let script = format!(
+            "[ -d {q} ] || exit {code}; cd {q} && {{ find . -mindepth 1 -maxdepth {depth} -type d | sed 's|^\\./|d |'; \
+             find . -mindepth 1 -maxdepth {depth} ! -type d | sed 's|^\\./|f |'; }}",
+            q = shell_quote(dir),
+            code = MISSING_EXIT_CODE,
+            depth = max_depth,
+        );
+        let output = self.exec(&script, None, FILE_OP_TIMEOUT)?;
+        match output.exit_code {
+            0 => Ok(Some(parse_tree_listing(&output.stdout_text()))),
+            MISSING_EXIT_CODE => Ok(None),
+            code => bail!("Failed to list {} on {} (exit code {}): {}", dir, self.name(), code, output.stderr.trim()),
+        }
+    }
+}
+
+/// Target a session starts on: the `[remote]` backend when enabled, else the local workspace
+pub fn default_target(config: &RemoteConfig) -> Option<RemoteBackendKind> {
+    config.enabled.then_some(config.backend)
+}
+
+/// Parse an execution target name; "local" is None
+pub fn parse_target(target: &str) -> Result<Option<RemoteBackendKind>> {
+    match target.trim() {
+        "local" => Ok(None),
+        "ssh" => Ok(Some(RemoteBackendKind::Ssh)),
+        "docker" => Ok(Some(RemoteBackendKind::Docker)),
+        "devcontainer" => Ok(Some(RemoteBackendKind::Devcontainer)),
+        other => bail!("Unknown execution target '{}'; expected local, ssh, docker or devcontainer", other),
+    }
+}
+
+/// Backend for `target` with the `[remote]` settings; None for the local workspace
+pub fn create(
+    target: Option<RemoteBackendKind>,
+    config: &RemoteConfig,
+    workspace: &Path,
+) -> Result<Option<Arc<dyn ExecBackend>>> {
+    let backend: Arc<dyn ExecBackend> = match target {
+        None => return Ok(None),
+        Some(RemoteBackendKind::Ssh) => Arc::new(SshBackend::new(config, workspace)?),
+        Some(RemoteBackendKind::Docker) => Arc::new(DockerBackend::new(config, workspace)?),
+        Some(RemoteBackendKind::Devcontainer) => Arc::new(DockerBackend::devcontainer(config, workspace)?),
+    };
+    Ok(Some(backend))
+}
+
+/// Make `backend` the target of `session_id`'s tool calls
+pub fn register(session_id: &str, backend: Arc<dyn ExecBackend>) {
+    backends().insert(session_id.to_string(), backend);
+}
+
+/// Run `session_id`'s tool calls locally again, forgetting its views of target files
+pub fn unregister(session_id: &str) {
+    backends().remove(session_id);
+    REMOTE_READS.lock().unwrap_or_else(|e| e.into_inner()).remove(session_id);
+}
+
+/// Backend registered for a session; None means the local workspace
+pub fn for_session(session_id: &str) -> Option<Arc<dyn ExecBackend>> {
+    backends().get(session_id).cloned()
+}
+
+/// The registry stays usable after a thread panicked holding it: each
+/// insert or removal is a single map operation, never left half done
+fn backends() -> MutexGuard<'static, HashMap<String, Arc<dyn ExecBackend>>> {
+    BACKENDS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Backend of the executing tool call's session
+pub fn current() -> Option<Arc<dyn ExecBackend>> {
+    for_session(&current_tool_session()?)
+}
+
+/// How a target file compares with the content last viewed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteRead {
+    NotRead,
+    Stale { read_at_ms: i64 },
+    Current,
+}
+
+/// Record a view of `path` by the executing tool call's session
+pub fn record_read(backend: &dyn ExecBackend, path: &str, content: &[u8]) {
+    let key = format!("{}:{}", backend.name(), path);
+    let read_at_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    REMOTE_READS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(current_tool_session().unwrap_or_default())
+        .or_default()
+        .insert(key, (content_hash(content), read_at_ms));
+}
+
+/// Compare `content` with the executing tool call's session's last view of `path`
+pub fn check_read(backend: &dyn ExecBackend, path: &str, content: &[u8]) -> RemoteRead {
+    let key = format!("{}:{}", backend.name(), path);
+    let reads = REMOTE_READS.lock().unwrap_or_else(|e| e.into_inner());
+    let session = current_tool_session().unwrap_or_default();
+    match reads.get(&session).and_then(|reads| reads.get(&key)) {
+        None => RemoteRead::NotRead,
+        Some((hash, read_at_ms)) if *hash != content_hash(content) => RemoteRead::Stale { read_at_ms: *read_at_ms },
+        Some(_) => RemoteRead::Current,
+    }
+}
+
+/// Lines of "d <path>" / "f <path>" into (path, is_dir) pairs
+fn parse_tree_listing(listing: &str) -> Vec<(String, bool)> {
+    listing
+        .lines()
+        .filter_map(|line| match line.split_once(' ') {
+            Some(("d", path)) => Some((path.to_string(), true)),
+            Some(("f", path)) => Some((path.to_string(), false)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Run a local client process (e.g. ssh) feeding it `stdin`, killing it after `timeout`
+pub(crate) fn run_process(mut command: Command, stdin: Option<&[u8]>, timeout: Duration) -> Result<CommandOutput> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    // Own process group, so a timeout also stops whatever the command started
+    #[cfg(unix)]
+    command.process_group(0);
+    let mut child = command
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start {}", program))?;
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        let input = input.to_vec();
+        thread::spawn(move || {
+            let _ = pipe.write_all(&input);
+        });
+    }
+    let mut stdout_pipe = child.stdout.take().context("stdout not captured")?;
+    let mut stderr_pipe = child.stderr.take().context("stderr not captured")?;
+    let stdout_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stdout_pipe.read_to_end(&mut buf);
+        buf
+    });
+    let stderr_reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = stderr_pipe.read_to_end(&mut buf);
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if Instant::now() >= deadline {
+            timed_out = true;
+            let _ = Command::new("kill").arg("-KILL").arg("--").arg(format!("-{}", child.id())).output();
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+
+    Ok(CommandOutput {
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: String::from_utf8_lossy(&stderr_reader.join().unwrap_or_default()).into_owned(),
+        exit_code: status.and_then(|s| s.code()).unwrap_or(-1),
+        timed_out,
+    })
+}
+
+/// Canonical local workspace root the backend maps from
+pub(crate) fn local_workspace(workspace: &Path) -> PathBuf {
+    std::fs::canonicalize(workspace).unwrap_or_else(|_| workspace.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::utils::tool_access::with_tool_session;
+    use crate::testing::FakeWorkspace;
+
+    /// Runs scripts with the local `sh`, standing in for a remote host
+    pub(super) struct LocalShell {
+        pub(super) workspace: PathBuf,
+        pub(super) root: String,
+    }
+
+    impl ExecBackend for LocalShell {
+        fn kind(&self) -> &'static str {
+            "ssh"
+        }
+
+        fn name(&self) -> String {
+            "local-test".to_string()
+        }
+
+        fn workspace(&self) -> &Path {
+            &self.workspace
+        }
+
+        fn root(&self) -> &str {
+            &self.root
+        }
+
+        fn exec(&self, script: &str, stdin: Option<&[u8]>, timeout: Duration) -> Result<CommandOutput> {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(script);
+            run_process(command, stdin, timeout)
+        }
+    }
+
+    #[test]
+    fn maps_paths_and_runs_file_operations_on_the_target() {
+        let root = FakeWorkspace::new().build().unwrap();
+        let backend = LocalShell {
+            workspace: PathBuf::from("/home/me/app"),
+            root: root.path().to_string_lossy().into_owned(),
+        };
+
+        assert_eq!(backend.target_path(Path::new("/home/me/app")).unwrap(), backend.root);
+        let file = backend.target_path(Path::new("/home/me/app/src/it's.rs")).unwrap();
+        assert_eq!(file, format!("{}/src/it's.rs", backend.root));
+        assert!(backend.target_path(Path::new("/etc/hosts")).is_err());
+        assert!(backend.target_path(Path::new("/home/me/application")).is_err());
+
+        assert_eq!(backend.read_file(&file).unwrap(), None);
+        backend.write_file(&file, b"fn main() {}\n").unwrap();
+        assert_eq!(backend.read_file(&file).unwrap().as_deref(), Some(&b"fn main() {}\n"[..]));
+
+        let mut tree = backend.list_tree(&backend.root, 3).unwrap().unwrap();
+        tree.sort();
+        assert_eq!(tree, vec![("src".to_string(), true), ("src/it's.rs".to_string(), false)]);
+        assert!(backend.list_tree(&format!("{}/nope", backend.root), 3).unwrap().is_none());
+
+        let output = backend.run("pwd; exit 3", Some(&format!("{}/src", backend.root)), FILE_OP_TIMEOUT).unwrap();
+        assert_eq!(output.exit_code, 3);
+        assert!(output.stdout_text().trim_end().ends_with("/src"));
+        let slow = backend.run("sleep 5", None, Duration::from_millis(100)).unwrap();
+        assert!(slow.timed_out);
+
+        assert_eq!(check_read(&backend, &file, b"fn main() {}\n"), RemoteRead::NotRead);
+        record_read(&backend, &file, b"fn main() {}\n");
+        assert_eq!(check_read(&backend, &file, b"fn main() {}\n"), RemoteRead::Current);
+        assert!(matches!(check_read(&backend, &file, b"changed"), RemoteRead::Stale { .. }));
+
+        // A view satisfies only the session that made it, until the session closes
+        with_tool_session("remote-a", || record_read(&backend, &file, b"fn main() {}\n"));
+        let check = |session: &str| with_tool_session(session, || check_read(&backend, &file, b"fn main() {}\n"));
+        assert_eq!(check("remote-a"), RemoteRead::Current);
+        assert_eq!(check("remote-b"), RemoteRead::NotRead);
+        unregister("remote-a");
+        assert_eq!(check("remote-a"), RemoteRead::NotRead);
+    }
+}
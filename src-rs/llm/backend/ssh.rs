@@ -0,0 +1,145 @@
+use super::{local_workspace, run_process, CommandOutput, ExecBackend};
+use crate::config::RemoteConfig;
+use crate::llm::tools::bash::shell_quote;
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Runs the tools on a host reached with the local `ssh` client. Auth is
+/// left to ssh (agent, `identity_file`, ~/.ssh/config); `BatchMode` makes a
+/// missing key fail instead of prompting for a password.
+pub struct SshBackend {
+    host: String,
+    user: Option<String>,
+    port: u16,
+    identity_file: Option<String>,
+    options: Vec<String>,
+    workspace: PathBuf,
+    root: String,
+}
+
+impl SshBackend {
+    pub fn new(config: &RemoteConfig, workspace: &Path) -> Result<Self> {
+        let host = config.host.trim();
+        if host.is_empty() {
+            bail!("[remote] host is not set");
+        }
+        // ssh would take a leading `-` as an option, e.g. `-oProxyCommand=...`
+        if host.starts_with('-') {
+            bail!("[remote] host must not start with '-', got '{}'", host);
+        }
+        if config.user.trim().starts_with('-') {
+            bail!("[remote] user must not start with '-', got '{}'", config.user.trim());
+        }
+        let root = config.remote_root.trim();
+        if !root.starts_with('/') {
+            bail!("[remote] remote_root must be an absolute path, got '{}'", root);
+        }
+        let non_empty = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        Ok(Self {
+            host: host.to_string(),
+            user: non_empty(&config.user),
+            port: config.port,
+            identity_file: non_empty(&config.identity_file).map(|f| expand_home(&f)),
+            options: config.ssh_options.clone(),
+            workspace: local_workspace(workspace),
+            root: root.to_string(),
+        })
+    }
+
+    fn destination(&self) -> String {
+        match &self.user {
+            Some(user) => format!("{}@{}", user, self.host),
+            None => self.host.clone(),
+        }
+    }
+
+    /// Arguments of the ssh invocation running `script`
+    fn ssh_args(&self, script: &str) -> Vec<String> {
+        let mut args = vec!["-p".to_string(), self.port.to_string()];
+        if let Some(identity_file) = &self.identity_file {
+            args.extend(["-i".to_string(), identity_file.clone()]);
+        }
+        args.extend(["-o".to_string(), "BatchMode=yes".to_string()]);
+        for option in &self.options {
+            args.extend(["-o".to_string(), option.clone()]);
+        }
+        // The remote side hands the command line to the login shell; pin it to sh
+        args.extend([self.destination(), "--".to_string(), format!("sh -c {}", shell_quote(script))]);
+        args
+    }
+}
+
+impl ExecBackend for SshBackend {
+    fn kind(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn name(&self) -> String {
+        format!("ssh {}", self.destination())
+    }
+
+    fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    fn root(&self) -> &str {
+        &self.root
+    }
+
+    fn exec(&self, script: &str, stdin: Option<&[u8]>, timeout: Duration) -> Result<CommandOutput> {
+        let mut command = Command::new("ssh");
+        command.args(self.ssh_args(script));
+        let output = run_process(command, stdin, timeout)?;
+        // ssh reserves 255 for its own failures (unreachable host, auth)
+        if output.exit_code == 255 {
+            bail!("ssh to {} failed: {}", self.destination(), output.stderr.trim());
+        }
+        Ok(output)
+    }
+}
+
+fn expand_home(path: &str) -> String {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_ssh_invocation_from_config() {
+        let config = RemoteConfig {
+            enabled: true,
+            host: "build-box".to_string(),
+            user: "dev".to_string(),
+            port: 2222,
+            remote_root: "/srv/app/".to_string(),
+            ssh_options: vec!["ConnectTimeout=10".to_string()],
+            ..RemoteConfig::default()
+        };
+        let ssh = SshBackend::new(&config, Path::new("/nonexistent/app")).unwrap();
+        assert_eq!(ssh.name(), "ssh dev@build-box");
+        assert_eq!(ssh.target_path(Path::new("/nonexistent/app/src/lib.rs")).unwrap(), "/srv/app/src/lib.rs");
+        assert_eq!(
+            ssh.ssh_args("echo 'hi'"),
+            [
+                "-p", "2222", "-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "dev@build-box", "--",
+                r"sh -c 'echo '\''hi'\'''",
+            ]
+        );
+
+        let relative = RemoteConfig { remote_root: "app".to_string(), ..config.clone() };
+        assert!(SshBackend::new(&relative, Path::new(".")).is_err());
+        let no_host = RemoteConfig { host: " ".to_string(), ..config.clone() };
+        assert!(SshBackend::new(&no_host, Path::new(".")).is_err());
+        let option_host = RemoteConfig { host: "-oProxyCommand=touch /tmp/x".to_string(), ..config.clone() };
+        assert!(SshBackend::new(&option_host, Path::new(".")).is_err());
+        let option_user = RemoteConfig { user: " -oProxyCommand=id".to_string(), ..config };
+        assert!(SshBackend::new(&option_user, Path::new(".")).is_err());
+    }
+}
@@ -0,0 +1,340 @@
+//! Keeping files in step between the local workspace and a session's target.
+//!
+//! Each backend keeps a ledger of the files pushed or pulled through it: the
+//! content hash both sides had at that moment. Against that base a file is in
+//! sync, ahead on one side, or in conflict when both sides changed. A push
+//! over changes made on the target (or a pull over local ones) is refused
+//! unless forced. The write tool pushes what it writes; other paths are
+//! pushed and pulled through the session API.
+//!
+//! The ledger is kept in `.carry/sync-ledger.json` in the workspace, so a
+//! file synced in one session is still known to the next.
+
+use super::ExecBackend;
+use crate::llm::utils::file_tracker::content_hash;
+use crate::llm::utils::overlay;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Ledger file under the workspace's `.carry/`
+const LEDGER_FILE: &str = "sync-ledger.json";
+
+/// Serializes read-modify-write of ledger files within the process
+static LEDGER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Sync bases by target (see `target_key`), then workspace-relative path
+type Ledger = HashMap<String, BTreeMap<String, SyncBase>>;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct SyncBase {
+    /// Content hash at the last sync; None if the file did not exist
+    hash: Option<u64>,
+    synced_at_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+    InSync,
+    /// Changed locally since the last sync
+    LocalAhead,
+    /// Changed on the target since the last sync
+    RemoteAhead,
+    /// Changed on both sides, or never synced and different
+    Conflict,
+}
+
+impl SyncState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SyncState::InSync => "in_sync",
+            SyncState::LocalAhead => "local_ahead",
+            SyncState::RemoteAhead => "remote_ahead",
+            SyncState::Conflict => "conflict",
+        }
+    }
+}
+
+/// Sync state of one file
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatus {
+    /// Path relative to the workspace
+    pub path: String,
+    pub state: SyncState,
+    /// When the file was last pushed or pulled
+    pub synced_at_ms: Option<i64>,
+}
+
+fn classify(base: Option<Option<u64>>, local: Option<u64>, remote: Option<u64>) -> SyncState {
+    if local == remote {
+        return SyncState::InSync;
+    }
+    match base {
+        None if local.is_none() => SyncState::RemoteAhead,
+        None if remote.is_none() => SyncState::LocalAhead,
+        None => SyncState::Conflict,
+        Some(base) => match (local != base, remote != base) {
+            (true, false) => SyncState::LocalAhead,
+            (false, true) => SyncState::RemoteAhead,
+            _ => SyncState::Conflict,
+        },
+    }
+}
+
+/// A file as both sides have it, with its ledger entry
+struct Snapshot {
+    rel: String,
+    remote_path: String,
+    local: Option<Vec<u8>>,
+    remote: Option<Vec<u8>>,
+    base: Option<SyncBase>,
+}
+
+impl Snapshot {
+    fn take(target: &dyn ExecBackend, local_path: &Path) -> Result<Self> {
+        let rel = local_path
+            .strip_prefix(target.workspace())
+            .map(|p| p.to_string_lossy().into_owned())
+            .ok()
+            .filter(|rel| !rel.is_empty())
+            .with_context(|| format!("{} is not a file in the workspace", local_path.display()))?;
+        let local = match fs::read(local_path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", local_path.display())),
+        };
+        let remote_path = target.target_path(local_path)?;
+        let remote = target.read_file(&remote_path)?;
+        let base = {
+            let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            load_ledger(target)?
+                .remove(&target_key(target))
+                .and_then(|mut files| files.remove(&rel))
+        };
+        Ok(Self { rel, remote_path, local, remote, base })
+    }
+
+    fn state(&self) -> SyncState {
+        classify(
+            self.base.map(|b| b.hash),
+            self.local.as_deref().map(content_hash),
+            self.remote.as_deref().map(content_hash),
+        )
+    }
+
+    fn status(&self) -> SyncStatus {
+        SyncStatus {
+            path: self.rel.clone(),
+            state: self.state(),
+            synced_at_ms: self.base.map(|b| b.synced_at_ms),
+        }
+    }
+}
+
+/// Ledger entries are per target as well as per workspace: one host can hold several checkouts
+fn target_key(target: &dyn ExecBackend) -> String {
+    format!("{} {}", target.name(), target.root())
+}
+
+fn ledger_path(target: &dyn ExecBackend) -> PathBuf {
+    target.workspace().join(".carry").join(LEDGER_FILE)
+}
+
+/// The workspace's ledger; empty when nothing was synced yet. Callers hold `LEDGER_LOCK`.
+fn load_ledger(target: &dyn ExecBackend) -> Result<Ledger> {
+    let path = ledger_path(target);
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Ledger::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn record(target: &dyn ExecBackend, rel: &str, content: Option<&[u8]>) -> Result<SyncStatus> {
+    let base = SyncBase {
+        hash: content.map(content_hash),
+        synced_at_ms: now_ms(),
+    };
+    let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut ledger = load_ledger(target)?;
+    ledger.entry(target_key(target)).or_default().insert(rel.to_string(), base);
+
+    let path = ledger_path(target);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(&ledger)?).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(SyncStatus {
+        path: rel.to_string(),
+        state: SyncState::InSync,
+        synced_at_ms: Some(base.synced_at_ms),
+    })
+}
+
+/// Copy a local file to the target, or remove it there if it was removed locally.
+/// Refused when the target's copy changed since the last sync, unless `force`.
+pub fn push(target: &dyn ExecBackend, local_path: &Path, force: bool) -> Result<SyncStatus> {
+    let snapshot = Snapshot::take(target, local_path)?;
+    match snapshot.state() {
+        SyncState::InSync => {}
+        SyncState::RemoteAhead | SyncState::Conflict if !force => bail!(
+            "{} changed on {} since it was last synced; pull it first or push with force",
+            snapshot.rel,
+            target.name()
+        ),
+        _ => match &snapshot.local {
+            Some(content) => target.write_file(&snapshot.remote_path, content)?,
+            None => target.remove_file(&snapshot.remote_path)?,
+        },
+    }
+    record(target, &snapshot.rel, snapshot.local.as_deref())
+}
+
+/// Copy a file from the target into the workspace, or remove the local copy if the
+/// target has none. Refused when the local file changed since the last sync, unless `force`.
+pub fn pull(target: &dyn ExecBackend, local_path: &Path, force: bool) -> Result<SyncStatus> {
+    let snapshot = Snapshot::take(target, local_path)?;
+    match snapshot.state() {
+        SyncState::InSync => {}
+        SyncState::LocalAhead | SyncState::Conflict if !force => bail!(
+            "{} changed locally since it was last synced; push it first or pull with force",
+            snapshot.rel
+        ),
+        _ => match &snapshot.remote {
+            Some(content) => {
+                if let Some(parent) = local_path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+                }
+                fs::write(local_path, content).with_context(|| format!("Failed to write {}", local_path.display()))?;
+            }
+            None => {
+                fs::remove_file(local_path).with_context(|| format!("Failed to remove {}", local_path.display()))?;
+            }
+        },
+    }
+    record(target, &snapshot.rel, snapshot.remote.as_deref())
+}
+
+/// Current state of the given files, or of every file synced so far when `paths` is empty
+pub fn status(target: &dyn ExecBackend, paths: &[&Path]) -> Result<Vec<SyncStatus>> {
+    let tracked: Vec<_>;
+    let paths = if paths.is_empty() {
+        tracked = {
+            let _guard = LEDGER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            load_ledger(target)?
+                .remove(&target_key(target))
+                .map(|files| files.keys().map(|rel| target.workspace().join(rel)).collect())
+                .unwrap_or_default()
+        };
+        tracked.iter().map(|p| p.as_path()).collect()
+    } else {
+        paths.to_vec()
+    };
+    paths
+        .into_iter()
+        .map(|path| Snapshot::take(target, path).map(|s| s.status()))
+        .collect()
+}
+
+/// Before the local tools write a file, take the content it has now as its
+/// sync base when the target's copy is the same. The write then shows as a
+/// local change that `push_current` sends, also for files never synced before;
+/// a target copy that differs is left for the push to refuse.
+pub fn before_local_write(local_path: &Path) {
+    let Some(target) = super::current() else {
+        return;
+    };
+    if overlay::is_active() {
+        return;
+    }
+    let result = Snapshot::take(target.as_ref(), local_path).and_then(|snapshot| match snapshot.state() {
+        SyncState::InSync => record(target.as_ref(), &snapshot.rel, snapshot.local.as_deref()).map(|_| ()),
+        _ => Ok(()),
+    });
+    if let Err(e) = result {
+        log::warn!("Failed to record the sync base of {}: {:#}", local_path.display(), e);
+    }
+}
+
+/// Push a file the local tools just wrote to the executing session's target.
+/// Returns a note for the tool output; None without a target or in overlay mode.
+pub fn push_current(local_path: &Path) -> Option<String> {
+    let target = super::current()?;
+    if overlay::is_active() {
+        return None;
+    }
+    Some(match push(target.as_ref(), local_path, false) {
+        Ok(_) => format!("Synced to {}", target.name()),
+        Err(e) => format!("Not synced to {}: {:#}", target.name(), e),
+    })
+}
+
+fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::backend::tests::LocalShell;
+    use crate::llm::utils::tool_access::with_tool_session;
+    use crate::testing::{FakeWorkspace, ToolHarness};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn classifies_against_the_last_synced_content() {
+        use SyncState::*;
+        let (a, b, c) = (Some(1), Some(2), Some(3));
+        // Never synced: whichever side has the file is ahead; two different copies conflict
+        assert_eq!(classify(None, a, a), InSync);
+        assert_eq!(classify(None, a, None), LocalAhead);
+        assert_eq!(classify(None, None, a), RemoteAhead);
+        assert_eq!(classify(None, a, b), Conflict);
+        // Synced at content a
+        assert_eq!(classify(Some(a), b, a), LocalAhead);
+        assert_eq!(classify(Some(a), a, b), RemoteAhead);
+        assert_eq!(classify(Some(a), b, c), Conflict);
+        assert_eq!(classify(Some(a), b, b), InSync);
+        assert_eq!(classify(Some(a), None, a), LocalAhead);
+        assert_eq!(classify(Some(None), None, a), RemoteAhead);
+    }
+
+    #[test]
+    fn files_modified_through_write_are_pushed() {
+        let workspace = FakeWorkspace::new().file("src/main.rs", "fn main() {}\n").build().unwrap();
+        let remote = FakeWorkspace::new().file("src/main.rs", "fn main() {}\n").build().unwrap();
+        let target = Arc::new(LocalShell {
+            workspace: workspace.path().to_path_buf(),
+            root: remote.path().to_string_lossy().into_owned(),
+        });
+        let session_id = format!("sync-write-{}", std::process::id());
+        super::super::register(&session_id, target.clone());
+
+        let write = ToolHarness::builtin("write").full_access();
+        let edited = "fn main() {\n    println!(\"hi\");\n}\n";
+        let result = with_tool_session(&session_id, || {
+            write.call(&json!({ "file_path": workspace.join("src/main.rs"), "content": edited }))
+        });
+        super::super::unregister(&session_id);
+
+        assert!(result.success, "{}", result.stderr);
+        assert!(result.stdout.contains("Synced to local-test"), "{}", result.stdout);
+        assert_eq!(remote.read("src/main.rs").unwrap(), edited);
+        // The ledger outlives the process's memory of it
+        let statuses = status(target.as_ref(), &[]).unwrap();
+        assert_eq!(statuses.len(), 1);
+        assert_eq!((statuses[0].path.as_str(), statuses[0].state), ("src/main.rs", SyncState::InSync));
+        assert!(workspace.join(".carry").join(LEDGER_FILE).exists());
+    }
+}
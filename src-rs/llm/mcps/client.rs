@@ -3,9 +3,10 @@ use serde::{ Deserialize, Serialize };
 use serde_json::{ json, Value };
 use std::collections::HashMap;
 use std::io::{ BufRead, BufReader, Write, Read };
-use std::process::{ Child, Command, Stdio };
 use std::sync::Mutex;
 
+use super::process::{ self, Launch };
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcRequest {
     pub jsonrpc: String,
@@ -37,8 +38,11 @@ enum Transport {
     Stdio {
         stdin: std::process::ChildStdin,
         reader: std::io::Lines<BufReader<std::process::ChildStdout>>,
-        #[allow(dead_code)]
-        child: Child,
+        /// Registered in `process`, which owns the child
+        pid: u32,
+        launch: Launch,
+        /// Times the server has been started again after crashing or timing out
+        restarts: u32,
     },
     Http {
         client: reqwest::blocking::Client,
@@ -57,18 +61,9 @@ pub struct McpClient {
 }
 
 impl McpClient {
-    pub fn new(command: &str, args: &[String], env: &HashMap<String, String>) -> Result<Self> {
-        let mut cmd = Command::new(command);
-        cmd.args(args);
-        cmd.envs(env);
-        cmd.stdin(Stdio::piped());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::inherit());
-
-        let mut child = cmd.spawn().context("Failed to spawn MCP server")?;
-
-        let stdin = child.stdin.take().ok_or_else(|| anyhow!("No stdin"))?;
-        let stdout = child.stdout.take().ok_or_else(|| anyhow!("No stdout"))?;
+    /// Start a stdio server; it is stopped when the client is dropped
+    pub fn new(launch: Launch) -> Result<Self> {
+        let (pid, stdin, stdout) = process::spawn(&launch)?;
         let reader = BufReader::new(stdout).lines();
 
         Ok(Self {
@@ -76,7 +71,9 @@ impl McpClient {
                 transport: Transport::Stdio {
                     stdin,
                     reader,
-                    child,
+                    pid,
+                    launch,
+                    restarts: 0,
                 },
                 request_id: 0,
             }),
@@ -119,9 +116,77 @@ impl McpClient {
 
     pub fn request(&self, method: &str, params: Option<Value>) -> Result<Value> {
         let mut inner = self.inner.lock().map_err(|_| anyhow!("Failed to lock client"))?;
+        inner.ensure_running()?;
+        inner.request(method, params)
+    }
+}
 
-        inner.request_id += 1;
-        let id = inner.request_id;
+impl ClientInner {
+    /// Start a stdio server again if it crashed or was killed for timing out
+    fn ensure_running(&mut self) -> Result<()> {
+        let Transport::Stdio { pid, launch, restarts, .. } = &mut self.transport else {
+            return Ok(());
+        };
+        let Some(reason) = process::exit_reason(*pid) else {
+            return Ok(());
+        };
+        if !reason.is_crash() {
+            return Err(anyhow!("MCP server {} {}", launch.server, reason.describe()));
+        }
+        if *restarts >= launch.limits.max_restarts {
+            return Err(anyhow!(
+                "MCP server {} {}; not restarting it after {} restarts",
+                launch.server,
+                reason.describe(),
+                restarts
+            ));
+        }
+
+        let (launch, old_pid) = (launch.clone(), *pid);
+        *restarts += 1;
+        let restarts = *restarts;
+        log::warn!("MCP server {} {}; restarting it (restart {})", launch.server, reason.describe(), restarts);
+        // The crashed process stays registered until a new one is up, so a failed
+        // start is retried on the next request
+        let result = process::spawn(&launch).and_then(|(pid, stdin, stdout)| {
+            process::stop(old_pid);
+            self.transport = Transport::Stdio {
+                stdin,
+                reader: BufReader::new(stdout).lines(),
+                pid,
+                launch: launch.clone(),
+                restarts,
+            };
+            self.handshake()
+        });
+        super::record_restart(&launch.server, restarts, result.as_ref().err().map(|e| e.to_string()));
+        result.with_context(|| format!("Failed to restart MCP server {}", launch.server))
+    }
+
+    fn handshake(&mut self) -> Result<()> {
+        self.request("initialize", Some(initialize_params()))?;
+        self.notify("notifications/initialized", None)
+    }
+
+    fn request(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
+        if let Transport::Stdio { pid, launch, .. } = &self.transport {
+            let pid = *pid;
+            let server = launch.server.clone();
+            process::begin_request(pid, launch.limits.request_timeout());
+            let result = self.exchange(method, params);
+            process::end_request(pid);
+            // A broken pipe or closed stream is better explained by why the server went away
+            return result.map_err(|e| match process::exit_reason(pid) {
+                Some(reason) => anyhow!("MCP server {} {}", server, reason.describe()),
+                None => e,
+            });
+        }
+        self.exchange(method, params)
+    }
+
+    fn exchange(&mut self, method: &str, params: Option<Value>) -> Result<Value> {
+        self.request_id += 1;
+        let id = self.request_id;
 
         let req = JsonRpcRequest {
             jsonrpc: "2.0".to_string(),
@@ -130,7 +195,7 @@ impl McpClient {
             params,
         };
 
-        match &mut inner.transport {
+        match &mut self.transport {
             Transport::Stdio { stdin, .. } => {
                 let json_req = serde_json::to_string(&req)?;
                 stdin.write_all(json_req.as_bytes())?;
@@ -175,7 +240,7 @@ impl McpClient {
 
         // Loop to find response
         loop {
-            let line = match &mut inner.transport {
+            let line = match &mut self.transport {
                 Transport::Stdio { reader, .. } => {
                     match reader.next() {
                         Some(Ok(l)) => l,
@@ -200,10 +265,10 @@ impl McpClient {
                                     // End of event
                                     break;
                                 }
-                                if l.starts_with("event: ") {
-                                    event_type = l[7..].trim().to_string();
-                                } else if l.starts_with("data: ") {
-                                    data.push_str(&l[6..]);
+                                if let Some(rest) = l.strip_prefix("event: ") {
+                                    event_type = rest.trim().to_string();
+                                } else if let Some(rest) = l.strip_prefix("data: ") {
+                                    data.push_str(rest);
                                 }
                             }
                             Some(Err(e)) => {
@@ -245,6 +310,33 @@ impl McpClient {
         }
     }
 
+    fn notify(&mut self, method: &str, params: Option<Value>) -> Result<()> {
+        let req = JsonRpcRequest {
+            jsonrpc: "2.0".to_string(),
+            id: None,
+            method: method.to_string(),
+            params,
+        };
+
+        match &mut self.transport {
+            Transport::Stdio { stdin, .. } => {
+                let json_req = serde_json::to_string(&req)?;
+                stdin.write_all(json_req.as_bytes())?;
+                stdin.write_all(b"\n")?;
+                stdin.flush()?;
+            }
+            Transport::Http { client, endpoint, .. } => {
+                let endpoint_url = endpoint
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("MCP endpoint not initialized"))?;
+                client.post(endpoint_url).json(&req).send()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl McpClient {
     pub fn initialize(&self) -> Result<()> {
         // Special handling for HTTP: wait for endpoint event
         {
@@ -262,10 +354,10 @@ impl McpClient {
                                 if l.is_empty() {
                                     break;
                                 }
-                                if l.starts_with("event: ") {
-                                    event_type = l[7..].trim().to_string();
-                                } else if l.starts_with("data: ") {
-                                    data.push_str(&l[6..]);
+                                if let Some(rest) = l.strip_prefix("event: ") {
+                                    event_type = rest.trim().to_string();
+                                } else if let Some(rest) = l.strip_prefix("data: ") {
+                                    data.push_str(rest);
                                 }
                             }
                             Some(Err(e)) => {
@@ -286,53 +378,8 @@ impl McpClient {
             }
         }
 
-        let params =
-            json!({
-            "protocolVersion": "2024-11-05",
-            "capabilities": {
-                "roots": {
-                    "listChanged": true
-                },
-                "sampling": {}
-            },
-            "clientInfo": {
-                "name": "carrycode-cli",
-                "version": "0.1.0"
-            }
-        });
-
-        self.request("initialize", Some(params))?;
-
-        self.notify("notifications/initialized", None)?;
-
-        Ok(())
-    }
-
-    pub fn notify(&self, method: &str, params: Option<Value>) -> Result<()> {
         let mut inner = self.inner.lock().map_err(|_| anyhow!("Failed to lock client"))?;
-
-        let req = JsonRpcRequest {
-            jsonrpc: "2.0".to_string(),
-            id: None,
-            method: method.to_string(),
-            params,
-        };
-
-        match &mut inner.transport {
-            Transport::Stdio { stdin, .. } => {
-                let json_req = serde_json::to_string(&req)?;
-                stdin.write_all(json_req.as_bytes())?;
-                stdin.write_all(b"\n")?;
-                stdin.flush()?;
-            }
-            Transport::Http { client, endpoint, .. } => {
-                let endpoint_url = endpoint
-                    .as_ref()
-                    .ok_or_else(|| anyhow!("MCP endpoint not initialized"))?;
-                client.post(endpoint_url).json(&req).send()?;
-            }
-        }
-        Ok(())
+        inner.handshake()
     }
 
     pub fn list_tools(&self) -> Result<Vec<Value>> {
@@ -353,3 +400,29 @@ impl McpClient {
         self.request("tools/call", Some(params))
     }
 }
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        if let Ok(inner) = self.inner.get_mut() {
+            if let Transport::Stdio { pid, .. } = &inner.transport {
+                process::stop(*pid);
+            }
+        }
+    }
+}
+
+fn initialize_params() -> Value {
+    json!({
+        "protocolVersion": "2024-11-05",
+        "capabilities": {
+            "roots": {
+                "listChanged": true
+            },
+            "sampling": {}
+        },
+        "clientInfo": {
+            "name": "carrycode-cli",
+            "version": "0.1.0"
+        }
+    })
+}
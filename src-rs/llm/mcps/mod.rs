@@ -1,23 +1,102 @@
 pub mod client;
+pub mod process;
 pub mod tool;
 
 pub use client::McpClient;
 pub use tool::McpTool;
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, McpServerConfig};
+use crate::health::{self, Subsystem};
 use crate::llm::tools::tool_trait::Tool;
-use std::sync::Arc;
+use crate::llm::utils::session_vars;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex as StdMutex};
 
-pub fn load_mcp_tools(config: &AppConfig) -> Vec<Box<dyn Tool>> {
-    let mut tools: Vec<Box<dyn Tool>> = Vec::new();
+/// Outcome of the last attempt to start an MCP server
+#[derive(Debug, Clone)]
+pub struct McpServerState {
+    /// "running" | "failed"
+    pub state: &'static str,
+    pub tool_count: usize,
+    pub last_error: Option<String>,
+    /// Times a process of the server was restarted after crashing or timing out
+    pub restarts: u32,
+}
+
+static SERVER_STATES: StdMutex<BTreeMap<String, McpServerState>> = StdMutex::new(BTreeMap::new());
+
+/// State of every MCP server a session has tried to start, by name
+pub fn server_states() -> Vec<(String, McpServerState)> {
+    SERVER_STATES
+        .lock()
+        .map(|states| states.iter().map(|(n, s)| (n.clone(), s.clone())).collect())
+        .unwrap_or_default()
+}
+
+fn set_server_state(name: &str, result: Result<usize, String>) {
+    let state = match result {
+        Ok(tool_count) => McpServerState {
+            state: "running",
+            tool_count,
+            last_error: None,
+            restarts: 0,
+        },
+        Err(e) => {
+            health::record_error(Subsystem::Mcp, format!("{}: {}", name, e));
+            McpServerState {
+                state: "failed",
+                tool_count: 0,
+                last_error: Some(e),
+                restarts: 0,
+            }
+        }
+    };
+    if let Ok(mut states) = SERVER_STATES.lock() {
+        states.insert(name.to_string(), state);
+    }
+}
 
-    for (name, server_config) in &config.mcp_servers {
+/// Record a restart of one of the server's processes, and its error if it failed
+fn record_restart(name: &str, restarts: u32, error: Option<String>) {
+    if let Some(e) = &error {
+        health::record_error(Subsystem::Mcp, format!("{}: restart failed: {}", name, e));
+    }
+    if let Ok(mut states) = SERVER_STATES.lock() {
+        if let Some(state) = states.get_mut(name) {
+            state.restarts = state.restarts.max(restarts);
+            if error.is_some() {
+                state.last_error = error;
+            }
+        }
+    }
+}
+
+/// Longest function name the provider APIs accept
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// Start the configured servers for session `owner` and wrap their tools.
+/// `reserved` are the names of the builtin tools, which MCP tools may not take.
+/// Stdio servers get the session's variables in their environment, under the
+/// server's own `env`.
+pub fn load_mcp_tools(config: &AppConfig, owner: &str, reserved: &HashSet<String>) -> Vec<Box<dyn Tool>> {
+    let mut servers: Vec<(&String, &McpServerConfig)> = config.mcp_servers.iter().collect();
+    servers.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut loaded: Vec<(&String, &McpServerConfig, Arc<McpClient>, Vec<Value>)> = Vec::new();
+    for (name, server_config) in servers {
         log::info!("Initializing MCP server: {}", name);
         let client_result = match server_config {
-            crate::config::McpServerConfig::Stdio { command, args, env, .. } => {
-                McpClient::new(command, args, env)
-            }
-            crate::config::McpServerConfig::Http { url, headers, .. } => {
+            McpServerConfig::Stdio { command, args, env, .. } => McpClient::new(process::Launch {
+                server: name.clone(),
+                owner: owner.to_string(),
+                command: command.clone(),
+                args: args.clone(),
+                // Later entries win, so the configured env overrides session variables
+                env: session_vars::for_session(owner).into_iter().chain(env.clone()).collect(),
+                limits: config.mcp.clone(),
+            }),
+            McpServerConfig::Http { url, headers, .. } => {
                 McpClient::new_http(url, headers)
             }
         };
@@ -26,26 +105,119 @@ pub fn load_mcp_tools(config: &AppConfig) -> Vec<Box<dyn Tool>> {
             Ok(client) => {
                 if let Err(e) = client.initialize() {
                     log::error!("Failed to initialize MCP server {}: {}", name, e);
+                    set_server_state(name, Err(format!("initialize failed: {}", e)));
                     continue;
                 }
 
                 match client.list_tools() {
                     Ok(tool_defs) => {
-                        let client_arc = Arc::new(client);
-                        for def in tool_defs {
-                            tools.push(Box::new(McpTool::new(client_arc.clone(), def, name)));
-                        }
+                        set_server_state(name, Ok(tool_defs.len()));
+                        loaded.push((name, server_config, Arc::new(client), tool_defs));
                     }
                     Err(e) => {
                         log::error!("Failed to list tools for MCP server {}: {}", name, e);
+                        set_server_state(name, Err(format!("listing tools failed: {}", e)));
                     }
                 }
             }
             Err(e) => {
                 log::error!("Failed to start MCP server {}: {}", name, e);
+                set_server_state(name, Err(format!("start failed: {}", e)));
             }
         }
     }
 
+    let listed: Vec<(&str, &str)> = loaded
+        .iter()
+        .flat_map(|(name, _, _, defs)| defs.iter().map(move |def| (name.as_str(), tool::original_name(def))))
+        .collect();
+    let mut names = exposed_names(&listed, config.mcp.flat_tool_names, reserved).into_iter();
+
+    let mut tools: Vec<Box<dyn Tool>> = Vec::new();
+    for (name, server_config, client, defs) in loaded {
+        for def in defs {
+            let exposed = names.next().unwrap_or_default();
+            tools.push(Box::new(McpTool::new(
+                client.clone(),
+                def,
+                name,
+                &exposed,
+                server_config.key_paths(),
+                config.mcp.trust_read_only_hints,
+                config.security.prompt_injection,
+            )));
+        }
+    }
     tools
 }
+
+/// Names under which the servers' tools are offered to the model, one per
+/// (server, tool) in order. Each is `server__tool`, or with `flat` the bare
+/// tool name where no other server's tool and no `reserved` name has it; the
+/// model calls the tool by that name and `McpTool` calls the server with its
+/// own. Characters the provider APIs reject become '_', and a name that is
+/// still taken, say after cutting it to length, gets a numeric suffix.
+pub fn exposed_names(tools: &[(&str, &str)], flat: bool, reserved: &HashSet<String>) -> Vec<String> {
+    let mut bare_counts: HashMap<String, usize> = HashMap::new();
+    for (_, tool) in tools {
+        *bare_counts.entry(sanitize_name(tool)).or_insert(0) += 1;
+    }
+
+    let mut taken = reserved.clone();
+    let mut names = Vec::with_capacity(tools.len());
+    for (server, tool) in tools {
+        let bare = sanitize_name(tool);
+        let wanted = if flat && bare_counts.get(&bare) == Some(&1) && !reserved.contains(&bare) {
+            bare
+        } else {
+            format!("{}__{}", sanitize_name(server), bare)
+        };
+        let mut name = truncate_name(&wanted, MAX_TOOL_NAME_LEN);
+        let mut n = 2;
+        while taken.contains(&name) {
+            let suffix = format!("_{}", n);
+            name = format!("{}{}", truncate_name(&wanted, MAX_TOOL_NAME_LEN - suffix.len()), suffix);
+            n += 1;
+        }
+        if name != wanted {
+            log::warn!("MCP tool {} of server {} is offered as {}", tool, server, name);
+        }
+        taken.insert(name.clone());
+        names.push(name);
+    }
+    names
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect()
+}
+
+/// `name` is ASCII after `sanitize_name`, so any byte is a boundary
+fn truncate_name(name: &str, max: usize) -> String {
+    name[..name.len().min(max)].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_names_are_namespaced_unless_flat_and_unique() {
+        let tools = [("github", "search"), ("jira", "search"), ("github", "create_issue"), ("fs", "read")];
+        let reserved: HashSet<String> = ["read".to_string()].into();
+        assert_eq!(
+            exposed_names(&tools, false, &reserved),
+            ["github__search", "jira__search", "github__create_issue", "fs__read"]
+        );
+        assert_eq!(exposed_names(&tools, true, &reserved), ["github__search", "jira__search", "create_issue", "fs__read"]);
+
+        let long = "x".repeat(80);
+        let clashing = [("my.server", "get item"), ("my_server", "get_item"), ("a", long.as_str()), ("a", long.as_str())];
+        let names = exposed_names(&clashing, false, &HashSet::new());
+        assert_eq!(names[..2], ["my_server__get_item", "my_server__get_item_2"]);
+        assert_eq!(names[2].len(), MAX_TOOL_NAME_LEN);
+        assert!(names[3].ends_with("_2") && names[3].len() == MAX_TOOL_NAME_LEN);
+    }
+}
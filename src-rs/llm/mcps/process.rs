@@ -0,0 +1,322 @@
+//! Registry of the stdio MCP server processes started by this crate.
+//!
+//! Every server process is spawned here, in its own process group and under
+//! the `[mcp]` resource limits where the OS supports them, and stays in the
+//! registry until it is stopped. A watcher thread reaps processes that exit
+//! on their own, so none is left a zombie, and kills any that has been busy
+//! on one request for longer than `request_timeout_secs`. A session's
+//! servers are stopped when it is closed, and all of them on `stop_all`,
+//! which hosts call on shutdown.
+
+use std::collections::HashMap;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{LazyLock, Mutex as StdMutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::config::McpConfig;
+
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// Time a stopped server gets to exit after SIGTERM before it is killed
+const STOP_GRACE: Duration = Duration::from_millis(500);
+
+/// What is needed to start, and restart, one stdio server
+#[derive(Debug, Clone)]
+pub struct Launch {
+    /// Name of the server in `mcp_servers`
+    pub server: String,
+    /// Session the server was started for
+    pub owner: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub limits: McpConfig,
+}
+
+/// Why a registered process is no longer running
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitReason {
+    /// It exited by itself, with this status
+    Exited(String),
+    /// The watcher killed it for not answering a request in time
+    TimedOut(Duration),
+    /// It was stopped on purpose, or was never registered
+    Stopped,
+}
+
+impl ExitReason {
+    /// Whether the server should be started again on its next request
+    pub fn is_crash(&self) -> bool {
+        !matches!(self, ExitReason::Stopped)
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            ExitReason::Exited(status) => format!("exited ({})", status),
+            ExitReason::TimedOut(timeout) => format!("did not answer within {}s and was killed", timeout.as_secs()),
+            ExitReason::Stopped => "was stopped".to_string(),
+        }
+    }
+}
+
+struct Entry {
+    server: String,
+    owner: String,
+    child: Child,
+    /// Deadline of the request in progress, with the timeout it came from
+    deadline: Option<(Instant, Duration)>,
+    exit: Option<ExitReason>,
+}
+
+static PROCESSES: LazyLock<StdMutex<HashMap<u32, Entry>>> = LazyLock::new(|| StdMutex::new(HashMap::new()));
+
+static START_WATCHER: Once = Once::new();
+
+/// Start the server and register it; returns its pid and pipes
+pub fn spawn(launch: &Launch) -> Result<(u32, ChildStdin, ChildStdout)> {
+    let mut cmd = Command::new(&launch.command);
+    cmd.args(&launch.args);
+    cmd.envs(&launch.env);
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::inherit());
+    apply_limits(&mut cmd, &launch.limits);
+
+    let mut child = cmd.spawn().context("Failed to spawn MCP server")?;
+    let stdin = child.stdin.take().ok_or_else(|| anyhow!("No stdin"))?;
+    let stdout = child.stdout.take().ok_or_else(|| anyhow!("No stdout"))?;
+    let pid = child.id();
+
+    START_WATCHER.call_once(|| {
+        if let Err(e) = thread::Builder::new().name("mcp-watcher".to_string()).spawn(run_watcher) {
+            log::warn!("MCP process watcher failed to start: {}", e);
+        }
+    });
+    if let Ok(mut processes) = PROCESSES.lock() {
+        processes.insert(
+            pid,
+            Entry {
+                server: launch.server.clone(),
+                owner: launch.owner.clone(),
+                child,
+                deadline: None,
+                exit: None,
+            },
+        );
+    }
+    log::info!("Started MCP server {} (pid {})", launch.server, pid);
+    Ok((pid, stdin, stdout))
+}
+
+/// Mark the process busy on a request that must be answered within `timeout`
+pub fn begin_request(pid: u32, timeout: Option<Duration>) {
+    if let Ok(mut processes) = PROCESSES.lock() {
+        if let Some(entry) = processes.get_mut(&pid) {
+            entry.deadline = timeout.map(|t| (Instant::now() + t, t));
+        }
+    }
+}
+
+pub fn end_request(pid: u32) {
+    begin_request(pid, None);
+}
+
+/// Why the process is not running, or None while it is
+pub fn exit_reason(pid: u32) -> Option<ExitReason> {
+    let Ok(mut processes) = PROCESSES.lock() else {
+        return Some(ExitReason::Stopped);
+    };
+    let Some(entry) = processes.get_mut(&pid) else {
+        return Some(ExitReason::Stopped);
+    };
+    if entry.exit.is_none() {
+        if let Ok(Some(status)) = entry.child.try_wait() {
+            entry.exit = Some(ExitReason::Exited(status.to_string()));
+        }
+    }
+    entry.exit.clone()
+}
+
+/// Stop the process and remove it from the registry
+pub fn stop(pid: u32) {
+    let entry = PROCESSES.lock().ok().and_then(|mut p| p.remove(&pid));
+    if let Some(entry) = entry {
+        terminate(vec![entry]);
+    }
+}
+
+/// Stop the servers started for a session; returns how many were running
+pub fn stop_owned_by(owner: &str) -> usize {
+    stop_matching(|entry| entry.owner == owner)
+}
+
+/// Stop every registered server; returns how many were running
+pub fn stop_all() -> usize {
+    stop_matching(|_| true)
+}
+
+/// Live processes by server name
+pub fn running_by_server() -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    if let Ok(processes) = PROCESSES.lock() {
+        for entry in processes.values().filter(|e| e.exit.is_none()) {
+            *counts.entry(entry.server.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+fn stop_matching(matches: impl Fn(&Entry) -> bool) -> usize {
+    let entries: Vec<Entry> = match PROCESSES.lock() {
+        Ok(mut processes) => {
+            let pids: Vec<u32> = processes.iter().filter(|(_, e)| matches(e)).map(|(pid, _)| *pid).collect();
+            pids.iter().filter_map(|pid| processes.remove(pid)).collect()
+        }
+        Err(_) => Vec::new(),
+    };
+    let running = entries.iter().filter(|e| e.exit.is_none()).count();
+    terminate(entries);
+    running
+}
+
+/// Ask the processes to exit, kill those still running after `STOP_GRACE`, and reap them all
+fn terminate(mut entries: Vec<Entry>) {
+    for entry in &entries {
+        if entry.exit.is_none() {
+            signal_group(entry.child.id(), Signal::Term);
+        }
+    }
+    let deadline = Instant::now() + STOP_GRACE;
+    loop {
+        entries.retain_mut(|entry| !matches!(entry.child.try_wait(), Ok(Some(_)) | Err(_)));
+        if entries.is_empty() || Instant::now() >= deadline {
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    for mut entry in entries {
+        log::warn!("MCP server {} (pid {}) ignored SIGTERM; killing it", entry.server, entry.child.id());
+        signal_group(entry.child.id(), Signal::Kill);
+        let _ = entry.child.kill();
+        let _ = entry.child.wait();
+    }
+}
+
+fn run_watcher() {
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let Ok(mut processes) = PROCESSES.lock() else {
+            return;
+        };
+        let now = Instant::now();
+        for (pid, entry) in processes.iter_mut().filter(|(_, e)| e.exit.is_none()) {
+            if let Ok(Some(status)) = entry.child.try_wait() {
+                log::warn!("MCP server {} (pid {}) exited: {}", entry.server, pid, status);
+                entry.exit = Some(ExitReason::Exited(status.to_string()));
+            } else if let Some((_, timeout)) = entry.deadline.filter(|(deadline, _)| now >= *deadline) {
+                log::warn!(
+                    "MCP server {} (pid {}) did not answer within {}s; killing it",
+                    entry.server,
+                    pid,
+                    timeout.as_secs()
+                );
+                signal_group(*pid, Signal::Kill);
+                let _ = entry.child.kill();
+                let _ = entry.child.wait();
+                entry.exit = Some(ExitReason::TimedOut(timeout));
+            }
+        }
+    }
+}
+
+enum Signal {
+    Term,
+    Kill,
+}
+
+/// Signal the server and anything it started, such as the node process behind `npx`
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: Signal) {
+    let signal = match signal {
+        Signal::Term => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // The server leads its own process group (see `apply_limits`)
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), signal);
+    }
+}
+
+#[cfg(not(unix))]
+fn signal_group(_pid: u32, _signal: Signal) {}
+
+#[cfg(unix)]
+fn apply_limits(cmd: &mut Command, limits: &McpConfig) {
+    use std::os::unix::process::CommandExt;
+
+    cmd.process_group(0);
+    let memory = limits.memory_limit_mb.saturating_mul(1024 * 1024);
+    let cpu = limits.cpu_time_limit_secs;
+    if memory == 0 && cpu == 0 {
+        return;
+    }
+    let rlimit = |value: u64| libc::rlimit {
+        rlim_cur: value as libc::rlim_t,
+        rlim_max: value as libc::rlim_t,
+    };
+    // SAFETY: the closure only calls setrlimit, which is async-signal-safe
+    unsafe {
+        cmd.pre_exec(move || {
+            if memory > 0 && libc::setrlimit(libc::RLIMIT_AS, &rlimit(memory)) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if cpu > 0 && libc::setrlimit(libc::RLIMIT_CPU, &rlimit(cpu)) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn apply_limits(_cmd: &mut Command, _limits: &McpConfig) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn launch(owner: &str, script: &str) -> Launch {
+        Launch {
+            server: "test".to_string(),
+            owner: owner.to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            env: HashMap::new(),
+            limits: McpConfig::default(),
+        }
+    }
+
+    #[test]
+    fn reaps_exits_times_out_and_stops_by_owner() {
+        let (exits, _stdin, _stdout) = spawn(&launch("registry-a", "exit 3")).unwrap();
+        let (hangs, _stdin2, _stdout2) = spawn(&launch("registry-a", "sleep 30")).unwrap();
+        let (kept, _stdin3, _stdout3) = spawn(&launch("registry-b", "sleep 30")).unwrap();
+
+        begin_request(hangs, Some(Duration::from_millis(100)));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while (exit_reason(exits).is_none() || exit_reason(hangs).is_none()) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(matches!(exit_reason(exits), Some(ExitReason::Exited(s)) if s.contains('3')));
+        assert_eq!(exit_reason(hangs), Some(ExitReason::TimedOut(Duration::from_millis(100))));
+        assert_eq!(exit_reason(kept), None);
+
+        assert_eq!(stop_owned_by("registry-a"), 0);
+        assert_eq!(exit_reason(exits), Some(ExitReason::Stopped));
+        assert_eq!(exit_reason(kept), None);
+        assert_eq!(stop_owned_by("registry-b"), 1);
+        assert_eq!(exit_reason(kept), Some(ExitReason::Stopped));
+    }
+}
@@ -1,41 +1,130 @@
+use crate::config::InjectionGuardMode;
 use crate::llm::mcps::client::McpClient;
-use crate::llm::tools::tool_trait::{Tool, ToolKind, ToolOperation, ToolOutput};
+use crate::llm::tools::arg_summary;
+use crate::llm::tools::tool_trait::{Tool, ToolKind, ToolOperation, ToolResult};
+use crate::llm::utils::prompt_guard;
 use anyhow::Result;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 pub struct McpTool {
     client: Arc<McpClient>,
     definition: Value,
     original_name: String,
+    server_name: String,
+    /// Arguments whose values scope confirmations, see `key_path`
+    key_args: Option<Vec<String>>,
+    annotations: Annotations,
+    prompt_injection: InjectionGuardMode,
 }
 
-impl McpTool {
-    pub fn new(client: Arc<McpClient>, mut definition: Value, server_name: &str) -> Self {
-        let original_name = definition
-            .get("name")
-            .and_then(|n| n.as_str())
-            .unwrap_or("unknown_mcp_tool")
-            .to_string();
+/// What the tool's MCP `annotations` say about its effects
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Annotations {
+    kind: ToolKind,
+    operation: ToolOperation,
+    destructive: bool,
+}
 
-        let exposed_name = if original_name.starts_with(server_name) {
-            original_name.clone()
-        } else {
-            format!("{}_{}", server_name, original_name)
+impl Annotations {
+    /// `readOnlyHint` makes the tool a read when `trust_read_only` (and it is
+    /// not also marked destructive); `destructiveHint` makes every call ask.
+    /// Only hints that are present count: the protocol's defaults would make
+    /// every unannotated tool destructive.
+    fn from_definition(definition: &Value, trust_read_only: bool) -> Self {
+        let hint = |name: &str| {
+            definition
+                .get("annotations")
+                .and_then(|a| a.get(name))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false)
         };
-        
+        let destructive = hint("destructiveHint");
+        if trust_read_only && hint("readOnlyHint") && !destructive {
+            return Self {
+                kind: ToolKind::Read,
+                operation: ToolOperation::Explored,
+                destructive,
+            };
+        }
+        Self {
+            kind: ToolKind::Other,
+            operation: ToolOperation::Other,
+            destructive,
+        }
+    }
+}
+
+impl McpTool {
+    pub fn new(
+        client: Arc<McpClient>,
+        mut definition: Value,
+        server_name: &str,
+        exposed_name: &str,
+        key_paths: &HashMap<String, String>,
+        trust_read_only: bool,
+        prompt_injection: InjectionGuardMode,
+    ) -> Self {
+        let original_name = original_name(&definition).to_string();
+
         log::info!("MCP Tool Loaded: server={}, original={}, exposed={}", server_name, original_name, exposed_name);
 
+        let key_args = key_arguments(&definition, &original_name, key_paths);
+        let annotations = Annotations::from_definition(&definition, trust_read_only);
         definition["name"] = json!(exposed_name);
 
         Self {
             client,
             definition,
             original_name,
+            server_name: server_name.to_string(),
+            key_args,
+            annotations,
+            prompt_injection,
         }
     }
 }
 
+/// The tool's name on its server
+pub fn original_name(definition: &Value) -> &str {
+    definition.get("name").and_then(|n| n.as_str()).unwrap_or("unknown_mcp_tool")
+}
+
+/// The arguments that scope confirmations of a tool: from the server's
+/// `key_paths` config (the tool's entry, else "*"), else from the tool's
+/// definition, as `annotations.keyPath` or properties marked `"x-key-path": true`.
+/// Entries name one argument, a dotted path into one, or several separated by
+/// commas; "*" scopes by the tool alone.
+fn key_arguments(definition: &Value, original_name: &str, configured: &HashMap<String, String>) -> Option<Vec<String>> {
+    let hint = configured
+        .get(original_name)
+        .or_else(|| configured.get("*"))
+        .cloned()
+        .or_else(|| definition.pointer("/annotations/keyPath").and_then(|v| v.as_str()).map(str::to_string))
+        .or_else(|| {
+            let properties = definition.pointer("/inputSchema/properties")?.as_object()?;
+            let marked: Vec<&str> = properties
+                .iter()
+                .filter(|(_, p)| p.get("x-key-path").and_then(|v| v.as_bool()) == Some(true))
+                .map(|(name, _)| name.as_str())
+                .collect();
+            (!marked.is_empty()).then(|| marked.join(","))
+        })?;
+    let args: Vec<String> = hint.split(',').map(|a| a.trim().to_string()).filter(|a| !a.is_empty()).collect();
+    (!args.is_empty()).then_some(args)
+}
+
+/// The value at `path`, a dotted path into `args`, as key text
+fn key_value(args: &Value, path: &str) -> Option<String> {
+    let value = path.split('.').try_fold(args, |value, part| value.get(part))?;
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
 impl Tool for McpTool {
     fn name(&self) -> &str {
         self.definition
@@ -52,11 +141,11 @@ impl Tool for McpTool {
     }
 
     fn kind(&self) -> ToolKind {
-        ToolKind::Other
+        self.annotations.kind
     }
 
     fn operation(&self) -> ToolOperation {
-        ToolOperation::Other
+        self.annotations.operation
     }
 
     fn to_tool_definition(&self) -> Value {
@@ -90,17 +179,19 @@ impl Tool for McpTool {
             }
         }
 
-        if is_error {
-            Ok(serde_json::to_string(&ToolOutput::error(
-                format!("mcp call {}", self.name()),
-                output,
-            ))?)
+        // Server output is untrusted: screen it before the model sees it
+        let screened = prompt_guard::screen(self.name(), output, None, self.prompt_injection);
+        let data = if screened.findings.is_empty() {
+            Value::Null
         } else {
-            Ok(serde_json::to_string(&ToolOutput::success(
-                format!("mcp call {}", self.name()),
-                output,
-            ))?)
-        }
+            json!({ "injection": screened.findings })
+        };
+        let result = if is_error {
+            ToolResult::err(self.name(), self.kind(), self.operation(), screened.content, data)
+        } else {
+            ToolResult::ok(self.name(), self.kind(), self.operation(), screened.content, data)
+        };
+        Ok(serde_json::to_string(&result)?)
     }
 
     fn clone_box(&self) -> Box<dyn Tool> {
@@ -108,6 +199,89 @@ impl Tool for McpTool {
             client: self.client.clone(),
             definition: self.definition.clone(),
             original_name: self.original_name.clone(),
+            server_name: self.server_name.clone(),
+            key_args: self.key_args.clone(),
+            annotations: self.annotations,
+            prompt_injection: self.prompt_injection,
         })
     }
+
+    fn is_destructive(&self, _arguments: &str) -> bool {
+        self.annotations.destructive
+    }
+
+    /// The server's own name for the tool, with the server
+    fn display_name(&self) -> String {
+        format!("{} ({})", self.original_name, self.server_name)
+    }
+
+    /// "name=value, ..." over the top-level arguments; the schema says nothing more useful
+    fn summarize_args(&self, arguments: &str) -> Option<String> {
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        let parts: Vec<String> = args
+            .as_object()?
+            .iter()
+            .filter(|(name, _)| name.as_str() != "confirmed")
+            .map(|(name, value)| match value {
+                Value::String(s) => format!("{}={}", name, arg_summary::first_line(s)),
+                other => format!("{}={}", name, other),
+            })
+            .collect();
+        Some(arg_summary::clip(&parts.join(", ")))
+    }
+
+    /// The configured or declared key arguments' values joined by "/"; None,
+    /// falling back to the generic keys, without a hint or if one is missing
+    fn key_path(&self, arguments: &str) -> Option<String> {
+        let key_args = self.key_args.as_ref()?;
+        if key_args.iter().any(|a| a == "*") {
+            return Some("*".to_string());
+        }
+        let args: Value = serde_json::from_str(arguments).ok()?;
+        let values = key_args.iter().map(|a| key_value(&args, a)).collect::<Option<Vec<String>>>()?;
+        Some(values.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_arguments_come_from_config_then_schema() {
+        let definition = json!({
+            "name": "create_issue",
+            "inputSchema": { "properties": { "repo": { "type": "string", "x-key-path": true }, "title": {} } }
+        });
+        let none = HashMap::new();
+        assert_eq!(key_arguments(&definition, "create_issue", &none), Some(vec!["repo".to_string()]));
+
+        let configured = HashMap::from([("*".to_string(), "target.owner, target.name".to_string())]);
+        let key_args = key_arguments(&definition, "create_issue", &configured).unwrap();
+        let args = json!({ "target": { "owner": "octo", "name": "hello" }, "title": "Bug" });
+        let values: Option<Vec<String>> = key_args.iter().map(|a| key_value(&args, a)).collect();
+        assert_eq!(values.map(|v| v.join("/")).as_deref(), Some("octo/hello"));
+        assert_eq!(key_value(&args, "target.missing"), None);
+
+        let annotated = json!({ "name": "query", "annotations": { "keyPath": "database" } });
+        assert_eq!(key_arguments(&annotated, "query", &none), Some(vec!["database".to_string()]));
+        let per_tool = HashMap::from([("query".to_string(), "*".to_string())]);
+        assert_eq!(key_arguments(&annotated, "query", &per_tool), Some(vec!["*".to_string()]));
+        assert_eq!(key_arguments(&json!({ "name": "ping" }), "ping", &none), None);
+    }
+
+    #[test]
+    fn annotations_set_kind_and_destructiveness() {
+        let read_only = json!({ "name": "list", "annotations": { "readOnlyHint": true } });
+        let read = Annotations::from_definition(&read_only, true);
+        assert_eq!((read.kind, read.operation, read.destructive), (ToolKind::Read, ToolOperation::Explored, false));
+        assert_eq!(Annotations::from_definition(&read_only, false).kind, ToolKind::Other);
+
+        let destructive = json!({ "annotations": { "readOnlyHint": true, "destructiveHint": true } });
+        let drop = Annotations::from_definition(&destructive, true);
+        assert_eq!((drop.kind, drop.destructive), (ToolKind::Other, true));
+
+        let plain = Annotations::from_definition(&json!({ "name": "ping" }), true);
+        assert_eq!((plain.kind, plain.destructive), (ToolKind::Other, false));
+    }
 }
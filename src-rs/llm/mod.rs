@@ -1,4 +1,6 @@
 pub mod agents;
+pub mod auxiliary;
+pub mod backend;
 pub mod mcps;
 pub mod models;
 pub mod prompts;
@@ -5,7 +5,8 @@ use std::collections::HashMap;
 use std::pin::Pin;
 use tokio_stream::Stream;
 
-use crate::llm::models::provider_base::{ Message, ProviderClient };
+use crate::config::AuthStyle;
+use crate::llm::models::provider_base::{ Message, ProviderClient, RequestAuth, ToolChoice, ToolUseOptions };
 
 fn extract_sse_frame_from_buffer(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
     let mut delimiter_len = 0usize;
@@ -16,9 +17,8 @@ fn extract_sse_frame_from_buffer(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
         buffer
             .windows(2)
             .position(|w| w == b"\n\n")
-            .map(|pos| {
+            .inspect(|_| {
                 delimiter_len = 2;
-                pos
             })
     })?;
 
@@ -252,6 +252,16 @@ pub struct ClaudeClient {
     pub model_name: String,
     #[serde(rename = "system_prompt")]
     pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub auth: RequestAuth,
+    /// Control tokens removed from the reply text
+    #[serde(default)]
+    pub strip_tokens: Vec<String>,
+    /// Sequences that end the reply
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
+    #[serde(skip)]
+    pub tool_use: ToolUseOptions,
 }
 
 impl ClaudeClient {
@@ -261,6 +271,10 @@ impl ClaudeClient {
             api_key,
             model_name,
             system_prompt: None,
+            auth: RequestAuth::default(),
+            strip_tokens: Vec::new(),
+            stop_sequences: Vec::new(),
+            tool_use: ToolUseOptions::default(),
         }
     }
 
@@ -268,6 +282,44 @@ impl ClaudeClient {
         self.system_prompt = prompt;
         self
     }
+
+    pub fn with_auth(mut self, auth: RequestAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_strip_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.strip_tokens = tokens;
+        self
+    }
+
+    pub fn with_stop_sequences(mut self, sequences: Vec<String>) -> Self {
+        self.stop_sequences = sequences;
+        self
+    }
+
+    pub fn with_tool_use(mut self, tool_use: ToolUseOptions) -> Self {
+        self.tool_use = tool_use;
+        self
+    }
+
+    /// `tool_choice` for a request. A forced call applies to the reply to the
+    /// user only: once tool results come back the model may answer, or the
+    /// turn would never end.
+    fn tool_choice(&self, answering_tool_results: bool) -> Value {
+        let choice = match &self.tool_use.choice {
+            forced if forced.forces_call() && answering_tool_results => &ToolChoice::Auto,
+            choice => choice,
+        };
+        let mut value = match choice {
+            ToolChoice::Tool(name) => json!({ "type": "tool", "name": name }),
+            other => json!({ "type": other.mode() }),
+        };
+        if self.tool_use.disable_parallel_tool_use && *choice != ToolChoice::None {
+            value["disable_parallel_tool_use"] = json!(true);
+        }
+        value
+    }
 }
 
 impl ProviderClient for ClaudeClient {
@@ -278,6 +330,9 @@ impl ProviderClient for ClaudeClient {
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Value>> + Send>>> {
         let url = format!("{}/v1/messages", self.base_url.trim_end_matches('/'));
 
+        let answering_tool_results = messages
+            .last()
+            .is_some_and(|m| tool_result_block_from_message_content(&m.content).is_some());
         let mut anthropic_messages = Vec::new();
         let mut system_prompt = self.system_prompt.clone();
 
@@ -403,12 +458,15 @@ impl ProviderClient for ClaudeClient {
         if let Some(sys) = system_prompt {
             request_body["system"] = json!(sys);
         }
+        if !self.stop_sequences.is_empty() {
+            request_body["stop_sequences"] = json!(self.stop_sequences);
+        }
 
         if let Some(tools) = tools {
             let converted: Vec<Value> = tools.iter().filter_map(openai_tool_to_anthropic).collect();
             if !converted.is_empty() {
                 request_body["tools"] = Value::Array(converted);
-                request_body["tool_choice"] = json!({ "type": "auto" });
+                request_body["tool_choice"] = self.tool_choice(answering_tool_results);
             }
         }
 
@@ -424,9 +482,8 @@ impl ProviderClient for ClaudeClient {
             .build()
             .context("Failed to build HTTP client")?;
 
-        let response = client
-            .post(&url)
-            .header("x-api-key", &self.api_key)
+        let response = self.auth
+            .apply(client.post(&url), &self.api_key, AuthStyle::XApiKey)
             .header("anthropic-version", "2023-06-01")
             .header("accept", "text/event-stream")
             .header("content-type", "application/json")
@@ -488,7 +545,8 @@ impl ProviderClient for ClaudeClient {
                         }
                         Ok(Some(Err(e))) => {
                             log::error!("Stream read error on chunk #{}: {}", chunk_num + 1, e);
-                            yield Err(anyhow::anyhow!("Stream read error: {}", e));
+                            // Kept as the source, so a dropped connection is told from other failures
+                            yield Err(anyhow::Error::new(e).context("Stream read error"));
                             break;
                         }
                         Ok(None) => {
@@ -537,7 +595,7 @@ impl ProviderClient for ClaudeClient {
                 total_bytes += data.len();
                 let data_trimmed = data.trim();
                 
-                if chunk_count <= 5 || chunk_count % 100 == 0 {
+                if chunk_count <= 5 || chunk_count.is_multiple_of(100) {
                     log::debug!("Claude SSE chunk #{}: {} bytes (total: {} bytes)", chunk_count, data.len(), total_bytes);
                 }
                 if data_trimmed == "[DONE]" {
@@ -856,6 +914,9 @@ impl ProviderClient for ClaudeClient {
     async fn chat(&self, messages: Vec<Message>, tools: Option<Vec<Value>>) -> Result<Value> {
         let url = format!("{}/v1/messages", self.base_url.trim_end_matches('/'));
 
+        let answering_tool_results = messages
+            .last()
+            .is_some_and(|m| tool_result_block_from_message_content(&m.content).is_some());
         let mut anthropic_messages = Vec::new();
         let mut system_prompt = self.system_prompt.clone();
 
@@ -980,12 +1041,15 @@ impl ProviderClient for ClaudeClient {
         if let Some(sys) = system_prompt {
             request_body["system"] = json!(sys);
         }
+        if !self.stop_sequences.is_empty() {
+            request_body["stop_sequences"] = json!(self.stop_sequences);
+        }
 
         if let Some(tools) = tools {
             let converted: Vec<Value> = tools.iter().filter_map(openai_tool_to_anthropic).collect();
             if !converted.is_empty() {
                 request_body["tools"] = Value::Array(converted);
-                request_body["tool_choice"] = json!({ "type": "auto" });
+                request_body["tool_choice"] = self.tool_choice(answering_tool_results);
             }
         }
 
@@ -997,9 +1061,8 @@ impl ProviderClient for ClaudeClient {
             .build()
             .context("Failed to build HTTP client")?;
 
-        let response = client
-            .post(&url)
-            .header("x-api-key", &self.api_key)
+        let response = self.auth
+            .apply(client.post(&url), &self.api_key, AuthStyle::XApiKey)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
             .json(&request_body)
@@ -1044,6 +1107,9 @@ mod tests {
         openai_tool_to_anthropic,
         sse_data_from_frame,
         tool_result_block_from_message_content,
+        ClaudeClient,
+        ToolChoice,
+        ToolUseOptions,
     };
     use serde_json::json;
 
@@ -1109,6 +1175,26 @@ mod tests {
         assert!(anthropic_tool.get("input_schema").is_some());
     }
 
+    #[test]
+    fn tool_choice_forces_only_the_reply_to_the_user() {
+        let client = ClaudeClient::new(String::new(), String::new(), String::new()).with_tool_use(ToolUseOptions {
+            choice: ToolChoice::Tool("grep".to_string()),
+            disable_parallel_tool_use: true,
+        });
+        assert_eq!(
+            client.tool_choice(false),
+            json!({ "type": "tool", "name": "grep", "disable_parallel_tool_use": true })
+        );
+        assert_eq!(client.tool_choice(true), json!({ "type": "auto", "disable_parallel_tool_use": true }));
+        let client = client.with_tool_use(ToolUseOptions {
+            choice: ToolChoice::None,
+            disable_parallel_tool_use: true,
+        });
+        assert_eq!(client.tool_choice(false), json!({ "type": "none" }));
+        assert!(ToolChoice::parse("tool", None).is_err());
+        assert_eq!(ToolChoice::parse("any", None).unwrap(), ToolChoice::Any);
+    }
+
     #[test]
     fn tool_result_block_from_message_content_parses_prefix() {
         let payload =
@@ -1116,7 +1202,7 @@ mod tests {
             "tool_use_id": "toolu_123",
             "result": { "ok": true }
         });
-        let s = format!("ToolResultJSON:{}", payload.to_string());
+        let s = format!("ToolResultJSON:{}", payload);
         let (id, result) = tool_result_block_from_message_content(&s).expect("parsed");
         assert_eq!(id, "toolu_123");
         assert_eq!(
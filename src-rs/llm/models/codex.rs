@@ -5,7 +5,8 @@ use std::path::PathBuf;
 use std::pin::Pin;
 use tokio_stream::Stream;
 
-use crate::llm::models::provider_base::{Message, ProviderClient};
+use crate::config::AuthStyle;
+use crate::llm::models::provider_base::{Message, ProviderClient, RequestAuth};
 
 #[derive(Debug)]
 enum CodexEvent {
@@ -150,6 +151,11 @@ pub struct CodexClient {
     pub model_name: String,
     #[serde(rename = "system_prompt")]
     pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub auth: RequestAuth,
+    /// Control tokens removed from the reply text
+    #[serde(default)]
+    pub strip_tokens: Vec<String>,
 }
 
 impl CodexClient {
@@ -159,6 +165,8 @@ impl CodexClient {
             api_key,
             model_name,
             system_prompt: None,
+            auth: RequestAuth::default(),
+            strip_tokens: Vec::new(),
         }
     }
 
@@ -167,6 +175,16 @@ impl CodexClient {
         self
     }
 
+    pub fn with_auth(mut self, auth: RequestAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_strip_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.strip_tokens = tokens;
+        self
+    }
+
     fn messages_to_task(&self, messages: Vec<Message>) -> String {
         let mut out = String::new();
         if let Some(sys) = &self.system_prompt {
@@ -203,9 +221,8 @@ impl ProviderClient for CodexClient {
         let mut last_err: Option<anyhow::Error> = None;
         let mut response_opt: Option<reqwest::Response> = None;
         for url in &url_candidates {
-            let resp = client
-                .post(url)
-                .header("authorization", format!("Bearer {}", self.api_key))
+            let resp = self.auth
+                .apply(client.post(url), &self.api_key, AuthStyle::Bearer)
                 .header("accept", "text/event-stream")
                 .header("content-type", "application/json")
                 .json(&json!({
@@ -5,7 +5,8 @@ use std::pin::Pin;
 use std::time::Duration;
 use tokio_stream::Stream;
 
-use crate::llm::models::provider_base::{ Message, ProviderClient };
+use crate::config::AuthStyle;
+use crate::llm::models::provider_base::{ Citation, Message, ProviderClient, RequestAuth, StopDetails };
 
 fn extract_sse_frame_from_buffer(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
     let mut delimiter_len = 0usize;
@@ -16,9 +17,8 @@ fn extract_sse_frame_from_buffer(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
         buffer
             .windows(2)
             .position(|w| w == b"\n\n")
-            .map(|pos| {
+            .inspect(|_| {
                 delimiter_len = 2;
-                pos
             })
     })?;
 
@@ -47,39 +47,133 @@ fn sse_data_from_frame(frame: &str) -> Option<String> {
     Some(data_parts.join("\n"))
 }
 
+/// Finish reasons meaning Gemini blocked the prompt or response
+const GEMINI_BLOCK_REASONS: &[&str] = &[
+    "SAFETY",
+    "RECITATION",
+    "BLOCKLIST",
+    "PROHIBITED_CONTENT",
+    "SPII",
+    "IMAGE_SAFETY",
+    "LANGUAGE",
+    "OTHER",
+];
+
+/// Why Gemini blocked the prompt or cut the response short, if it did
+fn gemini_stop_details(event: &Value) -> Option<StopDetails> {
+    if let Some(reason) = event.pointer("/promptFeedback/blockReason").and_then(|v| v.as_str()) {
+        return Some(StopDetails {
+            reason: reason.to_string(),
+            prompt_blocked: true,
+            categories: blocked_categories(event.pointer("/promptFeedback/safetyRatings")),
+        });
+    }
+    let candidate = event.pointer("/candidates/0")?;
+    let reason = candidate.get("finishReason")?.as_str()?;
+    if !GEMINI_BLOCK_REASONS.contains(&reason) {
+        return None;
+    }
+    Some(StopDetails {
+        reason: reason.to_string(),
+        prompt_blocked: false,
+        categories: blocked_categories(candidate.get("safetyRatings")),
+    })
+}
+
+/// Categories of the safety ratings that caused a block; without explicit
+/// `blocked` flags, the ones rated MEDIUM or HIGH
+fn blocked_categories(ratings: Option<&Value>) -> Vec<String> {
+    let ratings = ratings.and_then(|r| r.as_array()).map(Vec::as_slice).unwrap_or_default();
+    let category = |r: &Value| {
+        r.get("category")
+            .and_then(|c| c.as_str())
+            .map(|c| c.trim_start_matches("HARM_CATEGORY_").to_string())
+    };
+    let blocked: Vec<String> = ratings
+        .iter()
+        .filter(|r| r.get("blocked").and_then(|b| b.as_bool()) == Some(true))
+        .filter_map(category)
+        .collect();
+    if !blocked.is_empty() {
+        return blocked;
+    }
+    ratings
+        .iter()
+        .filter(|r| matches!(r.get("probability").and_then(|p| p.as_str()), Some("MEDIUM" | "HIGH")))
+        .filter_map(category)
+        .collect()
+}
+
+/// Citation sources of the first candidate (`citationSources`, or `citations` on Vertex AI)
+fn gemini_citations(event: &Value) -> Vec<Citation> {
+    let sources = event
+        .pointer("/candidates/0/citationMetadata/citationSources")
+        .or_else(|| event.pointer("/candidates/0/citationMetadata/citations"))
+        .and_then(|v| v.as_array());
+    let text = |c: &Value, key: &str| c.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let index = |c: &Value, key: &str| c.get(key).and_then(|v| v.as_u64()).map(|i| i as u32);
+    sources
+        .map(|sources| {
+            sources
+                .iter()
+                .map(|c| Citation {
+                    uri: text(c, "uri"),
+                    title: text(c, "title"),
+                    license: text(c, "license").filter(|l| !l.is_empty()),
+                    start_index: index(c, "startIndex"),
+                    end_index: index(c, "endIndex"),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn stream_value_from_gemini_event(event: &Value) -> Option<Value> {
     if event.get("choices").is_some() {
         return Some(event.clone());
     }
 
-    let finish_reason = event
-        .get("finishReason")
-        .and_then(|v| v.as_str())
-        .or_else(|| event.get("finish_reason").and_then(|v| v.as_str()));
-    if finish_reason.is_some_and(|r| r.eq_ignore_ascii_case("stop")) {
-        return Some(
-            json!({
-            "choices": [{
-                "delta": { "content": "" },
-                "finish_reason": "stop"
-            }]
-        })
-        );
-    }
+    let stop_details = gemini_stop_details(event);
+    let finish_reason = if stop_details.is_some() {
+        Some("content_filter")
+    } else {
+        event
+            .pointer("/candidates/0/finishReason")
+            .or_else(|| event.get("finishReason"))
+            .or_else(|| event.get("finish_reason"))
+            .and_then(|v| v.as_str())
+            .and_then(|r| match r.to_ascii_uppercase().as_str() {
+                "STOP" => Some("stop"),
+                "MAX_TOKENS" => Some("length"),
+                _ => None,
+            })
+    };
+    let citations = gemini_citations(event);
 
     let text = event
         .pointer("/candidates/0/content/parts/0/text")
         .and_then(|v| v.as_str())
         .unwrap_or_default();
-    if text.is_empty() {
+    if text.is_empty() && finish_reason.is_none() && citations.is_empty() {
         return None;
     }
 
-    Some(json!({
-        "choices": [{
-            "delta": { "content": text }
-        }]
-    }))
+    let mut choice = json!({ "delta": { "content": text } });
+    if let Some(reason) = finish_reason {
+        choice["finish_reason"] = json!(reason);
+    }
+    if let Some(details) = stop_details {
+        choice["stop_details"] = serde_json::to_value(details).ok()?;
+    }
+    if !citations.is_empty() {
+        choice["citations"] = serde_json::to_value(citations).ok()?;
+    }
+    Some(json!({ "choices": [choice] }))
+}
+
+/// Whether a converted chunk ends the response
+fn is_final_chunk(out: &Value) -> bool {
+    out.pointer("/choices/0/finish_reason").is_some_and(|r| !r.is_null())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,6 +186,14 @@ pub struct GeminiClient {
     pub model_name: String,
     #[serde(rename = "system_prompt")]
     pub system_prompt: Option<String>,
+    #[serde(default)]
+    pub auth: RequestAuth,
+    /// Control tokens removed from the reply text
+    #[serde(default)]
+    pub strip_tokens: Vec<String>,
+    /// Sequences that end the reply
+    #[serde(default)]
+    pub stop_sequences: Vec<String>,
 }
 
 impl GeminiClient {
@@ -101,6 +203,9 @@ impl GeminiClient {
             api_key,
             model_name,
             system_prompt: None,
+            auth: RequestAuth::default(),
+            strip_tokens: Vec::new(),
+            stop_sequences: Vec::new(),
         }
     }
 
@@ -108,6 +213,21 @@ impl GeminiClient {
         self.system_prompt = prompt;
         self
     }
+
+    pub fn with_auth(mut self, auth: RequestAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_strip_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.strip_tokens = tokens;
+        self
+    }
+
+    pub fn with_stop_sequences(mut self, sequences: Vec<String>) -> Self {
+        self.stop_sequences = sequences;
+        self
+    }
 }
 
 impl ProviderClient for GeminiClient {
@@ -117,10 +237,9 @@ impl ProviderClient for GeminiClient {
         _tools: Option<Vec<Value>>
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Value>> + Send>>> {
         let url = format!(
-            "{}/models/{}:streamGenerateContent?key={}",
+            "{}/models/{}:streamGenerateContent",
             self.base_url.trim_end_matches('/'),
-            self.model_name,
-            self.api_key
+            self.model_name
         );
 
         let mut contents = Vec::new();
@@ -151,14 +270,17 @@ impl ProviderClient for GeminiClient {
         if let Some(sys) = system_instruction {
             request_body["systemInstruction"] = sys;
         }
+        if !self.stop_sequences.is_empty() {
+            request_body["generationConfig"] = json!({ "stopSequences": self.stop_sequences });
+        }
 
         let client = reqwest::Client
             ::builder()
             .timeout(Duration::from_secs(300))
             .build()
             .context("Failed to build HTTP client")?;
-        let response = client
-            .post(&url)
+        let response = self.auth
+            .apply(client.post(&url), &self.api_key, AuthStyle::Query)
             .header("accept", "text/event-stream")
             .header("Content-Type", "application/json")
             .json(&request_body)
@@ -218,10 +340,7 @@ impl ProviderClient for GeminiClient {
                             Value::Array(arr) => {
                                 for event in arr {
                                     if let Some(out) = stream_value_from_gemini_event(&event) {
-                                        let is_stop = out
-                                            .pointer("/choices/0/finish_reason")
-                                            .and_then(|v| v.as_str())
-                                            .is_some_and(|r| r == "stop");
+                                        let is_stop = is_final_chunk(&out);
                                         yield Ok(out);
                                         if is_stop {
                                             return;
@@ -231,10 +350,7 @@ impl ProviderClient for GeminiClient {
                             }
                             _ => {
                                 if let Some(out) = stream_value_from_gemini_event(&parsed) {
-                                    let is_stop = out
-                                        .pointer("/choices/0/finish_reason")
-                                        .and_then(|v| v.as_str())
-                                        .is_some_and(|r| r == "stop");
+                                    let is_stop = is_final_chunk(&out);
                                     yield Ok(out);
                                     if is_stop {
                                         return;
@@ -263,10 +379,7 @@ impl ProviderClient for GeminiClient {
                             Value::Array(arr) => {
                                 for event in arr {
                                     if let Some(out) = stream_value_from_gemini_event(&event) {
-                                        let is_stop = out
-                                            .pointer("/choices/0/finish_reason")
-                                            .and_then(|v| v.as_str())
-                                            .is_some_and(|r| r == "stop");
+                                        let is_stop = is_final_chunk(&out);
                                         yield Ok(out);
                                         if is_stop {
                                             return;
@@ -276,10 +389,7 @@ impl ProviderClient for GeminiClient {
                             }
                             _ => {
                                 if let Some(out) = stream_value_from_gemini_event(&parsed) {
-                                    let is_stop = out
-                                        .pointer("/choices/0/finish_reason")
-                                        .and_then(|v| v.as_str())
-                                        .is_some_and(|r| r == "stop");
+                                    let is_stop = is_final_chunk(&out);
                                     yield Ok(out);
                                     if is_stop {
                                         return;
@@ -319,10 +429,9 @@ impl ProviderClient for GeminiClient {
 
     async fn chat(&self, messages: Vec<Message>, _tools: Option<Vec<Value>>) -> Result<Value> {
         let url = format!(
-            "{}/models/{}:generateContent?key={}",
+            "{}/models/{}:generateContent",
             self.base_url.trim_end_matches('/'),
-            self.model_name,
-            self.api_key
+            self.model_name
         );
 
         let mut contents = Vec::new();
@@ -353,14 +462,17 @@ impl ProviderClient for GeminiClient {
         if let Some(sys) = system_instruction {
             request_body["systemInstruction"] = sys;
         }
+        if !self.stop_sequences.is_empty() {
+            request_body["generationConfig"] = json!({ "stopSequences": self.stop_sequences });
+        }
 
         let client = reqwest::Client
             ::builder()
             .timeout(Duration::from_secs(300))
             .build()
             .context("Failed to build HTTP client")?;
-        let response = client
-            .post(&url)
+        let response = self.auth
+            .apply(client.post(&url), &self.api_key, AuthStyle::Query)
             .header("Content-Type", "application/json")
             .json(&request_body)
             .send().await
@@ -386,16 +498,21 @@ impl ProviderClient for GeminiClient {
             .and_then(|t| t.as_str())
             .unwrap_or_default();
 
-        Ok(
-            json!({
-            "choices": [{
-                "message": {
-                    "role": "assistant",
-                    "content": content
+        let mut choice = json!({
+            "message": {
+                "role": "assistant",
+                "content": content
+            }
+        });
+        if let Some(out) = stream_value_from_gemini_event(&json) {
+            for key in ["finish_reason", "stop_details", "citations"] {
+                if let Some(value) = out.pointer(&format!("/choices/0/{}", key)) {
+                    choice[key] = value.clone();
                 }
-            }]
-        })
-        )
+            }
+        }
+
+        Ok(json!({ "choices": [choice] }))
     }
 }
 
@@ -403,8 +520,11 @@ impl ProviderClient for GeminiClient {
 mod tests {
     use super::{
         extract_sse_frame_from_buffer,
+        is_final_chunk,
         sse_data_from_frame,
         stream_value_from_gemini_event,
+        Citation,
+        StopDetails,
     };
     use serde_json::json;
 
@@ -443,6 +563,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn stream_value_from_gemini_event_surfaces_safety_blocks_and_citations() {
+        let event =
+            json!({
+            "candidates": [{
+                "content": { "parts": [{ "text": "Partial" }] },
+                "finishReason": "SAFETY",
+                "safetyRatings": [
+                    { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" },
+                    { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH" }
+                ],
+                "citationMetadata": {
+                    "citationSources": [{ "startIndex": 0, "endIndex": 7, "uri": "https://example.com/a", "license": "" }]
+                }
+            }]
+        });
+        let out = stream_value_from_gemini_event(&event).expect("out");
+        assert!(is_final_chunk(&out));
+        assert_eq!(out.pointer("/choices/0/delta/content").and_then(|v| v.as_str()), Some("Partial"));
+        assert_eq!(
+            out.pointer("/choices/0/finish_reason").and_then(|v| v.as_str()),
+            Some("content_filter")
+        );
+        let details: StopDetails = serde_json::from_value(out["choices"][0]["stop_details"].clone()).unwrap();
+        assert_eq!(details.categories, vec!["DANGEROUS_CONTENT".to_string()]);
+        assert!(details.describe().contains("safety filters"));
+        let citations: Vec<Citation> = serde_json::from_value(out["choices"][0]["citations"].clone()).unwrap();
+        assert_eq!(citations[0].uri.as_deref(), Some("https://example.com/a"));
+        assert_eq!(citations[0].license, None);
+
+        let blocked_prompt = json!({ "promptFeedback": { "blockReason": "PROHIBITED_CONTENT" } });
+        let out = stream_value_from_gemini_event(&blocked_prompt).expect("out");
+        assert_eq!(out.pointer("/choices/0/stop_details/prompt_blocked"), Some(&json!(true)));
+    }
+
     #[test]
     fn stream_value_from_gemini_event_handles_stop() {
         let event = json!({ "finishReason": "STOP" });
@@ -7,3 +7,9 @@ pub mod codex;
 
 pub mod gemini;
 pub mod openai;
+pub mod model_limits;
+pub mod prompt_limit;
+pub mod provider_error;
+pub mod stream_sanitizer;
+pub mod strict_schema;
+pub mod tool_prompt;
@@ -0,0 +1,108 @@
+//! Context windows of models and the tool-result budgets they allow.
+//!
+//! A tool result longer than its budget is kept out of the history as an
+//! artifact (see `compact_tool_result`). A fixed threshold suits large
+//! models but lets a single bash run fill most of a small model's context,
+//! so the budget scales with the model's window: a provider's
+//! `context_windows` entry, or else the window of the model family below.
+
+use std::collections::HashMap;
+
+use crate::llm::utils::artifacts::{ARTIFACT_EXCERPT_CHARS, ARTIFACT_THRESHOLD_CHARS};
+
+/// Rough characters per token of code and command output
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Share of the context window one tool result may take
+const RESULT_SHARE: usize = 16;
+
+/// Smallest budget, so short outputs are never cut
+const MIN_RESULT_CHARS: usize = 1_000;
+
+/// Window assumed for models not listed, which keeps the fixed threshold
+const DEFAULT_CONTEXT_TOKENS: u32 = 128_000;
+
+/// Context windows in tokens by model name prefix; the longest match wins
+const CONTEXT_WINDOWS: [(&str, u32); 17] = [
+    ("gpt-3.5-turbo", 16_385),
+    ("gpt-4", 8_192),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("gpt-5", 400_000),
+    ("o1", 200_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+    ("claude", 200_000),
+    ("gemini", 1_048_576),
+    ("deepseek", 128_000),
+    ("qwen", 32_768),
+    ("mistral", 32_768),
+    ("llama", 8_192),
+    ("llama3.1", 128_000),
+    ("phi", 4_096),
+];
+
+/// How much of a tool result stays in the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ToolResultBudget {
+    /// Longest result kept whole, in characters of its JSON
+    pub max_chars: usize,
+    /// Characters of a longer result kept as an excerpt
+    pub excerpt_chars: usize,
+}
+
+impl Default for ToolResultBudget {
+    fn default() -> Self {
+        Self {
+            max_chars: ARTIFACT_THRESHOLD_CHARS,
+            excerpt_chars: ARTIFACT_EXCERPT_CHARS,
+        }
+    }
+}
+
+/// Context window of `model` in tokens; `configured` is the provider's `context_windows`
+pub fn context_tokens(model: &str, configured: &HashMap<String, u32>) -> u32 {
+    if let Some(tokens) = configured.get(model) {
+        return *tokens;
+    }
+    // Gateways prefix models with their vendor, e.g. "openai/gpt-4o"
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(prefix, _)| name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(DEFAULT_CONTEXT_TOKENS, |(_, tokens)| *tokens)
+}
+
+/// Tool-result budget of a model with a window of `context_tokens`
+pub fn tool_result_budget(context_tokens: u32) -> ToolResultBudget {
+    let max_chars = (context_tokens as usize * CHARS_PER_TOKEN / RESULT_SHARE)
+        .clamp(MIN_RESULT_CHARS, ARTIFACT_THRESHOLD_CHARS);
+    ToolResultBudget {
+        max_chars,
+        excerpt_chars: ARTIFACT_EXCERPT_CHARS.min(max_chars / 4),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn budgets_follow_the_context_window() {
+        let none = HashMap::new();
+        assert_eq!(context_tokens("gpt-4o-mini", &none), 128_000);
+        assert_eq!(context_tokens("gpt-4-0613", &none), 8_192);
+        assert_eq!(context_tokens("openai/GPT-4.1-mini", &none), 1_047_576);
+        assert_eq!(context_tokens("llama3:8b", &none), 8_192);
+        assert_eq!(context_tokens("my-local-model", &none), DEFAULT_CONTEXT_TOKENS);
+        let configured = HashMap::from([("my-local-model".to_string(), 4_096)]);
+        assert_eq!(context_tokens("my-local-model", &configured), 4_096);
+
+        // Large models keep the fixed threshold; small ones get a share of their window
+        assert_eq!(tool_result_budget(200_000), ToolResultBudget::default());
+        assert_eq!(tool_result_budget(8_192), ToolResultBudget { max_chars: 2_048, excerpt_chars: 512 });
+        assert_eq!(tool_result_budget(1_000).max_chars, MIN_RESULT_CHARS);
+    }
+}
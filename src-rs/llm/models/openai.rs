@@ -1,9 +1,57 @@
 use anyhow::{Context, Result};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::pin::Pin;
+use std::sync::{Arc, LazyLock, Mutex};
 use tokio_stream::Stream;
 
-use crate::llm::models::provider_base::{Message, ProviderClient};
+use crate::config::{AuthStyle, ToolCallingMode};
+use crate::llm::models::provider_base::{Message, ProviderClient, RequestAuth, ToolUseOptions};
+use crate::llm::models::tool_prompt;
+
+/// Probe result in `auto` mode: whether the server returns native tool calls
+type ProbeCell = Arc<tokio::sync::OnceCell<bool>>;
+
+/// Probe results in `auto` mode by (base URL, model), shared by every client
+/// so a session or an auxiliary model does not probe the same server again
+static NATIVE_TOOLS: LazyLock<Mutex<HashMap<(String, String), ProbeCell>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Hosted APIs known to return native tool calls; `auto` mode does not probe them
+const NATIVE_TOOL_HOSTS: &[&str] = &[
+    "api.openai.com",
+    "openai.azure.com",
+    "deepseek.com",
+    "moonshot.cn",
+    "moonshot.ai",
+    "bigmodel.cn",
+    "z.ai",
+    "minimax.chat",
+    "minimaxi.com",
+    "minimax.io",
+    "aliyuncs.com",
+    "x.ai",
+    "siliconflow.cn",
+    "siliconflow.com",
+    "openrouter.ai",
+    "groq.com",
+    "mistral.ai",
+    "together.xyz",
+    "fireworks.ai",
+];
+
+/// Whether `api_base` is one of the hosted APIs in `NATIVE_TOOL_HOSTS`
+fn is_native_tool_host(api_base: &str) -> bool {
+    let Some(host) = url::Url::parse(api_base)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+    else {
+        return false;
+    };
+    NATIVE_TOOL_HOSTS
+        .iter()
+        .any(|known| host == *known || host.ends_with(&format!(".{}", known)))
+}
 
 fn extract_sse_frame_from_buffer(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
     let mut delimiter_len = 0usize;
@@ -11,9 +59,8 @@ fn extract_sse_frame_from_buffer(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
         delimiter_len = 4;
         Some(pos)
     } else {
-        buffer.windows(2).position(|w| w == b"\n\n").map(|pos| {
+        buffer.windows(2).position(|w| w == b"\n\n").inspect(|_| {
             delimiter_len = 2;
-            pos
         })
     }?;
 
@@ -78,6 +125,16 @@ pub struct OpenAiClient {
     pub api_key: String,
     pub model: String,
     pub system_prompt: Option<String>,
+    pub auth: RequestAuth,
+    pub tool_calling: ToolCallingMode,
+    /// Control tokens removed from the reply text
+    pub strip_tokens: Vec<String>,
+    /// Sequences that end the reply
+    pub stop_sequences: Vec<String>,
+    /// Whether the API takes tool definitions with `strict: true`
+    pub strict_tools: bool,
+    /// At most one tool call per response
+    pub disable_parallel_tool_calls: bool,
     http_client: reqwest::Client,
 }
 
@@ -88,6 +145,12 @@ impl OpenAiClient {
             api_key,
             model,
             system_prompt: None,
+            auth: RequestAuth::default(),
+            tool_calling: ToolCallingMode::default(),
+            strip_tokens: Vec::new(),
+            stop_sequences: Vec::new(),
+            strict_tools: false,
+            disable_parallel_tool_calls: false,
             http_client: reqwest::Client::new(),
         }
     }
@@ -97,34 +160,193 @@ impl OpenAiClient {
         self
     }
 
+    pub fn with_auth(mut self, auth: RequestAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn with_tool_calling(mut self, mode: ToolCallingMode) -> Self {
+        self.tool_calling = mode;
+        self
+    }
+
+    pub fn with_strip_tokens(mut self, tokens: Vec<String>) -> Self {
+        self.strip_tokens = tokens;
+        self
+    }
+
+    pub fn with_stop_sequences(mut self, sequences: Vec<String>) -> Self {
+        self.stop_sequences = sequences;
+        self
+    }
+
+    pub fn with_strict_tools(mut self, strict: bool) -> Self {
+        self.strict_tools = strict;
+        self
+    }
+
+    pub fn with_tool_use(mut self, tool_use: ToolUseOptions) -> Self {
+        self.disable_parallel_tool_calls = tool_use.disable_parallel_tool_use;
+        self
+    }
+
+    /// Whether to pass tools through the API; `auto` trusts known hosted APIs
+    /// and probes a custom base URL once per model
+    async fn use_native_tools(&self) -> bool {
+        match self.tool_calling {
+            ToolCallingMode::Native => true,
+            ToolCallingMode::Prompt => false,
+            ToolCallingMode::Auto if is_native_tool_host(&self.api_base) => true,
+            ToolCallingMode::Auto => {
+                let probe = NATIVE_TOOLS
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .entry((self.api_base.trim_end_matches('/').to_string(), self.model.clone()))
+                    .or_default()
+                    .clone();
+                match probe.get_or_try_init(|| self.probe_native_tools()).await {
+                    Ok(native) => *native,
+                    Err(e) => {
+                        log::warn!("Tool calling probe for {} failed: {:#}", self.model, e);
+                        true
+                    }
+                }
+            }
+        }
+    }
+
+    /// Ask for a tool call and check that the reply carries a native one
+    ///
+    /// Servers without tool support either reject the `tools` parameter (vLLM
+    /// without a tool parser) or ignore it and answer in text (some LM Studio
+    /// models). Errors that say nothing about tool support are returned so the
+    /// probe runs again on the next request.
+    async fn probe_native_tools(&self) -> Result<bool> {
+        let probe_tool = serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": "report_ready",
+                "description": "Reports that the assistant is ready.",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "ready": { "type": "boolean" } },
+                    "required": ["ready"]
+                }
+            }
+        });
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: "Call the report_ready tool with ready set to true. Do not reply with text.".to_string(),
+        }];
+        let request_body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": false,
+            "tools": [probe_tool],
+            "max_tokens": 64,
+        });
+
+        let response = send_first_successful_chat_completions_request(
+            &self.http_client,
+            &chat_completions_url_candidates(&self.api_base),
+            &self.auth,
+            &self.api_key,
+            &request_body,
+        )
+        .await?;
+
+        let status = response.status();
+        if status.is_server_error() || matches!(status.as_u16(), 401 | 403 | 408 | 429) {
+            anyhow::bail!("probe request failed with {}", status);
+        }
+        let native = if status.is_success() {
+            let json: Value = response.json().await.context("Failed to parse probe response")?;
+            json.pointer("/choices/0/message/tool_calls")
+                .and_then(|c| c.as_array())
+                .is_some_and(|c| !c.is_empty())
+        } else {
+            false
+        };
+        if !native {
+            log::info!(
+                "{} at {} has no native tool calling; describing tools in the prompt instead",
+                self.model,
+                self.api_base
+            );
+        }
+        Ok(native)
+    }
+
+    /// Messages and tools to send, with tools moved into the prompt when the
+    /// server has no native support; the flag tells whether they were moved
+    async fn prepare_tools(
+        &self,
+        messages: Vec<Message>,
+        tools: Option<Vec<Value>>,
+    ) -> (Vec<Message>, Option<Vec<Value>>, bool) {
+        match tools {
+            Some(tools) if !tools.is_empty() && !self.use_native_tools().await => {
+                (tool_prompt::prepare_messages(messages, &tools), None, true)
+            }
+            tools => (messages, tools, false),
+        }
+    }
+
     fn apply_system_prompt(&self, messages: Vec<Message>) -> Vec<Message> {
-        if let Some(prompt) = &self.system_prompt {
-            let has_system = messages.iter().any(|m| m.role == "system");
-            if !has_system {
-                let mut final_messages = Vec::with_capacity(messages.len() + 1);
-                final_messages.push(Message {
+        let Some(prompt) = &self.system_prompt else {
+            return messages;
+        };
+        let mut messages = messages;
+        // A leading system message (the per-turn context header) goes after the
+        // prompt in a single system message, as some servers accept only one
+        match messages.first_mut() {
+            Some(first) if first.role == "system" => {
+                first.content = format!("{}\n\n{}", prompt, first.content);
+            }
+            _ => messages.insert(
+                0,
+                Message {
                     role: "system".to_string(),
                     content: prompt.clone(),
-                });
-                final_messages.extend(messages);
-                return final_messages;
-            }
+                },
+            ),
         }
         messages
     }
 
+    /// The chat completions request; `tools` is None when they are in the prompt
+    fn request_body(&self, messages: Vec<Message>, stream: bool, tools: Option<Vec<Value>>) -> Value {
+        let mut request_body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream,
+        });
+        if let Some(tools) = tools {
+            request_body["tools"] = Value::Array(tools);
+            if self.disable_parallel_tool_calls {
+                request_body["parallel_tool_calls"] = Value::Bool(false);
+            }
+        }
+        if !self.stop_sequences.is_empty() {
+            request_body["stop"] = serde_json::json!(self.stop_sequences);
+        }
+        request_body
+    }
+
     pub async fn stream_chat(
         &self,
         messages: Vec<Message>,
         tools: Option<Vec<Value>>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Value>> + Send>>> {
         let messages = self.apply_system_prompt(messages);
-        let request_body = build_chat_completions_request_body(&self.model, messages, true, tools);
+        let (messages, tools, prompt_tools) = self.prepare_tools(messages, tools).await;
+        let request_body = self.request_body(messages, true, tools);
         let url_candidates = chat_completions_url_candidates(&self.api_base);
 
         let response = send_first_successful_chat_completions_request(
             &self.http_client,
             &url_candidates,
+            &self.auth,
             &self.api_key,
             &request_body,
         )
@@ -155,18 +377,23 @@ impl OpenAiClient {
             }
         });
 
+        if prompt_tools {
+            return Ok(tool_prompt::extract_tool_calls(stream));
+        }
         Ok(stream)
     }
 
     #[allow(dead_code)]
     pub async fn chat(&self, messages: Vec<Message>, tools: Option<Vec<Value>>) -> Result<Value> {
         let messages = self.apply_system_prompt(messages);
-        let request_body = build_chat_completions_request_body(&self.model, messages, false, tools);
+        let (messages, tools, prompt_tools) = self.prepare_tools(messages, tools).await;
+        let request_body = self.request_body(messages, false, tools);
         let url_candidates = chat_completions_url_candidates(&self.api_base);
 
         let response = send_first_successful_chat_completions_request(
             &self.http_client,
             &url_candidates,
+            &self.auth,
             &self.api_key,
             &request_body,
         )
@@ -178,11 +405,14 @@ impl OpenAiClient {
             anyhow::bail!("LLM API error ({}): {}", status, error_text);
         }
 
-        let json: Value = response
+        let mut json: Value = response
             .json()
             .await
             .context("Failed to parse response JSON")?;
 
+        if prompt_tools {
+            tool_prompt::extract_message_tool_calls(&mut json);
+        }
         Ok(json)
     }
 }
@@ -237,23 +467,6 @@ pub fn create_deepseek(
     OpenAiClient::new(base_url, api_key, model_name).with_system_prompt(system_prompt)
 }
 
-fn build_chat_completions_request_body(
-    model: &str,
-    messages: Vec<Message>,
-    stream: bool,
-    tools: Option<Vec<Value>>,
-) -> Value {
-    let mut request_body = serde_json::json!({
-        "model": model,
-        "messages": messages,
-        "stream": stream,
-    });
-    if let Some(tools) = tools {
-        request_body["tools"] = Value::Array(tools);
-    }
-    request_body
-}
-
 fn chat_completions_url_candidates(api_base: &str) -> Vec<String> {
     let base = api_base.trim_end_matches('/');
     let mut out = Vec::new();
@@ -265,15 +478,15 @@ fn chat_completions_url_candidates(api_base: &str) -> Vec<String> {
 async fn send_first_successful_chat_completions_request(
     http_client: &reqwest::Client,
     url_candidates: &[String],
+    auth: &RequestAuth,
     api_key: &str,
     request_body: &Value,
 ) -> Result<reqwest::Response> {
     let mut last_err: Option<anyhow::Error> = None;
 
     for url in url_candidates {
-        let response = http_client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", api_key))
+        let response = auth
+            .apply(http_client.post(url), api_key, AuthStyle::Bearer)
             .header("Content-Type", "application/json")
             .json(request_body)
             .send()
@@ -301,7 +514,54 @@ async fn send_first_successful_chat_completions_request(
 
 #[cfg(test)]
 mod tests {
-    use super::{extract_sse_frame_from_buffer, sse_data_from_frame};
+    use super::{
+        create_openai, extract_sse_frame_from_buffer, is_native_tool_host, sse_data_from_frame, Message,
+        ToolUseOptions,
+    };
+
+    #[test]
+    fn auto_mode_trusts_known_hosted_apis_only() {
+        assert!(is_native_tool_host("https://api.openai.com/v1"));
+        assert!(is_native_tool_host("https://dashscope.aliyuncs.com/compatible-mode/v1"));
+        assert!(is_native_tool_host("https://open.bigmodel.cn/api/paas/v4"));
+        assert!(!is_native_tool_host("http://localhost:1234/v1"));
+        assert!(!is_native_tool_host("https://llm.example.com/v1"));
+        assert!(!is_native_tool_host("https://notopenai.com/v1"));
+    }
+
+    #[test]
+    fn system_prompt_merges_with_leading_system_message() {
+        let client = create_openai(
+            "http://localhost:1234".to_string(),
+            "k".to_string(),
+            "m".to_string(),
+            Some("prompt".to_string()),
+        );
+        let message = |role: &str, content: &str| Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+
+        let plain = client.apply_system_prompt(vec![message("user", "hi")]);
+        assert_eq!((plain[0].role.as_str(), plain[0].content.as_str()), ("system", "prompt"));
+        assert_eq!(plain.len(), 2);
+
+        let with_header = client.apply_system_prompt(vec![message("system", "header"), message("user", "hi")]);
+        assert_eq!(with_header.len(), 2);
+        assert_eq!(with_header[0].content, "prompt\n\nheader");
+    }
+
+    #[test]
+    fn parallel_tool_calls_are_disabled_only_with_tools() {
+        let client = create_openai("http://localhost:1234".to_string(), "k".to_string(), "m".to_string(), None)
+            .with_tool_use(ToolUseOptions {
+                disable_parallel_tool_use: true,
+                ..Default::default()
+            });
+        let body = client.request_body(Vec::new(), true, Some(vec![serde_json::json!({ "type": "function" })]));
+        assert_eq!(body["parallel_tool_calls"], false);
+        assert!(client.request_body(Vec::new(), true, None).get("parallel_tool_calls").is_none());
+    }
 
     #[test]
     fn sse_data_from_frame_supports_data_without_space() {
@@ -0,0 +1,52 @@
+//! Cutting system prompts down to a provider's `max_system_prompt_chars`.
+//!
+//! Some backends reject a request whose system prompt is over their limit
+//! with a bare 400, which is easy to hit once skills and the context header
+//! are added. Instead the prompt keeps its beginning, where the instructions
+//! are, and ends with a notice so the model knows text is missing. The
+//! session prompt is cut when the client is created; the per-turn context
+//! header gets what is left of the limit.
+
+use std::borrow::Cow;
+
+/// `text` cut to at most `max_chars` characters, including the notice
+pub fn fit<'a>(text: &'a str, max_chars: usize, what: &str) -> Cow<'a, str> {
+    let len = text.chars().count();
+    if len <= max_chars {
+        return Cow::Borrowed(text);
+    }
+    log::warn!(
+        "The {} is {} characters, over the provider's limit of {}; truncating it",
+        what,
+        len,
+        max_chars
+    );
+    let notice = format!(
+        "\n\n[The {} was truncated from {} to {} characters to fit this provider's limit.]",
+        what, len, max_chars
+    );
+    let keep = max_chars.saturating_sub(notice.chars().count());
+    if keep == 0 {
+        return Cow::Owned(text.chars().take(max_chars).collect());
+    }
+    let mut out: String = text.chars().take(keep).collect();
+    out.push_str(&notice);
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_long_prompts_with_a_notice() {
+        assert_eq!(fit("short", 10, "system prompt"), "short");
+        let long = "é".repeat(500);
+        let cut = fit(&long, 200, "system prompt");
+        assert_eq!(cut.chars().count(), 200);
+        assert!(cut.starts_with("éé"));
+        assert!(cut.ends_with("truncated from 500 to 200 characters to fit this provider's limit.]"));
+        // No room for the notice
+        assert_eq!(fit(&long, 3, "context header"), "ééé");
+    }
+}
@@ -1,15 +1,119 @@
 use anyhow::Result;
 use serde::{ Deserialize, Serialize };
 use serde_json::Value;
+use std::collections::HashMap;
 use std::pin::Pin;
 use tokio_stream::Stream;
 
+use crate::config::{ AuthStyle, ProviderConfig };
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
 }
 
+/// Why a provider ended a response before the model finished, sent on a
+/// stream chunk's choice as `stop_details` (with `finish_reason: "content_filter"`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StopDetails {
+    /// Provider reason as sent, e.g. `SAFETY` or `RECITATION`
+    pub reason: String,
+    /// Whether the prompt was rejected before any response was generated
+    #[serde(default)]
+    pub prompt_blocked: bool,
+    /// Safety categories that triggered the block
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+impl StopDetails {
+    /// One-line explanation for the user
+    pub fn describe(&self) -> String {
+        let subject = if self.prompt_blocked { "The request was blocked" } else { "The response was stopped" };
+        let why = match self.reason.as_str() {
+            "SAFETY" | "IMAGE_SAFETY" => "by the provider's safety filters",
+            "RECITATION" => "because it recited copyrighted or training material too closely",
+            "BLOCKLIST" => "because it contained blocklisted terms",
+            "PROHIBITED_CONTENT" => "because it involved prohibited content",
+            "SPII" => "because it contained sensitive personal information",
+            _ => "by the provider",
+        };
+        let mut text = format!("{} {} ({})", subject, why, self.reason);
+        if !self.categories.is_empty() {
+            text.push_str(&format!(": {}", self.categories.join(", ")));
+        }
+        text
+    }
+}
+
+/// Source the provider reports part of the response was drawn from, sent on a
+/// stream chunk's choice as `citations`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Character range of the cited part of the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_index: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_index: Option<u32>,
+}
+
+/// Which tool, if any, the model must call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// The model decides
+    #[default]
+    Auto,
+    /// Some tool must be called
+    Any,
+    /// The named tool must be called
+    Tool(String),
+    /// No tool may be called
+    None,
+}
+
+impl ToolChoice {
+    /// From a mode name ("auto", "any", "tool" or "none") and, for "tool", the tool
+    pub fn parse(mode: &str, tool_name: Option<&str>) -> Result<Self> {
+        match (mode, tool_name.filter(|t| !t.is_empty())) {
+            ("auto", _) => Ok(ToolChoice::Auto),
+            ("any", _) => Ok(ToolChoice::Any),
+            ("tool", Some(name)) => Ok(ToolChoice::Tool(name.to_string())),
+            ("tool", None) => anyhow::bail!("Tool choice 'tool' needs a tool name"),
+            ("none", _) => Ok(ToolChoice::None),
+            (other, _) => anyhow::bail!("Unknown tool choice '{}': use auto, any, tool or none", other),
+        }
+    }
+
+    pub fn mode(&self) -> &'static str {
+        match self {
+            ToolChoice::Auto => "auto",
+            ToolChoice::Any => "any",
+            ToolChoice::Tool(_) => "tool",
+            ToolChoice::None => "none",
+        }
+    }
+
+    /// Whether the model is made to call a tool
+    pub fn forces_call(&self) -> bool {
+        matches!(self, ToolChoice::Any | ToolChoice::Tool(_))
+    }
+}
+
+/// How the model may call tools, set per session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ToolUseOptions {
+    pub choice: ToolChoice,
+    /// At most one tool call per response
+    pub disable_parallel_tool_use: bool,
+}
+
 pub trait ProviderClient: Send + Sync {
     async fn stream_chat(
         &self,
@@ -20,3 +124,80 @@ pub trait ProviderClient: Send + Sync {
     #[allow(dead_code)]
     async fn chat(&self, messages: Vec<Message>, tools: Option<Vec<Value>>) -> Result<Value>;
 }
+
+/// How a client authenticates, plus extra headers for every request
+///
+/// Unset fields fall back to the provider's native scheme, so gateways
+/// (LiteLLM, Portkey, Azure) only need to configure what differs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RequestAuth {
+    pub style: Option<AuthStyle>,
+    pub header: Option<String>,
+    #[serde(default)]
+    pub extra_headers: HashMap<String, String>,
+}
+
+impl RequestAuth {
+    pub fn from_config(config: &ProviderConfig) -> Self {
+        Self {
+            style: config.auth_style,
+            header: config.auth_header.clone(),
+            extra_headers: config.headers.clone(),
+        }
+    }
+
+    /// Configured style, or `native` if none was configured
+    pub fn style_or(&self, native: AuthStyle) -> AuthStyle {
+        self.style.unwrap_or(native)
+    }
+
+    /// Attach the API key and extra headers to a request
+    pub fn apply(
+        &self,
+        builder: reqwest::RequestBuilder,
+        api_key: &str,
+        native: AuthStyle
+    ) -> reqwest::RequestBuilder {
+        let mut builder = match self.style_or(native) {
+            AuthStyle::Bearer => builder.header("Authorization", format!("Bearer {}", api_key)),
+            AuthStyle::ApiKey => builder.header("api-key", api_key),
+            AuthStyle::XApiKey => builder.header("x-api-key", api_key),
+            AuthStyle::Header =>
+                builder.header(self.header.as_deref().unwrap_or("Authorization"), api_key),
+            AuthStyle::Query => builder.query(&[("key", api_key)]),
+            AuthStyle::None => builder,
+        };
+        for (name, value) in &self.extra_headers {
+            builder = builder.header(name.as_str(), value.as_str());
+        }
+        builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(auth: &RequestAuth, native: AuthStyle) -> reqwest::Request {
+        auth.apply(reqwest::Client::new().post("http://localhost/v1"), "k", native).build().unwrap()
+    }
+
+    #[test]
+    fn falls_back_to_native_style_and_adds_extra_headers() {
+        let mut auth = RequestAuth::default();
+        let req = build(&auth, AuthStyle::XApiKey);
+        assert_eq!(req.headers()["x-api-key"], "k");
+
+        let req = build(&auth, AuthStyle::Query);
+        assert_eq!(req.url().query(), Some("key=k"));
+
+        auth.style = Some(AuthStyle::Header);
+        auth.header = Some("x-gateway-key".to_string());
+        auth.extra_headers.insert("x-route".to_string(), "claude".to_string());
+        let req = build(&auth, AuthStyle::Bearer);
+        assert_eq!(req.headers()["x-gateway-key"], "k");
+        assert_eq!(req.headers()["x-route"], "claude");
+        assert!(req.headers().get("authorization").is_none());
+        assert!(req.url().query().is_none());
+    }
+}
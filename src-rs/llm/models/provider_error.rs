@@ -0,0 +1,149 @@
+//! Recognising provider errors the user has to act on.
+//!
+//! Providers report a used-up quota, an empty balance or a bad key each in
+//! their own JSON, which the clients pass on as "API error (status): body".
+//! `classify` maps those to a `ProviderErrorKind` and pulls the provider's
+//! own sentence out of the body, so the error event can say what happened
+//! and what to do instead of showing the raw response. Retrying does not
+//! help with any of them; rate limits and outages are left to the retry logic.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderErrorKind {
+    /// The key's usage quota or plan limit is used up
+    QuotaExceeded,
+    /// The account has no credit or needs a payment method
+    CreditExhausted,
+    /// The key is missing, wrong, revoked or lacks access
+    InvalidApiKey,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderError {
+    pub kind: ProviderErrorKind,
+    /// The provider's message from the response body, when it had one
+    pub detail: Option<String>,
+}
+
+/// Phrases by kind, matched against the lowercased error; checked in this order
+/// because a billing error may also mention the quota
+const PATTERNS: [(ProviderErrorKind, &[&str]); 3] = [
+    (
+        ProviderErrorKind::CreditExhausted,
+        &[
+            "credit balance is too low",
+            "insufficient balance",
+            "insufficient_balance",
+            "insufficient credits",
+            "out of credits",
+            "credits exhausted",
+            "payment required",
+            "billing_hard_limit_reached",
+            "billing_not_active",
+            "arrearage",
+        ],
+    ),
+    (
+        ProviderErrorKind::QuotaExceeded,
+        &[
+            "insufficient_quota",
+            "exceeded your current quota",
+            "quota exceeded",
+            "quota_exceeded",
+            "usage limit",
+        ],
+    ),
+    (
+        ProviderErrorKind::InvalidApiKey,
+        &[
+            "invalid_api_key",
+            "invalid api key",
+            "incorrect api key",
+            "invalid x-api-key",
+            "api key not valid",
+            "api_key_invalid",
+            "authentication_error",
+            "invalid authentication",
+        ],
+    ),
+];
+
+/// The kind of a failed request's error, if it is one the user has to fix
+pub fn classify(message: &str) -> Option<ProviderError> {
+    let lower = message.to_lowercase();
+    let kind = PATTERNS
+        .iter()
+        .find(|(_, phrases)| phrases.iter().any(|p| lower.contains(p)))
+        .map(|(kind, _)| *kind)
+        .or_else(|| match status_code(message)? {
+            401 => Some(ProviderErrorKind::InvalidApiKey),
+            402 => Some(ProviderErrorKind::CreditExhausted),
+            _ => None,
+        })?;
+    Some(ProviderError {
+        kind,
+        detail: provider_message(message),
+    })
+}
+
+/// Status of an "API error (429 Too Many Requests): ..." message
+fn status_code(message: &str) -> Option<u16> {
+    let start = message.find("error (")? + "error (".len();
+    message[start..].get(..3)?.parse().ok()
+}
+
+/// `error.message`, `message` or `error` of the JSON body in the message
+fn provider_message(message: &str) -> Option<String> {
+    // From the first brace, which also skips the array some providers wrap the body in
+    let body: Value = serde_json::from_str(&message[message.find('{')?..=message.rfind('}')?]).ok()?;
+    let found = [body.pointer("/error/message"), body.get("message"), body.get("error")]
+        .into_iter()
+        .flatten()
+        .find_map(Value::as_str)
+        .map(|m| m.trim().to_string())
+        .filter(|m| !m.is_empty());
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_quota_billing_and_key_errors() {
+        let openai = concat!(
+            "LLM API error (429 Too Many Requests): ",
+            r#"{"error":{"message":"You exceeded your current quota, please check your plan.","#,
+            r#""type":"insufficient_quota","code":"insufficient_quota"}}"#
+        );
+        assert_eq!(
+            classify(openai),
+            Some(ProviderError {
+                kind: ProviderErrorKind::QuotaExceeded,
+                detail: Some("You exceeded your current quota, please check your plan.".to_string()),
+            })
+        );
+        let anthropic = concat!(
+            r#"Anthropic API error (400 Bad Request): {"type":"error","error":{"type":"invalid_request_error","#,
+            r#""message":"Your credit balance is too low to access the Anthropic API."}}"#
+        );
+        assert_eq!(classify(anthropic).unwrap().kind, ProviderErrorKind::CreditExhausted);
+        let deepseek = r#"LLM API error (402 Payment Required): {"error":{"message":"Insufficient Balance"}}"#;
+        assert_eq!(classify(deepseek).unwrap().kind, ProviderErrorKind::CreditExhausted);
+        let gemini = concat!(
+            r#"Gemini API error (400 Bad Request): [{"error":{"code":400,"#,
+            r#""message":"API key not valid. Please pass a valid API key.","status":"INVALID_ARGUMENT"}}]"#
+        );
+        let gemini = classify(gemini).unwrap();
+        assert_eq!(gemini.kind, ProviderErrorKind::InvalidApiKey);
+        assert_eq!(gemini.detail.as_deref(), Some("API key not valid. Please pass a valid API key."));
+        // A bare 401 without a known phrase is still a key problem
+        assert_eq!(classify("LLM API error (401 Unauthorized): ").unwrap().kind, ProviderErrorKind::InvalidApiKey);
+
+        // Rate limits and outages are retried, not reported as these
+        assert_eq!(classify(r#"LLM API error (429 Too Many Requests): {"error":{"message":"Rate limit"}}"#), None);
+        assert_eq!(classify("LLM API error (503 Service Unavailable): overloaded"), None);
+        assert_eq!(classify("error sending request for url"), None);
+    }
+}
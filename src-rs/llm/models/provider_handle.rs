@@ -5,13 +5,15 @@ use std::pin::Pin;
 use std::sync::Arc;
 use tokio_stream::Stream;
 
-use crate::config::ProviderConfig;
+use crate::config::{ ProviderConfig, ToolCallingMode };
 
 use super::claude::ClaudeClient;
 use super::codex::CodexClient;
 use super::gemini::GeminiClient;
 use super::openai::{ create_deepseek, create_openai, create_qwen, create_zhipuai, OpenAiClient };
-pub use super::provider_base::{ Message, ProviderClient };
+use super::prompt_limit;
+use super::stream_sanitizer;
+pub use super::provider_base::{ Message, ProviderClient, RequestAuth, ToolUseOptions };
 
 pub enum AnyProviderClient {
     Claude(ClaudeClient),
@@ -26,20 +28,101 @@ impl ProviderClient for AnyProviderClient {
         messages: Vec<Message>,
         tools: Option<Vec<Value>>
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Value>> + Send>>> {
-        match self {
+        let stream = match self {
             AnyProviderClient::Claude(c) => c.stream_chat(messages, tools).await,
             AnyProviderClient::Codex(c) => c.stream_chat(messages, tools).await,
             AnyProviderClient::Gemini(c) => c.stream_chat(messages, tools).await,
             AnyProviderClient::OpenAI(c) => c.stream_chat(messages, tools).await,
-        }
+        }?;
+        Ok(stream_sanitizer::sanitize_stream(stream, self.strip_tokens()))
     }
 
     async fn chat(&self, messages: Vec<Message>, tools: Option<Vec<Value>>) -> Result<Value> {
-        match self {
+        let mut response = match self {
             AnyProviderClient::Claude(c) => c.chat(messages, tools).await,
             AnyProviderClient::Codex(c) => c.chat(messages, tools).await,
             AnyProviderClient::Gemini(c) => c.chat(messages, tools).await,
             AnyProviderClient::OpenAI(c) => c.chat(messages, tools).await,
+        }?;
+        stream_sanitizer::sanitize_message(&mut response, self.strip_tokens());
+        Ok(response)
+    }
+}
+
+impl AnyProviderClient {
+    /// Whether a trailing assistant message is continued rather than answered,
+    /// which lets a dropped stream resume from the text received so far
+    pub fn supports_assistant_prefix(&self) -> bool {
+        matches!(self, AnyProviderClient::Claude(_))
+    }
+
+    /// Control tokens removed from the reply text
+    pub fn strip_tokens(&self) -> &[String] {
+        match self {
+            AnyProviderClient::Claude(c) => &c.strip_tokens,
+            AnyProviderClient::Codex(c) => &c.strip_tokens,
+            AnyProviderClient::Gemini(c) => &c.strip_tokens,
+            AnyProviderClient::OpenAI(c) => &c.strip_tokens,
+        }
+    }
+
+    /// Replace the brand's default tokens to strip; None keeps them
+    pub fn with_strip_tokens(self, tokens: Option<Vec<String>>) -> Self {
+        let Some(tokens) = tokens else {
+            return self;
+        };
+        match self {
+            AnyProviderClient::Claude(c) => AnyProviderClient::Claude(c.with_strip_tokens(tokens)),
+            AnyProviderClient::Codex(c) => AnyProviderClient::Codex(c.with_strip_tokens(tokens)),
+            AnyProviderClient::Gemini(c) => AnyProviderClient::Gemini(c.with_strip_tokens(tokens)),
+            AnyProviderClient::OpenAI(c) => AnyProviderClient::OpenAI(c.with_strip_tokens(tokens)),
+        }
+    }
+
+    /// Set the sequences that end a reply; the codex API has no such option
+    pub fn with_stop_sequences(self, sequences: Vec<String>) -> Self {
+        if sequences.is_empty() {
+            return self;
+        }
+        match self {
+            AnyProviderClient::Claude(c) => AnyProviderClient::Claude(c.with_stop_sequences(sequences)),
+            AnyProviderClient::Gemini(c) => AnyProviderClient::Gemini(c.with_stop_sequences(sequences)),
+            AnyProviderClient::OpenAI(c) => AnyProviderClient::OpenAI(c.with_stop_sequences(sequences)),
+            other @ AnyProviderClient::Codex(_) => {
+                log::warn!("Stop sequences are not supported by codex and are ignored");
+                other
+            }
+        }
+    }
+
+    /// Set the session's tool choice; Claude clients use all of it, OpenAI-compatible
+    /// ones whether parallel tool calls are allowed
+    pub fn with_tool_use(self, tool_use: ToolUseOptions) -> Self {
+        match self {
+            AnyProviderClient::Claude(c) => AnyProviderClient::Claude(c.with_tool_use(tool_use)),
+            AnyProviderClient::OpenAI(c) => AnyProviderClient::OpenAI(c.with_tool_use(tool_use)),
+            other => other,
+        }
+    }
+
+    /// Send strict tool schemas; only OpenAI-compatible clients take them
+    pub fn with_strict_tools(self, strict: bool) -> Self {
+        match self {
+            AnyProviderClient::OpenAI(c) => AnyProviderClient::OpenAI(c.with_strict_tools(strict)),
+            other => other,
+        }
+    }
+
+    /// Whether tool definitions should be sent in strict form
+    pub fn strict_tools(&self) -> bool {
+        matches!(self, AnyProviderClient::OpenAI(c) if c.strict_tools)
+    }
+
+    /// Set how tools are offered; only OpenAI-compatible clients have a choice
+    pub fn with_tool_calling(self, mode: ToolCallingMode) -> Self {
+        match self {
+            AnyProviderClient::OpenAI(c) => AnyProviderClient::OpenAI(c.with_tool_calling(mode)),
+            other => other,
         }
     }
 }
@@ -49,41 +132,71 @@ pub fn create_client(
     base_url: String,
     api_key: String,
     model_name: String,
-    system_prompt: Option<String>
+    system_prompt: Option<String>,
+    auth: RequestAuth
 ) -> AnyProviderClient {
-    match provider.to_lowercase().as_str() {
+    let client = match provider.to_lowercase().as_str() {
         "anthropic" | "claude" =>
             AnyProviderClient::Claude(
-                ClaudeClient::new(base_url, api_key, model_name).with_system_prompt(system_prompt)
+                ClaudeClient::new(base_url, api_key, model_name)
+                    .with_system_prompt(system_prompt)
+                    .with_auth(auth)
             ),
         "codex" =>
             AnyProviderClient::Codex(
-                CodexClient::new(base_url, api_key, model_name).with_system_prompt(system_prompt)
+                CodexClient::new(base_url, api_key, model_name)
+                    .with_system_prompt(system_prompt)
+                    .with_auth(auth)
             ),
         "gemini" =>
             AnyProviderClient::Gemini(
-                GeminiClient::new(base_url, api_key, model_name).with_system_prompt(system_prompt)
+                GeminiClient::new(base_url, api_key, model_name)
+                    .with_system_prompt(system_prompt)
+                    .with_auth(auth)
             ),
         "openai" =>
-            AnyProviderClient::OpenAI(create_openai(base_url, api_key, model_name, system_prompt)),
+            AnyProviderClient::OpenAI(
+                create_openai(base_url, api_key, model_name, system_prompt).with_auth(auth)
+            ),
         "zhipuai" =>
-            AnyProviderClient::OpenAI(create_zhipuai(base_url, api_key, model_name, system_prompt)),
+            AnyProviderClient::OpenAI(
+                create_zhipuai(base_url, api_key, model_name, system_prompt).with_auth(auth)
+            ),
         "deepseek" =>
             AnyProviderClient::OpenAI(
-                create_deepseek(base_url, api_key, model_name, system_prompt)
+                create_deepseek(base_url, api_key, model_name, system_prompt).with_auth(auth)
             ),
         "qwen" =>
-            AnyProviderClient::OpenAI(create_qwen(base_url, api_key, model_name, system_prompt)),
-        _ => AnyProviderClient::OpenAI(create_openai(base_url, api_key, model_name, system_prompt)),
-    }
+            AnyProviderClient::OpenAI(
+                create_qwen(base_url, api_key, model_name, system_prompt).with_auth(auth)
+            ),
+        _ =>
+            AnyProviderClient::OpenAI(
+                create_openai(base_url, api_key, model_name, system_prompt).with_auth(auth)
+            ),
+    };
+    client.with_strip_tokens(Some(stream_sanitizer::default_tokens(provider)))
 }
 
 #[derive(Default)]
 pub struct ProviderClientFactory {
     cache: HashMap<(String, String), Arc<AnyProviderClient>>,
+    tool_use: ToolUseOptions,
 }
 
 impl ProviderClientFactory {
+    /// Tool choice of the clients created from now on
+    pub fn set_tool_use(&mut self, tool_use: ToolUseOptions) {
+        if tool_use != self.tool_use {
+            self.tool_use = tool_use;
+            self.cache.clear();
+        }
+    }
+
+    pub fn tool_use(&self) -> &ToolUseOptions {
+        &self.tool_use
+    }
+
     pub fn get_or_create(
         &mut self,
         provider_name: &str,
@@ -92,6 +205,14 @@ impl ProviderClientFactory {
         system_prompt: Option<String>
     ) -> Result<Arc<AnyProviderClient>> {
         let key = (provider_name.to_string(), model_name.to_string());
+        let config = provider_configs
+            .iter()
+            .find(|c| c.name == provider_name)
+            .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))?;
+        let system_prompt = match (system_prompt, config.max_system_prompt_chars) {
+            (Some(prompt), Some(max)) => Some(prompt_limit::fit(&prompt, max, "system prompt").into_owned()),
+            (prompt, _) => prompt,
+        };
 
         if let Some(existing) = self.cache.get(&key) {
             let existing_prompt = system_prompt_of(existing);
@@ -100,18 +221,19 @@ impl ProviderClientFactory {
             }
         }
 
-        let config = provider_configs
-            .iter()
-            .find(|c| c.name == provider_name)
-            .ok_or_else(|| anyhow::anyhow!("Provider not found: {}", provider_name))?;
-
         let client = create_client(
             provider_name,
             config.base_url.clone(),
             config.api_key.clone(),
             model_name.to_string(),
-            system_prompt
-        );
+            system_prompt,
+            RequestAuth::from_config(config)
+        )
+            .with_tool_calling(config.tool_calling)
+            .with_strip_tokens(config.strip_tokens.clone())
+            .with_stop_sequences(config.stop_sequences.clone())
+            .with_strict_tools(config.strict_tools)
+            .with_tool_use(self.tool_use.clone());
 
         let client = Arc::new(client);
         self.cache.insert(key, Arc::clone(&client));
@@ -140,6 +262,7 @@ mod tests {
             base_url: "http://localhost:1234".to_string(),
             api_key: "k".to_string(),
             models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
         }];
 
         let mut factory = ProviderClientFactory::default();
@@ -159,6 +282,7 @@ mod tests {
             base_url: "http://localhost:1234".to_string(),
             api_key: "k".to_string(),
             models: vec!["gpt-4o-mini".to_string()],
+            ..Default::default()
         }];
 
         let mut factory = ProviderClientFactory::default();
@@ -0,0 +1,208 @@
+//! Removal of control tokens that some servers leak into reply text, such as
+//! ChatML's `<|im_end|>` or Llama's `[/INST]`, usually because the chat
+//! template or stop sequences of a self-hosted model are set up wrong.
+//!
+//! Every brand has a default list (empty for first-party APIs, which do not
+//! leak them); `strip_tokens` in a provider's config replaces it, and an
+//! empty list turns stripping off. Tokens are removed from `content` and
+//! `reasoning_content` deltas before the agent sees them, so they reach
+//! neither event consumers nor the history.
+
+use anyhow::Result;
+use serde_json::{ json, Value };
+use std::pin::Pin;
+use tokio_stream::Stream;
+
+/// Tokens of the common open-weight chat templates
+const TEMPLATE_TOKENS: &[&str] = &[
+    "<|im_start|>",
+    "<|im_end|>",
+    "<|endoftext|>",
+    "<|end_of_text|>",
+    "<|begin_of_text|>",
+    "<|eot_id|>",
+    "<|start_header_id|>",
+    "<|end_header_id|>",
+    "<|end|>",
+    "<|assistant|>",
+    "<|user|>",
+    "<|system|>",
+    "<|observation|>",
+    "[INST]",
+    "[/INST]",
+    "<end_of_turn>",
+    "<start_of_turn>",
+];
+
+/// DeepSeek's own sentence markers, written with fullwidth bars
+const DEEPSEEK_TOKENS: &[&str] = &["<｜end▁of▁sentence｜>", "<｜begin▁of▁sentence｜>"];
+
+/// Default tokens to strip for a provider brand, as named in `create_client`
+pub fn default_tokens(provider: &str) -> Vec<String> {
+    let tokens: Vec<&str> = match provider.to_lowercase().as_str() {
+        "anthropic" | "claude" | "codex" | "gemini" => Vec::new(),
+        "deepseek" => TEMPLATE_TOKENS.iter().chain(DEEPSEEK_TOKENS).copied().collect(),
+        // OpenAI-compatible servers, self-hosted ones included
+        _ => TEMPLATE_TOKENS.to_vec(),
+    };
+    tokens.into_iter().map(str::to_string).collect()
+}
+
+/// Strips tokens from streamed text, holding back text that may be the start
+/// of a token until the next chunk shows whether it is one
+#[derive(Debug, Default)]
+pub struct StreamSanitizer {
+    tokens: Vec<String>,
+    buffer: String,
+}
+
+impl StreamSanitizer {
+    pub fn new(tokens: &[String]) -> Self {
+        Self {
+            tokens: tokens.iter().filter(|t| !t.is_empty()).cloned().collect(),
+            buffer: String::new(),
+        }
+    }
+
+    /// Text of `chunk` that is safe to pass on
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.buffer.push_str(chunk);
+        self.strip_complete();
+        let keep = self.partial_token_len();
+        self.buffer.drain(..self.buffer.len() - keep).collect()
+    }
+
+    /// Text held back at the end of the stream
+    pub fn finish(&mut self) -> String {
+        std::mem::take(&mut self.buffer)
+    }
+
+    fn strip_complete(&mut self) {
+        // Removing one token can join the halves of another
+        while let Some(token) = self.tokens.iter().find(|t| self.buffer.contains(t.as_str())) {
+            self.buffer = self.buffer.replace(token.as_str(), "");
+        }
+    }
+
+    /// Length of the longest suffix of the buffer that starts some token
+    fn partial_token_len(&self) -> usize {
+        self.tokens
+            .iter()
+            .flat_map(|token| {
+                token
+                    .char_indices()
+                    .skip(1)
+                    .map(|(i, _)| &token[..i])
+                    .filter(|prefix| self.buffer.ends_with(prefix))
+                    .map(str::len)
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// `text` without any of `tokens`
+pub fn strip(text: &str, tokens: &[String]) -> String {
+    let mut sanitizer = StreamSanitizer::new(tokens);
+    let mut out = sanitizer.push(text);
+    out.push_str(&sanitizer.finish());
+    out
+}
+
+/// Strip tokens from the text deltas of an OpenAI-style chunk stream
+pub fn sanitize_stream(
+    stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>,
+    tokens: &[String]
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    if tokens.is_empty() {
+        return stream;
+    }
+    let tokens = tokens.to_vec();
+    Box::pin(
+        async_stream::stream! {
+            let mut stream = stream;
+            let mut content = StreamSanitizer::new(&tokens);
+            let mut reasoning = StreamSanitizer::new(&tokens);
+            while let Some(chunk) = tokio_stream::StreamExt::next(&mut stream).await {
+                let mut chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                let finished = chunk
+                    .pointer("/choices/0/finish_reason")
+                    .is_some_and(|r| !r.is_null());
+                for (field, sanitizer) in [("content", &mut content), ("reasoning_content", &mut reasoning)] {
+                    let text = chunk
+                        .pointer(&format!("/choices/0/delta/{}", field))
+                        .and_then(|c| c.as_str())
+                        .map(str::to_string);
+                    if text.is_none() && !finished {
+                        continue;
+                    }
+                    let mut clean = sanitizer.push(text.as_deref().unwrap_or_default());
+                    if finished {
+                        clean.push_str(&sanitizer.finish());
+                    }
+                    if text.is_some() || !clean.is_empty() {
+                        chunk["choices"][0]["delta"][field] = json!(clean);
+                    }
+                }
+                yield Ok(chunk);
+            }
+            let (content, reasoning) = (content.finish(), reasoning.finish());
+            if !content.is_empty() || !reasoning.is_empty() {
+                let mut delta = json!({});
+                if !content.is_empty() {
+                    delta["content"] = json!(content);
+                }
+                if !reasoning.is_empty() {
+                    delta["reasoning_content"] = json!(reasoning);
+                }
+                yield Ok(json!({ "choices": [{ "delta": delta }] }));
+            }
+        }
+    )
+}
+
+/// Strip tokens from the text of a non-streaming response
+pub fn sanitize_message(response: &mut Value, tokens: &[String]) {
+    if tokens.is_empty() {
+        return;
+    }
+    let Some(message) = response.pointer_mut("/choices/0/message") else {
+        return;
+    };
+    for field in ["content", "reasoning_content"] {
+        if let Some(text) = message.get(field).and_then(|c| c.as_str()) {
+            message[field] = json!(strip(text, tokens));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_tokens_split_across_chunks() {
+        let tokens = default_tokens("qwen");
+        let mut sanitizer = StreamSanitizer::new(&tokens);
+        let mut out = String::new();
+        for part in ["Done <", "3 <|im_", "end|>", "[/IN", "ST]\n<|e", "ot_id|><|"] {
+            out.push_str(&sanitizer.push(part));
+        }
+        assert_eq!(out, "Done <3 \n");
+        assert_eq!(sanitizer.finish(), "<|");
+
+        assert_eq!(strip("a<|im_<|im_end|>end|>b", &tokens), "ab");
+        assert_eq!(strip("x<｜end▁of▁sentence｜>", &default_tokens("deepseek")), "x");
+        assert!(default_tokens("claude").is_empty());
+
+        let mut response = json!({ "choices": [{ "message": { "content": "hi<|endoftext|>" } }] });
+        sanitize_message(&mut response, &tokens);
+        assert_eq!(response.pointer("/choices/0/message/content"), Some(&json!("hi")));
+    }
+}
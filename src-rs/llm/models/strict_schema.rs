@@ -0,0 +1,175 @@
+//! Tool definitions in OpenAI's strict function calling format.
+//!
+//! With `strict: true` the API makes the arguments match the schema, which
+//! stops the malformed calls GPT-4-class models otherwise make now and then.
+//! Strict schemas are a subset of JSON Schema: every object lists all its
+//! properties as required and has `additionalProperties: false`, so optional
+//! properties become nullable instead, and a few keywords are not allowed.
+//! `tighten` rewrites a definition into that form; the builtin tools take a
+//! null argument as an absent one (see `lenient_args::conform`).
+
+use anyhow::{bail, Result};
+use serde_json::{json, Map, Value};
+
+/// Keywords strict mode rejects and that can be dropped, since the tools
+/// check their arguments anyway
+const DROPPED_KEYWORDS: [&str; 3] = ["default", "minLength", "maxLength"];
+
+/// Keywords strict mode rejects and that change what the schema accepts
+const UNSUPPORTED_KEYWORDS: [&str; 7] =
+    ["allOf", "oneOf", "not", "if", "patternProperties", "dependentRequired", "unevaluatedProperties"];
+
+/// An OpenAI-format tool definition with `strict: true` and its parameters
+/// tightened, or an error naming what strict mode cannot express
+pub fn tighten(definition: &Value) -> Result<Value> {
+    let mut definition = definition.clone();
+    let Some(function) = definition.get_mut("function").and_then(Value::as_object_mut) else {
+        bail!("not a function definition");
+    };
+    let mut parameters = function
+        .remove("parameters")
+        .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+    if parameters.get("type").and_then(Value::as_str) != Some("object") {
+        bail!("parameters are not an object");
+    }
+    tighten_schema(&mut parameters)?;
+    function.insert("parameters".to_string(), parameters);
+    function.insert("strict".to_string(), Value::Bool(true));
+    Ok(definition)
+}
+
+fn tighten_schema(schema: &mut Value) -> Result<()> {
+    let Some(schema) = schema.as_object_mut() else {
+        return Ok(());
+    };
+    if let Some(keyword) = UNSUPPORTED_KEYWORDS.iter().find(|k| schema.contains_key(**k)) {
+        bail!("'{}' is not supported", keyword);
+    }
+    for keyword in DROPPED_KEYWORDS {
+        schema.remove(keyword);
+    }
+    if has_type(schema, "object") {
+        match schema.get("additionalProperties") {
+            None | Some(Value::Bool(false)) => {}
+            Some(_) => bail!("objects with additional properties are not supported"),
+        }
+        let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) else {
+            bail!("objects without properties are not supported");
+        };
+        let mut names = Vec::with_capacity(properties.len());
+        for (name, property) in properties.iter_mut() {
+            tighten_schema(property).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+            names.push(name.clone());
+        }
+        let required: Vec<String> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| r.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        if let Some(properties) = schema.get_mut("properties").and_then(Value::as_object_mut) {
+            for (name, property) in properties.iter_mut() {
+                if !required.contains(name) {
+                    make_nullable(property).map_err(|e| anyhow::anyhow!("{}: {}", name, e))?;
+                }
+            }
+        }
+        schema.insert("required".to_string(), json!(names));
+        schema.insert("additionalProperties".to_string(), Value::Bool(false));
+    }
+    if let Some(items) = schema.get_mut("items") {
+        tighten_schema(items)?;
+    }
+    if let Some(variants) = schema.get_mut("anyOf").and_then(Value::as_array_mut) {
+        for variant in variants {
+            tighten_schema(variant)?;
+        }
+    }
+    Ok(())
+}
+
+fn has_type(schema: &Map<String, Value>, name: &str) -> bool {
+    match schema.get("type") {
+        Some(Value::String(t)) => t == name,
+        Some(Value::Array(types)) => types.iter().any(|t| t == name),
+        _ => false,
+    }
+}
+
+/// Let an optional property be null, which stands for leaving it out
+fn make_nullable(property: &mut Value) -> Result<()> {
+    let Some(property) = property.as_object_mut() else {
+        bail!("schema is not an object");
+    };
+    if let Some(variants) = property.get_mut("anyOf").and_then(Value::as_array_mut) {
+        if !variants.iter().any(|v| v.get("type").and_then(Value::as_str) == Some("null")) {
+            variants.push(json!({ "type": "null" }));
+        }
+        return Ok(());
+    }
+    match property.get_mut("type") {
+        Some(Value::String(t)) => {
+            let t = std::mem::take(t);
+            property.insert("type".to_string(), json!([t, "null"]));
+        }
+        Some(Value::Array(types)) => {
+            if !types.iter().any(|t| t == "null") {
+                types.push(json!("null"));
+            }
+        }
+        _ => bail!("optional property without a type"),
+    }
+    if let Some(values) = property.get_mut("enum").and_then(Value::as_array_mut) {
+        if !values.contains(&Value::Null) {
+            values.push(Value::Null);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn makes_optional_properties_nullable_and_required() {
+        let definition = json!({
+            "type": "function",
+            "function": {
+                "name": "view",
+                "description": "Read a file",
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": { "type": "string", "minLength": 1 },
+                        "limit": { "type": "integer", "default": 200 },
+                        "mode": { "type": "string", "enum": ["text", "hex"] },
+                        "ranges": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": { "start": { "type": "integer" }, "end": { "type": "integer" } },
+                                "required": ["start"]
+                            }
+                        }
+                    },
+                    "required": ["file_path"]
+                }
+            }
+        });
+        let strict = tighten(&definition).unwrap();
+        assert_eq!(strict["function"]["strict"], true);
+        let parameters = &strict["function"]["parameters"];
+        assert_eq!(parameters["additionalProperties"], false);
+        assert_eq!(parameters["required"], json!(["file_path", "limit", "mode", "ranges"]));
+        assert_eq!(parameters["properties"]["file_path"], json!({ "type": "string" }));
+        assert_eq!(parameters["properties"]["limit"], json!({ "type": ["integer", "null"] }));
+        assert_eq!(parameters["properties"]["mode"]["enum"], json!(["text", "hex", null]));
+        let item = &parameters["properties"]["ranges"]["items"];
+        assert_eq!(item["required"], json!(["end", "start"]));
+        assert_eq!(item["properties"]["end"]["type"], json!(["integer", "null"]));
+
+        let mut free_form = definition.clone();
+        free_form["function"]["parameters"]["properties"]["env"] = json!({ "type": "object" });
+        assert!(tighten(&free_form).unwrap_err().to_string().contains("env"));
+    }
+}
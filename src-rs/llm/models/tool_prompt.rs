@@ -0,0 +1,348 @@
+//! Tool calling for servers without native support (some vLLM and LM Studio
+//! setups): tool definitions are rendered into the system prompt and calls are
+//! parsed back out of the reply text into regular `tool_calls` deltas.
+
+use anyhow::Result;
+use serde_json::{ json, Value };
+use std::pin::Pin;
+use tokio_stream::Stream;
+
+use crate::llm::models::provider_base::Message;
+
+const CALL_OPEN: &str = "<tool_call>";
+const CALL_CLOSE: &str = "</tool_call>";
+const HISTORY_CALLS_PREFIX: &str = "ToolCallsJSON:";
+
+/// System prompt section describing the tools and the call format
+pub fn render_tool_prompt(tools: &[Value]) -> String {
+    let mut out = format!(
+        "# Tools\n\n\
+         You can call the tools below. To call one, write a block like this in your reply, \
+         with the arguments as a JSON object matching the tool's parameters:\n\n\
+         {}\n{{\"name\": \"tool_name\", \"arguments\": {{\"arg\": \"value\"}}}}\n{}\n\n\
+         Write one block per call. After your calls, stop and wait: the results arrive in \
+         the next message, starting with `ToolResult:`. Only call tools listed here.\n\n\
+         ## Available tools\n",
+        CALL_OPEN,
+        CALL_CLOSE
+    );
+    for tool in tools {
+        let function = tool.get("function").unwrap_or(tool);
+        let Some(name) = function.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let description = function
+            .get("description")
+            .and_then(|d| d.as_str())
+            .unwrap_or_default()
+            .trim();
+        let parameters = function.get("parameters").cloned().unwrap_or_else(|| json!({}));
+        out.push_str(&format!("\n### {}\n{}\nParameters: {}\n", name, description, parameters));
+    }
+    out
+}
+
+/// Messages for a request in prompt mode: the tool prompt is appended to the
+/// system message and earlier calls are shown in the same block format
+pub fn prepare_messages(messages: Vec<Message>, tools: &[Value]) -> Vec<Message> {
+    let prompt = render_tool_prompt(tools);
+    let mut has_system = false;
+    let mut out: Vec<Message> = messages
+        .into_iter()
+        .map(|mut message| {
+            if message.role == "system" && !has_system {
+                message.content = format!("{}\n\n{}", message.content, prompt);
+                has_system = true;
+            } else if message.role == "assistant" {
+                message.content = render_history_calls(&message.content);
+            }
+            message
+        })
+        .collect();
+    if !has_system {
+        out.insert(0, Message {
+            role: "system".to_string(),
+            content: prompt,
+        });
+    }
+    out
+}
+
+/// Rewrite the agent's `ToolCallsJSON:[...]` history suffix as call blocks
+fn render_history_calls(content: &str) -> String {
+    let Some(pos) = content.find(HISTORY_CALLS_PREFIX) else {
+        return content.to_string();
+    };
+    let Ok(calls) = serde_json::from_str::<Vec<Value>>(&content[pos + HISTORY_CALLS_PREFIX.len()..]) else {
+        return content.to_string();
+    };
+
+    let mut out = content[..pos].trim_end().to_string();
+    for call in calls {
+        let name = call.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+        let arguments: Value = call
+            .get("arguments")
+            .and_then(|a| a.as_str())
+            .and_then(|a| serde_json::from_str(a).ok())
+            .unwrap_or_else(|| json!({}));
+        if !out.is_empty() {
+            out.push_str("\n\n");
+        }
+        out.push_str(
+            &format!(
+                "{}\n{}\n{}",
+                CALL_OPEN,
+                json!({ "name": name, "arguments": arguments }),
+                CALL_CLOSE
+            )
+        );
+    }
+    out
+}
+
+/// Part of the reply text: plain text or a parsed call
+#[derive(Debug, PartialEq)]
+pub enum ReplyPiece {
+    Text(String),
+    Call {
+        name: String,
+        arguments: String,
+    },
+}
+
+/// Splits streamed reply text into plain text and call blocks, holding back
+/// text that may be the start of a block until it is complete
+#[derive(Debug, Default)]
+pub struct ToolCallExtractor {
+    buffer: String,
+}
+
+impl ToolCallExtractor {
+    pub fn push(&mut self, text: &str) -> Vec<ReplyPiece> {
+        self.buffer.push_str(text);
+        let mut pieces = Vec::new();
+        loop {
+            let Some(start) = self.buffer.find(CALL_OPEN) else {
+                let emit = self.buffer.len() - partial_open_len(&self.buffer);
+                if emit > 0 {
+                    pieces.push(ReplyPiece::Text(self.buffer.drain(..emit).collect()));
+                }
+                break;
+            };
+            if start > 0 {
+                pieces.push(ReplyPiece::Text(self.buffer.drain(..start).collect()));
+            }
+            let Some(end) = self.buffer.find(CALL_CLOSE) else {
+                break;
+            };
+            let block: String = self.buffer.drain(..end + CALL_CLOSE.len()).collect();
+            let body = &block[CALL_OPEN.len()..block.len() - CALL_CLOSE.len()];
+            pieces.push(parse_call(body).unwrap_or(ReplyPiece::Text(block)));
+        }
+        pieces
+    }
+
+    /// Remaining text at the end of the reply, including an unterminated block
+    pub fn finish(&mut self) -> Vec<ReplyPiece> {
+        if self.buffer.is_empty() {
+            return Vec::new();
+        }
+        vec![ReplyPiece::Text(std::mem::take(&mut self.buffer))]
+    }
+}
+
+/// Length of the longest suffix of `text` that is a prefix of the opening tag
+fn partial_open_len(text: &str) -> usize {
+    (1..CALL_OPEN.len())
+        .rev()
+        .find(|&len| text.ends_with(&CALL_OPEN[..len]))
+        .unwrap_or(0)
+}
+
+fn parse_call(body: &str) -> Option<ReplyPiece> {
+    let body = body.trim();
+    let body = body
+        .strip_prefix("```json")
+        .or_else(|| body.strip_prefix("```"))
+        .map(|b| b.trim_end().trim_end_matches("```"))
+        .unwrap_or(body);
+    let value: Value = serde_json::from_str(body.trim()).ok()?;
+    let name = value.get("name")?.as_str()?.to_string();
+    let arguments = match value.get("arguments").or_else(|| value.get("parameters")) {
+        Some(Value::String(raw)) => raw.clone(),
+        Some(args) => args.to_string(),
+        None => "{}".to_string(),
+    };
+    Some(ReplyPiece::Call { name, arguments })
+}
+
+fn piece_chunk(piece: ReplyPiece, calls: &mut usize) -> Value {
+    match piece {
+        ReplyPiece::Text(text) => json!({ "choices": [{ "delta": { "content": text } }] }),
+        ReplyPiece::Call { name, arguments } => {
+            let index = *calls;
+            *calls += 1;
+            json!({
+                "choices": [{
+                    "delta": {
+                        "tool_calls": [{
+                            "index": index,
+                            "id": format!("call_{}", index),
+                            "type": "function",
+                            "function": { "name": name, "arguments": arguments }
+                        }]
+                    }
+                }]
+            })
+        }
+    }
+}
+
+/// Turn call blocks in an OpenAI-style chunk stream into `tool_calls` deltas
+pub fn extract_tool_calls(
+    stream: Pin<Box<dyn Stream<Item = Result<Value>> + Send>>
+) -> Pin<Box<dyn Stream<Item = Result<Value>> + Send>> {
+    Box::pin(
+        async_stream::stream! {
+            let mut stream = stream;
+            let mut extractor = ToolCallExtractor::default();
+            let mut calls = 0usize;
+            while let Some(chunk) = tokio_stream::StreamExt::next(&mut stream).await {
+                let mut chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+
+                let text = chunk
+                    .pointer("/choices/0/delta/content")
+                    .and_then(|c| c.as_str())
+                    .map(str::to_string);
+                if let Some(text) = text {
+                    chunk["choices"][0]["delta"]["content"] = json!("");
+                    for piece in extractor.push(&text) {
+                        yield Ok(piece_chunk(piece, &mut calls));
+                    }
+                }
+
+                let finished = chunk
+                    .pointer("/choices/0/finish_reason")
+                    .is_some_and(|r| !r.is_null());
+                if finished {
+                    for piece in extractor.finish() {
+                        yield Ok(piece_chunk(piece, &mut calls));
+                    }
+                    if calls > 0 {
+                        chunk["choices"][0]["finish_reason"] = json!("tool_calls");
+                    }
+                }
+                yield Ok(chunk);
+            }
+            for piece in extractor.finish() {
+                yield Ok(piece_chunk(piece, &mut calls));
+            }
+        }
+    )
+}
+
+/// Move call blocks in a non-streaming response into `message.tool_calls`
+pub fn extract_message_tool_calls(response: &mut Value) {
+    let Some(message) = response.pointer_mut("/choices/0/message") else {
+        return;
+    };
+    let Some(content) = message.get("content").and_then(|c| c.as_str()).map(str::to_string) else {
+        return;
+    };
+
+    let mut extractor = ToolCallExtractor::default();
+    let mut pieces = extractor.push(&content);
+    pieces.extend(extractor.finish());
+    let mut text = String::new();
+    let mut tool_calls = Vec::new();
+    for piece in pieces {
+        match piece {
+            ReplyPiece::Text(t) => text.push_str(&t),
+            ReplyPiece::Call { name, arguments } => {
+                tool_calls.push(json!({
+                    "id": format!("call_{}", tool_calls.len()),
+                    "type": "function",
+                    "function": { "name": name, "arguments": arguments }
+                }));
+            }
+        }
+    }
+    if tool_calls.is_empty() {
+        return;
+    }
+    message["content"] = json!(text.trim());
+    message["tool_calls"] = Value::Array(tool_calls);
+    if let Some(choice) = response.pointer_mut("/choices/0") {
+        choice["finish_reason"] = json!("tool_calls");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_calls_split_across_chunks() {
+        let mut extractor = ToolCallExtractor::default();
+        let mut pieces = Vec::new();
+        for part in [
+            "Let me look.\n<tool",
+            "_call>\n{\"name\": \"view\", \"arguments\": {\"file_path\": \"a.rs\"}}\n</tool_",
+            "call>\n<tool_call>not json</tool_call> done <",
+        ] {
+            pieces.extend(extractor.push(part));
+        }
+        pieces.extend(extractor.finish());
+
+        assert_eq!(
+            pieces,
+            vec![
+                ReplyPiece::Text("Let me look.\n".to_string()),
+                ReplyPiece::Call {
+                    name: "view".to_string(),
+                    arguments: "{\"file_path\":\"a.rs\"}".to_string(),
+                },
+                ReplyPiece::Text("\n".to_string()),
+                ReplyPiece::Text("<tool_call>not json</tool_call>".to_string()),
+                ReplyPiece::Text(" done ".to_string()),
+                ReplyPiece::Text("<".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn renders_tools_and_history_calls_into_messages() {
+        let tools =
+            vec![
+                json!({
+            "type": "function",
+            "function": {
+                "name": "view",
+                "description": "Reads a file.",
+                "parameters": { "type": "object", "properties": { "file_path": { "type": "string" } } }
+            }
+        })
+            ];
+        let messages = vec![
+            Message { role: "user".to_string(), content: "show a.rs".to_string() },
+            Message {
+                role: "assistant".to_string(),
+                content: "Reading.\n\nToolCallsJSON:[{\"id\":\"call_0\",\"name\":\"view\",\"arguments\":\"{\\\"file_path\\\":\\\"a.rs\\\"}\"}]".to_string(),
+            }
+        ];
+
+        let out = prepare_messages(messages, &tools);
+        assert_eq!(out[0].role, "system");
+        assert!(out[0].content.contains("### view\nReads a file."));
+        assert_eq!(
+            out[2].content,
+            "Reading.\n\n<tool_call>\n{\"arguments\":{\"file_path\":\"a.rs\"},\"name\":\"view\"}\n</tool_call>"
+        );
+    }
+}
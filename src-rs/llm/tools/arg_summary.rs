@@ -0,0 +1,55 @@
+//! Helpers for `ToolSpec::summarize_args`: short, single-line descriptions
+//! of a call, shown on ToolStart and confirmation events.
+
+use std::path::Path;
+
+/// Longest summary, in characters
+pub const MAX_SUMMARY_CHARS: usize = 300;
+
+/// `path` relative to the working directory when it is inside it
+pub fn path(path: &str) -> String {
+    let Ok(cwd) = std::env::current_dir() else {
+        return path.to_string();
+    };
+    match Path::new(path).strip_prefix(&cwd) {
+        Ok(rel) if !rel.as_os_str().is_empty() => rel.display().to_string(),
+        _ => path.to_string(),
+    }
+}
+
+/// The first line of `text`, noting how many more there are
+pub fn first_line(text: &str) -> String {
+    let text = text.trim();
+    let mut lines = text.lines();
+    let first = lines.next().unwrap_or("").to_string();
+    match lines.count() {
+        0 => first,
+        more => format!("{} …(+{} lines)", first, more),
+    }
+}
+
+/// `text` cut to `MAX_SUMMARY_CHARS`, with an ellipsis if it was longer
+pub fn clip(text: &str) -> String {
+    if text.chars().count() <= MAX_SUMMARY_CHARS {
+        return text.to_string();
+    }
+    let mut clipped: String = text.chars().take(MAX_SUMMARY_CHARS - 1).collect();
+    clipped.push('…');
+    clipped
+}
+
+/// "a.rs:12:5", leaving out what is not given
+pub fn position(file_path: &str, line: Option<usize>, column: Option<usize>) -> String {
+    let mut out = path(file_path);
+    if let Some(line) = line {
+        out.push_str(&format!(":{}", line));
+        if let Some(column) = column {
+            out.push_str(&format!(":{}", column));
+        }
+    }
+    out
+}
+
+pub fn line_count(text: &str) -> usize {
+    text.lines().count()
+}
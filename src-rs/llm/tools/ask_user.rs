@@ -0,0 +1,158 @@
+use crate::llm::config::AppConfig;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::utils::serde_util::deserialize_vec_or_str_lax;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Suggested answers offered at most
+const MAX_CHOICES: usize = 10;
+
+/// Tool the model asks the user a question with. The session pauses the turn
+/// on the result until `answer_question` is called and returns the answer in
+/// its place; run outside a session the call only validates the question.
+#[derive(Clone)]
+pub struct AskUserTool {
+    pub tool_name: String,
+    pub description: String,
+}
+
+/// Ask user request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AskUserRequest {
+    pub question: String,
+    /// Suggested answers, shown as options
+    #[serde(default, deserialize_with = "deserialize_vec_or_str_lax")]
+    pub choices: Vec<String>,
+}
+
+impl AskUserTool {
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self::from_config(&config),
+            Err(e) => {
+                log::warn!("Failed to load config.toml: {}, using defaults", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_ask_user.tool_name.clone(),
+            description: config.tool_ask_user.description.clone(),
+        }
+    }
+}
+
+impl Default for AskUserTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "ask_user".to_string(),
+            description: "Ask the user a clarifying question and wait for the answer".to_string(),
+        }
+    }
+}
+
+impl ToolSpec for AskUserTool {
+    type Args = AskUserRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Think
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Other
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "question": {
+                            "type": "string",
+                            "description": "The question, self-contained and specific"
+                        },
+                        "choices": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "Optional suggested answers (at most 10); the user may still answer freely"
+                        }
+                    },
+                    "required": ["question"]
+                }
+            }
+        })
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let question = args.question.trim();
+        if question.is_empty() {
+            return Err(anyhow!("question must not be empty"));
+        }
+        let mut choices: Vec<String> = Vec::new();
+        for choice in args.choices.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            if !choices.iter().any(|c| c == choice) {
+                choices.push(choice.to_string());
+            }
+        }
+        if choices.len() > MAX_CHOICES {
+            return Err(anyhow!("At most {} choices can be offered, got {}", MAX_CHOICES, choices.len()));
+        }
+        Ok(ToolResult {
+            executed: false,
+            ..ToolResult::ok(
+                self.tool_name.clone(),
+                self.kind(),
+                self.operation(),
+                "Waiting for the user's answer.",
+                json!({ "awaiting_user_input": true, "question": question, "choices": choices }),
+            )
+        })
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(args.question.trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_question_and_dedups_choices() {
+        let tool = AskUserTool::default();
+        let ask = |question: &str, choices: &[&str]| {
+            tool.run(
+                AskUserRequest {
+                    question: question.to_string(),
+                    choices: choices.iter().map(|c| c.to_string()).collect(),
+                },
+                false,
+            )
+        };
+        let result = ask(" Which database? ", &["Postgres", " SQLite", "Postgres", ""]).unwrap();
+        assert!(!result.executed);
+        assert_eq!(result.data["awaiting_user_input"], true);
+        assert_eq!(result.data["question"], "Which database?");
+        assert_eq!(result.data["choices"], json!(["Postgres", "SQLite"]));
+
+        assert!(ask("  ", &[]).is_err());
+        let many: Vec<String> = (0..=MAX_CHOICES).map(|i| i.to_string()).collect();
+        assert!(ask("Pick one", &many.iter().map(String::as_str).collect::<Vec<_>>()).is_err());
+    }
+}
@@ -1,21 +1,98 @@
+use crate::llm::agents::cancel::CancelToken;
+use crate::llm::backend::{self, ExecBackend};
 use crate::llm::config::AppConfig;
-use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::tool_trait::{block_on, ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
+use crate::llm::utils::overlay;
 use crate::llm::utils::path_policy::PathPolicy;
-use crate::llm::utils::tool_access::is_full_access;
+use crate::llm::utils::session_vars::{self, SessionVars};
+use crate::llm::utils::shell_safety::{self, CommandAssessment, CommandRisk};
+use crate::llm::utils::terminal_output::normalize_terminal_output;
+use crate::llm::utils::tool_access::{current_tool_session, is_full_access};
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
 use std::time::{Duration, Instant};
-use std::{fs, thread};
+use std::fs;
 
 const MAX_OUTPUT_LENGTH: usize = 30000;
+/// Time given to a command to exit after SIGTERM before it is SIGKILLed
+const KILL_GRACE_MS: u64 = 1000;
+
+/// Cancel token of the command each session is running, by session id
+static RUNNING_COMMANDS: LazyLock<Mutex<HashMap<String, (u64, CancelToken)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_COMMAND_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Request cancellation of the command the session is running in its shell
+///
+/// The command's process group is terminated and its result is reported as
+/// interrupted. The shell itself stays alive for subsequent commands. Other
+/// sessions' commands are not affected.
+pub fn cancel_running_command(session_id: &str) {
+    let commands = RUNNING_COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, cancel)) = commands.get(session_id) {
+        cancel.cancel();
+    }
+}
+
+/// Registration of a running command for `cancel_running_command`, removed on drop
+struct RunningCommand {
+    session_id: String,
+    id: u64,
+    cancel: CancelToken,
+}
+
+impl RunningCommand {
+    fn start(session_id: &str) -> Self {
+        let id = NEXT_COMMAND_ID.fetch_add(1, Ordering::Relaxed);
+        let cancel = CancelToken::new();
+        RUNNING_COMMANDS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(session_id.to_string(), (id, cancel.clone()));
+        Self {
+            session_id: session_id.to_string(),
+            id,
+            cancel,
+        }
+    }
+}
+
+impl Drop for RunningCommand {
+    fn drop(&mut self) {
+        let mut commands = RUNNING_COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+        if commands.get(&self.session_id).is_some_and(|(id, _)| *id == self.id) {
+            commands.remove(&self.session_id);
+        }
+    }
+}
+
+/// Variables bash maintains itself; changes to them are not reported
+const SHELL_MANAGED_VARS: &[&str] = &["_", "PWD", "OLDPWD", "SHLVL"];
+
+/// Working directory and exported environment of a session's persistent shell
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShellState {
+    pub cwd: String,
+    /// Exported variables added or changed since the shell was started
+    pub env_changes: BTreeMap<String, String>,
+}
 
 // Persistent shell implementation
 #[derive(Debug)]
 struct PersistentShell {
     child: Arc<Mutex<Option<Child>>>,
+    /// Exported environment at spawn time, used to compute `env_changes`
+    base_env: HashMap<String, String>,
+    state: Mutex<ShellState>,
 }
 
 #[derive(Debug)]
@@ -24,27 +101,70 @@ struct CommandResult {
     stderr: String,
     exit_code: i32,
     interrupted: bool,
+    cancelled: bool,
     start_time: i64,
     end_time: i64,
 }
 
 impl PersistentShell {
     fn new(cwd: &str) -> Result<Self> {
-        let child = Command::new("bash")
+        let mut child = Command::new("bash")
             .current_dir(cwd)
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
+            .stderr(Stdio::null())
             .env("GIT_EDITOR", "true")
             .spawn()
             .context("Failed to spawn bash process")?;
 
+        // Job control puts every command in its own process group so that a
+        // timeout or cancellation can terminate the whole tree.
+        if let Some(ref mut stdin) = child.stdin {
+            stdin.write_all(b"set -m\n")?;
+            stdin.flush()?;
+        }
+
+        let mut base_env: HashMap<String, String> = std::env::vars().collect();
+        base_env.insert("GIT_EDITOR".to_string(), "true".to_string());
+
         Ok(Self {
             child: Arc::new(Mutex::new(Some(child))),
+            base_env,
+            state: Mutex::new(ShellState {
+                cwd: cwd.to_string(),
+                env_changes: BTreeMap::new(),
+            }),
         })
     }
 
-    fn exec(&self, command: &str, timeout_ms: u64) -> Result<CommandResult> {
+    fn state(&self) -> ShellState {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn is_alive(&self) -> bool {
+        match self.child.lock().unwrap().as_mut() {
+            Some(child) => matches!(child.try_wait(), Ok(None)),
+            None => false,
+        }
+    }
+
+    /// Run a command in the shell
+    ///
+    /// The command runs in a background subshell so it can be killed as a
+    /// process group. Its final working directory and exported environment
+    /// are written out and re-applied to the parent shell, so `cd` and
+    /// `export` carry over to later commands. With `workdir` the command runs
+    /// in that directory without moving the shell. `vars` are exported for the
+    /// command only and are not kept by the shell. Cancelling `cancel` kills
+    /// the command.
+    async fn exec(
+        &self,
+        command: &str,
+        timeout_ms: u64,
+        workdir: Option<&str>,
+        vars: &SessionVars,
+        cancel: &CancelToken,
+    ) -> Result<CommandResult> {
         let start_time = Instant::now();
         let start_time_ms = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -60,45 +180,65 @@ impl PersistentShell {
         let stdout_file = temp_dir.join(format!("carrycode-stdout-{}", timestamp));
         let stderr_file = temp_dir.join(format!("carrycode-stderr-{}", timestamp));
         let status_file = temp_dir.join(format!("carrycode-status-{}", timestamp));
+        let pid_file = temp_dir.join(format!("carrycode-pid-{}", timestamp));
+        let cwd_file = temp_dir.join(format!("carrycode-cwd-{}", timestamp));
+        let env_file = temp_dir.join(format!("carrycode-env-{}", timestamp));
+        let export_file = temp_dir.join(format!("carrycode-export-{}", timestamp));
+
+        let body = match workdir {
+            Some(wd) => format!("(cd {} || exit 1\n{}\n)", shell_quote(wd), command),
+            None => command.to_string(),
+        };
+        let body = format!("{}{}", session_vars::export_script(vars), body);
+        // Unset before the environment is dumped, so they are not re-applied to the shell
+        let unset = match vars.is_empty() {
+            true => String::new(),
+            false => format!("unset {}; ", vars.keys().cloned().collect::<Vec<_>>().join(" ")),
+        };
 
+        // The subshell dumps its cwd and environment on the way out; the
+        // parent shell re-applies them before reporting the exit status.
         let full_command = format!(
-            "({}) > {} 2> {}; echo $? > {}\n",
-            command,
-            shell_quote(&stdout_file.to_string_lossy()),
-            shell_quote(&stderr_file.to_string_lossy()),
-            shell_quote(&status_file.to_string_lossy())
+            "({body}\n__carry_rc=$?; {unset}pwd > {cwd}; env -0 > {env}; export -p > {export}; exit $__carry_rc\n) \
+             > {stdout} 2> {stderr} < /dev/null & echo $! > {pid}; wait $!; __carry_rc=$?; \
+             if [ -s {export} ]; then . {export}; fi; if [ -s {cwd} ]; then cd \"$(cat {cwd})\"; fi; \
+             echo $__carry_rc > {status}\n",
+            body = body,
+            unset = unset,
+            cwd = shell_quote(&cwd_file.to_string_lossy()),
+            env = shell_quote(&env_file.to_string_lossy()),
+            export = shell_quote(&export_file.to_string_lossy()),
+            stdout = shell_quote(&stdout_file.to_string_lossy()),
+            stderr = shell_quote(&stderr_file.to_string_lossy()),
+            pid = shell_quote(&pid_file.to_string_lossy()),
+            status = shell_quote(&status_file.to_string_lossy()),
         );
 
-        let child_clone = Arc::clone(&self.child);
-        let mut child_guard = child_clone.lock().unwrap();
-
-        if let Some(ref mut child) = *child_guard {
+        if let Some(ref mut child) = *self.child.lock().unwrap() {
             if let Some(ref mut stdin) = child.stdin {
                 stdin.write_all(full_command.as_bytes())?;
                 stdin.flush()?;
             }
         }
-        drop(child_guard);
 
         let timeout = Duration::from_millis(timeout_ms);
         let mut interrupted = false;
+        let mut cancelled = false;
 
         loop {
-            if status_file.exists()
-                && fs::metadata(&status_file)
-                    .ok()
-                    .is_some_and(|m| m.len() > 0)
-            {
+            if file_has_content(&status_file) {
                 break;
             }
 
-            if start_time.elapsed() >= timeout {
+            let cancel = cancel.is_cancelled();
+            if cancel || start_time.elapsed() >= timeout {
                 interrupted = true;
-                self.kill_children()?;
+                cancelled = cancel;
+                self.kill_command(&pid_file, &status_file).await;
                 break;
             }
 
-            thread::sleep(Duration::from_millis(10));
+            tokio::time::sleep(Duration::from_millis(10)).await;
         }
 
         let stdout = fs::read_to_string(&stdout_file).unwrap_or_default();
@@ -113,9 +253,15 @@ impl PersistentShell {
             0
         };
 
+        self.update_state(&cwd_file, &env_file);
+
         let _ = fs::remove_file(&stdout_file);
         let _ = fs::remove_file(&stderr_file);
         let _ = fs::remove_file(&status_file);
+        let _ = fs::remove_file(&pid_file);
+        let _ = fs::remove_file(&cwd_file);
+        let _ = fs::remove_file(&env_file);
+        let _ = fs::remove_file(&export_file);
 
         let end_time_ms = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -127,12 +273,53 @@ impl PersistentShell {
             stderr,
             exit_code,
             interrupted,
+            cancelled,
             start_time: start_time_ms,
             end_time: end_time_ms,
         })
     }
 
-    fn kill_children(&self) -> Result<()> {
+    /// Record the cwd and environment dumped by the last command. A command
+    /// that exited early or was killed leaves the previous state in place.
+    fn update_state(&self, cwd_file: &Path, env_file: &Path) {
+        let mut state = self.state.lock().unwrap();
+        if let Ok(cwd) = fs::read_to_string(cwd_file) {
+            let cwd = cwd.trim_end_matches('\n');
+            if !cwd.is_empty() {
+                state.cwd = cwd.to_string();
+            }
+        }
+        if let Ok(env) = fs::read(env_file) {
+            if !env.is_empty() {
+                state.env_changes = env_changes(&self.base_env, &String::from_utf8_lossy(&env));
+            }
+        }
+    }
+
+    /// Terminate the running command's process group and wait for the shell
+    /// to record its exit status, so the next command starts from a clean state
+    async fn kill_command(&self, pid_file: &Path, status_file: &Path) {
+        let pgid = fs::read_to_string(pid_file)
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok());
+
+        let Some(pgid) = pgid else {
+            // The command has not been started yet; fall back to the shell's children
+            self.kill_children();
+            return;
+        };
+
+        signal_process_group(pgid, "TERM").await;
+        if wait_for_file(status_file, Duration::from_millis(KILL_GRACE_MS)).await {
+            return;
+        }
+        signal_process_group(pgid, "KILL").await;
+        if !wait_for_file(status_file, Duration::from_millis(KILL_GRACE_MS)).await {
+            log::warn!("Shell did not report status after killing process group {}", pgid);
+        }
+    }
+
+    fn kill_children(&self) {
         let child_guard = self.child.lock().unwrap();
         if let Some(ref child) = *child_guard {
             let pid = child.id();
@@ -141,7 +328,6 @@ impl PersistentShell {
                 .arg(pid.to_string())
                 .output();
         }
-        Ok(())
     }
 
     fn close(&self) -> Result<()> {
@@ -162,23 +348,122 @@ impl Drop for PersistentShell {
     }
 }
 
-fn shell_quote(s: &str) -> String {
+/// Diff a NUL-separated `env -0` dump against the spawn-time environment
+fn env_changes(base: &HashMap<String, String>, dump: &str) -> BTreeMap<String, String> {
+    dump.split('\0')
+        .filter_map(|entry| entry.split_once('='))
+        .filter(|(name, _)| !SHELL_MANAGED_VARS.contains(name))
+        .filter(|(name, value)| base.get(*name).map(String::as_str) != Some(*value))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+fn file_has_content(path: &Path) -> bool {
+    fs::metadata(path).ok().is_some_and(|m| m.len() > 0)
+}
+
+async fn wait_for_file(path: &Path, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if file_has_content(path) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    file_has_content(path)
+}
+
+async fn signal_process_group(pgid: u32, signal: &str) {
+    let _ = tokio::process::Command::new("kill")
+        .arg(format!("-{}", signal))
+        .arg("--")
+        .arg(format!("-{}", pgid))
+        .output()
+        .await;
+}
+
+/// Run a command on the session's remote target. Each command gets a fresh
+/// shell in the target root (or `workdir`), so `cd` and `export` do not carry over.
+fn execute_remote(
+    target: &dyn ExecBackend,
+    command: &str,
+    timeout_ms: u64,
+    workdir: Option<&str>,
+) -> Result<CommandResult> {
+    let now_ms = || {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64
+    };
+    let start_time = now_ms();
+    let output = target.run(command, workdir, Duration::from_millis(timeout_ms))?;
+    Ok(CommandResult {
+        stdout: output.stdout_text(),
+        stderr: output.stderr,
+        exit_code: output.exit_code,
+        interrupted: output.timed_out,
+        cancelled: false,
+        start_time,
+        end_time: now_ms(),
+    })
+}
+
+/// `s` as one single-quoted shell word
+pub fn shell_quote(s: &str) -> String {
     format!("'{}'", s.replace('\'', r"'\''"))
 }
 
-lazy_static::lazy_static! {
-    static ref GLOBAL_SHELL: Mutex<Option<Arc<PersistentShell>>> = Mutex::new(None);
+/// Persistent shell of each session, by session id, so one session's `cd`
+/// and `export` never reach another
+static SHELLS: LazyLock<Mutex<HashMap<String, Arc<PersistentShell>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn shells() -> std::sync::MutexGuard<'static, HashMap<String, Arc<PersistentShell>>> {
+    SHELLS.lock().unwrap_or_else(|e| e.into_inner())
 }
 
-fn get_persistent_shell(cwd: &str) -> Result<Arc<PersistentShell>> {
-    let mut shell_guard = GLOBAL_SHELL.lock().unwrap();
+fn get_persistent_shell(session_id: &str, cwd: &str) -> Result<Arc<PersistentShell>> {
+    let mut shells = shells();
+    if let Some(shell) = shells.get(session_id) {
+        return Ok(Arc::clone(shell));
+    }
+    let shell = PersistentShell::new(cwd).inspect_err(|e| {
+        crate::health::record_error(crate::health::Subsystem::Shell, format!("{:#}", e));
+    })?;
+    let shell = Arc::new(shell);
+    shells.insert(session_id.to_string(), Arc::clone(&shell));
+    Ok(shell)
+}
 
-    if shell_guard.is_none() {
-        let shell = PersistentShell::new(cwd)?;
-        *shell_guard = Some(Arc::new(shell));
+/// Current state of the session's shell, or `None` if it has run no command yet
+pub fn shell_state(session_id: &str) -> Option<ShellState> {
+    shells().get(session_id).map(|shell| shell.state())
+}
+
+/// Directory a command runs in: `workdir` when given, otherwise the working
+/// directory of the current session's shell, otherwise `root`
+pub fn command_dir(root: &Path, workdir: Option<&str>) -> PathBuf {
+    match workdir {
+        Some(workdir) => root.join(workdir),
+        None => current_tool_session()
+            .and_then(|session_id| shell_state(&session_id))
+            .map(|state| PathBuf::from(state.cwd))
+            .unwrap_or_else(|| root.to_path_buf()),
     }
+}
+
+/// Whether every started shell is still running, or `None` if none was started
+pub fn shell_alive() -> Option<bool> {
+    let shells = shells();
+    (!shells.is_empty()).then(|| shells.values().all(|shell| shell.is_alive()))
+}
 
-    Ok(Arc::clone(shell_guard.as_ref().unwrap()))
+/// Kill the session's running command and close its shell
+pub fn close_shell(session_id: &str) {
+    cancel_running_command(session_id);
+    if let Some(shell) = shells().remove(session_id) {
+        let _ = shell.close();
+    }
 }
 
 // Bash tool implementation
@@ -218,6 +503,9 @@ pub struct BashResult {
     pub end_time: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub interrupted: Option<bool>,
+    /// Shell working directory after the command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
     /// Summary of the result
     pub response_summary: String,
 }
@@ -253,35 +541,29 @@ impl BashTool {
             .to_string()
     }
 
-    pub fn is_banned(&self, command: &str) -> bool {
+    /// First banned command run anywhere in the line, substitutions included
+    fn banned_command(&self, command: &str) -> Option<String> {
         if is_full_access() {
-            return false;
+            return None;
         }
-        let primary = self.get_primary_command(command);
-        self.banned_commands
-            .iter()
-            .any(|banned| primary == *banned || primary.starts_with(&format!("{} ", banned)))
+        self.assess(command, None)
+            .commands
+            .into_iter()
+            .find(|name| self.banned_commands.contains(name))
     }
 
-    pub fn is_safe_read_only(&self, command: &str) -> bool {
-        let cmd = command.trim();
-        self.safe_read_only_commands.iter().any(|safe| {
-            if cmd == *safe {
-                return true;
-            }
-            if cmd.starts_with(safe) {
-                let remaining = &cmd[safe.len()..];
-                return remaining.is_empty()
-                    || remaining.starts_with(' ')
-                    || remaining.starts_with('\t');
-            }
-            false
-        })
+    /// Classify every segment of the command against the read-only list
+    pub fn assess(&self, command: &str, workdir: Option<&str>) -> CommandAssessment {
+        let root = PathPolicy::new()
+            .map(|p| p.root().to_path_buf())
+            .or_else(|_| std::env::current_dir())
+            .unwrap_or_default();
+        let cwd = command_dir(&root, workdir);
+        shell_safety::assess(command, &self.safe_read_only_commands, &root, &cwd)
     }
 
-    pub fn run_bash(&self, request: &BashRequest) -> Result<BashResult> {
-        if self.is_banned(&request.command) {
-            let primary = self.get_primary_command(&request.command);
+    pub async fn run_bash(&self, request: &BashRequest) -> Result<BashResult> {
+        if let Some(primary) = self.banned_command(&request.command) {
             return Ok(BashResult {
                 command: request.command.clone(),
                 exit_code: None,
@@ -292,53 +574,104 @@ impl BashTool {
                 start_time: None,
                 end_time: None,
                 interrupted: None,
+                cwd: None,
                 response_summary: format!("Command '{}' is not allowed", primary),
             });
         }
 
-        if self.is_safe_read_only(&request.command) {
-            return self.execute_command(request, true);
+        let assessment = self.assess(&request.command, request.workdir.as_deref());
+        if assessment.risk == CommandRisk::ReadOnly {
+            return self.execute_command(request, true).await;
+        }
+        if overlay::is_active() {
+            let message = "Overlay mode only allows read-only commands; make file changes with the file tools".to_string();
+            return Ok(BashResult {
+                command: request.command.clone(),
+                exit_code: None,
+                stdout: message.clone(),
+                stderr: String::new(),
+                requires_confirmation: false,
+                executed: false,
+                start_time: None,
+                end_time: None,
+                interrupted: None,
+                cwd: None,
+                response_summary: message,
+            });
         }
 
         // Check if confirmation is provided
         if request.confirmed {
-            return self.execute_command(request, true);
+            return self.execute_command(request, true).await;
         }
 
         let primary = self.get_primary_command(&request.command);
         Ok(BashResult {
             command: request.command.clone(),
             exit_code: None,
-            stdout: format!("Command '{}' requires confirmation. Please respond with 'yes' to execute or 'no' to cancel.", primary),
+            stdout: format!(
+                "Command '{}' requires confirmation ({}). Please respond with 'yes' to execute or 'no' to cancel.",
+                primary,
+                assessment.reasons.join("; ")
+            ),
             stderr: String::new(),
             requires_confirmation: true,
             executed: false,
             start_time: None,
             end_time: None,
             interrupted: None,
+            cwd: None,
             response_summary: "Requires confirmation".to_string(),
         })
     }
 
-    fn execute_command(&self, request: &BashRequest, _confirmed: bool) -> Result<BashResult> {
+    async fn execute_command(&self, request: &BashRequest, _confirmed: bool) -> Result<BashResult> {
         let command_str = request.command.trim();
         let timeout = request.timeout.unwrap_or(1800000).min(600000);
 
-        let workdir = if let Some(wd) = request.workdir.as_ref() {
-            let policy = PathPolicy::new()?;
-            policy.resolve(wd)?.to_string_lossy().to_string()
-        } else {
-            std::env::current_dir().unwrap().to_string_lossy().to_string()
+        let workdir = match request.workdir.as_ref() {
+            Some(wd) => {
+                let policy = PathPolicy::new()?;
+                Some(policy.resolve(wd)?)
+            }
+            None => None,
         };
 
-        let shell = get_persistent_shell(&workdir)?;
-        let result = shell.exec(command_str, timeout)?;
+        let vars = session_vars::current();
+        let (result, shell_cwd) = match backend::current() {
+            Some(target) => {
+                let workdir = workdir.map(|wd| target.target_path(&wd)).transpose()?;
+                let root = target.root().to_string();
+                let command = format!("{}{}", session_vars::export_script(&vars), command_str);
+                let remote_workdir = workdir.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    execute_remote(target.as_ref(), &command, timeout, remote_workdir.as_deref())
+                })
+                .await
+                .context("Remote command panicked")??;
+                (result, workdir.unwrap_or(root))
+            }
+            None => {
+                let workdir = workdir.map(|wd| wd.to_string_lossy().to_string());
+                let cwd = std::env::current_dir()?.to_string_lossy().to_string();
+                let session_id = current_tool_session().unwrap_or_default();
+                let shell = get_persistent_shell(&session_id, &cwd)?;
+                let running = RunningCommand::start(&session_id);
+                let result = shell.exec(command_str, timeout, workdir.as_deref(), &vars, &running.cancel).await?;
+                (result, shell.state().cwd)
+            }
+        };
 
-        let stdout = truncate_output(&result.stdout);
-        let stderr = truncate_output(&result.stderr);
+        let stdout = truncate_output(&normalize_terminal_output(&result.stdout));
+        let stderr = truncate_output(&normalize_terminal_output(&result.stderr));
 
         let mut error_message = stderr.clone();
-        if result.interrupted {
+        if result.cancelled {
+            if !error_message.is_empty() {
+                error_message.push('\n');
+            }
+            error_message.push_str("Command was cancelled by the user before completion");
+        } else if result.interrupted {
             if !error_message.is_empty() {
                 error_message.push('\n');
             }
@@ -363,20 +696,22 @@ impl BashTool {
         // Calculate summary (first 3 lines)
         let response_summary = final_stdout.lines().take(3).collect::<Vec<_>>().join("\n");
 
+        if final_stdout.is_empty() {
+            final_stdout.push_str("no output");
+        }
+        final_stdout.push_str(&format!("\ncwd: {}", shell_cwd));
+
         Ok(BashResult {
             command: request.command.clone(),
             exit_code: Some(result.exit_code),
-            stdout: if final_stdout.is_empty() {
-                "no output".to_string()
-            } else {
-                final_stdout
-            },
+            stdout: final_stdout,
             stderr: String::new(),
             requires_confirmation: false,
             executed: true,
             start_time: Some(result.start_time),
             end_time: Some(result.end_time),
             interrupted: Some(result.interrupted),
+            cwd: Some(shell_cwd),
             response_summary: if response_summary.is_empty() {
                 "no output".to_string()
             } else {
@@ -400,7 +735,7 @@ impl BashTool {
                         },
                         "workdir": {
                             "type": "string",
-                            "description": "Optional working directory for command. Defaults to current working directory."
+                            "description": "Optional working directory for this command only. Defaults to the shell's current directory, which persists across commands (as do exported variables)."
                         },
                         "timeout": {
                             "type": "integer",
@@ -469,8 +804,6 @@ impl Default for BashTool {
                 "groups".to_string(),
                 "env".to_string(),
                 "printenv".to_string(),
-                "set".to_string(),
-                "unset".to_string(),
                 "which".to_string(),
                 "type".to_string(),
                 "whereis".to_string(),
@@ -480,12 +813,8 @@ impl Default for BashTool {
                 "df".to_string(),
                 "du".to_string(),
                 "free".to_string(),
-                "top".to_string(),
                 "ps".to_string(),
-                "kill".to_string(),
-                "killall".to_string(),
                 "nice".to_string(),
-                "nohup".to_string(),
                 "time".to_string(),
                 "timeout".to_string(),
                 "git status".to_string(),
@@ -532,9 +861,31 @@ impl ToolSpec for BashTool {
         self.to_tool_definition_json()
     }
 
-    fn run(&self, mut args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let command = arg_summary::first_line(&args.command);
+        Some(match &args.workdir {
+            Some(dir) => format!("{} (in {})", command, arg_summary::path(dir)),
+            None => command,
+        })
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        block_on(self.run_async(args, confirmed))?
+    }
+
+    fn is_async(&self) -> bool {
+        true
+    }
+
+    fn run_async(&self, mut args: Self::Args, confirmed: bool) -> BoxFuture<'static, Result<ToolResult>> {
         args.confirmed = confirmed;
-        let result = self.run_bash(&args)?;
+        Box::pin(self.clone().run_command(args))
+    }
+}
+
+impl BashTool {
+    async fn run_command(self, args: BashRequest) -> Result<ToolResult> {
+        let result = self.run_bash(&args).await?;
         let mut tr = ToolResult::ok(
             self.tool_name.clone(),
             self.kind(),
@@ -554,3 +905,92 @@ impl ToolSpec for BashTool {
         Ok(tr)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[tokio::test]
+    async fn cancel_kills_running_command_and_keeps_shell_usable() {
+        let cwd = std::env::temp_dir();
+        let shell = PersistentShell::new(&cwd.to_string_lossy()).unwrap();
+
+        let none = SessionVars::new();
+        let running = RunningCommand::start("cancel-test-session");
+        let canceller = thread::spawn(|| {
+            thread::sleep(Duration::from_millis(300));
+            // Another session's cancel leaves the command running
+            cancel_running_command("other-session");
+            thread::sleep(Duration::from_millis(200));
+            cancel_running_command("cancel-test-session");
+        });
+        let started = Instant::now();
+        let result = shell.exec("sleep 5 | cat", 60_000, None, &none, &running.cancel).await.unwrap();
+        canceller.join().unwrap();
+        drop(running);
+        assert!(!RUNNING_COMMANDS.lock().unwrap().contains_key("cancel-test-session"));
+
+        assert!(result.interrupted);
+        assert!(result.cancelled);
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        let next = shell.exec("echo ok", 60_000, None, &none, &CancelToken::new()).await.unwrap();
+        assert!(!next.interrupted);
+        assert_eq!(next.stdout.trim(), "ok");
+        assert_eq!(next.exit_code, 0);
+    }
+
+    #[test]
+    fn each_session_gets_its_own_shell() {
+        let cwd = std::env::temp_dir().to_string_lossy().to_string();
+        let a = get_persistent_shell("shell-session-a", &cwd).unwrap();
+        let b = get_persistent_shell("shell-session-b", &cwd).unwrap();
+        assert!(!Arc::ptr_eq(&a, &b));
+        assert!(Arc::ptr_eq(&a, &get_persistent_shell("shell-session-a", &cwd).unwrap()));
+
+        close_shell("shell-session-a");
+        assert!(!a.is_alive());
+        assert!(b.is_alive());
+        assert!(shell_state("shell-session-a").is_none());
+        close_shell("shell-session-b");
+    }
+
+    #[tokio::test]
+    async fn cwd_and_exports_persist_across_commands() {
+        let cwd = fs::canonicalize(std::env::temp_dir()).unwrap();
+        let sub = cwd.join(format!("carrycode-shell-state-{}", std::process::id()));
+        fs::create_dir_all(&sub).unwrap();
+        let sub_str = sub.to_string_lossy().to_string();
+        let shell = PersistentShell::new(&cwd.to_string_lossy()).unwrap();
+        let none = SessionVars::new();
+        let cancel = CancelToken::new();
+
+        let first = shell
+            .exec(&format!("cd {} && export CARRY_TEST_VAR=one", shell_quote(&sub_str)), 60_000, None, &none, &cancel)
+            .await
+            .unwrap();
+        assert_eq!(first.exit_code, 0);
+        let state = shell.state();
+        assert_eq!(state.cwd, sub_str);
+        assert_eq!(state.env_changes.get("CARRY_TEST_VAR").map(String::as_str), Some("one"));
+
+        let second = shell.exec("pwd; echo $CARRY_TEST_VAR", 60_000, None, &none, &cancel).await.unwrap();
+        assert_eq!(second.stdout, format!("{}\none\n", sub_str));
+
+        // An explicit workdir does not move the shell
+        let third = shell.exec("pwd", 60_000, Some(&cwd.to_string_lossy()), &none, &cancel).await.unwrap();
+        assert_eq!(third.stdout.trim(), cwd.to_string_lossy());
+        assert_eq!(shell.state().cwd, sub_str);
+
+        // Session variables reach the command but are not kept by the shell
+        let vars: SessionVars = [("CARRY_TICKET".to_string(), "PROJ-1".to_string())].into();
+        let fourth = shell.exec("echo $CARRY_TICKET", 60_000, None, &vars, &cancel).await.unwrap();
+        assert_eq!(fourth.stdout.trim(), "PROJ-1");
+        assert!(!shell.state().env_changes.contains_key("CARRY_TICKET"));
+        let fifth = shell.exec("echo \"[$CARRY_TICKET]\"", 60_000, None, &none, &cancel).await.unwrap();
+        assert_eq!(fifth.stdout.trim(), "[]");
+
+        let _ = fs::remove_dir_all(sub);
+    }
+}
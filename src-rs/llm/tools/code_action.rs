@@ -0,0 +1,389 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::serde_util::deserialize_usize_opt_lax;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
+use crate::lsp::protocol::CodeAction;
+use crate::lsp::workspace_edit;
+use crate::lsp::LspManager;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+/// Tool for listing and applying LSP code actions (quick fixes, refactorings)
+#[derive(Clone)]
+pub struct CodeActionTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+    lsp_manager: Arc<Mutex<Option<Arc<LspManager>>>>,
+}
+
+impl std::fmt::Debug for CodeActionTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CodeActionTool")
+            .field("tool_name", &self.tool_name)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Code action request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeActionRequest {
+    /// File with the diagnostic or code to act on
+    pub file_path: String,
+    /// Line of the location (1-based)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub line: Option<usize>,
+    /// Column of the location (1-based, in characters)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub column: Option<usize>,
+    /// Number of the action to apply, from a previous listing; omit to list
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub action: Option<usize>,
+    /// Only return actions of this kind, e.g. `quickfix`
+    #[serde(default)]
+    pub only: Option<String>,
+}
+
+/// A code action as shown to the model
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CodeActionEntry {
+    /// 1-based number used to select the action
+    pub number: usize,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    pub preferred: bool,
+    /// Whether the action carries an edit this tool can apply
+    pub applicable: bool,
+}
+
+/// Result of listing or applying code actions
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CodeActionResult {
+    pub file_path: String,
+    pub actions: Vec<CodeActionEntry>,
+    /// Title of the selected action
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied: Option<String>,
+    /// Per-file diff of the selected action
+    pub files: Vec<DiffStats>,
+    /// Combined unified diff, shown before confirmation
+    pub preview: String,
+    /// Whether the edits were written (false while listing or awaiting confirmation)
+    pub executed: bool,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl CodeActionTool {
+    /// Create a new CodeActionTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self::from_config(&config),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new CodeActionTool from a specific AppConfig
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_code_action.tool_name.clone(),
+            description: config.tool_code_action.description.clone(),
+            lsp_manager: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn get_or_init_lsp_manager(&self) -> Result<Arc<LspManager>> {
+        {
+            let manager_lock = self.lsp_manager.lock().unwrap();
+            if let Some(manager) = manager_lock.as_ref() {
+                return Ok(Arc::clone(manager));
+            }
+        }
+
+        let manager = LspManager::shared().await?;
+        *self.lsp_manager.lock().unwrap() = Some(Arc::clone(&manager));
+        Ok(manager)
+    }
+
+    /// Ask the language server for the actions at the requested location
+    async fn request_actions(&self, policy: &PathPolicy, request: &CodeActionRequest) -> Result<Vec<CodeAction>> {
+        let path = policy.resolve(&request.file_path)?;
+        if !path.is_file() {
+            anyhow::bail!("File not found: {}", request.file_path);
+        }
+        let line = request.line.context("line is required")?;
+        let column = request.column.unwrap_or(1);
+
+        let content = fs::read_to_string(&path).context("Failed to read file")?;
+        let position = workspace_edit::position_at(&content, line, column)
+            .with_context(|| format!("Line {} is past the end of {}", line, request.file_path))?;
+        let only = request
+            .only
+            .as_deref()
+            .map(str::trim)
+            .filter(|k| !k.is_empty())
+            .map(|k| vec![k.to_string()]);
+
+        let manager = self.get_or_init_lsp_manager().await?;
+        manager
+            .code_actions(&path.to_string_lossy(), position, only)
+            .await
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "The file to act on. Must be within current working directory."
+                        },
+                        "line": {
+                            "type": "integer",
+                            "description": "Line of the diagnostic or code (1-based)."
+                        },
+                        "column": {
+                            "type": "integer",
+                            "description": "Column on that line (1-based). Defaults to 1."
+                        },
+                        "action": {
+                            "type": "integer",
+                            "description": "Number of the action to apply, from a previous call at the same location. Omit to list the available actions."
+                        },
+                        "only": {
+                            "type": "string",
+                            "description": "Only return actions of this kind, e.g. \"quickfix\" or \"refactor\"."
+                        }
+                    },
+                    "required": ["file_path", "line"]
+                }
+            }
+        })
+    }
+}
+
+/// Numbered entries for a list of actions; disabled actions are left out
+fn list_entries(actions: &[CodeAction]) -> Vec<(CodeActionEntry, &CodeAction)> {
+    actions
+        .iter()
+        .filter(|a| a.disabled.is_none())
+        .enumerate()
+        .map(|(i, a)| {
+            (
+                CodeActionEntry {
+                    number: i + 1,
+                    title: a.title.clone(),
+                    kind: a.kind.clone(),
+                    preferred: a.is_preferred.unwrap_or(false),
+                    applicable: a.edit.is_some(),
+                },
+                a,
+            )
+        })
+        .collect()
+}
+
+fn format_entries<'a>(entries: impl IntoIterator<Item = &'a CodeActionEntry>) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&format!("{}. {}", entry.number, entry.title));
+        if let Some(kind) = &entry.kind {
+            out.push_str(&format!(" [{}]", kind));
+        }
+        if entry.preferred {
+            out.push_str(" (preferred)");
+        }
+        if !entry.applicable {
+            out.push_str(" (runs a server command; not supported)");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+impl Default for CodeActionTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "code_action".to_string(),
+            description: "Lists and applies language server code actions such as quick fixes.".to_string(),
+            lsp_manager: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl ToolSpec for CodeActionTool {
+    type Args = CodeActionRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Edit
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Edited
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let position = arg_summary::position(&args.file_path, args.line, args.column);
+        Some(match args.action {
+            Some(action) => format!("{} (action {})", position, action),
+            None => position,
+        })
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        let policy = PathPolicy::new()?;
+        let self_clone = self.clone();
+        let (request, policy_for_lsp) = (args.clone(), policy.clone());
+        let actions = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(async move { self_clone.request_actions(&policy_for_lsp, &request).await })
+        })?;
+
+        let listed = list_entries(&actions);
+        let listing = format_entries(listed.iter().map(|(e, _)| e));
+
+        let Some(number) = args.action else {
+            let response_summary = format!("{} action(s)", listed.len());
+            let stdout = if listed.is_empty() {
+                "No code actions available at this location.".to_string()
+            } else {
+                listing
+            };
+            let result = CodeActionResult {
+                file_path: args.file_path.clone(),
+                actions: listed.into_iter().map(|(e, _)| e).collect(),
+                applied: None,
+                files: Vec::new(),
+                preview: String::new(),
+                executed: false,
+                response_summary: response_summary.clone(),
+            };
+            return Ok(ToolResult::ok(
+                self.tool_name.clone(),
+                self.kind(),
+                self.operation(),
+                stdout,
+                serde_json::to_value(result)?,
+            )
+            .with_summary(response_summary));
+        };
+
+        let (entry, action) = listed
+            .iter()
+            .find(|(e, _)| e.number == number)
+            .with_context(|| {
+                format!(
+                    "No code action {} at this location. Available actions:\n{}",
+                    number, listing
+                )
+            })?;
+        let edit = action.edit.as_ref().with_context(|| {
+            format!(
+                "Action '{}' runs a server command instead of returning an edit, which is not supported",
+                entry.title
+            )
+        })?;
+
+        let changes = workspace_edit::prepare(&policy, edit)?;
+        if confirmed {
+            workspace_edit::apply(&changes)?;
+        }
+        let files: Vec<DiffStats> = changes.into_iter().map(|c| c.diff_stats).collect();
+        let preview = workspace_edit::preview(&files);
+        let response_summary = format!(
+            "{} file(s){}",
+            files.len(),
+            if confirmed { "" } else { " (requires confirmation)" }
+        );
+        let stdout = if confirmed {
+            format!("Applied '{}':\n\n{}", entry.title, preview)
+        } else {
+            format!(
+                "Applying '{}' requires confirmation:\n\n{}",
+                entry.title, preview
+            )
+        };
+        let result = CodeActionResult {
+            file_path: args.file_path.clone(),
+            actions: Vec::new(),
+            applied: Some(entry.title.clone()),
+            files,
+            preview,
+            executed: confirmed,
+            response_summary: response_summary.clone(),
+        };
+        let mut tr = ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            serde_json::to_value(result)?,
+        )
+        .with_summary(response_summary);
+        tr.requires_confirmation = !confirmed;
+        tr.executed = confirmed;
+        Ok(tr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_enabled_actions_with_stable_numbers() {
+        let response = serde_json::json!([
+            { "title": "Import `HashMap`", "kind": "quickfix", "isPreferred": true,
+              "edit": { "changes": {} } },
+            { "title": "Extract function", "kind": "refactor.extract", "disabled": { "reason": "no selection" } },
+            { "title": "Run cargo fix", "command": "rust-analyzer.runFix", "arguments": [] }
+        ]);
+        let actions: Vec<CodeAction> = response
+            .as_array()
+            .unwrap()
+            .iter()
+            .cloned()
+            .filter_map(CodeAction::from_response_item)
+            .collect();
+        assert_eq!(actions.len(), 3);
+
+        let listed = list_entries(&actions);
+        assert_eq!(
+            format_entries(listed.iter().map(|(e, _)| e)),
+            "1. Import `HashMap` [quickfix] (preferred)\n2. Run cargo fix (runs a server command; not supported)\n"
+        );
+    }
+}
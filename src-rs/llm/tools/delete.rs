@@ -0,0 +1,266 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::file_lock;
+use crate::llm::utils::overlay;
+use crate::llm::utils::path_policy::{self, PathPolicy};
+use crate::llm::utils::tool_access::current_tool_session;
+use crate::llm::utils::trash::Trash;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Delete tool; deleted paths are moved to the session trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+}
+
+/// Delete request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeleteRequest {
+    /// File or directory to delete
+    pub path: String,
+    /// Required to delete a directory with its contents
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+/// Result of a delete
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteResult {
+    pub path: String,
+    /// Trash entry id, usable for restoring the path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trash_id: Option<String>,
+    /// Whether the delete was performed (false while awaiting confirmation)
+    pub executed: bool,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl DeleteTool {
+    /// Create a new DeleteTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self {
+                tool_name: config.tool_delete.tool_name,
+                description: config.tool_delete.description,
+            },
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new DeleteTool from a specific AppConfig
+    #[allow(dead_code)]
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_delete.tool_name.clone(),
+            description: config.tool_delete.description.clone(),
+        }
+    }
+
+    /// Move `request.path` to the trash of `session_id`
+    ///
+    /// Directories need `recursive` and confirmation. The workspace root,
+    /// anything in `.git`, `.carry` and the trash itself cannot be deleted.
+    fn run_delete(
+        &self,
+        policy: &PathPolicy,
+        trash: &Trash,
+        session_id: &str,
+        request: &DeleteRequest,
+        confirmed: bool,
+    ) -> Result<DeleteResult> {
+        let path = policy.resolve(&request.path)?;
+        let current = overlay::read_path(&path);
+
+        if current.symlink_metadata().is_err() {
+            anyhow::bail!("Path not found: {}", request.path);
+        }
+        let protected = path == policy.root()
+            || path.starts_with(trash.base())
+            || trash.base().starts_with(&path)
+            || path_policy::is_git_metadata(&path);
+        if protected {
+            anyhow::bail!("Path '{}' is protected and cannot be deleted", request.path);
+        }
+
+        if current.is_dir() {
+            if !request.recursive {
+                anyhow::bail!(
+                    "Path '{}' is a directory. Set recursive to true to delete it with its contents.",
+                    request.path
+                );
+            }
+            if !confirmed {
+                return Ok(DeleteResult {
+                    path: request.path.clone(),
+                    trash_id: None,
+                    executed: false,
+                    response_summary: "Requires confirmation".to_string(),
+                });
+            }
+        }
+
+        file_lock::lock_for_current_session(&path)?;
+        checkpoint::record_before_change(&path);
+        file_activity::record_edited(&path);
+        if let Some(overlay) = overlay::Overlay::current() {
+            overlay.remove(&path)?;
+            return Ok(DeleteResult {
+                path: request.path.clone(),
+                trash_id: None,
+                executed: true,
+                response_summary: "deleted in overlay".to_string(),
+            });
+        }
+        let entry = trash.put(session_id, &path)?;
+        Ok(DeleteResult {
+            path: request.path.clone(),
+            trash_id: Some(entry.id),
+            executed: true,
+            response_summary: "moved to trash".to_string(),
+        })
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "The file or directory to delete. Must be within current working directory."
+                        },
+                        "recursive": {
+                            "type": "boolean",
+                            "description": "Required to delete a directory and its contents. Requires user confirmation. Defaults to false."
+                        }
+                    },
+                    "required": ["path"]
+                }
+            }
+        })
+    }
+}
+
+impl Default for DeleteTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "delete".to_string(),
+            description: "Deletes a file or directory by moving it to the session trash.".to_string(),
+        }
+    }
+}
+
+impl ToolSpec for DeleteTool {
+    type Args = DeleteRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Delete
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Edited
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let path = arg_summary::path(&args.path);
+        Some(if args.recursive { format!("{} (recursive)", path) } else { path })
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        let policy = PathPolicy::new()?;
+        let trash = Trash::current()?;
+        let session_id = current_tool_session().unwrap_or_else(|| "default".to_string());
+        let result = self.run_delete(&policy, &trash, &session_id, &args, confirmed)?;
+        let response_summary = result.response_summary.clone();
+        let executed = result.executed;
+        let stdout = match &result.trash_id {
+            Some(id) => format!(
+                "Moved {} to trash (entry {}). It can be restored until the trash is purged.",
+                result.path, id
+            ),
+            None => format!(
+                "Deleting directory {} with its contents requires confirmation.",
+                result.path
+            ),
+        };
+        let data = serde_json::to_value(result)?;
+        let mut tr = ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary);
+        tr.requires_confirmation = !executed;
+        tr.executed = executed;
+        Ok(tr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn refuses_to_delete_repository_metadata() {
+        let workspace = FakeWorkspace::new()
+            .file(".git/HEAD", "ref: refs/heads/main")
+            .dir(".git/objects")
+            .file("a.txt", "a")
+            .build()
+            .unwrap();
+        let root = workspace.path();
+        let policy = PathPolicy::with_root(root.to_path_buf());
+        let trash = Trash::for_workspace(root);
+        let tool = DeleteTool::default();
+        let delete = |path: &str, recursive: bool| {
+            let request = DeleteRequest {
+                path: path.to_string(),
+                recursive,
+            };
+            tool.run_delete(&policy, &trash, "s1", &request, true)
+        };
+
+        for path in [".git", ".git/HEAD", ".git/objects", "./.git/../.git/HEAD"] {
+            let err = delete(path, true).unwrap_err();
+            assert!(err.to_string().contains("protected"), "{}: {}", path, err);
+        }
+        assert!(root.join(".git/HEAD").exists());
+        assert!(delete("a.txt", false).unwrap().executed);
+    }
+}
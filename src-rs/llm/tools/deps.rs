@@ -0,0 +1,693 @@
+use crate::llm::config::AppConfig;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::utils::path_policy::PathPolicy;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Manifest file names the tool understands, with their ecosystem
+const MANIFESTS: &[(&str, &str)] = &[
+    ("Cargo.toml", "cargo"),
+    ("package.json", "npm"),
+    ("pyproject.toml", "python"),
+    ("go.mod", "go"),
+];
+
+/// Makefile names, whose targets are listed as scripts
+const MAKEFILES: &[&str] = &["Makefile", "makefile", "GNUmakefile"];
+
+/// Dependency manifest inspection tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DepsTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+}
+
+/// Deps request parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepsRequest {
+    /// A manifest, or a directory whose manifests are read (defaults to the workspace root)
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// A direct dependency
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dependency {
+    pub name: String,
+    /// Version requirement, or where the dependency comes from (`path:..`, `git:..`, `workspace`)
+    pub version: Option<String>,
+    /// Section it is declared in, such as `dependencies`, `dev-dependencies` or `optional:test`
+    pub kind: String,
+}
+
+/// A script, task or entry point declared by a manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Script {
+    pub name: String,
+    /// What it runs
+    pub command: String,
+}
+
+/// What one manifest declares
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ManifestDeps {
+    /// Path relative to the workspace
+    pub path: String,
+    /// Package ecosystem, such as `cargo` or `npm`
+    pub ecosystem: String,
+    /// Package or module name
+    pub package: Option<String>,
+    pub version: Option<String>,
+    pub dependencies: Vec<Dependency>,
+    pub scripts: Vec<Script>,
+    /// Workspace member globs
+    pub workspace_members: Vec<String>,
+    /// Why the manifest could not be read
+    pub error: Option<String>,
+}
+
+/// Result of inspecting manifests
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DepsResult {
+    pub manifests: Vec<ManifestDeps>,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl DepsTool {
+    /// Create a new DepsTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self::from_config(&config),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new DepsTool from a specific AppConfig
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_deps.tool_name.clone(),
+            description: config.tool_deps.description.clone(),
+        }
+    }
+
+    /// Inspect the requested manifest, or the manifests of the requested directory
+    pub fn run_deps(&self, request: &DepsRequest) -> Result<DepsResult> {
+        let policy = PathPolicy::new()?;
+        let workspace = policy.resolve(".")?;
+        let target = policy.resolve(request.path.as_deref().unwrap_or("."))?;
+
+        let manifests: Vec<ManifestDeps> = if target.is_dir() {
+            let mut manifests: Vec<ManifestDeps> = MANIFESTS
+                .iter()
+                .map(|(name, _)| target.join(name))
+                .filter(|p| p.is_file())
+                .map(|file| inspect(&file, &workspace))
+                .collect();
+            manifests.extend(makefile(&target, &workspace));
+            if manifests.is_empty() {
+                anyhow::bail!(
+                    "No manifest in {}; use repo_stats to find the directories that have one",
+                    target.display()
+                );
+            }
+            manifests
+        } else if target.is_file() {
+            if ecosystem_of(&target).is_none() {
+                anyhow::bail!(
+                    "Not a supported manifest: {} (expected one of {})",
+                    target.display(),
+                    MANIFESTS.iter().map(|(n, _)| *n).collect::<Vec<_>>().join(", ")
+                );
+            }
+            vec![inspect(&target, &workspace)]
+        } else {
+            anyhow::bail!("Path not found: {}", target.display());
+        };
+
+        let dependencies: usize = manifests.iter().map(|m| m.dependencies.len()).sum();
+        let scripts: usize = manifests.iter().map(|m| m.scripts.len()).sum();
+        Ok(DepsResult {
+            response_summary: format!(
+                "{} manifest{}, {} dependencies, {} scripts",
+                manifests.len(),
+                if manifests.len() == 1 { "" } else { "s" },
+                dependencies,
+                scripts
+            ),
+            manifests,
+        })
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "A manifest file, or a directory whose Cargo.toml, package.json, pyproject.toml and go.mod are read. Defaults to the workspace root."
+                        }
+                    },
+                    "required": []
+                }
+            }
+        })
+    }
+}
+
+impl Default for DepsTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "deps".to_string(),
+            description: "Lists the direct dependencies and scripts declared by dependency manifests.".to_string(),
+        }
+    }
+}
+
+fn ecosystem_of(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    MANIFESTS.iter().find(|(n, _)| *n == name).map(|(_, e)| *e)
+}
+
+fn relative(path: &Path, workspace: &Path) -> String {
+    path.strip_prefix(workspace).unwrap_or(path).to_string_lossy().to_string()
+}
+
+/// Parse one manifest; a manifest that cannot be read is reported with its error
+fn inspect(path: &Path, workspace: &Path) -> ManifestDeps {
+    let ecosystem = ecosystem_of(path).unwrap_or_default();
+    let parsed = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))
+        .and_then(|content| match ecosystem {
+            "cargo" => parse_cargo(&content),
+            "npm" => parse_package_json(&content),
+            "python" => parse_pyproject(&content),
+            _ => Ok(parse_go_mod(&content)),
+        });
+    let mut manifest = parsed.unwrap_or_else(|e| ManifestDeps {
+        error: Some(format!("{:#}", e)),
+        ..Default::default()
+    });
+    manifest.path = relative(path, workspace);
+    manifest.ecosystem = ecosystem.to_string();
+    manifest
+}
+
+fn str_of(value: Option<&toml::Value>) -> Option<String> {
+    value.and_then(|v| v.as_str()).map(str::to_string)
+}
+
+fn string_list(value: Option<&toml::Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().filter_map(|i| i.as_str().map(str::to_string)).collect())
+        .unwrap_or_default()
+}
+
+fn parse_cargo(content: &str) -> Result<ManifestDeps> {
+    let doc: toml::Value = toml::from_str(content).context("Invalid Cargo.toml")?;
+    let package = doc.get("package");
+    let mut manifest = ManifestDeps {
+        package: str_of(package.and_then(|p| p.get("name"))),
+        version: str_of(package.and_then(|p| p.get("version"))),
+        workspace_members: string_list(doc.get("workspace").and_then(|w| w.get("members"))),
+        ..Default::default()
+    };
+
+    let mut sections: Vec<(String, &toml::Value)> = ["dependencies", "dev-dependencies", "build-dependencies"]
+        .iter()
+        .filter_map(|kind| doc.get(*kind).map(|table| (kind.to_string(), table)))
+        .collect();
+    if let Some(targets) = doc.get("target").and_then(|t| t.as_table()) {
+        for (cfg, target) in targets {
+            for kind in ["dependencies", "dev-dependencies", "build-dependencies"] {
+                if let Some(table) = target.get(kind) {
+                    sections.push((format!("{} ({})", kind, cfg), table));
+                }
+            }
+        }
+    }
+    if let Some(table) = doc.get("workspace").and_then(|w| w.get("dependencies")) {
+        sections.push(("workspace".to_string(), table));
+    }
+    for (kind, table) in sections {
+        for (name, spec) in table.as_table().into_iter().flatten() {
+            manifest.dependencies.push(Dependency {
+                name: name.clone(),
+                version: cargo_version(spec),
+                kind: kind.clone(),
+            });
+        }
+    }
+
+    for bin in doc.get("bin").and_then(|b| b.as_array()).into_iter().flatten() {
+        if let Some(name) = str_of(bin.get("name")) {
+            manifest.scripts.push(Script {
+                command: format!("cargo run --bin {}", name),
+                name,
+            });
+        }
+    }
+    Ok(manifest)
+}
+
+/// `1.0`, `{ version = "1.0" }`, `{ path = "../x" }`, `{ git = "..." }` or `{ workspace = true }`
+fn cargo_version(spec: &toml::Value) -> Option<String> {
+    if let Some(version) = spec.as_str() {
+        return Some(version.to_string());
+    }
+    let table = spec.as_table()?;
+    if let Some(version) = table.get("version").and_then(|v| v.as_str()) {
+        return Some(version.to_string());
+    }
+    if table.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+        return Some("workspace".to_string());
+    }
+    if let Some(path) = table.get("path").and_then(|p| p.as_str()) {
+        return Some(format!("path:{}", path));
+    }
+    table.get("git").and_then(|g| g.as_str()).map(|git| format!("git:{}", git))
+}
+
+fn parse_package_json(content: &str) -> Result<ManifestDeps> {
+    let doc: serde_json::Value = serde_json::from_str(content).context("Invalid package.json")?;
+    let text = |key: &str| doc.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let mut manifest = ManifestDeps {
+        package: text("name"),
+        version: text("version"),
+        ..Default::default()
+    };
+
+    for kind in ["dependencies", "devDependencies", "peerDependencies", "optionalDependencies"] {
+        for (name, version) in doc.get(kind).and_then(|d| d.as_object()).into_iter().flatten() {
+            manifest.dependencies.push(Dependency {
+                name: name.clone(),
+                version: version.as_str().map(str::to_string),
+                kind: kind.to_string(),
+            });
+        }
+    }
+    for (name, command) in doc.get("scripts").and_then(|s| s.as_object()).into_iter().flatten() {
+        if let Some(command) = command.as_str() {
+            manifest.scripts.push(Script {
+                name: name.clone(),
+                command: command.to_string(),
+            });
+        }
+    }
+    // `["packages/*"]` or `{ "packages": ["packages/*"] }`
+    let workspaces = doc.get("workspaces");
+    let workspaces = workspaces.and_then(|w| w.get("packages")).or(workspaces);
+    manifest.workspace_members = workspaces
+        .and_then(|w| w.as_array())
+        .map(|items| items.iter().filter_map(|i| i.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+    Ok(manifest)
+}
+
+fn parse_pyproject(content: &str) -> Result<ManifestDeps> {
+    let doc: toml::Value = toml::from_str(content).context("Invalid pyproject.toml")?;
+    let project = doc.get("project");
+    let tool = doc.get("tool");
+    let poetry = tool.and_then(|t| t.get("poetry"));
+    let mut manifest = ManifestDeps {
+        package: str_of(project.and_then(|p| p.get("name"))).or_else(|| str_of(poetry.and_then(|p| p.get("name")))),
+        version: str_of(project.and_then(|p| p.get("version")))
+            .or_else(|| str_of(poetry.and_then(|p| p.get("version")))),
+        ..Default::default()
+    };
+
+    // PEP 621 and PEP 735 list requirement strings such as `requests>=2.31`
+    let mut requirements: Vec<(String, String)> = string_list(project.and_then(|p| p.get("dependencies")))
+        .into_iter()
+        .map(|r| ("dependencies".to_string(), r))
+        .collect();
+    for (field, prefix) in [
+        (project.and_then(|p| p.get("optional-dependencies")), "optional"),
+        (doc.get("dependency-groups"), "group"),
+    ] {
+        for (group, items) in field.and_then(|f| f.as_table()).into_iter().flatten() {
+            for requirement in string_list(Some(items)) {
+                requirements.push((format!("{}:{}", prefix, group), requirement));
+            }
+        }
+    }
+    for (kind, requirement) in requirements {
+        let (name, version) = split_requirement(&requirement);
+        manifest.dependencies.push(Dependency { name, version, kind });
+    }
+
+    // Poetry keeps a table of name = version
+    let mut poetry_sections: Vec<(String, &toml::Value)> = Vec::new();
+    if let Some(table) = poetry.and_then(|p| p.get("dependencies")) {
+        poetry_sections.push(("dependencies".to_string(), table));
+    }
+    if let Some(table) = poetry.and_then(|p| p.get("dev-dependencies")) {
+        poetry_sections.push(("dev-dependencies".to_string(), table));
+    }
+    for (group, spec) in poetry.and_then(|p| p.get("group")).and_then(|g| g.as_table()).into_iter().flatten() {
+        if let Some(table) = spec.get("dependencies") {
+            poetry_sections.push((format!("group:{}", group), table));
+        }
+    }
+    for (kind, table) in poetry_sections {
+        for (name, spec) in table.as_table().into_iter().flatten().filter(|(name, _)| *name != "python") {
+            let version = spec
+                .as_str()
+                .or_else(|| spec.get("version").and_then(|v| v.as_str()))
+                .map(str::to_string);
+            manifest.dependencies.push(Dependency {
+                name: name.clone(),
+                version,
+                kind: kind.clone(),
+            });
+        }
+    }
+
+    let script_tables = [
+        project.and_then(|p| p.get("scripts")),
+        poetry.and_then(|p| p.get("scripts")),
+        tool.and_then(|t| t.get("pdm")).and_then(|p| p.get("scripts")),
+        tool.and_then(|t| t.get("taskipy")).and_then(|t| t.get("tasks")),
+    ];
+    for table in script_tables.into_iter().flatten().filter_map(|t| t.as_table()) {
+        for (name, command) in table {
+            // pdm and taskipy also allow `{ cmd = "..." }`
+            let command = command
+                .as_str()
+                .or_else(|| ["cmd", "shell", "call"].iter().find_map(|k| command.get(*k).and_then(|c| c.as_str())));
+            if let Some(command) = command {
+                manifest.scripts.push(Script {
+                    name: name.clone(),
+                    command: command.to_string(),
+                });
+            }
+        }
+    }
+    Ok(manifest)
+}
+
+/// `requests[socks]>=2.31; python_version>"3.8"` into its name and the rest
+fn split_requirement(requirement: &str) -> (String, Option<String>) {
+    let requirement = requirement.split(';').next().unwrap_or(requirement).trim();
+    let end = requirement
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(requirement.len());
+    let name = requirement[..end].to_string();
+    let rest = requirement[end..].trim();
+    let rest = match rest.strip_prefix('[') {
+        Some(extras) => extras.split_once(']').map(|(_, r)| r.trim()).unwrap_or(""),
+        None => rest,
+    };
+    (name, (!rest.is_empty()).then(|| rest.to_string()))
+}
+
+fn parse_go_mod(content: &str) -> ManifestDeps {
+    let mut manifest = ManifestDeps::default();
+    let mut in_require = false;
+    for line in content.lines() {
+        let (line, comment) = match line.split_once("//") {
+            Some((code, comment)) => (code.trim(), comment.trim()),
+            None => (line.trim(), ""),
+        };
+        let requirement = if in_require {
+            if line == ")" {
+                in_require = false;
+                continue;
+            }
+            line
+        } else if let Some(module) = line.strip_prefix("module ") {
+            manifest.package = Some(module.trim().to_string());
+            continue;
+        } else if let Some(go) = line.strip_prefix("go ") {
+            manifest.version = Some(format!("go {}", go.trim()));
+            continue;
+        } else if line == "require (" {
+            in_require = true;
+            continue;
+        } else if let Some(requirement) = line.strip_prefix("require ") {
+            requirement
+        } else {
+            continue;
+        };
+        let mut parts = requirement.split_whitespace();
+        if let Some(name) = parts.next() {
+            manifest.dependencies.push(Dependency {
+                name: name.to_string(),
+                version: parts.next().map(str::to_string),
+                kind: if comment == "indirect" { "indirect" } else { "require" }.to_string(),
+            });
+        }
+    }
+    manifest
+}
+
+/// The directory's Makefile, with its targets as `make <target>` scripts
+fn makefile(dir: &Path, workspace: &Path) -> Option<ManifestDeps> {
+    let path = MAKEFILES.iter().map(|m| dir.join(m)).find(|p| p.is_file())?;
+    let content = fs::read_to_string(&path).ok()?;
+    let mut targets: Vec<Script> = Vec::new();
+    for line in content.lines() {
+        if line.starts_with(['\t', ' ', '.', '#']) {
+            continue;
+        }
+        let Some((names, rest)) = line.split_once(':') else {
+            continue;
+        };
+        // `NAME := value` and `NAME ::= value` are assignments
+        if rest.starts_with('=') || rest.starts_with(":=") || names.contains('=') {
+            continue;
+        }
+        for name in names.split_whitespace() {
+            if !name.contains(['%', '$']) && !targets.iter().any(|t| t.name == name) {
+                targets.push(Script {
+                    name: name.to_string(),
+                    command: format!("make {}", name),
+                });
+            }
+        }
+    }
+    Some(ManifestDeps {
+        path: relative(&path, workspace),
+        ecosystem: "make".to_string(),
+        scripts: targets,
+        ..Default::default()
+    })
+}
+
+/// Plain text rendering of the manifests for the model
+fn render(result: &DepsResult) -> String {
+    let mut out = String::new();
+    for manifest in &result.manifests {
+        out.push_str(&format!("{} ({})", manifest.path, manifest.ecosystem));
+        if let Some(package) = &manifest.package {
+            out.push_str(&format!(" {}", package));
+        }
+        if let Some(version) = &manifest.version {
+            out.push_str(&format!(" {}", version));
+        }
+        out.push('\n');
+        if let Some(error) = &manifest.error {
+            out.push_str(&format!("  error: {}\n", error));
+        }
+        if !manifest.workspace_members.is_empty() {
+            out.push_str(&format!("  workspace members: {}\n", manifest.workspace_members.join(", ")));
+        }
+
+        let mut kinds: Vec<&str> = Vec::new();
+        for dep in &manifest.dependencies {
+            if !kinds.contains(&dep.kind.as_str()) {
+                kinds.push(&dep.kind);
+            }
+        }
+        for kind in kinds {
+            let deps: Vec<String> = manifest
+                .dependencies
+                .iter()
+                .filter(|d| d.kind == kind)
+                .map(|d| match &d.version {
+                    Some(version) => format!("{} {}", d.name, version),
+                    None => d.name.clone(),
+                })
+                .collect();
+            out.push_str(&format!("  {} ({}): {}\n", kind, deps.len(), deps.join(", ")));
+        }
+
+        if !manifest.scripts.is_empty() {
+            out.push_str("  scripts:\n");
+            for script in &manifest.scripts {
+                out.push_str(&format!("    {}: {}\n", script.name, script.command));
+            }
+        }
+    }
+    out
+}
+
+impl ToolSpec for DepsTool {
+    type Args = DepsRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Read
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Explored
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(args.path.clone().unwrap_or_else(|| ".".to_string()))
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let result = self.run_deps(&args)?;
+        let response_summary = result.response_summary.clone();
+        let stdout = render(&result);
+        let data = serde_json::to_value(result)?;
+        Ok(ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    fn dep(name: &str, version: &str, kind: &str) -> Dependency {
+        Dependency {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            kind: kind.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_each_manifest_format() {
+        let cargo = parse_cargo(
+            r#"
+[package]
+name = "demo"
+version = "0.1.0"
+
+[dependencies]
+serde = { version = "1", features = ["derive"] }
+core = { path = "../core" }
+anyhow = { workspace = true }
+
+[dev-dependencies]
+tempfile = "3"
+
+[target.'cfg(unix)'.dependencies]
+libc = "0.2"
+
+[[bin]]
+name = "demo-cli"
+"#,
+        )
+        .unwrap();
+        assert_eq!(cargo.package.as_deref(), Some("demo"));
+        assert!(cargo.dependencies.contains(&dep("serde", "1", "dependencies")));
+        assert!(cargo.dependencies.contains(&dep("core", "path:../core", "dependencies")));
+        assert!(cargo.dependencies.contains(&dep("anyhow", "workspace", "dependencies")));
+        assert!(cargo.dependencies.contains(&dep("tempfile", "3", "dev-dependencies")));
+        assert!(cargo.dependencies.contains(&dep("libc", "0.2", "dependencies (cfg(unix))")));
+        assert_eq!(cargo.scripts[0].command, "cargo run --bin demo-cli");
+
+        let npm = parse_package_json(
+            r#"{"name":"web","scripts":{"test":"vitest run"},"devDependencies":{"vitest":"^1.6.0"},"workspaces":{"packages":["apps/*"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(npm.scripts, [Script { name: "test".to_string(), command: "vitest run".to_string() }]);
+        assert_eq!(npm.dependencies, [dep("vitest", "^1.6.0", "devDependencies")]);
+        assert_eq!(npm.workspace_members, ["apps/*"]);
+        assert!(parse_package_json("{").is_err());
+
+        let python = parse_pyproject(
+            r#"
+[project]
+name = "tool"
+dependencies = ["requests[socks]>=2.31; python_version > '3.8'", "click"]
+[project.optional-dependencies]
+test = ["pytest>=8"]
+[tool.poetry.dependencies]
+python = "^3.10"
+rich = { version = "^13" }
+[tool.taskipy.tasks]
+lint = { cmd = "ruff check ." }
+"#,
+        )
+        .unwrap();
+        assert_eq!(python.dependencies[0], dep("requests", ">=2.31", "dependencies"));
+        assert_eq!(python.dependencies[1].version, None);
+        assert!(python.dependencies.contains(&dep("pytest", ">=8", "optional:test")));
+        assert!(python.dependencies.contains(&dep("rich", "^13", "dependencies")));
+        assert!(python.dependencies.iter().all(|d| d.name != "python"));
+        assert_eq!(python.scripts[0].command, "ruff check .");
+
+        let go = parse_go_mod(
+            "module example.com/app\n\ngo 1.22\n\nrequire github.com/spf13/cobra v1.8.0\n\nrequire (\n\tgolang.org/x/sys v0.20.0 // indirect\n)\n",
+        );
+        assert_eq!(go.package.as_deref(), Some("example.com/app"));
+        assert_eq!(go.version.as_deref(), Some("go 1.22"));
+        assert_eq!(
+            go.dependencies,
+            [
+                dep("github.com/spf13/cobra", "v1.8.0", "require"),
+                dep("golang.org/x/sys", "v0.20.0", "indirect")
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_makefile_targets() {
+        let workspace = FakeWorkspace::new()
+            .file(
+                "Makefile",
+                "CC := gcc\n.PHONY: test\nall build: deps\n\tcc main.c\n%.o: %.c\ntest:\n\t./run-tests\n",
+            )
+            .build()
+            .unwrap();
+        let dir = workspace.path();
+
+        let make = makefile(dir, dir).unwrap();
+        let targets: Vec<&str> = make.scripts.iter().map(|s| s.command.as_str()).collect();
+        assert_eq!(targets, ["make all", "make build", "make test"]);
+        assert_eq!(make.path, "Makefile");
+    }
+}
@@ -1,4 +1,3 @@
-use crate::llm::config::AppConfig;
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
 use crate::lsp::LspManager;
 use anyhow::Result;
@@ -54,18 +53,7 @@ impl DiagnosticsTool {
             }
         }
 
-        let config = AppConfig::load()?;
-        if !config.lsp.enabled {
-            anyhow::bail!("LSP not enabled in config");
-        }
-
-        let manager = LspManager::new(
-            &config.lsp,
-            Some(std::env::current_dir()?.to_string_lossy().to_string()),
-        )
-        .await?;
-
-        let manager_arc = Arc::new(manager);
+        let manager_arc = LspManager::shared().await?;
         *self.lsp_manager.lock().unwrap() = Some(Arc::clone(&manager_arc));
         Ok(manager_arc)
     }
@@ -1,13 +1,21 @@
+use crate::llm::backend::{self, ExecBackend, RemoteRead};
 use crate::llm::config::AppConfig;
-use crate::llm::utils::file_tracker::{PathSecurity, FILE_HISTORY_TRACKER, FILE_READ_TRACKER};
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::file_cache;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::file_lock;
+use crate::llm::utils::file_tracker::{StaleRead, FILE_HISTORY_TRACKER, FILE_READ_TRACKER};
+use crate::llm::utils::overlay;
 use crate::llm::utils::path_policy::PathPolicy;
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
 use crate::lsp::diagnostics::DiagnosticSummary;
 use crate::lsp::LspManager;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use similar::{ChangeTag, TextDiff};
 use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -48,15 +56,9 @@ pub struct EditResult {
     pub is_error: bool,
     /// Number of replacements made
     pub replacements: usize,
-    /// Diff preview of the changes
+    /// Lines added/removed, hunks and the unified diff
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub diff: Option<String>,
-    /// Number of lines added
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub additions: Option<usize>,
-    /// Number of lines removed
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub removals: Option<usize>,
+    pub diff_stats: Option<DiffStats>,
     /// LSP diagnostics (if available)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub diagnostics: Option<DiagnosticSummary>,
@@ -64,6 +66,25 @@ pub struct EditResult {
     pub response_summary: String,
 }
 
+/// `content` with the one occurrence of `old_string` replaced by
+/// `new_string`; an empty `old_string` stands for the whole (new) file
+pub fn apply_edit(content: &str, old_string: &str, new_string: &str) -> Result<String> {
+    if old_string.is_empty() {
+        return Ok(new_string.to_string());
+    }
+    let occurrence_count = content.matches(old_string).count();
+    if occurrence_count == 0 {
+        anyhow::bail!("old_string not found in file");
+    }
+    if occurrence_count > 1 {
+        anyhow::bail!(
+            "old_string found {} times. It must be unique. Add more context to make it unique.",
+            occurrence_count
+        );
+    }
+    Ok(content.replace(old_string, new_string))
+}
+
 impl EditTool {
     /// Create a new EditTool by loading configuration from config.toml
     ///
@@ -130,7 +151,12 @@ impl EditTool {
         let path_policy = PathPolicy::new()?;
         let path_buf = path_policy.resolve(&request.file_path)?;
         let absolute_path = path_buf.to_string_lossy().to_string();
-        let path = path_buf.as_path();
+        if let Some(target) = backend::current() {
+            return self.run_edit_remote(target.as_ref(), request, &path_buf);
+        }
+        // In overlay mode the session's copy is read and written instead
+        let source = overlay::read_path(&path_buf);
+        let path = source.as_path();
 
         // Check if path is a directory
         if path.exists() && path.is_dir() {
@@ -172,66 +198,38 @@ impl EditTool {
                     );
                 }
 
-                // Check if file has been modified since last read
-                if let Some(last_read_time) = tracker.get_last_read_time(&absolute_path) {
-                    let current_mod_time = PathSecurity::get_modification_time(path)?;
-
-                    if current_mod_time > last_read_time {
-                        anyhow::bail!(
-                            "File '{}' has been modified since it was last read. Please re-read the file before editing.",
-                            request.file_path
-                        );
+                // Reject edits based on content that changed on disk since the read
+                if let Some(stale) = tracker.check_stale(&absolute_path) {
+                    return Err(StaleRead {
+                        path: request.file_path.clone(),
+                        ..stale
                     }
+                    .into());
                 }
             }
 
-            original_content = fs::read_to_string(path).context("Failed to read file")?;
+            original_content = file_cache::read_to_string(path).context("Failed to read file")?.to_string();
         } else {
             anyhow::bail!("File not found: {}", request.file_path);
         }
 
-        let new_content;
-        let replacements;
-
-        if request.old_string.is_empty() {
-            new_content = request.new_string.clone();
-            replacements = 1;
-        } else if request.new_string.is_empty() {
-            let occurrence_count = original_content.matches(&request.old_string).count();
-            if occurrence_count == 0 {
-                anyhow::bail!("old_string not found in file");
-            }
-            if occurrence_count > 1 {
-                anyhow::bail!(
-                    "old_string found {} times. It must be unique. Add more context to make it unique.",
-                    occurrence_count
-                );
-            }
-            new_content = original_content.replace(&request.old_string, "");
-            replacements = 1;
-        } else {
-            let occurrence_count = original_content.matches(&request.old_string).count();
-            if occurrence_count == 0 {
-                anyhow::bail!("old_string not found in file");
-            }
-            if occurrence_count > 1 {
-                anyhow::bail!(
-                    "old_string found {} times. It must be unique. Add more context to make it unique.",
-                    occurrence_count
-                );
-            }
-            new_content = original_content.replace(&request.old_string, &request.new_string);
-            replacements = 1;
-        }
+        let new_content = apply_edit(&original_content, &request.old_string, &request.new_string)?;
+        let replacements = 1;
 
         // Check if content is actually changing
         if original_content == new_content {
             anyhow::bail!("New content is the same as old content. No changes made.");
         }
 
+        file_lock::lock_for_current_session(&path_buf)?;
+        checkpoint::record_before_change(&path_buf);
+        file_activity::record_edited(&path_buf);
+
         // Calculate diff before writing
-        let (diff_str, additions, removals) = Self::calculate_diff(&original_content, &new_content);
-        let diff = Some(diff_str);
+        let diff_stats = DiffStats::compute(&request.file_path, &original_content, &new_content);
+        let source_exists = path.exists();
+        let target = overlay::write_path(&path_buf)?;
+        let path = target.as_path();
 
         // Create parent directories if they don't exist (for new files)
         if let Some(parent) = path.parent() {
@@ -240,7 +238,7 @@ impl EditTool {
         }
 
         // Check if file content changed externally (intermediate version detection)
-        if path.exists() {
+        if source_exists {
             let history_tracker = FILE_HISTORY_TRACKER.lock().unwrap();
             if let Some(latest_version) = history_tracker.get_latest_version(&absolute_path) {
                 if latest_version.content != original_content {
@@ -254,16 +252,16 @@ impl EditTool {
 
         fs::write(path, &new_content).context("Failed to write file")?;
 
-        // Record file history
+        // Mark file as read after write
         {
-            let mut history_tracker = FILE_HISTORY_TRACKER.lock().unwrap();
-            history_tracker.record_version(&absolute_path, new_content);
+            let mut read_tracker = FILE_READ_TRACKER.lock().unwrap();
+            read_tracker.record_read(&absolute_path, new_content.as_bytes());
         }
 
-        // Mark file as read after write
+        // Record file history
         {
-            let mut read_tracker = FILE_READ_TRACKER.lock().unwrap();
-            read_tracker.record_read(&absolute_path);
+            let mut history_tracker = FILE_HISTORY_TRACKER.lock().unwrap();
+            history_tracker.record_version(&absolute_path, new_content);
         }
 
         let mut result = EditResult {
@@ -271,11 +269,9 @@ impl EditTool {
             success: true,
             is_error: false,
             replacements,
-            diff,
-            additions: Some(additions),
-            removals: Some(removals),
+            response_summary: format!("{} lines", diff_stats.additions + diff_stats.removals),
+            diff_stats: Some(diff_stats),
             diagnostics: None,
-            response_summary: format!("{} lines", additions + removals),
         };
 
         // Collect LSP diagnostics if available
@@ -286,6 +282,64 @@ impl EditTool {
         Ok(result)
     }
 
+    /// `run_edit` on the session's remote target. The same read-before-write
+    /// and staleness checks apply; file history and diagnostics are local-only.
+    fn run_edit_remote(&self, target: &dyn ExecBackend, request: &EditRequest, path: &Path) -> Result<EditResult> {
+        if request.old_string.is_empty() && request.new_string.is_empty() {
+            return Ok(EditResult {
+                file_path: request.file_path.clone(),
+                success: false,
+                is_error: true,
+                response_summary: "0 lines".to_string(),
+                ..Default::default()
+            });
+        }
+        let remote_path = target.target_path(path)?;
+        let original_content = match (target.read_file(&remote_path)?, request.old_string.is_empty()) {
+            (Some(_), true) => anyhow::bail!("File already exists: {}", request.file_path),
+            (None, true) => String::new(),
+            (None, false) => anyhow::bail!("File not found on {}: {}", target.name(), request.file_path),
+            (Some(bytes), false) => {
+                match backend::check_read(target, &remote_path, &bytes) {
+                    RemoteRead::NotRead => anyhow::bail!(
+                        "File '{}' has not been read. Use the 'view' tool to read the file before editing it.",
+                        request.file_path
+                    ),
+                    RemoteRead::Stale { read_at_ms } => {
+                        return Err(StaleRead {
+                            path: request.file_path.clone(),
+                            read_at_ms,
+                            modified_at_ms: None,
+                        }
+                        .into())
+                    }
+                    RemoteRead::Current => {}
+                }
+                String::from_utf8(bytes).context("File is not valid UTF-8")?
+            }
+        };
+
+        let new_content = apply_edit(&original_content, &request.old_string, &request.new_string)?;
+        if original_content == new_content {
+            anyhow::bail!("New content is the same as old content. No changes made.");
+        }
+        file_lock::lock_for_current_session(path)?;
+
+        let diff_stats = DiffStats::compute(&request.file_path, &original_content, &new_content);
+        target.write_file(&remote_path, new_content.as_bytes())?;
+        backend::record_read(target, &remote_path, new_content.as_bytes());
+
+        Ok(EditResult {
+            file_path: request.file_path.clone(),
+            success: true,
+            is_error: false,
+            replacements: 1,
+            response_summary: format!("{} lines", diff_stats.additions + diff_stats.removals),
+            diff_stats: Some(diff_stats),
+            diagnostics: None,
+        })
+    }
+
     /// Collect LSP diagnostics with timeout
     fn collect_diagnostics(
         &self,
@@ -317,38 +371,6 @@ impl EditTool {
         })
     }
 
-    /// Calculate diff between old and new content
-    ///
-    /// # Arguments
-    /// * `old_content` - Original content
-    /// * `new_content` - New content
-    ///
-    /// # Returns
-    /// * Tuple of (diff string, additions count, removals count)
-    fn calculate_diff(old_content: &str, new_content: &str) -> (String, usize, usize) {
-        let diff = TextDiff::from_lines(old_content, new_content);
-        let mut additions = 0;
-        let mut removals = 0;
-
-        for op in diff.ops() {
-            for change in diff.iter_changes(op) {
-                match change.tag() {
-                    ChangeTag::Delete => removals += 1,
-                    ChangeTag::Insert => additions += 1,
-                    ChangeTag::Equal => {}
-                }
-            }
-        }
-
-        let diff_output = diff
-            .unified_diff()
-            .context_radius(3)
-            .header("Original", "Modified")
-            .to_string();
-
-        (diff_output, additions, removals)
-    }
-
     /// Get tool definition as JSON for LLM
     fn to_tool_definition_json(&self) -> serde_json::Value {
         serde_json::json!({
@@ -414,13 +436,27 @@ impl ToolSpec for EditTool {
         self.to_tool_definition_json()
     }
 
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let path = arg_summary::path(&args.file_path);
+        if args.old_string.is_empty() {
+            return Some(format!("{} (new file, {} lines)", path, arg_summary::line_count(&args.new_string)));
+        }
+        Some(format!(
+            "{} (−{} +{} lines)",
+            path,
+            arg_summary::line_count(&args.old_string),
+            arg_summary::line_count(&args.new_string)
+        ))
+    }
+
     fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
         let result = self.run_edit(&args)?;
         let response_summary = result.response_summary.clone();
         let success = result.success && !result.is_error;
         let stdout = result
-            .diff
-            .as_deref()
+            .diff_stats
+            .as_ref()
+            .map(|stats| stats.unified_diff.as_str())
             .unwrap_or(&result.response_summary)
             .to_string();
         let data = serde_json::to_value(&result)?;
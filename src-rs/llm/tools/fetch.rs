@@ -1,6 +1,9 @@
-use crate::llm::config::AppConfig;
-use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::config::{AppConfig, InjectionGuardMode};
+use crate::llm::utils::prompt_guard::{self, InjectionFinding};
+use crate::llm::utils::sensitive_files::SensitiveFiles;
+use crate::llm::tools::tool_trait::{block_on, ToolKind, ToolOperation, ToolResult, ToolSpec};
 use anyhow::{Context, Result};
+use futures::future::BoxFuture;
 use serde::{Deserialize, Serialize};
 
 /// Fetch tool for retrieving content from URLs
@@ -8,6 +11,9 @@ use serde::{Deserialize, Serialize};
 pub struct FetchTool {
     pub tool_name: String,
     pub description: String,
+    /// Screening of fetched content for prompt injection
+    #[serde(default)]
+    pub prompt_injection: InjectionGuardMode,
 }
 
 use crate::llm::utils::serde_util::deserialize_u64_lax;
@@ -40,6 +46,9 @@ pub struct FetchResult {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     pub response_summary: String,
+    /// Passages that look like instructions to the model
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub injection: Vec<InjectionFinding>,
 }
 
 impl FetchTool {
@@ -48,6 +57,7 @@ impl FetchTool {
             Ok(config) => Self {
                 tool_name: config.tool_fetch.tool_name,
                 description: config.tool_fetch.description,
+                prompt_injection: config.security.prompt_injection,
             },
             Err(_) => Self::default(),
         }
@@ -58,130 +68,143 @@ impl FetchTool {
         Self {
             tool_name: config.tool_fetch.tool_name.clone(),
             description: config.tool_fetch.description.clone(),
+            prompt_injection: config.security.prompt_injection,
         }
     }
 
-    pub fn fetch_content(&self, request: &FetchRequest) -> Result<FetchResult> {
+    pub async fn fetch_content(&self, request: &FetchRequest) -> Result<FetchResult> {
         // Validate URL
         let parsed_url = url::Url::parse(&request.url).context("Invalid URL format")?;
 
         // Only allow HTTP and HTTPS
         if parsed_url.scheme() != "http" && parsed_url.scheme() != "https" {
-            return Ok(FetchResult {
-                content: String::new(),
-                metadata: FetchMetadata {
-                    url: request.url.clone(),
-                    format: request.format.clone(),
-                    size: 0,
-                },
-                status_code: None,
-                error: Some("Only HTTP and HTTPS protocols are supported".to_string()),
-                response_summary: "Error: unsupported protocol".to_string(),
-            });
+            return Ok(Self::refused(
+                request,
+                "Only HTTP and HTTPS protocols are supported",
+                "Error: unsupported protocol",
+            ));
         }
 
-        // Clone data needed for the thread
         let url = request.url.clone();
         let format = request.format.clone();
         let timeout = request.timeout;
+        let prompt_injection = self.prompt_injection;
 
-        // Use std::thread to avoid tokio runtime issues
-        let handle = std::thread::spawn(move || -> Result<FetchResult> {
-            // Build HTTP client with timeout
-            let timeout_duration = std::time::Duration::from_millis(timeout.min(120000));
-            let client = reqwest::blocking::Client::builder()
-                .timeout(timeout_duration)
-                .redirect(reqwest::redirect::Policy::limited(10))
-                .build()
-                .context("Failed to create HTTP client")?;
-
-            // Make the request
-            let response = client.get(&url).send().context("Failed to send request")?;
-
-            let status = response.status();
-            let status_code = status.as_u16();
-
-            if !status.is_success() {
-                return Ok(FetchResult {
-                    content: String::new(),
-                    metadata: FetchMetadata {
-                        url: url.clone(),
-                        format: format.clone(),
-                        size: 0,
-                    },
-                    status_code: Some(status_code),
-                    error: Some(format!("HTTP error: {}", status)),
-                    response_summary: format!("Error: HTTP {}", status_code),
-                });
-            }
+        // Build HTTP client with timeout
+        let timeout_duration = std::time::Duration::from_millis(timeout.min(120000));
+        let client = reqwest::Client::builder()
+            .timeout(timeout_duration)
+            .redirect(reqwest::redirect::Policy::limited(10))
+            .build()
+            .context("Failed to create HTTP client")?;
 
-            // Get content type before consuming response
-            let content_type = response
-                .headers()
-                .get(reqwest::header::CONTENT_TYPE)
-                .and_then(|v| v.to_str().ok())
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| "text/plain".to_string());
-
-            // Read response body
-            let body = response.text().context("Failed to read response body")?;
-            let body_size = body.len();
-
-            // Check size limit (5MB)
-            if body_size > 5 * 1024 * 1024 {
-                return Ok(FetchResult {
-                    content: String::new(),
-                    metadata: FetchMetadata {
-                        url: url.clone(),
-                        format: format.clone(),
-                        size: body_size,
-                    },
-                    status_code: Some(status_code),
-                    error: Some("Response too large (max 5MB)".to_string()),
-                    response_summary: "Error: response too large".to_string(),
-                });
-            }
+        // Make the request
+        let response = client.get(&url).send().await.context("Failed to send request")?;
 
-            // Convert content based on requested format
-            let content = match format.as_str() {
-                "html" => body,
-                "markdown" | "md" => {
-                    if content_type.contains("html") {
-                        html2md::parse_html(&body)
-                    } else {
-                        body
-                    }
-                }
-                "text" => {
-                    if content_type.contains("html") {
-                        html2text::from_read(body.as_bytes(), 100)
-                    } else {
-                        body
-                    }
-                }
-                _ => body,
-            };
+        let status = response.status();
+        let status_code = status.as_u16();
+
+        if !status.is_success() {
+            return Ok(FetchResult {
+                content: String::new(),
+                metadata: FetchMetadata {
+                    url: url.clone(),
+                    format: format.clone(),
+                    size: 0,
+                },
+                status_code: Some(status_code),
+                error: Some(format!("HTTP error: {}", status)),
+                response_summary: format!("Error: HTTP {}", status_code),
+                injection: Vec::new(),
+            });
+        }
 
-            // Calculate line count for summary
-            let line_count = content.lines().count();
+        // Get content type before consuming response
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "text/plain".to_string());
 
-            Ok(FetchResult {
-                content,
+        // Read response body
+        let body = response.text().await.context("Failed to read response body")?;
+        let body_size = body.len();
+
+        // Check size limit (5MB)
+        if body_size > 5 * 1024 * 1024 {
+            return Ok(FetchResult {
+                content: String::new(),
                 metadata: FetchMetadata {
                     url: url.clone(),
                     format: format.clone(),
                     size: body_size,
                 },
                 status_code: Some(status_code),
-                error: None,
-                response_summary: format!("{} lines", line_count),
-            })
-        });
-
-        // Wait for the thread to complete
-        handle
-            .join()
-            .map_err(|_| anyhow::anyhow!("Fetch thread panicked"))?
+                error: Some("Response too large (max 5MB)".to_string()),
+                response_summary: "Error: response too large".to_string(),
+                injection: Vec::new(),
+            });
+        }
+
+        // Convert content based on requested format
+        let content = match format.as_str() {
+            "html" => body.clone(),
+            "markdown" | "md" => {
+                if content_type.contains("html") {
+                    html2md::parse_html(&body)
+                } else {
+                    body.clone()
+                }
+            }
+            "text" => {
+                if content_type.contains("html") {
+                    html2text::from_read(body.as_bytes(), 100)
+                } else {
+                    body.clone()
+                }
+            }
+            _ => body.clone(),
+        };
+        let raw_html = content_type.contains("html").then_some(body.as_str());
+        let screened = prompt_guard::screen(&url, content, raw_html, prompt_injection);
+        let content = screened.content;
+
+        // Calculate line count for summary
+        let line_count = content.lines().count();
+
+        Ok(FetchResult {
+            content,
+            metadata: FetchMetadata {
+                url: url.clone(),
+                format: format.clone(),
+                size: body_size,
+            },
+            status_code: Some(status_code),
+            error: None,
+            response_summary: if screened.findings.is_empty() {
+                format!("{} lines", line_count)
+            } else {
+                format!("{} lines, possible prompt injection", line_count)
+            },
+            injection: screened.findings,
+        })
+    }
+
+    /// A result for a request that was not sent
+    fn refused(request: &FetchRequest, error: &str, response_summary: &str) -> FetchResult {
+        FetchResult {
+            content: String::new(),
+            metadata: FetchMetadata {
+                url: request.url.clone(),
+                format: request.format.clone(),
+                size: 0,
+            },
+            status_code: None,
+            error: Some(error.to_string()),
+            response_summary: response_summary.to_string(),
+            injection: Vec::new(),
+        }
     }
 
     fn to_tool_definition_json(&self) -> serde_json::Value {
@@ -221,6 +244,7 @@ impl Default for FetchTool {
             tool_name: "fetch".to_string(),
             description: "Fetches content from a URL and returns it in the specified format."
                 .to_string(),
+            prompt_injection: InjectionGuardMode::default(),
         }
     }
 }
@@ -248,8 +272,34 @@ impl ToolSpec for FetchTool {
         self.to_tool_definition_json()
     }
 
-    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
-        let result = self.fetch_content(&args)?;
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(args.url.clone())
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        block_on(self.run_async(args, confirmed))?
+    }
+
+    fn is_async(&self) -> bool {
+        true
+    }
+
+    fn run_async(&self, args: Self::Args, confirmed: bool) -> BoxFuture<'static, Result<ToolResult>> {
+        Box::pin(self.clone().run_fetch(args, confirmed))
+    }
+}
+
+impl FetchTool {
+    async fn run_fetch(self, args: FetchRequest, confirmed: bool) -> Result<ToolResult> {
+        let result = if !confirmed && SensitiveFiles::current().is_sensitive_url(&args.url) {
+            Self::refused(
+                &args,
+                "The URL names a file matched by [security] sensitive_files; it is fetched only once the user confirms",
+                "Requires confirmation",
+            )
+        } else {
+            self.fetch_content(&args).await?
+        };
         let response_summary = result.response_summary.clone();
         let stdout = result.content.clone();
         let data = serde_json::to_value(result)?;
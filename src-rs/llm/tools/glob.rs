@@ -1,12 +1,14 @@
 use crate::llm::config::AppConfig;
 use crate::llm::utils::path_policy::PathPolicy;
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
 use anyhow::{Context, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::SystemTime;
 use walkdir::WalkDir;
 
 /// Glob tool for finding files by pattern matching
@@ -20,6 +22,8 @@ pub struct GlobTool {
     pub max_glob_results: usize,
 }
 
+use crate::llm::utils::serde_util::{deserialize_usize_lax, deserialize_usize_opt_lax};
+
 /// Glob request parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GlobRequest {
@@ -27,6 +31,12 @@ pub struct GlobRequest {
     pub pattern: String,
     /// Starting directory for search (defaults to current working directory)
     pub path: Option<String>,
+    /// Matches to skip, for the pages after the first
+    #[serde(default, deserialize_with = "deserialize_usize_lax")]
+    pub offset: usize,
+    /// Matches to return, at most `max_glob_results`
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub limit: Option<usize>,
 }
 
 /// A single file match result
@@ -45,10 +55,14 @@ pub struct GlobMatch {
 pub struct GlobResult {
     /// List of matching files
     pub matches: Vec<GlobMatch>,
-    /// Whether results were truncated due to max limit
+    /// Whether more matches follow this page
     pub truncated: bool,
     /// Total number of matches found
     pub total_count: usize,
+    /// Matches skipped before this page
+    pub offset: usize,
+    /// Offset of the next page, if there is one
+    pub next_offset: Option<usize>,
     /// Search pattern used
     pub pattern: String,
     /// Summary of the result
@@ -107,17 +121,6 @@ impl GlobTool {
     /// # Returns
     /// * `Result<GlobResult>` - The result containing matches and metadata
     pub fn run_glob(&self, request: &GlobRequest) -> Result<GlobResult> {
-        // Try ripgrep first if available
-        if let Ok(result) = self.try_ripgrep(request) {
-            return Ok(result);
-        }
-
-        // Fallback to native implementation
-        self.run_glob_native(request)
-    }
-
-    /// Try to use ripgrep for better performance
-    fn try_ripgrep(&self, request: &GlobRequest) -> Result<GlobResult> {
         let policy = PathPolicy::new()?;
         let base_path = request
             .path
@@ -126,11 +129,66 @@ impl GlobTool {
             .transpose()?
             .unwrap_or_else(|| policy.resolve(".").expect("workspace path should resolve"));
 
+        // Try ripgrep first if available, falling back to the native implementation
+        let mut files = match self.try_ripgrep(request, &base_path) {
+            Ok(files) => files,
+            Err(_) => self.run_glob_native(request, &base_path),
+        };
+
+        // Sort by modification time (newest first)
+        files.sort_by_key(|b| std::cmp::Reverse(b.1));
+        Ok(self.page(request, &base_path, files))
+    }
+
+    /// The page of `files` the request asks for
+    fn page(&self, request: &GlobRequest, base_path: &Path, files: Vec<(PathBuf, SystemTime)>) -> GlobResult {
+        let total_count = files.len();
+        let limit = request.limit.unwrap_or(self.max_glob_results).clamp(1, self.max_glob_results.max(1));
+        let offset = request.offset.min(total_count);
+
+        let matches: Vec<GlobMatch> = files
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(path, mtime)| {
+                let modified = mtime
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .ok()
+                    .map(|d| d.as_secs());
+                let size = fs::metadata(&path).ok().map(|m| m.len());
+                let relative_path = path
+                    .strip_prefix(base_path)
+                    .unwrap_or(&path)
+                    .to_string_lossy()
+                    .to_string();
+                GlobMatch {
+                    path: relative_path,
+                    modified,
+                    size,
+                }
+            })
+            .collect();
+
+        let end = offset + matches.len();
+        let next_offset = (end < total_count).then_some(end);
+        GlobResult {
+            matches,
+            truncated: next_offset.is_some(),
+            total_count,
+            offset,
+            next_offset,
+            pattern: request.pattern.clone(),
+            response_summary: format!("{} lines", total_count),
+        }
+    }
+
+    /// Try to use ripgrep for better performance
+    fn try_ripgrep(&self, request: &GlobRequest, base_path: &Path) -> Result<Vec<(PathBuf, SystemTime)>> {
         let mut cmd = Command::new("rg");
         cmd.arg("--files")
             .arg("--glob")
             .arg(&request.pattern)
-            .arg(&base_path);
+            .arg(base_path);
 
         let output = cmd.output().context("Failed to execute ripgrep")?;
 
@@ -146,7 +204,7 @@ impl GlobTool {
         }
 
         let content = String::from_utf8_lossy(&output.stdout);
-        let mut file_list: Vec<(PathBuf, std::time::SystemTime)> = Vec::new();
+        let mut file_list: Vec<(PathBuf, SystemTime)> = Vec::new();
 
         for line in content.lines() {
             let path = PathBuf::from(line);
@@ -161,67 +219,24 @@ impl GlobTool {
             if path.is_file() {
                 let mtime = fs::metadata(&path)
                     .and_then(|m| m.modified())
-                    .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
+                    .unwrap_or(SystemTime::UNIX_EPOCH);
                 file_list.push((path, mtime));
             }
         }
 
-        // Sort by modification time (newest first)
-        file_list.sort_by(|a, b| b.1.cmp(&a.1));
-
-        let mut matches = Vec::new();
-        let total_count = file_list.len();
-
-        for (path, _) in file_list.into_iter().take(self.max_glob_results) {
-            let metadata = fs::metadata(&path).ok();
-            let modified = metadata
-                .as_ref()
-                .and_then(|m| m.modified().ok())
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs());
-            let size = metadata.as_ref().map(|m| m.len());
-
-            let relative_path = path
-                .strip_prefix(&base_path)
-                .unwrap_or(&path)
-                .to_string_lossy()
-                .to_string();
-
-            matches.push(GlobMatch {
-                path: relative_path,
-                modified,
-                size,
-            });
-        }
-
-        Ok(GlobResult {
-            matches,
-            truncated: total_count > self.max_glob_results,
-            total_count,
-            pattern: request.pattern.clone(),
-            response_summary: format!("{} lines", total_count),
-        })
+        Ok(file_list)
     }
 
     /// Native glob implementation (fallback)
-    fn run_glob_native(&self, request: &GlobRequest) -> Result<GlobResult> {
-        let policy = PathPolicy::new()?;
-        let base_path = request
-            .path
-            .as_deref()
-            .map(|p| policy.resolve(p))
-            .transpose()?
-            .unwrap_or_else(|| policy.resolve(".").expect("workspace path should resolve"));
-
-        let mut matches: Vec<GlobMatch> = Vec::new();
-        let mut total_count = 0;
+    fn run_glob_native(&self, request: &GlobRequest, base_path: &Path) -> Vec<(PathBuf, SystemTime)> {
+        let mut files = Vec::new();
 
         // Parse the glob pattern to determine search strategy
         let pattern_parts: Vec<&str> = request.pattern.split('/').collect();
         let has_recursive = pattern_parts.contains(&"**");
 
         // Build the walker
-        let walker = WalkDir::new(&base_path)
+        let walker = WalkDir::new(base_path)
             .follow_links(false)
             .into_iter()
             .filter_entry(|entry| {
@@ -259,52 +274,19 @@ impl GlobTool {
             }
 
             // Check if path matches pattern
-            if !glob_pattern_match(&request.pattern, path, &base_path, has_recursive) {
+            if !glob_pattern_match(&request.pattern, path, base_path, has_recursive) {
                 continue;
             }
 
-            total_count += 1;
-
-            // Get file metadata
-            let metadata = entry.metadata().ok();
-            let modified = metadata
-                .as_ref()
+            let mtime = entry
+                .metadata()
+                .ok()
                 .and_then(|m| m.modified().ok())
-                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
-                .map(|d| d.as_secs());
-            let size = metadata.as_ref().map(|m| m.len());
-
-            // Get relative path
-            let relative_path = path
-                .strip_prefix(&base_path)
-                .unwrap_or(path)
-                .to_string_lossy()
-                .to_string();
-
-            matches.push(GlobMatch {
-                path: relative_path,
-                modified,
-                size,
-            });
-
-            // Stop if we hit max results
-            if matches.len() >= self.max_glob_results {
-                break;
-            }
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            files.push((path.to_path_buf(), mtime));
         }
 
-        // Sort by modification time (newest first)
-        matches.sort_by(|a, b| b.modified.cmp(&a.modified));
-
-        let truncated = total_count > self.max_glob_results;
-
-        Ok(GlobResult {
-            matches,
-            truncated,
-            total_count,
-            pattern: request.pattern.clone(),
-            response_summary: format!("{} lines", total_count),
-        })
+        files
     }
 
     /// Get tool definition as JSON for LLM
@@ -324,6 +306,14 @@ impl GlobTool {
                         "path": {
                             "type": "string",
                             "description": "The directory path to start the search from. Defaults to current working directory."
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "Number of matches to skip, to read the page after a truncated result. Defaults to 0."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": format!("Maximum number of matches to return. Defaults to, and is capped at, {}.", self.max_glob_results)
                         }
                     },
                     "required": ["pattern"]
@@ -367,18 +357,32 @@ impl ToolSpec for GlobTool {
         self.to_tool_definition_json()
     }
 
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let mut out = format!("{} in {}", args.pattern, arg_summary::path(args.path.as_deref().unwrap_or(".")));
+        if args.offset > 0 {
+            out.push_str(&format!(" (from match {})", args.offset + 1));
+        }
+        Some(out)
+    }
+
     fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
         let result = self.run_glob(&args)?;
         let response_summary = result.response_summary.clone();
         let mut stdout = result
             .matches
             .iter()
-            .take(200)
             .map(|m| m.path.as_str())
             .collect::<Vec<_>>()
             .join("\n");
-        if result.matches.len() > 200 {
-            stdout.push_str("\n...");
+        if let Some(next_offset) = result.next_offset {
+            stdout.push_str(&format!(
+                "\n... {} more results (showing {}-{} of {}); call again with offset={} for the next page",
+                result.total_count - next_offset,
+                result.offset + 1,
+                next_offset,
+                result.total_count,
+                next_offset
+            ));
         }
         let data = serde_json::to_value(result)?;
         Ok(ToolResult::ok(
@@ -516,3 +520,33 @@ fn convert_glob_to_regex(pattern: &str) -> String {
     regex.push('$');
     regex
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_report_the_total_and_the_next_offset() {
+        let tool = GlobTool::with_config("glob".to_string(), String::new(), 2);
+        let base = Path::new("/work");
+        let files: Vec<(PathBuf, SystemTime)> =
+            (0..5).map(|i| (base.join(format!("f{}.rs", i)), SystemTime::UNIX_EPOCH)).collect();
+        let request = |offset, limit| GlobRequest {
+            pattern: "*.rs".to_string(),
+            path: None,
+            offset,
+            limit,
+        };
+
+        let first = tool.page(&request(0, None), base, files.clone());
+        let paths: Vec<&str> = first.matches.iter().map(|m| m.path.as_str()).collect();
+        assert_eq!(paths, ["f0.rs", "f1.rs"]);
+        assert_eq!((first.total_count, first.next_offset, first.truncated), (5, Some(2), true));
+
+        let last = tool.page(&request(4, Some(10)), base, files.clone());
+        assert_eq!((last.matches.len(), last.next_offset, last.truncated), (1, None, false));
+
+        let past_end = tool.page(&request(9, None), base, files);
+        assert_eq!((past_end.offset, past_end.matches.len()), (5, 0));
+    }
+}
@@ -1,6 +1,9 @@
 use crate::llm::config::AppConfig;
+use crate::llm::utils::file_cache;
 use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::sensitive_files::{SensitiveFiles, REDACTED};
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
 use anyhow::{Context, Result};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -9,6 +12,9 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use walkdir::WalkDir;
 
+/// Matches collected for a single file: (path, mtime, [(line_number, content)])
+type FileMatches = (PathBuf, std::time::SystemTime, Vec<(usize, String)>);
+
 /// Grep tool for searching file contents
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GrepTool {
@@ -241,7 +247,7 @@ impl GrepTool {
         let base_path = request
             .path
             .as_ref()
-            .map(|p| PathBuf::from(p))
+            .map(PathBuf::from)
             .unwrap_or_else(|| PathBuf::from("."));
 
         let regex = if request.literal_text {
@@ -259,8 +265,7 @@ impl GrepTool {
             request.literal_text
         );
 
-        let mut file_matches: Vec<(PathBuf, std::time::SystemTime, Vec<(usize, String)>)> =
-            Vec::new();
+        let mut file_matches: Vec<FileMatches> = Vec::new();
         let mut total_count = 0;
 
         // Build the walker
@@ -305,7 +310,7 @@ impl GrepTool {
             log::debug!("  Reading file: {}", path.display());
 
             // Try to read file
-            let content = match fs::read_to_string(path) {
+            let content = match file_cache::read_to_string(path) {
                 Ok(content) => {
                     log::debug!("  File read successfully, {} bytes", content.len());
                     content
@@ -336,8 +341,8 @@ impl GrepTool {
                         // Before context
                         if request.context_before > 0 {
                             let start = line_number.saturating_sub(request.context_before);
-                            for i in start..line_number {
-                                context_lines.push(format!("{}-{}", i + 1, lines[i]));
+                            for (i, ctx) in lines.iter().enumerate().take(line_number).skip(start) {
+                                context_lines.push(format!("{}-{}", i + 1, ctx));
                             }
                         }
 
@@ -347,8 +352,8 @@ impl GrepTool {
                         // After context
                         if request.context_after > 0 {
                             let end = (line_number + 1 + request.context_after).min(lines.len());
-                            for i in (line_number + 1)..end {
-                                context_lines.push(format!("{}-{}", i + 1, lines[i]));
+                            for (i, ctx) in lines.iter().enumerate().take(end).skip(line_number + 1) {
+                                context_lines.push(format!("{}-{}", i + 1, ctx));
                             }
                         }
 
@@ -376,7 +381,7 @@ impl GrepTool {
         }
 
         // Sort by modification time (newest first)
-        file_matches.sort_by(|a, b| b.1.cmp(&a.1));
+        file_matches.sort_by_key(|b| std::cmp::Reverse(b.1));
 
         // Flatten to GrepMatch
         let mut matches = Vec::new();
@@ -495,8 +500,17 @@ impl ToolSpec for GrepTool {
         self.to_tool_definition_json()
     }
 
-    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
-        let result = self.run_grep(&args)?;
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let mut out = format!("\"{}\" in {}", args.pattern, arg_summary::path(args.path.as_deref().unwrap_or(".")));
+        if let Some(include) = &args.include {
+            out.push_str(&format!(" ({})", include));
+        }
+        Some(out)
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        let mut result = self.run_grep(&args)?;
+        redact_sensitive(&mut result, &args, confirmed);
         let response_summary = result.response_summary.clone();
         let mut stdout = result
             .matches
@@ -520,6 +534,28 @@ impl ToolSpec for GrepTool {
     }
 }
 
+/// Replace lines found in sensitive files with `REDACTED`, unless the user
+/// confirmed searching the sensitive file or directory given as `path`
+fn redact_sensitive(result: &mut GrepResult, request: &GrepRequest, confirmed: bool) {
+    let sensitive = SensitiveFiles::current();
+    let base = PathBuf::from(request.path.as_deref().unwrap_or("."));
+    if confirmed && sensitive.is_sensitive(&base) {
+        return;
+    }
+    let mut redacted = 0;
+    for m in &mut result.matches {
+        // A search of a single file reports it with an empty path
+        let path = if m.path.is_empty() { base.clone() } else { base.join(&m.path) };
+        if sensitive.is_sensitive(&path) {
+            m.line = REDACTED.to_string();
+            redacted += 1;
+        }
+    }
+    if redacted > 0 {
+        result.response_summary.push_str(&format!(", {} in sensitive files redacted", redacted));
+    }
+}
+
 /// Check if a file path matches a glob pattern
 fn glob_match(pattern: &str, path: &Path) -> bool {
     // Simple glob matching
@@ -544,8 +580,7 @@ fn glob_match(pattern: &str, path: &Path) -> bool {
     }
 
     // Handle simple pattern like "*.rs"
-    if pattern.starts_with("*.") {
-        let ext = &pattern[2..];
+    if let Some(ext) = pattern.strip_prefix("*.") {
         return file_name.ends_with(ext);
     }
 
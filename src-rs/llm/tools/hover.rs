@@ -0,0 +1,334 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::serde_util::deserialize_usize_opt_lax;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::lsp::protocol::Position;
+use crate::lsp::workspace_edit;
+use crate::lsp::LspManager;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Tool for reading type signatures and docs from the language server
+#[derive(Clone)]
+pub struct HoverTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+    lsp_manager: Arc<Mutex<Option<Arc<LspManager>>>>,
+}
+
+impl std::fmt::Debug for HoverTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HoverTool")
+            .field("tool_name", &self.tool_name)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Hover request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HoverRequest {
+    /// File to hover in; omit to look the symbol up across the workspace
+    #[serde(default)]
+    pub file_path: String,
+    /// Line of the position (1-based)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub line: Option<usize>,
+    /// Column of the position (1-based, in characters)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub column: Option<usize>,
+    /// Symbol name, used when no column is given
+    #[serde(default)]
+    pub symbol: Option<String>,
+}
+
+/// Result of a hover
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HoverResult {
+    pub file_path: String,
+    pub line: usize,
+    pub column: usize,
+    /// Hover contents as markdown; empty when the server has nothing there
+    pub contents: String,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl HoverTool {
+    /// Create a new HoverTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self::from_config(&config),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new HoverTool from a specific AppConfig
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_hover.tool_name.clone(),
+            description: config.tool_hover.description.clone(),
+            lsp_manager: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn get_or_init_lsp_manager(&self) -> Result<Arc<LspManager>> {
+        {
+            let manager_lock = self.lsp_manager.lock().unwrap();
+            if let Some(manager) = manager_lock.as_ref() {
+                return Ok(Arc::clone(manager));
+            }
+        }
+
+        let manager = LspManager::shared().await?;
+        *self.lsp_manager.lock().unwrap() = Some(Arc::clone(&manager));
+        Ok(manager)
+    }
+
+    /// Resolve the requested position and ask the language server about it
+    async fn request_hover(&self, policy: &PathPolicy, request: &HoverRequest) -> Result<HoverResult> {
+        let manager = self.get_or_init_lsp_manager().await?;
+        let symbol = request
+            .symbol
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty());
+
+        let (path, content, line, column) = if !request.file_path.trim().is_empty() {
+            let path = policy.resolve(&request.file_path)?;
+            if !path.is_file() {
+                anyhow::bail!("File not found: {}", request.file_path);
+            }
+            let content = tokio::fs::read_to_string(&path).await.context("Failed to read file")?;
+            let (line, column) = match (request.line, request.column, symbol) {
+                (Some(line), Some(column), _) => (line, column),
+                (line, _, Some(symbol)) => find_in_text(&content, symbol, line).with_context(|| {
+                    match line {
+                        Some(line) => format!("'{}' not found on line {} of {}", symbol, line, request.file_path),
+                        None => format!("'{}' not found in {}", symbol, request.file_path),
+                    }
+                })?,
+                (Some(line), None, None) => (line, first_word_column(&content, line)),
+                (None, _, None) => anyhow::bail!("Provide line and column, or symbol"),
+            };
+            (path, content, line, column)
+        } else {
+            let symbol = symbol.context("Provide file_path with a position, or symbol")?;
+            let (path, position) = manager
+                .find_symbol(symbol)
+                .await?
+                .into_iter()
+                .find_map(|s| {
+                    let path = workspace_edit::uri_to_path(&s.location.uri).ok()?;
+                    let path = policy.resolve(&path.to_string_lossy()).ok()?;
+                    Some((path, s.location.range?.start))
+                })
+                .with_context(|| format!("No symbol named '{}' found in the workspace", symbol))?;
+            let content = tokio::fs::read_to_string(&path).await.context("Failed to read file")?;
+            let column = column_of(&content, &position);
+            (path, content, position.line as usize + 1, column)
+        };
+
+        let position = workspace_edit::position_at(&content, line, column)
+            .with_context(|| format!("Line {} is past the end of {}", line, path.display()))?;
+        let contents = manager
+            .hover(&path.to_string_lossy(), position)
+            .await?
+            .map(|hover| hover.markdown())
+            .unwrap_or_default();
+
+        let file_path = display_path(policy, &path);
+        Ok(HoverResult {
+            response_summary: if contents.is_empty() {
+                format!("nothing at {}:{}:{}", file_path, line, column)
+            } else {
+                format!("{}:{}:{}", file_path, line, column)
+            },
+            file_path,
+            line,
+            column,
+            contents,
+        })
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "The file to inspect. Must be within current working directory. Omit to look up symbol across the workspace."
+                        },
+                        "line": {
+                            "type": "integer",
+                            "description": "Line of the position (1-based)."
+                        },
+                        "column": {
+                            "type": "integer",
+                            "description": "Column on that line (1-based). Optional when symbol is given."
+                        },
+                        "symbol": {
+                            "type": "string",
+                            "description": "Name of the symbol. Located in file_path (on line, if given), or across the workspace when file_path is omitted."
+                        }
+                    },
+                    "required": []
+                }
+            }
+        })
+    }
+}
+
+/// 1-based line and column of the first whole-word occurrence of `symbol`
+fn find_in_text(content: &str, symbol: &str, line: Option<usize>) -> Option<(usize, usize)> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    content
+        .lines()
+        .enumerate()
+        .filter(|(i, _)| line.is_none_or(|l| l == i + 1))
+        .find_map(|(i, text)| {
+            text.match_indices(symbol)
+                .find(|(start, _)| {
+                    let before = text[..*start].chars().next_back();
+                    let after = text[start + symbol.len()..].chars().next();
+                    !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+                })
+                .map(|(start, _)| (i + 1, text[..start].chars().count() + 1))
+        })
+}
+
+/// 1-based column of the first non-whitespace character of a line
+fn first_word_column(content: &str, line: usize) -> usize {
+    content
+        .lines()
+        .nth(line.saturating_sub(1))
+        .map(|text| text.chars().take_while(|c| c.is_whitespace()).count() + 1)
+        .unwrap_or(1)
+}
+
+/// 1-based character column of an LSP position
+fn column_of(content: &str, position: &Position) -> usize {
+    let Some(offset) = workspace_edit::offset_at(content, position) else {
+        return 1;
+    };
+    let line_start = content[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    content[line_start..offset].chars().count() + 1
+}
+
+fn display_path(policy: &PathPolicy, path: &Path) -> String {
+    path.strip_prefix(policy.root())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string()
+}
+
+impl Default for HoverTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "hover".to_string(),
+            description: "Shows the type signature and documentation of a symbol using the language server.".to_string(),
+            lsp_manager: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl ToolSpec for HoverTool {
+    type Args = HoverRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Read
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Explored
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let policy = PathPolicy::new()?;
+        let self_clone = self.clone();
+        let result = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current()
+                .block_on(async move { self_clone.request_hover(&policy, &args).await })
+        })?;
+
+        let location = format!("{}:{}:{}", result.file_path, result.line, result.column);
+        let stdout = if result.contents.is_empty() {
+            format!("No hover information at {}", location)
+        } else {
+            format!("{}\n\n{}", location, result.contents)
+        };
+        let response_summary = result.response_summary.clone();
+        let data = serde_json::to_value(result)?;
+        Ok(ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::Hover;
+
+    #[test]
+    fn locates_symbols_by_whole_word() {
+        let content = "let total_sum = 1;\nlet sum = total_sum + sum;\n";
+        assert_eq!(find_in_text(content, "sum", None), Some((2, 5)));
+        assert_eq!(find_in_text(content, "sum", Some(2)), Some((2, 5)));
+        assert_eq!(find_in_text(content, "total_sum", Some(2)), Some((2, 11)));
+        assert_eq!(find_in_text(content, "sum", Some(1)), None);
+        assert_eq!(first_word_column("fn a() {\n    call();\n}", 2), 5);
+        assert_eq!(column_of("é = 1;\nlet x", &Position { line: 0, character: 4 }), 5);
+    }
+
+    #[test]
+    fn renders_every_hover_content_shape() {
+        let hover = |contents: serde_json::Value| -> Hover {
+            serde_json::from_value(serde_json::json!({ "contents": contents })).unwrap()
+        };
+        assert_eq!(
+            hover(serde_json::json!({ "kind": "markdown", "value": "```rust\nfn a()\n```" })).markdown(),
+            "```rust\nfn a()\n```"
+        );
+        assert_eq!(
+            hover(serde_json::json!([{ "language": "python", "value": "def a() -> int" }, "Docs."])).markdown(),
+            "```python\ndef a() -> int\n```\n\nDocs."
+        );
+        assert_eq!(hover(serde_json::json!("")).markdown(), "");
+    }
+}
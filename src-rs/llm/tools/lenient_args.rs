@@ -0,0 +1,282 @@
+//! Reading tool arguments the way the model meant them.
+//!
+//! Models do not all serialize arguments alike: some quote numbers and
+//! booleans (`"limit": "50"`, `"recursive": "true"`), some send an array or
+//! object JSON-encoded into a string, some send null for an argument they
+//! leave out, and a few encode the whole argument object a second time. The
+//! `deserialize_*_lax` helpers in `serde_util` handled this field by field;
+//! `conform` does it for every builtin tool at once, guided by the tool's own
+//! parameter schema, before the arguments reach serde. Only values that do not
+//! already have the type the schema asks for are touched, and those that
+//! cannot be converted are left for serde to reject.
+
+use anyhow::{Context, Result};
+use serde_json::{Number, Value};
+
+/// The arguments as a JSON value: empty arguments are an empty object, and an
+/// object encoded into a JSON string is decoded
+pub fn parse_value(arguments: &str) -> Result<Value> {
+    if arguments.trim().is_empty() {
+        return Ok(Value::Object(Default::default()));
+    }
+    let value: Value = serde_json::from_str(arguments).context("Failed to parse tool arguments")?;
+    match value {
+        Value::String(inner) => match serde_json::from_str::<Value>(&inner) {
+            Ok(decoded @ Value::Object(_)) => Ok(decoded),
+            _ => Ok(Value::String(inner)),
+        },
+        value => Ok(value),
+    }
+}
+
+/// Bring `value` to the types of `schema`, and drop null object members:
+/// strict schemas make optional arguments nullable, and a null one means it
+/// was left out
+pub fn conform(value: &mut Value, schema: &Value) {
+    let schema = branch(schema, value);
+    let types = types(schema);
+    if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+        if let Some(coerced) = types.iter().find_map(|t| coerce(value, t)) {
+            *value = coerced;
+        }
+    }
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, v| !v.is_null());
+            let properties = schema.get("properties");
+            for (name, member) in map.iter_mut() {
+                conform(member, properties.and_then(|p| p.get(name)).unwrap_or(&Value::Null));
+            }
+        }
+        Value::Array(items) => {
+            let item_schema = schema.get("items").unwrap_or(&Value::Null);
+            items.iter_mut().for_each(|item| conform(item, item_schema));
+        }
+        _ => {}
+    }
+}
+
+/// Truth value of a boolean, a quoted one ("true", "No", "1") or 0/1
+pub fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => parse_bool(s),
+        Value::Number(n) => match n.as_u64() {
+            Some(0) => Some(false),
+            Some(1) => Some(true),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+pub fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// The `anyOf` alternative `value` fits, or may be converted to; the schema
+/// itself if it has none
+fn branch<'a>(schema: &'a Value, value: &Value) -> &'a Value {
+    let Some(alternatives) = schema.get("anyOf").and_then(Value::as_array) else {
+        return schema;
+    };
+    alternatives
+        .iter()
+        .find(|alt| types(alt).iter().any(|t| has_type(value, t)))
+        .or_else(|| alternatives.iter().find(|alt| types(alt).iter().any(|t| coerce(value, t).is_some())))
+        .unwrap_or(schema)
+}
+
+/// `type` of a schema, which strict schemas make a list such as ["integer", "null"]
+fn types(schema: &Value) -> Vec<&str> {
+    match schema.get("type") {
+        Some(Value::String(t)) => vec![t.as_str()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn has_type(value: &Value, json_type: &str) -> bool {
+    match json_type {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => false,
+    }
+}
+
+/// `value` as `json_type`, if it is a spelling of one
+fn coerce(value: &Value, json_type: &str) -> Option<Value> {
+    match (json_type, value) {
+        ("integer", Value::String(s)) => integer(&number(s.trim())?),
+        ("integer", Value::Number(n)) => integer(n),
+        ("number", Value::String(s)) => number(s.trim()).map(Value::Number),
+        ("boolean", _) => as_bool(value).map(Value::Bool),
+        ("string", Value::Number(n)) => Some(Value::String(n.to_string())),
+        ("string", Value::Bool(b)) => Some(Value::String(b.to_string())),
+        ("array" | "object", Value::String(s)) => serde_json::from_str::<Value>(s)
+            .ok()
+            .filter(|decoded| has_type(decoded, json_type)),
+        _ => None,
+    }
+}
+
+fn number(s: &str) -> Option<Number> {
+    if let Ok(n) = s.parse::<i64>() {
+        return Some(n.into());
+    }
+    if let Ok(n) = s.parse::<u64>() {
+        return Some(n.into());
+    }
+    s.parse::<f64>().ok().and_then(Number::from_f64)
+}
+
+/// A whole number, also when written as 5.0
+fn integer(n: &Number) -> Option<Value> {
+    if n.is_i64() || n.is_u64() {
+        return Some(Value::Number(n.clone()));
+    }
+    let f = n.as_f64()?;
+    (f.fract() == 0.0 && f.abs() < i64::MAX as f64).then(|| Value::from(f as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::tools::list_available_tools;
+    use crate::llm::tools::tool_trait::parse_confirmed_and_args;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use serde_json::json;
+
+    /// A random value that `schema` accepts, leaving out optional properties at random
+    fn generate(schema: &Value, rng: &mut StdRng, depth: usize) -> Value {
+        if let Some(choices) = schema.get("enum").and_then(Value::as_array) {
+            return choices[rng.gen_range(0..choices.len())].clone();
+        }
+        let types: Vec<&str> = types(schema).into_iter().filter(|t| *t != "null").collect();
+        match types.first().copied().unwrap_or("string") {
+            "integer" => json!(rng.gen_range(-5i64..100_000)),
+            "number" => json!(rng.gen_range(0..1000) as f64 / 8.0),
+            "boolean" => json!(rng.gen_bool(0.5)),
+            "array" if depth < 3 => {
+                let items = schema.get("items").unwrap_or(&Value::Null);
+                (0..rng.gen_range(0..3)).map(|_| generate(items, rng, depth + 1)).collect()
+            }
+            "object" if depth < 3 => {
+                let required: Vec<&str> = schema
+                    .get("required")
+                    .and_then(Value::as_array)
+                    .map(|r| r.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+                let mut map = serde_json::Map::new();
+                for (name, property) in schema.get("properties").and_then(Value::as_object).into_iter().flatten() {
+                    if required.contains(&name.as_str()) || rng.gen_bool(0.5) {
+                        map.insert(name.clone(), generate(property, rng, depth + 1));
+                    }
+                }
+                Value::Object(map)
+            }
+            "array" => json!([]),
+            "object" => json!({}),
+            _ => json!(format!("s{}", rng.gen_range(0..1000))),
+        }
+    }
+
+    /// `value` written the ways models write it: quoted scalars, encoded
+    /// arrays and objects, and nulls for left-out properties
+    fn mangle(value: &Value, schema: &Value, rng: &mut StdRng) -> Value {
+        match value {
+            Value::Number(n) if rng.gen_bool(0.5) => json!(format!(" {} ", n)),
+            Value::Bool(b) if rng.gen_bool(0.5) => json!(if *b { "True" } else { "false" }),
+            Value::Array(items) => {
+                let item_schema = schema.get("items").unwrap_or(&Value::Null);
+                let items: Value = items.iter().map(|item| mangle(item, item_schema, rng)).collect();
+                if rng.gen_bool(0.3) { json!(items.to_string()) } else { items }
+            }
+            Value::Object(map) => {
+                let properties = schema.get("properties").and_then(Value::as_object);
+                let mut mangled = serde_json::Map::new();
+                for (name, property) in properties.into_iter().flatten() {
+                    match map.get(name) {
+                        Some(member) => {
+                            mangled.insert(name.clone(), mangle(member, property, rng));
+                        }
+                        None if rng.gen_bool(0.5) => {
+                            mangled.insert(name.clone(), Value::Null);
+                        }
+                        None => {}
+                    }
+                }
+                Value::Object(mangled)
+            }
+            other => other.clone(),
+        }
+    }
+
+    #[test]
+    fn mangled_arguments_read_like_clean_ones() {
+        let mut rng = StdRng::seed_from_u64(3234);
+        for tool in list_available_tools() {
+            let parameters = tool.to_tool_definition()["function"]["parameters"].clone();
+            for _ in 0..200 {
+                let clean = generate(&parameters, &mut rng, 0);
+                let mut mangled = mangle(&clean, &parameters, &mut rng).to_string();
+                if rng.gen_bool(0.1) {
+                    mangled = json!(mangled).to_string();
+                }
+
+                let mut expected = clean.clone();
+                conform(&mut expected, &parameters);
+                assert_eq!(expected, clean, "tool {} changed clean arguments", tool.name());
+                let mut read = parse_value(&mangled).unwrap();
+                conform(&mut read, &parameters);
+                assert_eq!(read, clean, "tool {} read {} wrongly", tool.name(), mangled);
+                assert_eq!(
+                    tool.summarize_args(&mangled),
+                    tool.summarize_args(&clean.to_string()),
+                    "tool {}",
+                    tool.name()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn builtin_requests_accept_loose_spellings() {
+        let delete = list_available_tools().into_iter().find(|t| t.name() == "delete").unwrap();
+        let parameters = &delete.to_tool_definition()["function"]["parameters"];
+        let (request, confirmed): (crate::llm::tools::delete::DeleteRequest, bool) =
+            parse_confirmed_and_args(r#"{"path":"build","recursive":"YES","confirmed":"true"}"#, parameters).unwrap();
+        assert!(request.recursive && confirmed);
+
+        assert_eq!(parse_value("  ").unwrap(), json!({}));
+        assert_eq!(parse_value(r#""{\"a\":1}""#).unwrap(), json!({ "a": 1 }));
+        assert!(parse_value("not json").is_err());
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "limit": { "type": ["integer", "null"] },
+                "ratio": { "type": "number" },
+                "name": { "type": "string" },
+                "id": { "anyOf": [{ "type": "integer" }, { "type": "array", "items": { "type": "integer" } }] }
+            }
+        });
+        let mut value = json!({ "limit": "5.0", "ratio": "0.5", "name": 7, "id": "[1, \"2\"]", "extra": null });
+        conform(&mut value, &schema);
+        assert_eq!(value, json!({ "limit": 5, "ratio": 0.5, "name": "7", "id": [1, 2] }));
+        // Values that spell no such type are left for serde to reject
+        let mut value = json!({ "limit": "many", "ratio": true });
+        conform(&mut value, &schema);
+        assert_eq!(value, json!({ "limit": "many", "ratio": true }));
+    }
+}
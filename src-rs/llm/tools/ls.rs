@@ -1,3 +1,4 @@
+use crate::llm::backend::{self, ExecBackend};
 use crate::llm::config::AppConfig;
 use crate::llm::utils::path_policy::PathPolicy;
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
@@ -6,6 +7,9 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Levels a remote listing descends; deeper entries are not shown
+const REMOTE_MAX_DEPTH: usize = 8;
+
 /// Ls tool for displaying directory structure in tree format
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LsTool {
@@ -19,6 +23,8 @@ pub struct LsTool {
     pub default_ignore: Vec<String>,
 }
 
+use crate::llm::utils::serde_util::{deserialize_usize_lax, deserialize_usize_opt_lax};
+
 /// Input arguments for the ls tool
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LsArgs {
@@ -28,6 +34,12 @@ pub struct LsArgs {
     /// Optional array of glob patterns to ignore
     #[serde(default)]
     pub ignore: Option<Vec<String>>,
+    /// Tree lines to skip, for the pages after the first
+    #[serde(default, deserialize_with = "deserialize_usize_lax")]
+    pub offset: usize,
+    /// Tree lines to return, at most `max_ls_files`
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub limit: Option<usize>,
 }
 
 /// Result of executing ls command (matching OpenCode format)
@@ -44,10 +56,16 @@ pub struct LsResult {
 /// Metadata for ls result
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LsMetadata {
-    /// Total number of items found
+    /// Number of items in this page
     pub count: usize,
-    /// Whether the result was truncated
+    /// Whether more items follow this page
     pub truncated: bool,
+    /// Number of items in the whole tree
+    pub total_count: usize,
+    /// Items skipped before this page
+    pub offset: usize,
+    /// Offset of the next page, if there is one
+    pub next_offset: Option<usize>,
 }
 
 /// Internal structure for building the tree
@@ -284,40 +302,95 @@ impl LsTool {
         Ok(root)
     }
 
-    /// Render tree to string with box-drawing characters
+    /// `build_tree` for the directory's counterpart on the session's remote target
+    fn build_remote_tree(
+        &self,
+        target: &dyn ExecBackend,
+        root_path: &Path,
+        ignore_patterns: &[String],
+    ) -> Result<TreeNode> {
+        let remote_dir = target.target_path(root_path)?;
+        let Some(mut entries) = target.list_tree(&remote_dir, REMOTE_MAX_DEPTH)? else {
+            anyhow::bail!("Directory does not exist on {}: {}", target.name(), remote_dir);
+        };
+        // Sorted, every directory comes before its contents
+        entries.sort();
+
+        let mut root = TreeNode {
+            name: root_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| ".".to_string()),
+            path: root_path.to_path_buf(),
+            is_dir: true,
+            children: Vec::new(),
+        };
+        for (rel, is_dir) in entries {
+            let path = root_path.join(&rel);
+            let ignored = path
+                .ancestors()
+                .take_while(|p| *p != root_path)
+                .any(|p| Self::should_ignore(p, ignore_patterns, root_path));
+            if !ignored {
+                Self::insert_node(&mut root, &rel, path, is_dir);
+            }
+        }
+        Self::sort_tree(&mut root);
+        Ok(root)
+    }
+
+    /// Add the entry at `rel` (relative to `node`) below its parent directory node
+    fn insert_node(node: &mut TreeNode, rel: &str, path: PathBuf, is_dir: bool) {
+        match rel.split_once('/') {
+            Some((dir, rest)) => {
+                let index = match node.children.iter().position(|c| c.is_dir && c.name == dir) {
+                    Some(index) => index,
+                    None => {
+                        node.children.push(TreeNode {
+                            name: dir.to_string(),
+                            path: node.path.join(dir),
+                            is_dir: true,
+                            children: Vec::new(),
+                        });
+                        node.children.len() - 1
+                    }
+                };
+                Self::insert_node(&mut node.children[index], rest, path, is_dir);
+            }
+            None => node.children.push(TreeNode {
+                name: rel.to_string(),
+                path,
+                is_dir,
+                children: Vec::new(),
+            }),
+        }
+    }
+
+    /// Directories first, then alphabetically, as `build_tree` orders entries
+    fn sort_tree(node: &mut TreeNode) {
+        node.children
+            .sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        for child in &mut node.children {
+            Self::sort_tree(child);
+        }
+    }
+
+    /// Render tree to lines with box-drawing characters
     ///
     /// # Arguments
     /// * `node` - Tree node to render
     /// * `prefix` - Current line prefix
     /// * `is_root` - Whether this is the root node
-    /// * `output` - Output buffer
-    /// * `count` - Current item count
-    /// * `max_items` - Maximum items to render
-    fn render_tree(
-        node: &TreeNode,
-        prefix: &str,
-        is_root: bool,
-        output: &mut String,
-        count: &mut usize,
-        max_items: usize,
-    ) -> bool {
-        if *count >= max_items {
-            return true; // truncated
-        }
-
+    /// * `lines` - Output lines, one per item
+    fn render_tree(node: &TreeNode, prefix: &str, is_root: bool, lines: &mut Vec<String>) {
         // Render current node
         if is_root {
-            output.push_str(".\n");
-            *count += 1;
+            lines.push(".".to_string());
         }
 
         // Render children
         if node.is_dir && !node.children.is_empty() {
             for (i, child) in node.children.iter().enumerate() {
-                if *count >= max_items {
-                    return true;
-                }
-
                 let is_last_child = i == node.children.len() - 1;
                 let connector = if is_last_child {
                     "└── "
@@ -331,8 +404,7 @@ impl LsTool {
                 };
 
                 // Print current child
-                output.push_str(&format!("{}{}{}\n", prefix, connector, name_display));
-                *count += 1;
+                lines.push(format!("{}{}{}", prefix, connector, name_display));
 
                 // Recursively render child's children
                 if child.is_dir && !child.children.is_empty() {
@@ -341,17 +413,10 @@ impl LsTool {
                     } else {
                         format!("{}│   ", prefix)
                     };
-
-                    let truncated =
-                        Self::render_tree(child, &child_prefix, false, output, count, max_items);
-                    if truncated {
-                        return true;
-                    }
+                    Self::render_tree(child, &child_prefix, false, lines);
                 }
             }
         }
-
-        false
     }
 
     /// Execute ls command at the given path
@@ -359,10 +424,18 @@ impl LsTool {
     /// # Arguments
     /// * `path` - Optional path to list. If None, lists current directory
     /// * `ignore` - Optional array of glob patterns to ignore
+    /// * `offset` - Tree lines to skip
+    /// * `limit` - Tree lines to return, capped at `max_ls_files`
     ///
     /// # Returns
     /// * `Result<LsResult>` - The result containing tree structure and metadata
-    fn run_ls(&self, path: Option<&str>, ignore: Option<Vec<String>>) -> Result<LsResult> {
+    fn run_ls(
+        &self,
+        path: Option<&str>,
+        ignore: Option<Vec<String>>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<LsResult> {
         let cwd = std::env::current_dir().context("Failed to get current directory")?;
         let policy = PathPolicy::new()?;
         let target_path = match path {
@@ -372,14 +445,6 @@ impl LsTool {
             _ => cwd,
         };
 
-        if !target_path.exists() {
-            anyhow::bail!("Path does not exist: {}", target_path.display());
-        }
-
-        if !target_path.is_dir() {
-            anyhow::bail!("Path is not a directory: {}", target_path.display());
-        }
-
         // Merge default ignore patterns with user-provided ones
         let mut ignore_patterns = self.default_ignore.clone();
         if let Some(user_ignore) = ignore {
@@ -387,24 +452,54 @@ impl LsTool {
         }
 
         // Build tree structure
-        let tree = self.build_tree(&target_path, &ignore_patterns)?;
+        let tree = match backend::current() {
+            Some(target) => self.build_remote_tree(target.as_ref(), &target_path, &ignore_patterns)?,
+            None => {
+                if !target_path.exists() {
+                    anyhow::bail!("Path does not exist: {}", target_path.display());
+                }
+                if !target_path.is_dir() {
+                    anyhow::bail!("Path is not a directory: {}", target_path.display());
+                }
+                self.build_tree(&target_path, &ignore_patterns)?
+            }
+        };
 
-        // Render tree to string
-        let mut output = String::new();
-        let mut count = 0;
-        let truncated =
-            Self::render_tree(&tree, "", true, &mut output, &mut count, self.max_ls_files);
+        // Render tree to lines and take the requested page of them
+        let mut lines = Vec::new();
+        Self::render_tree(&tree, "", true, &mut lines);
+        let total_count = lines.len();
+        let limit = limit.unwrap_or(self.max_ls_files).clamp(1, self.max_ls_files.max(1));
+        let offset = offset.min(total_count);
+        let page = &lines[offset..(offset + limit).min(total_count)];
+        let count = page.len();
+        let next_offset = (offset + count < total_count).then_some(offset + count);
 
-        if truncated {
+        let mut output = String::new();
+        for line in page {
+            output.push_str(line);
+            output.push('\n');
+        }
+        if let Some(next_offset) = next_offset {
             output.push_str(&format!(
-                "\n... (truncated at {} items)\n",
-                self.max_ls_files
+                "\n... {} more items (showing {}-{} of {}); call again with offset={} for the next page\n",
+                total_count - next_offset,
+                offset + 1,
+                next_offset,
+                total_count,
+                next_offset
             ));
         }
 
         Ok(LsResult {
             content: output,
-            metadata: LsMetadata { count, truncated },
+            metadata: LsMetadata {
+                count,
+                truncated: next_offset.is_some(),
+                total_count,
+                offset,
+                next_offset,
+            },
             response_summary: format!("{} files", count),
         })
     }
@@ -429,6 +524,14 @@ impl LsTool {
                                 "type": "string"
                             },
                             "description": "Array of glob patterns to ignore (e.g., [\"*.log\", \"tmp/**\"]). These patterns are added to the default ignore list."
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "Number of tree lines to skip, to read the page after a truncated listing. Defaults to 0."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": format!("Maximum number of tree lines to return. Defaults to, and is capped at, {}.", self.max_ls_files)
                         }
                     },
                     "required": []
@@ -484,7 +587,7 @@ impl ToolSpec for LsTool {
     }
 
     fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
-        let result = self.run_ls(args.path.as_deref(), args.ignore)?;
+        let result = self.run_ls(args.path.as_deref(), args.ignore, args.offset, args.limit)?;
         let response_summary = result.response_summary.clone();
         let stdout = result.content.clone();
         let data = serde_json::to_value(result)?;
@@ -0,0 +1,172 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::overlay;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+/// Mkdir tool for creating directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MkdirTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+}
+
+/// Mkdir request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MkdirRequest {
+    /// Directory to create, including missing parents
+    pub path: String,
+}
+
+/// Result of creating a directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MkdirResult {
+    /// Directory path as requested
+    pub path: String,
+    /// False when the directory already existed
+    pub created: bool,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl MkdirTool {
+    /// Create a new MkdirTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self {
+                tool_name: config.tool_mkdir.tool_name,
+                description: config.tool_mkdir.description,
+            },
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new MkdirTool from a specific AppConfig
+    #[allow(dead_code)]
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_mkdir.tool_name.clone(),
+            description: config.tool_mkdir.description.clone(),
+        }
+    }
+
+    /// Create the directory and any missing parents
+    fn run_mkdir(&self, policy: &PathPolicy, request: &MkdirRequest) -> Result<MkdirResult> {
+        let path = policy.resolve(&request.path)?;
+        let current = overlay::read_path(&path);
+
+        if current.exists() {
+            if !current.is_dir() {
+                anyhow::bail!("Path '{}' exists and is not a directory", request.path);
+            }
+            return Ok(MkdirResult {
+                path: request.path.clone(),
+                created: false,
+                response_summary: "already exists".to_string(),
+            });
+        }
+
+        match overlay::Overlay::current() {
+            Some(overlay) => overlay.create_dir(&path)?,
+            None => fs::create_dir_all(&path)
+                .with_context(|| format!("Failed to create directory: {}", path.display()))?,
+        }
+
+        Ok(MkdirResult {
+            path: request.path.clone(),
+            created: true,
+            response_summary: "created".to_string(),
+        })
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "The directory to create. Missing parent directories are created as well. Must be within current working directory."
+                        }
+                    },
+                    "required": ["path"]
+                }
+            }
+        })
+    }
+}
+
+impl Default for MkdirTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "mkdir".to_string(),
+            description: "Creates a directory, including missing parent directories.".to_string(),
+        }
+    }
+}
+
+impl ToolSpec for MkdirTool {
+    type Args = MkdirRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Edit
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Edited
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(arg_summary::path(&args.path))
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let policy = PathPolicy::new()?;
+        let result = self.run_mkdir(&policy, &args)?;
+        let response_summary = result.response_summary.clone();
+        let stdout = if result.created {
+            format!("Created directory: {}", result.path)
+        } else {
+            format!("Directory already exists: {}", result.path)
+        };
+        let data = serde_json::to_value(result)?;
+        Ok(ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary))
+    }
+}
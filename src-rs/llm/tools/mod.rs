@@ -1,25 +1,49 @@
 // Tool definitions and implementations
 
+pub mod arg_summary;
+pub mod ask_user;
 pub mod bash;
+pub mod code_action;
+pub mod delete;
+pub mod deps;
 pub mod diagnostics;
 pub mod edit;
 pub mod fetch;
 pub mod glob;
 pub mod grep;
+pub mod hover;
+pub mod lenient_args;
 pub mod ls;
+pub mod mkdir;
+pub mod move_file;
+pub mod read_artifact;
+pub mod rename_symbol;
+pub mod repo_stats;
+pub mod skill;
 pub mod todo_write;
 pub mod tool_trait;
 pub mod view;
 pub mod write;
 
 // Re-export main types
+pub use ask_user::AskUserTool;
 pub use bash::BashTool;
+pub use code_action::CodeActionTool;
+pub use delete::DeleteTool;
+pub use deps::DepsTool;
 pub use diagnostics::DiagnosticsTool;
 pub use edit::EditTool;
 pub use fetch::FetchTool;
 pub use glob::GlobTool;
 pub use grep::GrepTool;
+pub use hover::HoverTool;
 pub use ls::LsTool;
+pub use mkdir::MkdirTool;
+pub use move_file::MoveTool;
+pub use read_artifact::ReadArtifactTool;
+pub use rename_symbol::RenameSymbolTool;
+pub use repo_stats::RepoStatsTool;
+pub use skill::SkillTool;
 pub use todo_write::TodoWriteTool;
 pub use tool_trait::{Tool, ToolAdapter};
 pub use view::ViewTool;
@@ -28,13 +52,23 @@ pub use write::WriteTool;
 /// Get list of available tools
 pub fn list_available_tools() -> Vec<Box<dyn Tool>> {
     vec![
+        Box::new(ToolAdapter(AskUserTool::new())),
         Box::new(ToolAdapter(BashTool::new())),
+        Box::new(ToolAdapter(CodeActionTool::new())),
+        Box::new(ToolAdapter(DeleteTool::new())),
+        Box::new(ToolAdapter(DepsTool::new())),
         Box::new(ToolAdapter(DiagnosticsTool::new())),
         Box::new(ToolAdapter(EditTool::new())),
         Box::new(ToolAdapter(FetchTool::new())),
         Box::new(ToolAdapter(GlobTool::new())),
         Box::new(ToolAdapter(GrepTool::new())),
+        Box::new(ToolAdapter(HoverTool::new())),
         Box::new(ToolAdapter(LsTool::new())),
+        Box::new(ToolAdapter(MkdirTool::new())),
+        Box::new(ToolAdapter(MoveTool::new())),
+        Box::new(ToolAdapter(ReadArtifactTool::new())),
+        Box::new(ToolAdapter(RenameSymbolTool::new())),
+        Box::new(ToolAdapter(RepoStatsTool::new())),
         Box::new(ToolAdapter(TodoWriteTool::new())),
         Box::new(ToolAdapter(ViewTool::new())),
         Box::new(ToolAdapter(WriteTool::new())),
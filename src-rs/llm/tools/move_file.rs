@@ -0,0 +1,337 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::file_lock;
+use crate::llm::utils::file_tracker::FILE_READ_TRACKER;
+use crate::llm::utils::overlay;
+use crate::llm::utils::path_policy::{self, PathPolicy};
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Move tool for moving or renaming files and directories
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+}
+
+/// Move request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveRequest {
+    /// Existing file or directory
+    pub source: String,
+    /// New path (not a parent directory to move into)
+    pub destination: String,
+    /// Replace an existing destination file
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Result of a move
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveResult {
+    pub source: String,
+    pub destination: String,
+    /// Whether an existing destination file was replaced
+    pub overwritten: bool,
+    /// Whether the move was performed (false while awaiting confirmation)
+    pub executed: bool,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl MoveTool {
+    /// Create a new MoveTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self {
+                tool_name: config.tool_move.tool_name,
+                description: config.tool_move.description,
+            },
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new MoveTool from a specific AppConfig
+    #[allow(dead_code)]
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_move.tool_name.clone(),
+            description: config.tool_move.description.clone(),
+        }
+    }
+
+    /// Move `source` to `destination`
+    ///
+    /// Replacing an existing file requires `overwrite` and, for the actual
+    /// replacement, confirmation. Existing directories are never replaced.
+    fn run_move(&self, policy: &PathPolicy, request: &MoveRequest, confirmed: bool) -> Result<MoveResult> {
+        let source = policy.resolve(&request.source)?;
+        let destination = policy.resolve(&request.destination)?;
+        let overlay = overlay::Overlay::current();
+        let (current_source, current_destination) = match &overlay {
+            Some(overlay) => (overlay.read_path(&source), overlay.read_path(&destination)),
+            None => (source.clone(), destination.clone()),
+        };
+
+        if !current_source.exists() {
+            anyhow::bail!("Source not found: {}", request.source);
+        }
+        if source == policy.root() {
+            anyhow::bail!("Cannot move the workspace root");
+        }
+        if path_policy::is_git_metadata(&source) || path_policy::is_git_metadata(&destination) {
+            anyhow::bail!("Paths in .git are protected and cannot be moved");
+        }
+        if source == destination {
+            anyhow::bail!("Source and destination are the same path");
+        }
+        if current_source.is_dir() && destination.starts_with(&source) {
+            anyhow::bail!("Cannot move a directory into itself");
+        }
+        if overlay.is_some() && current_source.is_dir() {
+            anyhow::bail!("Moving directories is not supported in overlay mode");
+        }
+
+        let overwritten = current_destination.exists();
+        if overwritten {
+            if current_destination.is_dir() {
+                anyhow::bail!(
+                    "Destination '{}' is an existing directory. Give the full target path, e.g. '{}/<name>'.",
+                    request.destination,
+                    request.destination.trim_end_matches('/')
+                );
+            }
+            if !request.overwrite {
+                anyhow::bail!(
+                    "Destination '{}' already exists. Set overwrite to true to replace it.",
+                    request.destination
+                );
+            }
+            if !confirmed {
+                return Ok(MoveResult {
+                    source: request.source.clone(),
+                    destination: request.destination.clone(),
+                    overwritten: false,
+                    executed: false,
+                    response_summary: "Requires confirmation".to_string(),
+                });
+            }
+        }
+
+        file_lock::lock_for_current_session(&source)?;
+        file_lock::lock_for_current_session(&destination)?;
+        checkpoint::record_before_change(&source);
+        file_activity::record_edited(&source);
+        checkpoint::record_before_change(&destination);
+        file_activity::record_edited(&destination);
+
+        match &overlay {
+            Some(overlay) => {
+                let target = overlay.write_path(&destination)?;
+                fs::copy(&current_source, &target)
+                    .with_context(|| format!("Failed to copy to {}", destination.display()))?;
+                overlay.remove(&source)?;
+            }
+            None => {
+                if let Some(parent) = destination.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+                }
+                move_path(&source, &destination)?;
+            }
+        }
+
+        FILE_READ_TRACKER
+            .lock()
+            .unwrap()
+            .record_move(&source.to_string_lossy(), &destination.to_string_lossy());
+
+        Ok(MoveResult {
+            source: request.source.clone(),
+            destination: request.destination.clone(),
+            overwritten,
+            executed: true,
+            response_summary: if overwritten {
+                "moved (overwritten)".to_string()
+            } else {
+                "moved".to_string()
+            },
+        })
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "source": {
+                            "type": "string",
+                            "description": "The file or directory to move. Must be within current working directory."
+                        },
+                        "destination": {
+                            "type": "string",
+                            "description": "The full new path (not the directory to move into). Missing parent directories are created."
+                        },
+                        "overwrite": {
+                            "type": "boolean",
+                            "description": "Replace the destination if it is an existing file. Requires user confirmation. Defaults to false."
+                        }
+                    },
+                    "required": ["source", "destination"]
+                }
+            }
+        })
+    }
+}
+
+/// Rename, falling back to copy and remove for files on another filesystem
+fn move_path(source: &Path, destination: &Path) -> Result<()> {
+    match fs::rename(source, destination) {
+        Ok(()) => Ok(()),
+        Err(e) if source.is_file() => {
+            log::debug!("rename failed ({}), falling back to copy", e);
+            fs::copy(source, destination)
+                .with_context(|| format!("Failed to copy to {}", destination.display()))?;
+            fs::remove_file(source)
+                .with_context(|| format!("Failed to remove {}", source.display()))?;
+            Ok(())
+        }
+        Err(e) => Err(e).with_context(|| {
+            format!("Failed to move {} to {}", source.display(), destination.display())
+        }),
+    }
+}
+
+impl Default for MoveTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "move".to_string(),
+            description: "Moves or renames a file or directory.".to_string(),
+        }
+    }
+}
+
+impl ToolSpec for MoveTool {
+    type Args = MoveRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Move
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Edited
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let mut out = format!("{} → {}", arg_summary::path(&args.source), arg_summary::path(&args.destination));
+        if args.overwrite {
+            out.push_str(" (overwrite)");
+        }
+        Some(out)
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        let policy = PathPolicy::new()?;
+        let result = self.run_move(&policy, &args, confirmed)?;
+        let response_summary = result.response_summary.clone();
+        let executed = result.executed;
+        let stdout = if executed {
+            format!("Moved {} to {}", result.source, result.destination)
+        } else {
+            format!(
+                "Moving {} to {} replaces an existing file and requires confirmation.",
+                result.source, result.destination
+            )
+        };
+        let data = serde_json::to_value(result)?;
+        let mut tr = ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary);
+        tr.requires_confirmation = !executed;
+        tr.executed = executed;
+        Ok(tr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    fn request(source: &str, destination: &str, overwrite: bool) -> MoveRequest {
+        MoveRequest {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            overwrite,
+        }
+    }
+
+    #[test]
+    fn moves_into_new_directories_and_gates_overwrites() {
+        let workspace = FakeWorkspace::new().file("a.txt", "a").file("b.txt", "b").build().unwrap();
+        let root = workspace.path();
+        let policy = PathPolicy::with_root(root.to_path_buf());
+        let tool = MoveTool::default();
+
+        let moved = tool.run_move(&policy, &request("a.txt", "sub/dir/a.txt", false), false).unwrap();
+        assert!(moved.executed);
+        assert!(root.join("sub/dir/a.txt").exists());
+        assert!(!root.join("a.txt").exists());
+
+        let err = tool.run_move(&policy, &request("b.txt", "sub/dir/a.txt", false), false);
+        assert!(err.unwrap_err().to_string().contains("already exists"));
+
+        let pending = tool.run_move(&policy, &request("b.txt", "sub/dir/a.txt", true), false).unwrap();
+        assert!(!pending.executed);
+        assert_eq!(fs::read_to_string(root.join("sub/dir/a.txt")).unwrap(), "a");
+
+        let replaced = tool.run_move(&policy, &request("b.txt", "sub/dir/a.txt", true), true).unwrap();
+        assert!(replaced.overwritten);
+        assert_eq!(fs::read_to_string(root.join("sub/dir/a.txt")).unwrap(), "b");
+
+        assert!(tool.run_move(&policy, &request("sub", "sub/inner", false), false).is_err());
+
+        fs::create_dir_all(root.join(".git/refs")).unwrap();
+        fs::write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        assert!(tool.run_move(&policy, &request(".git/HEAD", "HEAD", false), false).is_err());
+        assert!(tool.run_move(&policy, &request("sub/dir/a.txt", ".git/refs/a.txt", false), false).is_err());
+        assert!(root.join(".git/HEAD").exists() && root.join("sub/dir/a.txt").exists());
+    }
+}
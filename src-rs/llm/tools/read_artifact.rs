@@ -0,0 +1,250 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::artifacts;
+use crate::llm::utils::serde_util::deserialize_usize_opt_lax;
+use crate::llm::utils::tool_access::current_tool_session;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Lines returned when no limit is given
+const DEFAULT_LIMIT: usize = 200;
+
+/// Upper bound on returned characters, keeping excerpts below the artifact threshold
+const MAX_OUTPUT_CHARS: usize = artifacts::ARTIFACT_THRESHOLD_CHARS / 2;
+
+/// Tool for reading excerpts of large tool outputs kept out of the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadArtifactTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+}
+
+/// Read artifact request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadArtifactRequest {
+    /// Artifact handle (`artifact://a12`) or id
+    pub artifact: String,
+    /// Line number to start reading from (1-based)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub offset: Option<usize>,
+    /// Number of lines to read
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub limit: Option<usize>,
+    /// Only return lines containing this text
+    #[serde(default)]
+    pub pattern: Option<String>,
+}
+
+/// Result of reading an artifact
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadArtifactResult {
+    pub artifact: String,
+    /// Tool that produced the artifact
+    pub source_tool: String,
+    pub total_lines: usize,
+    /// Numbered lines of the excerpt
+    pub content: String,
+    /// Whether more matching lines exist past the excerpt
+    pub truncated: bool,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl ReadArtifactTool {
+    /// Create a new ReadArtifactTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self {
+                tool_name: config.tool_read_artifact.tool_name,
+                description: config.tool_read_artifact.description,
+            },
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new ReadArtifactTool from a specific AppConfig
+    #[allow(dead_code)]
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_read_artifact.tool_name.clone(),
+            description: config.tool_read_artifact.description.clone(),
+        }
+    }
+
+    fn run_read(&self, request: &ReadArtifactRequest) -> Result<ReadArtifactResult> {
+        let session_id = current_tool_session().unwrap_or_default();
+        let artifact = artifacts::get(&session_id, &request.artifact).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Artifact not found: {}. It may have been evicted; re-run the tool that produced it.",
+                request.artifact
+            )
+        })?;
+
+        let total_lines = artifact.content.lines().count();
+        let start = request.offset.unwrap_or(1).max(1);
+        let limit = request.limit.unwrap_or(DEFAULT_LIMIT).max(1);
+        let pattern = request.pattern.as_deref().filter(|p| !p.is_empty());
+
+        let matching = artifact
+            .content
+            .lines()
+            .enumerate()
+            .skip(start - 1)
+            .filter(|(_, line)| pattern.is_none_or(|p| line.contains(p)));
+
+        let mut content = String::new();
+        let mut lines_read = 0;
+        let mut truncated = false;
+        for (index, line) in matching {
+            let numbered = format!("{}\t{}\n", index + 1, line);
+            if lines_read == limit || (lines_read > 0 && content.len() + numbered.len() > MAX_OUTPUT_CHARS) {
+                truncated = true;
+                break;
+            }
+            content.push_str(&numbered);
+            lines_read += 1;
+        }
+
+        Ok(ReadArtifactResult {
+            artifact: artifact.handle(),
+            source_tool: artifact.tool_name,
+            total_lines,
+            content,
+            truncated,
+            response_summary: format!("{} of {} lines", lines_read, total_lines),
+        })
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "artifact": {
+                            "type": "string",
+                            "description": "The artifact handle from a truncated tool result, e.g. artifact://a12."
+                        },
+                        "offset": {
+                            "type": "integer",
+                            "description": "The line number to start reading from (1-based). Defaults to 1."
+                        },
+                        "limit": {
+                            "type": "integer",
+                            "description": "The number of lines to read. Defaults to 200."
+                        },
+                        "pattern": {
+                            "type": "string",
+                            "description": "Only return lines containing this text (plain substring match)."
+                        }
+                    },
+                    "required": ["artifact"]
+                }
+            }
+        })
+    }
+}
+
+impl Default for ReadArtifactTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "read_artifact".to_string(),
+            description: "Reads part of a large tool output stored as an artifact.".to_string(),
+        }
+    }
+}
+
+impl ToolSpec for ReadArtifactTool {
+    type Args = ReadArtifactRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Read
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Explored
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(match &args.pattern {
+            Some(pattern) => format!("{} matching \"{}\"", args.artifact, pattern),
+            None => args.artifact.clone(),
+        })
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let result = self.run_read(&args)?;
+        let response_summary = result.response_summary.clone();
+        let mut stdout = result.content.clone();
+        if result.truncated {
+            stdout.push_str("... (more lines available, continue with a larger offset)\n");
+        }
+        let data = serde_json::to_value(result)?;
+        Ok(ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(artifact: &str, offset: Option<usize>, limit: Option<usize>, pattern: Option<&str>) -> ReadArtifactRequest {
+        ReadArtifactRequest {
+            artifact: artifact.to_string(),
+            offset,
+            limit,
+            pattern: pattern.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn reads_ranges_and_filters() {
+        let content: String = (1..=10).map(|i| format!("line {}\n", i)).collect();
+        let handle = artifacts::store("", "bash", content);
+        let tool = ReadArtifactTool::default();
+
+        let r = tool.run_read(&request(&handle, Some(3), Some(2), None)).unwrap();
+        assert_eq!(r.content, "3\tline 3\n4\tline 4\n");
+        assert!(r.truncated);
+        assert_eq!(r.total_lines, 10);
+
+        let r = tool.run_read(&request(&handle, None, None, Some("line 1"))).unwrap();
+        assert_eq!(r.content, "1\tline 1\n10\tline 10\n");
+        assert!(!r.truncated);
+
+        assert!(tool.run_read(&request("artifact://nope", None, None, None)).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+use crate::llm::config::AppConfig;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::serde_util::deserialize_usize_opt_lax;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
+use crate::lsp::protocol::WorkspaceEdit;
+use crate::lsp::workspace_edit;
+use crate::lsp::LspManager;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+/// Previewed renames kept for their confirmation, oldest dropped first
+const MAX_PREVIEWED: usize = 8;
+
+/// The changes of each previewed request, by its arguments as JSON
+type Previewed = VecDeque<(String, Vec<workspace_edit::FileChange>)>;
+
+/// Tool for renaming a symbol across the workspace through the language server
+#[derive(Clone)]
+pub struct RenameSymbolTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+    lsp_manager: Arc<Mutex<Option<Arc<LspManager>>>>,
+    /// Changes shown in unconfirmed previews, by request, so that confirming
+    /// applies exactly what was shown rather than asking the server again
+    previewed: Arc<Mutex<Previewed>>,
+}
+
+impl std::fmt::Debug for RenameSymbolTool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RenameSymbolTool")
+            .field("tool_name", &self.tool_name)
+            .field("description", &self.description)
+            .finish()
+    }
+}
+
+/// Rename request parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenameSymbolRequest {
+    /// File containing an occurrence of the symbol
+    pub file_path: String,
+    /// Line of the occurrence (1-based)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub line: Option<usize>,
+    /// Column of the occurrence (1-based, in characters)
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub column: Option<usize>,
+    /// New identifier
+    pub new_name: String,
+}
+
+/// Result of a rename
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameSymbolResult {
+    pub file_path: String,
+    pub new_name: String,
+    /// Per-file diff of the rename
+    pub files: Vec<DiffStats>,
+    /// Combined unified diff, shown before confirmation
+    pub preview: String,
+    /// Whether the edits were written (false while awaiting confirmation)
+    pub executed: bool,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl RenameSymbolTool {
+    /// Create a new RenameSymbolTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self::from_config(&config),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new RenameSymbolTool from a specific AppConfig
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_rename_symbol.tool_name.clone(),
+            description: config.tool_rename_symbol.description.clone(),
+            lsp_manager: Arc::new(Mutex::new(None)),
+            previewed: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    async fn get_or_init_lsp_manager(&self) -> Result<Arc<LspManager>> {
+        {
+            let manager_lock = self.lsp_manager.lock().unwrap();
+            if let Some(manager) = manager_lock.as_ref() {
+                return Ok(Arc::clone(manager));
+            }
+        }
+
+        let manager = LspManager::shared().await?;
+        *self.lsp_manager.lock().unwrap() = Some(Arc::clone(&manager));
+        Ok(manager)
+    }
+
+    /// Ask the language server for the rename edits
+    async fn request_rename(&self, policy: &PathPolicy, request: &RenameSymbolRequest) -> Result<WorkspaceEdit> {
+        let new_name = request.new_name.trim();
+        if new_name.is_empty() {
+            anyhow::bail!("new_name must not be empty");
+        }
+        let path = policy.resolve(&request.file_path)?;
+        if !path.is_file() {
+            anyhow::bail!("File not found: {}", request.file_path);
+        }
+        let line = request.line.context("line is required")?;
+        let column = request.column.context("column is required")?;
+
+        let content = fs::read_to_string(&path).context("Failed to read file")?;
+        let position = workspace_edit::position_at(&content, line, column)
+            .with_context(|| format!("Line {} is past the end of {}", line, request.file_path))?;
+
+        let manager = self.get_or_init_lsp_manager().await?;
+        manager
+            .rename_symbol(&path.to_string_lossy(), position, new_name)
+            .await?
+            .with_context(|| {
+                format!(
+                    "No renameable symbol at {}:{}:{}",
+                    request.file_path, line, column
+                )
+            })
+    }
+
+    /// Keep the changes of an unconfirmed preview for its confirmation
+    fn remember_preview(&self, key: String, changes: Vec<workspace_edit::FileChange>) {
+        let mut previewed = self.previewed.lock().unwrap_or_else(|e| e.into_inner());
+        previewed.retain(|(k, _)| *k != key);
+        previewed.push_back((key, changes));
+        while previewed.len() > MAX_PREVIEWED {
+            previewed.pop_front();
+        }
+    }
+
+    /// The previewed changes of a request, if it was previewed
+    fn take_preview(&self, key: &str) -> Option<Vec<workspace_edit::FileChange>> {
+        let mut previewed = self.previewed.lock().unwrap_or_else(|e| e.into_inner());
+        let index = previewed.iter().position(|(k, _)| k == key)?;
+        previewed.remove(index).map(|(_, changes)| changes)
+    }
+
+    fn build_result(
+        &self,
+        request: &RenameSymbolRequest,
+        changes: &[workspace_edit::FileChange],
+        executed: bool,
+    ) -> RenameSymbolResult {
+        let files: Vec<DiffStats> = changes.iter().map(|c| c.diff_stats.clone()).collect();
+        let preview = workspace_edit::preview(&files);
+        let occurrences: usize = files.iter().map(|f| f.removals).sum();
+        RenameSymbolResult {
+            file_path: request.file_path.clone(),
+            new_name: request.new_name.trim().to_string(),
+            response_summary: format!(
+                "{} line{} in {} file{}{}",
+                occurrences,
+                if occurrences == 1 { "" } else { "s" },
+                files.len(),
+                if files.len() == 1 { "" } else { "s" },
+                if executed { "" } else { " (requires confirmation)" }
+            ),
+            files,
+            preview,
+            executed,
+        }
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "file_path": {
+                            "type": "string",
+                            "description": "A file containing an occurrence of the symbol. Must be within current working directory."
+                        },
+                        "line": {
+                            "type": "integer",
+                            "description": "Line of the occurrence (1-based)."
+                        },
+                        "column": {
+                            "type": "integer",
+                            "description": "Column of any character of the symbol name on that line (1-based)."
+                        },
+                        "new_name": {
+                            "type": "string",
+                            "description": "The new name for the symbol."
+                        }
+                    },
+                    "required": ["file_path", "line", "column", "new_name"]
+                }
+            }
+        })
+    }
+}
+
+impl Default for RenameSymbolTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "rename_symbol".to_string(),
+            description: "Renames a symbol across the workspace using the language server.".to_string(),
+            lsp_manager: Arc::new(Mutex::new(None)),
+            previewed: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+}
+
+impl ToolSpec for RenameSymbolTool {
+    type Args = RenameSymbolRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Edit
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Edited
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(format!(
+            "{} → {}",
+            arg_summary::position(&args.file_path, args.line, args.column),
+            args.new_name
+        ))
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        let policy = PathPolicy::new()?;
+        let key = serde_json::to_string(&args)?;
+        let previewed = if confirmed { self.take_preview(&key) } else { None };
+        let changes = match previewed {
+            Some(changes) => changes,
+            None => {
+                let self_clone = self.clone();
+                let (request, policy_for_lsp) = (args.clone(), policy.clone());
+                let edit = tokio::task::block_in_place(|| {
+                    tokio::runtime::Handle::current()
+                        .block_on(async move { self_clone.request_rename(&policy_for_lsp, &request).await })
+                })?;
+                workspace_edit::prepare(&policy, &edit)?
+            }
+        };
+
+        // Applying refuses files changed since the preview
+        if confirmed {
+            workspace_edit::apply(&changes)?;
+        }
+        let result = self.build_result(&args, &changes, confirmed);
+        if !confirmed {
+            self.remember_preview(key, changes);
+        }
+
+        let response_summary = result.response_summary.clone();
+        let stdout = if confirmed {
+            result.preview.clone()
+        } else {
+            format!(
+                "Renaming to '{}' changes {} file(s) and requires confirmation:\n\n{}",
+                result.new_name,
+                result.files.len(),
+                result.preview
+            )
+        };
+        let data = serde_json::to_value(result)?;
+        let mut tr = ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary);
+        tr.requires_confirmation = !confirmed;
+        tr.executed = confirmed;
+        Ok(tr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+    use std::path::PathBuf;
+
+    fn edit_for(files: &[(PathBuf, u32, u32, u32)]) -> WorkspaceEdit {
+        let mut changes: std::collections::HashMap<String, Vec<serde_json::Value>> = Default::default();
+        for (path, line, start, end) in files {
+            changes
+                .entry(url::Url::from_file_path(path).unwrap().to_string())
+                .or_default()
+                .push(serde_json::json!({
+                    "range": { "start": { "line": line, "character": start }, "end": { "line": line, "character": end } },
+                    "newText": "total"
+                }));
+        }
+        serde_json::from_value(serde_json::json!({ "changes": changes })).unwrap()
+    }
+
+    #[test]
+    fn previews_then_applies_workspace_edit() {
+        let workspace = FakeWorkspace::new()
+            .file("lib.rs", "pub fn sum() {}\npub fn a() { sum() }\n")
+            .file("src/main.rs", "fn main() { sum(); }\n")
+            .build()
+            .unwrap();
+        let root = workspace.path();
+        let policy = PathPolicy::with_root(root.to_path_buf());
+        let tool = RenameSymbolTool::default();
+
+        let mut files = vec![
+            (root.join("lib.rs"), 0, 7, 10),
+            (root.join("lib.rs"), 1, 13, 16),
+            (root.join("src/main.rs"), 0, 12, 15),
+        ];
+        let edit = edit_for(&files);
+
+        // Edits outside the workspace are refused as a whole
+        files.push((PathBuf::from("/etc/hosts"), 0, 0, 1));
+        assert!(workspace_edit::prepare(&policy, &edit_for(&files)).is_err());
+
+        let changes = workspace_edit::prepare(&policy, &edit).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].diff_stats.file_path, "lib.rs");
+        assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "pub fn sum() {}\npub fn a() { sum() }\n");
+
+        workspace_edit::apply(&changes).unwrap();
+        assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "pub fn total() {}\npub fn a() { total() }\n");
+        assert_eq!(fs::read_to_string(root.join("src/main.rs")).unwrap(), "fn main() { total(); }\n");
+
+        let request = RenameSymbolRequest {
+            file_path: "lib.rs".to_string(),
+            line: Some(1),
+            column: Some(8),
+            new_name: "total".to_string(),
+        };
+        let result = tool.build_result(&request, &changes, true);
+        assert_eq!(result.response_summary, "3 lines in 2 files");
+        assert!(result.preview.contains("+++ b/src/main.rs"));
+
+        // A preview is kept for its confirmation and taken once
+        let key = serde_json::to_string(&request).unwrap();
+        tool.clone().remember_preview(key.clone(), changes);
+        let kept = tool.take_preview(&key).unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(tool.take_preview(&key).is_none());
+        // Applying what was previewed refuses files changed since
+        fs::write(root.join("lib.rs"), "pub fn sum() {}\n").unwrap();
+        fs::write(root.join("src/main.rs"), "fn main() { sum(); }\n").unwrap();
+        assert!(workspace_edit::apply(&kept).is_err());
+        fs::write(root.join("lib.rs"), "pub fn sum() {}\npub fn a() { sum() }\n").unwrap();
+        workspace_edit::apply(&kept).unwrap();
+        assert_eq!(fs::read_to_string(root.join("lib.rs")).unwrap(), "pub fn total() {}\npub fn a() { total() }\n");
+    }
+}
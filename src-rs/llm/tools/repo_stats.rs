@@ -0,0 +1,631 @@
+use crate::llm::config::AppConfig;
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::serde_util::deserialize_usize_opt_lax;
+use anyhow::Result;
+use ignore::WalkBuilder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+/// Bytes of a file inspected to tell text from binary
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+/// Files larger than this are counted by size but not read for lines
+const MAX_COUNTED_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Recent commits listed
+const RECENT_COMMITS: usize = 10;
+
+/// Commits scanned for the files changed most often
+const HOT_FILE_COMMITS: usize = 100;
+
+/// Manifests listed, shallowest first
+const MAX_MANIFESTS: usize = 30;
+
+/// Directories skipped even when no ignore file mentions them
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "__pycache__", "vendor", "dist", "build"];
+
+/// Dependency manifests by file name, with the ecosystem they belong to
+const MANIFESTS: &[(&str, &str)] = &[
+    ("Cargo.toml", "cargo"),
+    ("package.json", "npm"),
+    ("go.mod", "go"),
+    ("pyproject.toml", "python"),
+    ("requirements.txt", "pip"),
+    ("setup.py", "python"),
+    ("Pipfile", "pipenv"),
+    ("Gemfile", "bundler"),
+    ("pom.xml", "maven"),
+    ("build.gradle", "gradle"),
+    ("build.gradle.kts", "gradle"),
+    ("composer.json", "composer"),
+    ("Package.swift", "swift"),
+    ("mix.exs", "mix"),
+    ("pubspec.yaml", "pub"),
+    ("CMakeLists.txt", "cmake"),
+    ("deno.json", "deno"),
+];
+
+/// Repository statistics tool: languages, largest files, manifests and git history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepoStatsTool {
+    /// Tool name identifier
+    pub tool_name: String,
+    /// Description of what this tool does
+    pub description: String,
+    /// Largest files and most changed files listed when the request gives no `top`
+    pub top_files: usize,
+    /// Files scanned before the walk stops
+    pub max_files: usize,
+}
+
+/// Repo stats request parameters
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoStatsRequest {
+    /// Directory to summarize (defaults to the workspace root)
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Entries in the largest and most changed file lists
+    #[serde(default, deserialize_with = "deserialize_usize_opt_lax")]
+    pub top: Option<usize>,
+}
+
+/// Files and lines of one language
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageStat {
+    pub language: String,
+    pub files: usize,
+    pub lines: usize,
+    pub bytes: u64,
+}
+
+/// A file and its size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileStat {
+    /// Path relative to the summarized directory
+    pub path: String,
+    pub lines: usize,
+    pub bytes: u64,
+}
+
+/// A dependency manifest found in the tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestInfo {
+    /// Path relative to the summarized directory
+    pub path: String,
+    /// Package ecosystem, such as `cargo` or `npm`
+    pub ecosystem: String,
+}
+
+/// One commit of the recent history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommitInfo {
+    pub hash: String,
+    /// Commit date, YYYY-MM-DD
+    pub date: String,
+    pub author: String,
+    pub subject: String,
+}
+
+/// A file and how many recent commits changed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangedFile {
+    /// Path relative to the repository root
+    pub path: String,
+    pub commits: usize,
+}
+
+/// Git history of the summarized directory; absent outside a repository
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitActivity {
+    pub branch: Option<String>,
+    pub recent_commits: Vec<CommitInfo>,
+    /// Files changed most often in the last `HOT_FILE_COMMITS` commits
+    pub most_changed: Vec<ChangedFile>,
+}
+
+/// Result of summarizing a directory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepoStatsResult {
+    /// Directory summarized
+    pub root: String,
+    pub total_files: usize,
+    /// Lines in text files
+    pub total_lines: usize,
+    /// Files skipped as binary
+    pub binary_files: usize,
+    /// Whether the walk stopped at `max_files`
+    pub truncated: bool,
+    /// Languages by lines, most first
+    pub languages: Vec<LanguageStat>,
+    /// Text files by lines, most first
+    pub largest_files: Vec<FileStat>,
+    pub manifests: Vec<ManifestInfo>,
+    pub git: Option<GitActivity>,
+    /// Summary of the result
+    pub response_summary: String,
+}
+
+impl RepoStatsTool {
+    /// Create a new RepoStatsTool by loading configuration from config.toml
+    ///
+    /// If config.toml is not found or fails to parse, falls back to hardcoded defaults.
+    pub fn new() -> Self {
+        match AppConfig::load() {
+            Ok(config) => Self::from_config(&config),
+            Err(e) => {
+                log::warn!(
+                    "Failed to load config.toml: {}, using hardcoded defaults",
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+
+    /// Create a new RepoStatsTool from a specific AppConfig
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            tool_name: config.tool_repo_stats.tool_name.clone(),
+            description: config.tool_repo_stats.description.clone(),
+            top_files: config.tool_repo_stats.top_files,
+            max_files: config.tool_repo_stats.max_files,
+        }
+    }
+
+    /// Summarize the requested directory
+    pub fn run_stats(&self, request: &RepoStatsRequest) -> Result<RepoStatsResult> {
+        let policy = PathPolicy::new()?;
+        let root = policy.resolve(request.path.as_deref().unwrap_or("."))?;
+        if !root.is_dir() {
+            anyhow::bail!("Not a directory: {}", root.display());
+        }
+        let top = request.top.unwrap_or(self.top_files).max(1);
+        Ok(self.collect(&root, top))
+    }
+
+    /// Walk `root`, respecting ignore files, and gather its stats
+    fn collect(&self, root: &Path, top: usize) -> RepoStatsResult {
+        let mut languages: HashMap<String, LanguageStat> = HashMap::new();
+        let mut files: Vec<FileStat> = Vec::new();
+        let mut manifests: Vec<(usize, ManifestInfo)> = Vec::new();
+        let (mut total_files, mut binary_files, mut truncated) = (0, 0, false);
+
+        let walker = WalkBuilder::new(root)
+            .follow_links(false)
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || !entry.file_type().is_some_and(|t| t.is_dir())
+                    || !SKIPPED_DIRS.iter().any(|d| entry.file_name() == *d)
+            })
+            .build();
+        for entry in walker.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            if total_files == self.max_files {
+                truncated = true;
+                break;
+            }
+            total_files += 1;
+
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string();
+            let file_name = entry.file_name().to_string_lossy();
+            if let Some((_, ecosystem)) = MANIFESTS.iter().find(|(name, _)| file_name == *name) {
+                manifests.push((
+                    entry.depth(),
+                    ManifestInfo {
+                        path: relative.clone(),
+                        ecosystem: ecosystem.to_string(),
+                    },
+                ));
+            }
+
+            let bytes = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            let Some(lines) = count_lines(path, bytes) else {
+                binary_files += 1;
+                continue;
+            };
+            let stat = languages.entry(language_of(path)).or_insert_with_key(|language| LanguageStat {
+                language: language.clone(),
+                files: 0,
+                lines: 0,
+                bytes: 0,
+            });
+            stat.files += 1;
+            stat.lines += lines;
+            stat.bytes += bytes;
+            files.push(FileStat {
+                path: relative,
+                lines,
+                bytes,
+            });
+        }
+
+        let mut languages: Vec<LanguageStat> = languages.into_values().collect();
+        languages.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.language.cmp(&b.language)));
+        let total_lines = languages.iter().map(|l| l.lines).sum();
+        files.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.path.cmp(&b.path)));
+        files.truncate(top);
+        manifests.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.path.cmp(&b.1.path)));
+        let manifests: Vec<ManifestInfo> = manifests.into_iter().take(MAX_MANIFESTS).map(|(_, m)| m).collect();
+
+        let response_summary = format!(
+            "{} files, {} lines{}",
+            total_files,
+            total_lines,
+            languages
+                .first()
+                .map(|l| format!(", mostly {}", l.language))
+                .unwrap_or_default()
+        );
+        RepoStatsResult {
+            root: root.to_string_lossy().to_string(),
+            total_files,
+            total_lines,
+            binary_files,
+            truncated,
+            languages,
+            largest_files: files,
+            manifests,
+            git: git_activity(root, top),
+            response_summary,
+        }
+    }
+
+    /// Get tool definition as JSON for LLM
+    fn to_tool_definition_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": self.description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "path": {
+                            "type": "string",
+                            "description": "The directory to summarize. Defaults to the workspace root."
+                        },
+                        "top": {
+                            "type": "integer",
+                            "description": format!("How many of the largest and most changed files to list. Defaults to {}.", self.top_files)
+                        }
+                    },
+                    "required": []
+                }
+            }
+        })
+    }
+}
+
+impl Default for RepoStatsTool {
+    fn default() -> Self {
+        Self {
+            tool_name: "repo_stats".to_string(),
+            description: "Summarizes a repository: languages, largest files, dependency manifests and recent git activity."
+                .to_string(),
+            top_files: 10,
+            max_files: 20000,
+        }
+    }
+}
+
+/// Lines of a text file, None for a binary one
+fn count_lines(path: &Path, bytes: u64) -> Option<usize> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let n = file.read(&mut buf).ok()?;
+    if buf[..n.min(BINARY_SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    if bytes > MAX_COUNTED_BYTES {
+        // Too big to be worth reading; such files are rarely source
+        return Some(0);
+    }
+    let (mut lines, mut n, mut last) = (0, n, b'\n');
+    while n > 0 {
+        lines += buf[..n].iter().filter(|b| **b == b'\n').count();
+        last = buf[n - 1];
+        n = file.read(&mut buf).ok()?;
+    }
+    if last != b'\n' {
+        lines += 1;
+    }
+    Some(lines)
+}
+
+/// Language named by the file's extension, or the extension itself
+fn language_of(path: &Path) -> String {
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    match name.as_ref() {
+        "Dockerfile" => return "Dockerfile".to_string(),
+        "Makefile" | "makefile" | "GNUmakefile" => return "Makefile".to_string(),
+        _ => {}
+    }
+    let Some(ext) = path.extension().map(|e| e.to_string_lossy().to_lowercase()) else {
+        return "(no extension)".to_string();
+    };
+    let language = match ext.as_str() {
+        "rs" => "Rust",
+        "ts" | "tsx" | "mts" | "cts" => "TypeScript",
+        "js" | "jsx" | "mjs" | "cjs" => "JavaScript",
+        "py" | "pyi" => "Python",
+        "go" => "Go",
+        "java" => "Java",
+        "kt" | "kts" => "Kotlin",
+        "scala" => "Scala",
+        "c" | "h" => "C",
+        "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "C++",
+        "cs" => "C#",
+        "m" | "mm" => "Objective-C",
+        "swift" => "Swift",
+        "rb" => "Ruby",
+        "php" => "PHP",
+        "ex" | "exs" => "Elixir",
+        "erl" => "Erlang",
+        "hs" => "Haskell",
+        "lua" => "Lua",
+        "dart" => "Dart",
+        "zig" => "Zig",
+        "sh" | "bash" | "zsh" => "Shell",
+        "ps1" => "PowerShell",
+        "sql" => "SQL",
+        "html" | "htm" => "HTML",
+        "css" | "scss" | "sass" | "less" => "CSS",
+        "vue" => "Vue",
+        "svelte" => "Svelte",
+        "md" | "mdx" => "Markdown",
+        "json" | "jsonc" => "JSON",
+        "toml" => "TOML",
+        "yaml" | "yml" => "YAML",
+        "xml" => "XML",
+        "proto" => "Protobuf",
+        _ => return format!(".{}", ext),
+    };
+    language.to_string()
+}
+
+/// Branch, recent commits and most changed files of the repository `root` is in
+fn git_activity(root: &Path, top: usize) -> Option<GitActivity> {
+    let git = |args: &[&str]| -> Option<String> {
+        let output = Command::new("git").args(args).current_dir(root).output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).to_string())
+    };
+
+    git(&["rev-parse", "--is-inside-work-tree"])?;
+    let branch = git(&["rev-parse", "--abbrev-ref", "HEAD"])
+        .map(|b| b.trim().to_string())
+        .filter(|b| !b.is_empty() && b != "HEAD");
+    let log = git(&[
+        "log",
+        &format!("-n{}", RECENT_COMMITS),
+        "--date=short",
+        "--pretty=format:%h%x09%ad%x09%an%x09%s",
+        "--",
+        ".",
+    ])
+    .unwrap_or_default();
+    let changes = git(&[
+        "log",
+        &format!("-n{}", HOT_FILE_COMMITS),
+        "--name-only",
+        "--pretty=format:",
+        "--",
+        ".",
+    ])
+    .unwrap_or_default();
+
+    Some(GitActivity {
+        branch,
+        recent_commits: parse_commits(&log),
+        most_changed: most_changed(&changes, top),
+    })
+}
+
+fn parse_commits(log: &str) -> Vec<CommitInfo> {
+    log.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\t');
+            Some(CommitInfo {
+                hash: parts.next()?.to_string(),
+                date: parts.next()?.to_string(),
+                author: parts.next()?.to_string(),
+                subject: parts.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The `top` files named most often in `git log --name-only` output
+fn most_changed(changes: &str, top: usize) -> Vec<ChangedFile> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for path in changes.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        *counts.entry(path).or_insert(0) += 1;
+    }
+    let mut changed: Vec<ChangedFile> = counts
+        .into_iter()
+        .map(|(path, commits)| ChangedFile {
+            path: path.to_string(),
+            commits,
+        })
+        .collect();
+    changed.sort_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.path.cmp(&b.path)));
+    changed.truncate(top);
+    changed
+}
+
+/// Plain text rendering of the stats for the model
+fn render(result: &RepoStatsResult) -> String {
+    let mut out = format!(
+        "{}: {} files, {} lines of text{}{}\n",
+        result.root,
+        result.total_files,
+        result.total_lines,
+        if result.binary_files > 0 {
+            format!(", {} binary files", result.binary_files)
+        } else {
+            String::new()
+        },
+        if result.truncated { " (scan stopped early; pass a subdirectory for full counts)" } else { "" }
+    );
+
+    out.push_str("\nLanguages:\n");
+    for l in &result.languages {
+        let share = if result.total_lines == 0 {
+            0.0
+        } else {
+            l.lines as f64 * 100.0 / result.total_lines as f64
+        };
+        out.push_str(&format!("  {:<16} {:>6} files {:>9} lines {:>5.1}%\n", l.language, l.files, l.lines, share));
+    }
+
+    if !result.largest_files.is_empty() {
+        out.push_str("\nLargest files:\n");
+        for f in &result.largest_files {
+            out.push_str(&format!("  {:>7} lines  {}\n", f.lines, f.path));
+        }
+    }
+
+    out.push_str("\nDependency manifests:\n");
+    if result.manifests.is_empty() {
+        out.push_str("  (none found)\n");
+    }
+    for m in &result.manifests {
+        out.push_str(&format!("  {} ({})\n", m.path, m.ecosystem));
+    }
+
+    match &result.git {
+        None => out.push_str("\nGit: not a repository\n"),
+        Some(git) => {
+            out.push_str(&format!(
+                "\nGit{}:\n",
+                git.branch.as_deref().map(|b| format!(" (branch {})", b)).unwrap_or_default()
+            ));
+            if git.recent_commits.is_empty() {
+                out.push_str("  (no commits)\n");
+            }
+            for c in &git.recent_commits {
+                out.push_str(&format!("  {} {} {}: {}\n", c.hash, c.date, c.author, c.subject));
+            }
+            if !git.most_changed.is_empty() {
+                out.push_str(&format!("\nMost changed in the last {} commits:\n", HOT_FILE_COMMITS));
+                for f in &git.most_changed {
+                    out.push_str(&format!("  {:>4}  {}\n", f.commits, f.path));
+                }
+            }
+        }
+    }
+    out
+}
+
+impl ToolSpec for RepoStatsTool {
+    type Args = RepoStatsRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Search
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Explored
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        self.to_tool_definition_json()
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(args.path.clone().unwrap_or_else(|| ".".to_string()))
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let result = self.run_stats(&args)?;
+        let response_summary = result.response_summary.clone();
+        let stdout = render(&result);
+        let data = serde_json::to_value(result)?;
+        Ok(ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            data,
+        )
+        .with_summary(response_summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn counts_languages_files_and_manifests() {
+        let workspace = FakeWorkspace::new()
+            .file("Cargo.toml", "[package]\nname = \"demo\"\n")
+            .file("src/main.rs", "fn main() {\n    run();\n}\n")
+            .file("src/lib.rs", "pub fn run() {}")
+            .file("web/package.json", "{}\n")
+            .file("web/app.ts", "export {}\n")
+            .file("web/node_modules/dep/index.js", "x\n".repeat(500))
+            .file("logo.png", [0x89u8, b'P', b'N', b'G', 0, 0, 1])
+            .build()
+            .unwrap();
+        let root = workspace.path();
+
+        let result = RepoStatsTool::default().collect(root, 2);
+
+        assert_eq!((result.total_files, result.binary_files), (6, 1));
+        assert_eq!(result.total_lines, 8);
+        assert_eq!(
+            result.languages[0],
+            LanguageStat {
+                language: "Rust".to_string(),
+                files: 2,
+                lines: 4,
+                bytes: 40,
+            }
+        );
+        assert!(result.languages.iter().all(|l| l.language != "JavaScript"));
+        let largest: Vec<&str> = result.largest_files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(largest, ["src/main.rs", "Cargo.toml"]);
+        let manifests: Vec<(&str, &str)> = result
+            .manifests
+            .iter()
+            .map(|m| (m.path.as_str(), m.ecosystem.as_str()))
+            .collect();
+        assert_eq!(manifests, [("Cargo.toml", "cargo"), ("web/package.json", "npm")]);
+
+        let text = render(&result);
+        assert!(text.contains("Rust"));
+        assert!(text.contains("web/package.json (npm)"));
+    }
+
+    #[test]
+    fn most_changed_counts_files_across_commits() {
+        let changes = "\nsrc/a.rs\nsrc/b.rs\n\nsrc/a.rs\n\nREADME.md\nsrc/a.rs\n";
+        let changed = most_changed(changes, 2);
+        assert_eq!(changed.len(), 2);
+        assert_eq!((changed[0].path.as_str(), changed[0].commits), ("src/a.rs", 3));
+        assert_eq!(changed[1].path, "README.md");
+
+        let commits = parse_commits("abc1234\t2026-01-02\tAda\tFix: tabs\tkept\n");
+        assert_eq!(commits[0].subject, "Fix: tabs\tkept");
+    }
+}
@@ -0,0 +1,165 @@
+use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Prefix of skill tool names, keeping them apart from builtin and MCP tools
+pub const SKILL_TOOL_PREFIX: &str = "skill_";
+/// Longest tool name providers accept
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+/// A skill the model can invoke itself. The call returns the skill's
+/// instructions, with `$ARGUMENTS` replaced by the call's arguments, so they
+/// enter the conversation when the model decides the task needs them rather
+/// than sitting in every prompt.
+#[derive(Clone)]
+pub struct SkillTool {
+    pub tool_name: String,
+    pub skill_name: String,
+    pub description: String,
+    pub instructions: String,
+    /// What the skill expects as arguments, e.g. "[issue-number]"
+    pub argument_hint: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkillRequest {
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+impl SkillTool {
+    pub fn new(skill_name: &str, description: &str, instructions: &str, argument_hint: Option<&str>) -> Self {
+        Self {
+            tool_name: tool_name(skill_name),
+            skill_name: skill_name.to_string(),
+            description: description.to_string(),
+            instructions: instructions.to_string(),
+            argument_hint: argument_hint.map(str::to_string),
+        }
+    }
+
+    /// The instructions for a call with `arguments`; when they do not place
+    /// `$ARGUMENTS` themselves the arguments are appended
+    pub fn expand(&self, arguments: &str) -> String {
+        let arguments = arguments.trim();
+        if self.instructions.contains("$ARGUMENTS") {
+            self.instructions.replace("$ARGUMENTS", arguments)
+        } else if arguments.is_empty() {
+            self.instructions.clone()
+        } else {
+            format!("{}\n\nARGUMENTS: {}", self.instructions, arguments)
+        }
+    }
+}
+
+/// `skill_` and the skill name, in the characters tool names allow
+pub fn tool_name(skill_name: &str) -> String {
+    let name: String = skill_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    format!("{}{}", SKILL_TOOL_PREFIX, name).chars().take(MAX_TOOL_NAME_LEN).collect()
+}
+
+impl ToolSpec for SkillTool {
+    type Args = SkillRequest;
+
+    fn name(&self) -> &str {
+        &self.tool_name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn kind(&self) -> ToolKind {
+        ToolKind::Read
+    }
+
+    fn operation(&self) -> ToolOperation {
+        ToolOperation::Explored
+    }
+
+    fn to_tool_definition(&self) -> serde_json::Value {
+        let description = match self.description.trim() {
+            "" => format!("Load the instructions of the skill '{}' before doing its task", self.skill_name),
+            d => format!(
+                "{} (skill '{}': returns its instructions; call it before doing the task)",
+                d, self.skill_name
+            ),
+        };
+        let arguments = match &self.argument_hint {
+            Some(hint) => format!("Arguments for the skill: {}", hint),
+            None => "Arguments for the skill, if the task has any".to_string(),
+        };
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.tool_name,
+                "description": description,
+                "parameters": {
+                    "type": "object",
+                    "properties": {
+                        "arguments": { "type": "string", "description": arguments }
+                    }
+                }
+            }
+        })
+    }
+
+    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
+        let arguments = args.arguments.unwrap_or_default();
+        let stdout = format!(
+            "Instructions of the skill '{}'; follow them for this task:\n\n{}",
+            self.skill_name,
+            self.expand(&arguments)
+        );
+        Ok(ToolResult::ok(
+            self.tool_name.clone(),
+            self.kind(),
+            self.operation(),
+            stdout,
+            json!({ "skill": self.skill_name, "arguments": arguments.trim() }),
+        )
+        .with_summary(format!("loaded skill {}", self.skill_name)))
+    }
+
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let arguments = args.arguments.as_deref().unwrap_or_default().trim();
+        Some(if arguments.is_empty() {
+            self.skill_name.clone()
+        } else {
+            format!("{} {}", self.skill_name, arguments)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ToolHarness;
+    use crate::llm::tools::ToolAdapter;
+
+    #[test]
+    fn skill_calls_return_the_expanded_instructions() {
+        let fix = SkillTool::new("Fix Issue", "Fix a GitHub issue", "Fix issue #$ARGUMENTS, then run the tests.", None);
+        assert_eq!(fix.tool_name, "skill_fix_issue");
+        assert_eq!(tool_name(&"x".repeat(100)).len(), MAX_TOOL_NAME_LEN);
+
+        let harness = ToolHarness::new(Box::new(ToolAdapter(fix)));
+        harness.assert_contract();
+        let result = harness.call(&json!({ "arguments": " 42 " }));
+        assert!(result.success, "{}", result.stderr);
+        assert!(result.stdout.ends_with("Fix issue #42, then run the tests."));
+
+        let style = SkillTool::new("style", "", "Use short commit subjects.", Some("[scope]"));
+        assert_eq!(style.expand(""), "Use short commit subjects.");
+        assert_eq!(style.expand("api"), "Use short commit subjects.\n\nARGUMENTS: api");
+        let definition = style.to_tool_definition();
+        assert_eq!(
+            definition.pointer("/function/parameters/properties/arguments/description"),
+            Some(&json!("Arguments for the skill: [scope]"))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use super::list_available_tools;
+use crate::testing::ToolHarness;
+use std::collections::HashSet;
+
+#[test]
+fn tool_names_are_unique() {
+    let tools = list_available_tools();
+    let mut seen = HashSet::new();
+    for tool in &tools {
+        assert!(seen.insert(tool.name().to_string()), "duplicate tool name: {}", tool.name());
+    }
+}
+
+#[test]
+fn tool_definitions_match_openai_function_shape() {
+    for harness in ToolHarness::builtins() {
+        harness.assert_definition();
+    }
+}
+
+#[test]
+fn builtin_schemas_have_a_strict_form() {
+    for harness in ToolHarness::builtins() {
+        assert!(harness.tool().supports_strict_schema(), "tool {}", harness.name());
+        crate::testing::assert_schema_round_trips(&harness.tool().to_tool_definition());
+    }
+}
+
+#[test]
+fn null_arguments_count_as_absent() {
+    let delete = ToolHarness::builtin("delete");
+    assert_eq!(
+        delete.tool().summarize_args(r#"{"path":"build","recursive":null}"#).as_deref(),
+        Some("build")
+    );
+}
+
+#[test]
+fn invalid_arguments_yield_error_tool_result() {
+    for harness in ToolHarness::builtins() {
+        harness.assert_rejects_invalid_arguments();
+    }
+}
+
+#[test]
+fn argument_summaries_describe_the_call() {
+    let summary = |name: &str, args: &str| ToolHarness::builtin(name).tool().summarize_args(args);
+
+    assert_eq!(
+        summary("bash", r#"{"command":"cargo test\ncargo clippy","confirmed":true}"#).as_deref(),
+        Some("cargo test …(+1 lines)")
+    );
+    assert_eq!(
+        summary("edit", r#"{"file_path":"src/a.rs","old_string":"a\nb","new_string":"c\nd\ne"}"#).as_deref(),
+        Some("src/a.rs (−2 +3 lines)")
+    );
+    assert_eq!(
+        summary("fetch", r#"{"url":"https://example.com/docs","format":"markdown"}"#).as_deref(),
+        Some("https://example.com/docs")
+    );
+    assert_eq!(summary("bash", "not json"), None);
+}
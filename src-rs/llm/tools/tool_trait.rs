@@ -1,7 +1,12 @@
-use anyhow::{Context, Result};
+use crate::llm::utils::file_lock::FileConflict;
+use crate::llm::utils::file_tracker::StaleRead;
+use crate::llm::utils::tool_access::ToolScope;
+use anyhow::{anyhow, Context, Result};
+use futures::future::BoxFuture;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use super::lenient_args;
 
 /// Tool side-effect / capability classification (aligned with Gemini kinds)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -18,6 +23,24 @@ pub enum ToolKind {
     Other,
 }
 
+impl ToolKind {
+    /// Lowercase name, as used in config keys and metrics
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ToolKind::Read => "read",
+            ToolKind::Edit => "edit",
+            ToolKind::Delete => "delete",
+            ToolKind::Move => "move",
+            ToolKind::Search => "search",
+            ToolKind::Execute => "execute",
+            ToolKind::Think => "think",
+            ToolKind::Fetch => "fetch",
+            ToolKind::Todo => "todo",
+            ToolKind::Other => "other",
+        }
+    }
+}
+
 /// High level operation category (for display/policy)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ToolOperation {
@@ -49,6 +72,7 @@ pub struct ToolResult {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum ToolOperationEnd {
     BashEnd,
     ExploredEnd,
@@ -128,17 +152,36 @@ impl ToolResult {
         }
     }
 
+    /// A call held back for review in propose mode: not run, and not failed
+    pub fn proposed(
+        tool_name: impl Into<String>,
+        kind: ToolKind,
+        operation: ToolOperation,
+        proposal_id: &str,
+        stdout: impl Into<String>,
+        data: Value,
+    ) -> Self {
+        Self {
+            executed: false,
+            response_summary: Some(format!("proposed as {}", proposal_id)),
+            ..Self::ok(tool_name, kind, operation, stdout, data)
+        }
+    }
+
     pub fn with_summary(mut self, summary: impl Into<String>) -> Self {
         self.response_summary = Some(summary.into());
         self
     }
 }
 
-pub fn parse_confirmed_and_args<T: DeserializeOwned>(arguments: &str) -> Result<(T, bool)> {
-    let mut v: Value = serde_json::from_str(arguments).context("Failed to parse tool arguments")?;
+/// The arguments and the `confirmed` flag, read leniently against the tool's
+/// `parameters` schema (see `lenient_args`)
+pub fn parse_confirmed_and_args<T: DeserializeOwned>(arguments: &str, parameters: &Value) -> Result<(T, bool)> {
+    let mut v = lenient_args::parse_value(arguments)?;
+    lenient_args::conform(&mut v, parameters);
     let confirmed = v
         .get("confirmed")
-        .and_then(|x| x.as_bool())
+        .and_then(lenient_args::as_bool)
         .unwrap_or(false);
     if let Value::Object(map) = &mut v {
         map.remove("confirmed");
@@ -218,15 +261,52 @@ pub trait Tool: Send + Sync {
     ///
     /// # Arguments
     /// * `arguments` - JSON string containing the tool arguments.
-    ///                 If the tool supports confirmation, the JSON may contain
-    ///                 a `confirmed` boolean field.
+    ///   If the tool supports confirmation, the JSON may contain
+    ///   a `confirmed` boolean field.
     ///
     /// # Returns
     /// * Result with the execution result as a JSON string (schema: ToolOutput)
     fn execute(&self, arguments: &str) -> Result<String>;
 
+    /// Execute without blocking the async runtime. The default runs `execute`
+    /// on the blocking pool in the caller's `ToolScope`; async-native tools
+    /// override it and adapt `execute` with `block_on` instead.
+    fn execute_async(&self, arguments: &str) -> BoxFuture<'static, Result<String>> {
+        execute_blocking(self.clone_box(), arguments.to_string())
+    }
+
     /// Create a clone of the tool (boxed)
     fn clone_box(&self) -> Box<dyn Tool>;
+
+    /// Name to show for the tool in events and prompts
+    fn display_name(&self) -> String {
+        self.name().to_string()
+    }
+
+    /// One line saying what a call with `arguments` would do, for ToolStart and
+    /// confirmation events; None shows the raw arguments instead
+    fn summarize_args(&self, _arguments: &str) -> Option<String> {
+        None
+    }
+
+    /// Whether a call with `arguments` needs confirmation in every approval
+    /// mode, as the builtin calls in `approval_policy::is_destructive_call` do
+    fn is_destructive(&self, _arguments: &str) -> bool {
+        false
+    }
+
+    /// What confirmations of a call with `arguments` are scoped by: "Allow for
+    /// session" covers later calls of the tool with the same key. None leaves
+    /// it to `key_path_from_args`, which knows the builtin tools
+    fn key_path(&self, _arguments: &str) -> Option<String> {
+        None
+    }
+
+    /// Whether the definition may be sent in OpenAI's strict format, which
+    /// needs the tool to take null arguments as absent ones
+    fn supports_strict_schema(&self) -> bool {
+        false
+    }
 }
 
 impl Clone for Box<dyn Tool> {
@@ -244,6 +324,29 @@ pub trait ToolSpec: Send + Sync + Clone + 'static {
     fn operation(&self) -> ToolOperation;
     fn to_tool_definition(&self) -> Value;
     fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult>;
+
+    /// Whether `run_async` is the tool's own implementation, so that calls
+    /// from async code use it rather than `run` on the blocking pool
+    fn is_async(&self) -> bool {
+        false
+    }
+
+    /// See `Tool::execute_async`; async-native tools implement `run` as
+    /// `block_on(self.run_async(..))`
+    fn run_async(&self, args: Self::Args, confirmed: bool) -> BoxFuture<'static, Result<ToolResult>> {
+        let result = self.run(args, confirmed);
+        Box::pin(async move { result })
+    }
+
+    /// See `Tool::summarize_args`; helpers are in `arg_summary`
+    fn summarize_args(&self, _args: &Self::Args) -> Option<String> {
+        None
+    }
+
+    /// See `Tool::key_path`
+    fn key_path(&self, _args: &Self::Args) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -271,29 +374,83 @@ impl<T: ToolSpec> Tool for ToolAdapter<T> {
     }
 
     fn execute(&self, arguments: &str) -> Result<String> {
-        let (args, confirmed) = match parse_confirmed_and_args::<T::Args>(arguments) {
-            Ok(x) => x,
-            Err(e) => {
-                let tr = ToolResult::err(
-                    self.name(),
-                    self.kind(),
-                    self.operation(),
-                    e.to_string(),
-                    serde_json::json!({ "arguments": arguments }),
-                );
-                return serde_json::to_string(&tr).context("Failed to serialize ToolResult");
+        match self.parse(arguments) {
+            Ok((args, confirmed)) => self.finish(self.0.run(args, confirmed)),
+            Err(invalid) => invalid,
+        }
+    }
+
+    fn execute_async(&self, arguments: &str) -> BoxFuture<'static, Result<String>> {
+        if !self.0.is_async() {
+            return execute_blocking(self.clone_box(), arguments.to_string());
+        }
+        match self.parse(arguments) {
+            Ok((args, confirmed)) => {
+                let (tool, run) = (self.clone(), self.0.run_async(args, confirmed));
+                Box::pin(async move { tool.finish(run.await) })
             }
-        };
+            Err(invalid) => Box::pin(async move { invalid }),
+        }
+    }
 
-        let mut tr = match self.0.run(args, confirmed) {
-            Ok(x) => x,
-            Err(e) => ToolResult::err(
+    fn clone_box(&self) -> Box<dyn Tool> {
+        Box::new(self.clone())
+    }
+
+    fn summarize_args(&self, arguments: &str) -> Option<String> {
+        let (args, _) = parse_confirmed_and_args::<T::Args>(arguments, &self.parameters()).ok()?;
+        self.0.summarize_args(&args).map(|s| super::arg_summary::clip(&s))
+    }
+
+    fn key_path(&self, arguments: &str) -> Option<String> {
+        let (args, _) = parse_confirmed_and_args::<T::Args>(arguments, &self.parameters()).ok()?;
+        self.0.key_path(&args)
+    }
+
+    fn supports_strict_schema(&self) -> bool {
+        true
+    }
+}
+
+impl<T: ToolSpec> ToolAdapter<T> {
+    /// The parameter schema of the tool's definition
+    fn parameters(&self) -> Value {
+        self.0
+            .to_tool_definition()
+            .pointer("/function/parameters")
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The arguments, or the serialized error result for invalid ones
+    fn parse(&self, arguments: &str) -> std::result::Result<(T::Args, bool), Result<String>> {
+        parse_confirmed_and_args::<T::Args>(arguments, &self.parameters()).map_err(|e| {
+            let tr = ToolResult::err(
                 self.name(),
                 self.kind(),
                 self.operation(),
                 e.to_string(),
-                serde_json::json!({}),
-            ),
+                serde_json::json!({ "arguments": arguments }),
+            );
+            serde_json::to_string(&tr).context("Failed to serialize ToolResult")
+        })
+    }
+
+    /// Serialize the result of `run`, reporting errors as failed results
+    fn finish(&self, result: Result<ToolResult>) -> Result<String> {
+        let mut tr = match result {
+            Ok(x) => x,
+            Err(e) => {
+                // Structured failures are reported in `data` so callers need not parse text
+                let data = if let Some(conflict) = e.downcast_ref::<FileConflict>() {
+                    serde_json::json!({ "conflict": conflict })
+                } else if let Some(stale) = e.downcast_ref::<StaleRead>() {
+                    serde_json::json!({ "stale_read": stale })
+                } else {
+                    serde_json::json!({})
+                };
+                ToolResult::err(self.name(), self.kind(), self.operation(), e.to_string(), data)
+            }
         };
 
         tr.version = TOOL_RESULT_VERSION;
@@ -306,8 +463,42 @@ impl<T: ToolSpec> Tool for ToolAdapter<T> {
 
         serde_json::to_string(&tr).context("Failed to serialize ToolResult")
     }
+}
 
-    fn clone_box(&self) -> Box<dyn Tool> {
-        Box::new(self.clone())
+/// Run a sync tool on the blocking pool, in the scope of the task awaiting it
+fn execute_blocking(tool: Box<dyn Tool>, arguments: String) -> BoxFuture<'static, Result<String>> {
+    Box::pin(async move {
+        let scope = ToolScope::current();
+        let name = tool.name().to_string();
+        tokio::task::spawn_blocking(move || scope.enter(|| tool.execute(&arguments)))
+            .await
+            .map_err(|e| anyhow!("Tool '{}' panicked: {}", name, e))?
+    })
+}
+
+/// Run an async tool's future to completion from sync code, in the calling
+/// thread's `ToolScope`. On a multi-threaded runtime the worker is handed
+/// over while it blocks; without one (or on a current-thread runtime, which
+/// must not be blocked) the future runs on a runtime of its own.
+pub fn block_on<F>(future: F) -> Result<F::Output>
+where
+    F: std::future::Future + Send,
+    F::Output: Send,
+{
+    use tokio::runtime::{Builder, Handle, RuntimeFlavor};
+
+    let future = ToolScope::current().run(future);
+    if let Ok(handle) = Handle::try_current() {
+        if handle.runtime_flavor() == RuntimeFlavor::MultiThread {
+            return Ok(tokio::task::block_in_place(|| handle.block_on(future)));
+        }
     }
+    std::thread::scope(|s| {
+        s.spawn(|| -> Result<F::Output> {
+            let runtime = Builder::new_current_thread().enable_all().build()?;
+            Ok(runtime.block_on(future))
+        })
+        .join()
+        .map_err(|_| anyhow!("Tool thread panicked"))?
+    })
 }
@@ -1,11 +1,16 @@
+use crate::llm::backend::{self, ExecBackend};
 use crate::llm::config::AppConfig;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::file_cache;
 use crate::llm::utils::file_tracker::FILE_READ_TRACKER;
+use crate::llm::utils::overlay;
 use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::sensitive_files::{SensitiveFiles, REDACTED};
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader};
+use std::fs;
 use std::path::Path;
 
 /// View tool for reading file contents
@@ -199,18 +204,22 @@ impl ViewTool {
     ///
     /// # Arguments
     /// * `request` - View request parameters
+    /// * `confirmed` - Whether the user allowed reading a sensitive file
     ///
     /// # Returns
     /// * `Result<ViewResult>` - The result containing file content in OpenCode format
-    fn run_view(&self, request: &ViewRequest) -> Result<ViewResult> {
+    fn run_view(&self, request: &ViewRequest, confirmed: bool) -> Result<ViewResult> {
         // Convert to absolute path
         let path_policy = PathPolicy::new()?;
         let absolute_path = path_policy.resolve(&request.file_path)?;
         let absolute_path_str = absolute_path.to_string_lossy().to_string();
 
-        // Try to validate in workspace, but if it fails (e.g., for test directories),
-        // just use absolute path
-        let path_buf = absolute_path;
+        if let Some(target) = backend::current() {
+            return self.run_view_remote(target.as_ref(), request, &absolute_path, confirmed);
+        }
+
+        // In overlay mode the session's copy is read, while trackers keep the workspace path
+        let path_buf = overlay::read_path(&absolute_path);
         let path = path_buf.as_path();
 
         // Check if file exists
@@ -229,6 +238,10 @@ impl ViewTool {
             anyhow::bail!(error_msg);
         }
 
+        if !confirmed && SensitiveFiles::current().is_sensitive(&absolute_path) {
+            return Ok(Self::sensitive_result(request, absolute_path_str));
+        }
+
         // Check if it's an image file
         if Self::is_image_file(path) {
             return Ok(ViewResult {
@@ -261,19 +274,19 @@ impl ViewTool {
             );
         }
 
-        // Record file read for read-before-write validation
-        {
-            let mut tracker = FILE_READ_TRACKER.lock().unwrap();
-            tracker.record_read(&absolute_path_str);
-        }
-
         // Stream read file with offset and limit
         let offset = request.offset.unwrap_or(0);
         let limit = request.limit.unwrap_or(2000);
 
-        let file = File::open(path).context("Failed to open file")?;
-        let reader = BufReader::new(file);
-        let mut lines_iter = reader.lines();
+        let content = file_cache::read_to_string(path).context("Failed to read file")?;
+
+        // Record file read for read-before-write validation
+        {
+            let mut tracker = FILE_READ_TRACKER.lock().unwrap();
+            tracker.record_read(&absolute_path_str, content.as_bytes());
+        }
+        file_activity::record_viewed(&absolute_path);
+        let mut lines_iter = content.lines();
 
         // Skip offset lines
         let mut current_line = 0;
@@ -286,14 +299,12 @@ impl ViewTool {
 
         // Check if offset is beyond file
         if current_line < offset {
-            // Need to read full content for content_original
-            let content = fs::read_to_string(path).context("Failed to read file")?;
             return Ok(ViewResult {
                 content: String::new(),
                 metadata: ViewMetadata {
                     filepath: absolute_path_str.clone(),
                     preview: "(empty or offset beyond file)".to_string(),
-                    content_original: content,
+                    content_original: content.to_string(),
                 },
                 response_summary: "0 lines".to_string(),
             });
@@ -305,16 +316,15 @@ impl ViewTool {
         let mut first_line_for_preview = String::new();
         let mut has_more = false;
 
-        for line_result in lines_iter {
+        for line in lines_iter {
             if lines_read >= limit {
                 has_more = true;
                 break;
             }
 
-            let line = line_result.context("Failed to read line")?;
             let line_num = offset + lines_read + 1;
 
-            let truncated_line = truncate_utf8_with_ellipsis(&line, 2000);
+            let truncated_line = truncate_utf8_with_ellipsis(line, 2000);
 
             // Save first line for preview
             if lines_read == 0 {
@@ -336,20 +346,97 @@ impl ViewTool {
         // Generate preview
         let preview = truncate_utf8_with_ellipsis(&first_line_for_preview, 80);
 
-        // Read full content for content_original (required for compatibility)
-        let content_original = fs::read_to_string(path).context("Failed to read file")?;
-
         Ok(ViewResult {
             content: content_with_numbers,
             metadata: ViewMetadata {
                 filepath: absolute_path_str,
                 preview,
+                content_original: content.to_string(),
+            },
+            response_summary: format!("{} lines", lines_read),
+        })
+    }
+
+    /// `run_view` on the session's remote target; the file is read whole
+    fn run_view_remote(
+        &self,
+        target: &dyn ExecBackend,
+        request: &ViewRequest,
+        absolute_path: &Path,
+        confirmed: bool,
+    ) -> Result<ViewResult> {
+        let remote_path = target.target_path(absolute_path)?;
+        let Some(bytes) = target.read_file(&remote_path)? else {
+            anyhow::bail!("File not found on {}: {}", target.name(), request.file_path);
+        };
+        if !confirmed && SensitiveFiles::current().is_sensitive(absolute_path) {
+            return Ok(Self::sensitive_result(request, remote_path));
+        }
+        if Self::is_image_file(absolute_path) {
+            return Ok(ViewResult {
+                content: format!("This is an image file: {}\n\nImage files cannot be displayed as text.", remote_path),
+                metadata: ViewMetadata {
+                    filepath: remote_path,
+                    preview: "[Image file]".to_string(),
+                    content_original: String::new(),
+                },
+                response_summary: "Image file".to_string(),
+            });
+        }
+        if bytes.len() > self.max_file_size {
+            anyhow::bail!(
+                "File too large ({} bytes). Maximum size is {} bytes.",
+                bytes.len(),
+                self.max_file_size
+            );
+        }
+        backend::record_read(target, &remote_path, &bytes);
+
+        let content_original = String::from_utf8_lossy(&bytes).into_owned();
+        let offset = request.offset.unwrap_or(0);
+        let limit = request.limit.unwrap_or(2000);
+        let total_lines = content_original.lines().count();
+        let mut content_with_numbers = String::new();
+        for (i, line) in content_original.lines().enumerate().skip(offset).take(limit) {
+            content_with_numbers.push_str(&format!("{}\t{}\n", i + 1, truncate_utf8_with_ellipsis(line, 2000)));
+        }
+        let lines_read = total_lines.saturating_sub(offset).min(limit);
+        if offset + lines_read < total_lines {
+            content_with_numbers.push_str(&format!(
+                "\n(File has more lines. Use 'offset' parameter to read beyond line {})",
+                offset + lines_read
+            ));
+        }
+        let first_line = content_original.lines().nth(offset).unwrap_or("(empty or offset beyond file)");
+        let preview = truncate_utf8_with_ellipsis(first_line, 80);
+
+        Ok(ViewResult {
+            content: content_with_numbers,
+            metadata: ViewMetadata {
+                filepath: remote_path,
+                preview,
                 content_original,
             },
             response_summary: format!("{} lines", lines_read),
         })
     }
 
+    /// Placeholder for a sensitive file the user has not confirmed reading
+    fn sensitive_result(request: &ViewRequest, filepath: String) -> ViewResult {
+        ViewResult {
+            content: format!(
+                "{} {} matches [security] sensitive_files; its contents are shown only once the user confirms.",
+                REDACTED, request.file_path
+            ),
+            metadata: ViewMetadata {
+                filepath,
+                preview: REDACTED.to_string(),
+                content_original: String::new(),
+            },
+            response_summary: "Requires confirmation".to_string(),
+        }
+    }
+
     /// Get tool definition as JSON for LLM
     fn to_tool_definition_json(&self) -> serde_json::Value {
         serde_json::json!({
@@ -415,8 +502,22 @@ impl ToolSpec for ViewTool {
         self.to_tool_definition_json()
     }
 
-    fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
-        let result = self.run_view(&args)?;
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        let path = arg_summary::path(&args.file_path);
+        Some(match (args.offset, args.limit) {
+            (None, None) => path,
+            (offset, limit) => {
+                let start = offset.unwrap_or(0) + 1;
+                match limit {
+                    Some(limit) => format!("{} (lines {}–{})", path, start, start + limit.saturating_sub(1)),
+                    None => format!("{} (from line {})", path, start),
+                }
+            }
+        })
+    }
+
+    fn run(&self, args: Self::Args, confirmed: bool) -> Result<ToolResult> {
+        let result = self.run_view(&args, confirmed)?;
         let response_summary = result.response_summary.clone();
         let stdout = result.content.clone();
         let data = serde_json::to_value(result)?;
@@ -1,12 +1,19 @@
+use crate::llm::backend::sync;
 use crate::llm::config::AppConfig;
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::file_cache;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::file_lock;
 use crate::llm::utils::file_tracker::PathSecurity;
+use crate::llm::utils::overlay;
 use crate::llm::utils::path_policy::PathPolicy;
 use crate::llm::tools::tool_trait::{ToolKind, ToolOperation, ToolResult, ToolSpec};
+use crate::llm::tools::arg_summary;
 use crate::lsp::diagnostics::DiagnosticSummary;
 use crate::lsp::LspManager;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use similar::{ChangeTag, TextDiff};
 use std::collections::HashMap;
 use std::fs;
 use std::sync::{Arc, LazyLock, Mutex};
@@ -51,13 +58,9 @@ pub struct WriteRequest {
 /// Metadata for write operation
 #[derive(Debug, Serialize, Deserialize)]
 pub struct WriteMetadata {
-    /// File difference in unified format
+    /// Lines added/removed, hunks and the unified diff; absent when unchanged
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub diff: Option<String>,
-    /// Number of lines added
-    pub additions: usize,
-    /// Number of lines removed
-    pub removals: usize,
+    pub diff_stats: Option<DiffStats>,
 }
 
 /// Result of writing a file
@@ -96,9 +99,10 @@ impl WriteTool {
                     "Failed to load config.toml: {}, using hardcoded defaults",
                     e
                 );
-                let mut default = Self::default();
-                default.lsp_manager = lsp_manager;
-                default
+                Self {
+                    lsp_manager,
+                    ..Self::default()
+                }
             }
         }
     }
@@ -139,28 +143,23 @@ impl WriteTool {
         // 1) Canonicalize and restrict path to current workspace
         let policy = PathPolicy::new()?;
         let normalized = policy.resolve(&request.file_path)?;
-        let path = normalized.as_path();
         let absolute_path_str = normalized.to_string_lossy().to_string();
+        // In overlay mode the session's copy is read and written instead
+        let source = overlay::read_path(&normalized);
+        let path = source.as_path();
 
         let file_exists = path.exists();
-        let diff_output;
-        let additions;
-        let mut removals = 0;
 
-        if file_exists {
+        let original_content = if file_exists {
             // Read original content
             let original_content =
-                fs::read_to_string(path).context("Failed to read existing file")?;
+                file_cache::read_to_string(path).context("Failed to read existing file")?.to_string();
 
             // Content consistency check - if content is identical, skip write
             if original_content == request.content {
                 return Ok(WriteResult {
                     content: "File content unchanged, no write performed".to_string(),
-                    metadata: WriteMetadata {
-                        diff: None,
-                        additions: 0,
-                        removals: 0,
-                    },
+                    metadata: WriteMetadata { diff_stats: None },
                     diagnostics: None,
                     response_summary: "0 lines (unchanged)".to_string(),
                 });
@@ -185,23 +184,18 @@ impl WriteTool {
                 }
             }
 
-            // Calculate diff
-            let diff = Self::calculate_diff(&original_content, &request.content);
-
-            // Calculate additions and removals
-            let (add, rem) = Self::count_changes(&original_content, &request.content);
-            additions = add;
-            removals = rem;
-
-            diff_output = Some(diff);
+            original_content
         } else {
             // For new files, all lines are additions
-            additions = request.content.lines().count();
-
-            // Generate diff for new file
-            let diff = Self::calculate_diff("", &request.content);
-            diff_output = Some(diff);
-        }
+            String::new()
+        };
+        file_lock::lock_for_current_session(&normalized)?;
+        checkpoint::record_before_change(&normalized);
+        file_activity::record_edited(&normalized);
+        let diff_stats = DiffStats::compute(&request.file_path, &original_content, &request.content);
+        sync::before_local_write(&normalized);
+        let target = overlay::write_path(&normalized)?;
+        let path = target.as_path();
 
         // Create parent directories if needed
         if let Some(parent) = path.parent() {
@@ -232,11 +226,15 @@ impl WriteTool {
             );
         }
 
-        let success_message = if file_exists {
+        let mut success_message = if file_exists {
             format!("Successfully updated file: {}", request.file_path)
         } else {
             format!("Successfully created file: {}", request.file_path)
         };
+        // With a remote execution target the file goes there too
+        if let Some(note) = sync::push_current(&normalized) {
+            success_message.push_str(&format!("\n{}", note));
+        }
 
         // Calculate line count for summary
         let line_count = request.content.lines().count();
@@ -244,9 +242,7 @@ impl WriteTool {
         let mut result = WriteResult {
             content: success_message,
             metadata: WriteMetadata {
-                diff: diff_output,
-                additions,
-                removals,
+                diff_stats: Some(diff_stats),
             },
             diagnostics: None,
             response_summary: format!("{} lines", line_count),
@@ -298,69 +294,6 @@ impl WriteTool {
         .unwrap_or(None)
     }
 
-    /// Calculate diff between old and new content
-    ///
-    /// # Arguments
-    /// * `old_content` - Original content
-    /// * `new_content` - New content
-    ///
-    /// # Returns
-    /// * Diff string in unified format with + and - markers
-    fn calculate_diff(old_content: &str, new_content: &str) -> String {
-        let diff = TextDiff::from_lines(old_content, new_content);
-
-        let mut diff_output = String::new();
-
-        for op in diff.ops() {
-            for change in diff.iter_changes(op) {
-                let sign = match change.tag() {
-                    ChangeTag::Delete => "-",
-                    ChangeTag::Insert => "+",
-                    ChangeTag::Equal => " ",
-                };
-
-                if let Some(line) = change.as_str() {
-                    // Remove trailing newline for display
-                    let line = line.trim_end_matches('\n');
-
-                    diff_output.push_str(sign);
-                    diff_output.push(' ');
-                    diff_output.push_str(line);
-                    diff_output.push('\n');
-                }
-            }
-        }
-
-        diff_output
-    }
-
-    /// Count additions and removals between old and new content
-    ///
-    /// # Arguments
-    /// * `old_content` - Original content
-    /// * `new_content` - New content
-    ///
-    /// # Returns
-    /// * Tuple of (additions, removals)
-    fn count_changes(old_content: &str, new_content: &str) -> (usize, usize) {
-        let diff = TextDiff::from_lines(old_content, new_content);
-
-        let mut additions = 0;
-        let mut removals = 0;
-
-        for op in diff.ops() {
-            for change in diff.iter_changes(op) {
-                match change.tag() {
-                    ChangeTag::Delete => removals += 1,
-                    ChangeTag::Insert => additions += 1,
-                    ChangeTag::Equal => {}
-                }
-            }
-        }
-
-        (additions, removals)
-    }
-
     /// Get tool definition as JSON for LLM
     fn to_tool_definition_json(&self) -> serde_json::Value {
         serde_json::json!({
@@ -422,6 +355,10 @@ impl ToolSpec for WriteTool {
         self.to_tool_definition_json()
     }
 
+    fn summarize_args(&self, args: &Self::Args) -> Option<String> {
+        Some(format!("{} ({} lines)", arg_summary::path(&args.file_path), arg_summary::line_count(&args.content)))
+    }
+
     fn run(&self, args: Self::Args, _confirmed: bool) -> Result<ToolResult> {
         let result = self.run_write(&args)?;
         let response_summary = result.response_summary.clone();
@@ -0,0 +1,120 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+/// Tool outputs longer than this are kept out of the conversation history
+pub const ARTIFACT_THRESHOLD_CHARS: usize = 16_000;
+
+/// Characters of a stored output that stay in the history as an excerpt
+pub const ARTIFACT_EXCERPT_CHARS: usize = 2_000;
+
+/// Total size of a session's stored artifacts before its oldest are evicted
+const MAX_STORE_BYTES: usize = 16 * 1024 * 1024;
+
+const SCHEME: &str = "artifact://";
+
+/// A large tool output stored outside the conversation history
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub id: String,
+    /// Tool that produced the output
+    pub tool_name: String,
+    pub content: String,
+}
+
+impl Artifact {
+    /// Handle the model uses to refer to the artifact, e.g. `artifact://a12`
+    pub fn handle(&self) -> String {
+        format!("{}{}", SCHEME, self.id)
+    }
+}
+
+#[derive(Default)]
+struct ArtifactStore {
+    entries: VecDeque<Artifact>,
+    bytes: usize,
+    next_id: u64,
+}
+
+/// Artifacts of each session, by session id; a session only sees its own
+static ARTIFACTS: LazyLock<Mutex<HashMap<String, ArtifactStore>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn stores() -> MutexGuard<'static, HashMap<String, ArtifactStore>> {
+    ARTIFACTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Store `content` for the session and return the new artifact's handle
+pub fn store(session_id: &str, tool_name: &str, content: String) -> String {
+    let mut stores = stores();
+    let store = stores.entry(session_id.to_string()).or_default();
+    store.next_id += 1;
+    let artifact = Artifact {
+        id: format!("a{}", store.next_id),
+        tool_name: tool_name.to_string(),
+        content,
+    };
+    let handle = artifact.handle();
+
+    store.bytes += artifact.content.len();
+    store.entries.push_back(artifact);
+    while store.bytes > MAX_STORE_BYTES && store.entries.len() > 1 {
+        if let Some(evicted) = store.entries.pop_front() {
+            store.bytes -= evicted.content.len();
+        }
+    }
+    handle
+}
+
+/// Look up one of the session's artifacts by handle (`artifact://a12`) or bare id (`a12`)
+pub fn get(session_id: &str, handle: &str) -> Option<Artifact> {
+    let id = handle.trim().trim_start_matches(SCHEME);
+    stores()
+        .get(session_id)?
+        .entries
+        .iter()
+        .find(|a| a.id == id)
+        .cloned()
+}
+
+/// Drop the session's artifacts when it is closed
+pub fn forget(session_id: &str) {
+    stores().remove(session_id);
+}
+
+/// First `max_chars` characters of `content`, cut at a line boundary when possible
+pub fn excerpt(content: &str, max_chars: usize) -> &str {
+    let end = content
+        .char_indices()
+        .nth(max_chars)
+        .map(|(i, _)| i)
+        .unwrap_or(content.len());
+    let head = &content[..end];
+    if end == content.len() {
+        return head;
+    }
+    match head.rfind('\n') {
+        Some(i) if i > 0 => &head[..i],
+        _ => head,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_get_and_excerpt() {
+        let handle = store("artifact-session", "bash", "line 1\nline 2\nline 3".to_string());
+        assert!(handle.starts_with("artifact://a"));
+        let artifact = get("artifact-session", &handle).unwrap();
+        assert_eq!(artifact.tool_name, "bash");
+        assert_eq!(get("artifact-session", &artifact.id).unwrap().content, artifact.content);
+        assert!(get("artifact-session", "artifact://missing").is_none());
+        // Other sessions cannot read it, and closing the session drops it
+        assert!(get("other-session", &handle).is_none());
+        forget("artifact-session");
+        assert!(get("artifact-session", &handle).is_none());
+
+        assert_eq!(excerpt(&artifact.content, 10), "line 1");
+        assert_eq!(excerpt(&artifact.content, 100), artifact.content);
+    }
+}
@@ -0,0 +1,247 @@
+//! Per-turn file checkpoints: the content each file had before a turn first
+//! changed it. When the turn ends its net changes are offered for review as
+//! per-file diffs; reverting a file puts back the checkpointed content and
+//! accepting it drops the checkpoint. Files left unreviewed stay checkpointed
+//! across turns: the next review offers them again, diffed against their
+//! content before the first turn that changed them.
+//!
+//! Only the file tools record checkpoints. Files changed by bash commands or
+//! on a remote target are not checkpointed, and so are not offered for review
+//! unless a file tool changed them too. Overlay-mode changes are not
+//! checkpointed either; the overlay has its own review.
+
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::file_tracker::content_hash;
+use crate::llm::utils::overlay;
+use crate::llm::utils::tool_access::current_tool_session;
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+static CHECKPOINTS: LazyLock<Mutex<HashMap<String, TurnCheckpoint>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct TurnCheckpoint {
+    /// Keyed by absolute path
+    files: BTreeMap<PathBuf, FileCheckpoint>,
+    recording: bool,
+}
+
+struct FileCheckpoint {
+    /// Content before the turn changed the file; None if it did not exist
+    original: Option<Vec<u8>>,
+    /// Hash of the content the turn left, set when the turn ends
+    turn_end_hash: Option<Option<u64>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl FileChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FileChangeKind::Added => "added",
+            FileChangeKind::Modified => "modified",
+            FileChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// Net change a turn made to one file
+#[derive(Debug, Clone)]
+pub struct TurnFileChange {
+    pub change: FileChangeKind,
+    /// Against the checkpointed content, with the workspace-relative path
+    pub diff_stats: DiffStats,
+}
+
+/// Start checkpointing the session's file changes for a new turn, keeping
+/// the files earlier turns left unreviewed
+pub fn begin_turn(session_id: &str) {
+    let mut checkpoints = CHECKPOINTS.lock().unwrap();
+    checkpoints.entry(session_id.to_string()).or_default().recording = true;
+}
+
+/// Drop the session's checkpoint, e.g. when it closes
+pub fn forget(session_id: &str) {
+    CHECKPOINTS.lock().unwrap().remove(session_id);
+}
+
+/// Keep the current content of `path` before the executing tool call first changes it this turn
+pub fn record_before_change(path: &Path) {
+    let Some(session_id) = current_tool_session() else {
+        return;
+    };
+    if overlay::is_active() || path.is_dir() {
+        return;
+    }
+    let mut checkpoints = CHECKPOINTS.lock().unwrap();
+    let Some(checkpoint) = checkpoints.get_mut(&session_id).filter(|c| c.recording) else {
+        return;
+    };
+    checkpoint.files.entry(path.to_path_buf()).or_insert_with(|| FileCheckpoint {
+        original: fs::read(path).ok(),
+        turn_end_hash: None,
+    });
+}
+
+/// Stop checkpointing and list the unreviewed changed files, this turn's and
+/// those of earlier turns, relative to `workspace`. Files changed and then put
+/// back as they were are dropped.
+pub fn end_turn(session_id: &str, workspace: &Path) -> Vec<TurnFileChange> {
+    let mut checkpoints = CHECKPOINTS.lock().unwrap();
+    let Some(checkpoint) = checkpoints.get_mut(session_id) else {
+        return Vec::new();
+    };
+    checkpoint.recording = false;
+    let mut changes = Vec::new();
+    checkpoint.files.retain(|path, file| {
+        let current = fs::read(path).ok();
+        if current == file.original {
+            return false;
+        }
+        file.turn_end_hash = Some(current.as_deref().map(content_hash));
+        let change = match (&file.original, &current) {
+            (None, _) => FileChangeKind::Added,
+            (_, None) => FileChangeKind::Deleted,
+            _ => FileChangeKind::Modified,
+        };
+        let rel = path.strip_prefix(workspace).unwrap_or(path).to_string_lossy();
+        let text = |content: &Option<Vec<u8>>| {
+            String::from_utf8_lossy(content.as_deref().unwrap_or_default()).into_owned()
+        };
+        changes.push(TurnFileChange {
+            change,
+            diff_stats: DiffStats::compute(&rel, &text(&file.original), &text(&current)),
+        });
+        true
+    });
+    changes
+}
+
+/// Keep the turn's changes to `paths` (all files when empty). Returns the files accepted.
+pub fn accept(session_id: &str, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut checkpoints = CHECKPOINTS.lock().unwrap();
+    let Some(checkpoint) = checkpoints.get_mut(session_id) else {
+        return Vec::new();
+    };
+    let selected = select(checkpoint, paths);
+    for path in &selected {
+        checkpoint.files.remove(path);
+    }
+    selected
+}
+
+/// Put `paths` (all files when empty) back as they were before the turn.
+/// Refused, changing nothing, if one of them changed since the turn ended.
+pub fn revert(session_id: &str, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut checkpoints = CHECKPOINTS.lock().unwrap();
+    let Some(checkpoint) = checkpoints.get_mut(session_id) else {
+        return Ok(Vec::new());
+    };
+    if checkpoint.recording {
+        bail!("The turn is still running; revert its changes once it ends");
+    }
+    let selected = select(checkpoint, paths);
+    let changed_since: Vec<String> = selected
+        .iter()
+        .filter(|path| {
+            let current = fs::read(path).ok();
+            checkpoint.files[*path].turn_end_hash != Some(current.as_deref().map(content_hash))
+        })
+        .map(|path| path.display().to_string())
+        .collect();
+    if !changed_since.is_empty() {
+        bail!("Changed since the turn ended, not reverted: {}", changed_since.join(", "));
+    }
+    for path in &selected {
+        match &checkpoint.files[path].original {
+            Some(content) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+                }
+                fs::write(path, content).with_context(|| format!("Failed to restore {}", path.display()))?;
+            }
+            None => fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?,
+        }
+        checkpoint.files.remove(path);
+    }
+    Ok(selected)
+}
+
+/// The checkpointed files among `paths`, or all of them when `paths` is empty
+fn select(checkpoint: &TurnCheckpoint, paths: &[PathBuf]) -> Vec<PathBuf> {
+    checkpoint
+        .files
+        .keys()
+        .filter(|path| paths.is_empty() || paths.contains(path))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::utils::tool_access::with_tool_session;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn reviews_the_net_changes_of_a_turn() {
+        let workspace = FakeWorkspace::new()
+            .file("kept.rs", "fn a() {}\n")
+            .file("undone.rs", "same\n")
+            .build()
+            .unwrap();
+        let root = workspace.path();
+        let (kept, added, restored) = (root.join("kept.rs"), root.join("new.rs"), root.join("undone.rs"));
+
+        begin_turn("ck1");
+        with_tool_session("ck1", || {
+            for path in [&kept, &added, &restored] {
+                record_before_change(path);
+            }
+            // A later change of the same file keeps the first checkpoint
+            record_before_change(&kept);
+        });
+        fs::write(&kept, "fn a() {}\nfn b() {}\n").unwrap();
+        fs::write(&added, "new\n").unwrap();
+        fs::write(&kept, "fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+
+        let changes = end_turn("ck1", root);
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| (c.diff_stats.file_path.as_str(), c.change, c.diff_stats.additions))
+            .collect();
+        assert_eq!(summary, vec![("kept.rs", FileChangeKind::Modified, 2), ("new.rs", FileChangeKind::Added, 1)]);
+
+        fs::write(&kept, "edited by the user\n").unwrap();
+        assert!(revert("ck1", std::slice::from_ref(&kept)).is_err());
+        assert_eq!(revert("ck1", std::slice::from_ref(&added)).unwrap(), vec![added.clone()]);
+        assert!(!added.exists());
+        assert_eq!(accept("ck1", &[]), vec![kept.clone()]);
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "edited by the user\n");
+        assert!(revert("ck1", &[]).unwrap().is_empty());
+
+        // A change left unreviewed is offered again after the next turn
+        begin_turn("ck1");
+        with_tool_session("ck1", || record_before_change(&kept));
+        fs::write(&kept, "turn two\n").unwrap();
+        assert_eq!(end_turn("ck1", root).len(), 1);
+        begin_turn("ck1");
+        with_tool_session("ck1", || record_before_change(&added));
+        fs::write(&added, "turn three\n").unwrap();
+        let files: Vec<_> = end_turn("ck1", root).into_iter().map(|c| c.diff_stats.file_path).collect();
+        assert_eq!(files, vec!["kept.rs", "new.rs"]);
+        assert_eq!(revert("ck1", &[]).unwrap(), vec![kept.clone(), added.clone()]);
+        assert_eq!(fs::read_to_string(&kept).unwrap(), "edited by the user\n");
+        assert!(!added.exists());
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+/// Lines of context around each hunk in the unified diff
+const CONTEXT_RADIUS: usize = 3;
+
+/// Structured summary of a file change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffStats {
+    /// Path as given in the tool request
+    pub file_path: String,
+    pub additions: usize,
+    pub removals: usize,
+    /// Number of hunks in the unified diff
+    pub hunks: usize,
+    pub unified_diff: String,
+}
+
+impl DiffStats {
+    pub fn compute(file_path: &str, old_content: &str, new_content: &str) -> Self {
+        let diff = TextDiff::from_lines(old_content, new_content);
+        let mut additions = 0;
+        let mut removals = 0;
+
+        for change in diff.iter_all_changes() {
+            match change.tag() {
+                ChangeTag::Delete => removals += 1,
+                ChangeTag::Insert => additions += 1,
+                ChangeTag::Equal => {}
+            }
+        }
+
+        let hunks = diff.grouped_ops(CONTEXT_RADIUS).len();
+        let unified_diff = diff
+            .unified_diff()
+            .context_radius(CONTEXT_RADIUS)
+            .header(&format!("a/{}", file_path), &format!("b/{}", file_path))
+            .to_string();
+
+        Self {
+            file_path: file_path.to_string(),
+            additions,
+            removals,
+            hunks,
+            unified_diff,
+        }
+    }
+
+    /// Compact label such as "+12 −3 src/foo.rs"
+    pub fn label(&self) -> String {
+        format!("+{} \u{2212}{} {}", self.additions, self.removals, self.file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_lines_and_hunks() {
+        let old = (1..=20).map(|i| format!("line {}\n", i)).collect::<String>();
+        let new = old
+            .replace("line 2\n", "line two\nline 2b\n")
+            .replace("line 18\n", "");
+        let stats = DiffStats::compute("src/foo.rs", &old, &new);
+        assert_eq!(stats.additions, 2);
+        assert_eq!(stats.removals, 2);
+        assert_eq!(stats.hunks, 2);
+        assert!(stats.unified_diff.starts_with("--- a/src/foo.rs\n+++ b/src/foo.rs\n"));
+        assert_eq!(stats.label(), "+2 \u{2212}2 src/foo.rs");
+    }
+}
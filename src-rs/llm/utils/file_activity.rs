@@ -0,0 +1,107 @@
+//! Files each session's tools viewed or edited, most recent first, for the
+//! `recent_files` context provider. Only the latest activity per file is
+//! kept, and an edit is not downgraded by a later view.
+
+use crate::llm::utils::tool_access::current_tool_session;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+/// Files remembered per session
+const MAX_FILES: usize = 200;
+
+static ACTIVITY: LazyLock<Mutex<HashMap<String, Vec<FileActivity>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Ordered so that an edit outranks a view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActivityKind {
+    Viewed,
+    Edited,
+}
+
+impl ActivityKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ActivityKind::Viewed => "viewed",
+            ActivityKind::Edited => "edited",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileActivity {
+    /// Absolute path
+    pub path: PathBuf,
+    pub kind: ActivityKind,
+    pub at: SystemTime,
+}
+
+/// Note that the executing tool call read `path`
+pub fn record_viewed(path: &Path) {
+    record(path, ActivityKind::Viewed);
+}
+
+/// Note that the executing tool call changed `path`
+pub fn record_edited(path: &Path) {
+    record(path, ActivityKind::Edited);
+}
+
+fn record(path: &Path, kind: ActivityKind) {
+    let Some(session_id) = current_tool_session() else {
+        return;
+    };
+    let mut activity = ACTIVITY.lock().unwrap();
+    let files = activity.entry(session_id).or_default();
+    let kind = match files.iter().position(|f| f.path == path) {
+        Some(i) => files.remove(i).kind.max(kind),
+        None => kind,
+    };
+    files.insert(
+        0,
+        FileActivity {
+            path: path.to_path_buf(),
+            kind,
+            at: SystemTime::now(),
+        },
+    );
+    files.truncate(MAX_FILES);
+}
+
+/// The session's files, most recently used first
+pub fn recent(session_id: &str) -> Vec<FileActivity> {
+    ACTIVITY.lock().unwrap().get(session_id).cloned().unwrap_or_default()
+}
+
+/// Drop what the session touched, e.g. when it closes
+pub fn forget(session_id: &str) {
+    ACTIVITY.lock().unwrap().remove(session_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::utils::tool_access::with_tool_session;
+
+    #[test]
+    fn keeps_the_latest_use_of_each_file() {
+        with_tool_session("fa1", || {
+            record_viewed(Path::new("/ws/a.rs"));
+            record_edited(Path::new("/ws/b.rs"));
+            record_edited(Path::new("/ws/a.rs"));
+            record_viewed(Path::new("/ws/b.rs"));
+        });
+        record_viewed(Path::new("/ws/outside-a-session.rs"));
+        let files: Vec<_> = recent("fa1").into_iter().map(|f| (f.path, f.kind)).collect();
+        assert_eq!(
+            files,
+            vec![
+                (PathBuf::from("/ws/b.rs"), ActivityKind::Edited),
+                (PathBuf::from("/ws/a.rs"), ActivityKind::Edited),
+            ]
+        );
+        forget("fa1");
+        assert!(recent("fa1").is_empty());
+    }
+}
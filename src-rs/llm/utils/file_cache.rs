@@ -0,0 +1,173 @@
+//! Process-wide cache of file contents shared by the file tools.
+//!
+//! Entries are keyed by path and checked against the file's modification
+//! time and size on every read, so a file changed on disk is read again.
+//! Like git's index, an entry read within `RACY_WINDOW` of its modification
+//! time is not trusted: a second write in the same mtime tick would go
+//! unnoticed. The least recently used files are evicted beyond
+//! `MAX_CACHE_BYTES`, and files over `MAX_ENTRY_BYTES` are not cached.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// Total size of cached contents before the least recently used are evicted
+const MAX_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Files larger than this are read without being cached
+const MAX_ENTRY_BYTES: usize = 8 * 1024 * 1024;
+
+/// Entries read this soon after their modification time are read again
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+static CACHE: LazyLock<Mutex<FileCache>> = LazyLock::new(|| Mutex::new(FileCache::new(MAX_CACHE_BYTES)));
+
+struct CachedFile {
+    content: Arc<str>,
+    modified: SystemTime,
+    len: u64,
+    read_at: SystemTime,
+}
+
+impl CachedFile {
+    fn matches(&self, metadata: &fs::Metadata) -> bool {
+        let Ok(modified) = metadata.modified() else {
+            return false;
+        };
+        modified == self.modified
+            && metadata.len() == self.len
+            && self.read_at.duration_since(modified).is_ok_and(|d| d >= RACY_WINDOW)
+    }
+}
+
+struct FileCache {
+    entries: HashMap<PathBuf, CachedFile>,
+    /// Least recently used first
+    order: VecDeque<PathBuf>,
+    bytes: usize,
+    capacity: usize,
+}
+
+impl FileCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            bytes: 0,
+            capacity,
+        }
+    }
+
+    fn get(&mut self, path: &Path, metadata: &fs::Metadata) -> Option<Arc<str>> {
+        let content = self.entries.get(path).filter(|e| e.matches(metadata))?.content.clone();
+        self.touch(path);
+        Some(content)
+    }
+
+    fn insert(&mut self, path: &Path, file: CachedFile) {
+        self.remove(path);
+        if file.content.len() > MAX_ENTRY_BYTES.min(self.capacity) {
+            return;
+        }
+        self.bytes += file.content.len();
+        self.entries.insert(path.to_path_buf(), file);
+        self.order.push_back(path.to_path_buf());
+        while self.bytes > self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(evicted) = self.entries.remove(&oldest) {
+                self.bytes -= evicted.content.len();
+            }
+        }
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(removed) = self.entries.remove(path) {
+            self.bytes -= removed.content.len();
+            self.order.retain(|p| p != path);
+        }
+    }
+
+    fn touch(&mut self, path: &Path) {
+        if let Some(i) = self.order.iter().position(|p| p == path) {
+            if let Some(path) = self.order.remove(i) {
+                self.order.push_back(path);
+            }
+        }
+    }
+}
+
+/// The file's content as UTF-8, from the cache when the file is unchanged
+pub fn read_to_string(path: &Path) -> io::Result<Arc<str>> {
+    let metadata = fs::metadata(path)?;
+    if let Some(content) = CACHE.lock().unwrap().get(path, &metadata) {
+        return Ok(content);
+    }
+    let read_at = SystemTime::now();
+    let content: Arc<str> = fs::read_to_string(path)?.into();
+    // Cache only if the file did not change while it was read
+    let after = fs::metadata(path)?;
+    if let Ok(modified) = after.modified() {
+        if metadata.modified().ok() == Some(modified) && after.len() == content.len() as u64 {
+            let file = CachedFile {
+                content: content.clone(),
+                modified,
+                len: after.len(),
+                read_at,
+            };
+            CACHE.lock().unwrap().insert(path, file);
+        }
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    fn set_mtime(path: &Path, modified: SystemTime) {
+        fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn serves_unchanged_files_and_evicts_least_recently_used() {
+        let workspace = FakeWorkspace::new().build().unwrap();
+        let path = workspace.join("a.txt");
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        fs::write(&path, "one").unwrap();
+        set_mtime(&path, hour_ago);
+        assert_eq!(&*read_to_string(&path).unwrap(), "one");
+
+        // Same size and mtime: the cached content is served
+        fs::write(&path, "two").unwrap();
+        set_mtime(&path, hour_ago);
+        assert_eq!(&*read_to_string(&path).unwrap(), "one");
+        // A new mtime is noticed, and a just-modified file is not trusted
+        set_mtime(&path, SystemTime::now());
+        assert_eq!(&*read_to_string(&path).unwrap(), "two");
+        fs::write(&path, "six").unwrap();
+        assert_eq!(&*read_to_string(&path).unwrap(), "six");
+
+        let mut cache = FileCache::new(10);
+        let file = |content: &str| CachedFile {
+            content: content.into(),
+            modified: hour_ago,
+            len: content.len() as u64,
+            read_at: SystemTime::now(),
+        };
+        cache.insert(Path::new("/a"), file("aaaa"));
+        cache.insert(Path::new("/b"), file("bbbb"));
+        cache.touch(Path::new("/a"));
+        cache.insert(Path::new("/c"), file("cccc"));
+        assert!(cache.entries.contains_key(Path::new("/a")));
+        assert!(!cache.entries.contains_key(Path::new("/b")));
+        assert_eq!(cache.bytes, 8);
+        cache.insert(Path::new("/big"), file("more than ten"));
+        assert_eq!((cache.entries.len(), cache.bytes), (2, 8));
+    }
+}
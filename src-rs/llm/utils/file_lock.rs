@@ -0,0 +1,128 @@
+use crate::llm::utils::tool_access::current_tool_session;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// A lock not refreshed for this long is considered abandoned
+const LOCK_LEASE: Duration = Duration::from_secs(300);
+
+/// Owner of edits made outside any session, i.e. by the user
+const USER_OWNER: &str = "user";
+
+#[derive(Debug, Clone)]
+struct FileLock {
+    owner: String,
+    refreshed_at: Instant,
+}
+
+/// Advisory locks on file paths, keyed by absolute path
+///
+/// A session takes the lock on its first edit of a file and holds it until
+/// its turn ends, so a second session editing the same file gets a conflict
+/// instead of silently overwriting the first one's changes.
+static FILE_LOCKS: LazyLock<Mutex<HashMap<String, FileLock>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Another owner holds the lock on a path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileConflict {
+    pub path: String,
+    /// Key of the session holding the lock, the owner given to `lock_file`, or
+    /// "user" for an edit made outside any session
+    pub owner: String,
+}
+
+impl std::fmt::Display for FileConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "File '{}' is being edited by another session ({}). Wait for it to finish or work on a different file.",
+            self.path, self.owner
+        )
+    }
+}
+
+impl std::error::Error for FileConflict {}
+
+/// Take or refresh the lock on `path` for `owner`
+pub fn acquire(path: &str, owner: &str) -> Result<(), FileConflict> {
+    let mut locks = FILE_LOCKS.lock().unwrap();
+    if let Some(lock) = locks.get(path).filter(|lock| lock.owner != owner) {
+        if lock.refreshed_at.elapsed() < LOCK_LEASE {
+            return Err(FileConflict {
+                path: path.to_string(),
+                owner: lock.owner.clone(),
+            });
+        }
+        log::warn!(
+            "The lock of {} on {} lapsed after {}s without an edit; {} takes it over",
+            lock.owner,
+            path,
+            lock.refreshed_at.elapsed().as_secs(),
+            owner
+        );
+    }
+    locks.insert(
+        path.to_string(),
+        FileLock {
+            owner: owner.to_string(),
+            refreshed_at: Instant::now(),
+        },
+    );
+    Ok(())
+}
+
+/// Release the lock on `path` if `owner` holds it
+pub fn release(path: &str, owner: &str) -> bool {
+    let mut locks = FILE_LOCKS.lock().unwrap();
+    if locks.get(path).is_some_and(|l| l.owner == owner) {
+        locks.remove(path);
+        return true;
+    }
+    false
+}
+
+/// Release every lock held by `owner`, returning how many were held
+pub fn release_all(owner: &str) -> usize {
+    let mut locks = FILE_LOCKS.lock().unwrap();
+    let before = locks.len();
+    locks.retain(|_, l| l.owner != owner);
+    before - locks.len()
+}
+
+/// Lock `path` for the session executing the current tool call
+pub fn lock_for_current_session(path: &Path) -> Result<(), FileConflict> {
+    let owner = current_tool_session().unwrap_or_else(|| USER_OWNER.to_string());
+    acquire(&path.to_string_lossy(), &owner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_owner_conflicts_until_release() {
+        let path = "/tmp/carrycode-lock-test/a.rs";
+        acquire(path, "s1").unwrap();
+        acquire(path, "s1").unwrap();
+        let conflict = acquire(path, "s2").unwrap_err();
+        assert_eq!(conflict.owner, "s1");
+
+        assert!(!release(path, "s2"));
+        assert_eq!(release_all("s1"), 1);
+        acquire(path, "s2").unwrap();
+        assert!(release(path, "s2"));
+    }
+
+    #[test]
+    fn a_lapsed_lease_is_taken_over() {
+        let path = "/tmp/carrycode-lock-test/lapsed.rs";
+        let refreshed_at = Instant::now().checked_sub(LOCK_LEASE + Duration::from_secs(1)).unwrap();
+        FILE_LOCKS.lock().unwrap().insert(path.to_string(), FileLock { owner: "s1".to_string(), refreshed_at });
+        acquire(path, "s2").unwrap();
+        assert!(!release(path, "s1"));
+        assert!(release(path, "s2"));
+    }
+}
@@ -1,10 +1,12 @@
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::LazyLock;
 use std::sync::Mutex;
-use std::time::SystemTime;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub static FILE_READ_TRACKER: LazyLock<Mutex<FileReadTracker>> =
     LazyLock::new(|| Mutex::new(FileReadTracker::new()));
@@ -37,7 +39,7 @@ impl FileHistoryTracker {
 
         self.histories
             .entry(path_str)
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(version);
     }
 
@@ -55,9 +57,62 @@ impl FileHistoryTracker {
     }
 }
 
+/// When a file was last read, and a hash of what was read
+#[derive(Debug, Clone, Copy)]
+struct ReadRecord {
+    time: SystemTime,
+    hash: Option<u64>,
+}
+
+/// The file changed on disk after it was last read
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaleRead {
+    pub path: String,
+    pub read_at_ms: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_at_ms: Option<i64>,
+}
+
+impl std::fmt::Display for StaleRead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "File '{}' has changed on disk since it was last read. Re-read it with the view tool before editing.",
+            self.path
+        )
+    }
+}
+
+impl std::error::Error for StaleRead {}
+
+/// Hash of file content, used to detect changes since a read
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Canonical form of `path`, resolving the parent when the file itself is gone
+fn read_key(path: &str) -> String {
+    let path = Path::new(path);
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| {
+        match (path.parent().and_then(|p| fs::canonicalize(p).ok()), path.file_name()) {
+            (Some(parent), Some(name)) => parent.join(name),
+            _ => path.to_path_buf(),
+        }
+    });
+    canonical.to_string_lossy().to_string()
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
 #[derive(Debug)]
 pub struct FileReadTracker {
-    reads: HashMap<String, SystemTime>,
+    reads: HashMap<String, ReadRecord>,
 }
 
 impl FileReadTracker {
@@ -67,28 +122,51 @@ impl FileReadTracker {
         }
     }
 
-    pub fn record_read(&mut self, path: &str) {
-        let canonical_path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
-        let path_str = canonical_path.to_string_lossy().to_string();
-        self.reads.insert(path_str, SystemTime::now());
+    /// Record a read of `content`, the bytes that were shown for the file
+    ///
+    /// Hashing what was shown rather than re-reading the file means a write
+    /// that lands between the read and the record is still detected as stale.
+    pub fn record_read(&mut self, path: &str, content: &[u8]) {
+        self.reads.insert(
+            read_key(path),
+            ReadRecord {
+                time: SystemTime::now(),
+                hash: Some(content_hash(content)),
+            },
+        );
+    }
+
+    /// Carry the read record of a moved file over to its new path
+    pub fn record_move(&mut self, from: &str, to: &str) {
+        if let Some(record) = self.reads.remove(&read_key(from)) {
+            self.reads.insert(read_key(to), record);
+        }
+    }
+
+    fn record(&self, path: &str) -> Option<&ReadRecord> {
+        self.reads.get(&read_key(path))
     }
 
     pub fn has_been_read(&self, path: &str) -> bool {
-        let canonical_path = match fs::canonicalize(path) {
-            Ok(p) => p,
-            Err(_) => return false,
-        };
-        let path_str = canonical_path.to_string_lossy().to_string();
-        self.reads.contains_key(&path_str)
+        self.record(path).is_some()
     }
 
-    pub fn get_last_read_time(&self, path: &str) -> Option<SystemTime> {
-        let canonical_path = match fs::canonicalize(path) {
-            Ok(p) => p,
-            Err(_) => return None,
+    /// Check whether a previously read file changed on disk since the read
+    ///
+    /// Content hashes are compared when available; otherwise the modification
+    /// time is compared with the read time. Returns `None` for unread files.
+    pub fn check_stale(&self, path: &str) -> Option<StaleRead> {
+        let record = self.record(path)?;
+        let modified = PathSecurity::get_modification_time(path).ok();
+        let changed = match record.hash {
+            Some(hash) => fs::read(path).ok().map(|b| content_hash(&b)) != Some(hash),
+            None => modified.is_some_and(|m| m > record.time),
         };
-        let path_str = canonical_path.to_string_lossy().to_string();
-        self.reads.get(&path_str).copied()
+        changed.then(|| StaleRead {
+            path: path.to_string(),
+            read_at_ms: millis(record.time),
+            modified_at_ms: modified.map(millis),
+        })
     }
 }
 
@@ -120,3 +198,34 @@ impl PathSecurity {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn check_stale_detects_content_changes_after_read() {
+        let workspace = FakeWorkspace::new().build().unwrap();
+        let path = workspace.join("stale.txt");
+        let path_str = path.to_string_lossy().to_string();
+        fs::write(&path, "one").unwrap();
+
+        let mut tracker = FileReadTracker::new();
+        assert!(tracker.check_stale(&path_str).is_none());
+        tracker.record_read(&path_str, b"one");
+        assert!(tracker.check_stale(&path_str).is_none());
+
+        fs::write(&path, "two").unwrap();
+        let stale = tracker.check_stale(&path_str).expect("change should be detected");
+        assert_eq!(stale.path, path_str);
+
+        // Rewriting identical content is not a change
+        fs::write(&path, "one").unwrap();
+        assert!(tracker.check_stale(&path_str).is_none());
+
+        // A write that lands before the read is recorded is still caught
+        tracker.record_read(&path_str, b"zero");
+        let stale = tracker.check_stale(&path_str).expect("change should be detected");
+        assert_eq!(stale.path, path_str);
+    }
+}
@@ -0,0 +1,339 @@
+use crate::llm::utils::file_tracker::FILE_READ_TRACKER;
+use crate::llm::utils::path_policy::PathPolicy;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Files larger than this are attached as an outline instead of full content
+const MAX_INLINE_BYTES: u64 = 64 * 1024;
+/// Maximum number of outline lines attached for a large file
+const MAX_OUTLINE_LINES: usize = 200;
+/// Maximum number of entries listed for a directory mention
+const MAX_DIR_ENTRIES: usize = 200;
+/// Maximum number of mentions resolved per prompt
+const MAX_MENTIONS: usize = 20;
+
+static OUTLINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(#{1,6}\s|(pub(\([^)]*\))?\s+)?(async\s+)?(fn|struct|enum|trait|impl|mod|type|const|static|class|def|function|interface|export|func|package)\b)",
+    )
+    .expect("outline regex should compile")
+});
+
+/// How a mention was attached to the turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MentionKind {
+    File,
+    Outline,
+    Directory,
+}
+
+/// A mention that was resolved and attached
+#[derive(Debug, Clone)]
+pub struct ResolvedMention {
+    /// Mention text as written (without the leading '@')
+    pub mention: String,
+    /// Absolute path of the referenced file or directory
+    pub path: PathBuf,
+    pub kind: MentionKind,
+}
+
+/// A mention that could not be attached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedMention {
+    pub mention: String,
+    pub reason: String,
+}
+
+/// Result of mention preprocessing
+#[derive(Debug, Clone)]
+pub struct MentionResolution {
+    /// Prompt with referenced content appended
+    pub prompt: String,
+    pub resolved: Vec<ResolvedMention>,
+    pub unresolved: Vec<UnresolvedMention>,
+}
+
+/// Extract `@path` mentions from a prompt
+///
+/// A mention starts with '@' at the beginning of the prompt or after whitespace
+/// or an opening bracket. `@"path with spaces"` is supported. Trailing
+/// punctuation is not considered part of the path.
+pub fn extract_mentions(prompt: &str) -> Vec<String> {
+    let chars: Vec<char> = prompt.chars().collect();
+    let mut mentions: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] != '@' {
+            i += 1;
+            continue;
+        }
+        let at_boundary = i == 0 || matches!(chars[i - 1], c if c.is_whitespace() || c == '(' || c == '[');
+        if !at_boundary {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        let mention = if chars.get(j) == Some(&'"') {
+            j += 1;
+            let start = j;
+            while j < chars.len() && chars[j] != '"' {
+                j += 1;
+            }
+            let s: String = chars[start..j].iter().collect();
+            j += 1;
+            s
+        } else {
+            let start = j;
+            while j < chars.len() && !chars[j].is_whitespace() {
+                j += 1;
+            }
+            let s: String = chars[start..j].iter().collect();
+            s.trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '}', '\'', '"'])
+                .to_string()
+        };
+
+        if !mention.is_empty() && !mentions.contains(&mention) {
+            mentions.push(mention);
+        }
+        i = j;
+    }
+
+    mentions
+}
+
+fn looks_like_path(mention: &str) -> bool {
+    mention.contains('/') || mention.contains('.')
+}
+
+fn display_path(policy: &PathPolicy, path: &Path) -> String {
+    path.strip_prefix(policy.root())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string_lossy().to_string())
+}
+
+fn list_directory(path: &Path) -> std::io::Result<String> {
+    let mut entries: Vec<(String, bool)> = fs::read_dir(path)?
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') {
+                return None;
+            }
+            let is_dir = e.path().is_dir();
+            Some((name, is_dir))
+        })
+        .collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let total = entries.len();
+    let mut out = String::new();
+    for (name, is_dir) in entries.into_iter().take(MAX_DIR_ENTRIES) {
+        out.push_str(&name);
+        if is_dir {
+            out.push('/');
+        }
+        out.push('\n');
+    }
+    if total > MAX_DIR_ENTRIES {
+        out.push_str(&format!("... ({} more entries)\n", total - MAX_DIR_ENTRIES));
+    }
+    Ok(out)
+}
+
+fn outline(content: &str) -> String {
+    let mut out = String::new();
+    let mut count = 0;
+    for (idx, line) in content.lines().enumerate() {
+        if OUTLINE_RE.is_match(line) {
+            out.push_str(&format!("{}\t{}\n", idx + 1, line.trim_end()));
+            count += 1;
+            if count >= MAX_OUTLINE_LINES {
+                out.push_str("...\n");
+                break;
+            }
+        }
+    }
+    out
+}
+
+/// Resolve `@path` mentions in a prompt against the workspace
+///
+/// Referenced files are appended to the prompt in full, or as an outline when
+/// they are larger than 64KB. Directories are appended as a one-level listing.
+/// Mentions that look like paths but cannot be attached are returned in
+/// `unresolved`; other `@words` are left untouched.
+pub fn resolve_mentions(prompt: &str, policy: &PathPolicy) -> MentionResolution {
+    let mut resolved = Vec::new();
+    let mut unresolved = Vec::new();
+    let mut attachments = String::new();
+
+    for mention in extract_mentions(prompt).into_iter().take(MAX_MENTIONS) {
+        let path = match policy.resolve(&mention) {
+            Ok(p) => p,
+            Err(e) => {
+                unresolved.push(UnresolvedMention {
+                    mention,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if !path.exists() {
+            if looks_like_path(&mention) {
+                unresolved.push(UnresolvedMention {
+                    mention,
+                    reason: "No such file or directory".to_string(),
+                });
+            }
+            continue;
+        }
+
+        let shown = display_path(policy, &path);
+
+        if path.is_dir() {
+            match list_directory(&path) {
+                Ok(listing) => {
+                    attachments.push_str(&format!(
+                        "<mentioned_directory path=\"{}\">\n{}</mentioned_directory>\n",
+                        shown, listing
+                    ));
+                    resolved.push(ResolvedMention {
+                        mention,
+                        path,
+                        kind: MentionKind::Directory,
+                    });
+                }
+                Err(e) => unresolved.push(UnresolvedMention {
+                    mention,
+                    reason: format!("Failed to read directory: {}", e),
+                }),
+            }
+            continue;
+        }
+
+        let bytes = match fs::read(&path) {
+            Ok(b) => b,
+            Err(e) => {
+                unresolved.push(UnresolvedMention {
+                    mention,
+                    reason: format!("Failed to read file: {}", e),
+                });
+                continue;
+            }
+        };
+        let content = match String::from_utf8(bytes) {
+            Ok(s) if !s.contains('\0') => s,
+            _ => {
+                unresolved.push(UnresolvedMention {
+                    mention,
+                    reason: "Binary file cannot be attached".to_string(),
+                });
+                continue;
+            }
+        };
+
+        let line_count = content.lines().count();
+        if content.len() as u64 > MAX_INLINE_BYTES {
+            attachments.push_str(&format!(
+                "<mentioned_file path=\"{}\" lines=\"{}\" outline=\"true\">\nFile is {} bytes; only an outline is attached. Use the view tool to read specific ranges.\n{}</mentioned_file>\n",
+                shown,
+                line_count,
+                content.len(),
+                outline(&content)
+            ));
+            resolved.push(ResolvedMention {
+                mention,
+                path,
+                kind: MentionKind::Outline,
+            });
+        } else {
+            {
+                let mut tracker = FILE_READ_TRACKER.lock().unwrap();
+                tracker.record_read(&path.to_string_lossy(), content.as_bytes());
+            }
+            let mut body = content;
+            if !body.ends_with('\n') {
+                body.push('\n');
+            }
+            attachments.push_str(&format!(
+                "<mentioned_file path=\"{}\" lines=\"{}\">\n{}</mentioned_file>\n",
+                shown, line_count, body
+            ));
+            resolved.push(ResolvedMention {
+                mention,
+                path,
+                kind: MentionKind::File,
+            });
+        }
+    }
+
+    let prompt = if attachments.is_empty() {
+        prompt.to_string()
+    } else {
+        format!("{}\n\n{}", prompt, attachments.trim_end())
+    };
+
+    MentionResolution {
+        prompt,
+        resolved,
+        unresolved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn extract_mentions_handles_boundaries_and_punctuation() {
+        let mentions = extract_mentions("see @src/lib.rs, and (@docs/) but not a@b.com @\"a b.txt\"");
+        assert_eq!(mentions, vec!["src/lib.rs", "docs/", "a b.txt"]);
+    }
+
+    #[test]
+    fn resolve_mentions_attaches_files_and_directories() {
+        let workspace = FakeWorkspace::new().file("src/main.rs", "fn main() {}\n").build().unwrap();
+        let policy = PathPolicy::with_root(workspace.path().to_path_buf());
+
+        let res = resolve_mentions("look at @src/main.rs and @src/", &policy);
+        assert_eq!(res.resolved.len(), 2);
+        assert!(res.unresolved.is_empty());
+        assert!(res.prompt.contains("<mentioned_file path=\"src/main.rs\" lines=\"1\">"));
+        assert!(res.prompt.contains("fn main() {}"));
+        assert!(res.prompt.contains("<mentioned_directory path=\"src\">\nmain.rs\n"));
+    }
+
+    #[test]
+    fn resolve_mentions_reports_missing_paths_and_ignores_handles() {
+        let workspace = FakeWorkspace::new().build().unwrap();
+        let policy = PathPolicy::with_root(workspace.path().to_path_buf());
+
+        let res = resolve_mentions("ask @alice about @missing.rs", &policy);
+        assert!(res.resolved.is_empty());
+        assert_eq!(res.unresolved.len(), 1);
+        assert_eq!(res.unresolved[0].mention, "missing.rs");
+        assert_eq!(res.prompt, "ask @alice about @missing.rs");
+    }
+
+    #[test]
+    fn resolve_mentions_uses_outline_for_large_files() {
+        let mut content = String::from("pub struct Big;\n");
+        while (content.len() as u64) <= MAX_INLINE_BYTES {
+            content.push_str("    let x = 1;\n");
+        }
+        let workspace = FakeWorkspace::new().file("big.rs", &content).build().unwrap();
+        let policy = PathPolicy::with_root(workspace.path().to_path_buf());
+
+        let res = resolve_mentions("@big.rs", &policy);
+        assert_eq!(res.resolved[0].kind, MentionKind::Outline);
+        assert!(res.prompt.contains("outline=\"true\""));
+        assert!(res.prompt.contains("1\tpub struct Big;"));
+        assert!(!res.prompt.contains("let x = 1;"));
+    }
+}
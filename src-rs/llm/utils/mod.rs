@@ -1,5 +1,19 @@
+pub mod artifacts;
+pub mod checkpoint;
+pub mod diff_stats;
+pub mod file_activity;
+pub mod file_cache;
+pub mod file_lock;
 pub mod file_tracker;
+pub mod mentions;
 pub mod path_policy;
+pub mod prompt_guard;
 pub mod network;
+pub mod overlay;
 pub mod tool_access;
+pub mod sensitive_files;
 pub mod serde_util;
+pub mod session_vars;
+pub mod shell_safety;
+pub mod terminal_output;
+pub mod trash;
@@ -0,0 +1,430 @@
+//! Copy-on-write overlay for a session: file tools write into
+//! `.carry/overlay/<session>/files/` instead of the workspace, and read the
+//! shadow copy wherever one exists. The changes stay there until they are
+//! materialized into the workspace or discarded.
+//!
+//! Only the file tools go through the overlay; directory listings and
+//! searches show the workspace as it is, and bash is limited to read-only
+//! commands while the overlay is on.
+
+use crate::llm::utils::file_tracker::content_hash;
+use crate::llm::utils::tool_access::current_tool_session;
+use crate::llm::utils::trash::{session_dir_name, Trash};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const FILES_DIR: &str = "files";
+
+/// Serializes manifest updates between concurrent tool calls
+static MANIFEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlayChangeKind {
+    Write,
+    Delete,
+    Mkdir,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct OverlayEntry {
+    change: OverlayChangeKind,
+    /// Hash of the workspace file when it was first shadowed; None if it did not exist
+    base_hash: Option<u64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Keyed by path relative to the workspace
+    entries: BTreeMap<String, OverlayEntry>,
+}
+
+/// A pending change, for review before materializing
+#[derive(Debug, Clone, Serialize)]
+pub struct OverlayChange {
+    /// Path relative to the workspace
+    pub path: String,
+    pub change: OverlayChangeKind,
+    /// Unified diff against the workspace, for writes of text files
+    pub diff: Option<String>,
+    /// The workspace file changed since the overlay copied it
+    pub conflict: bool,
+}
+
+/// Outcome of materializing an overlay
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MaterializeReport {
+    pub applied: Vec<String>,
+    /// Changes left in the overlay because the workspace file changed meanwhile
+    pub conflicts: Vec<String>,
+}
+
+/// A session's overlay of a workspace
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    workspace: PathBuf,
+    session_id: String,
+    dir: PathBuf,
+}
+
+impl Overlay {
+    pub fn for_session(workspace: &Path, session_id: &str) -> Self {
+        Self {
+            workspace: workspace.to_path_buf(),
+            session_id: session_id.to_string(),
+            dir: workspace
+                .join(".carry")
+                .join("overlay")
+                .join(session_dir_name(session_id)),
+        }
+    }
+
+    /// Overlay of the executing tool call's session, if it has one switched on
+    pub fn current() -> Option<Self> {
+        let session_id = current_tool_session()?;
+        let workspace = fs::canonicalize(std::env::current_dir().ok()?).ok()?;
+        let overlay = Self::for_session(&workspace, &session_id);
+        overlay.is_enabled().then_some(overlay)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.join(MANIFEST_FILE).is_file()
+    }
+
+    /// Switch the overlay on, or off when it holds no changes
+    pub fn set_enabled(&self, enabled: bool) -> Result<()> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        if enabled {
+            if !self.is_enabled() {
+                fs::create_dir_all(&self.dir)
+                    .with_context(|| format!("Failed to create overlay directory: {}", self.dir.display()))?;
+                self.save(&Manifest::default())?;
+            }
+            return Ok(());
+        }
+        if !self.load().entries.is_empty() {
+            bail!("The overlay has unapplied changes; materialize or discard them first");
+        }
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir).context("Failed to remove overlay directory")?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Manifest {
+        fs::read_to_string(self.dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, manifest: &Manifest) -> Result<()> {
+        let json = serde_json::to_string_pretty(manifest)?;
+        fs::write(self.dir.join(MANIFEST_FILE), json).context("Failed to write overlay manifest")
+    }
+
+    fn relative(&self, path: &Path) -> Result<String> {
+        let rel = path
+            .strip_prefix(&self.workspace)
+            .with_context(|| format!("'{}' is outside the workspace the overlay covers", path.display()))?;
+        if rel.starts_with(".carry") {
+            bail!("'{}' is internal to carrycode", path.display());
+        }
+        Ok(rel.to_string_lossy().to_string())
+    }
+
+    fn shadow(&self, rel: &str) -> PathBuf {
+        self.dir.join(FILES_DIR).join(rel)
+    }
+
+    /// Entry for `rel`, or a delete of one of its ancestors
+    fn lookup(manifest: &Manifest, rel: &str) -> Option<OverlayChangeKind> {
+        if let Some(entry) = manifest.entries.get(rel) {
+            return Some(entry.change);
+        }
+        Path::new(rel)
+            .ancestors()
+            .skip(1)
+            .filter_map(|a| manifest.entries.get(a.to_string_lossy().as_ref()))
+            .any(|e| e.change == OverlayChangeKind::Delete)
+            .then_some(OverlayChangeKind::Delete)
+    }
+
+    /// Where the current content of `path` lives
+    pub fn read_path(&self, path: &Path) -> PathBuf {
+        let Ok(rel) = self.relative(path) else {
+            return path.to_path_buf();
+        };
+        match Self::lookup(&self.load(), &rel) {
+            Some(OverlayChangeKind::Write) => self.shadow(&rel),
+            // Never created: reads as missing
+            Some(OverlayChangeKind::Delete) => self.shadow(&rel),
+            Some(OverlayChangeKind::Mkdir) => self.shadow(&rel),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// Shadow file to write instead of `path`, seeded from the workspace on first write
+    pub fn write_path(&self, path: &Path) -> Result<PathBuf> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        let rel = self.relative(path)?;
+        let mut manifest = self.load();
+        let shadow = self.shadow(&rel);
+        let current = Self::lookup(&manifest, &rel);
+        if current != Some(OverlayChangeKind::Write) {
+            if let Some(parent) = shadow.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create overlay directory: {}", parent.display()))?;
+            }
+            if current.is_none() && path.is_file() {
+                fs::copy(path, &shadow).context("Failed to copy file into the overlay")?;
+            }
+            let base_hash = match manifest.entries.get(&rel) {
+                Some(entry) => entry.base_hash,
+                None => fs::read(path).ok().map(|b| content_hash(&b)),
+            };
+            manifest.entries.insert(
+                rel,
+                OverlayEntry {
+                    change: OverlayChangeKind::Write,
+                    base_hash,
+                },
+            );
+            self.save(&manifest)?;
+        }
+        Ok(shadow)
+    }
+
+    /// Record that `path` (a file or directory) is deleted
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        let rel = self.relative(path)?;
+        let mut manifest = self.load();
+        let shadow = self.shadow(&rel);
+        if shadow.is_dir() {
+            fs::remove_dir_all(&shadow).context("Failed to remove overlay copy")?;
+        } else if shadow.exists() {
+            fs::remove_file(&shadow).context("Failed to remove overlay copy")?;
+        }
+        // Changes below a deleted directory go with it
+        let prefix = format!("{}/", rel);
+        manifest.entries.retain(|k, _| !k.starts_with(&prefix));
+        let base_hash = match manifest.entries.get(&rel) {
+            Some(entry) => entry.base_hash,
+            None => fs::read(path).ok().map(|b| content_hash(&b)),
+        };
+        if path.symlink_metadata().is_ok() {
+            manifest.entries.insert(
+                rel,
+                OverlayEntry {
+                    change: OverlayChangeKind::Delete,
+                    base_hash,
+                },
+            );
+        } else {
+            // Only ever existed in the overlay
+            manifest.entries.remove(&rel);
+        }
+        self.save(&manifest)
+    }
+
+    /// Record a new directory
+    pub fn create_dir(&self, path: &Path) -> Result<()> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        let rel = self.relative(path)?;
+        let mut manifest = self.load();
+        fs::create_dir_all(self.shadow(&rel)).context("Failed to create overlay directory")?;
+        manifest.entries.insert(
+            rel,
+            OverlayEntry {
+                change: OverlayChangeKind::Mkdir,
+                base_hash: None,
+            },
+        );
+        self.save(&manifest)
+    }
+
+    fn has_conflict(&self, rel: &str, entry: &OverlayEntry) -> bool {
+        if entry.change == OverlayChangeKind::Mkdir {
+            return false;
+        }
+        let current = fs::read(self.workspace.join(rel)).ok().map(|b| content_hash(&b));
+        let is_dir = self.workspace.join(rel).is_dir();
+        !is_dir && current != entry.base_hash
+    }
+
+    /// Pending changes with diffs against the workspace
+    pub fn changes(&self) -> Vec<OverlayChange> {
+        self.load()
+            .entries
+            .iter()
+            .map(|(rel, entry)| {
+                let diff = (entry.change == OverlayChangeKind::Write)
+                    .then(|| {
+                        let old = fs::read_to_string(self.workspace.join(rel)).unwrap_or_default();
+                        let new = fs::read_to_string(self.shadow(rel)).ok()?;
+                        Some(
+                            similar::TextDiff::from_lines(&old, &new)
+                                .unified_diff()
+                                .header(&format!("a/{}", rel), &format!("b/{}", rel))
+                                .to_string(),
+                        )
+                    })
+                    .flatten();
+                OverlayChange {
+                    path: rel.clone(),
+                    change: entry.change,
+                    diff,
+                    conflict: self.has_conflict(rel, entry),
+                }
+            })
+            .collect()
+    }
+
+    /// Apply the changes to the workspace, leaving conflicting ones in place
+    ///
+    /// Deletes go to the session trash, so they can be restored like the
+    /// delete tool's.
+    pub fn materialize(&self) -> Result<MaterializeReport> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        let mut manifest = self.load();
+        let mut report = MaterializeReport::default();
+        let order = |kind: OverlayChangeKind| match kind {
+            OverlayChangeKind::Delete => 0,
+            OverlayChangeKind::Mkdir => 1,
+            OverlayChangeKind::Write => 2,
+        };
+        let mut pending: Vec<(String, OverlayEntry)> =
+            manifest.entries.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        pending.sort_by_key(|(rel, entry)| (order(entry.change), rel.clone()));
+
+        for (rel, entry) in pending {
+            if self.has_conflict(&rel, &entry) {
+                report.conflicts.push(rel);
+                continue;
+            }
+            let target = self.workspace.join(&rel);
+            match entry.change {
+                OverlayChangeKind::Delete => {
+                    if target.symlink_metadata().is_ok() {
+                        Trash::for_workspace(&self.workspace).put(&self.session_id, &target)?;
+                    }
+                }
+                OverlayChangeKind::Mkdir => {
+                    fs::create_dir_all(&target)
+                        .with_context(|| format!("Failed to create directory: {}", target.display()))?;
+                }
+                OverlayChangeKind::Write => {
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)
+                            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+                    }
+                    fs::copy(self.shadow(&rel), &target)
+                        .with_context(|| format!("Failed to write {}", target.display()))?;
+                }
+            }
+            manifest.entries.remove(&rel);
+            report.applied.push(rel);
+        }
+
+        self.save(&manifest)?;
+        self.prune_files(&manifest)?;
+        Ok(report)
+    }
+
+    /// Drop every pending change; returns how many there were
+    pub fn discard(&self) -> Result<usize> {
+        let _guard = MANIFEST_LOCK.lock().unwrap();
+        let count = self.load().entries.len();
+        self.save(&Manifest::default())?;
+        self.prune_files(&Manifest::default())?;
+        Ok(count)
+    }
+
+    /// Remove shadow files no longer referenced by the manifest
+    fn prune_files(&self, manifest: &Manifest) -> Result<()> {
+        if manifest.entries.is_empty() {
+            let files = self.dir.join(FILES_DIR);
+            if files.exists() {
+                fs::remove_dir_all(&files).context("Failed to clear overlay files")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a file tool should read `path` in the current session
+pub fn read_path(path: &Path) -> PathBuf {
+    match Overlay::current() {
+        Some(overlay) => overlay.read_path(path),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Where a file tool should write `path` in the current session
+pub fn write_path(path: &Path) -> Result<PathBuf> {
+    match Overlay::current() {
+        Some(overlay) => overlay.write_path(path),
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+pub fn is_active() -> bool {
+    Overlay::current().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn shadows_changes_until_materialized() {
+        let workspace = FakeWorkspace::new()
+            .file("src/a.rs", "fn a() {}\n")
+            .file("src/old/b.rs", "fn b() {}\n")
+            .file("c.rs", "fn c() {}\n")
+            .build()
+            .unwrap();
+        let root = workspace.path();
+        let overlay = Overlay::for_session(root, "s1");
+        overlay.set_enabled(true).unwrap();
+
+        let shadow = overlay.write_path(&root.join("src/a.rs")).unwrap();
+        assert_eq!(fs::read_to_string(&shadow).unwrap(), "fn a() {}\n");
+        fs::write(&shadow, "fn a2() {}\n").unwrap();
+        let new_file = overlay.write_path(&root.join("src/new/d.rs")).unwrap();
+        fs::write(new_file, "fn d() {}\n").unwrap();
+        overlay.remove(&root.join("src/old")).unwrap();
+        overlay.write_path(&root.join("c.rs")).unwrap();
+
+        assert_eq!(fs::read_to_string(root.join("src/a.rs")).unwrap(), "fn a() {}\n");
+        assert_eq!(overlay.read_path(&root.join("src/a.rs")), shadow);
+        assert!(!overlay.read_path(&root.join("src/old/b.rs")).exists());
+        assert!(overlay.set_enabled(false).is_err());
+
+        // The workspace copy of c.rs changes underneath the overlay
+        fs::write(root.join("c.rs"), "fn c2() {}\n").unwrap();
+        let changes = overlay.changes();
+        assert_eq!(changes.len(), 4);
+        assert!(changes.iter().find(|c| c.path == "c.rs").unwrap().conflict);
+        assert!(changes[1].diff.as_deref().unwrap().contains("+fn a2() {}"));
+
+        let report = overlay.materialize().unwrap();
+        assert_eq!(report.applied, vec!["src/old", "src/a.rs", "src/new/d.rs"]);
+        assert_eq!(report.conflicts, vec!["c.rs"]);
+        assert_eq!(fs::read_to_string(root.join("src/a.rs")).unwrap(), "fn a2() {}\n");
+        assert_eq!(fs::read_to_string(root.join("src/new/d.rs")).unwrap(), "fn d() {}\n");
+        assert!(!root.join("src/old").exists());
+
+        assert_eq!(overlay.discard().unwrap(), 1);
+        assert_eq!(fs::read_to_string(root.join("c.rs")).unwrap(), "fn c2() {}\n");
+        overlay.set_enabled(false).unwrap();
+        assert!(!overlay.is_enabled());
+    }
+}
@@ -1,56 +1,260 @@
 use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
 use std::path::{Component, Path, PathBuf};
-use crate::llm::utils::tool_access::is_full_access;
+use std::sync::{LazyLock, Mutex};
+use crate::llm::config::AppConfig;
+use crate::llm::utils::tool_access::{current_tool_session, is_full_access};
+
+/// `[security] allowed_roots` of each open session, by session id, so that a
+/// policy can be built without loading the config
+static SESSION_ROOTS: LazyLock<Mutex<HashMap<String, Vec<PathBuf>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Take the allowed roots of `session_id`'s policies from `config`
+pub fn register(session_id: &str, config: &AppConfig) {
+    let roots = configured_extra_roots(config);
+    SESSION_ROOTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(session_id.to_string(), roots);
+}
+
+pub fn unregister(session_id: &str) {
+    SESSION_ROOTS.lock().unwrap_or_else(|e| e.into_inner()).remove(session_id);
+}
+
+fn registered_roots(session_id: &str) -> Option<Vec<PathBuf>> {
+    SESSION_ROOTS.lock().unwrap_or_else(|e| e.into_inner()).get(session_id).cloned()
+}
 
 #[derive(Debug, Clone)]
 pub struct PathPolicy {
     root: PathBuf,
-    root_depth: usize,
+    /// Directories outside the workspace that tools may also use
+    extra_roots: Vec<PathBuf>,
+    /// `root` and `extra_roots` with symlinks resolved
+    real_roots: Vec<PathBuf>,
 }
 
 impl PathPolicy {
+    /// Policy of the executing tool call's session. Outside a registered
+    /// session the config is loaded for the allowed roots.
     pub fn new() -> Result<Self> {
+        match current_tool_session().and_then(|id| registered_roots(&id)) {
+            Some(roots) => Self::workspace(roots),
+            None => Self::from_config(&AppConfig::load()?),
+        }
+    }
+
+    /// Policy of `session_id`, with the allowed roots registered for it
+    pub fn for_session(session_id: &str) -> Result<Self> {
+        match registered_roots(session_id) {
+            Some(roots) => Self::workspace(roots),
+            None => Self::from_config(&AppConfig::load()?),
+        }
+    }
+
+    /// Policy with the allowed roots of an already loaded config
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        Self::workspace(configured_extra_roots(config))
+    }
+
+    /// The current workspace, or the whole file system with full access
+    fn workspace(extra_roots: Vec<PathBuf>) -> Result<Self> {
         if is_full_access() {
-            let root = PathBuf::from("/");
-            return Ok(Self { root, root_depth: 1 });
+            return Ok(Self::with_root(PathBuf::from("/")));
         }
         let root = std::fs::canonicalize(std::env::current_dir()?).context("Failed to determine workspace root")?;
-        let root_depth = root.components().count();
-        Ok(Self { root, root_depth })
+        Ok(Self::with_root(root).with_extra_roots(extra_roots))
     }
 
+    pub fn with_root(root: PathBuf) -> Self {
+        let real_roots = vec![std::fs::canonicalize(&root).unwrap_or_else(|_| root.clone())];
+        Self {
+            root,
+            extra_roots: Vec::new(),
+            real_roots,
+        }
+    }
+
+    /// Also allow absolute paths under these directories
+    pub fn with_extra_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        for root in roots {
+            let root = normalize(&root);
+            self.real_roots
+                .push(std::fs::canonicalize(&root).unwrap_or_else(|_| root.clone()));
+            self.extra_roots.push(root);
+        }
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Absolute form of a workspace path, rejected if it leaves the workspace
+    ///
+    /// Relative paths are taken from the workspace root. `..` is resolved
+    /// lexically, and the longest existing prefix of the result is resolved
+    /// through symlinks, so neither `../` nor a link inside the workspace can
+    /// reach outside it. The returned path keeps the link names as written.
     pub fn resolve(&self, input: &str) -> Result<PathBuf> {
         let requested = Path::new(input);
+        let joined = if requested.is_absolute() {
+            requested.to_path_buf()
+        } else {
+            self.root.join(requested)
+        };
+        let normalized = normalize(&joined);
+
+        if !self.lexically_allowed(&normalized) {
+            bail!(
+                "Path '{}' is outside the workspace '{}'",
+                requested.display(),
+                self.root.display()
+            );
+        }
 
-        let mut components: Vec<Component> = if requested.is_absolute() {
-            if let Ok(stripped) = requested.strip_prefix(&self.root) {
-                stripped.components().collect()
+        let real = real_path(&normalized, true);
+        if !self.real_roots.iter().any(|root| real.starts_with(root)) {
+            bail!(
+                "Path '{}' resolves through a symlink to '{}', outside the workspace '{}'",
+                requested.display(),
+                real.display(),
+                self.root.display()
+            );
+        }
+
+        Ok(normalized)
+    }
+
+    fn lexically_allowed(&self, path: &Path) -> bool {
+        path.starts_with(&self.root) || self.extra_roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+/// Whether `path` is a `.git` directory or inside one; the file tools neither
+/// delete nor move repository metadata
+pub fn is_git_metadata(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == ".git")
+}
+
+/// `[security] allowed_roots`, absolute and with `~` expanded
+fn configured_extra_roots(config: &AppConfig) -> Vec<PathBuf> {
+    config
+        .security
+        .allowed_roots
+        .iter()
+        .filter_map(|root| {
+            let path = match root.strip_prefix("~/") {
+                Some(rest) => dirs::home_dir()?.join(rest),
+                None => PathBuf::from(root),
+            };
+            if path.is_absolute() {
+                Some(path)
             } else {
-                bail!(
-                    "Path '{}' is outside the workspace '{}'",
-                    requested.display(),
-                    self.root.display()
-                );
+                log::warn!("Ignoring relative allowed root '{}'", root);
+                None
             }
-        } else {
-            requested.components().collect()
-        };
+        })
+        .collect()
+}
 
-        let mut normalized = self.root.clone();
-
-        for comp in components.drain(..) {
-            match comp {
-                Component::CurDir => {}
-                Component::ParentDir => {
-                    if normalized.components().count() > self.root_depth {
-                        normalized.pop();
-                    }
-                }
-                Component::Normal(c) => normalized.push(c),
-                _ => {}
+/// Resolve `.` and `..` without touching the file system; `..` at `/` stays at `/`
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
             }
+            other => out.push(other),
         }
+    }
+    out
+}
 
-        Ok(normalized)
+/// Canonical form of the longest existing prefix, with the rest appended
+///
+/// A dangling symlink on the way is followed to its target once, so creating
+/// a file through it cannot land outside the workspace.
+fn real_path(path: &Path, follow_dangling: bool) -> PathBuf {
+    let mut existing = path.to_path_buf();
+    let mut rest: Vec<std::ffi::OsString> = Vec::new();
+    loop {
+        let resolved = match std::fs::canonicalize(&existing) {
+            Ok(real) => Some(real),
+            Err(_) if follow_dangling => std::fs::read_link(&existing).ok().map(|target| {
+                let parent = existing.parent().map(Path::to_path_buf).unwrap_or_default();
+                real_path(&normalize(&parent.join(target)), false)
+            }),
+            Err(_) => None,
+        };
+        if let Some(mut real) = resolved {
+            for part in rest.iter().rev() {
+                real.push(part);
+            }
+            return real;
+        }
+        match (existing.file_name().map(|n| n.to_os_string()), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                rest.push(name);
+                existing = parent.to_path_buf();
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeWorkspace, TempWorkspace};
+    use std::os::unix::fs::symlink;
+
+    /// A workspace at `ws` with a `src` directory, next to an `outside` directory holding a secret
+    fn workspace() -> (TempWorkspace, PathBuf, PathBuf) {
+        let base = FakeWorkspace::new().dir("ws/src").file("outside/secret.txt", "x").build().unwrap();
+        let (root, outside) = (base.join("ws"), base.join("outside"));
+        (base, root, outside)
+    }
+
+    #[test]
+    fn rejects_parent_dir_escapes() {
+        let (_base, root, outside) = workspace();
+        let policy = PathPolicy::with_root(root.clone());
+
+        assert_eq!(policy.resolve("src/../src/./a.rs").unwrap(), root.join("src/a.rs"));
+        assert_eq!(policy.resolve(&root.join("src/../b.rs").to_string_lossy()).unwrap(), root.join("b.rs"));
+        assert!(policy.resolve("../outside/secret.txt").is_err());
+        assert!(policy.resolve("src/../../ws/../outside").is_err());
+        assert!(policy.resolve(&outside.join("secret.txt").to_string_lossy()).is_err());
+        // A sibling sharing the root's name as a prefix is not inside it
+        assert!(policy.resolve(&format!("{}-other/x", root.display())).is_err());
+    }
+
+    #[test]
+    fn rejects_symlinks_leading_out_of_the_workspace() {
+        let (_base, root, outside) = workspace();
+        symlink(&outside, root.join("link_dir")).unwrap();
+        symlink(outside.join("secret.txt"), root.join("link_file")).unwrap();
+        symlink(outside.join("new.txt"), root.join("dangling")).unwrap();
+        symlink(root.join("src"), root.join("inner_link")).unwrap();
+        let policy = PathPolicy::with_root(root.clone());
+
+        assert!(policy.resolve("link_dir/secret.txt").is_err());
+        assert!(policy.resolve("link_dir/not_yet/created.txt").is_err());
+        assert!(policy.resolve("link_file").is_err());
+        assert!(policy.resolve("dangling").is_err());
+        assert_eq!(policy.resolve("inner_link/a.rs").unwrap(), root.join("inner_link/a.rs"));
+        assert_eq!(policy.resolve("src/new_dir/new.rs").unwrap(), root.join("src/new_dir/new.rs"));
+
+        let shared = PathPolicy::with_root(root.clone()).with_extra_roots(vec![outside.clone()]);
+        assert!(shared.resolve("link_dir/secret.txt").is_ok());
+        assert_eq!(
+            shared.resolve(&outside.join("secret.txt").to_string_lossy()).unwrap(),
+            outside.join("secret.txt")
+        );
+        assert!(shared.resolve("../elsewhere").is_err());
     }
 }
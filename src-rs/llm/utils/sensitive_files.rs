@@ -0,0 +1,178 @@
+//! Files whose contents the read tools keep from the model unless the user
+//! confirms: `.env` files, private keys, cloud credentials.
+//!
+//! `[security] sensitive_files` lists them as gitignore-style patterns. A
+//! pattern without a slash matches a file name at any depth, one with a slash
+//! matches from the filesystem root (so `**/` is usually wanted), a trailing
+//! `/` covers a whole directory, and `!` exempts files an earlier pattern
+//! matched. `view` of such a file and `fetch` of a URL naming one ask for
+//! confirmation first; `grep` reports their matching lines as `REDACTED`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::llm::config::AppConfig;
+use crate::llm::utils::tool_access::current_tool_session;
+
+/// Stands in for the contents of a sensitive file
+pub const REDACTED: &str = "[redacted: sensitive file]";
+
+/// Matcher of each open session, by session id, built once from its config
+static SESSION_FILES: LazyLock<Mutex<HashMap<String, Arc<SensitiveFiles>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Build `session_id`'s matcher from the patterns in `config`
+pub fn register(session_id: &str, config: &AppConfig) {
+    let files = Arc::new(SensitiveFiles::new(&config.security.sensitive_files));
+    SESSION_FILES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(session_id.to_string(), files);
+}
+
+pub fn unregister(session_id: &str) {
+    SESSION_FILES.lock().unwrap_or_else(|e| e.into_inner()).remove(session_id);
+}
+
+pub fn default_patterns() -> Vec<String> {
+    [
+        ".env*",
+        "!.env.example",
+        "!.env.sample",
+        "!.env.template",
+        "*.pem",
+        "*.key",
+        "*.p12",
+        "*.pfx",
+        "id_rsa*",
+        "id_dsa*",
+        "id_ecdsa*",
+        "id_ed25519*",
+        "!id_*.pub",
+        ".netrc",
+        ".pgpass",
+        "**/.aws/credentials",
+        "**/.azure/accessTokens.json",
+        "**/.azure/msal_token_cache.*",
+        "**/.config/gcloud/application_default_credentials.json",
+        "**/.config/gcloud/credentials.db",
+        "**/.docker/config.json",
+        "**/.kube/config",
+    ]
+    .iter()
+    .map(|p| p.to_string())
+    .collect()
+}
+
+pub struct SensitiveFiles(Gitignore);
+
+impl SensitiveFiles {
+    pub fn new(patterns: &[String]) -> Self {
+        let mut builder = GitignoreBuilder::new("/");
+        for pattern in patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                log::warn!("Ignoring sensitive file pattern '{}': {}", pattern, e);
+            }
+        }
+        Self(builder.build().unwrap_or_else(|e| {
+            log::warn!("Invalid sensitive file patterns, treating no file as sensitive: {}", e);
+            Gitignore::empty()
+        }))
+    }
+
+    /// Matcher of the executing tool call's session; outside a registered
+    /// session the config is loaded for the patterns
+    pub fn current() -> Arc<Self> {
+        let registered = current_tool_session()
+            .and_then(|id| SESSION_FILES.lock().unwrap_or_else(|e| e.into_inner()).get(&id).cloned());
+        registered.unwrap_or_else(|| Arc::new(Self::load()))
+    }
+
+    /// The configured patterns, or the defaults when the config cannot be read
+    pub fn load() -> Self {
+        let patterns = AppConfig::load()
+            .map(|config| config.security.sensitive_files)
+            .unwrap_or_else(|_| default_patterns());
+        Self::new(&patterns)
+    }
+
+    /// Whether `path`, relative to the working directory or absolute, is or
+    /// lies in a sensitive file or directory
+    pub fn is_sensitive(&self, path: &Path) -> bool {
+        let path = absolute(path);
+        self.0.matched_path_or_any_parents(&path, path.is_dir()).is_ignore()
+    }
+
+    /// Whether the path of `url` names a sensitive file, as `http://host/.env` does
+    pub fn is_sensitive_url(&self, url: &str) -> bool {
+        let Ok(url) = url::Url::parse(url) else {
+            return false;
+        };
+        self.0.matched_path_or_any_parents(Path::new(url.path()), false).is_ignore()
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let path = path.strip_prefix(".").unwrap_or(path);
+    cwd.join(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_patterns_cover_secrets_but_not_examples() {
+        let files = SensitiveFiles::new(&default_patterns());
+        for path in [
+            "/work/app/.env",
+            "/work/app/config/.env.production",
+            "/work/app/certs/server.pem",
+            "/home/dev/.ssh/id_ed25519",
+            "/home/dev/.aws/credentials",
+            "/home/dev/.kube/config",
+        ] {
+            assert!(files.is_sensitive(Path::new(path)), "{}", path);
+        }
+        for path in [
+            "/work/app/.env.example",
+            "/work/app/src/env.rs",
+            "/home/dev/.ssh/id_ed25519.pub",
+            "/work/app/aws/credentials",
+        ] {
+            assert!(!files.is_sensitive(Path::new(path)), "{}", path);
+        }
+
+        assert!(files.is_sensitive_url("http://localhost:3000/.env"));
+        assert!(files.is_sensitive_url("https://example.com/keys/deploy.pem?raw=1"));
+        assert!(!files.is_sensitive_url("https://example.com/docs/env"));
+
+        let custom = SensitiveFiles::new(&["secrets/".to_string(), "*.tfstate".to_string()]);
+        assert!(custom.is_sensitive(Path::new("/work/app/secrets/db.txt")));
+        assert!(custom.is_sensitive(Path::new("/work/app/infra/prod.tfstate")));
+        assert!(!custom.is_sensitive(Path::new("/work/app/.env")));
+    }
+
+    #[test]
+    fn sessions_use_the_patterns_they_were_registered_with() {
+        let mut config: AppConfig = toml::from_str(include_str!("../../../Config.toml")).unwrap();
+        config.security.sensitive_files = vec!["*.tfstate".to_string()];
+        register("sensitive-files-test", &config);
+        let prod = Path::new("/work/app/prod.tfstate");
+
+        let files = crate::llm::utils::tool_access::with_tool_session("sensitive-files-test", SensitiveFiles::current);
+        assert!(files.is_sensitive(prod));
+        assert!(!files.is_sensitive(Path::new("/work/app/.env")));
+
+        unregister("sensitive-files-test");
+        let files = crate::llm::utils::tool_access::with_tool_session("sensitive-files-test", SensitiveFiles::current);
+        assert!(!files.is_sensitive(prod));
+    }
+}
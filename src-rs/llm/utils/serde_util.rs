@@ -1,3 +1,4 @@
+use crate::llm::tools::lenient_args::parse_bool;
 use serde::{Deserialize, Deserializer};
 
 pub fn deserialize_usize_lax<'de, D>(deserializer: D) -> Result<usize, D::Error>
@@ -91,14 +92,8 @@ where
 
     match Wrapper::deserialize(deserializer)? {
         Wrapper::Bool(b) => Ok(b),
-        Wrapper::Str(s) => match s.to_lowercase().as_str() {
-            "true" | "yes" | "1" => Ok(true),
-            "false" | "no" | "0" => Ok(false),
-            _ => Err(serde::de::Error::custom(format!(
-                "invalid boolean string: {}",
-                s
-            ))),
-        },
+        Wrapper::Str(s) => parse_bool(&s)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid boolean string: {}", s))),
     }
 }
 
@@ -0,0 +1,132 @@
+//! Variables a host sets on a session, e.g. a ticket id or a feature flag.
+//!
+//! The session keeps them with its snapshot; this registry holds a copy per
+//! session for the tools, which only know the session of the executing call.
+//! `{{NAME}}` in the mode's system prompt is replaced by the value of `NAME`,
+//! bash commands run with them in their environment, and stdio MCP servers
+//! started for the session get them too (a server already running keeps the
+//! environment it was started with).
+
+use crate::llm::tools::bash::shell_quote;
+use crate::llm::utils::tool_access::current_tool_session;
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{LazyLock, Mutex};
+
+/// Variables per session
+pub const MAX_VARS: usize = 50;
+const MAX_NAME_LEN: usize = 64;
+pub const MAX_VALUE_CHARS: usize = 4_000;
+
+/// Variables the shell and the tools rely on, which a session may not override
+const RESERVED: &[&str] = &[
+    "PATH", "HOME", "USER", "SHELL", "PWD", "OLDPWD", "SHLVL", "IFS", "TERM", "LANG", "TMPDIR", "GIT_EDITOR",
+    "BASH_ENV", "ENV", "PROMPT_COMMAND", "LD_PRELOAD", "LD_LIBRARY_PATH", "DYLD_INSERT_LIBRARIES",
+];
+
+pub type SessionVars = BTreeMap<String, String>;
+
+static VARS: LazyLock<Mutex<HashMap<String, SessionVars>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `name` can be a session variable: a shell variable name that is not reserved
+pub fn validate_name(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name.len() <= MAX_NAME_LEN;
+    if !valid {
+        bail!(
+            "Invalid variable name '{}': use letters, digits and _ (at most {} characters), not starting with a digit",
+            name,
+            MAX_NAME_LEN
+        );
+    }
+    if RESERVED.contains(&name.to_ascii_uppercase().as_str()) || name.starts_with("CARRYCODE_") {
+        bail!("Variable '{}' is reserved", name);
+    }
+    Ok(())
+}
+
+/// Make `vars` the variables of `session_id`'s tool calls
+pub fn register(session_id: &str, vars: SessionVars) {
+    let mut registry = VARS.lock().unwrap();
+    if vars.is_empty() {
+        registry.remove(session_id);
+    } else {
+        registry.insert(session_id.to_string(), vars);
+    }
+}
+
+pub fn unregister(session_id: &str) {
+    VARS.lock().unwrap().remove(session_id);
+}
+
+pub fn for_session(session_id: &str) -> SessionVars {
+    VARS.lock().unwrap().get(session_id).cloned().unwrap_or_default()
+}
+
+/// Variables of the executing tool call's session
+pub fn current() -> SessionVars {
+    current_tool_session().map(|id| for_session(&id)).unwrap_or_default()
+}
+
+/// `template` with each `{{NAME}}` of a set variable replaced by its value;
+/// other braces are left alone
+pub fn interpolate(template: &str, vars: &SessionVars) -> String {
+    if vars.is_empty() {
+        return template.to_string();
+    }
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}").and_then(|end| vars.get(after[..end].trim()).map(|value| (end, value))) {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Shell lines exporting `vars`
+pub fn export_script(vars: &SessionVars) -> String {
+    vars.iter()
+        .map(|(name, value)| format!("export {}={}\n", name, shell_quote(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_checked_and_values_interpolated() {
+        assert!(validate_name("TICKET_ID").is_ok());
+        assert!(validate_name("_flag2").is_ok());
+        assert!(validate_name("2FA").is_err());
+        assert!(validate_name("A-B").is_err());
+        assert!(validate_name("path").is_err());
+        assert!(validate_name("CARRYCODE_NOTIFY_MESSAGE").is_err());
+
+        let vars: SessionVars = [("TICKET".to_string(), "PROJ-42".to_string())].into();
+        assert_eq!(
+            interpolate("Work on {{TICKET}} ({{ TICKET }}); keep {{OTHER}} and {{ as is", &vars),
+            "Work on PROJ-42 (PROJ-42); keep {{OTHER}} and {{ as is"
+        );
+        let quoted: SessionVars = [("NAME".to_string(), "it's".to_string())].into();
+        assert_eq!(export_script(&quoted), "export NAME='it'\\''s'\n");
+
+        register("vars-test", vars.clone());
+        assert_eq!(for_session("vars-test"), vars);
+        register("vars-test", SessionVars::new());
+        assert!(for_session("vars-test").is_empty());
+    }
+}
@@ -0,0 +1,669 @@
+//! Classification of shell commands for the bash tool's confirmation policy.
+//!
+//! The command line is split into simple commands along `;`, `&&`, `||`, `|`,
+//! `&` and newlines, with quoting, comments and here-documents honoured.
+//! Command and process substitutions are parsed recursively, and output
+//! redirections are collected so writes outside the workspace can be caught.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Commands that run another command given as their arguments. `nohup` is
+/// not one: it writes `nohup.out` and leaves its command running, so it is
+/// rated as a command of its own.
+const WRAPPERS: &[&str] = &["time", "nice", "timeout", "env", "command"];
+
+/// Arguments that make an otherwise read-only command change state. A short
+/// option also matches with its value attached, as in `-Ovim`.
+const MUTATING_ARGS: &[(&str, &[&str])] = &[
+    ("git branch", &["-d", "-D", "-m", "-M", "-c", "-C", "-f", "--delete", "--move", "--copy", "--force", "--set-upstream-to", "-u"]),
+    ("git tag", &["-d", "-f", "-a", "-s", "--delete", "--force"]),
+    ("git remote", &["add", "remove", "rm", "rename", "set-url", "set-head", "prune", "update"]),
+    ("git diff", &["--output"]),
+    ("git log", &["--output"]),
+    ("git show", &["--output"]),
+    ("git grep", &["-O", "--open-files-in-pager"]),
+    ("date", &["-s", "--set"]),
+];
+
+/// Commands that create a ref named by a positional argument
+const CREATES_FROM_POSITIONAL: &[&str] = &["git branch", "git tag"];
+
+/// Options that make those commands list refs, taking positional arguments as
+/// patterns or option values instead
+const LIST_MODE_ARGS: &[&str] = &["-l", "--list", "--contains", "--no-contains", "--merged", "--no-merged", "--points-at"];
+
+/// Redirection targets that are not files
+const HARMLESS_TARGETS: &[&str] = &["/dev/null", "/dev/stdout", "/dev/stderr", "/dev/tty"];
+
+/// How much a command can do, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CommandRisk {
+    /// Only commands from the read-only list, writing nothing
+    ReadOnly,
+    /// Runs other commands or writes inside the workspace
+    Mutating,
+    /// Writes to a path outside the workspace, or one that cannot be resolved
+    Escaping,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandAssessment {
+    pub risk: CommandRisk,
+    /// Why the command is not read-only
+    pub reasons: Vec<String>,
+    /// Name of every simple command, substitutions included
+    pub commands: Vec<String>,
+}
+
+/// A command line split into simple commands
+#[derive(Debug, Default)]
+struct ParsedCommand {
+    /// Words of each simple command, leading assignments included
+    segments: Vec<Vec<String>>,
+    /// Targets of output redirections
+    writes: Vec<String>,
+    /// Bodies of `$(...)`, backtick and `<(...)`/`>(...)` substitutions
+    substitutions: Vec<String>,
+    /// False when a quote or substitution is left open
+    complete: bool,
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    out: &'a mut ParsedCommand,
+    words: Vec<String>,
+    word: String,
+    /// Whether `word` has started, so `""` still counts as a word
+    in_word: bool,
+    /// Redirection whose target is the next word: (writes, heredoc)
+    pending_redirect: Option<(bool, bool)>,
+    heredocs: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn finish_word(&mut self) {
+        if !self.in_word {
+            return;
+        }
+        let word = std::mem::take(&mut self.word);
+        self.in_word = false;
+        match self.pending_redirect.take() {
+            Some((_, true)) => self.heredocs.push(word),
+            Some((true, false)) => self.out.writes.push(word),
+            Some((false, false)) => {}
+            None => self.words.push(word),
+        }
+    }
+
+    fn finish_segment(&mut self) {
+        self.finish_word();
+        let words = std::mem::take(&mut self.words);
+        if !words.is_empty() {
+            self.out.segments.push(words);
+        }
+    }
+
+    /// Text up to the `close` matching an already consumed opener, tracking nesting and quotes
+    fn take_until_closing(&mut self, open: char, close: char) -> Option<String> {
+        let mut depth = 1;
+        let mut body = String::new();
+        let mut quote: Option<char> = None;
+        while let Some(c) = self.peek(0) {
+            self.pos += 1;
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') | (None, '\\') => {
+                    body.push(c);
+                    if let Some(next) = self.peek(0) {
+                        body.push(next);
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                (Some(_), _) => {}
+                (None, '\'') | (None, '"') => quote = Some(c),
+                (None, c) if c == open => depth += 1,
+                (None, c) if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(body);
+                    }
+                }
+                _ => {}
+            }
+            body.push(c);
+        }
+        None
+    }
+
+    /// Skip here-document bodies after the newline that ends their command
+    fn skip_heredocs(&mut self) {
+        for delimiter in std::mem::take(&mut self.heredocs) {
+            let delimiter = delimiter.trim_matches(|c| c == '\'' || c == '"');
+            loop {
+                if self.pos >= self.chars.len() {
+                    return;
+                }
+                let start = self.pos;
+                while self.peek(0).is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+                let line: String = self.chars[start..self.pos].iter().collect();
+                self.pos += 1;
+                if line.trim_start_matches('\t') == delimiter {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// `$(`, `<(`, `>(` or a backtick at the cursor: parse the body as its own command
+    fn substitution(&mut self) -> bool {
+        let (skip, open, close) = match (self.peek(0), self.peek(1), self.peek(2)) {
+            (Some('$'), Some('('), Some('(')) => {
+                // Arithmetic expansion: no commands inside
+                self.pos += 3;
+                let body = self.take_until_closing('(', ')');
+                if body.is_none() || self.peek(0) != Some(')') {
+                    self.out.complete = false;
+                }
+                self.pos += 1;
+                self.word.push('0');
+                self.in_word = true;
+                return true;
+            }
+            (Some('$'), Some('('), _) | (Some('<'), Some('('), _) | (Some('>'), Some('('), _) => (2, '(', ')'),
+            (Some('`'), _, _) => (1, '`', '`'),
+            _ => return false,
+        };
+        self.pos += skip;
+        let body = if open == '`' {
+            let start = self.pos;
+            while self.peek(0).is_some_and(|c| c != '`') {
+                self.pos += 1;
+            }
+            let body: Option<String> =
+                (self.pos < self.chars.len()).then(|| self.chars[start..self.pos].iter().collect());
+            self.pos += 1;
+            body
+        } else {
+            self.take_until_closing(open, close)
+        };
+        match body {
+            Some(body) => self.out.substitutions.push(body),
+            None => self.out.complete = false,
+        }
+        // Stands for the expansion in the enclosing word
+        self.word.push('$');
+        self.in_word = true;
+        true
+    }
+
+    fn parse(&mut self) {
+        let mut quote: Option<char> = None;
+        while let Some(c) = self.peek(0) {
+            if let Some(q) = quote {
+                if c == q {
+                    quote = None;
+                    self.pos += 1;
+                } else if !(q == '"' && (c == '$' || c == '`') && self.substitution()) {
+                    if q == '"' && c == '\\' {
+                        self.pos += 1;
+                    }
+                    if let Some(c) = self.peek(0) {
+                        self.word.push(c);
+                    }
+                    self.pos += 1;
+                }
+                continue;
+            }
+
+            match c {
+                '\'' | '"' => {
+                    quote = Some(c);
+                    self.in_word = true;
+                    self.pos += 1;
+                }
+                '\\' => {
+                    self.pos += 1;
+                    match self.peek(0) {
+                        Some('\n') => {}
+                        Some(next) => {
+                            self.word.push(next);
+                            self.in_word = true;
+                        }
+                        None => {}
+                    }
+                    self.pos += 1;
+                }
+                '#' if !self.in_word => {
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                '$' | '`' if self.substitution() => {}
+                '<' | '>' if self.peek(1) == Some('(') && self.substitution() => {}
+                '<' | '>' => self.redirection(c),
+                '&' if self.peek(1) == Some('>') => {
+                    self.finish_word();
+                    self.pos += 1;
+                    self.redirection('>');
+                }
+                ';' | '&' | '|' | '\n' | '(' | ')' => {
+                    self.finish_segment();
+                    self.pos += 1;
+                    if c == '\n' {
+                        self.skip_heredocs();
+                    }
+                    // `&&`, `||`, `|&`, `;;`
+                    if matches!(self.peek(0), Some('&' | '|' | ';')) && c != '\n' && c != '(' && c != ')' {
+                        self.pos += 1;
+                    }
+                }
+                c if c.is_whitespace() => {
+                    self.finish_word();
+                    self.pos += 1;
+                }
+                _ => {
+                    self.word.push(c);
+                    self.in_word = true;
+                    self.pos += 1;
+                }
+            }
+        }
+        if quote.is_some() {
+            self.out.complete = false;
+        }
+        self.finish_segment();
+    }
+
+    /// Redirection operator at the cursor; a file descriptor number may already be in `word`
+    fn redirection(&mut self, c: char) {
+        if self.in_word && self.word.chars().all(|d| d.is_ascii_digit()) {
+            self.word.clear();
+            self.in_word = false;
+        } else {
+            self.finish_word();
+        }
+        self.pos += 1;
+        let writes = c == '>';
+        let mut heredoc = false;
+        match self.peek(0) {
+            Some('>') | Some('|') if writes => self.pos += 1,
+            Some('&') => {
+                self.pos += 1;
+                // Descriptor duplication such as `2>&1`: no file involved;
+                // `>&file` is the same as `&>file`
+                if self.peek(0).is_some_and(|d| d.is_ascii_digit() || d == '-') {
+                    while self.peek(0).is_some_and(|d| d.is_ascii_digit() || d == '-') {
+                        self.pos += 1;
+                    }
+                    return;
+                }
+            }
+            Some('<') if !writes => {
+                self.pos += 1;
+                if self.peek(0) == Some('<') {
+                    // Here-string: the word is input, not a file
+                    self.pos += 1;
+                } else {
+                    heredoc = true;
+                    if self.peek(0) == Some('-') {
+                        self.pos += 1;
+                    }
+                }
+            }
+            Some('>') if !writes => self.pos += 1,
+            _ => {}
+        }
+        while self.peek(0).is_some_and(|c| c == ' ' || c == '\t') {
+            self.pos += 1;
+        }
+        self.pending_redirect = Some((writes, heredoc));
+    }
+}
+
+fn parse(command: &str) -> ParsedCommand {
+    let mut out = ParsedCommand {
+        complete: true,
+        ..Default::default()
+    };
+    let mut parser = Parser {
+        chars: command.chars().collect(),
+        pos: 0,
+        out: &mut out,
+        words: Vec::new(),
+        word: String::new(),
+        in_word: false,
+        pending_redirect: None,
+        heredocs: Vec::new(),
+    };
+    parser.parse();
+    out
+}
+
+/// Words of the command a segment actually runs: leading assignments and
+/// wrappers such as `timeout 5` or `env -i` are stripped
+fn effective_words(words: &[String]) -> &[String] {
+    let is_assignment = |w: &String| {
+        w.split_once('=').is_some_and(|(name, _)| {
+            !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+        })
+    };
+    let mut rest = words;
+    while rest.first().is_some_and(is_assignment) {
+        rest = &rest[1..];
+    }
+    while let Some(first) = rest.first() {
+        if !WRAPPERS.contains(&first.as_str()) || rest.len() == 1 {
+            break;
+        }
+        let wrapper = first.as_str();
+        rest = &rest[1..];
+        while let Some(arg) = rest.first() {
+            let takes_value = matches!((wrapper, arg.as_str()), ("nice", "-n") | ("timeout", "-s" | "-k" | "--signal" | "--kill-after") | ("env", "-u" | "-C" | "-S"));
+            if arg.starts_with('-') || (wrapper == "env" && is_assignment(arg)) {
+                rest = &rest[if takes_value { 2 } else { 1 }.min(rest.len())..];
+            } else {
+                break;
+            }
+        }
+        if wrapper == "timeout" && !rest.is_empty() {
+            // The duration
+            rest = &rest[1..];
+        }
+    }
+    rest
+}
+
+/// Whether the words start with the words of a read-only list entry
+fn matches_entry(words: &[String], entry: &str) -> bool {
+    let entry: Vec<&str> = entry.split_whitespace().collect();
+    !entry.is_empty() && words.len() >= entry.len() && words.iter().zip(&entry).all(|(w, e)| w == e)
+}
+
+/// Whether `word` is the option or argument `arg`, with or without a value
+fn matches_arg(word: &str, arg: &str) -> bool {
+    if arg.starts_with("--") {
+        word == arg || word.strip_prefix(arg).is_some_and(|rest| rest.starts_with('='))
+    } else if arg.starts_with('-') {
+        word.starts_with(arg)
+    } else {
+        word == arg
+    }
+}
+
+fn has_mutating_args(words: &[String]) -> bool {
+    let args_of = |entry: &str| &words[entry.split_whitespace().count().min(words.len())..];
+    let mutating_arg = MUTATING_ARGS.iter().any(|(entry, args)| {
+        matches_entry(words, entry) && args_of(entry).iter().any(|w| args.iter().any(|a| matches_arg(w, a)))
+    });
+    let creates_ref = CREATES_FROM_POSITIONAL.iter().any(|entry| {
+        let args = args_of(entry);
+        matches_entry(words, entry)
+            && args.iter().any(|w| !w.starts_with('-'))
+            && !args.iter().any(|w| LIST_MODE_ARGS.iter().any(|a| matches_arg(w, a)))
+    });
+    mutating_arg || creates_ref
+}
+
+/// The path an argument names when it is clearly one: absolute, from the
+/// home directory, up from the working directory or built from an expansion,
+/// alone or as an `--option=` value
+fn path_argument(word: &str) -> Option<&str> {
+    let value = match word.split_once('=') {
+        Some((option, value)) if option.starts_with('-') => value,
+        _ => word,
+    };
+    let is_path = value.starts_with('/')
+        || value.starts_with('~')
+        || value == ".."
+        || value.starts_with("../")
+        || value.contains(['$', '`']);
+    is_path.then_some(value)
+}
+
+/// Whether writing to `target` from `cwd` stays inside `root`
+///
+/// Targets with parameter expansion, a backtick or `~user` depend on runtime
+/// values and count as escaping.
+fn writes_inside(root: &Path, cwd: &Path, target: &str) -> bool {
+    if HARMLESS_TARGETS.contains(&target) {
+        return true;
+    }
+    if target.contains(['$', '`']) || target.is_empty() {
+        return false;
+    }
+    let path = if target == "~" || target.starts_with("~/") {
+        match dirs::home_dir() {
+            Some(home) => home.join(target.trim_start_matches('~').trim_start_matches('/')),
+            None => return false,
+        }
+    } else if target.starts_with('~') {
+        return false;
+    } else {
+        cwd.join(target)
+    };
+    normalize(&path).starts_with(root)
+}
+
+/// `path` with `.` and `..` components folded away
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Classify a command line against the read-only command list; relative
+/// paths are resolved from `cwd`, the directory the shell will run it in
+pub fn assess(command: &str, safe_commands: &[String], root: &Path, cwd: &Path) -> CommandAssessment {
+    let mut assessment = CommandAssessment {
+        risk: CommandRisk::ReadOnly,
+        reasons: Vec::new(),
+        commands: Vec::new(),
+    };
+    let cwd = normalize(&root.join(cwd));
+    assess_into(command, safe_commands, root, &cwd, &mut assessment, 0);
+    assessment
+}
+
+fn raise(out: &mut CommandAssessment, risk: CommandRisk, reason: String) {
+    out.risk = out.risk.max(risk);
+    if !out.reasons.contains(&reason) {
+        out.reasons.push(reason);
+    }
+}
+
+fn assess_into(
+    command: &str,
+    safe_commands: &[String],
+    root: &Path,
+    cwd: &Path,
+    out: &mut CommandAssessment,
+    depth: usize,
+) {
+    let parsed = parse(command);
+    if !parsed.complete {
+        raise(out, CommandRisk::Mutating, "unterminated quote or substitution".to_string());
+    }
+
+    for segment in &parsed.segments {
+        let words = effective_words(segment);
+        let Some(name) = words.first() else {
+            continue;
+        };
+        if name == "{" || name == "}" {
+            continue;
+        }
+        out.commands.push(name.clone());
+        let read_only = safe_commands.iter().any(|entry| matches_entry(words, entry)) && !has_mutating_args(words);
+        if !read_only {
+            raise(out, CommandRisk::Mutating, format!("runs '{}'", name));
+            if !cwd.starts_with(root) {
+                raise(out, CommandRisk::Escaping, format!("'{}' runs outside the workspace in {}", name, cwd.display()));
+            }
+            for path in words[1..].iter().filter_map(|word| path_argument(word)) {
+                if !writes_inside(root, cwd, path) {
+                    raise(out, CommandRisk::Escaping, format!("'{}' acts outside the workspace: {}", name, path));
+                }
+            }
+        }
+    }
+
+    for target in &parsed.writes {
+        if writes_inside(root, cwd, target) {
+            if !HARMLESS_TARGETS.contains(&target.as_str()) {
+                raise(out, CommandRisk::Mutating, format!("writes to {}", target));
+            }
+        } else {
+            raise(out, CommandRisk::Escaping, format!("writes outside the workspace: {}", target));
+        }
+    }
+
+    for body in &parsed.substitutions {
+        if depth >= 8 {
+            raise(out, CommandRisk::Mutating, "deeply nested substitution".to_string());
+            continue;
+        }
+        assess_into(body, safe_commands, root, cwd, out, depth + 1);
+    }
+}
+
+/// Whether every simple command in `command`, substitutions included, matches
+/// one of `patterns` and nothing is written to a file. A pattern ending in
+/// `:*` matches commands that start with its words, any other pattern the
+/// whole command. Leading assignments are part of the command, so they have
+/// to match too.
+pub fn matches_patterns(command: &str, patterns: &[&str]) -> bool {
+    matches_patterns_at(command, patterns, 0)
+}
+
+fn matches_patterns_at(command: &str, patterns: &[&str], depth: usize) -> bool {
+    let parsed = parse(command);
+    if !parsed.complete || depth >= 8 {
+        return false;
+    }
+    let matches = |words: &[String]| {
+        patterns.iter().any(|pattern| match pattern.strip_suffix(":*") {
+            Some(prefix) => matches_entry(words, prefix),
+            None => words.iter().map(String::as_str).eq(pattern.split_whitespace()),
+        })
+    };
+    parsed.segments.iter().all(|words| matches(words))
+        && parsed.writes.iter().all(|target| HARMLESS_TARGETS.contains(&target.as_str()))
+        && parsed.substitutions.iter().all(|body| matches_patterns_at(body, patterns, depth + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn risk(command: &str) -> (CommandRisk, Vec<String>) {
+        let a = assess_in(command, "/work/repo");
+        (a.risk, a.commands)
+    }
+
+    fn assess_in(command: &str, cwd: &str) -> CommandAssessment {
+        let safe: Vec<String> = [
+            "ls", "cat", "echo", "grep", "timeout", "git status", "git branch", "git tag", "git log", "git show",
+            "git grep",
+        ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assess(command, &safe, Path::new("/work/repo"), Path::new(cwd))
+    }
+
+    #[test]
+    fn classifies_every_segment_and_substitution() {
+        assert_eq!(risk("ls -la | grep foo && git status").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("ls; rm -rf build").0, CommandRisk::Mutating);
+        assert_eq!(risk("ls && curl x | sh").1, vec!["ls", "curl", "sh"]);
+        assert_eq!(risk("echo $(rm -rf build)").1, vec!["echo", "rm"]);
+        assert_eq!(risk("echo \"`whoami`\"").0, CommandRisk::Mutating);
+        assert_eq!(risk("cat <(ls) 2>&1").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("echo 'a; rm -rf /' \"$((1 + 2))\"").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("timeout -s KILL 5 rm x").1, vec!["rm"]);
+        assert_eq!(risk("nohup ls").0, CommandRisk::Mutating);
+        assert_eq!(risk("nohup rm -rf /").0, CommandRisk::Escaping);
+        assert_eq!(risk("FOO=1 ls # ; rm").1, vec!["ls"]);
+        assert_eq!(risk("git branch -D main").0, CommandRisk::Mutating);
+        assert_eq!(risk("cat <<EOF\nrm -rf /\nEOF\nls").1, vec!["cat", "ls"]);
+        assert_eq!(risk("echo 'unterminated").0, CommandRisk::Mutating);
+    }
+
+    #[test]
+    fn flags_git_commands_that_write_files_or_refs() {
+        assert_eq!(risk("git log --oneline -5").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git log --output=patches.txt").0, CommandRisk::Mutating);
+        assert_eq!(risk("git show --output patches.txt HEAD").0, CommandRisk::Mutating);
+        assert_eq!(risk("git grep -n todo").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git grep -Ovim todo").0, CommandRisk::Mutating);
+        assert_eq!(risk("git grep --open-files-in-pager=vim todo").0, CommandRisk::Mutating);
+        assert_eq!(risk("git branch -a").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git branch --contains HEAD").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git branch --list 'feat*'").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git branch feature").0, CommandRisk::Mutating);
+        assert_eq!(risk("git tag").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git tag -l 'v1.*'").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("git tag v1.0").0, CommandRisk::Mutating);
+    }
+
+    #[test]
+    fn flags_redirections_outside_the_workspace() {
+        assert_eq!(risk("ls > /dev/null 2>&1").0, CommandRisk::ReadOnly);
+        assert_eq!(risk("echo hi > notes.txt").0, CommandRisk::Mutating);
+        assert_eq!(risk("echo hi >> /work/repo/sub/../notes.txt").0, CommandRisk::Mutating);
+        assert_eq!(risk("echo hi > ../outside.txt").0, CommandRisk::Escaping);
+        assert_eq!(risk("echo key >>~/.ssh/authorized_keys").0, CommandRisk::Escaping);
+        assert_eq!(risk("ls &> /etc/motd").0, CommandRisk::Escaping);
+        assert_eq!(risk("echo x > \"$HOME/x\"").0, CommandRisk::Escaping);
+        assert_eq!(risk("ls $(echo > /tmp/x)").0, CommandRisk::Escaping);
+        assert_eq!(risk("cat < /etc/hosts").0, CommandRisk::ReadOnly);
+    }
+
+    #[test]
+    fn flags_mutating_commands_on_paths_outside_the_workspace() {
+        assert_eq!(risk("ls; rm -rf /").0, CommandRisk::Escaping);
+        assert_eq!(risk("cp notes.txt ~/notes.txt").0, CommandRisk::Escaping);
+        assert_eq!(risk("tar -xf a.tar --directory=../elsewhere").0, CommandRisk::Escaping);
+        assert_eq!(risk("rm -rf /work/repo/build ./target").0, CommandRisk::Mutating);
+        assert_eq!(risk("ls /etc && cat /etc/hosts").0, CommandRisk::ReadOnly);
+    }
+
+    #[test]
+    fn treats_expansions_as_outside_the_workspace() {
+        assert_eq!(risk("rm -rf $HOME").0, CommandRisk::Escaping);
+        assert_eq!(risk("rm -rf \"$HOME/Documents\"").0, CommandRisk::Escaping);
+        assert_eq!(risk("cd $HOME && rm -rf *").0, CommandRisk::Escaping);
+        assert_eq!(risk("rm -rf ~alice/projects").0, CommandRisk::Escaping);
+        assert_eq!(risk("rm -rf `pwd`/build").0, CommandRisk::Escaping);
+        assert_eq!(risk("rm -rf --dir=${TARGET}").0, CommandRisk::Escaping);
+        assert_eq!(risk("echo $HOME").0, CommandRisk::ReadOnly);
+    }
+
+    #[test]
+    fn resolves_relative_paths_from_the_shell_directory() {
+        assert_eq!(assess_in("rm -rf ../build", "/work/repo/sub").risk, CommandRisk::Mutating);
+        assert_eq!(assess_in("rm -rf ../build", "/work/repo").risk, CommandRisk::Escaping);
+        assert_eq!(assess_in("echo hi > ../../notes.txt", "/work/repo/sub").risk, CommandRisk::Escaping);
+        assert_eq!(assess_in("rm -rf build", "/tmp").risk, CommandRisk::Escaping);
+        assert_eq!(assess_in("echo hi > notes.txt", "/tmp").risk, CommandRisk::Escaping);
+        assert_eq!(assess_in("ls -la", "/tmp").risk, CommandRisk::ReadOnly);
+    }
+}
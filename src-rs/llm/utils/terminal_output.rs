@@ -0,0 +1,128 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Identical consecutive lines kept before the rest are collapsed into a note
+const MAX_REPEATED_LINES: usize = 3;
+
+/// CSI sequences (colors, cursor movement), OSC sequences (titles, hyperlinks)
+/// and the remaining two-byte escapes
+static ANSI_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(\x07|\x1b\\)|\x1b[@-Z\\-_]")
+        .expect("ansi regex should compile")
+});
+
+const SPINNER_CHARS: &[char] = &[
+    '⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏', '⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷', '◐', '◓',
+    '◑', '◒',
+];
+
+/// Remove ANSI escape sequences
+pub fn strip_ansi(input: &str) -> String {
+    ANSI_RE.replace_all(input, "").into_owned()
+}
+
+/// Resolve carriage returns and backspaces the way a terminal would display them
+///
+/// A line redrawn with '\r' (progress bars) keeps only its final frame.
+fn render_line(line: &str) -> String {
+    let frame = line
+        .split('\r')
+        .rev()
+        .find(|s| !s.is_empty())
+        .unwrap_or("");
+    if !frame.contains('\x08') {
+        return frame.to_string();
+    }
+    let mut out = String::with_capacity(frame.len());
+    for c in frame.chars() {
+        if c == '\x08' {
+            out.pop();
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Text of a spinner frame without its spinner glyph, or `None` for other lines
+fn spinner_text(line: &str) -> Option<&str> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.strip_prefix(SPINNER_CHARS)?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some(rest.trim())
+}
+
+/// Normalize captured terminal output for the model
+///
+/// Strips ANSI escapes, keeps only the final state of carriage-return
+/// progress bars, collapses consecutive spinner frames into the last one and
+/// caps runs of identical lines.
+pub fn normalize_terminal_output(input: &str) -> String {
+    if input.is_empty() {
+        return String::new();
+    }
+
+    let stripped = strip_ansi(input);
+    let mut lines: Vec<String> = Vec::new();
+    // Length of the current run of identical lines, and how many were dropped
+    let mut run = 0usize;
+    let mut dropped = 0usize;
+
+    for raw in stripped.split('\n') {
+        let line = render_line(raw);
+        let last = lines.last();
+
+        if run == 1 {
+            if let (Some(prev), Some(text)) = (last, spinner_text(&line)) {
+                if spinner_text(prev) == Some(text) {
+                    *lines.last_mut().unwrap() = line;
+                    continue;
+                }
+            }
+        }
+
+        if run > 0 && last == Some(&line) {
+            run += 1;
+            if run > MAX_REPEATED_LINES {
+                dropped += 1;
+            } else {
+                lines.push(line);
+            }
+            continue;
+        }
+
+        if dropped > 0 {
+            lines.push(format!("... (previous line repeated {} more times)", dropped));
+            dropped = 0;
+        }
+        lines.push(line);
+        run = 1;
+    }
+    if dropped > 0 {
+        lines.push(format!("... (previous line repeated {} more times)", dropped));
+    }
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_ansi_and_resolves_progress_bars() {
+        let input = "\x1b[1;32mCompiling\x1b[0m foo\n 10%\r 50%\r100%\ndone\r\n";
+        assert_eq!(normalize_terminal_output(input), "Compiling foo\n100%\ndone\n");
+    }
+
+    #[test]
+    fn collapses_spinner_frames_and_repeated_lines() {
+        let input = "⠋ Installing\n⠙ Installing\n⠹ Installing\nok\nx\nx\nx\nx\nx\ny";
+        assert_eq!(
+            normalize_terminal_output(input),
+            "⠹ Installing\nok\nx\nx\nx\n... (previous line repeated 2 more times)\ny"
+        );
+    }
+}
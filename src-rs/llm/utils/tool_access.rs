@@ -1,4 +1,5 @@
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ToolAccessLevel {
@@ -31,10 +32,76 @@ pub fn with_tool_access<R>(level: ToolAccessLevel, f: impl FnOnce() -> R) -> R {
 }
 
 pub fn current_tool_access() -> ToolAccessLevel {
-    TOOL_ACCESS_LEVEL.with(|c| c.get())
+    TASK_SCOPE
+        .try_with(|scope| scope.access)
+        .unwrap_or_else(|_| TOOL_ACCESS_LEVEL.with(|c| c.get()))
 }
 
 pub fn is_full_access() -> bool {
     current_tool_access() == ToolAccessLevel::Full
 }
 
+
+thread_local! {
+    static TOOL_SESSION_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub struct ToolSessionGuard {
+    prev: Option<String>,
+}
+
+impl Drop for ToolSessionGuard {
+    fn drop(&mut self) {
+        TOOL_SESSION_ID.with(|c| *c.borrow_mut() = self.prev.take());
+    }
+}
+
+/// Run `f` with `session_id` as the session of the executing tool call
+pub fn with_tool_session<R>(session_id: &str, f: impl FnOnce() -> R) -> R {
+    let prev = TOOL_SESSION_ID.with(|c| c.replace(Some(session_id.to_string())));
+    let _guard = ToolSessionGuard { prev };
+    f()
+}
+
+/// Session of the executing tool call, if it runs inside a session
+pub fn current_tool_session() -> Option<String> {
+    TASK_SCOPE
+        .try_with(|scope| scope.session_id.clone())
+        .unwrap_or_else(|_| TOOL_SESSION_ID.with(|c| c.borrow().clone()))
+}
+
+tokio::task_local! {
+    static TASK_SCOPE: ToolScope;
+}
+
+/// Access level and session of a tool call. Sync tool code sees them through
+/// the thread-locals above; async tool code, which may move between threads,
+/// through a task-local set by `run`.
+#[derive(Debug, Clone)]
+pub struct ToolScope {
+    pub access: ToolAccessLevel,
+    pub session_id: Option<String>,
+}
+
+impl ToolScope {
+    /// The scope of the executing tool call, thread or task
+    pub fn current() -> Self {
+        Self {
+            access: current_tool_access(),
+            session_id: current_tool_session(),
+        }
+    }
+
+    /// Run sync `f` in this scope on the current thread
+    pub fn enter<R>(&self, f: impl FnOnce() -> R) -> R {
+        with_tool_access(self.access, || match &self.session_id {
+            Some(session_id) => with_tool_session(session_id, f),
+            None => f(),
+        })
+    }
+
+    /// Run `future` in this scope, wherever it is polled
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        TASK_SCOPE.scope(self, future).await
+    }
+}
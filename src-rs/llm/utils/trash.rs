@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// A file or directory moved to the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Name of the entry inside the session trash directory
+    pub id: String,
+    /// Absolute path the entry was deleted from
+    pub original_path: String,
+    pub is_dir: bool,
+    pub deleted_at_ms: i64,
+}
+
+/// Directory for a session's files under `.carry/`. A session key
+/// ("namespace/id") gets a directory per namespace.
+pub fn session_dir_name(session_id: &str) -> PathBuf {
+    session_id
+        .split('/')
+        .map(|segment| {
+            let name: String = segment
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+                .collect();
+            if name.is_empty() { "default".to_string() } else { name }
+        })
+        .collect()
+}
+
+/// Workspace-local trash, one directory per session under `.carry/trash/`
+#[derive(Debug, Clone)]
+pub struct Trash {
+    base: PathBuf,
+}
+
+impl Trash {
+    /// Trash rooted at `<workspace>/.carry/trash`
+    pub fn for_workspace(workspace: &Path) -> Self {
+        Self {
+            base: workspace.join(".carry").join("trash"),
+        }
+    }
+
+    /// Trash of the current working directory
+    pub fn current() -> Result<Self> {
+        let cwd = std::env::current_dir().context("Failed to determine workspace root")?;
+        Ok(Self::for_workspace(&cwd))
+    }
+
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    fn session_dir(&self, session_id: &str) -> PathBuf {
+        self.base.join(session_dir_name(session_id))
+    }
+
+    fn load_manifest(dir: &Path) -> Vec<TrashEntry> {
+        fs::read_to_string(dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Entries in the manifests of `dir` and the directories under it
+    fn count_entries(dir: &Path) -> usize {
+        let nested: usize = match fs::read_dir(dir) {
+            Ok(rd) => rd.flatten().map(|e| e.path()).filter(|p| p.is_dir()).map(|p| Self::count_entries(&p)).sum(),
+            Err(_) => 0,
+        };
+        Self::load_manifest(dir).len() + nested
+    }
+
+    fn save_manifest(dir: &Path, entries: &[TrashEntry]) -> Result<()> {
+        let json = serde_json::to_string_pretty(entries)?;
+        fs::write(dir.join(MANIFEST_FILE), json).context("Failed to write trash manifest")
+    }
+
+    /// Move `path` into the session's trash
+    pub fn put(&self, session_id: &str, path: &Path) -> Result<TrashEntry> {
+        let dir = self.session_dir(session_id);
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create trash directory: {}", dir.display()))?;
+
+        let deleted_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "entry".to_string());
+        let mut id = format!("{}-{}", deleted_at_ms, file_name);
+        let mut suffix = 1;
+        while dir.join(&id).exists() {
+            id = format!("{}-{}-{}", deleted_at_ms, suffix, file_name);
+            suffix += 1;
+        }
+
+        let is_dir = path.is_dir();
+        rename_or_copy(path, &dir.join(&id))?;
+
+        let entry = TrashEntry {
+            id,
+            original_path: path.to_string_lossy().to_string(),
+            is_dir,
+            deleted_at_ms,
+        };
+        let mut entries = Self::load_manifest(&dir);
+        entries.push(entry.clone());
+        Self::save_manifest(&dir, &entries)?;
+        Ok(entry)
+    }
+
+    /// Entries in the session's trash, oldest first
+    pub fn list(&self, session_id: &str) -> Vec<TrashEntry> {
+        Self::load_manifest(&self.session_dir(session_id))
+    }
+
+    /// Move an entry back to where it was deleted from
+    pub fn restore(&self, session_id: &str, id: &str) -> Result<TrashEntry> {
+        let dir = self.session_dir(session_id);
+        let mut entries = Self::load_manifest(&dir);
+        let index = entries
+            .iter()
+            .position(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Trash entry not found: {}", id))?;
+
+        let original = PathBuf::from(&entries[index].original_path);
+        if original.exists() {
+            anyhow::bail!(
+                "Cannot restore '{}': the path exists again",
+                entries[index].original_path
+            );
+        }
+        if let Some(parent) = original.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        rename_or_copy(&dir.join(id), &original)?;
+
+        let entry = entries.remove(index);
+        Self::save_manifest(&dir, &entries)?;
+        Ok(entry)
+    }
+
+    /// Permanently remove trashed entries of one session (or of every session
+    /// of a namespace, given just the namespace), or of all sessions
+    ///
+    /// Returns the number of entries removed.
+    pub fn purge(&self, session_id: Option<&str>) -> Result<usize> {
+        let dirs: Vec<PathBuf> = match session_id {
+            Some(id) => vec![self.session_dir(id)],
+            None => match fs::read_dir(&self.base) {
+                Ok(rd) => rd.flatten().map(|e| e.path()).filter(|p| p.is_dir()).collect(),
+                Err(_) => Vec::new(),
+            },
+        };
+
+        let mut removed = 0;
+        for dir in dirs {
+            if !dir.exists() {
+                continue;
+            }
+            removed += Self::count_entries(&dir);
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to purge trash: {}", dir.display()))?;
+        }
+        Ok(removed)
+    }
+}
+
+/// Rename, falling back to copy and remove for files on another filesystem
+fn rename_or_copy(from: &Path, to: &Path) -> Result<()> {
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(_) if from.is_file() => {
+            fs::copy(from, to).with_context(|| format!("Failed to copy to {}", to.display()))?;
+            fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to move {} to {}", from.display(), to.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn put_restore_and_purge() {
+        let workspace = FakeWorkspace::new().file("a.txt", "a").dir("d").build().unwrap();
+        let root = workspace.path();
+        let trash = Trash::for_workspace(root);
+        let file = root.join("a.txt");
+
+        let entry = trash.put("s1", &file).unwrap();
+        assert!(!file.exists());
+        assert!(trash.base().join("s1").join(&entry.id).exists());
+        assert_eq!(trash.list("s1").len(), 1);
+
+        trash.restore("s1", &entry.id).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a");
+        assert!(trash.list("s1").is_empty());
+
+        trash.put("s1", &file).unwrap();
+        trash.put("s2", &root.join("d")).unwrap();
+        assert_eq!(trash.purge(Some("s1")).unwrap(), 1);
+        assert_eq!(trash.purge(None).unwrap(), 1);
+        assert!(!trash.base().join("s2").exists());
+    }
+}
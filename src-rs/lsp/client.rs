@@ -2,31 +2,171 @@ use anyhow::{Context, Result};
 use std::collections::HashMap;
 use std::path::Path;
 use std::process::Stdio;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::{Child, Command};
-use tokio::sync::{oneshot, Mutex, RwLock};
+use tokio::sync::{oneshot, Mutex, Notify, RwLock};
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
 use crate::lsp::protocol::*;
 use crate::lsp::transport::{MessageReader, MessageWriter};
 
+/// Quiet period after a publishDiagnostics before the result is taken as settled;
+/// servers often publish fast syntax results first and semantic ones shortly after
+const DIAGNOSTICS_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Consecutive request timeouts after which a server is considered hung
+const UNRESPONSIVE_AFTER_TIMEOUTS: u32 = 3;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ServerState {
     Starting,
     Ready,
 }
 
+/// Liveness of a server process as seen by its client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientHealth {
+    Running,
+    /// The process exited or closed its output
+    Exited,
+    /// Several requests in a row timed out
+    Unresponsive,
+}
+
+/// Latest pushed diagnostics of one document
+#[derive(Debug, Clone)]
+struct FileDiagnostics {
+    /// Document version the server computed them for, if it reports one
+    version: Option<i32>,
+    /// Store sequence number at which they arrived
+    received: u64,
+    items: Vec<Diagnostic>,
+}
+
+/// Diagnostics pushed by the server, with a notification on every update
+#[derive(Debug, Default)]
+struct DiagnosticsStore {
+    files: RwLock<HashMap<String, FileDiagnostics>>,
+    seq: AtomicU64,
+    changed: Notify,
+}
+
+impl DiagnosticsStore {
+    async fn publish(&self, params: PublishDiagnosticsParams) {
+        let received = self.seq.fetch_add(1, Ordering::SeqCst) + 1;
+        self.files.write().await.insert(
+            params.uri,
+            FileDiagnostics {
+                version: params.version,
+                received,
+                items: params.diagnostics,
+            },
+        );
+        self.changed.notify_waiters();
+    }
+
+    fn current_seq(&self) -> u64 {
+        self.seq.load(Ordering::SeqCst)
+    }
+
+    /// Diagnostics of `uri` for document `version`, waiting until `deadline`
+    ///
+    /// A publish counts when it carries `version`, or carries no version and
+    /// arrived after the document was last synced (`synced_at`). Once one
+    /// arrives, further publishes are awaited until the server is quiet for
+    /// `debounce`. At the deadline the latest (possibly stale) result is returned.
+    async fn wait_for(
+        &self,
+        uri: &str,
+        version: i32,
+        synced_at: u64,
+        deadline: Instant,
+        debounce: Duration,
+    ) -> Vec<Diagnostic> {
+        let is_fresh = |d: &FileDiagnostics| match d.version {
+            Some(v) => v == version,
+            None => d.received > synced_at,
+        };
+
+        let mut waited = false;
+        loop {
+            let notified = self.changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.files.read().await.get(uri).is_some_and(is_fresh) {
+                break;
+            }
+            waited = true;
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                break;
+            }
+        }
+
+        if waited {
+            loop {
+                let notified = self.changed.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                let quiet_until = (Instant::now() + debounce).min(deadline);
+                if tokio::time::timeout_at(quiet_until, notified).await.is_err() {
+                    break;
+                }
+            }
+        }
+
+        self.files
+            .read()
+            .await
+            .get(uri)
+            .map(|d| d.items.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// A document as last sent to the server
+#[derive(Debug)]
+struct OpenDocument {
+    version: i32,
+    language_id: String,
+    text: String,
+    /// Diagnostics store sequence number when this version was sent
+    synced_at: u64,
+}
+
+/// Result of a pull diagnostics request, reused while the document is unchanged
+#[derive(Debug, Clone)]
+struct PulledDiagnostics {
+    version: i32,
+    result_id: Option<String>,
+    items: Vec<Diagnostic>,
+}
+
 pub struct LspClient {
     server_name: String,
-    _process: Arc<Mutex<Child>>,
+    process: Arc<Mutex<Child>>,
     writer: Arc<Mutex<MessageWriter>>,
     request_id: Arc<AtomicU32>,
     pending_requests: Arc<Mutex<HashMap<u32, oneshot::Sender<Message>>>>,
-    diagnostics: Arc<RwLock<HashMap<String, Vec<Diagnostic>>>>,
+    diagnostics: Arc<DiagnosticsStore>,
+    documents: Mutex<HashMap<String, OpenDocument>>,
+    /// Whether the server supports textDocument/diagnostic (LSP 3.17 pull model)
+    pull_diagnostics: AtomicBool,
+    pulled: Mutex<HashMap<String, PulledDiagnostics>>,
     state: Arc<RwLock<ServerState>>,
     timeout_ms: u64,
-    _message_loop: JoinHandle<()>,
+    /// Cleared when the message loop stops reading
+    alive: Arc<AtomicBool>,
+    consecutive_timeouts: AtomicU32,
+    message_loop: JoinHandle<()>,
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        self.message_loop.abort();
+    }
 }
 
 impl LspClient {
@@ -42,6 +182,7 @@ impl LspClient {
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::null())
+            .kill_on_drop(true)
             .spawn()
             .context(format!("Failed to spawn LSP server: {}", command))?;
 
@@ -53,12 +194,15 @@ impl LspClient {
 
         let request_id = Arc::new(AtomicU32::new(1));
         let pending_requests = Arc::new(Mutex::new(HashMap::new()));
-        let diagnostics = Arc::new(RwLock::new(HashMap::new()));
+        let diagnostics = Arc::new(DiagnosticsStore::default());
         let state = Arc::new(RwLock::new(ServerState::Starting));
+        let alive = Arc::new(AtomicBool::new(true));
 
         // Spawn message reading loop
         let pending_clone = pending_requests.clone();
         let diagnostics_clone = diagnostics.clone();
+        let alive_clone = alive.clone();
+        let name_clone = server_name.clone();
         let message_loop = tokio::spawn(async move {
             loop {
                 match reader.read_message().await {
@@ -66,23 +210,31 @@ impl LspClient {
                         Self::handle_message(message, &pending_clone, &diagnostics_clone).await;
                     }
                     Err(e) => {
-                        log::error!("Error reading message: {}", e);
+                        log::error!("LSP server '{}' stopped responding: {}", name_clone, e);
                         break;
                     }
                 }
             }
+            alive_clone.store(false, Ordering::SeqCst);
+            // Dropping the senders fails pending requests right away instead of at their timeout
+            pending_clone.lock().await.clear();
         });
 
         let client = Self {
             server_name: server_name.clone(),
-            _process: Arc::new(Mutex::new(child)),
+            process: Arc::new(Mutex::new(child)),
             writer,
             request_id,
             pending_requests,
             diagnostics,
+            documents: Mutex::new(HashMap::new()),
+            pull_diagnostics: AtomicBool::new(false),
+            pulled: Mutex::new(HashMap::new()),
             state,
             timeout_ms,
-            _message_loop: message_loop,
+            alive,
+            consecutive_timeouts: AtomicU32::new(0),
+            message_loop,
         };
 
         // Initialize
@@ -94,7 +246,7 @@ impl LspClient {
     async fn handle_message(
         message: Message,
         pending: &Arc<Mutex<HashMap<u32, oneshot::Sender<Message>>>>,
-        diagnostics: &Arc<RwLock<HashMap<String, Vec<Diagnostic>>>>,
+        diagnostics: &DiagnosticsStore,
     ) {
         // Handle response
         if let Some(id) = message.id {
@@ -110,8 +262,7 @@ impl LspClient {
             if method == "textDocument/publishDiagnostics" {
                 if let Some(params) = message.params {
                     if let Ok(params) = serde_json::from_value::<PublishDiagnosticsParams>(params) {
-                        let mut diag_map = diagnostics.write().await;
-                        diag_map.insert(params.uri, params.diagnostics);
+                        diagnostics.publish(params).await;
                     }
                 }
             }
@@ -141,13 +292,57 @@ impl LspClient {
             writer.write_message(&message).await?;
         }
 
-        let response = tokio::time::timeout(std::time::Duration::from_millis(self.timeout_ms), rx)
-            .await
-            .context("Request timeout")??;
+        let response = match tokio::time::timeout(Duration::from_millis(self.timeout_ms), rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => anyhow::bail!("LSP server '{}' exited", self.server_name),
+            Err(_) => {
+                self.pending_requests.lock().await.remove(&id);
+                self.consecutive_timeouts.fetch_add(1, Ordering::SeqCst);
+                anyhow::bail!("Request timeout: {}", method);
+            }
+        };
+        self.consecutive_timeouts.store(0, Ordering::SeqCst);
 
         Ok(response)
     }
 
+    /// Whether the process is still serving requests
+    pub fn health(&self) -> ClientHealth {
+        if !self.alive.load(Ordering::SeqCst) {
+            ClientHealth::Exited
+        } else if self.consecutive_timeouts.load(Ordering::SeqCst) >= UNRESPONSIVE_AFTER_TIMEOUTS {
+            ClientHealth::Unresponsive
+        } else {
+            ClientHealth::Running
+        }
+    }
+
+    /// Kill the server process; used before replacing a crashed or hung client
+    pub async fn shutdown(&self) {
+        self.message_loop.abort();
+        self.alive.store(false, Ordering::SeqCst);
+        if let Err(e) = self.process.lock().await.kill().await {
+            log::debug!("Failed to kill LSP server '{}': {}", self.server_name, e);
+        }
+    }
+
+    /// Open documents as (path, language id, text), for replaying into a restarted server
+    pub async fn open_documents(&self) -> Vec<(String, String, String)> {
+        let documents = self.documents.lock().await;
+        let mut open: Vec<_> = documents
+            .iter()
+            .map(|(uri, doc)| {
+                (
+                    uri.trim_start_matches("file://").to_string(),
+                    doc.language_id.clone(),
+                    doc.text.clone(),
+                )
+            })
+            .collect();
+        open.sort();
+        open
+    }
+
     async fn initialize(&self, root_path: Option<String>) -> Result<()> {
         let root_uri = root_path.map(|p| {
             let path = Path::new(&p);
@@ -161,6 +356,13 @@ impl LspClient {
                 text_document: Some(TextDocumentClientCapabilities {
                     publish_diagnostics: Some(PublishDiagnosticsClientCapabilities {
                         related_information: Some(true),
+                        version_support: Some(true),
+                    }),
+                    diagnostic: Some(DiagnosticClientCapabilities {
+                        dynamic_registration: false,
+                    }),
+                    hover: Some(HoverClientCapabilities {
+                        content_format: vec!["markdown".to_string(), "plaintext".to_string()],
                     }),
                 }),
             },
@@ -174,6 +376,13 @@ impl LspClient {
             anyhow::bail!("Initialize failed: {:?}", response.error);
         }
 
+        let pull = response
+            .result
+            .as_ref()
+            .and_then(|r| r.pointer("/capabilities/diagnosticProvider"))
+            .is_some_and(|p| !p.is_null() && p != &serde_json::Value::Bool(false));
+        self.pull_diagnostics.store(pull, Ordering::SeqCst);
+
         // Send initialized notification
         let initialized_msg = Message {
             jsonrpc: "2.0".to_string(),
@@ -195,49 +404,332 @@ impl LspClient {
         Ok(())
     }
 
-    pub async fn open_file(
+    /// Send the current content of a file: didOpen the first time, didChange
+    /// with a new version when it changed, nothing when it is unchanged
+    ///
+    /// Returns the document version now known to the server.
+    pub async fn sync_file(
         &self,
         file_path: &str,
         language_id: &str,
         content: String,
-    ) -> Result<()> {
+    ) -> Result<i32> {
         let uri = format!("file://{}", file_path);
-
-        let params = DidOpenTextDocumentParams {
-            text_document: TextDocumentItem {
-                uri,
-                language_id: language_id.to_string(),
-                version: 1,
-                text: content,
-            },
+        let mut documents = self.documents.lock().await;
+
+        let (method, params, version) = match documents.get(&uri) {
+            Some(doc) if doc.text == content => return Ok(doc.version),
+            Some(doc) => {
+                let version = doc.version + 1;
+                let params = DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version: Some(version),
+                    },
+                    content_changes: vec![TextDocumentContentChangeEvent {
+                        text: content.clone(),
+                    }],
+                };
+                ("textDocument/didChange", serde_json::to_value(params)?, version)
+            }
+            None => {
+                let params = DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem {
+                        uri: uri.clone(),
+                        language_id: language_id.to_string(),
+                        version: 1,
+                        text: content.clone(),
+                    },
+                };
+                ("textDocument/didOpen", serde_json::to_value(params)?, 1)
+            }
         };
 
         let message = Message {
             jsonrpc: "2.0".to_string(),
             id: None,
-            method: Some("textDocument/didOpen".to_string()),
-            params: Some(serde_json::to_value(params)?),
+            method: Some(method.to_string()),
+            params: Some(params),
             result: None,
             error: None,
         };
+        // Taken before sending so a publish racing the notification counts as fresh
+        let synced_at = self.diagnostics.current_seq();
+        self.writer.lock().await.write_message(&message).await?;
+
+        documents.insert(
+            uri,
+            OpenDocument {
+                version,
+                language_id: language_id.to_string(),
+                text: content,
+                synced_at,
+            },
+        );
+        Ok(version)
+    }
+
+    /// Diagnostics for the synced version of a file
+    ///
+    /// Uses pull diagnostics when the server supports them, otherwise waits up
+    /// to `wait` for pushed diagnostics of that version.
+    pub async fn diagnostics_for(&self, file_path: &str, wait: Duration) -> Result<Vec<Diagnostic>> {
+        let uri = format!("file://{}", file_path);
+        let (version, synced_at) = match self.documents.lock().await.get(&uri) {
+            Some(doc) => (doc.version, doc.synced_at),
+            None => anyhow::bail!("File is not open: {}", file_path),
+        };
 
-        let mut writer = self.writer.lock().await;
-        writer.write_message(&message).await?;
+        if self.pull_diagnostics.load(Ordering::SeqCst) {
+            return self.pull_diagnostics(&uri, version).await;
+        }
 
-        Ok(())
+        Ok(self
+            .diagnostics
+            .wait_for(&uri, version, synced_at, Instant::now() + wait, DIAGNOSTICS_DEBOUNCE)
+            .await)
+    }
+
+    async fn pull_diagnostics(&self, uri: &str, version: i32) -> Result<Vec<Diagnostic>> {
+        let previous = self.pulled.lock().await.get(uri).cloned();
+        if let Some(previous) = &previous {
+            if previous.version == version {
+                return Ok(previous.items.clone());
+            }
+        }
+
+        let params = DocumentDiagnosticParams {
+            text_document: TextDocumentIdentifier {
+                uri: uri.to_string(),
+            },
+            previous_result_id: previous.as_ref().and_then(|p| p.result_id.clone()),
+        };
+        let response = self
+            .send_request("textDocument/diagnostic", serde_json::to_value(params)?)
+            .await?;
+        if let Some(error) = response.error {
+            anyhow::bail!("Diagnostic request failed: {}", error.message);
+        }
+
+        let report: DocumentDiagnosticReport =
+            serde_json::from_value(response.result.unwrap_or_default())?;
+        let items = if report.kind == "unchanged" {
+            previous.map(|p| p.items).unwrap_or_default()
+        } else {
+            report.items
+        };
+        self.pulled.lock().await.insert(
+            uri.to_string(),
+            PulledDiagnostics {
+                version,
+                result_id: report.result_id,
+                items: items.clone(),
+            },
+        );
+        Ok(items)
     }
 
+    /// Ask the server for the edits renaming the symbol at `position`
+    ///
+    /// Returns `None` when the server finds nothing to rename there.
+    pub async fn rename(
+        &self,
+        file_path: &str,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let params = RenameParams {
+            text_document: TextDocumentIdentifier {
+                uri: format!("file://{}", file_path),
+            },
+            position,
+            new_name: new_name.to_string(),
+        };
+
+        let response = self
+            .send_request("textDocument/rename", serde_json::to_value(params)?)
+            .await?;
+
+        if let Some(error) = response.error {
+            anyhow::bail!("Rename failed: {}", error.message);
+        }
+
+        match response.result {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(result) => Ok(Some(serde_json::from_value(result)?)),
+        }
+    }
+
+    /// Hover information (type signature, docs) at `position`
+    pub async fn hover(&self, file_path: &str, position: Position) -> Result<Option<Hover>> {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: format!("file://{}", file_path),
+            },
+            position,
+        };
+
+        let response = self
+            .send_request("textDocument/hover", serde_json::to_value(params)?)
+            .await?;
+
+        if let Some(error) = response.error {
+            anyhow::bail!("Hover failed: {}", error.message);
+        }
+
+        match response.result {
+            None | Some(serde_json::Value::Null) => Ok(None),
+            Some(result) => Ok(Some(serde_json::from_value(result)?)),
+        }
+    }
+
+    /// Workspace symbols matching `query` (servers match fuzzily)
+    pub async fn workspace_symbols(&self, query: &str) -> Result<Vec<SymbolInformation>> {
+        let params = WorkspaceSymbolParams {
+            query: query.to_string(),
+        };
+
+        let response = self
+            .send_request("workspace/symbol", serde_json::to_value(params)?)
+            .await?;
+
+        if let Some(error) = response.error {
+            anyhow::bail!("Workspace symbol search failed: {}", error.message);
+        }
+
+        let items = match response.result {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => Vec::new(),
+        };
+        Ok(items
+            .into_iter()
+            .filter_map(|item| serde_json::from_value(item).ok())
+            .collect())
+    }
+
+    /// Code actions available for `range`, in the order the server returns them
+    pub async fn code_actions(
+        &self,
+        file_path: &str,
+        range: Range,
+        diagnostics: Vec<Diagnostic>,
+        only: Option<Vec<String>>,
+    ) -> Result<Vec<CodeAction>> {
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier {
+                uri: format!("file://{}", file_path),
+            },
+            range,
+            context: CodeActionContext { diagnostics, only },
+        };
+
+        let response = self
+            .send_request("textDocument/codeAction", serde_json::to_value(params)?)
+            .await?;
+
+        if let Some(error) = response.error {
+            anyhow::bail!("Code action request failed: {}", error.message);
+        }
+
+        let items = match response.result {
+            Some(serde_json::Value::Array(items)) => items,
+            _ => Vec::new(),
+        };
+        Ok(items.into_iter().filter_map(CodeAction::from_response_item).collect())
+    }
+
+    /// Fill in the edit of a code action the server returned lazily
+    pub async fn resolve_code_action(&self, action: &CodeAction) -> Result<CodeAction> {
+        let response = self
+            .send_request("codeAction/resolve", serde_json::to_value(action)?)
+            .await?;
+
+        if let Some(error) = response.error {
+            anyhow::bail!("Code action resolve failed: {}", error.message);
+        }
+
+        match response.result {
+            Some(result) => Ok(serde_json::from_value(result)?),
+            None => Ok(action.clone()),
+        }
+    }
+
+    /// Latest known diagnostics of a file, without waiting
     pub async fn get_diagnostics(&self, file_path: &str) -> Result<Vec<Diagnostic>> {
         let uri = format!("file://{}", file_path);
-        let diagnostics = self.diagnostics.read().await;
-        Ok(diagnostics.get(&uri).cloned().unwrap_or_default())
+        if let Some(pulled) = self.pulled.lock().await.get(&uri) {
+            return Ok(pulled.items.clone());
+        }
+        let files = self.diagnostics.files.read().await;
+        Ok(files.get(&uri).map(|d| d.items.clone()).unwrap_or_default())
     }
 
     pub async fn get_all_diagnostics(&self) -> HashMap<String, Vec<Diagnostic>> {
-        self.diagnostics.read().await.clone()
+        let mut all: HashMap<String, Vec<Diagnostic>> = self
+            .diagnostics
+            .files
+            .read()
+            .await
+            .iter()
+            .map(|(uri, d)| (uri.clone(), d.items.clone()))
+            .collect();
+        for (uri, pulled) in self.pulled.lock().await.iter() {
+            all.insert(uri.clone(), pulled.items.clone());
+        }
+        all
     }
 
     pub async fn is_ready(&self) -> bool {
         *self.state.read().await == ServerState::Ready
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn publish(uri: &str, version: Option<i32>, message: &str) -> PublishDiagnosticsParams {
+        serde_json::from_value(serde_json::json!({
+            "uri": uri,
+            "version": version,
+            "diagnostics": [{
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+                "severity": 1,
+                "message": message
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_synced_version_and_debounces() {
+        let store = Arc::new(DiagnosticsStore::default());
+        let uri = "file:///w/a.rs";
+        store.publish(publish(uri, Some(1), "old")).await;
+
+        let publisher = Arc::clone(&store);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            publisher.publish(publish(uri, Some(2), "syntax")).await;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            publisher.publish(publish(uri, Some(2), "semantic")).await;
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let items = store.wait_for(uri, 2, 0, deadline, Duration::from_millis(100)).await;
+        assert_eq!(items[0].message, "semantic");
+
+        // Already fresh: returned without waiting
+        let start = Instant::now();
+        let items = store.wait_for(uri, 2, 0, deadline, Duration::from_secs(5)).await;
+        assert_eq!(items[0].message, "semantic");
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Unversioned publishes count only after the sync point; the deadline bounds the wait
+        let synced_at = store.current_seq();
+        let items = store
+            .wait_for("file:///w/b.rs", 1, synced_at, Instant::now() + Duration::from_millis(50), Duration::from_millis(10))
+            .await;
+        assert!(items.is_empty());
+    }
+}
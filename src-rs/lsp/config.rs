@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LspConfig {
@@ -6,8 +7,14 @@ pub struct LspConfig {
     pub enabled: bool,
     #[serde(default = "default_timeout")]
     pub timeout_ms: u64,
+    /// How long to wait for a server to publish diagnostics for a changed file
+    #[serde(default = "default_diagnostics_wait")]
+    pub diagnostics_wait_ms: u64,
     #[serde(default)]
     pub servers: Vec<ServerConfig>,
+    /// Install missing servers that have an install spec into `lsp/` of the data directory
+    #[serde(default = "default_auto_install")]
+    pub auto_install: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +25,56 @@ pub struct ServerConfig {
     pub args: Vec<String>,
     pub file_extensions: Vec<String>,
     pub root_markers: Vec<String>,
+    /// How to install the server when `command` is not on PATH
+    #[serde(default)]
+    pub install: Option<InstallSpec>,
+}
+
+/// Where to get a language server from
+///
+/// Downloads are tried first for the current platform; the package command
+/// is the fallback. Installs land in `<data dir>/lsp/<server name>/`. Both
+/// are pinned: downloads by checksum, the command by an exact `version`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstallSpec {
+    /// Exact version to install, substituted for `{version}` in download URLs
+    /// and the command; a command without `{version}` is refused
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Downloads keyed by `<os>-<arch>` as in `std::env::consts`, e.g. `linux-x86_64`
+    #[serde(default)]
+    pub downloads: HashMap<String, DownloadSpec>,
+    /// Package manager command run in the install directory, e.g.
+    /// `["npm", "install", "pyright@{version}"]`
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Hex SHA-256 of `binary` after the command ran, checked when set
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// Server executable relative to the install directory; defaults to the server command name
+    #[serde(default)]
+    pub binary: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadSpec {
+    pub url: String,
+    /// Hex SHA-256 of the downloaded file; downloads without one are refused
+    pub sha256: String,
+    /// Whether the download is a gzip-compressed executable
+    #[serde(default)]
+    pub gzip: bool,
+}
+
+/// Pyright release installed when `pyright-langserver` is not on PATH
+const PYRIGHT_VERSION: &str = "1.1.389";
+
+fn default_diagnostics_wait() -> u64 {
+    3000
+}
+
+fn default_auto_install() -> bool {
+    true
 }
 
 fn default_timeout() -> u64 {
@@ -29,6 +86,8 @@ impl Default for LspConfig {
         Self {
             enabled: false,
             timeout_ms: 180000,
+            diagnostics_wait_ms: 3000,
+            auto_install: true,
             servers: vec![
                 ServerConfig {
                     name: "rust-analyzer".to_string(),
@@ -36,6 +95,7 @@ impl Default for LspConfig {
                     args: vec![],
                     file_extensions: vec!["rs".to_string()],
                     root_markers: vec!["Cargo.toml".to_string()],
+                    install: None,
                 },
                 ServerConfig {
                     name: "pyright".to_string(),
@@ -47,6 +107,20 @@ impl Default for LspConfig {
                         "setup.py".to_string(),
                         "requirements.txt".to_string(),
                     ],
+                    install: Some(InstallSpec {
+                        version: Some(PYRIGHT_VERSION.to_string()),
+                        command: vec![
+                            "npm".to_string(),
+                            "install".to_string(),
+                            "--prefix".to_string(),
+                            ".".to_string(),
+                            "--ignore-scripts".to_string(),
+                            "--no-save".to_string(),
+                            "pyright@{version}".to_string(),
+                        ],
+                        binary: Some("node_modules/.bin/pyright-langserver".to_string()),
+                        ..Default::default()
+                    }),
                 },
             ],
         }
@@ -0,0 +1,314 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::lsp::config::{DownloadSpec, InstallSpec, ServerConfig};
+
+/// One install at a time, so concurrent first uses do not race on the same directory
+static INSTALL_LOCK: LazyLock<tokio::sync::Mutex<()>> = LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+/// Upper bound on one download or install command
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Root of installed language servers, `lsp/` in the data directory
+pub fn install_root() -> Option<PathBuf> {
+    crate::paths::lsp_dir()
+}
+
+/// Key of the current platform in `InstallSpec::downloads`, e.g. `linux-x86_64`
+pub fn platform_key() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Executable to spawn for `server`
+///
+/// The configured command wins when it is on PATH. Otherwise an earlier install
+/// under `<data dir>/lsp/<name>/` is used, or the server is installed now if the
+/// workspace looks like a project for it (one of its root markers exists).
+pub async fn resolve_command(
+    server: &ServerConfig,
+    workspace_root: Option<&str>,
+    auto_install: bool,
+) -> Result<String> {
+    if find_on_path(&server.command).is_some() {
+        return Ok(server.command.clone());
+    }
+
+    let Some(spec) = &server.install else {
+        anyhow::bail!(
+            "Language server '{}' not found: install `{}` and make sure it is on PATH",
+            server.name,
+            server.command
+        );
+    };
+    let dir = install_root()
+        .context("Cannot determine the data directory for LSP installs")?
+        .join(&server.name);
+    let binary = binary_path(&dir, server, spec);
+    if binary.is_file() {
+        return Ok(binary.to_string_lossy().to_string());
+    }
+    if !auto_install {
+        anyhow::bail!(
+            "Language server '{}' not found and auto_install is disabled",
+            server.name
+        );
+    }
+    if !has_root_marker(workspace_root, server) {
+        anyhow::bail!(
+            "Language server '{}' not installed; skipped since the workspace has none of {:?}",
+            server.name,
+            server.root_markers
+        );
+    }
+
+    let _guard = INSTALL_LOCK.lock().await;
+    if !binary.is_file() {
+        log::info!("Installing language server '{}' into {}", server.name, dir.display());
+        install(spec, &dir, &binary)
+            .await
+            .with_context(|| format!("Failed to install language server '{}'", server.name))?;
+        log::info!("Installed language server '{}'", server.name);
+    }
+    Ok(binary.to_string_lossy().to_string())
+}
+
+fn binary_path(dir: &Path, server: &ServerConfig, spec: &InstallSpec) -> PathBuf {
+    let relative = spec.binary.clone().unwrap_or_else(|| {
+        Path::new(&server.command)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| server.name.clone())
+    });
+    dir.join(relative)
+}
+
+fn has_root_marker(workspace_root: Option<&str>, server: &ServerConfig) -> bool {
+    let Some(root) = workspace_root else {
+        return false;
+    };
+    server.root_markers.is_empty()
+        || server
+            .root_markers
+            .iter()
+            .any(|marker| Path::new(root).join(marker).exists())
+}
+
+/// Absolute path of `command`, looked up on PATH unless it contains a separator
+fn find_on_path(command: &str) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(command))
+        .find(|candidate| candidate.is_file())
+}
+
+async fn install(spec: &InstallSpec, dir: &Path, binary: &Path) -> Result<()> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    if let Some(download) = spec.downloads.get(&platform_key()) {
+        let url = pinned(&download.url, spec.version.as_deref());
+        return install_download(&url, download, binary).await;
+    }
+    if !spec.command.is_empty() {
+        let command = pinned_command(spec)?;
+        run_install_command(&command, dir).await?;
+        if !binary.is_file() {
+            anyhow::bail!(
+                "Install command finished but {} does not exist",
+                binary.display()
+            );
+        }
+        if let Some(expected) = &spec.sha256 {
+            let installed = tokio::fs::read(binary).await?;
+            if let Err(e) = verify_sha256(&installed, expected) {
+                let _ = tokio::fs::remove_dir_all(dir).await;
+                return Err(e);
+            }
+        }
+        return Ok(());
+    }
+    anyhow::bail!("No download for platform {} and no install command", platform_key())
+}
+
+/// `template` with `{version}` replaced by the pinned version
+fn pinned(template: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => template.replace("{version}", version),
+        None => template.to_string(),
+    }
+}
+
+/// The install command with its version filled in; a command that would
+/// install whatever is latest is refused
+fn pinned_command(spec: &InstallSpec) -> Result<Vec<String>> {
+    let Some(version) = spec.version.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+        anyhow::bail!("Install command has no pinned version");
+    };
+    if version == "latest" || version.contains(['*', '^', '~', '<', '>', ' ']) {
+        anyhow::bail!("Install version '{}' is not an exact version", version);
+    }
+    if !spec.command.iter().any(|arg| arg.contains("{version}")) {
+        anyhow::bail!("Install command `{}` does not use {{version}}", spec.command.join(" "));
+    }
+    Ok(spec.command.iter().map(|arg| pinned(arg, Some(version))).collect())
+}
+
+async fn install_download(url: &str, download: &DownloadSpec, binary: &Path) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(30))
+        .timeout(INSTALL_TIMEOUT)
+        .build()?;
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?
+        .error_for_status()?;
+    let bytes = response
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {}", url))?;
+    verify_sha256(&bytes, &download.sha256)?;
+    let contents = unpack(&bytes, download.gzip)?;
+
+    // Write next to the target and rename, so an interrupted install leaves no partial binary
+    let tmp = binary.with_extension("download");
+    if let Some(parent) = binary.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::write(&tmp, contents).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+    tokio::fs::rename(&tmp, binary).await?;
+    Ok(())
+}
+
+async fn run_install_command(command: &[String], dir: &Path) -> Result<()> {
+    let child = tokio::process::Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(dir)
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(INSTALL_TIMEOUT, child)
+        .await
+        .map_err(|_| anyhow::anyhow!("`{}` timed out after {:?}", command.join(" "), INSTALL_TIMEOUT))?
+        .with_context(|| format!("Failed to run `{}`", command.join(" ")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let tail: Vec<&str> = stderr.lines().rev().take(5).collect();
+        anyhow::bail!(
+            "`{}` exited with {}: {}",
+            command.join(" "),
+            output.status,
+            tail.into_iter().rev().collect::<Vec<_>>().join("\n")
+        );
+    }
+    Ok(())
+}
+
+fn verify_sha256(bytes: &[u8], expected: &str) -> Result<()> {
+    let expected = expected.trim().to_ascii_lowercase();
+    if expected.is_empty() {
+        anyhow::bail!("Download has no sha256 checksum configured");
+    }
+    let actual: String = Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != expected {
+        anyhow::bail!("Checksum mismatch: expected {}, got {}", expected, actual);
+    }
+    Ok(())
+}
+
+fn unpack(bytes: &[u8], gzip: bool) -> Result<Vec<u8>> {
+    if !gzip {
+        return Ok(bytes.to_vec());
+    }
+    let mut out = Vec::new();
+    flate2::read::GzDecoder::new(bytes)
+        .read_to_end(&mut out)
+        .context("Failed to decompress download")?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn verifies_checksum_and_unpacks_gzip() {
+        let payload = b"#!/bin/sh\necho lsp\n";
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(payload).unwrap();
+        let gz = encoder.finish().unwrap();
+
+        let sha: String = Sha256::digest(&gz).iter().map(|b| format!("{:02x}", b)).collect();
+        verify_sha256(&gz, &sha.to_uppercase()).unwrap();
+        assert!(verify_sha256(&gz, &"0".repeat(64)).is_err());
+        assert!(verify_sha256(&gz, "").is_err());
+        assert_eq!(unpack(&gz, true).unwrap(), payload);
+        assert_eq!(unpack(payload, false).unwrap(), payload);
+    }
+
+    #[test]
+    fn install_commands_must_pin_a_version() {
+        let spec = |version: Option<&str>, package: &str| InstallSpec {
+            version: version.map(str::to_string),
+            command: vec!["npm".to_string(), "install".to_string(), package.to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            pinned_command(&spec(Some("1.1.389"), "pyright@{version}")).unwrap(),
+            ["npm", "install", "pyright@1.1.389"]
+        );
+        assert!(pinned_command(&spec(None, "pyright@{version}")).is_err());
+        assert!(pinned_command(&spec(Some("latest"), "pyright@{version}")).is_err());
+        assert!(pinned_command(&spec(Some("^1.1"), "pyright@{version}")).is_err());
+        assert!(pinned_command(&spec(Some("1.1.389"), "pyright")).is_err());
+
+        let shipped: crate::config::AppConfig = toml::from_str(include_str!("../../Config.toml")).unwrap();
+        let default = crate::lsp::config::LspConfig::default();
+        for spec in shipped.lsp.servers.iter().chain(&default.servers).filter_map(|s| s.install.as_ref()) {
+            assert!(pinned_command(spec).is_ok());
+        }
+    }
+
+    #[test]
+    fn resolves_commands_on_path_and_install_dir() {
+        let server = ServerConfig {
+            name: "fake-ls".to_string(),
+            command: "carrycode-missing-language-server".to_string(),
+            args: vec![],
+            file_extensions: vec![],
+            root_markers: vec![],
+            install: None,
+        };
+        assert!(find_on_path("sh").is_some());
+        assert!(find_on_path(&server.command).is_none());
+
+        let spec = InstallSpec {
+            binary: Some("node_modules/.bin/fake-ls".to_string()),
+            ..Default::default()
+        };
+        let dir = Path::new("/opt/lsp/fake-ls");
+        assert_eq!(binary_path(dir, &server, &spec), dir.join("node_modules/.bin/fake-ls"));
+        assert_eq!(
+            binary_path(dir, &server, &InstallSpec::default()),
+            dir.join("carrycode-missing-language-server")
+        );
+    }
+}
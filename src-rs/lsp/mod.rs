@@ -1,23 +1,47 @@
 pub mod client;
 pub mod config;
 pub mod diagnostics;
+pub mod install;
 pub mod protocol;
+pub mod supervisor;
 pub mod transport;
+pub mod workspace_edit;
 
 use anyhow::Result;
 use std::collections::HashMap;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
-use crate::lsp::client::LspClient;
+use crate::lsp::client::{ClientHealth, LspClient};
 use crate::lsp::config::{LspConfig, ServerConfig};
 use crate::lsp::diagnostics::{format_diagnostics, DiagnosticSummary};
-use crate::lsp::protocol::Diagnostic;
+use crate::lsp::protocol::{
+    CodeAction, Diagnostic, Hover, Position, Range, SymbolInformation, WorkspaceEdit,
+};
+use crate::lsp::supervisor::RestartState;
+
+/// Managers shared by the LSP tools, one per workspace root
+static SHARED_MANAGERS: LazyLock<tokio::sync::Mutex<HashMap<String, Arc<LspManager>>>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(HashMap::new()));
 
 pub struct LspManager {
     clients: Arc<RwLock<HashMap<String, Arc<LspClient>>>>,
+    restarts: std::sync::Mutex<HashMap<String, RestartState>>,
     config: LspConfig,
+    workspace_root: Option<String>,
+}
+
+/// Health of one configured server
+#[derive(Debug, Clone)]
+pub struct ServerStatus {
+    pub name: String,
+    /// `running`, `unresponsive`, `exited`, `restarting` or `failed`
+    pub state: String,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    pub open_files: usize,
 }
 
 impl std::fmt::Debug for LspManager {
@@ -31,84 +55,324 @@ impl std::fmt::Debug for LspManager {
 impl LspManager {
     pub async fn new(config: &LspConfig, workspace_root: Option<String>) -> Result<Self> {
         let mut clients = HashMap::new();
+        let mut restarts = HashMap::new();
 
         for server_config in &config.servers {
-            match Self::start_server(server_config, workspace_root.clone(), config.timeout_ms).await
-            {
+            match Self::start_server(server_config, workspace_root.clone(), config).await {
                 Ok(client) => {
                     log::info!("Started LSP server: {}", server_config.name);
                     clients.insert(server_config.name.clone(), Arc::new(client));
                 }
                 Err(e) => {
                     log::warn!("Failed to start LSP server {}: {}", server_config.name, e);
+                    crate::health::record_error(crate::health::Subsystem::Lsp, format!("{}: {}", server_config.name, e));
+                    let mut state = RestartState::default();
+                    state.record_failure(Instant::now(), e.to_string());
+                    restarts.insert(server_config.name.clone(), state);
                 }
             }
         }
 
         Ok(Self {
             clients: Arc::new(RwLock::new(clients)),
+            restarts: std::sync::Mutex::new(restarts),
             config: config.clone(),
+            workspace_root,
         })
     }
 
+    /// Manager for the current workspace, started on first use and shared by all tools
+    pub async fn shared() -> Result<Arc<Self>> {
+        let root = std::env::current_dir()?.to_string_lossy().to_string();
+        let mut managers = SHARED_MANAGERS.lock().await;
+        if let Some(manager) = managers.get(&root) {
+            return Ok(Arc::clone(manager));
+        }
+        let manager = Arc::new(Self::from_app_config().await?);
+        managers.insert(root, Arc::clone(&manager));
+        Ok(manager)
+    }
+
+    /// Manager for the current workspace if a tool has already started it
+    pub async fn existing_shared() -> Option<Arc<Self>> {
+        let root = std::env::current_dir().ok()?.to_string_lossy().to_string();
+        SHARED_MANAGERS.lock().await.get(&root).cloned()
+    }
+
+    /// Status of every shared manager, as (workspace root, servers)
+    pub async fn shared_statuses() -> Vec<(String, Vec<ServerStatus>)> {
+        let managers: Vec<(String, Arc<Self>)> = SHARED_MANAGERS
+            .lock()
+            .await
+            .iter()
+            .map(|(root, manager)| (root.clone(), Arc::clone(manager)))
+            .collect();
+        let mut statuses = Vec::with_capacity(managers.len());
+        for (root, manager) in managers {
+            statuses.push((root, manager.status().await));
+        }
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        statuses
+    }
+
+    /// Start the servers configured in config.toml for the current workspace
+    pub async fn from_app_config() -> Result<Self> {
+        let config = crate::llm::config::AppConfig::load()?;
+        if !config.lsp.enabled {
+            anyhow::bail!("LSP not enabled in config");
+        }
+
+        Self::new(
+            &config.lsp,
+            Some(std::env::current_dir()?.to_string_lossy().to_string()),
+        )
+        .await
+    }
+
     async fn start_server(
         server_config: &ServerConfig,
         workspace_root: Option<String>,
-        timeout_ms: u64,
+        config: &LspConfig,
     ) -> Result<LspClient> {
+        let command =
+            install::resolve_command(server_config, workspace_root.as_deref(), config.auto_install)
+                .await?;
         LspClient::new(
             server_config.name.clone(),
-            server_config.command.clone(),
+            command,
             server_config.args.clone(),
             workspace_root,
-            timeout_ms,
+            config.timeout_ms,
         )
         .await
     }
 
     pub async fn get_diagnostics(&self, file_path: &str) -> Result<Option<DiagnosticSummary>> {
-        let clients = self.clients.read().await;
+        let Some(client) = self.find_client(file_path).await else {
+            return Ok(None);
+        };
+        self.sync_file(&client, file_path).await?;
 
-        if clients.is_empty() {
+        let wait = Duration::from_millis(self.config.diagnostics_wait_ms);
+        let diagnostics = client.diagnostics_for(file_path, wait).await?;
+        if diagnostics.is_empty() {
             return Ok(None);
         }
+        let mut map = HashMap::new();
+        map.insert(format!("file://{}", file_path), diagnostics);
+        Ok(Some(format_diagnostics(map)))
+    }
 
-        // Determine which server to use based on file extension
+    /// Ready client of the server configured for the file's extension
+    ///
+    /// A server that exited or stopped answering is restarted here, subject to
+    /// backoff and the restart limit.
+    async fn find_client(&self, file_path: &str) -> Option<Arc<LspClient>> {
         let ext = Path::new(file_path)
             .extension()
             .and_then(|e| e.to_str())
             .unwrap_or("");
 
-        for (name, client) in clients.iter() {
-            if let Some(server_config) = self
-                .config
-                .servers
-                .iter()
-                .find(|s| s.name == *name && s.file_extensions.contains(&ext.to_string()))
-            {
-                if !client.is_ready().await {
-                    continue;
+        for server in self
+            .config
+            .servers
+            .iter()
+            .filter(|s| s.file_extensions.iter().any(|e| e == ext))
+        {
+            let client = self.clients.read().await.get(&server.name).cloned();
+            if let Some(client) = &client {
+                match client.health() {
+                    ClientHealth::Running if client.is_ready().await => return Some(Arc::clone(client)),
+                    ClientHealth::Running => continue,
+                    health => log::warn!("LSP server {} is {:?}", server.name, health),
                 }
+            }
+            if let Some(client) = self.restart(server, client).await {
+                return Some(client);
+            }
+        }
+        None
+    }
 
-                // Open file if not already open
-                let language_id = &server_config.name;
-                if let Ok(content) = tokio::fs::read_to_string(file_path).await {
-                    let _ = client.open_file(file_path, language_id, content).await;
-                }
+    /// Replace a crashed, hung or never started server and reopen its documents
+    async fn restart(&self, server: &ServerConfig, old: Option<Arc<LspClient>>) -> Option<Arc<LspClient>> {
+        {
+            let mut restarts = self.restarts.lock().unwrap();
+            let state = restarts.entry(server.name.clone()).or_default();
+            if !state.begin_attempt(Instant::now()) {
+                return None;
+            }
+            if let Some(old) = &old {
+                state.last_error = Some(match old.health() {
+                    ClientHealth::Unresponsive => "server stopped answering requests".to_string(),
+                    _ => "server process exited".to_string(),
+                });
+            }
+            log::info!("Restarting LSP server {} (attempt {})", server.name, state.restarts);
+        }
 
-                // Wait a bit for diagnostics
-                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        let documents = match &old {
+            Some(old) => {
+                old.shutdown().await;
+                old.open_documents().await
+            }
+            None => Vec::new(),
+        };
+        self.clients.write().await.remove(&server.name);
 
-                let diagnostics = client.get_diagnostics(file_path).await?;
-                if !diagnostics.is_empty() {
-                    let mut map = HashMap::new();
-                    map.insert(format!("file://{}", file_path), diagnostics);
-                    return Ok(Some(format_diagnostics(map)));
+        let client = match Self::start_server(server, self.workspace_root.clone(), &self.config).await {
+            Ok(client) => Arc::new(client),
+            Err(e) => {
+                log::warn!("Failed to restart LSP server {}: {}", server.name, e);
+                crate::health::record_error(crate::health::Subsystem::Lsp, format!("{}: {}", server.name, e));
+                if let Some(state) = self.restarts.lock().unwrap().get_mut(&server.name) {
+                    state.last_error = Some(e.to_string());
                 }
+                return None;
+            }
+        };
+        for (path, language_id, text) in documents {
+            if let Err(e) = client.sync_file(&path, &language_id, text).await {
+                log::debug!("Failed to reopen {} in {}: {}", path, server.name, e);
+            }
+        }
+        self.clients
+            .write()
+            .await
+            .insert(server.name.clone(), Arc::clone(&client));
+        Some(client)
+    }
+
+    /// Health of every configured server
+    pub async fn status(&self) -> Vec<ServerStatus> {
+        let clients = self.clients.read().await.clone();
+        let restarts = self.restarts.lock().unwrap().clone();
+        let mut statuses = Vec::with_capacity(self.config.servers.len());
+        for server in &self.config.servers {
+            let restart = restarts.get(&server.name).cloned().unwrap_or_default();
+            let client = clients.get(&server.name);
+            let health = client.map(|c| c.health());
+            let state = match health {
+                Some(ClientHealth::Running) => "running",
+                _ if restart.exhausted() => "failed",
+                Some(ClientHealth::Unresponsive) => "unresponsive",
+                Some(ClientHealth::Exited) => "exited",
+                None => "restarting",
+            };
+            let open_files = match client {
+                Some(client) => client.open_documents().await.len(),
+                None => 0,
+            };
+            statuses.push(ServerStatus {
+                name: server.name.clone(),
+                state: state.to_string(),
+                restarts: restart.restarts,
+                last_error: restart.last_error,
+                open_files,
+            });
+        }
+        statuses
+    }
+
+    /// Send the file's current content to the server
+    async fn sync_file(&self, client: &LspClient, file_path: &str) -> Result<i32> {
+        let content = tokio::fs::read_to_string(file_path).await?;
+        client
+            .sync_file(file_path, &language_id(file_path), content)
+            .await
+    }
+
+    /// Ready client for the file's extension, with the file synced
+    async fn client_for_file(&self, file_path: &str) -> Result<Arc<LspClient>> {
+        let Some(client) = self.find_client(file_path).await else {
+            let ext = Path::new(file_path)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("");
+            anyhow::bail!("No running language server handles .{} files", ext);
+        };
+        self.sync_file(&client, file_path).await?;
+        Ok(client)
+    }
+
+    /// Rename the symbol at `position` across the workspace
+    ///
+    /// Only computes the edits; applying them is up to the caller.
+    pub async fn rename_symbol(
+        &self,
+        file_path: &str,
+        position: Position,
+        new_name: &str,
+    ) -> Result<Option<WorkspaceEdit>> {
+        let client = self.client_for_file(file_path).await?;
+        client.rename(file_path, position, new_name).await
+    }
+
+    /// Hover information (type signature, docs) at `position`
+    pub async fn hover(&self, file_path: &str, position: Position) -> Result<Option<Hover>> {
+        let client = self.client_for_file(file_path).await?;
+        client.hover(file_path, position).await
+    }
+
+    /// Symbols named exactly `name`, from every running server
+    ///
+    /// Servers match workspace symbol queries fuzzily, so only exact name matches
+    /// with a known range are kept.
+    pub async fn find_symbol(&self, name: &str) -> Result<Vec<SymbolInformation>> {
+        let clients: Vec<Arc<LspClient>> = self.clients.read().await.values().cloned().collect();
+        let mut found = Vec::new();
+        let mut last_error = None;
+        for client in clients {
+            if client.health() != ClientHealth::Running || !client.is_ready().await {
+                continue;
+            }
+            match client.workspace_symbols(name).await {
+                Ok(symbols) => found.extend(
+                    symbols
+                        .into_iter()
+                        .filter(|s| s.name == name && s.location.range.is_some()),
+                ),
+                Err(e) => last_error = Some(e),
             }
         }
+        match last_error {
+            Some(e) if found.is_empty() => Err(e),
+            _ => Ok(found),
+        }
+    }
+
+    /// Code actions at `position`, with the diagnostics on that line as context
+    ///
+    /// Actions without an edit are resolved when the server supports it.
+    pub async fn code_actions(
+        &self,
+        file_path: &str,
+        position: Position,
+        only: Option<Vec<String>>,
+    ) -> Result<Vec<CodeAction>> {
+        let client = self.client_for_file(file_path).await?;
 
-        Ok(None)
+        let diagnostics: Vec<Diagnostic> = client
+            .get_diagnostics(file_path)
+            .await?
+            .into_iter()
+            .filter(|d| d.range.start.line <= position.line && position.line <= d.range.end.line)
+            .collect();
+        let range = Range {
+            start: position.clone(),
+            end: position,
+        };
+
+        let mut actions = client.code_actions(file_path, range, diagnostics, only).await?;
+        for action in actions.iter_mut() {
+            if action.edit.is_none() && action.command.is_none() && action.data.is_some() {
+                match client.resolve_code_action(action).await {
+                    Ok(resolved) => *action = resolved,
+                    Err(e) => log::debug!("Failed to resolve code action '{}': {}", action.title, e),
+                }
+            }
+        }
+        Ok(actions)
     }
 
     pub async fn get_all_diagnostics(&self) -> Result<DiagnosticSummary> {
@@ -120,7 +384,7 @@ impl LspManager {
             for (uri, diagnostics) in diag_map {
                 all_diagnostics
                     .entry(uri)
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .extend(diagnostics);
             }
         }
@@ -128,3 +392,27 @@ impl LspManager {
         Ok(format_diagnostics(all_diagnostics))
     }
 }
+
+/// LSP language identifier for a file, from its extension
+fn language_id(file_path: &str) -> String {
+    let ext = Path::new(file_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("");
+    match ext {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" => "cpp",
+        "java" => "java",
+        "rb" => "ruby",
+        "sh" => "shellscript",
+        other => other,
+    }
+    .to_string()
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 
 /// LSP Position (line, character)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -15,8 +16,8 @@ pub struct Range {
     pub end: Position,
 }
 
-/// Diagnostic severity levels
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+/// Diagnostic severity levels, encoded as integers on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum DiagnosticSeverity {
     Error = 1,
@@ -25,10 +26,32 @@ pub enum DiagnosticSeverity {
     Hint = 4,
 }
 
+impl Serialize for DiagnosticSeverity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for DiagnosticSeverity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            1 => Ok(Self::Error),
+            2 => Ok(Self::Warning),
+            3 => Ok(Self::Information),
+            4 => Ok(Self::Hint),
+            other => Err(serde::de::Error::custom(format!(
+                "invalid diagnostic severity {}",
+                other
+            ))),
+        }
+    }
+}
+
 /// LSP Diagnostic
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Diagnostic {
     pub range: Range,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub severity: Option<DiagnosticSeverity>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub code: Option<Value>,
@@ -61,6 +84,7 @@ pub struct ResponseError {
 
 /// Initialize parameters
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct InitializeParams {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub process_id: Option<u32>,
@@ -70,40 +94,271 @@ pub struct InitializeParams {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ClientCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text_document: Option<TextDocumentClientCapabilities>,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextDocumentClientCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub publish_diagnostics: Option<PublishDiagnosticsClientCapabilities>,
+    /// Pull diagnostics (LSP 3.17)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnostic: Option<DiagnosticClientCapabilities>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hover: Option<HoverClientCapabilities>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoverClientCapabilities {
+    /// Preferred formats of hover contents, most preferred first
+    pub content_format: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticClientCapabilities {
+    pub dynamic_registration: bool,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct PublishDiagnosticsClientCapabilities {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub related_information: Option<bool>,
+    /// Whether the client uses the document version of published diagnostics
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_support: Option<bool>,
 }
 
 /// PublishDiagnostics notification params
 #[derive(Debug, Deserialize)]
 pub struct PublishDiagnosticsParams {
     pub uri: String,
+    /// Document version the diagnostics were computed for
+    #[serde(default)]
+    pub version: Option<i32>,
     pub diagnostics: Vec<Diagnostic>,
 }
 
 /// DidOpenTextDocument notification params
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DidOpenTextDocumentParams {
     pub text_document: TextDocumentItem,
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TextDocumentItem {
     pub uri: String,
     pub language_id: String,
     pub version: i32,
     pub text: String,
 }
+
+/// A single text replacement
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+/// Edits of one document, as used in `documentChanges`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentEdit {
+    pub text_document: VersionedTextDocumentIdentifier,
+    pub edits: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionedTextDocumentIdentifier {
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
+}
+
+/// Changes to many documents, returned by rename and code actions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceEdit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<HashMap<String, Vec<TextEdit>>>,
+    /// Text document edits and resource operations (create/rename/delete)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_changes: Option<Vec<Value>>,
+}
+
+/// textDocument/rename request params
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenameParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+    pub new_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextDocumentIdentifier {
+    pub uri: String,
+}
+
+/// DidChangeTextDocument notification params, always sending the full text
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidChangeTextDocumentParams {
+    pub text_document: VersionedTextDocumentIdentifier,
+    pub content_changes: Vec<TextDocumentContentChangeEvent>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TextDocumentContentChangeEvent {
+    pub text: String,
+}
+
+/// textDocument/diagnostic request params
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentDiagnosticParams {
+    pub text_document: TextDocumentIdentifier,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_result_id: Option<String>,
+}
+
+/// textDocument/diagnostic response: a full report or "unchanged since previousResultId"
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentDiagnosticReport {
+    pub kind: String,
+    #[serde(default)]
+    pub result_id: Option<String>,
+    #[serde(default)]
+    pub items: Vec<Diagnostic>,
+}
+
+/// textDocument/codeAction request params
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeActionParams {
+    pub text_document: TextDocumentIdentifier,
+    pub range: Range,
+    pub context: CodeActionContext,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CodeActionContext {
+    /// Diagnostics overlapping the range
+    pub diagnostics: Vec<Diagnostic>,
+    /// Requested action kinds, e.g. `quickfix`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub only: Option<Vec<String>>,
+}
+
+/// A code action; bare `Command` results are stored with only `title` and `command`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CodeAction {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_preferred: Option<bool>,
+    /// Present when the server cannot apply the action here
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disabled: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit: Option<WorkspaceEdit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<Value>,
+    /// Opaque server data for codeAction/resolve
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl CodeAction {
+    /// Parse one entry of a codeAction response (`Command | CodeAction`)
+    pub fn from_response_item(item: Value) -> Option<Self> {
+        if item.get("command").is_some_and(|c| c.is_string()) {
+            let title = item.get("title")?.as_str()?.to_string();
+            return Some(Self {
+                title,
+                kind: None,
+                is_preferred: None,
+                disabled: None,
+                edit: None,
+                command: Some(item),
+                data: None,
+            });
+        }
+        serde_json::from_value(item).ok()
+    }
+}
+
+/// Params of requests at a document position, such as textDocument/hover
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextDocumentPositionParams {
+    pub text_document: TextDocumentIdentifier,
+    pub position: Position,
+}
+
+/// textDocument/hover response
+#[derive(Debug, Clone, Deserialize)]
+pub struct Hover {
+    /// `MarkupContent`, a `MarkedString` or an array of `MarkedString`
+    pub contents: Value,
+}
+
+impl Hover {
+    /// Contents as markdown, whichever of the LSP shapes the server used
+    pub fn markdown(&self) -> String {
+        marked_to_markdown(&self.contents).trim().to_string()
+    }
+}
+
+fn marked_to_markdown(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(marked_to_markdown)
+            .filter(|s| !s.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(obj) => {
+            let text = obj.get("value").and_then(Value::as_str).unwrap_or_default();
+            match obj.get("language").and_then(Value::as_str) {
+                Some(language) => format!("```{}\n{}\n```", language, text),
+                None => text.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// workspace/symbol request params
+#[derive(Debug, Serialize)]
+pub struct WorkspaceSymbolParams {
+    pub query: String,
+}
+
+/// Entry of a workspace/symbol response (`SymbolInformation` or `WorkspaceSymbol`)
+#[derive(Debug, Clone, Deserialize)]
+pub struct SymbolInformation {
+    pub name: String,
+    pub location: SymbolLocation,
+}
+
+/// Location of a workspace symbol; `range` is absent when the server resolves it lazily
+#[derive(Debug, Clone, Deserialize)]
+pub struct SymbolLocation {
+    pub uri: String,
+    #[serde(default)]
+    pub range: Option<Range>,
+}
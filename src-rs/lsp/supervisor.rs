@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+
+/// Restarts of one server before giving up until the manager is recreated
+pub const MAX_RESTARTS: u32 = 5;
+
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+/// Restart bookkeeping of one configured server
+#[derive(Debug, Clone, Default)]
+pub struct RestartState {
+    /// Restart attempts made so far, successful or not
+    pub restarts: u32,
+    /// Earliest time of the next attempt
+    next_attempt: Option<Instant>,
+    /// Why the last start failed or the server was replaced
+    pub last_error: Option<String>,
+}
+
+impl RestartState {
+    /// Delay before the attempt after `attempt`: 1s, 2s, 4s, ... up to a minute
+    pub fn backoff(attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        BACKOFF_BASE.saturating_mul(factor).min(BACKOFF_MAX)
+    }
+
+    /// Record a failure that is not itself a restart, such as the first start failing
+    pub fn record_failure(&mut self, now: Instant, error: String) {
+        self.last_error = Some(error);
+        self.next_attempt = Some(now + Self::backoff(self.restarts.max(1)));
+    }
+
+    /// Claim a restart attempt; false while backing off or once the limit is reached
+    pub fn begin_attempt(&mut self, now: Instant) -> bool {
+        if self.exhausted() || self.next_attempt.is_some_and(|t| now < t) {
+            return false;
+        }
+        self.restarts += 1;
+        self.next_attempt = Some(now + Self::backoff(self.restarts));
+        true
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.restarts >= MAX_RESTARTS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_and_gives_up() {
+        assert_eq!(RestartState::backoff(1), Duration::from_secs(1));
+        assert_eq!(RestartState::backoff(4), Duration::from_secs(8));
+        assert_eq!(RestartState::backoff(40), BACKOFF_MAX);
+
+        let start = Instant::now();
+        let mut state = RestartState::default();
+        state.record_failure(start, "spawn failed".to_string());
+        assert!(!state.begin_attempt(start));
+
+        let mut now = start + Duration::from_secs(1);
+        for attempt in 1..=MAX_RESTARTS {
+            assert!(state.begin_attempt(now), "attempt {}", attempt);
+            assert!(!state.begin_attempt(now));
+            now += RestartState::backoff(attempt);
+        }
+        assert!(state.exhausted());
+        assert!(!state.begin_attempt(now + BACKOFF_MAX));
+    }
+}
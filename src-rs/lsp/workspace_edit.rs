@@ -0,0 +1,267 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::llm::utils::checkpoint;
+use crate::llm::utils::file_activity;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::file_lock;
+use crate::llm::utils::file_tracker::{FILE_HISTORY_TRACKER, FILE_READ_TRACKER};
+use crate::llm::utils::overlay;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::lsp::protocol::{Position, TextDocumentEdit, TextEdit, WorkspaceEdit};
+
+/// New content of one file touched by a WorkspaceEdit
+#[derive(Debug)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub original: String,
+    pub updated: String,
+    /// Diff with the path relative to the workspace root
+    pub diff_stats: DiffStats,
+}
+
+/// Text edits of a WorkspaceEdit grouped by file, in a stable order
+///
+/// Resource operations (file create/rename/delete) are not supported and fail
+/// the whole edit rather than applying it partially.
+pub fn file_edits(edit: &WorkspaceEdit) -> Result<Vec<(PathBuf, Vec<TextEdit>)>> {
+    let mut files: Vec<(PathBuf, Vec<TextEdit>)> = Vec::new();
+    let mut push = |uri: &str, edits: Vec<TextEdit>| -> Result<()> {
+        let path = uri_to_path(uri)?;
+        match files.iter_mut().find(|(p, _)| *p == path) {
+            Some((_, existing)) => existing.extend(edits),
+            None => files.push((path, edits)),
+        }
+        Ok(())
+    };
+
+    if let Some(document_changes) = &edit.document_changes {
+        for change in document_changes {
+            if let Some(kind) = change.get("kind").and_then(|k| k.as_str()) {
+                anyhow::bail!("Unsupported workspace edit operation: {}", kind);
+            }
+            let doc: TextDocumentEdit = serde_json::from_value(change.clone())
+                .context("Invalid text document edit")?;
+            push(&doc.text_document.uri, doc.edits)?;
+        }
+    } else if let Some(changes) = &edit.changes {
+        let mut uris: Vec<&String> = changes.keys().collect();
+        uris.sort();
+        for uri in uris {
+            push(uri, changes[uri].clone())?;
+        }
+    }
+
+    files.retain(|(_, edits)| !edits.is_empty());
+    Ok(files)
+}
+
+/// Local path of a `file://` URI
+pub fn uri_to_path(uri: &str) -> Result<PathBuf> {
+    let url = url::Url::parse(uri).with_context(|| format!("Invalid URI: {}", uri))?;
+    url.to_file_path()
+        .map_err(|_| anyhow::anyhow!("Not a local file URI: {}", uri))
+}
+
+/// Byte offset of an LSP position (UTF-16 character offsets)
+pub fn offset_at(content: &str, position: &Position) -> Option<usize> {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        line_start += content[line_start..].find('\n')? + 1;
+    }
+    let line_end = content[line_start..]
+        .find('\n')
+        .map(|i| line_start + i)
+        .unwrap_or(content.len());
+
+    let mut units = 0;
+    for (i, c) in content[line_start..line_end].char_indices() {
+        if units >= position.character as usize {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    // Positions past the end of the line refer to the line end
+    Some(line_end)
+}
+
+/// LSP position of a 1-based line and character column
+pub fn position_at(content: &str, line: usize, column: usize) -> Option<Position> {
+    let text = content.lines().nth(line.checked_sub(1)?)?;
+    let character: usize = text
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum();
+    Some(Position {
+        line: (line - 1) as u32,
+        character: character as u32,
+    })
+}
+
+/// Apply text edits to `content`; edits must not overlap
+pub fn apply_text_edits(content: &str, edits: &[TextEdit]) -> Result<String> {
+    let mut ranges = Vec::with_capacity(edits.len());
+    for edit in edits {
+        let start = offset_at(content, &edit.range.start)
+            .with_context(|| format!("Edit start out of range: {:?}", edit.range.start))?;
+        let end = offset_at(content, &edit.range.end)
+            .with_context(|| format!("Edit end out of range: {:?}", edit.range.end))?;
+        if end < start {
+            anyhow::bail!("Invalid edit range: {:?}", edit.range);
+        }
+        ranges.push((start, end, edit.new_text.as_str()));
+    }
+    // Stable sort keeps the server's order for inserts at the same position
+    ranges.sort_by_key(|(start, end, _)| (*start, *end));
+
+    let mut result = String::with_capacity(content.len());
+    let mut cursor = 0;
+    for (start, end, new_text) in ranges {
+        if start < cursor {
+            anyhow::bail!("Overlapping edits in workspace edit");
+        }
+        result.push_str(&content[cursor..start]);
+        result.push_str(new_text);
+        cursor = end;
+    }
+    result.push_str(&content[cursor..]);
+    Ok(result)
+}
+
+/// Compute the new content of every file a WorkspaceEdit touches, without writing
+pub fn prepare(policy: &PathPolicy, edit: &WorkspaceEdit) -> Result<Vec<FileChange>> {
+    let mut changes = Vec::new();
+    for (path, edits) in file_edits(edit)? {
+        let path = policy.resolve(&path.to_string_lossy())?;
+        let original = fs::read_to_string(overlay::read_path(&path))
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let updated = apply_text_edits(&original, &edits)
+            .with_context(|| format!("Failed to apply edits to {}", path.display()))?;
+        if updated == original {
+            continue;
+        }
+        let display = path
+            .strip_prefix(policy.root())
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .to_string();
+        let diff_stats = DiffStats::compute(&display, &original, &updated);
+        changes.push(FileChange {
+            path,
+            original,
+            updated,
+            diff_stats,
+        });
+    }
+    if changes.is_empty() {
+        anyhow::bail!("The language server returned no changes");
+    }
+    Ok(changes)
+}
+
+/// Write the changes, recording file history like the edit tool
+pub fn apply(changes: &[FileChange]) -> Result<()> {
+    // Lock and re-check everything first so a conflict leaves no edit half-applied
+    for change in changes {
+        file_lock::lock_for_current_session(&change.path)?;
+        let current = fs::read_to_string(overlay::read_path(&change.path))
+            .with_context(|| format!("Failed to read {}", change.path.display()))?;
+        if current != change.original {
+            anyhow::bail!(
+                "{} changed while the edit was prepared; run the tool again",
+                change.diff_stats.file_path
+            );
+        }
+    }
+
+    for change in changes {
+        let absolute_path = change.path.to_string_lossy().to_string();
+        {
+            let mut history_tracker = FILE_HISTORY_TRACKER.lock().unwrap();
+            let externally_changed = history_tracker
+                .get_latest_version(&absolute_path)
+                .is_some_and(|v| v.content != change.original);
+            if externally_changed {
+                history_tracker.record_version(&absolute_path, change.original.clone());
+            }
+        }
+
+        checkpoint::record_before_change(&change.path);
+        file_activity::record_edited(&change.path);
+        fs::write(overlay::write_path(&change.path)?, &change.updated)
+            .with_context(|| format!("Failed to write {}", change.path.display()))?;
+
+        FILE_HISTORY_TRACKER
+            .lock()
+            .unwrap()
+            .record_version(&absolute_path, change.updated.clone());
+        let mut read_tracker = FILE_READ_TRACKER.lock().unwrap();
+        if read_tracker.has_been_read(&absolute_path) {
+            read_tracker.record_read(&absolute_path, change.updated.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Combined unified diff of all changed files
+pub fn preview(files: &[DiffStats]) -> String {
+    files
+        .iter()
+        .map(|f| f.unified_diff.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsp::protocol::Range;
+
+    fn edit(line: u32, start: u32, end: u32, text: &str) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position { line, character: start },
+                end: Position { line, character: end },
+            },
+            new_text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn applies_edits_with_utf16_columns() {
+        let content = "let a = 1;\nlet s = \"é😀\"; a + a\n";
+        let pos = position_at(content, 2, 15).unwrap();
+        assert_eq!(pos, Position { line: 1, character: 15 });
+
+        // Server order is not guaranteed to be sorted
+        let edits = vec![edit(1, 19, 20, "count"), edit(0, 4, 5, "count"), edit(1, 15, 16, "count")];
+        let out = apply_text_edits(content, &edits).unwrap();
+        assert_eq!(out, "let count = 1;\nlet s = \"é😀\"; count + count\n");
+
+        let overlapping = vec![edit(0, 0, 5, "x"), edit(0, 3, 6, "y")];
+        assert!(apply_text_edits(content, &overlapping).is_err());
+    }
+
+    #[test]
+    fn groups_edits_by_file_and_rejects_resource_operations() {
+        let edit_json = serde_json::json!({
+            "documentChanges": [
+                { "textDocument": { "uri": "file:///w/a.rs", "version": 3 },
+                  "edits": [{ "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } }, "newText": "b" }] },
+                { "textDocument": { "uri": "file:///w/a.rs", "version": 3 }, "edits": [] }
+            ]
+        });
+        let ws: WorkspaceEdit = serde_json::from_value(edit_json).unwrap();
+        let files = file_edits(&ws).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, PathBuf::from("/w/a.rs"));
+
+        let ws: WorkspaceEdit = serde_json::from_value(serde_json::json!({
+            "documentChanges": [{ "kind": "rename", "oldUri": "file:///w/a.rs", "newUri": "file:///w/b.rs" }]
+        }))
+        .unwrap();
+        assert!(file_edits(&ws).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Notifications for turns that need the user's attention.
+//!
+//! With `[notify] enabled`, a turn finishing after at least `min_turn_secs`
+//! and a tool call waiting for confirmation (or a question of the ask_user
+//! tool) run the configured actions: a shell command with the details in
+//! `CARRYCODE_NOTIFY_*` variables and/or a JSON POST to `webhook_url`. Hosts
+//! that know whether the user is looking report it with `set_user_present`;
+//! with `only_when_away` nothing runs while they are. Actions run on their
+//! own thread so the turn never waits on them.
+
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex as StdMutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::config::NotifyConfig;
+use crate::health::{self, Subsystem};
+use crate::session::types::{CoreEvent, CoreEventType};
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+static USER_PRESENT: AtomicBool = AtomicBool::new(false);
+
+static CONFIG: LazyLock<StdMutex<NotifyConfig>> = LazyLock::new(|| StdMutex::new(NotifyConfig::default()));
+
+#[derive(Debug, Clone, PartialEq)]
+struct Notification {
+    /// "turn_end" | "confirmation" | "question"
+    event: &'static str,
+    session_id: String,
+    message: String,
+    duration_ms: Option<u32>,
+}
+
+pub fn configure(config: &NotifyConfig) {
+    if let Ok(mut current) = CONFIG.lock() {
+        *current = config.clone();
+    }
+}
+
+/// Whether the user is looking at the session, as reported by the host
+pub fn set_user_present(present: bool) {
+    USER_PRESENT.store(present, Ordering::SeqCst);
+}
+
+/// Run the notification actions for `event`, if it calls for any
+pub fn on_event(event: &CoreEvent) {
+    let Some(config) = CONFIG.lock().ok().filter(|c| c.enabled).map(|c| c.clone()) else {
+        return;
+    };
+    let Some(notification) = notification_for(event, &config, USER_PRESENT.load(Ordering::SeqCst)) else {
+        return;
+    };
+    let spawned = thread::Builder::new()
+        .name("notifier".to_string())
+        .spawn(move || dispatch(&config, &notification));
+    if let Err(e) = spawned {
+        log::warn!("Notifier failed to start: {}", e);
+    }
+}
+
+fn notification_for(event: &CoreEvent, config: &NotifyConfig, user_present: bool) -> Option<Notification> {
+    if config.only_when_away && user_present {
+        return None;
+    }
+    let (kind, message, duration_ms) = match event.event_type {
+        CoreEventType::Usage if config.on_turn_end => {
+            let duration_ms = event.turn_timing.as_ref()?.duration_ms;
+            if u64::from(duration_ms) < config.min_turn_secs.saturating_mul(1000) {
+                return None;
+            }
+            let message = format!("Turn finished after {}s", duration_ms / 1000);
+            ("turn_end", message, Some(duration_ms))
+        }
+        CoreEventType::ConfirmationRequested if config.on_confirmation => {
+            let confirm = event.confirm.as_ref()?;
+            let message = format!("Confirmation needed: {} {}", confirm.tool_display_name, confirm.summary);
+            ("confirmation", message, None)
+        }
+        CoreEventType::UserInputRequested if config.on_confirmation => {
+            let question = event.question.as_ref()?;
+            ("question", format!("Question: {}", question.question), None)
+        }
+        _ => return None,
+    };
+    Some(Notification {
+        event: kind,
+        session_id: event.session_id.clone(),
+        message: message.trim().to_string(),
+        duration_ms,
+    })
+}
+
+fn dispatch(config: &NotifyConfig, notification: &Notification) {
+    let command = config.command.trim();
+    if !command.is_empty() {
+        if let Err(e) = run_command(command, notification) {
+            health::record_error(Subsystem::Notifier, format!("command failed: {:#}", e));
+        }
+    }
+    let webhook_url = config.webhook_url.trim();
+    if !webhook_url.is_empty() {
+        if let Err(e) = post_webhook(webhook_url, notification) {
+            health::record_error(Subsystem::Notifier, format!("webhook failed: {:#}", e));
+        }
+    }
+}
+
+fn run_command(command: &str, notification: &Notification) -> anyhow::Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("CARRYCODE_NOTIFY_EVENT", notification.event)
+        .env("CARRYCODE_NOTIFY_SESSION_ID", &notification.session_id)
+        .env("CARRYCODE_NOTIFY_MESSAGE", &notification.message)
+        .env(
+            "CARRYCODE_NOTIFY_DURATION_MS",
+            notification.duration_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+        )
+        .status()?;
+    if !status.success() {
+        anyhow::bail!("exited with {}", status);
+    }
+    Ok(())
+}
+
+fn post_webhook(url: &str, notification: &Notification) -> anyhow::Result<()> {
+    reqwest::blocking::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()?
+        .post(url)
+        .json(&json!({
+            "event": notification.event,
+            "session_id": notification.session_id,
+            "message": notification.message,
+            "duration_ms": notification.duration_ms,
+        }))
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::types::{CoreConfirmationRequest, CoreTurnTiming};
+
+    fn event(event_type: CoreEventType) -> CoreEvent {
+        CoreEvent {
+            session_id: "s1".to_string(),
+            ts_ms: 0,
+            event_type,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn notifies_long_turns_and_confirmations_while_away() {
+        let config = NotifyConfig {
+            enabled: true,
+            min_turn_secs: 30,
+            ..NotifyConfig::default()
+        };
+        let usage = |duration_ms: u32| CoreEvent {
+            turn_timing: Some(CoreTurnTiming {
+                provider: "openai".to_string(),
+                model: "m".to_string(),
+                started_at_ms: 0,
+                first_token_ms: None,
+                duration_ms,
+                model_ms: 0,
+                tool_ms: 0,
+            }),
+            ..event(CoreEventType::Usage)
+        };
+        let long = notification_for(&usage(95_000), &config, false).unwrap();
+        assert_eq!((long.event, long.message.as_str()), ("turn_end", "Turn finished after 95s"));
+        assert_eq!(long.duration_ms, Some(95_000));
+        assert!(notification_for(&usage(5_000), &config, false).is_none());
+        assert!(notification_for(&usage(95_000), &config, true).is_none());
+        let present_ok = NotifyConfig { only_when_away: false, ..config.clone() };
+        assert!(notification_for(&usage(95_000), &present_ok, true).is_some());
+
+        let confirmation = CoreEvent {
+            confirm: Some(CoreConfirmationRequest {
+                request_id: "r1".to_string(),
+                tool_name: "bash".to_string(),
+                tool_display_name: "bash".to_string(),
+                arguments: "{}".to_string(),
+                summary: "cargo test".to_string(),
+                kind: "Execute".to_string(),
+                key_path: String::new(),
+                preview: None,
+            }),
+            ..event(CoreEventType::ConfirmationRequested)
+        };
+        let asked = notification_for(&confirmation, &config, false).unwrap();
+        assert_eq!(asked.message, "Confirmation needed: bash cargo test");
+        let quiet = NotifyConfig { on_confirmation: false, ..config };
+        assert!(notification_for(&confirmation, &quiet, false).is_none());
+        assert!(notification_for(&event(CoreEventType::End), &NotifyConfig::default(), false).is_none());
+    }
+}
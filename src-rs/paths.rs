@@ -0,0 +1,211 @@
+//! Where CarryCode keeps its files outside the workspace.
+//!
+//! `CARRY_HOME`, when set, holds everything in the legacy `~/.carry` layout.
+//! Otherwise the platform's locations are used: on Linux the XDG base
+//! directories (`$XDG_CONFIG_HOME/carry` for config, `$XDG_DATA_HOME/carry`
+//! for sessions and language servers, `$XDG_STATE_HOME/carry` for logs), on
+//! Windows `%APPDATA%\carry` and `%LOCALAPPDATA%\carry`, and on macOS
+//! `~/Library/Application Support/carry`. Files left in `~/.carry` by
+//! earlier versions are moved over the first time any path is resolved.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+/// Overrides every location below, e.g. for portable installs or tests
+pub const HOME_ENV: &str = "CARRY_HOME";
+
+/// Directory name under the platform's config, data and state directories
+const APP_DIR: &str = "carry";
+
+/// Directory used before the platform locations, relative to the home directory
+const LEGACY_DIR: &str = ".carry";
+
+const USER_CONFIG_FILE: &str = "carrycode.json";
+const RUNTIME_CONFIG_FILE: &str = "carrycode-runtime.json";
+
+static MIGRATE: Once = Once::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Config,
+    Data,
+    State,
+}
+
+/// What earlier versions kept in `~/.carry`, and where it lives now
+const LEGACY_ENTRIES: [(&str, Kind); 4] = [
+    (USER_CONFIG_FILE, Kind::Config),
+    (RUNTIME_CONFIG_FILE, Kind::Config),
+    ("sessions", Kind::Data),
+    ("lsp", Kind::Data),
+];
+
+fn carry_home() -> Option<PathBuf> {
+    env::var_os(HOME_ENV).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+fn resolve(kind: Kind) -> Option<PathBuf> {
+    if let Some(home) = carry_home() {
+        return Some(home);
+    }
+    let base = match kind {
+        Kind::Config => dirs::config_dir(),
+        Kind::Data => dirs::data_dir(),
+        // Only Linux has a state directory
+        Kind::State => dirs::state_dir().or_else(dirs::data_local_dir),
+    };
+    base.map(|base| base.join(APP_DIR))
+}
+
+fn dir(kind: Kind) -> Option<PathBuf> {
+    MIGRATE.call_once(|| {
+        if let Err(e) = migrate_legacy() {
+            log::warn!("Failed to move files from ~/{}: {}", LEGACY_DIR, e);
+        }
+    });
+    resolve(kind)
+}
+
+/// User and runtime config, and user-level skills
+pub fn config_dir() -> Option<PathBuf> {
+    dir(Kind::Config)
+}
+
+/// Saved sessions and installed language servers
+pub fn data_dir() -> Option<PathBuf> {
+    dir(Kind::Data)
+}
+
+/// Logs
+pub fn state_dir() -> Option<PathBuf> {
+    dir(Kind::State)
+}
+
+/// `carrycode.json`, the user's providers and preferences
+pub fn user_config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(USER_CONFIG_FILE))
+}
+
+/// `carrycode-runtime.json`, state such as the default model and trusted workspaces
+pub fn runtime_config_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(RUNTIME_CONFIG_FILE))
+}
+
+/// User-level skill files
+pub fn skills_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("skills"))
+}
+
+pub fn sessions_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("sessions"))
+}
+
+pub fn lsp_dir() -> Option<PathBuf> {
+    data_dir().map(|dir| dir.join("lsp"))
+}
+
+pub fn log_dir() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join("logs"))
+}
+
+/// Move what `~/.carry` holds to the current locations, skipping entries that
+/// already exist there. Returns the moved entries' new paths. Nothing is moved
+/// while `CARRY_HOME` is set.
+pub fn migrate_legacy() -> io::Result<Vec<PathBuf>> {
+    if carry_home().is_some() {
+        return Ok(Vec::new());
+    }
+    let Some(legacy) = dirs::home_dir().map(|home| home.join(LEGACY_DIR)) else {
+        return Ok(Vec::new());
+    };
+    let targets: Vec<(&str, PathBuf)> = LEGACY_ENTRIES
+        .iter()
+        .filter_map(|(name, kind)| Some((*name, resolve(*kind)?.join(name))))
+        .collect();
+    migrate_from(&legacy, &targets)
+}
+
+fn migrate_from(legacy: &Path, targets: &[(&str, PathBuf)]) -> io::Result<Vec<PathBuf>> {
+    let mut moved = Vec::new();
+    if !legacy.is_dir() {
+        return Ok(moved);
+    }
+    for (name, target) in targets {
+        let source = legacy.join(name);
+        if !source.exists() || target.exists() || &source == target {
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // A rename fails across file systems; copy and remove instead
+        if fs::rename(&source, target).is_err() {
+            copy_recursive(&source, target)?;
+            remove(&source)?;
+        }
+        log::info!("Moved {} to {}", source.display(), target.display());
+        moved.push(target.clone());
+    }
+    // Leave the directory if it holds anything else
+    let _ = fs::remove_dir(legacy);
+    Ok(moved)
+}
+
+fn copy_recursive(source: &Path, target: &Path) -> io::Result<()> {
+    if !source.is_dir() {
+        return fs::copy(source, target).map(|_| ());
+    }
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        copy_recursive(&entry.path(), &target.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn moves_legacy_entries_without_overwriting() {
+        let root = FakeWorkspace::new().build().unwrap();
+        let legacy = root.join(".carry");
+        fs::create_dir_all(legacy.join("sessions/default/s1")).unwrap();
+        fs::write(legacy.join("sessions/default/s1/snapshot.json"), "{}").unwrap();
+        fs::write(legacy.join(USER_CONFIG_FILE), "old").unwrap();
+        fs::write(legacy.join(RUNTIME_CONFIG_FILE), "runtime").unwrap();
+        let (config, data) = (root.join("config/carry"), root.join("data/carry"));
+        fs::create_dir_all(&config).unwrap();
+        fs::write(config.join(USER_CONFIG_FILE), "new").unwrap();
+
+        let targets = [
+            (USER_CONFIG_FILE, config.join(USER_CONFIG_FILE)),
+            (RUNTIME_CONFIG_FILE, config.join(RUNTIME_CONFIG_FILE)),
+            ("sessions", data.join("sessions")),
+            ("lsp", data.join("lsp")),
+        ];
+        let moved = migrate_from(&legacy, &targets).unwrap();
+        assert_eq!(moved, vec![config.join(RUNTIME_CONFIG_FILE), data.join("sessions")]);
+        assert!(data.join("sessions/default/s1/snapshot.json").exists());
+        assert_eq!(fs::read_to_string(config.join(USER_CONFIG_FILE)).unwrap(), "new");
+        // The entry that was not moved keeps the legacy directory
+        assert!(legacy.join(USER_CONFIG_FILE).exists());
+        assert!(!legacy.join("sessions").exists());
+
+        copy_recursive(&data.join("sessions"), &root.join("copy")).unwrap();
+        assert!(root.join("copy/default/s1/snapshot.json").exists());
+    }
+}
@@ -0,0 +1,334 @@
+//! Checks of the environment, run once when the first session opens.
+//!
+//! A read-only data directory, a missing `rg` or a clock far off the
+//! provider's otherwise surface later as confusing tool or request
+//! failures. `run` looks for them up front and returns one `Finding` per
+//! check, with a hint on how to fix it; the welcome screen lists those that
+//! are not ok. The first result is kept, so a host asking after the session
+//! opened gets it without probing again.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::paths;
+use crate::session::environment_probe::find_executable;
+
+/// How long the provider gets to answer
+const PROVIDER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Clock difference to the provider above which signed requests and TLS may fail
+const MAX_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// Free space under which logs and snapshots may stop being written
+const LOW_DISK_BYTES: u64 = 500 * 1024 * 1024;
+const CRITICAL_DISK_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Executables the tools run, how bad it is when one is missing, and what to do
+const EXECUTABLES: [(&str, Status, &str); 3] = [
+    ("bash", Status::Error, "Install bash; the bash tool runs every command through it"),
+    ("rg", Status::Warning, "Install ripgrep (rg) so searches do not fall back to a slower scan"),
+    ("git", Status::Warning, "Install git to use diffs, checkpoints and branch information"),
+];
+
+static FIRST_RUN: OnceLock<Vec<Finding>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+    /// The check does not apply, e.g. no provider is configured
+    Skipped,
+}
+
+impl Status {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Warning => "warning",
+            Status::Error => "error",
+            Status::Skipped => "skipped",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// e.g. "data_dir", "executable:rg", "provider", "clock", "disk"
+    pub check: String,
+    pub status: Status,
+    pub message: String,
+    /// What the user can do about it, unless the check passed
+    pub remediation: Option<String>,
+}
+
+impl Finding {
+    fn ok(check: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            check: check.into(),
+            status: Status::Ok,
+            message: message.into(),
+            remediation: None,
+        }
+    }
+
+    fn problem(check: impl Into<String>, status: Status, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            check: check.into(),
+            status,
+            message: message.into(),
+            remediation: Some(fix.into()),
+        }
+    }
+}
+
+/// The findings of the first run in this process, running it if none has
+pub fn run_once(config: &AppConfig) -> Vec<Finding> {
+    FIRST_RUN.get_or_init(|| run(config)).clone()
+}
+
+/// Run every check now. Blocks for up to `PROVIDER_TIMEOUT` on the provider.
+pub fn run(config: &AppConfig) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    for (check, dir) in [
+        ("config_dir", paths::config_dir()),
+        ("data_dir", paths::data_dir()),
+        ("log_dir", paths::log_dir()),
+    ] {
+        // Under CARRY_HOME these are all the same directory
+        if let Some(dir) = &dir {
+            if seen.contains(dir) {
+                continue;
+            }
+            seen.push(dir.clone());
+        }
+        findings.push(writable(check, dir.as_deref()));
+    }
+
+    let path = env::var_os("PATH").unwrap_or_default();
+    for (name, missing, fix) in EXECUTABLES {
+        let check = format!("executable:{}", name);
+        findings.push(match find_executable(name, &path) {
+            Some(found) => Finding::ok(check, found.display().to_string()),
+            None => Finding::problem(check, missing, format!("{} was not found on the PATH", name), fix),
+        });
+    }
+
+    findings.extend(provider(config));
+    findings.push(disk_finding(paths::data_dir().as_deref().and_then(free_bytes)));
+    for finding in findings.iter().filter(|f| f.status != Status::Ok && f.status != Status::Skipped) {
+        log::warn!("Preflight {} {}: {}", finding.check, finding.status.as_str(), finding.message);
+    }
+    findings
+}
+
+/// Whether a file can be created in `dir`, creating it if needed
+fn writable(check: &str, dir: Option<&Path>) -> Finding {
+    let Some(dir) = dir else {
+        return Finding::problem(
+            check,
+            Status::Error,
+            "the home directory could not be determined",
+            format!("Set HOME, or {} to a writable directory", paths::HOME_ENV),
+        );
+    };
+    let probe = dir.join(format!(".preflight-{}", std::process::id()));
+    let result = fs::create_dir_all(dir)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(()) => Finding::ok(check, dir.display().to_string()),
+        Err(e) => Finding::problem(
+            check,
+            Status::Error,
+            format!("{} is not writable: {}", dir.display(), e),
+            format!(
+                "Fix the permissions of {}, or set {} to a writable directory",
+                dir.display(),
+                paths::HOME_ENV
+            ),
+        ),
+    }
+}
+
+/// Base URL of the provider of the default model
+fn provider_url(config: &AppConfig) -> Option<String> {
+    let named = config
+        .default_model
+        .as_deref()
+        .and_then(|m| config.resolve_model(m))
+        .and_then(|(provider, _)| config.providers.iter().find(|p| p.name == provider))
+        .map(|p| p.base_url.clone());
+    named
+        .or_else(|| config.llm_provider.as_ref().map(|p| p.base_url.clone()))
+        .or_else(|| config.providers.first().map(|p| p.base_url.clone()))
+        .filter(|url| !url.trim().is_empty())
+}
+
+/// Reachability of the provider, and the clock skew its `Date` header shows
+fn provider(config: &AppConfig) -> Vec<Finding> {
+    let Some(url) = provider_url(config) else {
+        return vec![
+            Finding {
+                check: "provider".to_string(),
+                status: Status::Skipped,
+                message: "no provider is configured".to_string(),
+                remediation: Some("Add a provider in the settings or carrycode.json".to_string()),
+            },
+            Finding {
+                check: "clock".to_string(),
+                status: Status::Skipped,
+                message: "no provider to compare with".to_string(),
+                remediation: None,
+            },
+        ];
+    };
+    let sent_ms = chrono::Utc::now().timestamp_millis();
+    let response = reqwest::blocking::Client::builder()
+        .timeout(PROVIDER_TIMEOUT)
+        .build()
+        .and_then(|client| client.get(&url).send());
+    // Any answer, even 401 or 404, means the provider can be reached
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            return vec![
+                Finding::problem(
+                    "provider",
+                    Status::Warning,
+                    format!("{} could not be reached: {}", url, e),
+                    "Check the network, proxy settings (HTTPS_PROXY) and the provider's base_url",
+                ),
+                Finding {
+                    check: "clock".to_string(),
+                    status: Status::Skipped,
+                    message: "the provider did not answer".to_string(),
+                    remediation: None,
+                },
+            ];
+        }
+    };
+    let received_ms = chrono::Utc::now().timestamp_millis();
+    let skew = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| clock_skew_secs(date, sent_ms + (received_ms - sent_ms) / 2));
+    vec![
+        Finding::ok("provider", format!("{} answered in {} ms", url, received_ms - sent_ms)),
+        clock_finding(skew),
+    ]
+}
+
+/// Seconds the local clock is ahead of an HTTP `Date` header read at `local_ms`
+fn clock_skew_secs(date: &str, local_ms: i64) -> Option<i64> {
+    let server = chrono::DateTime::parse_from_rfc2822(date.trim()).ok()?;
+    Some((local_ms - server.timestamp_millis()) / 1000)
+}
+
+fn clock_finding(skew_secs: Option<i64>) -> Finding {
+    match skew_secs {
+        None => Finding {
+            check: "clock".to_string(),
+            status: Status::Skipped,
+            message: "the provider sent no usable Date header".to_string(),
+            remediation: None,
+        },
+        Some(skew) if skew.abs() > MAX_CLOCK_SKEW_SECS => Finding::problem(
+            "clock",
+            Status::Warning,
+            format!(
+                "the system clock is {} s {} the provider's",
+                skew.abs(),
+                if skew > 0 { "ahead of" } else { "behind" }
+            ),
+            "Enable network time sync (NTP) so requests and certificates are not rejected",
+        ),
+        Some(skew) => Finding::ok("clock", format!("within {} s of the provider", skew.abs())),
+    }
+}
+
+fn disk_finding(free: Option<u64>) -> Finding {
+    let fix = "Free up disk space, or delete old sessions and logs";
+    match free {
+        None => Finding {
+            check: "disk".to_string(),
+            status: Status::Skipped,
+            message: "free space could not be determined".to_string(),
+            remediation: None,
+        },
+        Some(bytes) if bytes < CRITICAL_DISK_BYTES => Finding::problem(
+            "disk",
+            Status::Error,
+            format!("only {} MB free for sessions and logs", bytes / (1024 * 1024)),
+            fix,
+        ),
+        Some(bytes) if bytes < LOW_DISK_BYTES => Finding::problem(
+            "disk",
+            Status::Warning,
+            format!("{} MB free for sessions and logs", bytes / (1024 * 1024)),
+            fix,
+        ),
+        Some(bytes) => Finding::ok("disk", format!("{} MB free", bytes / (1024 * 1024))),
+    }
+}
+
+/// Bytes available to this user on the filesystem holding `path`, or its
+/// nearest existing ancestor
+#[cfg(unix)]
+fn free_bytes(path: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let c_path = CString::new(existing.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is NUL-terminated and stat is a valid, writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    #[allow(clippy::unnecessary_cast)]
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_bytes(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn findings_carry_a_fix_for_each_problem() {
+        let now_ms = chrono::DateTime::parse_from_rfc2822("Sat, 17 Oct 2026 10:00:00 GMT")
+            .unwrap()
+            .timestamp_millis();
+        assert_eq!(clock_skew_secs("Sat, 17 Oct 2026 09:50:00 GMT", now_ms), Some(600));
+        assert_eq!(clock_skew_secs("yesterday", now_ms), None);
+        assert_eq!(clock_finding(Some(-600)).status, Status::Warning);
+        assert!(clock_finding(Some(-600)).message.contains("behind"));
+        assert_eq!(clock_finding(Some(30)).status, Status::Ok);
+        assert_eq!(clock_finding(None).status, Status::Skipped);
+
+        assert_eq!(disk_finding(Some(10 * 1024 * 1024)).status, Status::Error);
+        assert_eq!(disk_finding(Some(100 * 1024 * 1024)).status, Status::Warning);
+        assert_eq!(disk_finding(Some(10 * 1024 * 1024 * 1024)).status, Status::Ok);
+        assert!(disk_finding(Some(0)).remediation.is_some());
+        assert!(disk_finding(Some(u64::MAX)).remediation.is_none());
+
+        let workspace = FakeWorkspace::new().build().unwrap();
+        let dir = workspace.join("data");
+        assert_eq!(writable("data_dir", Some(&dir)).status, Status::Ok);
+        assert_eq!(writable("data_dir", None).status, Status::Error);
+        #[cfg(unix)]
+        assert!(free_bytes(&dir.join("missing")).is_some());
+    }
+}
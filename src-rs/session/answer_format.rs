@@ -0,0 +1,184 @@
+//! Post-processing of the final answer of a turn, per `[answer_format]`.
+//!
+//! Outside code blocks, absolute paths into the workspace are rewritten as
+//! workspace-relative and heading levels are renumbered to start at
+//! `top_heading_level` without gaps. `file:line` references to files that
+//! exist, code blocks included, are reported with their position in the
+//! processed text so hosts can make them clickable. The End event carries the
+//! processed answer and its references; the streamed text is left as it was.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::config::AnswerFormatConfig;
+
+use super::types::CoreFileReference;
+
+/// `path:line` or `path:line:column`, after a boundary character
+static FILE_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(concat!(
+        r"(?m)(?:^|[\s(\[`'\x22])",
+        r"((?:\.{0,2}/)?[\w@.+-]+(?:/[\w@.+-]+)*\.[A-Za-z0-9]+)",
+        r":(\d+)(?::(\d+))?"
+    ))
+    .unwrap()
+});
+
+pub struct FormattedAnswer {
+    pub content: String,
+    pub references: Vec<CoreFileReference>,
+}
+
+/// The answer `content` as `config` asks, for the workspace at `root`
+pub fn process(content: &str, root: &Path, config: &AnswerFormatConfig) -> FormattedAnswer {
+    if !config.enabled {
+        return FormattedAnswer {
+            content: content.to_string(),
+            references: Vec::new(),
+        };
+    }
+    let mut content = content.to_string();
+    if config.relative_paths {
+        content = map_prose(&content, |line| relative_paths(line, root));
+    }
+    if config.normalize_headings {
+        content = normalize_headings(&content, config.top_heading_level);
+    }
+    let references = if config.file_references {
+        file_references(&content, root)
+    } else {
+        Vec::new()
+    };
+    FormattedAnswer { content, references }
+}
+
+/// Apply `f` to the lines outside fenced code blocks
+fn map_prose(content: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut in_fence = false;
+    content
+        .split('\n')
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+                return line.to_string();
+            }
+            if in_fence {
+                line.to_string()
+            } else {
+                f(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn heading_level(line: &str) -> Option<usize> {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[hashes..];
+    ((1..=6).contains(&hashes) && (rest.is_empty() || rest.starts_with(' '))).then_some(hashes)
+}
+
+/// Renumber heading levels so the shallowest is `top` and none is skipped
+fn normalize_headings(content: &str, top: u8) -> String {
+    let mut levels = BTreeSet::new();
+    map_prose(content, |line| {
+        levels.extend(heading_level(line));
+        line.to_string()
+    });
+    let top = usize::from(top.clamp(1, 6));
+    map_prose(content, |line| match heading_level(line) {
+        Some(level) => {
+            let rank = levels.iter().position(|&l| l == level).unwrap_or(0);
+            format!("{}{}", "#".repeat((top + rank).min(6)), &line[level..])
+        }
+        None => line.to_string(),
+    })
+}
+
+/// `line` with `<root>/x` written as `x`, where `<root>` is not itself part
+/// of a longer path
+fn relative_paths(line: &str, root: &Path) -> String {
+    let prefix = format!("{}/", root.to_string_lossy().trim_end_matches('/'));
+    if prefix.len() < 2 {
+        return line.to_string();
+    }
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(pos) = rest.find(&prefix) {
+        let inside_path = rest[..pos]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || "/._-~".contains(c));
+        out.push_str(&rest[..pos]);
+        if inside_path {
+            out.push_str(&prefix);
+        }
+        rest = &rest[pos + prefix.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// References to existing files, with character offsets into `content`
+fn file_references(content: &str, root: &Path) -> Vec<CoreFileReference> {
+    let char_index = |byte: usize| content[..byte].chars().count() as u32;
+    FILE_REFERENCE
+        .captures_iter(content)
+        .filter_map(|caps| {
+            let path = caps.get(1)?;
+            let absolute = root.join(path.as_str());
+            if !absolute.is_file() {
+                return None;
+            }
+            let end = caps.get(0)?.end();
+            Some(CoreFileReference {
+                path: path.as_str().to_string(),
+                absolute_path: absolute.to_string_lossy().to_string(),
+                line: caps.get(2)?.as_str().parse().ok()?,
+                column: caps.get(3).and_then(|c| c.as_str().parse().ok()),
+                start_index: char_index(path.start()),
+                end_index: char_index(end),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_paths_and_headings_and_finds_references() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let config = AnswerFormatConfig::default();
+        let answer = format!(
+            "# Fix\nSee {root}/Cargo.toml:3 and ~{root}/x.\n### Details\n\
+             ```\n{root}/Cargo.toml\n# not a heading\n```\n\
+             Changed `Cargo.toml:1:5`, not missing.rs:2 or localhost:8080.",
+            root = root.display()
+        );
+        let out = process(&answer, root, &config);
+        assert_eq!(
+            out.content,
+            format!(
+                "## Fix\nSee Cargo.toml:3 and ~{root}/x.\n### Details\n\
+                 ```\n{root}/Cargo.toml\n# not a heading\n```\n\
+                 Changed `Cargo.toml:1:5`, not missing.rs:2 or localhost:8080.",
+                root = root.display()
+            )
+        );
+        let found: Vec<_> = out.references.iter().map(|r| (r.path.as_str(), r.line, r.column)).collect();
+        assert_eq!(found, [("Cargo.toml", 3, None), ("Cargo.toml", 1, Some(5))]);
+        let first = &out.references[0];
+        let span = first.start_index as usize..first.end_index as usize;
+        let text: String = out.content.chars().skip(span.start).take(span.len()).collect();
+        assert_eq!(text, "Cargo.toml:3");
+
+        let off = AnswerFormatConfig { enabled: false, ..config };
+        assert_eq!(process(&answer, root, &off).content, answer);
+    }
+}
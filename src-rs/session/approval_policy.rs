@@ -1,14 +1,115 @@
+use crate::llm::tools::bash;
 use crate::llm::tools::tool_trait::ToolKind;
+use crate::llm::utils::path_policy::PathPolicy;
+use crate::llm::utils::sensitive_files::SensitiveFiles;
+use crate::llm::utils::shell_safety::{self, CommandRisk};
+use serde_json::Value;
 
 use super::context::ApprovalMode;
 
-pub fn requires_confirmation(approval_mode: &ApprovalMode, kind: ToolKind) -> bool {
+/// Whether a call of `kind` needs the user's approval in `approval_mode`.
+/// Shell commands go by their `risk` instead of their kind: read-only ones run
+/// freely, mutating ones ask in read-only mode, and ones acting outside the
+/// workspace ask in every mode
+pub fn requires_confirmation(approval_mode: &ApprovalMode, kind: ToolKind, risk: Option<CommandRisk>) -> bool {
+    if risk == Some(CommandRisk::Escaping) {
+        return true;
+    }
     match approval_mode {
-        ApprovalMode::ReadOnly => matches!(
-            kind,
-            ToolKind::Edit | ToolKind::Delete | ToolKind::Move | ToolKind::Execute | ToolKind::Fetch | ToolKind::Other
-        ),
+        ApprovalMode::ReadOnly => match risk {
+            Some(risk) => risk > CommandRisk::ReadOnly,
+            None => matches!(
+                kind,
+                ToolKind::Edit
+                    | ToolKind::Delete
+                    | ToolKind::Move
+                    | ToolKind::Execute
+                    | ToolKind::Fetch
+                    | ToolKind::Other
+            ),
+        },
         ApprovalMode::Agent | ApprovalMode::AgentFull => false,
     }
 }
 
+/// Risk of a bash call's command, with `safe_commands` as the read-only list;
+/// None for other tools and for arguments without a command
+pub fn command_risk(tool_name: &str, args_json: &str, safe_commands: &[String]) -> Option<CommandRisk> {
+    if tool_name != "bash" {
+        return None;
+    }
+    let args = serde_json::from_str::<Value>(args_json).ok()?;
+    let command = args.get("command")?.as_str()?;
+    let root = PathPolicy::new()
+        .map(|p| p.root().to_path_buf())
+        .or_else(|_| std::env::current_dir())
+        .ok()?;
+    let cwd = bash::command_dir(&root, args.get("workdir").and_then(|v| v.as_str()));
+    Some(shell_safety::assess(command, safe_commands, &root, &cwd).risk)
+}
+
+/// Calls other than shell commands that need confirmation in every approval
+/// mode: a move that replaces an existing file, a recursive directory delete,
+/// a workspace-wide rename, or applying a code action
+pub fn is_destructive_call(tool_name: &str, args_json: &str) -> bool {
+    let Ok(args) = serde_json::from_str::<Value>(args_json) else {
+        return false;
+    };
+    match tool_name {
+        "move" => {
+            let overwrite = args.get("overwrite").and_then(|v| v.as_bool()).unwrap_or(false);
+            overwrite
+                && args
+                    .get("destination")
+                    .and_then(|v| v.as_str())
+                    .and_then(|d| PathPolicy::new().ok()?.resolve(d).ok())
+                    .is_some_and(|p| p.exists())
+        }
+        "delete" => {
+            let recursive = args.get("recursive").and_then(|v| v.as_bool()).unwrap_or(false);
+            recursive
+                && args
+                    .get("path")
+                    .and_then(|v| v.as_str())
+                    .and_then(|p| PathPolicy::new().ok()?.resolve(p).ok())
+                    .is_some_and(|p| p.is_dir())
+        }
+        "rename_symbol" => true,
+        "code_action" => args.get("action").is_some_and(|v| !v.is_null()),
+        _ => false,
+    }
+}
+
+/// Reads of a file matched by `[security] sensitive_files`, which need
+/// confirmation in every approval mode: `view` of one, `grep` with one as its
+/// path, `fetch` of a URL naming one
+pub fn is_sensitive_read(tool_name: &str, args_json: &str) -> bool {
+    let Ok(args) = serde_json::from_str::<Value>(args_json) else {
+        return false;
+    };
+    let path_arg = |name: &str| {
+        args.get(name)
+            .and_then(|v| v.as_str())
+            .and_then(|p| PathPolicy::new().ok()?.resolve(p).ok())
+    };
+    match tool_name {
+        "view" => path_arg("file_path").is_some_and(|p| SensitiveFiles::current().is_sensitive(&p)),
+        "grep" => path_arg("path").is_some_and(|p| SensitiveFiles::current().is_sensitive(&p)),
+        "fetch" => args
+            .get("url")
+            .and_then(|v| v.as_str())
+            .is_some_and(|url| SensitiveFiles::current().is_sensitive_url(url)),
+        _ => false,
+    }
+}
+
+/// Calls that propose mode holds back for review instead of running
+pub fn is_proposable(kind: ToolKind) -> bool {
+    matches!(kind, ToolKind::Edit | ToolKind::Delete | ToolKind::Move)
+}
+
+/// Tools that can compute their changes without applying them; the session
+/// runs them unconfirmed first and shows the preview in the confirmation request
+pub fn previews_before_confirmation(tool_name: &str) -> bool {
+    matches!(tool_name, "rename_symbol" | "code_action")
+}
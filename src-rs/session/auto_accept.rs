@@ -0,0 +1,87 @@
+//! Paths a session's edits are pre-approved for.
+//!
+//! In read-only approval mode every edit asks for confirmation, and in the
+//! agent modes none does. `set_auto_accept_paths` sits between the two: the
+//! user lists gitignore-style globs, relative to the workspace (`src/**`,
+//! `*.md`, `!src/generated/**`), and edit calls whose path they match run
+//! without asking. Edits elsewhere still ask, and so do calls that ask in
+//! every mode (destructive ones, reads of sensitive files). The scope lasts
+//! as long as the session is open.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+
+use crate::llm::tools::tool_trait::ToolKind;
+use crate::llm::utils::path_policy::PathPolicy;
+
+pub struct AutoAcceptScope {
+    globs: Vec<String>,
+    root: PathBuf,
+    matcher: Gitignore,
+}
+
+impl AutoAcceptScope {
+    /// Scope of `globs` in the workspace at `root`; fails on an invalid glob
+    pub fn new(root: &Path, globs: Vec<String>) -> Result<Self> {
+        let mut builder = GitignoreBuilder::new(root);
+        for glob in &globs {
+            builder
+                .add_line(None, glob)
+                .map_err(|e| anyhow!("Invalid auto-accept glob '{}': {}", glob, e))?;
+        }
+        let matcher = builder.build().map_err(|e| anyhow!("Invalid auto-accept globs: {}", e))?;
+        Ok(Self {
+            globs,
+            root: root.to_path_buf(),
+            matcher,
+        })
+    }
+
+    pub fn globs(&self) -> &[String] {
+        &self.globs
+    }
+
+    /// Whether `path`, absolute, is or lies in a directory the globs match
+    pub fn covers(&self, path: &Path) -> bool {
+        path.starts_with(&self.root) && self.matcher.matched_path_or_any_parents(path, path.is_dir()).is_ignore()
+    }
+
+    /// Whether an edit call with this key path is pre-approved
+    pub fn accepts(&self, kind: ToolKind, key_path: &str) -> bool {
+        if kind != ToolKind::Edit || key_path == "*" {
+            return false;
+        }
+        PathPolicy::with_root(self.root.clone())
+            .resolve(key_path)
+            .is_ok_and(|path| self.covers(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_matching_paths_inside_the_workspace_only() {
+        let root = Path::new("/work/app");
+        let globs = vec!["src/**".to_string(), "*.md".to_string(), "!src/generated/**".to_string()];
+        let scope = AutoAcceptScope::new(root, globs.clone()).unwrap();
+        assert_eq!(scope.globs(), globs.as_slice());
+
+        assert!(scope.covers(Path::new("/work/app/src/main.rs")));
+        assert!(scope.covers(Path::new("/work/app/src/ui/view.rs")));
+        assert!(scope.covers(Path::new("/work/app/docs/guide.md")));
+        assert!(!scope.covers(Path::new("/work/app/src/generated/api.rs")));
+        assert!(!scope.covers(Path::new("/work/app/Cargo.toml")));
+        assert!(!scope.covers(Path::new("/elsewhere/src/main.rs")));
+
+        assert!(scope.accepts(ToolKind::Edit, "/work/app/src/lib.rs"));
+        assert!(!scope.accepts(ToolKind::Edit, "/work/app/src/../Cargo.toml"));
+        assert!(!scope.accepts(ToolKind::Execute, "/work/app/src/lib.rs"));
+        assert!(!scope.accepts(ToolKind::Edit, "*"));
+
+        assert!(AutoAcceptScope::new(root, vec!["src/{a".to_string()]).is_err());
+    }
+}
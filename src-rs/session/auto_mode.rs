@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+
+use crate::llm::agents::agent::ToolExecutionResult;
+
+/// Limits of a time-boxed autonomous run
+#[derive(Debug, Clone, Copy)]
+pub struct AutoRunConfig {
+    /// Wall-clock budget for the whole run
+    pub budget: Duration,
+    /// Minimum time between progress checkpoints
+    pub checkpoint_interval: Duration,
+    /// How long a checkpoint waits for a steer message before continuing
+    pub steer_window: Duration,
+}
+
+#[derive(Debug)]
+struct Progress {
+    last_checkpoint: Instant,
+    /// Tool results of the turn already covered by a checkpoint
+    reported_tools: usize,
+    checkpoints: u32,
+}
+
+/// State of an autonomous run, shared between the session and its checkpoint callback
+#[derive(Debug)]
+pub struct AutoRun {
+    pub config: AutoRunConfig,
+    pub started_at: Instant,
+    progress: StdMutex<Progress>,
+    steers: StdMutex<Vec<String>>,
+    steer_ready: Notify,
+}
+
+/// Outcome of reaching a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckpointStatus {
+    /// Not yet time for a checkpoint
+    Skip,
+    /// Checkpoint with the progress summary
+    Report(String),
+    /// Budget used up; summary of the whole run
+    BudgetExhausted(String),
+}
+
+impl AutoRun {
+    pub fn new(config: AutoRunConfig) -> Self {
+        let now = Instant::now();
+        Self {
+            config,
+            started_at: now,
+            progress: StdMutex::new(Progress {
+                last_checkpoint: now,
+                reported_tools: 0,
+                checkpoints: 0,
+            }),
+            steers: StdMutex::new(Vec::new()),
+            steer_ready: Notify::new(),
+        }
+    }
+
+    pub fn checkpoints(&self) -> u32 {
+        self.progress.lock().map(|p| p.checkpoints).unwrap_or(0)
+    }
+
+    /// Decide whether `now` is a checkpoint and summarize the tools run since the last one
+    pub fn checkpoint(&self, tool_results: &[ToolExecutionResult], now: Instant) -> CheckpointStatus {
+        let Ok(mut progress) = self.progress.lock() else {
+            return CheckpointStatus::Skip;
+        };
+        let elapsed = now.duration_since(self.started_at);
+        let exhausted = elapsed >= self.config.budget;
+        if !exhausted && now.duration_since(progress.last_checkpoint) < self.config.checkpoint_interval {
+            return CheckpointStatus::Skip;
+        }
+
+        let since = progress.reported_tools.min(tool_results.len());
+        let summary = summarize(
+            progress.checkpoints + 1,
+            &tool_results[since..],
+            elapsed,
+            self.config.budget,
+        );
+        progress.last_checkpoint = now;
+        progress.reported_tools = tool_results.len();
+        progress.checkpoints += 1;
+
+        if exhausted {
+            CheckpointStatus::BudgetExhausted(summary)
+        } else {
+            CheckpointStatus::Report(summary)
+        }
+    }
+
+    /// Queue a steer message for the next checkpoint
+    pub fn steer(&self, message: String) {
+        if let Ok(mut steers) = self.steers.lock() {
+            steers.push(message);
+        }
+        self.steer_ready.notify_one();
+    }
+
+    /// Take queued steer messages, waiting up to the steer window if there are none
+    pub async fn wait_for_steer(&self) -> Option<String> {
+        if let Some(message) = self.take_steers() {
+            return Some(message);
+        }
+        let _ = tokio::time::timeout(self.config.steer_window, self.steer_ready.notified()).await;
+        self.take_steers()
+    }
+
+    fn take_steers(&self) -> Option<String> {
+        let mut steers = self.steers.lock().ok()?;
+        if steers.is_empty() {
+            return None;
+        }
+        Some(std::mem::take(&mut *steers).join("\n\n"))
+    }
+}
+
+fn format_minutes(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else {
+        format!("{}m", secs / 60)
+    }
+}
+
+/// One-line progress summary, e.g.
+/// "Checkpoint 2 at 10m: 5 tool calls since last checkpoint (bash ×3, edit ×2; 1 failed). 50m of 60m budget left."
+fn summarize(
+    number: u32,
+    results: &[ToolExecutionResult],
+    elapsed: Duration,
+    budget: Duration,
+) -> String {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for r in results {
+        *counts.entry(r.tool_name.as_str()).or_default() += 1;
+    }
+    let failed = results.iter().filter(|r| !r.success).count();
+
+    let mut text = format!(
+        "Checkpoint {} at {}: {} tool call{} since last checkpoint",
+        number,
+        format_minutes(elapsed),
+        results.len(),
+        if results.len() == 1 { "" } else { "s" }
+    );
+    if !counts.is_empty() {
+        let by_tool: Vec<String> = counts
+            .iter()
+            .map(|(name, n)| format!("{} ×{}", name, n))
+            .collect();
+        text.push_str(&format!(" ({}", by_tool.join(", ")));
+        if failed > 0 {
+            text.push_str(&format!("; {} failed", failed));
+        }
+        text.push(')');
+    }
+    text.push_str(&format!(
+        ". {} of {} budget left.",
+        format_minutes(budget.saturating_sub(elapsed)),
+        format_minutes(budget)
+    ));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(tool_name: &str, success: bool) -> ToolExecutionResult {
+        ToolExecutionResult {
+            tool_name: tool_name.to_string(),
+            success,
+            result: String::new(),
+        }
+    }
+
+    #[test]
+    fn checkpoints_follow_interval_and_budget() {
+        let run = AutoRun::new(AutoRunConfig {
+            budget: Duration::from_secs(600),
+            checkpoint_interval: Duration::from_secs(120),
+            steer_window: Duration::from_secs(0),
+        });
+        let start = run.started_at;
+        let mut results = vec![result("bash", true), result("edit", false), result("bash", true)];
+
+        assert_eq!(run.checkpoint(&results, start + Duration::from_secs(60)), CheckpointStatus::Skip);
+        assert_eq!(
+            run.checkpoint(&results, start + Duration::from_secs(120)),
+            CheckpointStatus::Report(
+                "Checkpoint 1 at 2m: 3 tool calls since last checkpoint (bash ×2, edit ×1; 1 failed). 8m of 10m budget left."
+                    .to_string()
+            )
+        );
+
+        results.push(result("read", true));
+        match run.checkpoint(&results, start + Duration::from_secs(600)) {
+            CheckpointStatus::BudgetExhausted(summary) => {
+                assert!(summary.starts_with("Checkpoint 2 at 10m: 1 tool call since"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(run.checkpoints(), 2);
+    }
+}
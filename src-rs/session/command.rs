@@ -0,0 +1,130 @@
+/// Slash commands understood by the core
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlashCommand {
+    /// `/model [provider:model | model]` - show or switch the active model
+    Model(Option<String>),
+    /// `/clear` - clear conversation history
+    Clear,
+    /// `/mode [plan|build]` - show or switch the agent mode
+    Mode(Option<String>),
+    /// `/approvals [read-only|agent|agent-full]` - show or switch the approval mode
+    Approvals(Option<String>),
+    /// `/theme <name>` - switch the UI theme
+    Theme(Option<String>),
+    /// `/sessions` - list saved sessions
+    Sessions,
+    /// `/skills` - list available skills
+    Skills,
+    /// `/compact` - compact conversation history
+    Compact,
+    /// `/exit` - handled by the frontend
+    Exit,
+    /// `/help` - list commands
+    Help,
+    /// Any other `/word`
+    Unknown(String),
+}
+
+impl SlashCommand {
+    pub fn name(&self) -> &str {
+        match self {
+            SlashCommand::Model(_) => "model",
+            SlashCommand::Clear => "clear",
+            SlashCommand::Mode(_) => "mode",
+            SlashCommand::Approvals(_) => "approvals",
+            SlashCommand::Theme(_) => "theme",
+            SlashCommand::Sessions => "sessions",
+            SlashCommand::Skills => "skills",
+            SlashCommand::Compact => "compact",
+            SlashCommand::Exit => "exit",
+            SlashCommand::Help => "help",
+            SlashCommand::Unknown(name) => name,
+        }
+    }
+}
+
+/// Names and one-line descriptions, used for `/help`
+pub const SLASH_COMMAND_HELP: &[(&str, &str)] = &[
+    ("model", "show or switch the model: /model [provider:model]"),
+    ("clear", "clear conversation history"),
+    ("mode", "show or switch agent mode: /mode [plan|build]"),
+    ("approvals", "show or switch approval mode: /approvals [read-only|agent|agent-full]"),
+    ("theme", "switch UI theme: /theme <name>"),
+    ("sessions", "list saved sessions"),
+    ("skills", "list available skills"),
+    ("compact", "compact conversation history"),
+    ("exit", "exit the application"),
+    ("help", "list available commands"),
+];
+
+/// Parse a slash command from user input
+///
+/// Returns `None` when the input is not a slash command (does not start with
+/// '/', or starts with '//' or a path such as '/usr/bin').
+pub fn parse_slash_command(input: &str) -> Option<SlashCommand> {
+    let trimmed = input.trim();
+    let rest = trimmed.strip_prefix('/')?;
+    let mut parts = rest.splitn(2, char::is_whitespace);
+    let name = parts.next().unwrap_or("");
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return None;
+    }
+    let arg = parts
+        .next()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    let command = match name.to_lowercase().as_str() {
+        "model" => SlashCommand::Model(arg),
+        "clear" => SlashCommand::Clear,
+        "mode" => SlashCommand::Mode(arg),
+        "approvals" => SlashCommand::Approvals(arg),
+        "theme" => SlashCommand::Theme(arg),
+        "sessions" => SlashCommand::Sessions,
+        "skills" => SlashCommand::Skills,
+        "compact" => SlashCommand::Compact,
+        "exit" | "quit" => SlashCommand::Exit,
+        "help" => SlashCommand::Help,
+        // Legacy frontend spelling: `/config model <m>` and `/config theme <t>`
+        "config" => {
+            let arg = arg.unwrap_or_default();
+            let mut sub = arg.splitn(2, char::is_whitespace);
+            let sub_name = sub.next().unwrap_or("");
+            let sub_arg = sub
+                .next()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+            match sub_name {
+                "model" => SlashCommand::Model(sub_arg),
+                "theme" => SlashCommand::Theme(sub_arg),
+                _ => SlashCommand::Unknown("config".to_string()),
+            }
+        }
+        other => SlashCommand::Unknown(other.to_string()),
+    };
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_with_and_without_args() {
+        assert_eq!(parse_slash_command("/clear"), Some(SlashCommand::Clear));
+        assert_eq!(parse_slash_command("  /mode plan "), Some(SlashCommand::Mode(Some("plan".into()))));
+        assert_eq!(parse_slash_command("/model"), Some(SlashCommand::Model(None)));
+        assert_eq!(
+            parse_slash_command("/config model openai:gpt-4o"),
+            Some(SlashCommand::Model(Some("openai:gpt-4o".into())))
+        );
+        assert_eq!(parse_slash_command("/foo"), Some(SlashCommand::Unknown("foo".into())));
+    }
+
+    #[test]
+    fn ignores_non_commands() {
+        assert_eq!(parse_slash_command("hello"), None);
+        assert_eq!(parse_slash_command("/usr/bin/env is broken"), None);
+        assert_eq!(parse_slash_command("/"), None);
+    }
+}
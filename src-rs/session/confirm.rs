@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::llm::tools::tool_trait::Tool;
 use crate::llm::utils::file_tracker::PathSecurity;
 use super::manager::SESSION_MANAGER;
 use super::types::ConfirmationStatus;
@@ -43,6 +44,12 @@ pub fn set_confirmation_status(
     }
 }
 
+/// Key of a call to `tool`: the tool's own `Tool::key_path`, else `key_path_from_args`
+pub fn tool_key_path(tool: Option<&dyn Tool>, tool_name: &str, args_json: &str) -> String {
+    tool.and_then(|t| t.key_path(args_json))
+        .unwrap_or_else(|| key_path_from_args(tool_name, args_json))
+}
+
 pub fn key_path_from_args(tool_name: &str, args_json: &str) -> String {
     match serde_json::from_str::<serde_json::Value>(args_json) {
         Ok(value) => {
@@ -59,22 +66,29 @@ pub fn key_path_from_args(tool_name: &str, args_json: &str) -> String {
                     value.get("command").and_then(|v| v.as_str()).unwrap_or("*").to_string()
                 },
                 // Tools with 'file_path'
-                "edit" | "view" | "write" | "diagnostics" => {
+                "edit" | "view" | "write" | "diagnostics" | "hover" | "rename_symbol" | "code_action" => {
                     to_abs(value.get("file_path").and_then(|v| v.as_str()).unwrap_or("*"))
                 },
                 // Tools with 'url'
                 "fetch" => {
                     value.get("url").and_then(|v| v.as_str()).unwrap_or("*").to_string()
                 },
+                // Tools with 'path' (required)
+                "mkdir" | "delete" => {
+                    to_abs(value.get("path").and_then(|v| v.as_str()).unwrap_or("*"))
+                },
+                "move" => {
+                    to_abs(value.get("source").and_then(|v| v.as_str()).unwrap_or("*"))
+                },
                 // Tools with 'path' (optional)
-                "ls" | "grep" => {
+                "ls" | "grep" | "repo_stats" | "deps" => {
                     to_abs(value.get("path").and_then(|v| v.as_str()).unwrap_or("*"))
                 },
                 "glob" => {
                     value.get("pattern").and_then(|v| v.as_str()).unwrap_or("*").to_string()
                 },
                 // Tools with no specific path or global scope
-                "todo_write" => {
+                "todo_write" | "ask_user" => {
                     "*".to_string()
                 },
                 // Fallback for unknown tools
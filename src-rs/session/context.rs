@@ -1,29 +1,32 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex as StdMutex};
 
-use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction};
-use tokio::sync::Mutex;
+use tokio::sync::{oneshot, Mutex};
 
 use crate::llm::agents::agent::Agent as RustAgent;
+use crate::llm::agents::cancel::CancelToken;
+use crate::llm::utils::session_vars::SessionVars;
 
-use super::types::{ConfirmationStatus, CoreEvent, ResponseStage, SessionToolOperation};
+use super::auto_accept::AutoAcceptScope;
+use super::auto_mode::AutoRun;
+use super::output_limit::OutputLimit;
+use super::plan::PlanRun;
+use super::proposals::Proposals;
+use super::skills::Skill;
+use super::tool_stats::ToolStats;
+use super::turn_journal::InterruptedTurn;
+use super::turn_timing::TurnTiming;
+use super::events::SessionEventSink;
+use super::eviction::EvictedSession;
+use super::types::{ConfirmationStatus, ResponseStage, SessionToolOperation};
 
-pub struct SessionEventSink {
-    pub handler: ThreadsafeFunction<CoreEvent, ErrorStrategy::CalleeHandled>,
-}
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum AgentMode {
     Plan,
+    #[default]
     Build,
 }
 
-impl Default for AgentMode {
-    fn default() -> Self {
-        AgentMode::Build
-    }
-}
-
 impl From<String> for AgentMode {
     fn from(s: String) -> Self {
         match s.to_lowercase().as_str() {
@@ -33,28 +36,23 @@ impl From<String> for AgentMode {
     }
 }
 
-impl ToString for AgentMode {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for AgentMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            AgentMode::Plan => "plan".to_string(),
-            AgentMode::Build => "build".to_string(),
+            AgentMode::Plan => f.write_str("plan"),
+            AgentMode::Build => f.write_str("build"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum ApprovalMode {
     ReadOnly,
+    #[default]
     Agent,
     AgentFull,
 }
 
-impl Default for ApprovalMode {
-    fn default() -> Self {
-        ApprovalMode::Agent
-    }
-}
-
 impl From<String> for ApprovalMode {
     fn from(s: String) -> Self {
         match s.to_lowercase().as_str() {
@@ -65,27 +63,89 @@ impl From<String> for ApprovalMode {
     }
 }
 
-impl ToString for ApprovalMode {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for ApprovalMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ApprovalMode::ReadOnly => "read-only".to_string(),
-            ApprovalMode::AgentFull => "agent-full".to_string(),
-            ApprovalMode::Agent => "agent".to_string(),
+            ApprovalMode::ReadOnly => f.write_str("read-only"),
+            ApprovalMode::AgentFull => f.write_str("agent-full"),
+            ApprovalMode::Agent => f.write_str("agent"),
         }
     }
 }
+
+/// An ask_user call paused until the user answers
+pub struct PendingQuestion {
+    pub request_id: String,
+    pub sender: oneshot::Sender<String>,
+}
+
+/// The tool call in progress, which `cancel_tool` can stop on its own
+#[derive(Clone)]
+pub struct RunningToolCall {
+    /// Id carried as `tool_call_id` by the call's events
+    pub request_id: String,
+    pub cancel: CancelToken,
+}
+
 pub struct SessionContext {
     pub inner: Arc<Mutex<RustAgent>>,
+    /// The id the host opened it with; the manager keys it by namespace too
     pub session_id: String,
+    /// Owner namespace; the session is only reachable through it
+    pub namespace: String,
     pub created_at: u64,
     pub updated_at: u64,
     pub tool_confirm: Arc<StdMutex<HashMap<(String, String), ConfirmationStatus>>>,
     pub response_stage: Arc<StdMutex<ResponseStage>>,
     pub tool_operation: Arc<StdMutex<Option<SessionToolOperation>>>,
-    pub event_sink: Arc<StdMutex<Option<SessionEventSink>>>,
-    pub event_seq: Arc<StdMutex<i64>>,
+    /// Taken out of the lock to send, so no lock is held while the host is slow
+    pub event_sink: Arc<StdMutex<Option<Arc<SessionEventSink>>>>,
     pub agent_mode: AgentMode,
     pub approval_mode: ApprovalMode,
+    /// Cancels the running turn without waiting for the agent lock
+    pub cancel_token: CancelToken,
+    /// Approved plan being executed by `execute_plan_then_build`
+    pub active_plan: Option<PlanRun>,
+    /// Time-boxed autonomous run in progress, if any
+    pub auto_run: Option<Arc<AutoRun>>,
+    /// Indices of messages the user pinned, ascending
+    pub pinned: Vec<usize>,
+    /// Title the user gave the session
+    pub title: Option<String>,
+    /// The user's notes on the session
+    pub notes: Option<String>,
+    /// Variables the host set with `set_session_var`, see `session_vars`
+    pub vars: SessionVars,
+    /// Skills the user enabled; the active ones add the workspace defaults, see `skills`
+    pub skills: Vec<String>,
+    /// The skills in the system prompt, whose `allowed-tools` limit the tool calls
+    pub active_skills: Vec<Skill>,
+    /// Tool calls made in the session so far
+    pub tool_stats: ToolStats,
+    /// Timing of the latest turns, oldest first
+    pub turn_timings: VecDeque<TurnTiming>,
+    /// Turn the process died in, found in the journal when the session was opened
+    pub interrupted_turn: Option<InterruptedTurn>,
+    /// Capabilities probed when the session was opened, kept in the system prompt
+    pub environment: Option<String>,
+    /// Paths edits may be made in without confirmation
+    pub auto_accept: Option<Arc<AutoAcceptScope>>,
+    /// Propose mode and the changes it has held back
+    pub proposals: Proposals,
+    /// Question of an ask_user call waiting for `answer_question`
+    pub pending_question: Option<PendingQuestion>,
+    /// Tool call waiting for confirmation or running
+    pub running_tool: Option<RunningToolCall>,
+    /// Set with `set_output_limit`; otherwise `[sessions]` applies
+    pub output_limit: Option<OutputLimit>,
+    /// Workspace the session was saved in, when opened in another; warned about on the next turn
+    pub workspace_mismatch: Option<String>,
+    /// Last time the agent was locked, for idle eviction
+    pub last_used_ms: i64,
+    /// Estimated size of the history after the latest turn
+    pub memory_bytes: u64,
+    /// Set while the agent's history and tools are out of memory
+    pub evicted: Option<EvictedSession>,
 }
 
 impl SessionContext {
@@ -95,18 +155,41 @@ impl SessionContext {
             .unwrap_or_default()
             .as_secs();
 
+        let cancel_token = agent.cancel_token();
         Self {
             inner: Arc::new(Mutex::new(agent)),
             session_id,
+            namespace: super::store::DEFAULT_NAMESPACE.to_string(),
             created_at: now,
             updated_at: now,
             tool_confirm: Arc::new(StdMutex::new(HashMap::new())),
             response_stage: Arc::new(StdMutex::new(ResponseStage::Thinking)),
             tool_operation: Arc::new(StdMutex::new(None)),
             event_sink: Arc::new(StdMutex::new(None)),
-            event_seq: Arc::new(StdMutex::new(0)),
             agent_mode,
             approval_mode,
+            cancel_token,
+            active_plan: None,
+            auto_run: None,
+            pinned: Vec::new(),
+            title: None,
+            notes: None,
+            vars: SessionVars::new(),
+            skills: Vec::new(),
+            active_skills: Vec::new(),
+            tool_stats: ToolStats::default(),
+            turn_timings: VecDeque::new(),
+            interrupted_turn: None,
+            environment: None,
+            auto_accept: None,
+            proposals: Proposals::default(),
+            pending_question: None,
+            running_tool: None,
+            output_limit: None,
+            workspace_mismatch: None,
+            last_used_ms: (now * 1000) as i64,
+            memory_bytes: 0,
+            evicted: None,
         }
     }
 }
@@ -0,0 +1,105 @@
+//! Environment header sent with every turn.
+//!
+//! The header (date and time, OS, workspace name, git branch and status) is
+//! rebuilt before each turn and sent as a system message ahead of the
+//! history; it is never stored in the conversation, so it is always current
+//! and saved sessions do not carry stale copies. `[context_header]` turns it
+//! and each of its lines on or off.
+
+use std::path::Path;
+use std::process::Command;
+
+use chrono::Local;
+
+use crate::config::ContextHeaderConfig;
+
+/// The header for a turn in `workspace`, or None if it is turned off or empty
+pub fn build(config: &ContextHeaderConfig, workspace: &Path) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    let mut lines = Vec::new();
+    if config.datetime {
+        lines.push(format!("Current date and time: {}", Local::now().format("%A, %Y-%m-%d %H:%M (UTC%:z)")));
+    }
+    if config.os {
+        lines.push(format!("OS: {} ({})", std::env::consts::OS, std::env::consts::ARCH));
+    }
+    if config.workspace {
+        let name = workspace
+            .canonicalize()
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_else(|| ".".to_string());
+        lines.push(format!("Workspace: {}", name));
+    }
+    if config.git {
+        if let Some(git) = git_summary(workspace) {
+            lines.push(format!("Git: {}", git));
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    Some(format!("<environment>\n{}\n</environment>", lines.join("\n")))
+}
+
+fn git_summary(workspace: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["status", "--porcelain=v1", "--branch"])
+        .current_dir(workspace)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(summarize_status(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// One line from `git status --porcelain=v1 --branch` output
+fn summarize_status(status: &str) -> String {
+    let mut lines = status.lines();
+    let branch = lines
+        .next()
+        .and_then(|l| l.strip_prefix("## "))
+        .map(|l| {
+            let l = l.strip_prefix("No commits yet on ").unwrap_or(l);
+            if l.starts_with("HEAD (no branch)") {
+                "detached HEAD".to_string()
+            } else {
+                let name = l.split("...").next().unwrap_or(l);
+                let name = name.split(' ').next().unwrap_or(name);
+                format!("branch {}", name)
+            }
+        })
+        .unwrap_or_else(|| "unknown branch".to_string());
+
+    let (mut changed, mut untracked) = (0, 0);
+    for line in lines {
+        if line.starts_with("??") {
+            untracked += 1;
+        } else if !line.trim().is_empty() {
+            changed += 1;
+        }
+    }
+    if changed == 0 && untracked == 0 {
+        return format!("{}, clean", branch);
+    }
+    format!("{}, {} changed, {} untracked", branch, changed, untracked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::summarize_status;
+
+    #[test]
+    fn summarizes_porcelain_status() {
+        assert_eq!(summarize_status("## main...origin/main\n"), "branch main, clean");
+        assert_eq!(
+            summarize_status("## feature/x...origin/feature/x [ahead 2]\n M src/lib.rs\nA  new.rs\n?? notes.txt\n"),
+            "branch feature/x, 2 changed, 1 untracked"
+        );
+        assert_eq!(summarize_status("## No commits yet on main\n?? a\n"), "branch main, 0 changed, 1 untracked");
+        assert_eq!(summarize_status("## HEAD (no branch)\n"), "detached HEAD, clean");
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+use std::time::Duration;
+
+use super::{ContextItem, ContextProvider};
+use crate::lsp::LspManager;
+
+/// How long to wait for the language servers' diagnostics
+const DIAGNOSTICS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Errors and warnings of the language servers already running for the
+/// workspace; servers are not started for it. Files named in the prompt come first.
+pub struct DiagnosticsProvider;
+
+impl ContextProvider for DiagnosticsProvider {
+    fn name(&self) -> &str {
+        "diagnostics"
+    }
+
+    fn gather(&self, workspace: &Path, prompt: &str) -> Vec<ContextItem> {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return Vec::new();
+        };
+        let summary = handle.block_on(async {
+            let manager = LspManager::existing_shared().await?;
+            tokio::time::timeout(DIAGNOSTICS_TIMEOUT, manager.get_all_diagnostics())
+                .await
+                .ok()?
+                .ok()
+        });
+        let Some(summary) = summary else {
+            return Vec::new();
+        };
+        let root = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+        let mut diagnostics: Vec<_> = summary
+            .items
+            .into_iter()
+            .filter(|d| d.severity == "Error" || d.severity == "Warning")
+            .map(|d| {
+                let file = Path::new(&d.file)
+                    .strip_prefix(&root)
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or(d.file);
+                (file, d.line, d.column, d.severity, d.message)
+            })
+            .collect();
+        diagnostics.sort_by_key(|(file, line, _, severity, _)| {
+            (!prompt.contains(file.as_str()), severity != "Error", file.clone(), *line)
+        });
+        diagnostics
+            .into_iter()
+            .map(|(file, line, column, severity, message)| {
+                let message = message.lines().next().unwrap_or_default().to_string();
+                ContextItem::new(format!("{}:{}:{}", file, line, column), format!("{}: {}", severity, message))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::Path;
+use std::process::Command;
+
+use super::{ContextItem, ContextProvider};
+
+/// Files git reports as changed or untracked
+pub struct GitStatusProvider;
+
+impl ContextProvider for GitStatusProvider {
+    fn name(&self) -> &str {
+        "git_status"
+    }
+
+    fn gather(&self, workspace: &Path, _prompt: &str) -> Vec<ContextItem> {
+        let output = Command::new("git")
+            .args(["status", "--porcelain=v1", "--untracked-files=normal"])
+            .current_dir(workspace)
+            .output();
+        match output {
+            Ok(output) if output.status.success() => parse_status(&String::from_utf8_lossy(&output.stdout)),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Items from `git status --porcelain=v1` output
+fn parse_status(status: &str) -> Vec<ContextItem> {
+    status
+        .lines()
+        .filter(|line| line.len() > 3)
+        .map(|line| {
+            let (code, path) = line.split_at(3);
+            let code = code.trim_end();
+            let detail = if code == "??" {
+                "untracked"
+            } else if code.contains('U') || code == "AA" || code == "DD" {
+                "conflict"
+            } else if code.contains('R') {
+                "renamed"
+            } else if code.contains('A') {
+                "added"
+            } else if code.contains('D') {
+                "deleted"
+            } else {
+                "modified"
+            };
+            ContextItem::new(path.trim_matches('"'), detail)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_porcelain_status() {
+        let status = " M src/lib.rs\nA  new.rs\nR  old.rs -> moved.rs\nUU both.rs\n D gone.rs\n?? notes.txt\n";
+        let items = parse_status(status);
+        let items: Vec<(&str, &str)> = items.iter().map(|i| (i.label.as_str(), i.detail.as_str())).collect();
+        assert_eq!(
+            items,
+            vec![
+                ("src/lib.rs", "modified"),
+                ("new.rs", "added"),
+                ("old.rs -> moved.rs", "renamed"),
+                ("both.rs", "conflict"),
+                ("gone.rs", "deleted"),
+                ("notes.txt", "untracked"),
+            ]
+        );
+    }
+}
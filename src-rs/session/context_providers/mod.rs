@@ -0,0 +1,147 @@
+//! Extra context gathered for every turn.
+//!
+//! A `ContextProvider` looks at the workspace (and the turn's prompt) and
+//! returns short items, e.g. the files git reports as changed. The providers
+//! named in `[context_providers] enabled` run before each turn and their
+//! items are rendered into one `<context>` block sent with the environment
+//! header, so like the header it is always current and never stored in the
+//! history. Besides the built-ins, embedders can `register` their own.
+
+mod diagnostics;
+mod git_status;
+mod recent_files;
+
+use std::path::Path;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::config::ContextProvidersConfig;
+
+pub use diagnostics::DiagnosticsProvider;
+pub use git_status::GitStatusProvider;
+pub use recent_files::RecentFilesProvider;
+
+static REGISTERED: LazyLock<Mutex<Vec<Arc<dyn ContextProvider>>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// One piece of context, rendered as `- label: detail`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextItem {
+    pub label: String,
+    /// May be empty
+    pub detail: String,
+}
+
+impl ContextItem {
+    pub fn new(label: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            detail: detail.into(),
+        }
+    }
+}
+
+/// A source of per-turn context. `gather` runs on a blocking thread before
+/// each turn, so it may read files or run short commands, and should return
+/// nothing rather than fail.
+pub trait ContextProvider: Send + Sync {
+    /// Name used in `[context_providers] enabled` and in the rendered block
+    fn name(&self) -> &str;
+
+    /// Items for a turn in `workspace` starting with `prompt`, most relevant first
+    fn gather(&self, workspace: &Path, prompt: &str) -> Vec<ContextItem>;
+}
+
+/// Make a provider available to enable by name; it replaces a registered
+/// provider of the same name, and shadows a built-in one
+pub fn register(provider: Arc<dyn ContextProvider>) {
+    let mut registered = REGISTERED.lock().unwrap();
+    registered.retain(|p| p.name() != provider.name());
+    registered.push(provider);
+}
+
+fn provider(name: &str, config: &ContextProvidersConfig) -> Option<Arc<dyn ContextProvider>> {
+    if let Some(provider) = REGISTERED.lock().unwrap().iter().find(|p| p.name() == name) {
+        return Some(Arc::clone(provider));
+    }
+    match name {
+        "git_status" => Some(Arc::new(GitStatusProvider)),
+        "diagnostics" => Some(Arc::new(DiagnosticsProvider)),
+        "recent_files" => Some(Arc::new(RecentFilesProvider::new(config.recent_hours))),
+        _ => None,
+    }
+}
+
+/// Run the enabled providers and render their items, or None if they had nothing.
+/// Providers can tell the turn's session from `current_tool_session`.
+pub fn gather(config: &ContextProvidersConfig, workspace: &Path, prompt: &str) -> Option<String> {
+    let mut sections = Vec::new();
+    for name in &config.enabled {
+        let Some(provider) = provider(name, config) else {
+            log::warn!("Unknown context provider '{}' in [context_providers]", name);
+            continue;
+        };
+        let items = provider.gather(workspace, prompt);
+        if !items.is_empty() {
+            sections.push((provider.name().to_string(), items));
+        }
+    }
+    render(&sections, config.max_items)
+}
+
+fn render(sections: &[(String, Vec<ContextItem>)], max_items: usize) -> Option<String> {
+    if sections.is_empty() {
+        return None;
+    }
+    let mut out = String::from("<context>");
+    for (name, items) in sections {
+        out.push_str(&format!("\n[{}]", name));
+        for item in items.iter().take(max_items) {
+            if item.detail.is_empty() {
+                out.push_str(&format!("\n- {}", item.label));
+            } else {
+                out.push_str(&format!("\n- {}: {}", item.label, item.detail));
+            }
+        }
+        if items.len() > max_items {
+            out.push_str(&format!("\n- ... and {} more", items.len() - max_items));
+        }
+    }
+    out.push_str("\n</context>");
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Todo;
+
+    impl ContextProvider for Todo {
+        fn name(&self) -> &str {
+            "todo"
+        }
+
+        fn gather(&self, _workspace: &Path, prompt: &str) -> Vec<ContextItem> {
+            (1..=3).map(|i| ContextItem::new(format!("TODO {}", i), prompt)).collect()
+        }
+    }
+
+    #[test]
+    fn renders_enabled_providers_within_the_item_limit() {
+        register(Arc::new(Todo));
+        let config = ContextProvidersConfig {
+            enabled: vec!["todo".to_string(), "no_such_provider".to_string()],
+            max_items: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            gather(&config, Path::new("."), "fix it").unwrap(),
+            "<context>\n[todo]\n- TODO 1: fix it\n- TODO 2: fix it\n- ... and 1 more\n</context>"
+        );
+        let config = ContextProvidersConfig {
+            enabled: Vec::new(),
+            max_items: 2,
+            ..Default::default()
+        };
+        assert_eq!(gather(&config, Path::new("."), "fix it"), None);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use ignore::WalkBuilder;
+
+use super::{ContextItem, ContextProvider};
+use crate::llm::utils::file_activity;
+use crate::llm::utils::tool_access::current_tool_session;
+
+/// Files looked at before the walk stops
+const MAX_WALKED: usize = 20_000;
+
+/// Files the session's tools viewed or edited, most recent first, then the
+/// other files of the workspace modified on disk within the window
+pub struct RecentFilesProvider {
+    window: Duration,
+}
+
+impl RecentFilesProvider {
+    pub fn new(hours: u64) -> Self {
+        Self {
+            window: Duration::from_secs(hours * 3600),
+        }
+    }
+
+    fn modified_on_disk(&self, workspace: &Path, now: SystemTime) -> Vec<(Duration, PathBuf)> {
+        let mut recent: Vec<(Duration, PathBuf)> = WalkBuilder::new(workspace)
+            .follow_links(false)
+            .build()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_some_and(|t| t.is_file()))
+            .take(MAX_WALKED)
+            .filter_map(|e| {
+                let age = now.duration_since(e.metadata().ok()?.modified().ok()?).unwrap_or_default();
+                (age <= self.window).then(|| (age, e.into_path()))
+            })
+            .collect();
+        recent.sort();
+        recent
+    }
+}
+
+impl ContextProvider for RecentFilesProvider {
+    fn name(&self) -> &str {
+        "recent_files"
+    }
+
+    fn gather(&self, workspace: &Path, _prompt: &str) -> Vec<ContextItem> {
+        let now = SystemTime::now();
+        let root = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+        let display = |path: &Path| {
+            let rel = path.strip_prefix(&root).or_else(|_| path.strip_prefix(workspace));
+            rel.unwrap_or(path).to_string_lossy().into_owned()
+        };
+        let mut listed = HashSet::new();
+        let mut items = Vec::new();
+        let session_files = current_tool_session().map(|id| file_activity::recent(&id)).unwrap_or_default();
+        for file in session_files.into_iter().filter(|f| f.path.is_file()) {
+            let label = display(&file.path);
+            let age = now.duration_since(file.at).unwrap_or_default();
+            items.push(ContextItem::new(label.clone(), format!("{} {}", file.kind.as_str(), format_age(age))));
+            listed.insert(label);
+        }
+        if self.window.is_zero() {
+            return items;
+        }
+        for (age, path) in self.modified_on_disk(workspace, now) {
+            let label = display(&path);
+            if listed.insert(label.clone()) {
+                items.push(ContextItem::new(label, format!("modified {}", format_age(age))));
+            }
+        }
+        items
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        s if s < 60 => "just now".to_string(),
+        s if s < 3600 => format!("{}m ago", s / 60),
+        s => format!("{}h ago", s / 3600),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::utils::tool_access::with_tool_session;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn lists_session_files_before_other_recent_changes() {
+        let workspace = FakeWorkspace::new()
+            .file("viewed.rs", "viewed.rs")
+            .file("edited.rs", "edited.rs")
+            .file("touched.rs", "touched.rs")
+            .build()
+            .unwrap();
+        let root = workspace.path();
+        let items = with_tool_session("rf1", || {
+            file_activity::record_viewed(&root.join("viewed.rs"));
+            file_activity::record_edited(&root.join("edited.rs"));
+            RecentFilesProvider::new(1).gather(root, "")
+        });
+        let items: Vec<(&str, &str)> = items.iter().map(|i| (i.label.as_str(), i.detail.as_str())).collect();
+        assert_eq!(
+            items,
+            vec![
+                ("edited.rs", "edited just now"),
+                ("viewed.rs", "viewed just now"),
+                ("touched.rs", "modified just now"),
+            ]
+        );
+        file_activity::forget("rf1");
+    }
+}
@@ -0,0 +1,252 @@
+//! Encryption at rest for session snapshots and message logs.
+//!
+//! With `[privacy] encrypt_sessions = true`, every snapshot file, every meta
+//! file (it holds the title and notes) and every message log line is written
+//! as an envelope:
+//!
+//! ```text
+//! carry-enc1:<key source>:<salt>:<nonce + AES-256-GCM ciphertext>
+//! ```
+//!
+//! (salt and ciphertext base64). The key is derived from the passphrase in
+//! `CARRYCODE_SESSION_PASSPHRASE` when it is set, otherwise it is a random key
+//! kept in the OS keychain (macOS and Windows). Files are decrypted on load
+//! whatever the setting, so turning encryption off keeps old sessions readable;
+//! plain files from before it was turned on are read as they are.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex as StdMutex, OnceLock};
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+
+pub const PASSPHRASE_ENV: &str = "CARRYCODE_SESSION_PASSPHRASE";
+
+const ENVELOPE_PREFIX: &str = "carry-enc1:";
+const SOURCE_PASSPHRASE: &str = "passphrase";
+const SOURCE_KEYCHAIN: &str = "keychain";
+const PBKDF2_ROUNDS: u32 = 210_000;
+const NONCE_LEN: usize = 12;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Derived keys by "<source>:<salt>", as passphrase derivation is slow
+static KEYS: LazyLock<StdMutex<HashMap<String, [u8; 32]>>> = LazyLock::new(|| StdMutex::new(HashMap::new()));
+
+/// Salt for keys derived by this process; each file records the salt it used
+static WRITE_SALT: OnceLock<String> = OnceLock::new();
+
+/// Raised when a sealed file cannot be opened: no key, or the wrong one
+#[derive(Debug)]
+pub struct DecryptError(String);
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot decrypt session data: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+/// Whether new session files are encrypted (`[privacy] encrypt_sessions`)
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Encrypt `plaintext` when encryption is enabled, otherwise return it as is
+pub fn seal_if_enabled(plaintext: String) -> Result<String> {
+    if !is_enabled() {
+        return Ok(plaintext);
+    }
+    let (source, passphrase) = match passphrase() {
+        Some(p) => (SOURCE_PASSPHRASE, Some(p)),
+        None => (SOURCE_KEYCHAIN, None),
+    };
+    let salt = match passphrase {
+        Some(_) => WRITE_SALT.get_or_init(|| BASE64.encode(random_bytes::<16>())).as_str(),
+        None => "",
+    };
+    let key = key_for(source, salt, true)?;
+    seal_with(&key, source, salt, &plaintext)
+}
+
+/// Decrypt an envelope written by `seal_if_enabled`; other content is returned unchanged
+pub fn open(content: &str) -> Result<String> {
+    let Some((source, salt, sealed)) = parse_envelope(content)? else {
+        return Ok(content.to_string());
+    };
+    let key = key_for(source, salt, false)?;
+    open_with(&key, sealed)
+}
+
+/// Encrypt `plaintext` with a key derived from `passphrase` rather than the
+/// session key, e.g. for data leaving the machine
+pub fn seal_with_passphrase(passphrase: &str, plaintext: &str) -> Result<String> {
+    let salt = random_bytes::<16>();
+    seal_with(&derive_key(passphrase, &salt), SOURCE_PASSPHRASE, &BASE64.encode(salt), plaintext)
+}
+
+/// Decrypt an envelope written by `seal_with_passphrase`
+pub fn open_with_passphrase(passphrase: &str, content: &str) -> Result<String> {
+    let Some((SOURCE_PASSPHRASE, salt, sealed)) = parse_envelope(content)? else {
+        anyhow::bail!("not data encrypted with a passphrase");
+    };
+    let salt = BASE64.decode(salt).context("malformed salt in encrypted data")?;
+    open_with(&derive_key(passphrase, &salt), sealed)
+}
+
+/// Key source, salt and sealed data of an envelope, or None for plain content
+fn parse_envelope(content: &str) -> Result<Option<(&str, &str, &str)>> {
+    let Some(rest) = content.trim_end().strip_prefix(ENVELOPE_PREFIX) else {
+        return Ok(None);
+    };
+    let mut parts = rest.splitn(3, ':');
+    let (Some(source), Some(salt), Some(sealed)) = (parts.next(), parts.next(), parts.next()) else {
+        anyhow::bail!("malformed encrypted session data");
+    };
+    Ok(Some((source, salt, sealed)))
+}
+
+fn seal_with(key: &[u8; 32], source: &str, salt: &str, plaintext: &str) -> Result<String> {
+    let nonce = random_bytes::<NONCE_LEN>();
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("failed to encrypt session data"))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    Ok(format!("{}{}:{}:{}", ENVELOPE_PREFIX, source, salt, BASE64.encode(sealed)))
+}
+
+fn open_with(key: &[u8; 32], sealed: &str) -> Result<String> {
+    let sealed = BASE64.decode(sealed.trim()).context("malformed encrypted session data")?;
+    if sealed.len() < NONCE_LEN {
+        anyhow::bail!("truncated encrypted session data");
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| DecryptError("wrong key or damaged data".to_string()))?;
+    String::from_utf8(plaintext).context("decrypted session data is not UTF-8")
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn passphrase() -> Option<String> {
+    std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty())
+}
+
+fn key_for(source: &str, salt: &str, create: bool) -> Result<[u8; 32]> {
+    let cache_key = format!("{}:{}", source, salt);
+    if let Some(key) = KEYS.lock().ok().and_then(|keys| keys.get(&cache_key).copied()) {
+        return Ok(key);
+    }
+    let key = match source {
+        SOURCE_PASSPHRASE => {
+            let passphrase = passphrase()
+                .ok_or_else(|| DecryptError(format!("{} is not set", PASSPHRASE_ENV)))?;
+            let salt = BASE64.decode(salt).context("malformed salt in encrypted session data")?;
+            derive_key(&passphrase, &salt)
+        }
+        SOURCE_KEYCHAIN => keychain::key(create)?,
+        other => anyhow::bail!("unknown session key source '{}'", other),
+    };
+    if let Ok(mut keys) = KEYS.lock() {
+        keys.insert(cache_key, key);
+    }
+    Ok(key)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+mod keychain {
+    use super::*;
+
+    const SERVICE: &str = "carrycode";
+    const ACCOUNT: &str = "session-encryption-key";
+
+    /// The key stored in the OS keychain; with `create`, a new one is stored if there is none
+    pub(super) fn key(create: bool) -> Result<[u8; 32]> {
+        // Keychain failures are decryption failures, so a saved session is never
+        // taken for damaged (and overwritten) just because the keychain is locked
+        let entry = keyring::Entry::new(SERVICE, ACCOUNT)
+            .map_err(|e| DecryptError(format!("failed to open the OS keychain: {}", e)))?;
+        match entry.get_password() {
+            Ok(encoded) => {
+                let bytes = BASE64.decode(encoded.trim()).context("malformed session key in the OS keychain")?;
+                bytes
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("malformed session key in the OS keychain"))
+            }
+            Err(keyring::Error::NoEntry) if create => {
+                let key = random_bytes::<32>();
+                entry
+                    .set_password(&BASE64.encode(key))
+                    .context("failed to store the session key in the OS keychain")?;
+                Ok(key)
+            }
+            Err(keyring::Error::NoEntry) => {
+                Err(DecryptError("no session key in the OS keychain".to_string()).into())
+            }
+            Err(e) => Err(DecryptError(format!("failed to read the session key from the OS keychain: {}", e)).into()),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+mod keychain {
+    use super::*;
+
+    pub(super) fn key(_create: bool) -> Result<[u8; 32]> {
+        Err(DecryptError(format!(
+            "no OS keychain is supported on this platform; set {} to encrypt sessions",
+            PASSPHRASE_ENV
+        ))
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_opens_only_with_its_key() {
+        let salt = [7u8; 16];
+        let key = derive_key("correct horse", &salt);
+        let sealed = seal_with(&key, SOURCE_PASSPHRASE, &BASE64.encode(salt), "{\"messages\":[]}").unwrap();
+        assert!(sealed.starts_with("carry-enc1:passphrase:"));
+        assert!(!sealed.contains("messages"));
+
+        let body = sealed.rsplit(':').next().unwrap();
+        assert_eq!(open_with(&key, body).unwrap(), "{\"messages\":[]}");
+        let wrong = [9u8; 32];
+        let err = open_with(&wrong, body).unwrap_err();
+        assert!(err.is::<DecryptError>());
+
+        // Plain content passes through
+        assert_eq!(open("{\"version\":1}").unwrap(), "{\"version\":1}");
+    }
+}
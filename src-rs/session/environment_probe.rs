@@ -0,0 +1,114 @@
+//! Capabilities of the machine the session runs on, for the system prompt.
+//!
+//! When a session is opened, the `[environment_probe]` executables are
+//! looked up on PATH (nothing is run) and the result, with the OS and the
+//! shells, is appended to the system prompt as a short `<capabilities>`
+//! block. Unlike the per-turn context header it is probed once, since tools
+//! rarely appear or vanish mid-session, and it lets the model pick commands
+//! that exist instead of guessing.
+
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::config::EnvironmentProbeConfig;
+
+/// The `<capabilities>` block, or None if the probe is turned off
+pub fn probe(config: &EnvironmentProbeConfig) -> Option<String> {
+    if !config.enabled {
+        return None;
+    }
+    let path = env::var_os("PATH").unwrap_or_default();
+    let (available, missing): (Vec<&str>, Vec<&str>) = config
+        .executables
+        .iter()
+        .map(String::as_str)
+        .partition(|name| find_executable(name, &path).is_some());
+    Some(render(&available, &missing, user_shell()))
+}
+
+fn render(available: &[&str], missing: &[&str], user_shell: Option<String>) -> String {
+    let mut lines = vec![format!("OS: {} ({})", env::consts::OS, env::consts::ARCH)];
+    // The bash tool always runs bash, whatever the user's login shell is
+    lines.push(match user_shell.filter(|s| s != "bash") {
+        Some(shell) => format!("Shell: commands run in bash; the user's shell is {}", shell),
+        None => "Shell: bash".to_string(),
+    });
+    if !available.is_empty() {
+        lines.push(format!("Available: {}", available.join(", ")));
+    }
+    if !missing.is_empty() {
+        lines.push(format!("Not installed: {}", missing.join(", ")));
+    }
+    format!("<capabilities>\n{}\n</capabilities>", lines.join("\n"))
+}
+
+/// Name of the login shell, such as `zsh`
+fn user_shell() -> Option<String> {
+    let shell = env::var_os("SHELL").or_else(|| env::var_os("COMSPEC"))?;
+    let name = Path::new(&shell).file_stem()?.to_string_lossy().to_lowercase();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Where `name` is found on the PATH given, if it is
+pub(crate) fn find_executable(name: &str, path: &OsString) -> Option<PathBuf> {
+    let extensions: Vec<String> = if cfg!(windows) {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| ".EXE;.CMD;.BAT".to_string())
+            .split(';')
+            .map(str::to_string)
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+    env::split_paths(path).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", name, ext)))
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    #[test]
+    fn finds_executables_on_path_and_renders_them() {
+        let workspace = FakeWorkspace::new().build().unwrap();
+        let tool = workspace.join(if cfg!(windows) { "fake-tool.EXE" } else { "fake-tool" });
+        std::fs::write(&tool, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let path = env::join_paths([Path::new("/nonexistent"), workspace.path()]).unwrap();
+
+        assert_eq!(find_executable("fake-tool", &path), Some(tool));
+        assert_eq!(find_executable("missing-tool", &path), None);
+
+        let block = render(&["git", "rg"], &["docker"], Some("zsh".to_string()));
+        assert!(block.starts_with("<capabilities>\nOS: "));
+        assert!(block.contains("Shell: commands run in bash; the user's shell is zsh"));
+        assert!(block.contains("Available: git, rg\nNot installed: docker\n</capabilities>"));
+        assert!(render(&[], &[], Some("bash".to_string())).contains("Shell: bash\n</capabilities>"));
+        assert!(probe(&EnvironmentProbeConfig {
+            enabled: false,
+            executables: Vec::new(),
+        })
+        .is_none());
+    }
+}
@@ -0,0 +1,239 @@
+//! Log of each session's control events, for looking back at what the agent
+//! did and when.
+//!
+//! Every CoreEvent a session emits, except streamed text (the messages hold
+//! it), is appended as one JSON line to `events.jsonl` beside the snapshot,
+//! sealed like the message log when sessions are encrypted. Events are queued
+//! and appended by a writer thread; `flush` writes the queue before
+//! returning. A log that has grown past `MAX_LOG_BYTES` is moved to
+//! `events.1.jsonl`, replacing the one before it. `[privacy] event_log =
+//! false` stops the recording.
+
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex as StdMutex, Once};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+
+use super::crypto;
+use super::store;
+use super::types::{CoreEvent, CoreEventFilter, CoreEventType};
+
+const EVENTS_FILE: &str = "events.jsonl";
+const ROTATED_EVENTS_FILE: &str = "events.1.jsonl";
+const MAX_LOG_BYTES: u64 = 8 * 1024 * 1024;
+const WRITE_DELAY: Duration = Duration::from_millis(200);
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static START_WRITER: Once = Once::new();
+
+/// Events not yet written, with the session directory they go to
+static PENDING: StdMutex<Vec<(PathBuf, CoreEvent)>> = StdMutex::new(Vec::new());
+static QUEUED: Condvar = Condvar::new();
+/// Held while a batch is taken and written, so batches land in order
+static WRITING: StdMutex<()> = StdMutex::new(());
+
+/// Whether events are recorded (`[privacy] event_log`)
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Queue `event` of the session to be appended to its log
+pub fn record(namespace: &str, session_id: &str, event: &CoreEvent) {
+    if !ENABLED.load(Ordering::SeqCst) || matches!(event.event_type, CoreEventType::Text) {
+        return;
+    }
+    let Ok(dir) = store::session_dir(namespace, session_id) else {
+        return;
+    };
+    let mut started = true;
+    START_WRITER.call_once(|| {
+        started = thread::Builder::new()
+            .name("event-log-writer".to_string())
+            .spawn(run_writer)
+            .is_ok();
+    });
+    if !started {
+        write_batch(vec![(dir, event.clone())]);
+        return;
+    }
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.push((dir, event.clone()));
+        QUEUED.notify_one();
+    }
+}
+
+/// Write every queued event now
+pub fn flush() {
+    let Ok(_writing) = WRITING.lock() else {
+        return;
+    };
+    let batch = PENDING.lock().map(|mut p| std::mem::take(&mut *p)).unwrap_or_default();
+    write_batch(batch);
+}
+
+/// The session's logged events that pass `filter`, oldest first
+pub fn read(namespace: &str, session_id: &str, filter: &CoreEventFilter) -> Result<Vec<CoreEvent>> {
+    flush();
+    read_dir(&store::session_dir(namespace, session_id)?, filter)
+}
+
+fn read_dir(dir: &Path, filter: &CoreEventFilter) -> Result<Vec<CoreEvent>> {
+    let mut events = Vec::new();
+    for file in [ROTATED_EVENTS_FILE, EVENTS_FILE] {
+        let content = match fs::read_to_string(dir.join(file)) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).context("failed to read event log"),
+        };
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let line = match crypto::open(line) {
+                Ok(line) => line,
+                Err(e) if e.is::<crypto::DecryptError>() => return Err(e),
+                // A torn line from a crash; the rest of the log is still good
+                Err(_) => continue,
+            };
+            if let Ok(event) = serde_json::from_str::<CoreEvent>(&line) {
+                if matches(filter, &event) {
+                    events.push(event);
+                }
+            }
+        }
+    }
+    if let Some(limit) = filter.limit {
+        let excess = events.len().saturating_sub(limit as usize);
+        events.drain(..excess);
+    }
+    Ok(events)
+}
+
+fn matches(filter: &CoreEventFilter, event: &CoreEvent) -> bool {
+    if let Some(types) = &filter.event_types {
+        let name = serde_json::to_value(event.event_type).ok();
+        let name = name.as_ref().and_then(|v| v.as_str()).unwrap_or_default();
+        if !types.iter().any(|t| t == name) {
+            return false;
+        }
+    }
+    if let Some(tool_name) = &filter.tool_name {
+        if event.tool_name.as_ref() != Some(tool_name) {
+            return false;
+        }
+    }
+    filter.since_ms.is_none_or(|since| event.ts_ms >= since) && filter.until_ms.is_none_or(|until| event.ts_ms < until)
+}
+
+fn write_batch(batch: Vec<(PathBuf, CoreEvent)>) {
+    let mut by_dir: Vec<(PathBuf, String)> = Vec::new();
+    for (dir, event) in batch {
+        let line = serde_json::to_string(&event)
+            .context("failed to serialize event")
+            .and_then(crypto::seal_if_enabled);
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                log::warn!("Failed to log a session event: {:#}", e);
+                continue;
+            }
+        };
+        let index = match by_dir.iter().position(|(d, _)| *d == dir) {
+            Some(index) => index,
+            None => {
+                by_dir.push((dir, String::new()));
+                by_dir.len() - 1
+            }
+        };
+        by_dir[index].1.push_str(&line);
+        by_dir[index].1.push('\n');
+    }
+    for (dir, lines) in by_dir {
+        if let Err(e) = append(&dir, &lines) {
+            log::warn!("Failed to write event log in {}: {:#}", dir.display(), e);
+            crate::health::record_error(crate::health::Subsystem::Persistence, format!("{:#}", e));
+        }
+    }
+}
+
+fn append(dir: &Path, lines: &str) -> Result<()> {
+    fs::create_dir_all(dir).context("failed to create session directory")?;
+    let path = dir.join(EVENTS_FILE);
+    if fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_LOG_BYTES) {
+        fs::rename(&path, dir.join(ROTATED_EVENTS_FILE)).context("failed to rotate event log")?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .context("failed to open event log")?;
+    file.write_all(lines.as_bytes()).context("failed to append to event log")
+}
+
+fn run_writer() {
+    loop {
+        {
+            let Ok(mut pending) = PENDING.lock() else {
+                return;
+            };
+            while pending.is_empty() {
+                pending = match QUEUED.wait(pending) {
+                    Ok(p) => p,
+                    Err(_) => return,
+                };
+            }
+        }
+        thread::sleep(WRITE_DELAY);
+        flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+
+    fn event(event_type: CoreEventType, tool_name: Option<&str>, ts_ms: i64) -> CoreEvent {
+        CoreEvent {
+            session_id: "s".to_string(),
+            ts_ms,
+            event_type,
+            tool_name: tool_name.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn events_are_appended_and_filtered() {
+        let workspace = FakeWorkspace::new().build().unwrap();
+        let dir = workspace.join("events");
+        write_batch(vec![
+            (dir.clone(), event(CoreEventType::ToolStart, Some("bash"), 10)),
+            (dir.clone(), event(CoreEventType::ToolEnd, Some("bash"), 20)),
+        ]);
+        write_batch(vec![(dir.clone(), event(CoreEventType::ToolStart, Some("view"), 30))]);
+        fs::OpenOptions::new().append(true).open(dir.join(EVENTS_FILE)).unwrap().write_all(b"{\"torn").unwrap();
+
+        let all = read_dir(&dir, &CoreEventFilter::default()).unwrap();
+        assert_eq!(all.iter().map(|e| e.ts_ms).collect::<Vec<_>>(), [10, 20, 30]);
+
+        let starts = CoreEventFilter {
+            event_types: Some(vec!["ToolStart".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(read_dir(&dir, &starts).unwrap().len(), 2);
+        let bash_since = CoreEventFilter {
+            tool_name: Some("bash".to_string()),
+            since_ms: Some(15),
+            ..Default::default()
+        };
+        assert_eq!(read_dir(&dir, &bash_since).unwrap()[0].ts_ms, 20);
+        let latest = CoreEventFilter {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(read_dir(&dir, &latest).unwrap()[0].tool_name.as_deref(), Some("view"));
+    }
+}
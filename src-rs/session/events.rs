@@ -0,0 +1,301 @@
+//! Delivery of session events to the host.
+//!
+//! A subscription has a control channel and, optionally, a separate channel
+//! for streamed response text. Without a text channel the text is sent on the
+//! control channel as `Text` events, as with a single `subscribe` callback.
+//!
+//! Guarantees, per subscription:
+//! - `seq` starts at 1 and increases by one per event across both channels,
+//!   in emission order, so a host with two callbacks can interleave them.
+//! - Control events are never dropped: when the queue refuses a non-blocking
+//!   send the event is queued again, waiting for room. That wait happens on
+//!   the sink's forwarding thread, never in the task that emitted the event,
+//!   so a slow host does not block the runtime's workers.
+//! - Text chunks are sent without waiting and may be lost only when the
+//!   subscriber is going away; the full text is in the turn result anyway.
+//! - Events emitted while nothing is subscribed are discarded, not buffered.
+//! - Events are downleveled to the protocol the frontend negotiated; events
+//!   with no counterpart there are dropped without taking a `seq`.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+
+use tokio::sync::{mpsc, oneshot};
+
+#[cfg(feature = "napi")]
+use napi::threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+#[cfg(feature = "napi")]
+use napi::Status;
+
+use super::protocol;
+use super::types::{CoreEvent, CoreEventType, CoreTextEvent};
+
+/// Somewhere events of type `T` can be queued for the host
+pub trait EventChannel<T>: Send + Sync {
+    /// Queue an event; with `blocking`, wait for room in a full queue.
+    /// Returns whether the event was queued.
+    fn send(&self, event: T, blocking: bool) -> bool;
+}
+
+#[cfg(feature = "napi")]
+fn call_mode(blocking: bool) -> ThreadsafeFunctionCallMode {
+    if blocking {
+        ThreadsafeFunctionCallMode::Blocking
+    } else {
+        ThreadsafeFunctionCallMode::NonBlocking
+    }
+}
+
+#[cfg(feature = "napi")]
+impl<T: 'static> EventChannel<T> for ThreadsafeFunction<T, ErrorStrategy::CalleeHandled> {
+    fn send(&self, event: T, blocking: bool) -> bool {
+        self.call(Ok(event), call_mode(blocking)) == Status::Ok
+    }
+}
+
+#[cfg(feature = "napi")]
+impl<T: 'static> EventChannel<T> for ThreadsafeFunction<T, ErrorStrategy::Fatal> {
+    fn send(&self, event: T, blocking: bool) -> bool {
+        self.call(event, call_mode(blocking)) == Status::Ok
+    }
+}
+
+/// What the forwarding thread hands to the host, in `seq` order
+enum Outgoing {
+    Text(CoreTextEvent),
+    Control(Box<CoreEvent>),
+    /// Answered once everything queued before it was handed over
+    Flush(oneshot::Sender<()>),
+}
+
+pub struct SessionEventSink {
+    queue: mpsc::UnboundedSender<Outgoing>,
+    seq: AtomicI64,
+    /// Held from taking a `seq` to queueing the event, so that events sent
+    /// from several threads are queued in `seq` order
+    order: Mutex<()>,
+}
+
+impl SessionEventSink {
+    /// Every event, text included, on one channel
+    pub fn unified(channel: impl EventChannel<CoreEvent> + 'static) -> Self {
+        Self::start(Box::new(channel), None)
+    }
+
+    /// Stream text and control events on separate channels
+    pub fn split(
+        text: impl EventChannel<CoreTextEvent> + 'static,
+        control: impl EventChannel<CoreEvent> + 'static,
+    ) -> Self {
+        Self::start(Box::new(control), Some(Box::new(text)))
+    }
+
+    /// Spawn the thread that forwards queued events to the channels; it ends
+    /// once the sink is dropped and its queue is drained
+    fn start(control: Box<dyn EventChannel<CoreEvent>>, text: Option<Box<dyn EventChannel<CoreTextEvent>>>) -> Self {
+        let (queue, mut rx) = mpsc::unbounded_channel();
+        let forward = move || {
+            while let Some(outgoing) = rx.blocking_recv() {
+                match outgoing {
+                    Outgoing::Text(event) => match &text {
+                        Some(channel) => {
+                            channel.send(event, false);
+                        }
+                        None => {
+                            control.send(event.into(), false);
+                        }
+                    },
+                    Outgoing::Control(event) => {
+                        if !control.send((*event).clone(), false) {
+                            control.send(*event, true);
+                        }
+                    }
+                    Outgoing::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        };
+        if let Err(e) = std::thread::Builder::new().name("session-events".to_string()).spawn(forward) {
+            log::error!("Failed to start the session event thread: {}", e);
+        }
+        Self {
+            queue,
+            seq: AtomicI64::new(0),
+            order: Mutex::new(()),
+        }
+    }
+
+    fn next_seq(&self) -> i64 {
+        self.seq.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    pub fn send_text(&self, event: CoreTextEvent) {
+        let mut event = protocol::downlevel_text(event);
+        let _order = self.order.lock().unwrap_or_else(|e| e.into_inner());
+        event.seq = self.next_seq();
+        let _ = self.queue.send(Outgoing::Text(event));
+    }
+
+    pub fn send_control(&self, event: CoreEvent) {
+        let Some(mut event) = protocol::downlevel(event) else {
+            return;
+        };
+        let _order = self.order.lock().unwrap_or_else(|e| e.into_inner());
+        if event.seq.is_none() {
+            event.seq = Some(self.next_seq());
+        }
+        let _ = self.queue.send(Outgoing::Control(Box::new(event)));
+    }
+
+    /// Wait until every event sent so far has been handed to the host
+    pub async fn flush(&self) {
+        let (done, handed_over) = oneshot::channel();
+        if self.queue.send(Outgoing::Flush(done)).is_ok() {
+            let _ = handed_over.await;
+        }
+    }
+}
+
+impl From<CoreTextEvent> for CoreEvent {
+    fn from(event: CoreTextEvent) -> Self {
+        CoreEvent {
+            protocol_version: event.protocol_version,
+            session_id: event.session_id,
+            ts_ms: event.ts_ms,
+            event_type: CoreEventType::Text,
+            seq: Some(event.seq),
+            text: Some(event.text),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::types::CORE_EVENT_PROTOCOL_VERSION;
+    use futures::executor::block_on;
+    use std::sync::{Arc, Mutex};
+
+    /// Records what it is sent; refuses non-blocking sends when `full`
+    #[derive(Clone)]
+    struct Recorder<T> {
+        sent: Arc<Mutex<Vec<(T, bool)>>>,
+        full: bool,
+    }
+
+    impl<T> Default for Recorder<T> {
+        fn default() -> Self {
+            Self {
+                sent: Arc::new(Mutex::new(Vec::new())),
+                full: false,
+            }
+        }
+    }
+
+    impl<T: Send + 'static> EventChannel<T> for Recorder<T> {
+        fn send(&self, event: T, blocking: bool) -> bool {
+            if self.full && !blocking {
+                return false;
+            }
+            self.sent.lock().unwrap().push((event, blocking));
+            true
+        }
+    }
+
+    fn text(chunk: &str) -> CoreTextEvent {
+        CoreTextEvent {
+            protocol_version: CORE_EVENT_PROTOCOL_VERSION,
+            session_id: "s".to_string(),
+            ts_ms: 0,
+            seq: 0,
+            text: chunk.to_string(),
+        }
+    }
+
+    fn control(event_type: CoreEventType) -> CoreEvent {
+        let mut event: CoreEvent = text("").into();
+        event.event_type = event_type;
+        event.seq = None;
+        event.text = None;
+        event
+    }
+
+    #[test]
+    fn split_channels_share_one_sequence() {
+        let texts = Recorder::<CoreTextEvent>::default();
+        let controls = Recorder::<CoreEvent>::default();
+        let sink = SessionEventSink::split(texts.clone(), controls.clone());
+
+        sink.send_control(control(CoreEventType::StageStart));
+        sink.send_text(text("Hel"));
+        sink.send_text(text("lo"));
+        sink.send_control(control(CoreEventType::End));
+        block_on(sink.flush());
+
+        let texts = texts.sent.lock().unwrap();
+        let controls = controls.sent.lock().unwrap();
+        assert_eq!(texts.iter().map(|(e, _)| (e.seq, e.text.as_str())).collect::<Vec<_>>(), vec![(2, "Hel"), (3, "lo")]);
+        assert_eq!(controls.iter().map(|(e, _)| e.seq).collect::<Vec<_>>(), vec![Some(1), Some(4)]);
+        assert!(controls.iter().all(|(e, _)| !matches!(e.event_type, CoreEventType::Text)));
+    }
+
+    #[test]
+    fn unified_channel_receives_text_as_core_events() {
+        let events = Recorder::<CoreEvent>::default();
+        let sink = SessionEventSink::unified(events.clone());
+
+        sink.send_text(text("chunk"));
+        sink.send_control(control(CoreEventType::End));
+        block_on(sink.flush());
+
+        let events = events.sent.lock().unwrap();
+        assert!(matches!(events[0].0.event_type, CoreEventType::Text));
+        assert_eq!(events[0].0.text.as_deref(), Some("chunk"));
+        assert_eq!(events[0].0.seq, Some(1));
+        assert!(matches!(events[1].0.event_type, CoreEventType::End));
+        assert_eq!(events[1].0.seq, Some(2));
+    }
+
+    #[test]
+    fn control_events_wait_for_room_but_text_does_not() {
+        let texts = Recorder::<CoreTextEvent> { full: true, ..Default::default() };
+        let controls = Recorder::<CoreEvent> { full: true, ..Default::default() };
+        let sink = SessionEventSink::split(texts.clone(), controls.clone());
+
+        sink.send_text(text("dropped"));
+        sink.send_control(control(CoreEventType::ConfirmationRequested));
+        block_on(sink.flush());
+
+        assert!(texts.sent.lock().unwrap().is_empty());
+        let controls = controls.sent.lock().unwrap();
+        assert_eq!(controls.len(), 1);
+        assert!(controls[0].1, "control event should be re-sent blocking");
+        assert_eq!(controls[0].0.seq, Some(2));
+    }
+
+    #[test]
+    fn a_full_host_queue_does_not_block_the_sender() {
+        let (tx, rx) = std::sync::mpsc::sync_channel::<CoreEvent>(1);
+        struct HostQueue(std::sync::mpsc::SyncSender<CoreEvent>);
+        impl EventChannel<CoreEvent> for HostQueue {
+            fn send(&self, event: CoreEvent, blocking: bool) -> bool {
+                if blocking {
+                    self.0.send(event).is_ok()
+                } else {
+                    self.0.try_send(event).is_ok()
+                }
+            }
+        }
+        let sink = SessionEventSink::unified(HostQueue(tx));
+
+        // The host is not draining: the second event waits on the forwarding thread
+        for _ in 0..3 {
+            sink.send_control(control(CoreEventType::Warning));
+        }
+        let seqs: Vec<_> = rx.iter().take(3).map(|e| e.seq).collect();
+        block_on(sink.flush());
+        assert_eq!(seqs, vec![Some(1), Some(2), Some(3)]);
+    }
+}
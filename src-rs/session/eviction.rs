@@ -0,0 +1,90 @@
+//! Dropping the agents of idle sessions from memory.
+//!
+//! An open session keeps its history, tools and MCP servers for as long as
+//! the process runs. Per `[sessions]`, a session unused for
+//! `idle_evict_minutes` is evicted: its snapshot is written, the agent's
+//! history and tools are dropped and its MCP servers stopped, while the
+//! session itself stays open. When the estimated size of the open
+//! histories passes `memory_ceiling_mb`, the least recently used sessions
+//! are evicted until it is back under. The next call that needs the agent
+//! loads the snapshot and tools again (see `session_util::lock_agent`).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::SessionsConfig;
+
+/// How often the background sweep looks for idle sessions
+pub const SWEEP_INTERVAL_SECS: u64 = 60;
+
+/// 0 disables either limit
+static IDLE_MS: AtomicU64 = AtomicU64::new(0);
+static CEILING_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Marks a session whose agent was evicted
+#[derive(Debug, Clone)]
+pub struct EvictedSession {
+    pub message_count: usize,
+    pub evicted_at_ms: i64,
+}
+
+/// An open session whose agent is in memory
+#[derive(Debug, Clone)]
+pub struct Resident {
+    /// Its key in the manager, see `manager::session_key`
+    pub session_id: String,
+    pub last_used_ms: i64,
+    /// Estimated size of its history
+    pub bytes: u64,
+}
+
+pub fn configure(config: &SessionsConfig) {
+    IDLE_MS.store(config.idle_evict_minutes.saturating_mul(60_000), Ordering::Relaxed);
+    CEILING_BYTES.store(config.memory_ceiling_mb.saturating_mul(1024 * 1024), Ordering::Relaxed);
+}
+
+/// Sessions to evict now under the configured limits, in eviction order
+pub fn victims(residents: &[Resident], now_ms: i64) -> Vec<String> {
+    pick(
+        residents,
+        now_ms,
+        IDLE_MS.load(Ordering::Relaxed),
+        CEILING_BYTES.load(Ordering::Relaxed),
+    )
+}
+
+/// The idle sessions, then the least recently used ones until the rest fit
+/// in `ceiling_bytes`
+fn pick(residents: &[Resident], now_ms: i64, idle_ms: u64, ceiling_bytes: u64) -> Vec<String> {
+    let mut by_age: Vec<&Resident> = residents.iter().collect();
+    by_age.sort_by_key(|r| r.last_used_ms);
+    let mut total: u64 = residents.iter().map(|r| r.bytes).sum();
+    let mut out = Vec::new();
+    for resident in by_age {
+        let idle = idle_ms > 0 && now_ms.saturating_sub(resident.last_used_ms) >= idle_ms as i64;
+        let over = ceiling_bytes > 0 && total > ceiling_bytes;
+        if idle || over {
+            total -= resident.bytes;
+            out.push(resident.session_id.clone());
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resident(id: &str, last_used_ms: i64, bytes: u64) -> Resident {
+        Resident { session_id: id.to_string(), last_used_ms, bytes }
+    }
+
+    #[test]
+    fn evicts_idle_then_least_recently_used() {
+        let residents = [resident("new", 900, 40), resident("old", 100, 10), resident("mid", 500, 30)];
+        // "old" is idle; the rest fit
+        assert_eq!(pick(&residents, 1_000, 600, 100), vec!["old"]);
+        // Over the ceiling, the oldest go first until the rest fit
+        assert_eq!(pick(&residents, 1_000, 0, 45), vec!["old", "mid"]);
+        assert!(pick(&residents, 1_000, 0, 0).is_empty());
+    }
+}
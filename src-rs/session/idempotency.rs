@@ -0,0 +1,139 @@
+//! Suppressing duplicate requests.
+//!
+//! A double-click or an IPC retry can deliver the same `execute` or
+//! `confirmTool` call twice, running a turn (and its tools) twice. A call
+//! given an idempotency key runs once per session and key: a repeat while the
+//! first runs, or within `TTL` after it finished, waits for the first and
+//! returns its result, or its error. Keys are chosen by the frontend, e.g. a
+//! UUID per user action.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, LazyLock, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use tokio::sync::OnceCell;
+
+/// How long a key is remembered after its call finished
+pub const TTL: Duration = Duration::from_secs(10 * 60);
+
+type Outcome = Result<Arc<dyn Any + Send + Sync>, String>;
+
+/// Outcome of a key's call and when it finished, set once the call is done
+type Entry = Arc<OnceCell<(Outcome, Instant)>>;
+
+/// Keys by (session id, key)
+static KEYS: LazyLock<StdMutex<HashMap<(String, String), Entry>>> =
+    LazyLock::new(|| StdMutex::new(HashMap::new()));
+
+/// Drop the keys whose call finished more than `TTL` before `now`; a call
+/// still running keeps its key however long it takes
+fn expire(keys: &mut HashMap<(String, String), Entry>, now: Instant) {
+    keys.retain(|_, entry| entry.get().is_none_or(|(_, finished)| now.saturating_duration_since(*finished) < TTL));
+}
+
+/// Run `call` unless a call with `key` already ran or is running in the
+/// session, in which case its outcome is returned. Without a key `call` always runs.
+pub async fn run_once<T, F, Fut>(session_id: &str, key: Option<&str>, call: F) -> Result<T>
+where
+    T: Clone + Send + Sync + 'static,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let Some(key) = key.filter(|k| !k.is_empty()) else {
+        return call().await;
+    };
+    let outcome = {
+        let mut keys = KEYS.lock().map_err(|_| anyhow!("Failed to lock idempotency keys"))?;
+        expire(&mut keys, Instant::now());
+        Arc::clone(keys.entry((session_id.to_string(), key.to_string())).or_default())
+    };
+
+    let mut ran = false;
+    let (outcome, _) = outcome
+        .get_or_init(|| {
+            ran = true;
+            async move {
+                let outcome = match call().await {
+                    Ok(value) => Ok(Arc::new(value) as Arc<dyn Any + Send + Sync>),
+                    Err(e) => Err(format!("{:#}", e)),
+                };
+                (outcome, Instant::now())
+            }
+        })
+        .await;
+    if !ran {
+        log::info!("Duplicate request with key {} in session {} returned the first one's result", key, session_id);
+    }
+    match outcome {
+        Ok(value) => value
+            .downcast_ref::<T>()
+            .cloned()
+            .ok_or_else(|| anyhow!("Idempotency key {} was already used for another kind of request", key)),
+        Err(message) => Err(anyhow!("{}", message)),
+    }
+}
+
+/// Drop the keys of a closed session
+pub fn forget(session_id: &str) {
+    if let Ok(mut keys) = KEYS.lock() {
+        keys.retain(|(session, _), _| session != session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn repeats_share_the_first_outcome() {
+        let runs = AtomicUsize::new(0);
+        let call = || async {
+            runs.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Ok(runs.load(Ordering::SeqCst))
+        };
+        // A retry while the first is running waits for it
+        let (a, b) = tokio::join!(
+            run_once("idem", Some("k1"), call),
+            run_once("idem", Some("k1"), call)
+        );
+        assert_eq!((a.unwrap(), b.unwrap()), (1, 1));
+        assert_eq!(run_once("idem", Some("k1"), call).await.unwrap(), 1);
+        assert_eq!(run_once("idem", Some("k2"), call).await.unwrap(), 2);
+        assert_eq!(run_once("idem", None, call).await.unwrap(), 3);
+        assert_eq!(run_once("other", Some("k1"), call).await.unwrap(), 4);
+
+        // Errors are repeated too, and a key keeps its type
+        let failing = || async { Err::<(), _>(anyhow!("provider down")) };
+        assert!(run_once("idem", Some("k3"), failing).await.is_err());
+        assert_eq!(run_once("idem", Some("k3"), || async { Ok(()) }).await.unwrap_err().to_string(), "provider down");
+        assert!(run_once("idem", Some("k1"), || async { Ok(()) }).await.is_err());
+
+        forget("idem");
+        assert_eq!(run_once("idem", Some("k1"), call).await.unwrap(), 5);
+        assert_eq!(runs.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn keys_expire_only_after_their_call_finished() {
+        let key = |k: &str| ("idem-ttl".to_string(), k.to_string());
+        let start = Instant::now();
+        let finished_at = |at: Instant| {
+            let done: Entry = Arc::default();
+            done.set((Ok(Arc::new(()) as Arc<dyn Any + Send + Sync>), at)).unwrap();
+            done
+        };
+        let mut keys = HashMap::from([
+            (key("running"), Entry::default()),
+            (key("recent"), finished_at(start + TTL)),
+            (key("old"), finished_at(start)),
+        ]);
+        expire(&mut keys, start + TTL + Duration::from_secs(1));
+        assert!(keys.contains_key(&key("running")) && keys.contains_key(&key("recent")));
+        assert!(!keys.contains_key(&key("old")));
+    }
+}
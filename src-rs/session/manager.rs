@@ -1,12 +1,28 @@
 use std::collections::HashMap;
-use std::sync::Mutex as StdMutex;
+use std::sync::{Arc, Mutex as StdMutex};
 
 use lazy_static::lazy_static;
+use tokio::sync::Mutex;
 
 use crate::llm::agents::agent::Agent as RustAgent;
 
 use super::context::{AgentMode, ApprovalMode, SessionContext};
+use super::eviction::Resident;
+use super::store::DEFAULT_NAMESPACE;
 
+/// Key of an open session. Ids are only unique within a namespace, so the
+/// manager, and every per-session registry, keys sessions by both.
+pub fn session_key(namespace: &str, session_id: &str) -> String {
+    format!("{}/{}", namespace, session_id)
+}
+
+/// The namespace and id of a `session_key`
+pub fn split_session_key(key: &str) -> (&str, &str) {
+    key.split_once('/').unwrap_or((DEFAULT_NAMESPACE, key))
+}
+
+/// Open sessions, keyed by `session_key`
+#[derive(Default)]
 pub struct SessionManager {
     sessions: HashMap<String, SessionContext>,
 }
@@ -18,32 +34,73 @@ impl SessionManager {
         }
     }
 
-    pub fn get(&self, session_id: &str) -> Option<&SessionContext> {
-        self.sessions.get(session_id)
+    pub fn get(&self, key: &str) -> Option<&SessionContext> {
+        self.sessions.get(key)
     }
 
-    pub fn get_mut(&mut self, session_id: &str) -> Option<&mut SessionContext> {
-        self.sessions.get_mut(session_id)
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut SessionContext> {
+        self.sessions.get_mut(key)
     }
 
+    /// Add a session of the default namespace
     pub fn add(&mut self, session_id: String, agent: RustAgent) -> &SessionContext {
-        let ctx = SessionContext::new(session_id.clone(), agent, AgentMode::default(), ApprovalMode::default());
-        self.sessions.insert(session_id.clone(), ctx);
-        self.sessions.get(&session_id).expect("Just inserted")
+        self.add_with_context(
+            DEFAULT_NAMESPACE.to_string(),
+            session_id,
+            agent,
+            AgentMode::default(),
+            ApprovalMode::default(),
+        )
+    }
+
+    pub fn add_with_context(
+        &mut self,
+        namespace: String,
+        session_id: String,
+        agent: RustAgent,
+        agent_mode: AgentMode,
+        approval_mode: ApprovalMode,
+    ) -> &SessionContext {
+        let key = session_key(&namespace, &session_id);
+        let mut ctx = SessionContext::new(session_id, agent, agent_mode, approval_mode);
+        ctx.namespace = namespace;
+        self.sessions.insert(key.clone(), ctx);
+        self.sessions.get(&key).expect("Just inserted")
+    }
+
+    /// The session that owns `agent`
+    pub fn find_by_agent_mut(&mut self, agent: &Arc<Mutex<RustAgent>>) -> Option<&mut SessionContext> {
+        self.sessions.values_mut().find(|ctx| Arc::ptr_eq(&ctx.inner, agent))
+    }
+
+    /// Sessions whose agent is in memory, by key
+    pub fn residents(&self) -> Vec<Resident> {
+        self.sessions
+            .iter()
+            .filter(|(_, ctx)| ctx.evicted.is_none())
+            .map(|(key, ctx)| Resident {
+                session_id: key.clone(),
+                last_used_ms: ctx.last_used_ms,
+                bytes: ctx.memory_bytes,
+            })
+            .collect()
     }
 
-    pub fn add_with_context(&mut self, session_id: String, agent: RustAgent, agent_mode: AgentMode, approval_mode: ApprovalMode) -> &SessionContext {
-        let ctx = SessionContext::new(session_id.clone(), agent, agent_mode, approval_mode);
-        self.sessions.insert(session_id.clone(), ctx);
-        self.sessions.get(&session_id).expect("Just inserted")
+    pub fn remove(&mut self, key: &str) -> Option<SessionContext> {
+        self.sessions.remove(key)
     }
 
-    pub fn remove(&mut self, session_id: &str) -> Option<SessionContext> {
-        self.sessions.remove(session_id)
+    /// Every open session, in no particular order
+    pub fn all(&self) -> impl Iterator<Item = &SessionContext> {
+        self.sessions.values()
     }
 
-    pub fn list_ids(&self) -> Vec<String> {
-        self.sessions.keys().cloned().collect()
+    pub fn list_ids_in(&self, namespace: &str) -> Vec<String> {
+        self.sessions
+            .values()
+            .filter(|ctx| ctx.namespace == namespace)
+            .map(|ctx| ctx.session_id.clone())
+            .collect()
     }
 }
 
@@ -1,16 +1,38 @@
+pub mod command;
 pub mod confirm;
 pub mod context;
+pub mod context_header;
+pub mod context_providers;
+pub mod crypto;
+pub mod environment_probe;
+pub mod event_log;
+pub mod events;
+pub mod eviction;
+pub mod answer_format;
 pub mod approval_policy;
+pub mod auto_accept;
+pub mod auto_mode;
 pub mod id;
+pub mod idempotency;
 pub mod manager;
+pub mod output_limit;
+pub mod plan;
+pub mod proposals;
+pub mod protocol;
 pub mod state;
 pub mod types;
+pub mod skills;
+pub mod snapshot_migration;
+pub mod snapshot_writer;
 pub mod store;
+pub mod tool_stats;
+pub mod turn_journal;
+pub mod turn_timing;
 
-pub use confirm::{get_confirmation_status, key_path_from_args, set_confirmation_status};
+pub use confirm::{get_confirmation_status, key_path_from_args, set_confirmation_status, tool_key_path};
 pub use context::SessionContext;
 pub use id::generate_session_id;
 pub use id::generate_request_id;
-pub use manager::{SessionManager, SESSION_MANAGER};
-pub use state::{clear_event_sink, emit_control_event, emit_stream_text, set_event_sink, set_response_stage, set_tool_operation};
-pub use types::{session_tool_operation_tag, ConfirmationStatus, ResponseStage, SessionToolOperation};
+pub use manager::{session_key, SessionManager, SESSION_MANAGER};
+pub use state::{clear_event_sink, emit_control_event, emit_stream_text, flush_events, set_event_sink, set_response_stage, set_tool_operation};
+pub use types::{session_tool_operation_tag, ConfirmDecision, ConfirmationStatus, ResponseStage, SessionToolOperation};
@@ -0,0 +1,52 @@
+//! Limits on the length of a single model response.
+//!
+//! A misbehaving model can repeat itself until the provider's own limit,
+//! costing minutes and tokens. Per `[sessions]`, or per session with
+//! `set_output_limit`, a response that streams more than `max_output_chars`
+//! characters, or about `max_output_tokens` tokens, of text and reasoning is
+//! stopped: the agent stops reading the stream, keeps what was written,
+//! marked truncated in the history, and sends a `PartialResponse` event.
+
+use crate::config::SessionsConfig;
+use crate::llm::models::model_limits::CHARS_PER_TOKEN;
+
+/// 0 disables either limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputLimit {
+    pub max_chars: u64,
+    pub max_tokens: u64,
+}
+
+impl OutputLimit {
+    pub fn from_config(config: &SessionsConfig) -> Self {
+        Self {
+            max_chars: config.max_output_chars,
+            max_tokens: config.max_output_tokens,
+        }
+    }
+
+    /// Characters a response may stream, the tighter of the two limits
+    pub fn max_response_chars(&self) -> Option<usize> {
+        let from_tokens = self.max_tokens.saturating_mul(CHARS_PER_TOKEN as u64);
+        [self.max_chars, from_tokens]
+            .into_iter()
+            .filter(|limit| *limit > 0)
+            .min()
+            .map(|limit| usize::try_from(limit).unwrap_or(usize::MAX))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_tighter_limit_applies() {
+        let limit = |max_chars, max_tokens| OutputLimit { max_chars, max_tokens }.max_response_chars();
+        assert_eq!(limit(0, 0), None);
+        assert_eq!(limit(10_000, 0), Some(10_000));
+        assert_eq!(limit(0, 1_000), Some(1_000 * CHARS_PER_TOKEN));
+        assert_eq!(limit(2_000, 1_000), Some(2_000));
+        assert_eq!(limit(0, u64::MAX), Some(usize::MAX));
+    }
+}
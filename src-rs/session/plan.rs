@@ -0,0 +1,131 @@
+/// Appended to the prompt of the planning turn so the plan can be parsed
+pub const PLAN_FORMAT_INSTRUCTIONS: &str = "\n\nEnd your answer with the plan as a top-level numbered list (`1. ...`, `2. ...`), one self-contained, actionable step per item. Do not make any changes yet.";
+
+/// Plan approved by the user, executed one step per Build-mode turn
+#[derive(Debug, Clone)]
+pub struct PlanRun {
+    pub steps: Vec<String>,
+    /// Index of the next step to execute
+    pub next_step: usize,
+}
+
+impl PlanRun {
+    pub fn new(steps: Vec<String>) -> Self {
+        Self { steps, next_step: 0 }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_step >= self.steps.len()
+    }
+
+    /// Prompt for a Build-mode turn executing step `index`
+    pub fn step_prompt(&self, index: usize) -> String {
+        let mut prompt = String::from("You are executing an approved plan:\n");
+        for (i, step) in self.steps.iter().enumerate() {
+            let marker = if i < index {
+                "done"
+            } else if i == index {
+                "current"
+            } else {
+                "pending"
+            };
+            prompt.push_str(&format!("{}. [{}] {}\n", i + 1, marker, step));
+        }
+        prompt.push_str(&format!(
+            "\nCarry out step {} only: {}\nWhen it is complete, briefly summarize what you changed.",
+            index + 1,
+            self.steps[index]
+        ));
+        prompt
+    }
+}
+
+/// Extract the steps of a top-level numbered list from a plan
+///
+/// Nested (indented) items are folded into their parent step. If the text
+/// contains several numbered lists, the last one is taken as the plan.
+pub fn parse_plan_steps(text: &str) -> Vec<String> {
+    let mut lists: Vec<Vec<String>> = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        if indent < 2 {
+            if let Some(step) = numbered_item(line.trim()) {
+                current.push(step);
+                continue;
+            }
+        }
+
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if indent >= 2 {
+            if let Some(last) = current.last_mut() {
+                last.push(' ');
+                last.push_str(trimmed.trim_start_matches(['-', '*']).trim());
+            }
+        } else if !current.is_empty() {
+            lists.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        lists.push(current);
+    }
+    lists.pop().unwrap_or_default()
+}
+
+/// Text of a `1.` / `1)` list item, without markdown emphasis
+fn numbered_item(line: &str) -> Option<String> {
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest = &line[digits..];
+    let rest = rest.strip_prefix('.').or_else(|| rest.strip_prefix(')'))?;
+    if !rest.starts_with(' ') {
+        return None;
+    }
+    let step = rest.trim().replace("**", "");
+    (!step.is_empty()).then_some(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_last_top_level_list_and_folds_sub_items() {
+        let text = "Context:\n1. not a step\n\nPlan:\n1. **Add** the config field\n   - with a default\n2) Wire it into the client\n```\n3. inside code\n```\n3. Add a test\n\nLet me know.";
+        let steps = parse_plan_steps(text);
+        assert_eq!(
+            steps,
+            vec![
+                "Add the config field with a default".to_string(),
+                "Wire it into the client".to_string(),
+                "Add a test".to_string(),
+            ]
+        );
+        assert!(parse_plan_steps("No list here.").is_empty());
+    }
+
+    #[test]
+    fn step_prompt_marks_progress() {
+        let mut run = PlanRun::new(vec!["a".to_string(), "b".to_string()]);
+        run.next_step = 1;
+        let prompt = run.step_prompt(1);
+        assert!(prompt.contains("1. [done] a"));
+        assert!(prompt.contains("Carry out step 2 only: b"));
+        assert!(!run.is_finished());
+    }
+}
@@ -0,0 +1,295 @@
+//! Changes held back for review in propose mode.
+//!
+//! With `set_propose_mode` on, edit, delete and move calls are not run.
+//! Each is recorded as a proposal, with the diff it would make where that can
+//! be worked out up front, and the model is told the change waits for review.
+//! When the turn ends the pending proposals go out in one `Proposals` event,
+//! and `apply_proposals` runs the ones the user approves, in the order they
+//! were proposed. Later edits of a file are previewed against its content as
+//! the earlier proposals leave it, so their diffs apply one after the other.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::llm::tools::edit::apply_edit;
+use crate::llm::utils::diff_stats::DiffStats;
+use crate::llm::utils::overlay;
+use crate::llm::utils::path_policy::PathPolicy;
+
+use super::types::{CoreDiffStats, CoreProposal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProposalStatus {
+    Pending,
+    Applied,
+    Failed,
+    Discarded,
+}
+
+impl ProposalStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProposalStatus::Pending => "pending",
+            ProposalStatus::Applied => "applied",
+            ProposalStatus::Failed => "failed",
+            ProposalStatus::Discarded => "discarded",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Proposal {
+    pub id: String,
+    pub tool_name: String,
+    /// Arguments the call runs with when applied
+    pub arguments: String,
+    pub summary: String,
+    pub key_path: String,
+    /// Unified diff of the change, when known before running the call
+    pub diff: Option<String>,
+    pub diff_stats: Option<DiffStats>,
+    pub status: ProposalStatus,
+    pub error: Option<String>,
+}
+
+impl Proposal {
+    pub fn to_core(&self) -> CoreProposal {
+        CoreProposal {
+            id: self.id.clone(),
+            tool_name: self.tool_name.clone(),
+            summary: self.summary.clone(),
+            key_path: self.key_path.clone(),
+            diff: self.diff.clone(),
+            diff_stats: self.diff_stats.as_ref().map(|s| CoreDiffStats {
+                file_path: s.file_path.clone(),
+                additions: s.additions as u32,
+                removals: s.removals as u32,
+                hunks: s.hunks as u32,
+                label: s.label(),
+            }),
+            status: self.status.as_str().to_string(),
+            error: self.error.clone(),
+        }
+    }
+}
+
+/// A file change worked out from the arguments of a write or edit call
+pub struct FileChange {
+    pub path: PathBuf,
+    pub content: String,
+    pub diff_stats: DiffStats,
+}
+
+/// Proposals of one session, oldest first
+#[derive(Default)]
+pub struct Proposals {
+    /// Whether mutating calls are proposed instead of run
+    pub enabled: bool,
+    next_id: u32,
+    items: Vec<Proposal>,
+    /// Contents of files as the pending proposals leave them
+    staged: HashMap<PathBuf, String>,
+}
+
+impl Proposals {
+    /// Record a pending proposal; returns its id, such as "p3"
+    pub fn add(
+        &mut self,
+        tool_name: &str,
+        arguments: &str,
+        summary: &str,
+        key_path: &str,
+        diff: Option<String>,
+        change: Option<FileChange>,
+    ) -> String {
+        self.next_id += 1;
+        let id = format!("p{}", self.next_id);
+        let diff_stats = change.map(|change| {
+            self.staged.insert(change.path, change.content);
+            change.diff_stats
+        });
+        self.items.push(Proposal {
+            id: id.clone(),
+            tool_name: tool_name.to_string(),
+            arguments: arguments.to_string(),
+            summary: summary.to_string(),
+            key_path: key_path.to_string(),
+            diff: diff.or_else(|| diff_stats.as_ref().map(|s| s.unified_diff.clone())),
+            diff_stats,
+            status: ProposalStatus::Pending,
+            error: None,
+        });
+        id
+    }
+
+    pub fn staged(&self, path: &Path) -> Option<&str> {
+        self.staged.get(path).map(String::as_str)
+    }
+
+    pub fn pending(&self) -> impl Iterator<Item = &Proposal> {
+        self.items.iter().filter(|p| p.status == ProposalStatus::Pending)
+    }
+
+    /// Pending proposals with the given ids, or all of them if `ids` is empty,
+    /// in the order they were proposed
+    pub fn select(&self, ids: &[String]) -> Result<Vec<Proposal>> {
+        for id in ids {
+            match self.items.iter().find(|p| &p.id == id) {
+                None => return Err(anyhow!("Unknown proposal: {}", id)),
+                Some(p) if p.status != ProposalStatus::Pending => {
+                    return Err(anyhow!("Proposal {} is already {}", id, p.status.as_str()))
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(self
+            .pending()
+            .filter(|p| ids.is_empty() || ids.contains(&p.id))
+            .cloned()
+            .collect())
+    }
+
+    /// Record the outcome of applying a proposal; returns the proposal updated
+    pub fn resolve(&mut self, id: &str, outcome: std::result::Result<(), String>) -> Option<Proposal> {
+        let proposal = self.items.iter_mut().find(|p| p.id == id)?;
+        match outcome {
+            Ok(()) => proposal.status = ProposalStatus::Applied,
+            Err(e) => {
+                proposal.status = ProposalStatus::Failed;
+                proposal.error = Some(e);
+            }
+        }
+        let resolved = proposal.clone();
+        if resolved.status == ProposalStatus::Failed {
+            // Later previews of the file assumed this change
+            self.staged.clear();
+        }
+        self.forget_staged_if_done();
+        Some(resolved)
+    }
+
+    /// Drop the pending proposals with the given ids, or all of them if `ids`
+    /// is empty. Returns the number discarded.
+    pub fn discard(&mut self, ids: &[String]) -> usize {
+        let mut discarded = 0;
+        for proposal in &mut self.items {
+            if proposal.status == ProposalStatus::Pending && (ids.is_empty() || ids.contains(&proposal.id)) {
+                proposal.status = ProposalStatus::Discarded;
+                discarded += 1;
+            }
+        }
+        if discarded > 0 {
+            self.staged.clear();
+        }
+        self.forget_staged_if_done();
+        discarded
+    }
+
+    fn forget_staged_if_done(&mut self) {
+        if self.pending().next().is_none() {
+            self.staged.clear();
+        }
+    }
+}
+
+/// The change a write or edit call would make, or None for other tools.
+/// `staged` gives the content earlier proposals leave a file with. Runs in
+/// the tool scope of the session, so overlay and path policy apply.
+pub fn preview(
+    tool_name: &str,
+    args_json: &str,
+    staged: impl Fn(&Path) -> Option<String>,
+) -> Result<Option<FileChange>> {
+    if !matches!(tool_name, "write" | "edit") {
+        return Ok(None);
+    }
+    let args: Value = serde_json::from_str(args_json).map_err(|e| anyhow!("Invalid arguments: {}", e))?;
+    let arg = |name: &str| args.get(name).and_then(|v| v.as_str()).unwrap_or_default();
+    let file_path = arg("file_path");
+    let path = PathPolicy::new()?.resolve(file_path)?;
+    let current = match staged(&path) {
+        Some(content) => Some(content),
+        None => fs::read_to_string(overlay::read_path(&path)).ok(),
+    };
+    let content = if tool_name == "write" {
+        arg("content").to_string()
+    } else {
+        let (old_string, new_string) = (arg("old_string"), arg("new_string"));
+        if old_string.is_empty() && current.is_some() {
+            anyhow::bail!("File already exists: {}", file_path);
+        }
+        let Some(current) = current.as_deref().or(old_string.is_empty().then_some("")) else {
+            anyhow::bail!("File not found: {}", file_path);
+        };
+        apply_edit(current, old_string, new_string)?
+    };
+    let current = current.unwrap_or_default();
+    if current == content {
+        anyhow::bail!("New content is the same as old content. No changes made.");
+    }
+    Ok(Some(FileChange {
+        diff_stats: DiffStats::compute(file_path, &current, &content),
+        path,
+        content,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, old: &str, new: &str) -> FileChange {
+        FileChange {
+            path: PathBuf::from(path),
+            content: new.to_string(),
+            diff_stats: DiffStats::compute(path, old, new),
+        }
+    }
+
+    #[test]
+    fn proposals_are_selected_resolved_and_discarded_in_order() {
+        let mut proposals = Proposals::default();
+        let p1 = proposals.add("edit", "{}", "a.rs", "/w/a.rs", None, Some(change("/w/a.rs", "a\n", "b\n")));
+        let p2 = proposals.add("delete", "{}", "old.rs", "/w/old.rs", None, None);
+        let p3 = proposals.add("edit", "{}", "a.rs", "/w/a.rs", None, Some(change("/w/a.rs", "b\n", "c\n")));
+        assert_eq!((p1.as_str(), p2.as_str(), p3.as_str()), ("p1", "p2", "p3"));
+        assert_eq!(proposals.staged(Path::new("/w/a.rs")), Some("c\n"));
+        let first = proposals.pending().next().unwrap().to_core();
+        assert_eq!(first.status, "pending");
+        assert!(first.diff.unwrap().contains("+b"));
+        assert_eq!(first.diff_stats.unwrap().label, "+1 \u{2212}1 /w/a.rs");
+
+        let ids = |ps: Vec<Proposal>| ps.into_iter().map(|p| p.id).collect::<Vec<_>>();
+        assert_eq!(ids(proposals.select(&[]).unwrap()), ["p1", "p2", "p3"]);
+        assert_eq!(ids(proposals.select(&[p3.clone(), p1.clone()]).unwrap()), ["p1", "p3"]);
+        assert!(proposals.select(&["p9".to_string()]).is_err());
+
+        assert_eq!(proposals.resolve(&p1, Ok(())).unwrap().status, ProposalStatus::Applied);
+        assert!(proposals.select(&[p1]).is_err());
+        assert_eq!(proposals.staged(Path::new("/w/a.rs")), Some("c\n"));
+        assert_eq!(proposals.discard(&[p2]), 1);
+        assert_eq!(proposals.staged(Path::new("/w/a.rs")), None);
+        let failed = proposals.resolve(&p3, Err("old_string not found in file".to_string())).unwrap();
+        assert_eq!(failed.to_core().error.as_deref(), Some("old_string not found in file"));
+        assert_eq!(proposals.pending().count(), 0);
+        assert_eq!(proposals.discard(&[]), 0);
+    }
+    #[test]
+    fn previews_edits_against_staged_content() {
+        let staged = |path: &Path| path.ends_with("staged.txt").then(|| "one\ntwo\n".to_string());
+        let edit = r#"{"file_path": "proposal-test/staged.txt", "old_string": "two", "new_string": "three"}"#;
+        let change = preview("edit", edit, staged).unwrap().unwrap();
+        assert_eq!(change.content, "one\nthree\n");
+        assert_eq!((change.diff_stats.additions, change.diff_stats.removals), (1, 1));
+
+        let write = r#"{"file_path": "proposal-test/new.txt", "content": "hi\n"}"#;
+        assert_eq!(preview("write", write, staged).unwrap().unwrap().diff_stats.additions, 1);
+        let missing = r#"{"file_path": "proposal-test/new.txt", "old_string": "a", "new_string": "b"}"#;
+        assert!(preview("edit", missing, staged).is_err());
+        assert!(preview("delete", r#"{"path": "x"}"#, staged).unwrap().is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//! Event protocol versions and what a frontend understands.
+//!
+//! Version 1 had the event types `Text` to `Error` and the event fields up to
+//! `errorMessage`. Version 2 added the rest up to `turnDiff`, version 3
+//! `toolCallId` and version 4 `error` and `PartialResponse` events, each as a
+//! named feature. A frontend calls `negotiate_protocol` with the version it
+//! was built for and, optionally, the features it handles; every subscription
+//! is then sent events at that level. Event types it does not know are
+//! dropped, except that a block or a question it cannot show becomes an
+//! `Error`, and fields it does not know are cleared. Without negotiation
+//! events are sent as they are, so the core and the UI can be upgraded
+//! independently.
+
+use std::sync::RwLock;
+
+use anyhow::{bail, Result};
+
+use super::types::{CoreEvent, CoreEventType, CoreTextEvent, CORE_EVENT_PROTOCOL_VERSION};
+
+/// Oldest version events can be downleveled to
+pub const MIN_EVENT_PROTOCOL_VERSION: u16 = 1;
+
+/// Optional features, with the version that introduced them
+const FEATURES: [(&str, u16); 14] = [
+    // Warning, Resuming, Security and FileConflict events, `warning`
+    ("warnings", 2),
+    // PlanStepStart and PlanStepEnd events, `planStep`
+    ("planSteps", 2),
+    // Checkpoint events
+    ("checkpoints", 2),
+    // Blocked events, `stopDetails`
+    ("stopDetails", 2),
+    // Citations events, `citations`
+    ("citations", 2),
+    // Usage events, `toolStats` and `turnTiming`
+    ("usage", 2),
+    // Proposals events, `proposals`
+    ("proposals", 2),
+    // UserInputRequested events, `question`
+    ("userQuestions", 2),
+    // TurnDiffReady events, `turnDiff`
+    ("turnDiffs", 2),
+    // `toolDisplayName`, `diffStats` and `timeoutMs` on tool events
+    ("toolDetails", 2),
+    // `fileReferences` on End
+    ("fileReferences", 2),
+    // `toolCallId` on tool events, for `cancel_tool`
+    ("toolCallIds", 3),
+    // `error` on Error events, for provider errors the user has to act on
+    ("errorCodes", 4),
+    // PartialResponse events
+    ("partialResponses", 4),
+];
+
+/// Version and features negotiated by the frontend; None sends events as they are
+static NEGOTIATED: RwLock<Option<ProtocolLevel>> = RwLock::new(None);
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct ProtocolInfo {
+    /// Version the core emits
+    pub version: u32,
+    pub min_version: u32,
+    /// Features of the current version
+    pub features: Vec<String>,
+    /// Version events are sent with, after `negotiate_protocol`
+    pub negotiated_version: u32,
+    pub negotiated_features: Vec<String>,
+}
+
+/// What a frontend understands
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolLevel {
+    version: u16,
+    features: Vec<&'static str>,
+}
+
+impl ProtocolLevel {
+    /// `features` limited to those of `version`; None takes all of them.
+    /// Names the core does not know are ignored.
+    pub fn new(version: u16, features: Option<&[String]>) -> Result<Self> {
+        if version < MIN_EVENT_PROTOCOL_VERSION {
+            bail!(
+                "Event protocol version {} is not supported; the oldest supported is {}",
+                version,
+                MIN_EVENT_PROTOCOL_VERSION
+            );
+        }
+        // A newer frontend gets what this core has
+        let version = version.min(CORE_EVENT_PROTOCOL_VERSION);
+        let features = FEATURES
+            .iter()
+            .filter(|(name, since)| {
+                *since <= version && features.is_none_or(|wanted| wanted.iter().any(|w| w == name))
+            })
+            .map(|(name, _)| *name)
+            .collect();
+        Ok(Self { version, features })
+    }
+
+    fn has(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+
+    /// The event as this level expects it, or None when it has no counterpart
+    pub fn downlevel(&self, mut event: CoreEvent) -> Option<CoreEvent> {
+        event.protocol_version = self.version;
+        let required = match event.event_type {
+            CoreEventType::Warning
+            | CoreEventType::Resuming
+            | CoreEventType::Security
+            | CoreEventType::FileConflict => Some("warnings"),
+            CoreEventType::PlanStepStart | CoreEventType::PlanStepEnd => Some("planSteps"),
+            CoreEventType::Checkpoint => Some("checkpoints"),
+            CoreEventType::Blocked => Some("stopDetails"),
+            CoreEventType::Citations => Some("citations"),
+            CoreEventType::Usage => Some("usage"),
+            CoreEventType::Proposals => Some("proposals"),
+            CoreEventType::UserInputRequested => Some("userQuestions"),
+            CoreEventType::TurnDiffReady => Some("turnDiffs"),
+            CoreEventType::PartialResponse => Some("partialResponses"),
+            _ => None,
+        };
+        if let Some(feature) = required.filter(|f| !self.has(f)) {
+            // What the user must see or act on is shown as an error
+            let message = match event.event_type {
+                CoreEventType::Blocked => event.stop_details.as_ref().map(|d| d.message.clone()),
+                CoreEventType::UserInputRequested => event
+                    .question
+                    .as_ref()
+                    .map(|q| format!("The assistant asked a question this client cannot show: {}", q.question)),
+                _ => None,
+            };
+            let Some(message) = message else {
+                log::debug!("Dropping an event that needs the '{}' protocol feature", feature);
+                return None;
+            };
+            event.event_type = CoreEventType::Error;
+            event.error_message = Some(message);
+        }
+        if !self.has("warnings") {
+            event.warning = None;
+        }
+        if !self.has("planSteps") {
+            event.plan_step = None;
+        }
+        if !self.has("stopDetails") {
+            event.stop_details = None;
+        }
+        if !self.has("citations") {
+            event.citations = None;
+        }
+        if !self.has("usage") {
+            event.tool_stats = None;
+            event.turn_timing = None;
+        }
+        if !self.has("proposals") {
+            event.proposals = None;
+        }
+        if !self.has("userQuestions") {
+            event.question = None;
+        }
+        if !self.has("turnDiffs") {
+            event.turn_diff = None;
+        }
+        if !self.has("toolDetails") {
+            event.tool_display_name = None;
+            event.diff_stats = None;
+            event.timeout_ms = None;
+        }
+        if !self.has("fileReferences") {
+            event.file_references = None;
+        }
+        if !self.has("toolCallIds") {
+            event.tool_call_id = None;
+        }
+        if !self.has("errorCodes") {
+            event.error = None;
+        }
+        Some(event)
+    }
+}
+
+/// Send every subscription's events at `version` with `features`
+pub fn negotiate(version: u16, features: Option<&[String]>) -> Result<ProtocolInfo> {
+    let level = ProtocolLevel::new(version, features)?;
+    log::info!("Event protocol negotiated: version {}, features {:?}", level.version, level.features);
+    *NEGOTIATED.write().unwrap_or_else(|e| e.into_inner()) = Some(level);
+    Ok(info())
+}
+
+pub fn info() -> ProtocolInfo {
+    let negotiated = NEGOTIATED.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let (negotiated_version, negotiated_features) = match negotiated {
+        Some(level) => (level.version, level.features),
+        None => (CORE_EVENT_PROTOCOL_VERSION, FEATURES.iter().map(|(name, _)| *name).collect()),
+    };
+    ProtocolInfo {
+        version: CORE_EVENT_PROTOCOL_VERSION as u32,
+        min_version: MIN_EVENT_PROTOCOL_VERSION as u32,
+        features: FEATURES.iter().map(|(name, _)| name.to_string()).collect(),
+        negotiated_version: negotiated_version as u32,
+        negotiated_features: negotiated_features.into_iter().map(str::to_string).collect(),
+    }
+}
+
+/// A control event at the negotiated level
+pub(crate) fn downlevel(event: CoreEvent) -> Option<CoreEvent> {
+    match &*NEGOTIATED.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(level) => level.downlevel(event),
+        None => Some(event),
+    }
+}
+
+/// A text event at the negotiated level; text exists in every version
+pub(crate) fn downlevel_text(mut event: CoreTextEvent) -> CoreTextEvent {
+    if let Some(level) = &*NEGOTIATED.read().unwrap_or_else(|e| e.into_inner()) {
+        event.protocol_version = level.version;
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::types::{CoreStopDetails, CoreWarning};
+
+    fn event(event_type: CoreEventType) -> CoreEvent {
+        CoreEvent {
+            session_id: "s".to_string(),
+            event_type,
+            seq: Some(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn downlevels_to_what_the_frontend_knows() {
+        let v1 = ProtocolLevel::new(1, None).unwrap();
+        let mut tool_end = event(CoreEventType::ToolEnd);
+        tool_end.tool_display_name = Some("Read".to_string());
+        tool_end.tool_call_id = Some("r1".to_string());
+        let v2_tool_end = ProtocolLevel::new(2, None).unwrap().downlevel(tool_end.clone()).unwrap();
+        assert!(v2_tool_end.tool_display_name.is_some() && v2_tool_end.tool_call_id.is_none());
+        let tool_end = v1.downlevel(tool_end).unwrap();
+        assert_eq!(tool_end.protocol_version, 1);
+        assert!(tool_end.tool_display_name.is_none());
+        assert!(v1.downlevel(event(CoreEventType::Usage)).is_none());
+
+        let mut blocked = event(CoreEventType::Blocked);
+        blocked.stop_details = Some(CoreStopDetails {
+            reason: "SAFETY".to_string(),
+            prompt_blocked: true,
+            categories: Vec::new(),
+            message: "The prompt was blocked".to_string(),
+        });
+        let blocked = v1.downlevel(blocked).unwrap();
+        assert!(matches!(blocked.event_type, CoreEventType::Error));
+        assert_eq!(blocked.error_message.as_deref(), Some("The prompt was blocked"));
+        assert!(blocked.stop_details.is_none());
+
+        // A newer frontend naming some features gets those only
+        let level = ProtocolLevel::new(9, Some(&["warnings".to_string(), "unknown".to_string()])).unwrap();
+        assert_eq!(level.version, CORE_EVENT_PROTOCOL_VERSION);
+        assert_eq!(level.features, vec!["warnings"]);
+        let mut warning = event(CoreEventType::Warning);
+        warning.warning = Some(CoreWarning { code: "c".to_string(), message: "m".to_string(), items: Vec::new() });
+        assert!(level.downlevel(warning).unwrap().warning.is_some());
+        assert!(level.downlevel(event(CoreEventType::Checkpoint)).is_none());
+
+        assert!(ProtocolLevel::new(0, None).is_err());
+    }
+}
@@ -0,0 +1,390 @@
+//! Skills: instructions for a kind of task, kept in `<name>/SKILL.md`.
+//!
+//! Skills live in the user's skills directory and, for trusted workspaces, in
+//! `.carry/skills`; a workspace skill hides a user skill of the same name. A
+//! SKILL.md may start with a `---` block of `key: value` lines (`name`,
+//! `description`); the rest is the instructions. The skills active in a
+//! session are the workspace's defaults from the `[skills]` config, those for
+//! the session's agent mode, and the ones the user enabled, and their
+//! instructions are added to the system prompt. Defaults let a team ship
+//! conventions such as a commit style that every session in the workspace
+//! follows.
+//!
+//! A skill with `allowed-tools` (e.g. `allowed-tools: view, grep, glob`)
+//! also limits the session to those tools while it is active: a call of any
+//! other tool is denied with a `ToolDeniedBySkill` event naming the skill.
+//! With several such skills a tool must be allowed by each of them. A bash
+//! entry may carry command patterns, e.g. `bash(git diff:*, git log:*)`: then
+//! only commands made of matching simple commands run. Patterns on other
+//! tools are not supported, and such entries allow nothing.
+//!
+//! Every skill is also a tool the model can call (`skill_<name>`, see
+//! `SkillTool`) to load its instructions when a task needs them, unless its
+//! frontmatter sets `disable-model-invocation: true`; `argument-hint`
+//! describes the call's arguments.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{AppConfig, SkillsConfig};
+use crate::llm::tools::{SkillTool, Tool, ToolAdapter};
+use crate::llm::utils::shell_safety;
+use crate::paths;
+
+use super::context::AgentMode;
+
+pub const SKILL_FILE: &str = "SKILL.md";
+/// Skills one session may enable
+pub const MAX_ENABLED: usize = 32;
+/// Tools no skill can deny: asking the user is not a capability to limit
+const ALWAYS_ALLOWED: &[&str] = &["ask_user"];
+/// Skills offered to the model as tools; each definition is sent on every turn
+const MAX_SKILL_TOOLS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkillSource {
+    User,
+    Workspace,
+}
+
+impl SkillSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkillSource::User => "user",
+            SkillSource::Workspace => "workspace",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Skill {
+    pub name: String,
+    pub description: String,
+    pub instructions: String,
+    pub source: SkillSource,
+    /// Tools the session may call while the skill is active; None allows all
+    pub allowed_tools: Option<Vec<String>>,
+    /// Whether only the user may enable the skill, so the model gets no tool for it
+    pub disable_model_invocation: bool,
+    /// What the skill's tool expects as arguments
+    pub argument_hint: Option<String>,
+}
+
+impl Skill {
+    /// The skill in `content`, named after its directory unless the
+    /// frontmatter names it
+    pub fn parse(dir_name: &str, content: &str, source: SkillSource) -> Self {
+        let (fields, body) = frontmatter(content);
+        Self {
+            name: fields.get("name").cloned().unwrap_or_else(|| dir_name.to_string()),
+            description: fields.get("description").cloned().unwrap_or_default(),
+            instructions: body.trim().to_string(),
+            source,
+            allowed_tools: fields.get("allowed-tools").map(|list| tool_list(dir_name, list)),
+            disable_model_invocation: fields.get("disable-model-invocation").is_some_and(|v| v == "true"),
+            argument_hint: fields.get("argument-hint").filter(|h| !h.is_empty()).cloned(),
+        }
+    }
+
+    /// Whether the skill lets the session call `tool_name` with `args`; a
+    /// bash entry with patterns allows only matching commands, and is taken
+    /// as allowing the tool when `args` is None
+    pub fn allows(&self, tool_name: &str, args: Option<&str>) -> bool {
+        let Some(entries) = &self.allowed_tools else {
+            return true;
+        };
+        entries.iter().any(|entry| {
+            let (name, patterns) = split_entry(entry);
+            if !name.eq_ignore_ascii_case(tool_name) {
+                return false;
+            }
+            match (patterns, args) {
+                (Some(patterns), Some(args)) => bash_command(args)
+                    .is_some_and(|command| shell_safety::matches_patterns(&command, &patterns)),
+                _ => true,
+            }
+        })
+    }
+}
+
+/// Tool name of an `allowed-tools` entry and its argument patterns, if any
+fn split_entry(entry: &str) -> (&str, Option<Vec<&str>>) {
+    match entry.split_once('(') {
+        Some((name, patterns)) => {
+            let patterns = patterns.trim_end_matches(')').split(',').map(str::trim);
+            (name, Some(patterns.filter(|p| !p.is_empty()).collect()))
+        }
+        None => (entry, None),
+    }
+}
+
+/// The `command` argument of a bash call
+fn bash_command(args: &str) -> Option<String> {
+    let args: serde_json::Value = serde_json::from_str(args).ok()?;
+    args.get("command")?.as_str().map(str::to_string)
+}
+
+/// Entries of an `allowed-tools` value: separated by commas or spaces,
+/// optionally in brackets, with names lowercased. Argument patterns are kept
+/// on bash entries; other tools with patterns are dropped with a warning, as
+/// granting the whole tool would allow more than the skill asked for
+fn tool_list(skill: &str, value: &str) -> Vec<String> {
+    let mut tools: Vec<String> = Vec::new();
+    let mut depth = 0;
+    let mut current = String::new();
+    for c in value.trim().trim_start_matches('[').trim_end_matches(']').chars().chain([',']) {
+        match c {
+            '(' => {
+                depth += 1;
+                current.push(c);
+            }
+            ')' => {
+                depth -= 1;
+                current.push(c);
+            }
+            ',' | ' ' if depth == 0 => {
+                let entry = current.trim().trim_matches(|c| c == '"' || c == '\'');
+                let entry = match split_entry(entry) {
+                    (name, None) => name.to_lowercase(),
+                    (name, Some(_)) if !name.eq_ignore_ascii_case("bash") => {
+                        log::warn!(
+                            "Skill '{}' allows {} only with argument patterns, which are supported for bash alone; it is not allowed",
+                            skill,
+                            name
+                        );
+                        String::new()
+                    }
+                    (name, Some(patterns)) => format!("{}({})", name.to_lowercase(), patterns.join(", ")),
+                };
+                if !entry.is_empty() && !tools.contains(&entry) {
+                    tools.push(entry);
+                }
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    tools
+}
+
+/// The `key: value` lines of a leading `---` block, and the text after it
+fn frontmatter(content: &str) -> (BTreeMap<String, String>, &str) {
+    let mut fields = BTreeMap::new();
+    let Some(rest) = content.strip_prefix("---").and_then(|r| r.strip_prefix(['\n', '\r'])) else {
+        return (fields, content);
+    };
+    let rest = rest.trim_start_matches('\n');
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end();
+        if line == "---" {
+            return (fields, &rest[offset..]);
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            fields.insert(key.trim().to_lowercase().replace('_', "-"), value.to_string());
+        }
+    }
+    // No closing line: it was not frontmatter
+    (BTreeMap::new(), content)
+}
+
+/// Directories skills are read from, later ones hiding earlier ones
+pub fn dirs(config: &AppConfig) -> Vec<(SkillSource, PathBuf)> {
+    let mut dirs: Vec<_> = paths::skills_dir().map(|d| (SkillSource::User, d)).into_iter().collect();
+    if config.workspace_trusted {
+        dirs.push((SkillSource::Workspace, Path::new(".carry").join("skills")));
+    }
+    dirs
+}
+
+/// The skills in `dirs`, by name
+pub fn discover(dirs: &[(SkillSource, PathBuf)]) -> BTreeMap<String, Skill> {
+    let mut skills = BTreeMap::new();
+    for (source, dir) in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path().join(SKILL_FILE);
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let skill = Skill::parse(&entry.file_name().to_string_lossy(), &content, *source);
+            skills.insert(skill.name.clone(), skill);
+        }
+    }
+    skills
+}
+
+/// Names of the skills `config` enables for sessions in `mode`
+pub fn defaults(config: &SkillsConfig, mode: &AgentMode) -> Vec<String> {
+    let mode_skills = config.modes.get(&mode.to_string()).into_iter().flatten();
+    dedup(config.default.iter().chain(mode_skills))
+}
+
+/// The workspace defaults for `mode` followed by the user's `enabled` skills
+pub fn active_names(config: &SkillsConfig, mode: &AgentMode, enabled: &[String]) -> Vec<String> {
+    dedup(defaults(config, mode).iter().chain(enabled))
+}
+
+fn dedup<'a>(names: impl Iterator<Item = &'a String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for name in names.map(|n| n.trim()).filter(|n| !n.is_empty()) {
+        if !out.iter().any(|o| o == name) {
+            out.push(name.to_string());
+        }
+    }
+    out
+}
+
+/// The active skills of a session in `mode`; names with no skill are logged and skipped
+pub fn active(config: &AppConfig, mode: &AgentMode, enabled: &[String]) -> Vec<Skill> {
+    let available = discover(&dirs(config));
+    active_names(&config.skills, mode, enabled)
+        .into_iter()
+        .filter_map(|name| {
+            let skill = available.get(&name).cloned();
+            if skill.is_none() {
+                log::warn!("Skill '{}' is enabled but has no {}", name, SKILL_FILE);
+            }
+            skill
+        })
+        .collect()
+}
+
+/// Ok if the active `skills` let the session call `tool_name` with `args`,
+/// else the first skill that does not; without `args` only the tool is checked
+pub fn check_tool_permission<'a>(skills: &'a [Skill], tool_name: &str, args: Option<&str>) -> Result<(), &'a Skill> {
+    if ALWAYS_ALLOWED.contains(&tool_name) {
+        return Ok(());
+    }
+    match skills.iter().find(|skill| !skill.allows(tool_name, args)) {
+        Some(skill) => Err(skill),
+        None => Ok(()),
+    }
+}
+
+/// Tools for the `skills` the model may invoke, skipping names in `taken`
+pub fn tools<'a>(skills: impl IntoIterator<Item = &'a Skill>, taken: &[String]) -> Vec<Box<dyn Tool>> {
+    let mut tools: Vec<Box<dyn Tool>> = Vec::new();
+    for skill in skills.into_iter().filter(|s| !s.disable_model_invocation) {
+        let tool = SkillTool::new(&skill.name, &skill.description, &skill.instructions, skill.argument_hint.as_deref());
+        if taken.contains(&tool.tool_name) || tools.iter().any(|t| t.name() == tool.tool_name) {
+            log::warn!("Skill '{}' gets no tool: {} is taken", skill.name, tool.tool_name);
+            continue;
+        }
+        if tools.len() == MAX_SKILL_TOOLS {
+            log::warn!("Only the first {} skills are offered to the model as tools", MAX_SKILL_TOOLS);
+            break;
+        }
+        tools.push(Box::new(ToolAdapter(tool)));
+    }
+    tools
+}
+
+/// The system prompt section for `skills`, None if there are none
+pub fn prompt_section(skills: &[Skill]) -> Option<String> {
+    if skills.is_empty() {
+        return None;
+    }
+    let mut section = String::from("# Active skills\n\nFollow these instructions whenever they apply.");
+    for skill in skills {
+        section.push_str(&format!("\n\n## {}\n", skill.name));
+        if !skill.description.is_empty() {
+            section.push_str(&format!("{}\n\n", skill.description));
+        }
+        section.push_str(&skill.instructions);
+        if let Some(tools) = &skill.allowed_tools {
+            let tools = tools.join(", ");
+            section.push_str(&format!("\n\nWhile this skill is active, only these tools can be used: {}", tools));
+        }
+    }
+    Some(section)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+    use std::collections::HashMap;
+
+    #[test]
+    fn workspace_defaults_merge_with_enabled_skills() {
+        let root = FakeWorkspace::new()
+            .file("user/commit-style/SKILL.md", "Write short commit subjects.")
+            .file("user/review/SKILL.md", "---\nname: review-checklist\ndescription: \"Before review\"\n---\nRun the tests.\n")
+            .file("workspace/commit-style/SKILL.md", "Start commit subjects with the ticket id.")
+            .build()
+            .unwrap();
+        let skills = discover(&[(SkillSource::User, root.join("user")), (SkillSource::Workspace, root.join("workspace"))]);
+
+        assert_eq!(skills.keys().collect::<Vec<_>>(), ["commit-style", "review-checklist"]);
+        assert_eq!(skills["commit-style"].source, SkillSource::Workspace, "the workspace hides user skills");
+        assert_eq!(skills["review-checklist"].description, "Before review");
+        assert_eq!(skills["review-checklist"].instructions, "Run the tests.");
+
+        let config = SkillsConfig {
+            default: vec!["commit-style".to_string()],
+            modes: HashMap::from([("plan".to_string(), vec!["review-checklist".to_string()])]),
+        };
+        let enabled = vec!["review-checklist".to_string(), "commit-style".to_string()];
+        assert_eq!(active_names(&config, &AgentMode::Build, &[]), ["commit-style"]);
+        assert_eq!(active_names(&config, &AgentMode::Plan, &enabled), ["commit-style", "review-checklist"]);
+
+        let section = prompt_section(&[skills["review-checklist"].clone()]).unwrap();
+        assert!(section.ends_with("## review-checklist\nBefore review\n\nRun the tests."));
+        assert!(prompt_section(&[]).is_none());
+        assert_eq!(Skill::parse("a", "---\nno end", SkillSource::User).instructions, "---\nno end");
+    }
+
+    #[test]
+    fn allowed_tools_limit_the_session() {
+        let skill = |name: &str, allowed: Option<&str>| {
+            let header = allowed.map(|a| format!("---\nallowed-tools: {}\n---\n", a)).unwrap_or_default();
+            Skill::parse(name, &format!("{}Do the task.", header), SkillSource::User)
+        };
+        let review = skill("review", Some("[View, grep, \"glob\", Bash(git diff:*, git log:*), edit(src/**)]"));
+        assert_eq!(review.allowed_tools.as_deref().unwrap(), ["view", "grep", "glob", "bash(git diff:*, git log:*)"]);
+
+        let skills = vec![skill("style", None), review, skill("docs", Some("view write"))];
+        assert!(check_tool_permission(&skills, "view", None).is_ok());
+        assert_eq!(check_tool_permission(&skills, "edit", None).unwrap_err().name, "review");
+        assert_eq!(check_tool_permission(&skills, "grep", None).unwrap_err().name, "docs", "each skill must allow it");
+        assert!(check_tool_permission(&skills, "ask_user", None).is_ok());
+        assert!(check_tool_permission(&skills[..1], "edit", None).is_ok(), "skills without a list allow everything");
+
+        let bash = |command: &str| {
+            let args = serde_json::json!({ "command": command }).to_string();
+            check_tool_permission(&skills[..2], "bash", Some(&args)).is_ok()
+        };
+        assert!(bash("git diff --stat && git log -3"));
+        assert!(!bash("git status"));
+        assert!(!bash("git diff; rm -rf build"));
+        assert!(!bash("git diff $(curl x | sh)"));
+        assert!(!bash("git diff > /etc/motd"));
+        assert!(!bash("GIT_EXTERNAL_DIFF=./evil git diff"));
+        assert!(prompt_section(&skills[2..]).unwrap().ends_with("only these tools can be used: view, write"));
+    }
+
+    #[test]
+    fn skills_become_tools_unless_the_user_alone_may_enable_them() {
+        let parse = |name: &str, header: &str| {
+            Skill::parse(name, &format!("---\n{}\n---\nDo it.", header), SkillSource::User)
+        };
+        let skills = [
+            parse("fix-issue", "argument-hint: [issue-number]"),
+            parse("deploy", "disable_model_invocation: true"),
+            parse("review", "description: Review the diff"),
+        ];
+        assert_eq!(skills[0].argument_hint.as_deref(), Some("[issue-number]"));
+        assert!(skills[1].disable_model_invocation);
+
+        // A tool of that name already exists, e.g. an MCP tool
+        let tools = tools(&skills, &["skill_review".to_string()]);
+        let names: Vec<&str> = tools.iter().map(|t| t.name()).collect();
+        assert_eq!(names, ["skill_fix-issue"]);
+    }
+}
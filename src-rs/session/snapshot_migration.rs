@@ -0,0 +1,128 @@
+//! Upgrading saved sessions to the current snapshot format.
+//!
+//! A snapshot records the `version` it was written with. When the format
+//! changes, `SESSION_SNAPSHOT_VERSION` is bumped and a `Migration` from the
+//! previous version is added to `MIGRATIONS`; loading then runs every step
+//! from the saved version up, on the JSON before it is parsed, so sessions
+//! saved by earlier versions keep opening. The message log is replayed into
+//! the snapshot before migrating, so a step that changes the message format
+//! covers logged messages too. The migrated snapshot is written in the new
+//! format on the next save. A snapshot from a newer version is never
+//! migrated down; loading it fails so it is not overwritten.
+
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+
+use super::store::SESSION_SNAPSHOT_VERSION;
+
+/// Turns a snapshot of version `from` into one of version `from + 1`
+pub struct Migration {
+    pub from: u16,
+    pub apply: fn(&mut Map<String, Value>) -> Result<()>,
+}
+
+/// Every step up to `SESSION_SNAPSHOT_VERSION`, oldest first
+const MIGRATIONS: &[Migration] = &[];
+
+/// Raised for a snapshot written by a newer version of CarryCode
+#[derive(Debug)]
+pub struct NewerSnapshotError(pub u16);
+
+impl fmt::Display for NewerSnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the session was saved in snapshot format {}, newer than the {} this version reads",
+            self.0, SESSION_SNAPSHOT_VERSION
+        )
+    }
+}
+
+impl std::error::Error for NewerSnapshotError {}
+
+/// The `version` a snapshot was written with
+pub fn version_of(snapshot: &Value) -> Result<u16> {
+    snapshot
+        .get("version")
+        .and_then(Value::as_u64)
+        .and_then(|v| u16::try_from(v).ok())
+        .context("snapshot has no version")
+}
+
+/// Bring `snapshot` to the current version. Returns whether anything changed.
+pub fn migrate(snapshot: &mut Value) -> Result<bool> {
+    migrate_with(snapshot, SESSION_SNAPSHOT_VERSION, MIGRATIONS)
+}
+
+fn migrate_with(snapshot: &mut Value, to: u16, migrations: &[Migration]) -> Result<bool> {
+    let from = version_of(snapshot)?;
+    if from > to {
+        return Err(NewerSnapshotError(from).into());
+    }
+    let Some(fields) = snapshot.as_object_mut() else {
+        bail!("snapshot is not an object");
+    };
+    for version in from..to {
+        let Some(step) = migrations.iter().find(|m| m.from == version) else {
+            bail!("no migration from snapshot version {}", version);
+        };
+        (step.apply)(fields).with_context(|| format!("failed to migrate snapshot version {}", version))?;
+        fields.insert("version".to_string(), Value::from(version + 1));
+    }
+    if from < to {
+        log::info!("Migrated a session snapshot from version {} to {}", from, to);
+    }
+    Ok(from < to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Messages became `{ role, parts: [{ text }] }`
+    fn structured_messages(fields: &mut Map<String, Value>) -> Result<()> {
+        for message in fields.get_mut("messages").and_then(Value::as_array_mut).context("no messages")? {
+            let text = message.as_object_mut().context("message")?.remove("content").unwrap_or_default();
+            message["parts"] = json!([{ "text": text }]);
+        }
+        Ok(())
+    }
+
+    fn attachments(fields: &mut Map<String, Value>) -> Result<()> {
+        fields.insert("attachments".to_string(), json!([]));
+        Ok(())
+    }
+
+    const STEPS: &[Migration] = &[
+        Migration { from: 2, apply: attachments },
+        Migration { from: 1, apply: structured_messages },
+    ];
+
+    #[test]
+    fn runs_each_step_from_the_saved_version() {
+        let mut snapshot = json!({ "version": 1, "messages": [{ "role": "user", "content": "hi" }] });
+        assert!(migrate_with(&mut snapshot, 3, STEPS).unwrap());
+        assert_eq!(
+            snapshot,
+            json!({ "version": 3, "messages": [{ "role": "user", "parts": [{ "text": "hi" }] }], "attachments": [] })
+        );
+        // Current snapshots are left as they are
+        assert!(!migrate_with(&mut snapshot, 3, STEPS).unwrap());
+
+        let mut partial = json!({ "version": 2, "messages": [] });
+        migrate_with(&mut partial, 3, STEPS).unwrap();
+        assert_eq!(partial, json!({ "version": 3, "messages": [], "attachments": [] }));
+
+        let mut gap = json!({ "version": 0, "messages": [] });
+        assert!(migrate_with(&mut gap, 3, STEPS).unwrap_err().to_string().contains("version 0"));
+        let newer = migrate_with(&mut json!({ "version": 4 }), 3, STEPS).unwrap_err();
+        assert!(newer.is::<NewerSnapshotError>());
+        assert!(migrate_with(&mut json!({ "messages": [] }), 3, STEPS).is_err());
+
+        // The shipped steps reach the current version
+        assert_eq!(MIGRATIONS.len(), usize::from(SESSION_SNAPSHOT_VERSION - 1));
+    }
+}
@@ -0,0 +1,94 @@
+//! Background persistence of session snapshots.
+//!
+//! Turns and skill toggles schedule a snapshot instead of writing it. A writer
+//! thread waits `DEBOUNCE` after a snapshot is scheduled, then writes the
+//! latest snapshot of every session, so a burst of changes to one session
+//! costs a single write. `flush` writes whatever is pending before returning;
+//! readers of the store call it first so they never see older state than was
+//! scheduled.
+
+use std::collections::HashMap;
+use std::sync::{Condvar, LazyLock, Mutex as StdMutex, Once};
+use std::thread;
+use std::time::Duration;
+
+
+use super::store::{self, SessionSnapshot};
+
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Latest unwritten snapshot per (namespace, session id)
+type Pending = HashMap<(String, String), SessionSnapshot>;
+
+static PENDING: LazyLock<StdMutex<Pending>> = LazyLock::new(|| StdMutex::new(HashMap::new()));
+static SCHEDULED: Condvar = Condvar::new();
+/// Held while a batch is taken and written, so batches land in order
+static WRITING: StdMutex<()> = StdMutex::new(());
+
+static START_WRITER: Once = Once::new();
+
+/// Queue `snapshot` to be written shortly, replacing any queued one for the session
+pub fn schedule(snapshot: SessionSnapshot) {
+    let mut started = true;
+    START_WRITER.call_once(|| {
+        started = thread::Builder::new()
+            .name("snapshot-writer".to_string())
+            .spawn(run_writer)
+            .is_ok();
+    });
+    if !started {
+        log::warn!("Snapshot writer thread failed to start; writing synchronously");
+        write_batch(HashMap::from([(key(&snapshot), snapshot)]));
+        return;
+    }
+
+    let Ok(mut pending) = PENDING.lock() else {
+        return;
+    };
+    pending.insert(key(&snapshot), snapshot);
+    SCHEDULED.notify_one();
+}
+
+/// Write every scheduled snapshot now
+pub fn flush() {
+    let Ok(_writing) = WRITING.lock() else {
+        return;
+    };
+    write_batch(take_pending());
+}
+
+fn key(snapshot: &SessionSnapshot) -> (String, String) {
+    (snapshot.namespace.clone(), snapshot.session_id.clone())
+}
+
+fn take_pending() -> Pending {
+    PENDING.lock().map(|mut p| std::mem::take(&mut *p)).unwrap_or_default()
+}
+
+fn write_batch(batch: Pending) {
+    for snapshot in batch.into_values() {
+        let session_id = snapshot.session_id.clone();
+        if let Err(e) = store::save_session(snapshot) {
+            log::warn!("Failed to persist session {}: {:#}", session_id, e);
+            crate::health::record_error(crate::health::Subsystem::Persistence, format!("{:#}", e));
+        }
+    }
+}
+
+fn run_writer() {
+    loop {
+        {
+            let Ok(mut pending) = PENDING.lock() else {
+                return;
+            };
+            while pending.is_empty() {
+                pending = match SCHEDULED.wait(pending) {
+                    Ok(p) => p,
+                    Err(_) => return,
+                };
+            }
+        }
+        thread::sleep(DEBOUNCE);
+        flush();
+    }
+}
@@ -1,10 +1,18 @@
-use super::manager::SESSION_MANAGER;
+//! Per-session state updated from the turn's async tasks.
+//!
+//! `SESSION_MANAGER` is a std mutex locked from async code, so it is held
+//! only to copy out what is needed. Events in particular are sent with no
+//! lock held, and only queued for the sink's forwarding thread: that thread
+//! is what waits for room in the host's queue while the host, draining it,
+//! may be calling into the core.
+
+use std::sync::Arc;
 
-use napi::threadsafe_function::ThreadsafeFunctionCallMode;
-use napi::Status;
+use super::manager::SESSION_MANAGER;
 
-use super::context::SessionEventSink;
-use super::types::{CoreEvent, CoreEventType, ResponseStage, SessionToolOperation, CORE_EVENT_PROTOCOL_VERSION};
+use super::event_log;
+use super::events::SessionEventSink;
+use super::types::{CoreEvent, CoreTextEvent, ResponseStage, SessionToolOperation, CORE_EVENT_PROTOCOL_VERSION};
 
 pub fn set_response_stage(session_id: &str, stage: ResponseStage) {
     if let Ok(manager) = SESSION_MANAGER.lock() {
@@ -37,10 +45,7 @@ pub fn set_event_sink(session_id: &str, sink: SessionEventSink) -> bool {
     if let Ok(manager) = SESSION_MANAGER.lock() {
         if let Some(ctx) = manager.get(session_id) {
             if let Ok(mut guard) = ctx.event_sink.lock() {
-                *guard = Some(sink);
-            }
-            if let Ok(mut seq) = ctx.event_seq.lock() {
-                *seq = 0;
+                *guard = Some(Arc::new(sink));
             }
             return true;
         }
@@ -58,68 +63,52 @@ pub fn clear_event_sink(session_id: &str) {
     }
 }
 
-fn next_seq(session_id: &str) -> Option<i64> {
+/// Wait until the events emitted so far have reached the session's subscriber
+pub async fn flush_events(session_id: &str) {
+    let sink = session_target(session_id).and_then(|target| target.sink);
+    if let Some(sink) = sink {
+        sink.flush().await;
+    }
+}
+
+/// The session's namespace, public id and sink, if it is open. A sink
+/// replaced after this may still get the event being sent.
+struct SessionTarget {
+    namespace: String,
+    session_id: String,
+    sink: Option<Arc<SessionEventSink>>,
+}
+
+fn session_target(key: &str) -> Option<SessionTarget> {
     let manager = SESSION_MANAGER.lock().ok()?;
-    let ctx = manager.get(session_id)?;
-    let mut guard = ctx.event_seq.lock().ok()?;
-    *guard = guard.saturating_add(1);
-    Some(*guard)
+    let ctx = manager.get(key)?;
+    let sink = ctx.event_sink.lock().ok().and_then(|guard| guard.clone());
+    Some(SessionTarget { namespace: ctx.namespace.clone(), session_id: ctx.session_id.clone(), sink })
 }
 
+/// Events carry the id the host opened the session with, not its key
 pub fn emit_stream_text(session_id: &str, text: String) {
-    let seq = next_seq(session_id);
-    let event = CoreEvent {
+    let Some(SessionTarget { session_id, sink: Some(sink), .. }) = session_target(session_id) else {
+        return;
+    };
+    sink.send_text(CoreTextEvent {
         protocol_version: CORE_EVENT_PROTOCOL_VERSION,
-        session_id: session_id.to_string(),
+        session_id,
         ts_ms: now_ms(),
-        event_type: CoreEventType::Text,
-        seq,
-        text: Some(text),
-        stage: None,
-        tool_operation: None,
-        tool_name: None,
-        key_path: None,
-        kind: None,
-        args_summary: None,
-        response_summary: None,
-        display_text: None,
-        success: None,
-        confirm: None,
-        error_message: None,
-    };
-
-    if let Ok(manager) = SESSION_MANAGER.lock() {
-        if let Some(ctx) = manager.get(session_id) {
-            if let Ok(guard) = ctx.event_sink.lock() {
-                if let Some(sink) = guard.as_ref() {
-                    let _ = sink.handler.call(Ok(event), ThreadsafeFunctionCallMode::NonBlocking);
-                }
-            }
-        }
-    }
+        seq: 0,
+        text,
+    });
 }
 
-pub fn emit_control_event(session_id: &str, event: CoreEvent) {
-    if let Ok(manager) = SESSION_MANAGER.lock() {
-        if let Some(ctx) = manager.get(session_id) {
-            if let Ok(guard) = ctx.event_sink.lock() {
-                if let Some(sink) = guard.as_ref() {
-                    let mut event = event;
-                    if event.seq.is_none() {
-                        if let Ok(mut seq_guard) = ctx.event_seq.lock() {
-                            *seq_guard = seq_guard.saturating_add(1);
-                            event.seq = Some(*seq_guard);
-                        }
-                    }
-                    let status =
-                        sink.handler
-                            .call(Ok(event.clone()), ThreadsafeFunctionCallMode::NonBlocking);
-                    if status != Status::Ok {
-                        let _ =
-                            sink.handler.call(Ok(event), ThreadsafeFunctionCallMode::Blocking);
-                    }
-                }
-            }
-        }
+pub fn emit_control_event(session_id: &str, mut event: CoreEvent) {
+    let Some(target) = session_target(session_id) else {
+        crate::notifier::on_event(&event);
+        return;
+    };
+    event.session_id = target.session_id;
+    event_log::record(&target.namespace, &event.session_id, &event);
+    crate::notifier::on_event(&event);
+    if let Some(sink) = target.sink {
+        sink.send_control(event);
     }
 }
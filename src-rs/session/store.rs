@@ -1,31 +1,87 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::io::{ErrorKind, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex as StdMutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use super::crypto;
+use super::snapshot_migration::{self, NewerSnapshotError};
 use crate::llm::models::provider_handle::Message;
 
+/// Format of `SessionSnapshot`; older snapshots are upgraded by `snapshot_migration`
 pub const SESSION_SNAPSHOT_VERSION: u16 = 1;
 
+/// First line of a snapshot file, followed by the sha256 of the JSON below it
+const SNAPSHOT_CHECKSUM_HEADER: &str = "#carry-snapshot sha256=";
+const SNAPSHOT_FILE: &str = "snapshot.json";
+/// The snapshot replaced by the last write, loaded when the current one is damaged
+const SNAPSHOT_BACKUP_FILE: &str = "snapshot.json.bak";
+
+/// Messages added since the snapshot was written, one `LogRecord` per line
+const MESSAGE_LOG_FILE: &str = "messages.jsonl";
+/// Log records after which the next write folds the log into a new snapshot
+const COMPACT_AFTER_RECORDS: usize = 256;
+
+/// Namespace of sessions not opened on behalf of a particular user
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+fn default_namespace() -> String {
+    DEFAULT_NAMESPACE.to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionSnapshot {
     pub version: u16,
     pub session_id: String,
+    /// Owner of the session; snapshots live under `sessions/<namespace>/`
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
     pub created_at_ms: i64,
     pub updated_at_ms: i64,
     pub agent_mode: String,
     pub approval_mode: String,
     pub messages: Vec<Message>,
+    /// Indices of messages the user pinned, kept verbatim by compaction
+    #[serde(default)]
+    pub pinned: Vec<usize>,
+    /// Workspace the session works in, as `workspace_key` writes it; None for
+    /// sessions saved before it was recorded
+    #[serde(default)]
+    pub workspace: Option<String>,
+    /// Title the user gave the session
+    #[serde(default)]
+    pub title: Option<String>,
+    /// The user's notes on the session
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Variables the host set on the session, see `session_vars`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub vars: BTreeMap<String, String>,
+    /// Skills the user enabled in the session; workspace defaults are not saved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skills: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionMeta {
     pub version: u16,
     pub session_id: String,
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
     pub created_at_ms: i64,
     pub updated_at_ms: i64,
     pub message_count: usize,
+    #[serde(default)]
+    pub workspace: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
 }
 
 fn now_ms() -> i64 {
@@ -35,41 +91,91 @@ fn now_ms() -> i64 {
         .as_millis() as i64
 }
 
-pub fn validate_session_id(session_id: &str) -> Result<()> {
-    if session_id.is_empty() {
-        anyhow::bail!("session_id is empty");
+fn validate_name(kind: &str, name: &str) -> Result<()> {
+    if name.is_empty() {
+        anyhow::bail!("{} is empty", kind);
     }
-    if session_id.len() > 128 {
-        anyhow::bail!("session_id too long");
+    if name.len() > 128 {
+        anyhow::bail!("{} too long", kind);
     }
-    let ok = session_id
+    let ok = name
         .chars()
         .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
     if !ok {
-        anyhow::bail!("invalid session_id");
+        anyhow::bail!("invalid {}", kind);
     }
     Ok(())
 }
 
+pub fn validate_session_id(session_id: &str) -> Result<()> {
+    validate_name("session_id", session_id)
+}
+
+pub fn validate_namespace(namespace: &str) -> Result<()> {
+    validate_name("namespace", namespace)
+}
+
 fn sessions_root_dir() -> Option<PathBuf> {
-    dirs::home_dir().map(|home| home.join(".carry").join("sessions"))
+    crate::paths::sessions_dir()
 }
 
-fn session_dir(session_id: &str) -> Result<PathBuf> {
-    validate_session_id(session_id)?;
-    let root = sessions_root_dir().context("failed to determine home directory")?;
-    Ok(root.join(session_id))
+fn namespace_dir(namespace: &str) -> Result<PathBuf> {
+    validate_namespace(namespace)?;
+    let root = sessions_root_dir().context("failed to determine the data directory")?;
+    migrate_legacy_sessions(&root);
+    Ok(root.join(namespace))
 }
 
-fn snapshot_path(session_id: &str) -> Result<PathBuf> {
-    Ok(session_dir(session_id)?.join("snapshot.json"))
+/// Data directories whose legacy sessions were already moved
+static MIGRATED_ROOTS: StdMutex<Vec<PathBuf>> = StdMutex::new(Vec::new());
+
+/// Sessions saved before namespaces existed sit in `sessions/<id>/`, where a
+/// namespace of the same name would take them over. The first time the store
+/// is used, they are moved into the default namespace.
+fn migrate_legacy_sessions(root: &Path) {
+    let mut migrated = MIGRATED_ROOTS.lock().unwrap_or_else(|e| e.into_inner());
+    if migrated.iter().any(|r| r == root) {
+        return;
+    }
+    migrated.push(root.to_path_buf());
+    let Ok(entries) = fs::read_dir(root) else {
+        return;
+    };
+    let session_files = [SNAPSHOT_FILE, SNAPSHOT_BACKUP_FILE, "meta.json"];
+    for dir in entries.flatten().map(|e| e.path()) {
+        if !session_files.iter().any(|file| dir.join(file).is_file()) {
+            continue;
+        }
+        let Some(name) = dir.file_name() else {
+            continue;
+        };
+        let target = root.join(DEFAULT_NAMESPACE).join(name);
+        if target.exists() {
+            log::warn!("Not migrating legacy session {}: {} exists", dir.display(), target.display());
+            continue;
+        }
+        let moved = fs::create_dir_all(root.join(DEFAULT_NAMESPACE)).and_then(|_| fs::rename(&dir, &target));
+        if let Err(e) = moved {
+            log::warn!("Failed to migrate legacy session {}: {}", dir.display(), e);
+        }
+    }
 }
 
-fn meta_path(session_id: &str) -> Result<PathBuf> {
-    Ok(session_dir(session_id)?.join("meta.json"))
+pub(crate) fn session_dir(namespace: &str, session_id: &str) -> Result<PathBuf> {
+    validate_session_id(session_id)?;
+    Ok(namespace_dir(namespace)?.join(session_id))
 }
 
-fn atomic_write(path: &Path, content: &str) -> Result<()> {
+/// `file` of a session, if it was written
+fn existing_session_file(namespace: &str, session_id: &str, file: &str) -> Result<Option<PathBuf>> {
+    let path = session_dir(namespace, session_id)?.join(file);
+    Ok(path.exists().then_some(path))
+}
+
+/// Write `content` next to `path` and rename it into place, so readers see
+/// either the old file or the complete new one. With `backup`, the file being
+/// replaced is kept under that name.
+fn atomic_write(path: &Path, content: &str, backup: Option<&Path>) -> Result<()> {
     let parent = path
         .parent()
         .context("missing parent directory for atomic write")?;
@@ -83,95 +189,398 @@ fn atomic_write(path: &Path, content: &str) -> Result<()> {
         .unwrap_or("file");
     let tmp_path = parent.join(format!("{file_name}.tmp.{}", now_ms()));
 
-    fs::write(&tmp_path, content).context("failed to write tmp file")?;
+    let written = fs::File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .context("failed to write tmp file");
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+    if let Some(backup) = backup {
+        if path.exists() {
+            fs::rename(path, backup).context("failed to keep previous file")?;
+        }
+    }
     fs::rename(&tmp_path, path).context("failed to rename tmp file")?;
     Ok(())
 }
 
-pub fn load_snapshot(session_id: &str) -> Result<Option<SessionSnapshot>> {
-    let path = snapshot_path(session_id)?;
-    if !path.exists() {
-        return Ok(None);
+fn sha256_hex(data: &str) -> String {
+    Sha256::digest(data.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn encode_snapshot(snapshot: &SessionSnapshot) -> Result<String> {
+    let json = serde_json::to_string_pretty(snapshot).context("failed to serialize snapshot")?;
+    Ok(format!("{}{}\n{}", SNAPSHOT_CHECKSUM_HEADER, sha256_hex(&json), json))
+}
+
+/// Parse a snapshot file as JSON, rejecting it when the checksum does not
+/// match. Files written before checksums were added have no header and are trusted.
+fn decode_snapshot(content: &str) -> Result<Value> {
+    let json = match content.strip_prefix(SNAPSHOT_CHECKSUM_HEADER) {
+        Some(rest) => {
+            let (checksum, json) = rest.split_once('\n').context("truncated snapshot file")?;
+            if checksum.trim() != sha256_hex(json) {
+                anyhow::bail!("snapshot checksum mismatch");
+            }
+            json
+        }
+        None => content,
+    };
+    serde_json::from_str(json).context("failed to parse snapshot file")
+}
+
+/// Load a session's snapshot, falling back to the previous copy when the
+/// current one is missing or damaged. Snapshots of older versions are
+/// migrated; one of a newer version is a `NewerSnapshotError`.
+pub fn load_snapshot(namespace: &str, session_id: &str) -> Result<Option<SessionSnapshot>> {
+    let mut first_error = None;
+    for file in [SNAPSHOT_FILE, SNAPSHOT_BACKUP_FILE] {
+        let Some(path) = existing_session_file(namespace, session_id, file)? else {
+            continue;
+        };
+        let decoded = fs::read_to_string(&path)
+            .context("failed to read snapshot file")
+            .and_then(|content| crypto::open(&content))
+            .and_then(|content| decode_snapshot(&content));
+        // Falling back to the older copy would lose the newer one on the next save
+        if let Some(newer) = decoded.as_ref().ok().and_then(|s| snapshot_migration::version_of(s).ok()) {
+            if newer > SESSION_SNAPSHOT_VERSION {
+                return Err(NewerSnapshotError(newer).into());
+            }
+        }
+        let parsed = decoded.and_then(|mut snapshot| {
+            let log = path.with_file_name(MESSAGE_LOG_FILE);
+            // The log follows the current snapshot; its indices do not fit the previous copy
+            let replay = if file == SNAPSHOT_FILE {
+                replay_log(&log, &mut snapshot)?
+            } else {
+                if log.exists() {
+                    log::warn!("Discarding message log {} of the unusable snapshot", log.display());
+                }
+                LogReplay { records: 0, clean: true }
+            };
+            let migrated = snapshot_migration::migrate(&mut snapshot)?;
+            let snapshot: SessionSnapshot = serde_json::from_value(snapshot).context("failed to parse snapshot file")?;
+            Ok((snapshot, replay, migrated))
+        });
+        match parsed {
+            Ok((mut snapshot, replay, migrated)) => {
+                if first_error.is_some() {
+                    log::warn!("Session {} restored from its previous snapshot", session_id);
+                }
+                snapshot.namespace = namespace.to_string();
+                // Later writes may append to the log only when it ends cleanly, is the
+                // one in the session's own directory and the format did not change
+                let dir = session_dir(namespace, session_id)?;
+                if replay.clean && !migrated && file == SNAPSHOT_FILE && path.parent() == Some(dir.as_path()) {
+                    set_log_state(&dir, LogState::of(&snapshot, replay.records));
+                }
+                return Ok(Some(snapshot));
+            }
+            Err(e) => {
+                log::warn!("Unusable snapshot {}: {:#}", path.display(), e);
+                first_error.get_or_insert(e);
+            }
+        }
     }
-    let content = fs::read_to_string(&path).context("failed to read snapshot file")?;
-    let snapshot: SessionSnapshot =
-        serde_json::from_str(&content).context("failed to parse snapshot file")?;
-    if snapshot.version != SESSION_SNAPSHOT_VERSION {
-        return Ok(None);
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(None),
+    }
+}
+
+/// Persist a session's current state. Messages added since the last write are
+/// appended to the message log; the whole snapshot is rewritten only when the
+/// history was changed rather than extended (cleared, compacted), when the log
+/// reaches `COMPACT_AFTER_RECORDS`, or on a session's first write in this process.
+pub fn save_session(snapshot: SessionSnapshot) -> Result<()> {
+    let dir = session_dir(&snapshot.namespace, &snapshot.session_id)?;
+    match log_state(&dir).filter(|state| state.can_append(&snapshot)) {
+        Some(state) => append_to_log(&dir, &state, snapshot),
+        None => save_snapshot(snapshot),
     }
-    Ok(Some(snapshot))
 }
 
+/// Write the full snapshot, folding in and removing the message log
 pub fn save_snapshot(mut snapshot: SessionSnapshot) -> Result<()> {
-    let existing = load_meta(&snapshot.session_id).ok().flatten();
+    let existing = load_meta(&snapshot.namespace, &snapshot.session_id).ok().flatten();
     if let Some(meta) = existing {
         snapshot.created_at_ms = meta.created_at_ms;
     } else if snapshot.created_at_ms <= 0 {
         snapshot.created_at_ms = now_ms();
     }
+    let dir = session_dir(&snapshot.namespace, &snapshot.session_id)?;
+    write_snapshot(&dir, snapshot)
+}
+
+fn write_snapshot(dir: &Path, mut snapshot: SessionSnapshot) -> Result<()> {
     snapshot.updated_at_ms = now_ms();
     snapshot.version = SESSION_SNAPSHOT_VERSION;
 
-    let snapshot_json =
-        serde_json::to_string_pretty(&snapshot).context("failed to serialize snapshot")?;
-    atomic_write(&snapshot_path(&snapshot.session_id)?, &snapshot_json)?;
+    let snapshot_content = crypto::seal_if_enabled(encode_snapshot(&snapshot)?)?;
+    atomic_write(
+        &dir.join(SNAPSHOT_FILE),
+        &snapshot_content,
+        Some(&dir.join(SNAPSHOT_BACKUP_FILE)),
+    )?;
+    write_meta(dir, &snapshot)?;
 
+    // Should this fail or crash, replaying skips records already in the snapshot
+    match fs::remove_file(dir.join(MESSAGE_LOG_FILE)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => {
+            return Err(e).context("failed to remove message log");
+        }
+        _ => {}
+    }
+    set_log_state(dir, LogState::of(&snapshot, 0));
+    Ok(())
+}
+
+fn write_meta(dir: &Path, snapshot: &SessionSnapshot) -> Result<()> {
     let meta = SessionMeta {
         version: SESSION_SNAPSHOT_VERSION,
         session_id: snapshot.session_id.clone(),
+        namespace: snapshot.namespace.clone(),
         created_at_ms: snapshot.created_at_ms,
         updated_at_ms: snapshot.updated_at_ms,
         message_count: snapshot.messages.len(),
+        workspace: snapshot.workspace.clone(),
+        title: snapshot.title.clone(),
+        notes: snapshot.notes.clone(),
     };
     let meta_json = serde_json::to_string_pretty(&meta).context("failed to serialize meta")?;
-    atomic_write(&meta_path(&meta.session_id)?, &meta_json)?;
+    // The title and notes are as private as the history
+    atomic_write(&dir.join("meta.json"), &crypto::seal_if_enabled(meta_json)?, None)
+}
+
+/// Written with `Message`, read back as JSON in the format of the snapshot it follows
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LogRecord<M = Message> {
+    /// The message at `index` in the history
+    Message { index: usize, message: M },
+    Modes { agent_mode: String, approval_mode: String },
+}
+
+/// What is on disk for a session whose log this process may append to
+#[derive(Debug, Clone)]
+struct LogState {
+    messages: usize,
+    /// The last persisted message as JSON, to tell an extended history from a rewritten one
+    last_message: Option<String>,
+    agent_mode: String,
+    approval_mode: String,
+    pinned: Vec<usize>,
+    title: Option<String>,
+    notes: Option<String>,
+    vars: BTreeMap<String, String>,
+    skills: Vec<String>,
+    created_at_ms: i64,
+    records: usize,
+}
+
+impl LogState {
+    fn of(snapshot: &SessionSnapshot, records: usize) -> Self {
+        Self {
+            messages: snapshot.messages.len(),
+            last_message: snapshot.messages.last().and_then(|m| serde_json::to_string(m).ok()),
+            agent_mode: snapshot.agent_mode.clone(),
+            approval_mode: snapshot.approval_mode.clone(),
+            pinned: snapshot.pinned.clone(),
+            title: snapshot.title.clone(),
+            notes: snapshot.notes.clone(),
+            vars: snapshot.vars.clone(),
+            skills: snapshot.skills.clone(),
+            created_at_ms: snapshot.created_at_ms,
+            records,
+        }
+    }
+
+    fn can_append(&self, snapshot: &SessionSnapshot) -> bool {
+        // Pins, titles, notes, variables and skills are rarely changed, so a change rewrites the snapshot
+        if self.records >= COMPACT_AFTER_RECORDS
+            || snapshot.messages.len() < self.messages
+            || snapshot.pinned != self.pinned
+            || snapshot.title != self.title
+            || snapshot.notes != self.notes
+            || snapshot.vars != self.vars
+            || snapshot.skills != self.skills
+        {
+            return false;
+        }
+        let last = self.messages.checked_sub(1).map(|i| &snapshot.messages[i]);
+        last.and_then(|m| serde_json::to_string(m).ok()) == self.last_message
+    }
+}
+
+static LOG_STATES: LazyLock<StdMutex<HashMap<PathBuf, LogState>>> = LazyLock::new(|| StdMutex::new(HashMap::new()));
+
+fn log_state(dir: &Path) -> Option<LogState> {
+    LOG_STATES.lock().ok()?.get(dir).cloned()
+}
+
+fn set_log_state(dir: &Path, state: LogState) {
+    if let Ok(mut states) = LOG_STATES.lock() {
+        states.insert(dir.to_path_buf(), state);
+    }
+}
+
+fn append_to_log(dir: &Path, state: &LogState, mut snapshot: SessionSnapshot) -> Result<()> {
+    let mut records = Vec::new();
+    if state.agent_mode != snapshot.agent_mode || state.approval_mode != snapshot.approval_mode {
+        records.push(LogRecord::Modes {
+            agent_mode: snapshot.agent_mode.clone(),
+            approval_mode: snapshot.approval_mode.clone(),
+        });
+    }
+    for (index, message) in snapshot.messages.iter().enumerate().skip(state.messages) {
+        records.push(LogRecord::Message {
+            index,
+            message: message.clone(),
+        });
+    }
+
+    if !records.is_empty() {
+        let mut lines = String::new();
+        for record in &records {
+            let line = serde_json::to_string(record).context("failed to serialize log record")?;
+            lines.push_str(&crypto::seal_if_enabled(line)?);
+            lines.push('\n');
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(MESSAGE_LOG_FILE))
+            .context("failed to open message log")?;
+        file.write_all(lines.as_bytes())
+            .and_then(|_| file.sync_data())
+            .context("failed to append to message log")?;
+    }
+
+    snapshot.created_at_ms = state.created_at_ms;
+    snapshot.updated_at_ms = now_ms();
+    write_meta(dir, &snapshot)?;
+    set_log_state(dir, LogState::of(&snapshot, state.records + records.len()));
     Ok(())
 }
 
-pub fn load_meta(session_id: &str) -> Result<Option<SessionMeta>> {
-    let path = meta_path(session_id)?;
-    if !path.exists() {
-        return Ok(None);
+struct LogReplay {
+    records: usize,
+    /// False when the log ends in a torn or out-of-order record
+    clean: bool,
+}
+
+/// Apply the message log at `path` to the snapshot it follows, before the
+/// snapshot is migrated
+fn replay_log(path: &Path, snapshot: &mut Value) -> Result<LogReplay> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Ok(LogReplay { records: 0, clean: true });
+        }
+        Err(e) => return Err(e).context("failed to read message log"),
+    };
+
+    let Some(fields) = snapshot.as_object_mut() else {
+        anyhow::bail!("snapshot is not an object");
+    };
+    let Some(Value::Array(mut messages)) = fields.remove("messages") else {
+        anyhow::bail!("snapshot has no messages");
+    };
+    let mut modes = None;
+    let mut records = 0;
+    let mut clean = true;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let record = match crypto::open(line) {
+            Ok(line) => serde_json::from_str::<LogRecord<Value>>(&line).ok(),
+            // Not damage: the log must not be dropped for want of the key
+            Err(e) if e.is::<crypto::DecryptError>() => return Err(e),
+            Err(_) => None,
+        };
+        match record {
+            Some(LogRecord::Message { index, .. }) if index < messages.len() => {}
+            Some(LogRecord::Message { index, message }) if index == messages.len() => {
+                messages.push(message);
+            }
+            Some(LogRecord::Modes {
+                agent_mode,
+                approval_mode,
+            }) => {
+                modes = Some((agent_mode, approval_mode));
+            }
+            _ => {
+                log::warn!("Message log {} ends in a damaged record", path.display());
+                clean = false;
+                break;
+            }
+        }
+        records += 1;
     }
+    fields.insert("messages".to_string(), Value::Array(messages));
+    if let Some((agent_mode, approval_mode)) = modes {
+        fields.insert("agent_mode".to_string(), Value::String(agent_mode));
+        fields.insert("approval_mode".to_string(), Value::String(approval_mode));
+    }
+    Ok(LogReplay { records, clean })
+}
+
+pub fn load_meta(namespace: &str, session_id: &str) -> Result<Option<SessionMeta>> {
+    let Some(path) = existing_session_file(namespace, session_id, "meta.json")? else {
+        return Ok(None);
+    };
     let content = fs::read_to_string(&path).context("failed to read meta file")?;
-    let meta: SessionMeta = serde_json::from_str(&content).context("failed to parse meta file")?;
+    let content = crypto::open(&content)?;
+    let mut meta: SessionMeta = serde_json::from_str(&content).context("failed to parse meta file")?;
+    meta.namespace = namespace.to_string();
     if meta.version != SESSION_SNAPSHOT_VERSION {
         return Ok(None);
     }
     Ok(Some(meta))
 }
 
-pub fn list_saved_sessions() -> Result<Vec<SessionMeta>> {
-    let root = sessions_root_dir().context("failed to determine home directory")?;
-    if !root.exists() {
-        return Ok(Vec::new());
+/// Saved sessions of a namespace, most recently updated first
+pub fn list_saved_sessions(namespace: &str) -> Result<Vec<SessionMeta>> {
+    let dirs = [namespace_dir(namespace)?];
+    let mut session_ids: Vec<String> = Vec::new();
+    for dir in dirs.iter().filter(|d| d.exists()) {
+        for entry in fs::read_dir(dir).context("failed to read sessions directory")? {
+            let path = entry?.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let session_id = match path.file_name().and_then(|n| n.to_str()) {
+                Some(s) => s.to_string(),
+                None => continue,
+            };
+            if validate_session_id(&session_id).is_ok() && !session_ids.contains(&session_id) {
+                session_ids.push(session_id);
+            }
+        }
     }
 
     let mut metas: Vec<SessionMeta> = Vec::new();
-    for entry in fs::read_dir(&root).context("failed to read sessions directory")? {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_dir() {
-            continue;
-        }
-        let session_id = match path.file_name().and_then(|n| n.to_str()) {
-            Some(s) => s.to_string(),
-            None => continue,
-        };
-        if validate_session_id(&session_id).is_err() {
-            continue;
-        }
-
-        match load_meta(&session_id) {
+    for session_id in session_ids {
+        match load_meta(namespace, &session_id) {
             Ok(Some(meta)) => metas.push(meta),
             Ok(None) => {
-                if let Ok(Some(snapshot)) = load_snapshot(&session_id) {
+                if let Ok(Some(snapshot)) = load_snapshot(namespace, &session_id) {
                     metas.push(SessionMeta {
                         version: SESSION_SNAPSHOT_VERSION,
                         session_id: snapshot.session_id,
+                        namespace: snapshot.namespace,
                         created_at_ms: snapshot.created_at_ms,
                         updated_at_ms: snapshot.updated_at_ms,
                         message_count: snapshot.messages.len(),
+                        workspace: snapshot.workspace,
+                        title: snapshot.title,
+                        notes: snapshot.notes,
                     });
                 }
             }
@@ -179,14 +588,154 @@ pub fn list_saved_sessions() -> Result<Vec<SessionMeta>> {
         }
     }
 
-    metas.sort_by(|a, b| b.updated_at_ms.cmp(&a.updated_at_ms));
+    metas.sort_by_key(|b| std::cmp::Reverse(b.updated_at_ms));
+    Ok(metas)
+}
+
+/// Orders for listing saved sessions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SessionSort {
+    /// Most recently updated first
+    #[default]
+    Updated,
+    /// Newest first
+    Created,
+    /// A to Z, untitled sessions last
+    Title,
+    /// Longest first
+    MessageCount,
+}
+
+impl SessionSort {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.to_lowercase().replace('-', "_").as_str() {
+            "updated" | "updated_at" => Ok(SessionSort::Updated),
+            "created" | "created_at" => Ok(SessionSort::Created),
+            "title" => Ok(SessionSort::Title),
+            "message_count" | "messages" => Ok(SessionSort::MessageCount),
+            other => anyhow::bail!("unknown session sort '{}'", other),
+        }
+    }
+}
+
+/// Sort `metas` by `sort`; `reverse` flips the order
+pub fn sort_sessions(metas: &mut [SessionMeta], sort: SessionSort, reverse: bool) {
+    match sort {
+        SessionSort::Updated => metas.sort_by_key(|m| std::cmp::Reverse(m.updated_at_ms)),
+        SessionSort::Created => metas.sort_by_key(|m| std::cmp::Reverse(m.created_at_ms)),
+        SessionSort::MessageCount => metas.sort_by_key(|m| std::cmp::Reverse(m.message_count)),
+        SessionSort::Title => metas.sort_by_cached_key(|m| match &m.title {
+            Some(title) => (false, title.to_lowercase()),
+            None => (true, String::new()),
+        }),
+    }
+    if reverse {
+        metas.reverse();
+    }
+}
+
+/// Remove a saved session's directory: snapshot, message log, meta, event
+/// log and turn journal. Returns false if nothing was saved for it.
+pub fn delete_session(namespace: &str, session_id: &str) -> Result<bool> {
+    let dir = session_dir(namespace, session_id)?;
+    if !dir.is_dir() {
+        return Ok(false);
+    }
+    fs::remove_dir_all(&dir).with_context(|| format!("failed to remove {}", dir.display()))?;
+    if let Ok(mut states) = LOG_STATES.lock() {
+        states.remove(&dir);
+    }
+    Ok(true)
+}
+
+/// Save a copy of a session under `new_id`, titled as a copy. Its events and
+/// turn journal stay with the original.
+pub fn duplicate_session(namespace: &str, session_id: &str, new_id: &str) -> Result<SessionMeta> {
+    validate_session_id(new_id)?;
+    let mut snapshot = load_snapshot(namespace, session_id)?.context("session not found")?;
+    snapshot.session_id = new_id.to_string();
+    snapshot.created_at_ms = 0;
+    snapshot.title = Some(match snapshot.title.take() {
+        Some(title) => format!("{} (copy)", title),
+        None => format!("Copy of {}", session_id),
+    });
+    save_snapshot(snapshot)?;
+    load_meta(namespace, new_id)?.context("failed to read the copy back")
+}
+
+/// Saved sessions of a namespace that work in `workspace` (a `workspace_key`),
+/// most recently updated first
+pub fn list_saved_sessions_for_workspace(namespace: &str, workspace: &str) -> Result<Vec<SessionMeta>> {
+    let mut metas = list_saved_sessions(namespace)?;
+    metas.retain(|m| m.workspace.as_deref() == Some(workspace));
     Ok(metas)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env;
+    use crate::testing::TestHome;
+
+    fn message(content: &str) -> Message {
+        Message {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn snapshot(session_id: &str, messages: &[&str]) -> SessionSnapshot {
+        SessionSnapshot {
+            version: SESSION_SNAPSHOT_VERSION,
+            session_id: session_id.to_string(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            created_at_ms: 0,
+            updated_at_ms: 0,
+            agent_mode: "build".to_string(),
+            approval_mode: "agent".to_string(),
+            messages: messages.iter().map(|m| message(m)).collect(),
+            pinned: Vec::new(),
+            workspace: None,
+            title: None,
+            notes: None,
+            vars: BTreeMap::new(),
+            skills: Vec::new(),
+        }
+    }
+
+    fn contents(snapshot: &SessionSnapshot) -> Vec<&str> {
+        snapshot.messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn sessions_sort_by_each_key() {
+        let meta = |id: &str, created: i64, updated: i64, messages: usize, title: Option<&str>| SessionMeta {
+            version: SESSION_SNAPSHOT_VERSION,
+            session_id: id.to_string(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            created_at_ms: created,
+            updated_at_ms: updated,
+            message_count: messages,
+            workspace: None,
+            title: title.map(str::to_string),
+            notes: None,
+        };
+        let mut metas = vec![
+            meta("a", 1, 30, 5, None),
+            meta("b", 3, 10, 9, Some("zebra")),
+            meta("c", 2, 20, 1, Some("Apple")),
+        ];
+        let ids = |metas: &[SessionMeta]| metas.iter().map(|m| m.session_id.clone()).collect::<Vec<_>>();
+        sort_sessions(&mut metas, SessionSort::Updated, false);
+        assert_eq!(ids(&metas), ["a", "c", "b"]);
+        sort_sessions(&mut metas, SessionSort::Created, false);
+        assert_eq!(ids(&metas), ["b", "c", "a"]);
+        sort_sessions(&mut metas, SessionSort::MessageCount, true);
+        assert_eq!(ids(&metas), ["c", "a", "b"]);
+        sort_sessions(&mut metas, SessionSort::Title, false);
+        assert_eq!(ids(&metas), ["c", "b", "a"]);
+        assert_eq!(SessionSort::parse("message-count").unwrap(), SessionSort::MessageCount);
+        assert!(SessionSort::parse("size").is_err());
+    }
 
     #[test]
     fn validate_session_id_allows_simple_ids() {
@@ -197,17 +746,123 @@ mod tests {
         assert!(validate_session_id("a b").is_err());
     }
 
+    #[test]
+    fn snapshot_checksum_is_verified() {
+        let snapshot = SessionSnapshot {
+            version: SESSION_SNAPSHOT_VERSION,
+            session_id: "s".to_string(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            created_at_ms: 1,
+            updated_at_ms: 1,
+            agent_mode: "build".to_string(),
+            approval_mode: "agent".to_string(),
+            messages: Vec::new(),
+            pinned: Vec::new(),
+            workspace: None,
+            title: None,
+            notes: None,
+            vars: BTreeMap::new(),
+            skills: Vec::new(),
+        };
+        let encoded = encode_snapshot(&snapshot).unwrap();
+        assert!(encoded.starts_with(SNAPSHOT_CHECKSUM_HEADER));
+        assert_eq!(decode_snapshot(&encoded).unwrap()["session_id"], "s");
+        assert!(decode_snapshot(&encoded.replace("\"build\"", "\"plan\"")).is_err());
+        assert!(decode_snapshot(&encoded[..encoded.len() - 3]).is_err());
+
+        // Snapshots from before checksums have no header
+        let plain = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(decode_snapshot(&plain).unwrap()["agent_mode"], "build");
+    }
+
+    #[test]
+    fn message_log_appends_and_replays() {
+        let home = TestHome::new();
+        let dir = home.path().join("log");
+        fs::create_dir_all(&dir).unwrap();
+        let mut snapshot = SessionSnapshot {
+            version: SESSION_SNAPSHOT_VERSION,
+            session_id: "s".to_string(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
+            created_at_ms: 1,
+            updated_at_ms: 1,
+            agent_mode: "build".to_string(),
+            approval_mode: "agent".to_string(),
+            messages: vec![message("one")],
+            pinned: Vec::new(),
+            workspace: None,
+            title: None,
+            notes: None,
+            vars: BTreeMap::new(),
+            skills: Vec::new(),
+        };
+        let load = || {
+            let content = fs::read_to_string(dir.join(SNAPSHOT_FILE)).unwrap();
+            let mut loaded = decode_snapshot(&content).unwrap();
+            let replay = replay_log(&dir.join(MESSAGE_LOG_FILE), &mut loaded).unwrap();
+            (serde_json::from_value::<SessionSnapshot>(loaded).unwrap(), replay)
+        };
+        write_snapshot(&dir, snapshot.clone()).unwrap();
+
+        // Extending the history appends only the new messages
+        snapshot.messages.extend([message("two"), message("three")]);
+        snapshot.agent_mode = "plan".to_string();
+        let state = log_state(&dir).unwrap();
+        assert!(state.can_append(&snapshot));
+        append_to_log(&dir, &state, snapshot.clone()).unwrap();
+        let (loaded, replay) = load();
+        let on_disk = decode_snapshot(&fs::read_to_string(dir.join(SNAPSHOT_FILE)).unwrap()).unwrap();
+        assert_eq!(on_disk["messages"].as_array().unwrap().len(), 1);
+        assert_eq!(loaded.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["one", "two", "three"]);
+        assert_eq!(loaded.agent_mode, "plan");
+        assert!(replay.clean);
+        assert_eq!(replay.records, 3);
+
+        // Nor can a change of pins or of the title
+        let mut pinned = snapshot.clone();
+        pinned.pinned = vec![0];
+        assert!(!log_state(&dir).unwrap().can_append(&pinned));
+        let mut renamed = snapshot.clone();
+        renamed.title = Some("Fix the login flow".to_string());
+        assert!(!log_state(&dir).unwrap().can_append(&renamed));
+
+        // A rewritten history cannot be appended
+        let mut rewritten = snapshot.clone();
+        rewritten.messages = vec![message("summary"), message("three")];
+        assert!(!log_state(&dir).unwrap().can_append(&rewritten));
+
+        // A torn last line is ignored and marks the log as unclean
+        let mut log = fs::OpenOptions::new().append(true).open(dir.join(MESSAGE_LOG_FILE)).unwrap();
+        log.write_all(b"{\"type\":\"message\",\"ind").unwrap();
+        let (loaded, replay) = load();
+        assert_eq!(loaded.messages.len(), 3);
+        assert!(!replay.clean);
+
+        // Compaction folds the log into the snapshot
+        write_snapshot(&dir, rewritten).unwrap();
+        assert!(!dir.join(MESSAGE_LOG_FILE).exists());
+        assert_eq!(load().0.messages.len(), 2);
+    }
+
     #[test]
     fn snapshot_roundtrip() {
-        let original_home = env::var("HOME").ok();
-        let tmp_home = env::temp_dir().join(format!("carrycode-test-home-{}", now_ms()));
-        fs::create_dir_all(&tmp_home).unwrap();
-        env::set_var("HOME", &tmp_home);
+        let home = TestHome::new();
+
+        // Sessions saved before namespaces are moved into the default namespace
+        let legacy_dir = home.path().join("sessions/legacy_session");
+        fs::create_dir_all(&legacy_dir).unwrap();
+        let legacy = serde_json::json!({
+            "version": SESSION_SNAPSHOT_VERSION, "session_id": "legacy_session",
+            "created_at_ms": 1, "updated_at_ms": 1, "agent_mode": "build",
+            "approval_mode": "agent", "messages": []
+        });
+        fs::write(legacy_dir.join("snapshot.json"), legacy.to_string()).unwrap();
 
         let session_id = "test_session_1";
         let snapshot = SessionSnapshot {
             version: SESSION_SNAPSHOT_VERSION,
             session_id: session_id.to_string(),
+            namespace: DEFAULT_NAMESPACE.to_string(),
             created_at_ms: 0,
             updated_at_ms: 0,
             agent_mode: "build".to_string(),
@@ -216,18 +871,167 @@ mod tests {
                 role: "user".to_string(),
                 content: "hello".to_string(),
             }],
+            pinned: Vec::new(),
+            workspace: Some("/work/app".to_string()),
+            title: Some("Greeting".to_string()),
+            notes: None,
+            vars: [("TICKET".to_string(), "PROJ-42".to_string())].into(),
+            skills: vec!["commit-style".to_string()],
         };
         save_snapshot(snapshot).unwrap();
 
-        let loaded = load_snapshot(session_id).unwrap().unwrap();
+        let loaded = load_snapshot(DEFAULT_NAMESPACE, session_id).unwrap().unwrap();
         assert_eq!(loaded.session_id, session_id);
+        assert_eq!(loaded.title.as_deref(), Some("Greeting"));
+        assert_eq!(loaded.vars.get("TICKET").map(String::as_str), Some("PROJ-42"));
+        assert_eq!(loaded.skills, ["commit-style"]);
+        assert_eq!(load_meta(DEFAULT_NAMESPACE, session_id).unwrap().unwrap().title.as_deref(), Some("Greeting"));
         assert_eq!(loaded.messages.len(), 1);
         assert_eq!(loaded.messages[0].role, "user");
         assert_eq!(loaded.messages[0].content, "hello");
 
-        match original_home {
-            Some(v) => env::set_var("HOME", v),
-            None => env::remove_var("HOME"),
+        // Namespaces do not see each other's sessions
+        assert!(home.path().join("sessions/default/test_session_1/snapshot.json").exists());
+        assert!(load_snapshot("alice", session_id).unwrap().is_none());
+        let mut alice = loaded.clone();
+        alice.namespace = "alice".to_string();
+        alice.session_id = "alice_session".to_string();
+        save_snapshot(alice).unwrap();
+        let listed = |ns: &str| -> Vec<String> {
+            list_saved_sessions(ns).unwrap().into_iter().map(|m| m.session_id).collect()
+        };
+        assert_eq!(listed("alice"), vec!["alice_session"]);
+        assert!(load_snapshot(DEFAULT_NAMESPACE, "alice_session").unwrap().is_none());
+        assert!(load_snapshot("../default", session_id).is_err());
+
+        // The legacy session was migrated and no longer reads as a namespace
+        assert!(!legacy_dir.exists());
+        assert_eq!(load_snapshot(DEFAULT_NAMESPACE, "legacy_session").unwrap().unwrap().namespace, "default");
+        let mut default_sessions = listed(DEFAULT_NAMESPACE);
+        default_sessions.sort();
+        assert_eq!(default_sessions, vec!["legacy_session", "test_session_1"]);
+        assert!(listed("legacy_session").is_empty());
+
+        // Only sessions that recorded the workspace are listed for it
+        let for_workspace = |workspace: &str| -> Vec<String> {
+            list_saved_sessions_for_workspace(DEFAULT_NAMESPACE, workspace)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.session_id)
+                .collect()
+        };
+        assert_eq!(for_workspace("/work/app"), vec!["test_session_1"]);
+        assert!(for_workspace("/work/other").is_empty());
+
+        // A damaged snapshot falls back to the copy it replaced
+        let mut second = loaded.clone();
+        second.messages.push(Message {
+            role: "assistant".to_string(),
+            content: "hi".to_string(),
+        });
+        save_snapshot(second).unwrap();
+        assert_eq!(load_snapshot(DEFAULT_NAMESPACE, session_id).unwrap().unwrap().messages.len(), 2);
+        let current = home.path().join("sessions/default/test_session_1/snapshot.json");
+        let damaged = fs::read_to_string(&current).unwrap().replace("\"hi\"", "\"hx\"");
+        fs::write(&current, damaged).unwrap();
+        assert_eq!(load_snapshot(DEFAULT_NAMESPACE, session_id).unwrap().unwrap().messages.len(), 1);
+
+        // The message log is not replayed onto the previous snapshot, whose history it does not extend
+        let mut compacted = loaded.clone();
+        compacted.session_id = "compacted_session".to_string();
+        compacted.messages = ["a", "b", "c"].map(message).to_vec();
+        save_snapshot(compacted.clone()).unwrap();
+        compacted.messages = vec![message("summary")];
+        save_snapshot(compacted.clone()).unwrap();
+        compacted.messages.extend(["d", "e", "f"].map(message));
+        save_session(compacted).unwrap();
+        let current = home.path().join("sessions/default/compacted_session/snapshot.json");
+        assert!(current.with_file_name(MESSAGE_LOG_FILE).exists());
+        let damaged = fs::read_to_string(&current).unwrap().replace("\"summary\"", "\"summarx\"");
+        fs::write(&current, damaged).unwrap();
+        let restored = load_snapshot(DEFAULT_NAMESPACE, "compacted_session").unwrap().unwrap();
+        assert_eq!(restored.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["a", "b", "c"]);
+
+        // A snapshot saved by a newer version is an error rather than a fresh start
+        let newer_dir = home.path().join("sessions/default/newer_session");
+        fs::create_dir_all(&newer_dir).unwrap();
+        let mut newer = legacy.clone();
+        newer["version"] = serde_json::json!(SESSION_SNAPSHOT_VERSION + 1);
+        newer["session_id"] = serde_json::json!("newer_session");
+        fs::write(newer_dir.join("snapshot.json"), newer.to_string()).unwrap();
+        assert!(load_snapshot(DEFAULT_NAMESPACE, "newer_session").unwrap_err().is::<NewerSnapshotError>());
+
+        // A copy keeps the history under a new id; deleting removes every file of a session
+        let copy = duplicate_session(DEFAULT_NAMESPACE, session_id, "copy_session").unwrap();
+        assert_eq!((copy.message_count, copy.title.as_deref()), (1, Some("Greeting (copy)")));
+        assert!(duplicate_session(DEFAULT_NAMESPACE, "missing_session", "copy_2").is_err());
+        assert!(delete_session(DEFAULT_NAMESPACE, session_id).unwrap());
+        assert!(delete_session(DEFAULT_NAMESPACE, "legacy_session").unwrap());
+        assert!(!delete_session(DEFAULT_NAMESPACE, session_id).unwrap());
+        assert!(!home.path().join("sessions/default/legacy_session").exists());
+        assert!(load_snapshot(DEFAULT_NAMESPACE, session_id).unwrap().is_none());
+        assert_eq!(load_snapshot(DEFAULT_NAMESPACE, "copy_session").unwrap().unwrap().messages.len(), 1);
+    }
+
+    #[test]
+    fn deleting_a_session_removes_its_files_and_leaves_its_copy() {
+        let home = TestHome::new();
+        let mut original = snapshot("original", &["one"]);
+        original.title = Some("Fix the login flow".to_string());
+        save_snapshot(original.clone()).unwrap();
+        original.messages.push(message("two"));
+        save_session(original).unwrap();
+        let dir = home.session_dir(DEFAULT_NAMESPACE, "original");
+        assert!(dir.join(MESSAGE_LOG_FILE).exists());
+        assert!(log_state(&dir).is_some());
+
+        // The copy takes the logged messages along with the snapshot
+        let copy = duplicate_session(DEFAULT_NAMESPACE, "original", "copy").unwrap();
+        assert_eq!((copy.message_count, copy.title.as_deref()), (2, Some("Fix the login flow (copy)")));
+
+        assert!(delete_session(DEFAULT_NAMESPACE, "original").unwrap());
+        assert!(!dir.exists());
+        assert!(log_state(&dir).is_none());
+        assert!(!delete_session(DEFAULT_NAMESPACE, "original").unwrap());
+        assert!(load_snapshot(DEFAULT_NAMESPACE, "original").unwrap().is_none());
+
+        let copy = load_snapshot(DEFAULT_NAMESPACE, "copy").unwrap().unwrap();
+        assert_eq!(contents(&copy), ["one", "two"]);
+        let listed: Vec<String> = list_saved_sessions(DEFAULT_NAMESPACE).unwrap().into_iter().map(|m| m.session_id).collect();
+        assert_eq!(listed, ["copy"]);
+
+        // A session saved again under the deleted id starts a new snapshot
+        // rather than appending to a log that is gone
+        save_session(snapshot("original", &["fresh"])).unwrap();
+        assert!(!dir.join(MESSAGE_LOG_FILE).exists());
+        assert_eq!(contents(&load_snapshot(DEFAULT_NAMESPACE, "original").unwrap().unwrap()), ["fresh"]);
+    }
+
+    #[test]
+    fn sessions_are_listed_for_the_workspace_they_were_saved_in() {
+        let _home = TestHome::new();
+        for (id, workspace) in [("in_app", Some("/work/app")), ("in_lib", Some("/work/lib")), ("unrecorded", None)] {
+            let mut saved = snapshot(id, &["hello"]);
+            saved.workspace = workspace.map(str::to_string);
+            save_snapshot(saved).unwrap();
         }
+        let mut elsewhere = snapshot("alice_app", &["hello"]);
+        (elsewhere.namespace, elsewhere.workspace) = ("alice".to_string(), Some("/work/app".to_string()));
+        save_snapshot(elsewhere).unwrap();
+
+        let listed = |namespace: &str, workspace: &str| -> Vec<String> {
+            list_saved_sessions_for_workspace(namespace, workspace)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.session_id)
+                .collect()
+        };
+        assert_eq!(listed(DEFAULT_NAMESPACE, "/work/app"), ["in_app"]);
+        assert_eq!(listed(DEFAULT_NAMESPACE, "/work/lib"), ["in_lib"]);
+        assert_eq!(listed("alice", "/work/app"), ["alice_app"]);
+        // Keys are compared as they are; callers pass them through `workspace_key`
+        assert!(listed(DEFAULT_NAMESPACE, "/work").is_empty());
+        assert!(listed(DEFAULT_NAMESPACE, "/work/app/").is_empty());
+        assert_eq!(list_saved_sessions(DEFAULT_NAMESPACE).unwrap().len(), 3);
     }
 }
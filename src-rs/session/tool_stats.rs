@@ -0,0 +1,124 @@
+//! Per-session tool usage: calls, failures and time spent, by tool name.
+//!
+//! The executor counts each call once it has a result; time is added for
+//! every run of the tool itself, so waiting on a confirmation is not counted
+//! but a preview run before one is. A turn's share is the difference between
+//! the stats at its end and a copy taken when it started.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::types::CoreToolStat;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolStat {
+    pub calls: u32,
+    pub failures: u32,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToolStats(BTreeMap<String, ToolStat>);
+
+impl ToolStats {
+    pub fn record_call(&mut self, tool_name: &str, success: bool) {
+        let stat = self.0.entry(tool_name.to_string()).or_default();
+        stat.calls += 1;
+        if !success {
+            stat.failures += 1;
+        }
+    }
+
+    pub fn record_time(&mut self, tool_name: &str, duration: Duration) {
+        self.0.entry(tool_name.to_string()).or_default().duration += duration;
+    }
+
+    /// What was recorded after `earlier`, a copy of these stats taken before
+    pub fn since(&self, earlier: &ToolStats) -> ToolStats {
+        let mut delta = BTreeMap::new();
+        for (name, now) in &self.0 {
+            let before = earlier.0.get(name).cloned().unwrap_or_default();
+            let stat = ToolStat {
+                calls: now.calls.saturating_sub(before.calls),
+                failures: now.failures.saturating_sub(before.failures),
+                duration: now.duration.saturating_sub(before.duration),
+            };
+            if stat != ToolStat::default() {
+                delta.insert(name.clone(), stat);
+            }
+        }
+        ToolStats(delta)
+    }
+
+    /// Time all tools ran
+    pub fn total_time(&self) -> Duration {
+        self.0.values().map(|s| s.duration).sum()
+    }
+
+    /// Tools by time spent, longest first
+    pub fn to_core(&self) -> Vec<CoreToolStat> {
+        let mut stats: Vec<CoreToolStat> = self
+            .0
+            .iter()
+            .map(|(name, stat)| CoreToolStat {
+                tool_name: name.clone(),
+                calls: stat.calls,
+                failures: stat.failures,
+                success_rate: if stat.calls == 0 {
+                    1.0
+                } else {
+                    f64::from(stat.calls - stat.failures) / f64::from(stat.calls)
+                },
+                duration_ms: stat.duration.as_millis().min(u32::MAX as u128) as u32,
+            })
+            .collect();
+        stats.sort_by(|a, b| b.duration_ms.cmp(&a.duration_ms).then_with(|| a.tool_name.cmp(&b.tool_name)));
+        stats
+    }
+
+    /// One line such as "bash ×3 4.2s (1 failed), read ×5 0.3s"; None without calls
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<String> = self
+            .to_core()
+            .into_iter()
+            .filter(|s| s.calls > 0)
+            .map(|s| {
+                let mut part = format!("{} ×{} {:.1}s", s.tool_name, s.calls, f64::from(s.duration_ms) / 1000.0);
+                if s.failures > 0 {
+                    part.push_str(&format!(" ({} failed)", s.failures));
+                }
+                part
+            })
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turn_share_and_summary() {
+        let mut stats = ToolStats::default();
+        stats.record_call("read", true);
+        stats.record_time("read", Duration::from_millis(100));
+        let turn_start = stats.clone();
+
+        stats.record_time("bash", Duration::from_millis(3000));
+        stats.record_call("bash", true);
+        stats.record_time("bash", Duration::from_millis(1200));
+        stats.record_call("bash", false);
+        stats.record_time("read", Duration::from_millis(300));
+        stats.record_call("read", true);
+
+        let turn = stats.since(&turn_start);
+        assert_eq!(turn.summary().as_deref(), Some("bash ×2 4.2s (1 failed), read ×1 0.3s"));
+        let bash = &turn.to_core()[0];
+        assert_eq!((bash.calls, bash.failures, bash.duration_ms), (2, 1, 4200));
+        assert_eq!(bash.success_rate, 0.5);
+
+        assert_eq!(stats.to_core()[1].calls, 2);
+        assert_eq!(stats.since(&stats).summary(), None);
+    }
+}
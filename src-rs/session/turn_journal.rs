@@ -0,0 +1,278 @@
+//! Write-ahead journal of the turn in progress.
+//!
+//! The snapshot is written when a turn ends, so a host that dies mid-turn
+//! loses the prompt and all the turn did. While a turn runs, `turn.journal`
+//! beside the snapshot records the prompt, every finished round of tool
+//! calls (the messages it added to the history) and the answer text streamed
+//! since, one JSON line each, sealed like the message log. The journal of a
+//! turn that ends is removed once its snapshot is on disk.
+//!
+//! A journal found when the session is opened belongs to a turn that never
+//! ended. If the snapshot holds exactly the history the turn started from,
+//! the turn is kept as an `InterruptedTurn` for the host to resume or
+//! discard; otherwise the journal is stale and dropped.
+
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::sync::Mutex as StdMutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::crypto;
+use super::store;
+use crate::llm::models::provider_handle::Message;
+
+const JOURNAL_FILE: &str = "turn.journal";
+/// Streamed text is written in pieces of about this size; less may be lost
+const TEXT_WRITE_BYTES: usize = 512;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JournalRecord {
+    Start {
+        started_at_ms: i64,
+        /// Messages in the history before the turn
+        base_messages: usize,
+        /// The user message the turn added
+        prompt: String,
+    },
+    Round { messages: Vec<Message> },
+    Text { text: String },
+    /// The stream restarted; the text since the last round was discarded
+    Restart,
+}
+
+/// Journal of the running turn
+pub struct TurnJournal {
+    path: PathBuf,
+    /// Streamed text not yet written
+    text: StdMutex<String>,
+}
+
+impl TurnJournal {
+    /// Start the journal of a turn, replacing any earlier one
+    pub fn start(namespace: &str, session_id: &str, started_at_ms: i64, base_messages: usize, prompt: &str) -> Result<Self> {
+        let dir = store::session_dir(namespace, session_id)?;
+        fs::create_dir_all(&dir).context("failed to create session directory")?;
+        let journal = Self {
+            path: dir.join(JOURNAL_FILE),
+            text: StdMutex::new(String::new()),
+        };
+        let start = line(&JournalRecord::Start {
+            started_at_ms,
+            base_messages,
+            prompt: prompt.to_string(),
+        })?;
+        fs::write(&journal.path, start).context("failed to write turn journal")?;
+        Ok(journal)
+    }
+
+    /// Answer text streamed by the model
+    pub fn text(&self, chunk: &str) {
+        let Ok(mut text) = self.text.lock() else {
+            return;
+        };
+        text.push_str(chunk);
+        if text.len() >= TEXT_WRITE_BYTES {
+            let text = std::mem::take(&mut *text);
+            self.append(&JournalRecord::Text { text });
+        }
+    }
+
+    /// The stream restarted from the beginning of the response
+    pub fn restart(&self) {
+        if let Ok(mut text) = self.text.lock() {
+            text.clear();
+        }
+        self.append(&JournalRecord::Restart);
+    }
+
+    /// A round of tool calls finished, adding `messages` to the history
+    pub fn round(&self, messages: Vec<Message>) {
+        if let Ok(mut text) = self.text.lock() {
+            // Its text is in the round's response
+            text.clear();
+        }
+        self.append(&JournalRecord::Round { messages });
+    }
+
+    /// The turn ended and its snapshot is written
+    pub fn finish(&self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            if e.kind() != ErrorKind::NotFound {
+                log::warn!("Failed to remove turn journal {}: {}", self.path.display(), e);
+            }
+        }
+    }
+
+    /// A failed write costs only recoverability, so it does not fail the turn
+    fn append(&self, record: &JournalRecord) {
+        let written = line(record).and_then(|line| {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(&self.path)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+                .context("failed to append to turn journal")
+        });
+        if let Err(e) = written {
+            log::warn!("{:#}", e);
+        }
+    }
+}
+
+fn line(record: &JournalRecord) -> Result<String> {
+    let json = serde_json::to_string(record).context("failed to serialize journal record")?;
+    Ok(format!("{}\n", crypto::seal_if_enabled(json)?))
+}
+
+/// A turn the process died in the middle of
+#[derive(Debug, Clone)]
+pub struct InterruptedTurn {
+    pub started_at_ms: i64,
+    pub base_messages: usize,
+    pub prompt: String,
+    /// Messages of the rounds of tool calls that finished
+    pub rounds: Vec<Message>,
+    /// Answer text streamed after the last round
+    pub partial_text: String,
+}
+
+impl InterruptedTurn {
+    /// The messages the turn had added to the history when it stopped
+    pub fn messages(&self) -> Vec<Message> {
+        let mut messages = vec![Message {
+            role: "user".to_string(),
+            content: self.prompt.clone(),
+        }];
+        messages.extend(self.rounds.iter().cloned());
+        if !self.partial_text.trim().is_empty() {
+            messages.push(Message {
+                role: "assistant".to_string(),
+                content: format!("{}\n\n[response interrupted]", self.partial_text),
+            });
+        }
+        messages
+    }
+
+    /// Rounds of tool calls that finished
+    pub fn completed_rounds(&self) -> usize {
+        self.rounds.iter().filter(|m| m.role == "assistant").count()
+    }
+}
+
+/// The turn left by a journal in the session's directory, if it continues a
+/// history of `snapshot_messages` messages; a journal that does not is removed
+pub fn recover(namespace: &str, session_id: &str, snapshot_messages: usize) -> Result<Option<InterruptedTurn>> {
+    let path = store::session_dir(namespace, session_id)?.join(JOURNAL_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("failed to read turn journal"),
+    };
+    let turn = replay(&content)?;
+    match turn {
+        Some(turn) if turn.base_messages == snapshot_messages => Ok(Some(turn)),
+        _ => {
+            discard(namespace, session_id)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Remove the session's journal; false if there was none
+pub fn discard(namespace: &str, session_id: &str) -> Result<bool> {
+    let path = store::session_dir(namespace, session_id)?.join(JOURNAL_FILE);
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e).context("failed to remove turn journal"),
+    }
+}
+
+fn replay(content: &str) -> Result<Option<InterruptedTurn>> {
+    let mut turn: Option<InterruptedTurn> = None;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let record = match crypto::open(line) {
+            Ok(line) => serde_json::from_str::<JournalRecord>(&line).ok(),
+            Err(e) if e.is::<crypto::DecryptError>() => return Err(e),
+            Err(_) => None,
+        };
+        // A torn last line is what a crash mid-write leaves
+        let Some(record) = record else {
+            break;
+        };
+        match (record, turn.as_mut()) {
+            (
+                JournalRecord::Start {
+                    started_at_ms,
+                    base_messages,
+                    prompt,
+                },
+                None,
+            ) => {
+                turn = Some(InterruptedTurn {
+                    started_at_ms,
+                    base_messages,
+                    prompt,
+                    rounds: Vec::new(),
+                    partial_text: String::new(),
+                });
+            }
+            (JournalRecord::Round { messages }, Some(turn)) => {
+                turn.rounds.extend(messages);
+                turn.partial_text.clear();
+            }
+            (JournalRecord::Text { text }, Some(turn)) => turn.partial_text.push_str(&text),
+            (JournalRecord::Restart, Some(turn)) => turn.partial_text.clear(),
+            _ => break,
+        }
+    }
+    Ok(turn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn replays_rounds_and_the_text_after_them() {
+        let records = [
+            JournalRecord::Start {
+                started_at_ms: 5,
+                base_messages: 2,
+                prompt: "fix the build".to_string(),
+            },
+            JournalRecord::Text { text: "Let me look".to_string() },
+            JournalRecord::Round {
+                messages: vec![message("assistant", "ToolCallsJSON:[]"), message("user", "ToolResultJSON:{}")],
+            },
+            JournalRecord::Text { text: "dropped".to_string() },
+            JournalRecord::Restart,
+            JournalRecord::Text { text: "The build".to_string() },
+            JournalRecord::Text { text: " is fixed".to_string() },
+        ];
+        let mut content: String = records.iter().map(|r| line(r).unwrap()).collect();
+        content.push_str("{\"type\":\"te");
+
+        let turn = replay(&content).unwrap().unwrap();
+        assert_eq!((turn.started_at_ms, turn.base_messages), (5, 2));
+        assert_eq!(turn.partial_text, "The build is fixed");
+        assert_eq!(turn.completed_rounds(), 1);
+        let messages = turn.messages();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].content, "fix the build");
+        assert!(messages[3].content.starts_with("The build is fixed"));
+
+        assert!(replay("").unwrap().is_none());
+        assert!(replay(&line(&JournalRecord::Restart).unwrap()).unwrap().is_none());
+    }
+}
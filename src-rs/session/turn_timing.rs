@@ -0,0 +1,138 @@
+//! Timing of each turn: time to first token, total duration, and how it
+//! split between the model and the tools.
+//!
+//! A `TurnClock` starts with the turn and is shared with the stream and tool
+//! executor callbacks. Time spent inside the executor, confirmations
+//! included, is not model time; of it, only the runs of the tools count as
+//! tool time, so the two need not add up to the whole turn.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use super::types::CoreTurnTiming;
+
+/// Turns whose timing a session keeps
+pub const MAX_TURN_TIMINGS: usize = 100;
+
+const UNSET: u64 = u64::MAX;
+
+pub struct TurnClock {
+    started: Instant,
+    started_at_ms: i64,
+    first_token_us: AtomicU64,
+    executor_us: AtomicU64,
+}
+
+impl TurnClock {
+    pub fn start(started_at_ms: i64) -> Self {
+        Self {
+            started: Instant::now(),
+            started_at_ms,
+            first_token_us: AtomicU64::new(UNSET),
+            executor_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Called for every streamed token; only the first is kept
+    pub fn mark_token(&self) {
+        let elapsed = micros(self.started.elapsed());
+        let _ = self
+            .first_token_us
+            .compare_exchange(UNSET, elapsed, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Time spent in the tool executor, which the model is not answering
+    pub fn add_executor_time(&self, duration: Duration) {
+        self.executor_us.fetch_add(micros(duration), Ordering::SeqCst);
+    }
+
+    /// Timing of the finished turn; `tool_time` is how long its tools ran
+    pub fn finish(&self, provider: String, model: String, tool_time: Duration) -> TurnTiming {
+        let duration = self.started.elapsed();
+        let first_token = self.first_token_us.load(Ordering::SeqCst);
+        let executor = Duration::from_micros(self.executor_us.load(Ordering::SeqCst));
+        TurnTiming {
+            provider,
+            model,
+            started_at_ms: self.started_at_ms,
+            first_token: (first_token != UNSET).then(|| Duration::from_micros(first_token)),
+            duration,
+            model_time: duration.saturating_sub(executor),
+            tool_time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnTiming {
+    pub provider: String,
+    pub model: String,
+    pub started_at_ms: i64,
+    pub first_token: Option<Duration>,
+    pub duration: Duration,
+    pub model_time: Duration,
+    pub tool_time: Duration,
+}
+
+impl TurnTiming {
+    pub fn to_core(&self) -> CoreTurnTiming {
+        CoreTurnTiming {
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            started_at_ms: self.started_at_ms,
+            first_token_ms: self.first_token.map(millis),
+            duration_ms: millis(self.duration),
+            model_ms: millis(self.model_time),
+            tool_ms: millis(self.tool_time),
+        }
+    }
+}
+
+/// Keep `timing`, dropping the oldest beyond `MAX_TURN_TIMINGS`
+pub fn push(timings: &mut VecDeque<TurnTiming>, timing: TurnTiming) {
+    if timings.len() == MAX_TURN_TIMINGS {
+        timings.pop_front();
+    }
+    timings.push_back(timing);
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().min(u128::from(UNSET - 1)) as u64
+}
+
+fn millis(duration: Duration) -> u32 {
+    duration.as_millis().min(u32::MAX as u128) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_model_and_tool_time() {
+        let clock = TurnClock::start(1_000);
+        std::thread::sleep(Duration::from_millis(20));
+        clock.mark_token();
+        let first = clock.first_token_us.load(Ordering::SeqCst);
+        clock.mark_token();
+        assert_eq!(clock.first_token_us.load(Ordering::SeqCst), first);
+        clock.add_executor_time(Duration::from_millis(5));
+        clock.add_executor_time(Duration::from_millis(5));
+
+        let timing = clock.finish("openai".to_string(), "gpt-4o".to_string(), Duration::from_millis(8));
+        assert!(timing.first_token.unwrap() >= Duration::from_millis(20));
+        assert_eq!(timing.model_time, timing.duration - Duration::from_millis(10));
+
+        let core = timing.to_core();
+        assert_eq!((core.started_at_ms, core.tool_ms), (1_000, 8));
+        assert!(core.first_token_ms.unwrap() >= 20);
+        assert!(TurnClock::start(0).finish(String::new(), String::new(), Duration::ZERO).first_token.is_none());
+
+        let mut timings = VecDeque::new();
+        for _ in 0..=MAX_TURN_TIMINGS {
+            push(&mut timings, timing.clone());
+        }
+        assert_eq!(timings.len(), MAX_TURN_TIMINGS);
+    }
+}
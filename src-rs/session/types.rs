@@ -1,4 +1,6 @@
+#[cfg(feature = "napi")]
 use napi_derive::napi;
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ResponseStage {
@@ -21,6 +23,35 @@ pub enum ConfirmationStatus {
     AllowForSession,
 }
 
+/// Longest denial message passed on to the model, in characters
+pub const MAX_DENIAL_MESSAGE_CHARS: usize = 500;
+
+/// The user's answer to a confirmation request, as sent to the waiting call
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfirmDecision {
+    AllowOnce,
+    AllowForSession,
+    Deny,
+    /// Deny and tell the model why or what to do instead
+    DenyWithMessage { text: String },
+}
+
+impl ConfirmDecision {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, ConfirmDecision::AllowOnce | ConfirmDecision::AllowForSession)
+    }
+
+    /// Name used in the session log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfirmDecision::AllowOnce => "allow_once",
+            ConfirmDecision::AllowForSession => "allow_for_session",
+            ConfirmDecision::Deny => "deny",
+            ConfirmDecision::DenyWithMessage { .. } => "deny_with_message",
+        }
+    }
+}
+
 pub fn session_tool_operation_tag(op: SessionToolOperation) -> &'static str {
     match op {
         SessionToolOperation::Explored => "__EXPLORED__",
@@ -30,9 +61,12 @@ pub fn session_tool_operation_tag(op: SessionToolOperation) -> &'static str {
     }
 }
 
-pub const CORE_EVENT_PROTOCOL_VERSION: u16 = 1;
+/// Version of the events sent to the host; see `protocol` for what changed
+pub const CORE_EVENT_PROTOCOL_VERSION: u16 = 4;
 
-#[napi(string_enum)]
+#[cfg_attr(feature = "napi", napi(string_enum))]
+#[cfg_attr(not(feature = "napi"), derive(Clone, Copy))]
+#[derive(Serialize, Deserialize)]
 pub enum CoreEventType {
     Text,
     StageStart,
@@ -43,58 +77,474 @@ pub enum CoreEventType {
     End,
     ConfirmationRequested,
     Error,
+    Warning,
+    FileConflict,
+    /// A dropped provider stream is being re-issued; see `warning.code`
+    Resuming,
+    PlanStepStart,
+    PlanStepEnd,
+    /// Progress summary of an autonomous run; steer it with `Session.steer`
+    Checkpoint,
+    /// The provider blocked the prompt or stopped the response; see `stopDetails`
+    Blocked,
+    /// Sources the provider cited for the response; see `citations`
+    Citations,
+    /// Tool output looked like a prompt injection; see `warning.items`
+    Security,
+    /// End of a turn; `toolStats` has the tools it ran, `turnTiming` where the time went
+    Usage,
+    /// End of a turn in propose mode; `proposals` has the changes waiting for review
+    Proposals,
+    /// The model asked the user a question; see `question`, answer with `answerQuestion`
+    UserInputRequested,
+    /// End of a turn in build mode with changed files to review, this turn's and
+    /// any left unreviewed before; see `turnDiff`, review with
+    /// `acceptTurnChanges` / `revertTurnChanges`
+    TurnDiffReady,
+    /// The response was cut short, because the stream failed or it passed the
+    /// output limit, and what it had was kept and marked truncated in the
+    /// history; `warning.code` says which, `errorMessage` why
+    PartialResponse,
+    /// A tool call was refused because an active skill does not allow the
+    /// tool; `toolName` and `skill` say which, see `getAllowedTools`
+    ToolDeniedBySkill,
 }
 
-#[napi(object)]
-#[derive(Clone)]
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CoreConfirmationRequest {
-    #[napi(js_name = "requestId")]
     pub request_id: String,
-    #[napi(js_name = "toolName")]
     pub tool_name: String,
+    /// Name to show for the tool, e.g. "create_issue (github)" for an MCP tool
+    pub tool_display_name: String,
     pub arguments: String,
+    /// What the call would do in one line, e.g. the command or "src/a.rs (−3 +5 lines)"
+    pub summary: String,
     pub kind: String,
-    #[napi(js_name = "keyPath")]
     pub key_path: String,
+    /// Changes the call would make (unified diff), for tools that compute them up front
+    pub preview: Option<String>,
 }
 
-#[napi(object)]
+#[cfg_attr(feature = "napi", napi(string_enum))]
+#[cfg_attr(not(feature = "napi"), derive(Clone, Copy))]
+pub enum CoreConfirmDecisionKind {
+    /// Run this call
+    AllowOnce,
+    /// Run this call and later ones of the tool on the same target without asking
+    AllowForSession,
+    Deny,
+    /// Deny, with `message` passed on to the model
+    DenyWithMessage,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
 #[derive(Clone)]
 pub struct CoreConfirmDecision {
-    #[napi(js_name = "requestId")]
     pub request_id: String,
-    pub decision: String,
+    pub decision: CoreConfirmDecisionKind,
+    /// Why the call was denied or what to do instead; used with `DenyWithMessage`
+    pub message: Option<String>,
 }
 
-#[napi(object)]
-#[derive(Clone)]
+impl CoreConfirmDecision {
+    pub fn to_decision(&self) -> ConfirmDecision {
+        match self.decision {
+            CoreConfirmDecisionKind::AllowOnce => ConfirmDecision::AllowOnce,
+            CoreConfirmDecisionKind::AllowForSession => ConfirmDecision::AllowForSession,
+            CoreConfirmDecisionKind::Deny => ConfirmDecision::Deny,
+            CoreConfirmDecisionKind::DenyWithMessage => {
+                let text = self.message.as_deref().unwrap_or_default().trim();
+                if text.is_empty() {
+                    ConfirmDecision::Deny
+                } else {
+                    ConfirmDecision::DenyWithMessage {
+                        text: text.chars().take(MAX_DENIAL_MESSAGE_CHARS).collect(),
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreWarningItem {
+    pub subject: String,
+    pub reason: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreDiffStats {
+    pub file_path: String,
+    pub additions: u32,
+    pub removals: u32,
+    pub hunks: u32,
+    /// Compact form such as "+12 −3 src/foo.rs"
+    pub label: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CorePlanStep {
+    /// 1-based position of the step in the plan
+    pub index: u32,
+    pub total: u32,
+    pub title: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreStopDetails {
+    /// Provider reason as sent, e.g. "SAFETY" or "RECITATION"
+    pub reason: String,
+    pub prompt_blocked: bool,
+    /// Safety categories that triggered the block
+    pub categories: Vec<String>,
+    /// One-line explanation for the user
+    pub message: String,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreCitation {
+    pub uri: Option<String>,
+    pub title: Option<String>,
+    pub license: Option<String>,
+    /// Character range of the cited part of the response
+    pub start_index: Option<u32>,
+    pub end_index: Option<u32>,
+}
+
+/// Calls of one tool and the time they took
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoreToolStat {
+    pub tool_name: String,
+    pub calls: u32,
+    pub failures: u32,
+    /// Share of calls that succeeded, from 0 to 1
+    pub success_rate: f64,
+    pub duration_ms: u32,
+}
+
+/// Where the time of one turn went
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoreTurnTiming {
+    pub provider: String,
+    pub model: String,
+    pub started_at_ms: i64,
+    /// From the start of the turn to the first streamed token; None if the model only called tools
+    pub first_token_ms: Option<u32>,
+    pub duration_ms: u32,
+    /// Waiting on the model, retries included
+    pub model_ms: u32,
+    /// Running tools; time waiting on confirmations is in neither
+    pub tool_ms: u32,
+}
+
+/// A `file:line` reference found in a final answer
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoreFileReference {
+    /// Path as written in the answer
+    pub path: String,
+    pub absolute_path: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    /// Character range of `path:line[:column]` in the answer
+    pub start_index: u32,
+    pub end_index: u32,
+}
+
+/// A change held back for review in propose mode
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreProposal {
+    /// Id to pass to `applyProposals`, such as "p3"
+    pub id: String,
+    pub tool_name: String,
+    /// What the call would do in one line
+    pub summary: String,
+    pub key_path: String,
+    /// Unified diff of the change, when known before it is applied
+    pub diff: Option<String>,
+    pub diff_stats: Option<CoreDiffStats>,
+    /// "pending" | "applied" | "failed" | "discarded"
+    pub status: String,
+    /// Why applying the proposal failed
+    pub error: Option<String>,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreWarning {
+    pub code: String,
+    pub message: String,
+    pub items: Vec<CoreWarningItem>,
+}
+
+/// Provider errors that retrying does not fix; see `CoreError`
+#[cfg_attr(feature = "napi", napi(string_enum))]
+#[cfg_attr(not(feature = "napi"), derive(Clone, Copy))]
+#[derive(Serialize, Deserialize)]
+pub enum CoreErrorCode {
+    /// The API key's quota or plan limit is used up
+    QuotaExceeded,
+    /// The provider account is out of credit
+    CreditExhausted,
+    /// The API key was rejected
+    InvalidApiKey,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreErrorSuggestion {
+    /// "switch_model" | "add_credits" | "update_api_key"
+    pub action: String,
+    /// For "switch_model", models of other providers as "provider:model", which `setModel` takes
+    pub models: Vec<String>,
+}
+
+/// A classified provider error, in place of the provider's raw response
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreError {
+    pub code: CoreErrorCode,
+    /// What happened, in English; frontends show their own text for `code`
+    pub message: String,
+    /// Provider the failed request went to
+    pub provider: String,
+    /// The provider's own message, without the rest of its response
+    pub detail: Option<String>,
+    /// What the user can do, most direct first
+    pub suggestions: Vec<CoreErrorSuggestion>,
+}
+
+/// A question the model asked with the ask_user tool
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CoreUserQuestion {
+    /// Id to pass to `answerQuestion`
+    pub request_id: String,
+    pub question: String,
+    /// Suggested answers; the user may still answer in their own words
+    pub choices: Vec<String>,
+}
+
+/// A file a turn changed, with its diff against the content before the turn
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoreTurnFileDiff {
+    /// Path relative to the workspace, as `acceptTurnChanges` / `revertTurnChanges` take it
+    pub path: String,
+    /// "added" | "modified" | "deleted"
+    pub change: String,
+    pub diff: String,
+    pub diff_stats: CoreDiffStats,
+}
+
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CoreEvent {
-    #[napi(js_name = "protocolVersion")]
     pub protocol_version: u16,
-    #[napi(js_name = "sessionId")]
     pub session_id: String,
-    #[napi(js_name = "tsMs")]
     pub ts_ms: i64,
-    #[napi(js_name = "eventType")]
     pub event_type: CoreEventType,
     pub seq: Option<i64>,
     pub text: Option<String>,
     pub stage: Option<String>,
-    #[napi(js_name = "toolOperation")]
     pub tool_operation: Option<String>,
-    #[napi(js_name = "toolName")]
     pub tool_name: Option<String>,
-    #[napi(js_name = "keyPath")]
+    /// On tool events, the name to show for the tool (see `CoreConfirmationRequest`)
+    pub tool_display_name: Option<String>,
     pub key_path: Option<String>,
     pub kind: Option<String>,
-    #[napi(js_name = "argsSummary")]
     pub args_summary: Option<String>,
-    #[napi(js_name = "responseSummary")]
     pub response_summary: Option<String>,
-    #[napi(js_name = "displayText")]
     pub display_text: Option<String>,
     pub success: Option<bool>,
     pub confirm: Option<CoreConfirmationRequest>,
-    #[napi(js_name = "errorMessage")]
     pub error_message: Option<String>,
+    pub warning: Option<CoreWarning>,
+    pub diff_stats: Option<CoreDiffStats>,
+    pub plan_step: Option<CorePlanStep>,
+    pub stop_details: Option<CoreStopDetails>,
+    pub citations: Option<Vec<CoreCitation>>,
+    /// On a ToolEnd for a call that ran past its limit, the limit in milliseconds
+    pub timeout_ms: Option<u32>,
+    /// On Usage, the tools the turn ran, longest first
+    pub tool_stats: Option<Vec<CoreToolStat>>,
+    /// On Usage, the turn's timing
+    pub turn_timing: Option<CoreTurnTiming>,
+    /// On Proposals, the pending changes, oldest first
+    pub proposals: Option<Vec<CoreProposal>>,
+    /// On End, the `file:line` references in the answer in `text`
+    pub file_references: Option<Vec<CoreFileReference>>,
+    /// On UserInputRequested, the question waiting for an answer
+    pub question: Option<CoreUserQuestion>,
+    /// On TurnDiffReady, the changed files not yet reviewed
+    pub turn_diff: Option<Vec<CoreTurnFileDiff>>,
+    /// On tool events and ConfirmationRequested, the call's id, which
+    /// `cancel_tool` takes; the same as `confirm.request_id`
+    pub tool_call_id: Option<String>,
+    /// On Error, what kind of provider error it was, when the user has to act on it
+    pub error: Option<CoreError>,
+    /// On ToolDeniedBySkill, the active skill whose `allowed-tools` leaves out `tool_name`
+    pub skill: Option<String>,
+}
+
+/// An event of the current protocol with every optional field unset; build
+/// events with `CoreEvent { event_type, .., ..Default::default() }`
+impl Default for CoreEvent {
+    fn default() -> Self {
+        CoreEvent {
+            protocol_version: CORE_EVENT_PROTOCOL_VERSION,
+            session_id: String::new(),
+            ts_ms: 0,
+            event_type: CoreEventType::Text,
+            seq: None,
+            text: None,
+            stage: None,
+            tool_operation: None,
+            tool_name: None,
+            tool_display_name: None,
+            key_path: None,
+            kind: None,
+            args_summary: None,
+            response_summary: None,
+            display_text: None,
+            success: None,
+            confirm: None,
+            error_message: None,
+            warning: None,
+            diff_stats: None,
+            plan_step: None,
+            stop_details: None,
+            citations: None,
+            timeout_ms: None,
+            tool_stats: None,
+            turn_timing: None,
+            proposals: None,
+            file_references: None,
+            question: None,
+            turn_diff: None,
+            tool_call_id: None,
+            error: None,
+            skill: None,
+        }
+    }
+}
+
+/// Chunk of streamed response text, for hosts subscribed with a text channel
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone)]
+pub struct CoreTextEvent {
+    pub protocol_version: u16,
+    pub session_id: String,
+    pub ts_ms: i64,
+    /// Shared with the control channel's `seq`
+    pub seq: i64,
+    pub text: String,
+}
+
+/// Which of a session's logged events `get_session_events` returns; unset fields match all
+#[cfg_attr(feature = "napi", napi(object))]
+#[derive(Clone, Default)]
+pub struct CoreEventFilter {
+    /// Event types to keep, e.g. ["ToolStart", "ToolEnd"]
+    pub event_types: Option<Vec<String>>,
+    pub tool_name: Option<String>,
+    /// Only events at or after this time (ms since the epoch)
+    pub since_ms: Option<i64>,
+    /// Only events before this time
+    pub until_ms: Option<i64>,
+    /// Keep the latest this many of the matching events
+    pub limit: Option<u32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES_RS: &str = include_str!("types.rs");
+    const TYPINGS: &str = include_str!("../../src-ts/types/popcode-native.d.ts");
+
+    /// Text between `header` and the first `end` after it
+    fn block<'a>(source: &'a str, header: &str, end: &str) -> &'a str {
+        let start = source.find(header).unwrap_or_else(|| panic!("'{}' not found", header)) + header.len();
+        let len = source[start..].find(end).unwrap();
+        &source[start..start + len]
+    }
+
+    /// JS names of the fields of a `#[napi(object)]` struct (napi camel-cases them)
+    fn js_fields(name: &str) -> Vec<String> {
+        block(TYPES_RS, &format!("pub struct {} {{", name), "\n}")
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("pub "))
+            .map(|rest| {
+                let mut parts = rest.split(':').next().unwrap().split('_');
+                let mut js = parts.next().unwrap().to_string();
+                for part in parts {
+                    let mut chars = part.chars();
+                    js.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                    js.push_str(chars.as_str());
+                }
+                js
+            })
+            .collect()
+    }
+
+    #[test]
+    fn typescript_definitions_match_core_event_types() {
+        for name in ["CoreEvent", "CoreTextEvent", "CoreConfirmDecision"] {
+            let declared = block(TYPINGS, &format!("export interface {} {{", name), "\n  }");
+            for field in js_fields(name) {
+                assert!(
+                    declared.contains(&format!("    {}:", field)) || declared.contains(&format!("    {}?:", field)),
+                    "{}.{} is missing from popcode-native.d.ts",
+                    name,
+                    field
+                );
+            }
+        }
+
+        // Comments in the union may contain ';', so stop at the last quoted member
+        let declared_types = format!("{}'", block(TYPINGS, "export type CoreEventType =", "';\n"));
+        let variants: Vec<&str> = block(TYPES_RS, "pub enum CoreEventType {", "\n}")
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.starts_with("///") && !l.is_empty())
+            .map(|l| l.trim_end_matches(','))
+            .collect();
+        assert!(variants.contains(&"Security"));
+        for variant in variants {
+            assert!(
+                declared_types.contains(&format!("'{}'", variant)),
+                "CoreEventType::{} is missing from popcode-native.d.ts",
+                variant
+            );
+        }
+    }
+    #[test]
+    fn denial_messages_are_trimmed_and_bounded() {
+        let decide = |decision, message: Option<&str>| {
+            CoreConfirmDecision { request_id: "r".to_string(), decision, message: message.map(str::to_string) }
+                .to_decision()
+        };
+        assert_eq!(decide(CoreConfirmDecisionKind::AllowOnce, Some("ignored")), ConfirmDecision::AllowOnce);
+        assert_eq!(
+            decide(CoreConfirmDecisionKind::DenyWithMessage, Some("  use the staging db  ")),
+            ConfirmDecision::DenyWithMessage { text: "use the staging db".to_string() }
+        );
+        assert_eq!(decide(CoreConfirmDecisionKind::DenyWithMessage, Some(" ")), ConfirmDecision::Deny);
+        let long = "x".repeat(MAX_DENIAL_MESSAGE_CHARS + 10);
+        let ConfirmDecision::DenyWithMessage { text } = decide(CoreConfirmDecisionKind::DenyWithMessage, Some(&long))
+        else {
+            panic!("expected a message");
+        };
+        assert_eq!(text.chars().count(), MAX_DENIAL_MESSAGE_CHARS);
+    }
 }
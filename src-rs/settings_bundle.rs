@@ -0,0 +1,594 @@
+//! One-file bundle of the user's settings, for moving to another machine.
+//!
+//! A bundle holds the user config (`carrycode.json`), the runtime config
+//! (default model, theme and per-session approval modes) and the user-level
+//! skill files. Secrets are blanked in the bundled user config: provider API
+//! keys and `headers`, the `env` and `headers` values of MCP servers and the
+//! notification webhook URL. With a passphrase they travel alongside it,
+//! encrypted with a key derived from the passphrase. Importing without the
+//! passphrase keeps the secrets this machine already has for the same
+//! providers and servers.
+//!
+//! Workspace trust is left out: trusting a directory lets its project config
+//! run commands, so it has to be confirmed on each machine.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use walkdir::WalkDir;
+
+use crate::config::{RuntimeConfig, UserOverrideConfig};
+use crate::paths;
+use crate::session::crypto;
+
+const BUNDLE_FORMAT: &str = "carrycode-settings";
+const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Bundle {
+    format: String,
+    version: u32,
+    exported_at_ms: i64,
+    #[serde(default)]
+    user_config: Option<Value>,
+    #[serde(default)]
+    runtime_config: Option<Value>,
+    /// Skill files by path relative to the skills directory
+    #[serde(default)]
+    skills: BTreeMap<String, String>,
+    /// `Secrets` of `user_config`, sealed with the passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secrets: Option<String>,
+}
+
+/// What `take_secrets` blanks in a user config
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Secrets {
+    /// Of `providers`, in order
+    #[serde(default)]
+    providers: Vec<ProviderSecrets>,
+    /// Of `mcpServers`, by server name
+    #[serde(default)]
+    mcp_servers: BTreeMap<String, McpServerSecrets>,
+    /// `notify.webhook_url`, whose path often is the token
+    #[serde(default)]
+    webhook_url: String,
+}
+
+impl Secrets {
+    /// Providers, MCP servers and webhooks with a secret
+    fn count(&self) -> u32 {
+        let providers = self.providers.iter().filter(|s| !s.is_empty()).count();
+        let servers = self.mcp_servers.values().filter(|s| !s.is_empty()).count();
+        (providers + servers + usize::from(!self.webhook_url.is_empty())) as u32
+    }
+}
+
+/// What `take_secrets` blanks in one provider entry
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ProviderSecrets {
+    #[serde(default)]
+    api_key: String,
+    /// Header values by header name
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+impl ProviderSecrets {
+    fn is_empty(&self) -> bool {
+        self.api_key.is_empty() && self.headers.values().all(String::is_empty)
+    }
+}
+
+/// What `take_secrets` blanks in one MCP server entry
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct McpServerSecrets {
+    /// Environment values of a stdio server by variable name
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Header values of an HTTP server by header name
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+impl McpServerSecrets {
+    fn is_empty(&self) -> bool {
+        self.env.values().chain(self.headers.values()).all(String::is_empty)
+    }
+}
+
+/// What a bundle held, or what of it was imported
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsBundleInfo {
+    pub user_config: bool,
+    pub runtime_config: bool,
+    pub skills: u32,
+    /// Providers, MCP servers and webhooks whose secrets the bundle carries,
+    /// or restored from it
+    pub api_keys: u32,
+}
+
+/// Where the bundled settings live on this machine
+struct Locations {
+    user_config: PathBuf,
+    runtime_config: PathBuf,
+    skills: PathBuf,
+}
+
+impl Locations {
+    fn current() -> Result<Self> {
+        let missing = || anyhow::anyhow!("Cannot determine the config directory");
+        Ok(Self {
+            user_config: paths::user_config_file().ok_or_else(missing)?,
+            runtime_config: paths::runtime_config_file().ok_or_else(missing)?,
+            skills: paths::skills_dir().ok_or_else(missing)?,
+        })
+    }
+}
+
+/// Write the current settings to a bundle at `path`
+pub fn export(path: &Path, passphrase: Option<&str>) -> Result<SettingsBundleInfo> {
+    export_from(&Locations::current()?, path, passphrase)
+}
+
+/// Install the settings of the bundle at `path`, keeping a `.bak` copy of each
+/// config file it replaces
+pub fn import(path: &Path, passphrase: Option<&str>) -> Result<SettingsBundleInfo> {
+    import_into(&Locations::current()?, path, passphrase)
+}
+
+fn export_from(locations: &Locations, path: &Path, passphrase: Option<&str>) -> Result<SettingsBundleInfo> {
+    let mut user_config = read_json(&locations.user_config)?;
+    let secrets = user_config.as_mut().map(take_secrets).unwrap_or_default();
+    let key_count = secrets.count();
+    let sealed = match passphrase {
+        Some(passphrase) if key_count > 0 => {
+            Some(crypto::seal_with_passphrase(passphrase, &serde_json::to_string(&secrets)?)?)
+        }
+        _ => None,
+    };
+    let mut runtime_config = read_json(&locations.runtime_config)?;
+    if let Some(runtime_config) = runtime_config.as_mut().and_then(Value::as_object_mut) {
+        runtime_config.remove("workspaces");
+    }
+    let bundle = Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0),
+        user_config,
+        runtime_config,
+        skills: read_skills(&locations.skills)?,
+        secrets: sealed,
+    };
+    let info = SettingsBundleInfo {
+        user_config: bundle.user_config.is_some(),
+        runtime_config: bundle.runtime_config.is_some(),
+        skills: bundle.skills.len() as u32,
+        api_keys: if bundle.secrets.is_some() { key_count } else { 0 },
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(&bundle)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(info)
+}
+
+fn import_into(locations: &Locations, path: &Path, passphrase: Option<&str>) -> Result<SettingsBundleInfo> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let bundle: Bundle = serde_json::from_str(&content).context("Not a settings bundle")?;
+    if bundle.format != BUNDLE_FORMAT {
+        anyhow::bail!("Not a settings bundle: format is '{}'", bundle.format);
+    }
+    if bundle.version > BUNDLE_VERSION {
+        anyhow::bail!("Settings bundle version {} is newer than this version supports", bundle.version);
+    }
+    let mut info = SettingsBundleInfo::default();
+
+    // Check everything before writing anything
+    let secrets: Secrets = match (&bundle.secrets, passphrase) {
+        (Some(sealed), Some(passphrase)) => {
+            let secrets = crypto::open_with_passphrase(passphrase, sealed)
+                .context("Cannot decrypt the secrets; is the passphrase right?")?;
+            serde_json::from_str(&secrets).context("Malformed secrets in the bundle")?
+        }
+        _ => Secrets::default(),
+    };
+    let user_config = match bundle.user_config {
+        Some(mut user_config) => {
+            info.api_keys = restore_secrets(&mut user_config, &secrets);
+            if let Some(existing) = read_json(&locations.user_config)? {
+                keep_existing_secrets(&mut user_config, &existing);
+            }
+            serde_json::from_value::<UserOverrideConfig>(user_config.clone())
+                .context("Invalid user config in the bundle")?;
+            Some(user_config)
+        }
+        None => None,
+    };
+    let runtime_config = match bundle.runtime_config {
+        Some(mut runtime_config) => {
+            keep_workspace_trust(&mut runtime_config, read_json(&locations.runtime_config)?.as_ref());
+            serde_json::from_value::<RuntimeConfig>(runtime_config.clone())
+                .context("Invalid runtime config in the bundle")?;
+            Some(runtime_config)
+        }
+        None => None,
+    };
+    for name in bundle.skills.keys() {
+        if !is_relative_inside(Path::new(name)) {
+            anyhow::bail!("Invalid skill path '{}' in the bundle", name);
+        }
+    }
+
+    if let Some(user_config) = user_config {
+        replace_json(&locations.user_config, &user_config)?;
+        info.user_config = true;
+    }
+    if let Some(runtime_config) = runtime_config {
+        replace_json(&locations.runtime_config, &runtime_config)?;
+        info.runtime_config = true;
+    }
+    for (name, content) in &bundle.skills {
+        let target = locations.skills.join(name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, content).with_context(|| format!("Failed to write {}", target.display()))?;
+        info.skills += 1;
+    }
+    Ok(info)
+}
+
+fn read_json(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value = serde_json::from_str(&content).with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    Ok(Some(value))
+}
+
+/// Write `value` to `path`, moving the file it replaces to `<path>.bak`
+fn replace_json(path: &Path, value: &Value) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if path.exists() {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(".bak");
+        fs::copy(path, &backup)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(value)?).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn read_skills(dir: &Path) -> Result<BTreeMap<String, String>> {
+    let mut skills = BTreeMap::new();
+    if !dir.is_dir() {
+        return Ok(skills);
+    }
+    for entry in WalkDir::new(dir).follow_links(false).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(dir) else {
+            continue;
+        };
+        let name = relative.to_string_lossy().replace('\\', "/");
+        match fs::read_to_string(entry.path()) {
+            Ok(content) => {
+                skills.insert(name, content);
+            }
+            Err(e) => log::warn!("Skipping skill file {} in the settings bundle: {}", entry.path().display(), e),
+        }
+    }
+    Ok(skills)
+}
+
+fn providers_mut(user_config: &mut Value) -> impl Iterator<Item = &mut serde_json::Map<String, Value>> {
+    user_config
+        .get_mut("providers")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+}
+
+/// Blank the secrets of `user_config`, returning them
+fn take_secrets(user_config: &mut Value) -> Secrets {
+    let providers = providers_mut(user_config).map(take_provider_secrets).collect();
+    let mcp_servers = mcp_servers_mut(user_config)
+        .map(|(name, server)| (name.clone(), take_mcp_server_secrets(server)))
+        .collect();
+    let webhook_url = match user_config.pointer_mut("/notify/webhook_url") {
+        Some(Value::String(url)) => std::mem::take(url),
+        _ => String::new(),
+    };
+    Secrets {
+        providers,
+        mcp_servers,
+        webhook_url,
+    }
+}
+
+fn take_provider_secrets(provider: &mut serde_json::Map<String, Value>) -> ProviderSecrets {
+    let api_key = match provider.get_mut("api_key") {
+        Some(Value::String(key)) => std::mem::take(key),
+        _ => String::new(),
+    };
+    ProviderSecrets {
+        api_key,
+        headers: take_values(provider.get_mut("headers")),
+    }
+}
+
+fn take_mcp_server_secrets(server: &mut serde_json::Map<String, Value>) -> McpServerSecrets {
+    McpServerSecrets {
+        env: take_values(server.get_mut("env")),
+        headers: take_values(server.get_mut("headers")),
+    }
+}
+
+/// Blank the string values of a JSON object, returning them by key
+fn take_values(object: Option<&mut Value>) -> BTreeMap<String, String> {
+    object
+        .and_then(Value::as_object_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| match value {
+            Value::String(value) => Some((name.clone(), std::mem::take(value))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// MCP server entries by name, under either spelling of the key
+fn mcp_servers_mut(user_config: &mut Value) -> impl Iterator<Item = (&String, &mut serde_json::Map<String, Value>)> {
+    let servers = user_config.as_object_mut().and_then(|config| {
+        let key = if config.contains_key("mcpServers") { "mcpServers" } else { "mcp_servers" };
+        config.get_mut(key)
+    });
+    servers
+        .and_then(Value::as_object_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, server)| Some((name, server.as_object_mut()?)))
+}
+
+/// Put secrets taken by `take_secrets` back, returning for how many providers,
+/// MCP servers and webhooks
+fn restore_secrets(user_config: &mut Value, secrets: &Secrets) -> u32 {
+    let mut restored = 0;
+    for (provider, secrets) in providers_mut(user_config).zip(&secrets.providers) {
+        if secrets.is_empty() {
+            continue;
+        }
+        if !secrets.api_key.is_empty() {
+            provider.insert("api_key".to_string(), Value::String(secrets.api_key.clone()));
+        }
+        fill_blank_values(provider.get_mut("headers"), &secrets.headers);
+        restored += 1;
+    }
+    restored += fill_blank_mcp_secrets(user_config, &secrets.mcp_servers);
+    if fill_blank_webhook(user_config, &secrets.webhook_url) {
+        restored += 1;
+    }
+    restored
+}
+
+/// Fill blank secrets from this machine's config, providers by provider name
+/// and MCP servers by server name
+fn keep_existing_secrets(user_config: &mut Value, existing: &Value) {
+    let mut existing = existing.clone();
+    let known: BTreeMap<String, ProviderSecrets> = providers_mut(&mut existing)
+        .filter_map(|p| Some((provider_name(p)?, take_provider_secrets(p))))
+        .collect();
+    for provider in providers_mut(user_config) {
+        let Some(known) = provider_name(provider).and_then(|name| known.get(&name)) else {
+            continue;
+        };
+        let blank = provider.get("api_key").and_then(Value::as_str).is_none_or(str::is_empty);
+        if blank && !known.api_key.is_empty() {
+            provider.insert("api_key".to_string(), Value::String(known.api_key.clone()));
+        }
+        fill_blank_values(provider.get_mut("headers"), &known.headers);
+    }
+    let existing = take_secrets(&mut existing);
+    fill_blank_mcp_secrets(user_config, &existing.mcp_servers);
+    fill_blank_webhook(user_config, &existing.webhook_url);
+}
+
+/// Fill the blank `env` and `headers` values of the MCP servers named in
+/// `secrets`, returning for how many servers
+fn fill_blank_mcp_secrets(user_config: &mut Value, secrets: &BTreeMap<String, McpServerSecrets>) -> u32 {
+    let mut filled = 0;
+    for (name, server) in mcp_servers_mut(user_config) {
+        let Some(secrets) = secrets.get(name).filter(|s| !s.is_empty()) else {
+            continue;
+        };
+        fill_blank_values(server.get_mut("env"), &secrets.env);
+        fill_blank_values(server.get_mut("headers"), &secrets.headers);
+        filled += 1;
+    }
+    filled
+}
+
+/// Set a blank `notify.webhook_url` to `url`, if that is not blank
+fn fill_blank_webhook(user_config: &mut Value, url: &str) -> bool {
+    match user_config.pointer_mut("/notify/webhook_url") {
+        Some(Value::String(current)) if current.is_empty() && !url.is_empty() => {
+            *current = url.to_string();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Set the blank string values of a JSON object to the non-empty ones in `values`
+fn fill_blank_values(object: Option<&mut Value>, values: &BTreeMap<String, String>) {
+    let Some(current) = object.and_then(Value::as_object_mut) else {
+        return;
+    };
+    for (name, value) in current.iter_mut() {
+        let blank = value.as_str().is_some_and(str::is_empty);
+        if let Some(known) = values.get(name).filter(|v| blank && !v.is_empty()) {
+            *value = Value::String(known.clone());
+        }
+    }
+}
+
+/// Replace the workspace trust in a bundled runtime config with this machine's
+fn keep_workspace_trust(runtime_config: &mut Value, existing: Option<&Value>) {
+    let Some(runtime_config) = runtime_config.as_object_mut() else {
+        return;
+    };
+    match existing.and_then(|e| e.get("workspaces")) {
+        Some(workspaces) => runtime_config.insert("workspaces".to_string(), workspaces.clone()),
+        None => runtime_config.remove("workspaces"),
+    };
+}
+
+fn provider_name(provider: &serde_json::Map<String, Value>) -> Option<String> {
+    let name = provider.get("provider_name").or_else(|| provider.get("provider_id"))?;
+    name.as_str().map(str::to_string)
+}
+
+fn is_relative_inside(path: &Path) -> bool {
+    path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::FakeWorkspace;
+    use serde_json::json;
+
+    fn locations(root: &Path) -> Locations {
+        Locations {
+            user_config: root.join("carrycode.json"),
+            runtime_config: root.join("carrycode-runtime.json"),
+            skills: root.join("skills"),
+        }
+    }
+
+    fn provider(name: &str, key: &str) -> Value {
+        json!({ "provider_name": name, "model_name": "m", "base_url": "https://api.example.com", "api_key": key })
+    }
+
+    fn provider_with_header(name: &str, key: &str, token: &str) -> Value {
+        let mut provider = provider(name, key);
+        provider["headers"] = json!({ "X-Gateway-Token": token });
+        provider
+    }
+
+    #[test]
+    fn round_trips_settings_with_encrypted_keys() {
+        let root = FakeWorkspace::new().build().unwrap();
+        let (old, new) = (locations(&root.join("old")), locations(&root.join("new")));
+        fs::create_dir_all(old.skills.join("review")).unwrap();
+        let user_config = json!({ "providers": [provider("openai", "sk-1"), provider_with_header("gateway", "", "gw-1")] });
+        fs::write(&old.user_config, user_config.to_string()).unwrap();
+        fs::write(&old.runtime_config, r#"{"theme":"dark","workspaces":[{"path":"/w","level":"trusted"}]}"#).unwrap();
+        fs::write(old.skills.join("review/SKILL.md"), "Review carefully").unwrap();
+
+        let bundle = root.join("bundle.json");
+        let exported = export_from(&old, &bundle, Some("secret")).unwrap();
+        assert_eq!(
+            exported,
+            SettingsBundleInfo {
+                user_config: true,
+                runtime_config: true,
+                skills: 1,
+                api_keys: 2
+            }
+        );
+        let written = fs::read_to_string(&bundle).unwrap();
+        assert!(!written.contains("sk-1") && !written.contains("gw-1"));
+        assert!(written.contains("X-Gateway-Token"), "header names stay in the config");
+
+        assert!(import_into(&new, &bundle, Some("wrong")).is_err());
+        assert!(!new.user_config.exists(), "nothing is written when the bundle cannot be read");
+
+        // Without the passphrase the keys this machine has are kept
+        fs::create_dir_all(root.join("new")).unwrap();
+        let here = json!({ "providers": [provider("openai", "sk-here"), provider_with_header("gateway", "", "gw-here")] });
+        fs::write(&new.user_config, here.to_string()).unwrap();
+        let imported = import_into(&new, &bundle, None).unwrap();
+        assert_eq!(imported.api_keys, 0);
+        let config = read_json(&new.user_config).unwrap().unwrap();
+        assert_eq!(config["providers"][0]["api_key"], "sk-here");
+        assert_eq!(config["providers"][1]["headers"]["X-Gateway-Token"], "gw-here");
+        assert!(root.join("new/carrycode.json.bak").exists());
+
+        let imported = import_into(&new, &bundle, Some("secret")).unwrap();
+        assert_eq!((imported.api_keys, imported.skills), (2, 1));
+        let config = read_json(&new.user_config).unwrap().unwrap();
+        assert_eq!(config["providers"][0]["api_key"], "sk-1");
+        assert_eq!(config["providers"][1]["headers"]["X-Gateway-Token"], "gw-1");
+        let runtime = read_json(&new.runtime_config).unwrap().unwrap();
+        assert_eq!(runtime["theme"], "dark");
+        assert_eq!(fs::read_to_string(new.skills.join("review/SKILL.md")).unwrap(), "Review carefully");
+
+        // Trust has to be given again on this machine
+        assert!(!written.contains("\"/w\""));
+        assert!(runtime.get("workspaces").is_none());
+        fs::write(&new.runtime_config, r#"{"workspaces":[{"path":"/here","level":"untrusted"}]}"#).unwrap();
+        let mut sneaked: Value = serde_json::from_str(&written).unwrap();
+        sneaked["runtime_config"]["workspaces"] = json!([{ "path": "/w", "level": "trusted" }]);
+        fs::write(&bundle, sneaked.to_string()).unwrap();
+        import_into(&new, &bundle, None).unwrap();
+        let runtime = read_json(&new.runtime_config).unwrap().unwrap();
+        assert_eq!(runtime["workspaces"], json!([{ "path": "/here", "level": "untrusted" }]));
+    }
+
+    #[test]
+    fn seals_mcp_server_secrets_and_the_webhook() {
+        let root = FakeWorkspace::new().build().unwrap();
+        let (old, new) = (locations(&root.join("old")), locations(&root.join("new")));
+        fs::create_dir_all(root.join("old")).unwrap();
+        let user_config = json!({
+            "mcpServers": {
+                "github": { "command": "github-mcp", "env": { "GITHUB_TOKEN": "ghp-1" } },
+                "tracker": { "url": "https://mcp.example.com", "headers": { "Authorization": "Bearer tr-1" } }
+            },
+            "notify": { "enabled": true, "webhook_url": "https://hooks.example.com/T0/hook-1" }
+        });
+        fs::write(&old.user_config, user_config.to_string()).unwrap();
+
+        for passphrase in [None, Some("secret")] {
+            let bundle = root.join("bundle.json");
+            let exported = export_from(&old, &bundle, passphrase).unwrap();
+            assert_eq!(exported.api_keys, if passphrase.is_some() { 3 } else { 0 });
+            let written = fs::read_to_string(&bundle).unwrap();
+            for secret in ["ghp-1", "tr-1", "hook-1"] {
+                assert!(!written.contains(secret), "{} leaked into the bundle", secret);
+            }
+            assert!(written.contains("GITHUB_TOKEN") && written.contains("Authorization"));
+        }
+
+        let bundle = root.join("bundle.json");
+        assert_eq!(import_into(&new, &bundle, Some("secret")).unwrap().api_keys, 3);
+        let config = read_json(&new.user_config).unwrap().unwrap();
+        assert_eq!(config["mcpServers"]["github"]["env"]["GITHUB_TOKEN"], "ghp-1");
+        assert_eq!(config["mcpServers"]["tracker"]["headers"]["Authorization"], "Bearer tr-1");
+        assert_eq!(config["notify"]["webhook_url"], "https://hooks.example.com/T0/hook-1");
+
+        // Without the passphrase the secrets this machine has are kept
+        let mut here = config.clone();
+        here["mcpServers"]["github"]["env"]["GITHUB_TOKEN"] = json!("ghp-here");
+        fs::write(&new.user_config, here.to_string()).unwrap();
+        import_into(&new, &bundle, None).unwrap();
+        let config = read_json(&new.user_config).unwrap().unwrap();
+        assert_eq!(config["mcpServers"]["github"]["env"]["GITHUB_TOKEN"], "ghp-here");
+        assert_eq!(config["notify"]["webhook_url"], "https://hooks.example.com/T0/hook-1");
+    }
+}
@@ -0,0 +1,333 @@
+//! Opt-in, anonymous usage metrics.
+//!
+//! Nothing is counted until telemetry is turned on, with `[telemetry] enabled`
+//! or `set_telemetry_enabled`. Only counters with fixed labels are kept:
+//! turns, tool calls by tool kind, turns by provider brand (recognised hosted
+//! APIs by base URL, anything else "local" or "other"), errors by category, and
+//! failed tool calls by cause, split by whether the session already sent a note
+//! on that cause (see `failure_hints`).
+//! No session ids, prompts, paths, model names or configured provider names
+//! are recorded.
+//!
+//! With an `endpoint` configured, the counts gathered since the last upload
+//! are POSTed as one JSON batch every `upload_interval_secs`. Without one they
+//! stay local, readable through `metrics()`.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex as StdMutex, Once};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::config::{AppConfig, TelemetryConfig};
+use crate::llm::tools::tool_trait::ToolKind;
+
+const METRIC_TURNS: &str = "turns";
+const METRIC_TOOL_CALLS: &str = "tool_calls";
+const METRIC_PROVIDER_TURNS: &str = "provider_turns";
+const METRIC_ERRORS: &str = "errors";
+const METRIC_TOOL_FAILURES: &str = "tool_failures";
+const METRIC_HINTED_TOOL_FAILURES: &str = "hinted_tool_failures";
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelemetryCounter {
+    /// "turns", "tool_calls", "provider_turns", "errors", "tool_failures" or "hinted_tool_failures"
+    pub metric: String,
+    /// Tool kind, provider brand, error category or failure cause; empty for turns
+    pub label: String,
+    pub count: u32,
+}
+
+#[cfg_attr(feature = "napi", napi_derive::napi(object))]
+pub struct TelemetryMetrics {
+    pub enabled: bool,
+    /// Where batches are uploaded, if anywhere
+    pub endpoint: Option<String>,
+    /// Counts since the process started
+    pub counters: Vec<TelemetryCounter>,
+}
+
+/// Counts by (metric, label)
+#[derive(Debug, Default)]
+struct Counters(BTreeMap<(&'static str, &'static str), u64>);
+
+impl Counters {
+    fn add(&mut self, metric: &'static str, label: &'static str) {
+        *self.0.entry((metric, label)).or_insert(0) += 1;
+    }
+
+    fn merge(&mut self, other: Counters) {
+        for (key, count) in other.0 {
+            *self.0.entry(key).or_insert(0) += count;
+        }
+    }
+
+    fn to_vec(&self) -> Vec<TelemetryCounter> {
+        self.0
+            .iter()
+            .map(|((metric, label), count)| TelemetryCounter {
+                metric: metric.to_string(),
+                label: label.to_string(),
+                count: (*count).min(u32::MAX as u64) as u32,
+            })
+            .collect()
+    }
+}
+
+struct State {
+    endpoint: Option<String>,
+    interval: Duration,
+    /// Since the process started
+    totals: Counters,
+    /// Since the last upload
+    pending: Counters,
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static START_UPLOADER: Once = Once::new();
+
+static STATE: LazyLock<StdMutex<State>> = LazyLock::new(|| StdMutex::new(State {
+    endpoint: None,
+    interval: Duration::from_secs(3600),
+    totals: Counters::default(),
+    pending: Counters::default(),
+}));
+
+/// Apply the configuration; `runtime_enabled` is the choice saved by `set_enabled`
+pub fn configure(config: &TelemetryConfig, runtime_enabled: Option<bool>) {
+    let enabled = runtime_enabled.unwrap_or(config.enabled);
+    let endpoint = Some(config.endpoint.trim().to_string()).filter(|e| !e.is_empty());
+    if let Ok(mut state) = STATE.lock() {
+        state.endpoint = endpoint.clone();
+        state.interval = Duration::from_secs(config.upload_interval_secs.max(60));
+    }
+    ENABLED.store(enabled, Ordering::SeqCst);
+    if enabled && endpoint.is_some() {
+        START_UPLOADER.call_once(|| {
+            if let Err(e) = thread::Builder::new().name("telemetry".to_string()).spawn(run_uploader) {
+                log::warn!("Telemetry uploader failed to start: {}", e);
+            }
+        });
+    }
+}
+
+/// Turn telemetry on or off and remember the choice in the runtime config
+pub fn set_enabled(enabled: bool) -> anyhow::Result<()> {
+    let mut config = AppConfig::load()?;
+    config.runtime.telemetry_enabled = Some(enabled);
+    config.save_runtime()?;
+    configure(&config.telemetry, Some(enabled));
+    if !enabled {
+        if let Ok(mut state) = STATE.lock() {
+            state.pending = Counters::default();
+        }
+    }
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+pub fn metrics() -> TelemetryMetrics {
+    let (endpoint, counters) = match STATE.lock() {
+        Ok(state) => (state.endpoint.clone(), state.totals.to_vec()),
+        Err(_) => (None, Vec::new()),
+    };
+    TelemetryMetrics {
+        enabled: is_enabled(),
+        endpoint,
+        counters,
+    }
+}
+
+fn record(metric: &'static str, label: &'static str) {
+    if !is_enabled() {
+        return;
+    }
+    if let Ok(mut state) = STATE.lock() {
+        state.totals.add(metric, label);
+        state.pending.add(metric, label);
+    }
+}
+
+/// A turn sent to the provider at `base_url`
+pub fn record_turn(base_url: &str) {
+    record(METRIC_TURNS, "");
+    record(METRIC_PROVIDER_TURNS, provider_brand(base_url));
+}
+
+pub fn record_tool_call(kind: ToolKind) {
+    record(METRIC_TOOL_CALLS, kind.as_str());
+}
+
+pub fn record_error(error: &anyhow::Error) {
+    record(METRIC_ERRORS, error_category(&format!("{:#}", error)));
+}
+
+/// A failed tool call with the fixed `cause` label; `hinted` if the tool was
+/// sent with a note on that cause
+pub fn record_tool_failure(cause: &'static str, hinted: bool) {
+    record(
+        if hinted { METRIC_HINTED_TOOL_FAILURES } else { METRIC_TOOL_FAILURES },
+        cause,
+    );
+}
+
+/// The hosted API behind `base_url`; self-hosted and unknown endpoints are not told apart
+fn provider_brand(base_url: &str) -> &'static str {
+    const BRANDS: &[(&str, &str)] = &[
+        ("api.openai.com", "openai"),
+        ("openai.azure.com", "azure"),
+        ("anthropic.com", "anthropic"),
+        ("googleapis.com", "google"),
+        ("deepseek.com", "deepseek"),
+        ("moonshot.cn", "moonshot"),
+        ("moonshot.ai", "moonshot"),
+        ("bigmodel.cn", "zhipuai"),
+        ("z.ai", "zhipuai"),
+        ("minimax.chat", "minimax"),
+        ("minimaxi.com", "minimax"),
+        ("minimax.io", "minimax"),
+        ("aliyuncs.com", "alibaba"),
+        ("x.ai", "xai"),
+        ("siliconflow.cn", "siliconflow"),
+        ("siliconflow.com", "siliconflow"),
+        ("openrouter.ai", "openrouter"),
+    ];
+    let host = url::Url::parse(base_url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.to_lowercase()))
+        .unwrap_or_default();
+    if host.is_empty() {
+        return "other";
+    }
+    if host == "localhost" || host.starts_with("127.") || host.starts_with("192.168.") || host.starts_with("10.") {
+        return "local";
+    }
+    BRANDS
+        .iter()
+        .find(|(domain, _)| host == *domain || host.ends_with(&format!(".{}", domain)))
+        .map(|(_, brand)| *brand)
+        .unwrap_or("other")
+}
+
+fn error_category(message: &str) -> &'static str {
+    let m = message.to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|n| m.contains(n));
+    if has(&["401", "403", "unauthorized", "invalid api key", "invalid_api_key", "authentication"]) {
+        "auth"
+    } else if has(&["insufficient_quota", "quota", "billing", "credit"]) {
+        "quota"
+    } else if has(&["429", "rate limit", "rate_limit", "too many requests"]) {
+        "rate_limit"
+    } else if has(&["context length", "context_length", "maximum context", "too many tokens", "prompt is too long"]) {
+        "context_length"
+    } else if has(&["500", "502", "503", "504", "overloaded", "internal server error", "bad gateway"]) {
+        "server"
+    } else if has(&["timed out", "timeout", "connection", "dns", "error sending request", "failed to initiate llm stream"]) {
+        "network"
+    } else {
+        "other"
+    }
+}
+
+fn run_uploader() {
+    loop {
+        let interval = STATE.lock().map(|s| s.interval).unwrap_or(Duration::from_secs(3600));
+        thread::sleep(interval);
+        if !is_enabled() {
+            continue;
+        }
+        let (endpoint, batch) = match STATE.lock() {
+            Ok(mut state) => (state.endpoint.clone(), std::mem::take(&mut state.pending)),
+            Err(_) => return,
+        };
+        let Some(endpoint) = endpoint else {
+            continue;
+        };
+        if batch.0.is_empty() {
+            continue;
+        }
+        if let Err(e) = upload(&endpoint, &batch) {
+            log::debug!("Telemetry upload failed: {:#}", e);
+            crate::health::record_error(crate::health::Subsystem::Telemetry, format!("upload failed: {:#}", e));
+            // Keep the counts for the next attempt
+            if let Ok(mut state) = STATE.lock() {
+                state.pending.merge(batch);
+            }
+        }
+    }
+}
+
+fn upload(endpoint: &str, batch: &Counters) -> anyhow::Result<()> {
+    let body = json!({
+        "schema": 1,
+        "version": env!("CARGO_PKG_VERSION"),
+        "os": std::env::consts::OS,
+        "counters": batch
+            .to_vec()
+            .into_iter()
+            .map(|c| json!({ "metric": c.metric, "label": c.label, "count": c.count }))
+            .collect::<Vec<_>>(),
+    });
+    reqwest::blocking::Client::builder()
+        .timeout(UPLOAD_TIMEOUT)
+        .build()?
+        .post(endpoint)
+        .json(&body)
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_come_from_fixed_sets() {
+        assert_eq!(provider_brand("https://api.openai.com/v1"), "openai");
+        assert_eq!(provider_brand("https://api.anthropic.com"), "anthropic");
+        assert_eq!(provider_brand("https://open.bigmodel.cn/api/paas/v4"), "zhipuai");
+        assert_eq!(provider_brand("http://localhost:11434"), "local");
+        assert_eq!(provider_brand("https://llm.internal.example.com/v1"), "other");
+        assert_eq!(provider_brand("not a url"), "other");
+
+        assert_eq!(error_category("HTTP 401 Unauthorized: invalid api key"), "auth");
+        assert_eq!(error_category("429 Too Many Requests"), "rate_limit");
+        assert_eq!(error_category("This model's maximum context length is 128000 tokens"), "context_length");
+        assert_eq!(error_category("error sending request for url"), "network");
+        assert_eq!(error_category("something odd at /home/alice/project"), "other");
+    }
+
+    #[test]
+    fn counters_accumulate_and_merge() {
+        let mut counters = Counters::default();
+        counters.add(METRIC_TOOL_CALLS, "read");
+        counters.add(METRIC_TOOL_CALLS, "read");
+        let mut other = Counters::default();
+        other.add(METRIC_TOOL_CALLS, "read");
+        other.add(METRIC_TURNS, "");
+        counters.merge(other);
+        assert_eq!(
+            counters.to_vec(),
+            vec![
+                TelemetryCounter {
+                    metric: "tool_calls".to_string(),
+                    label: "read".to_string(),
+                    count: 3
+                },
+                TelemetryCounter {
+                    metric: "turns".to_string(),
+                    label: String::new(),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+//! Helpers for checking that a tool behaves like the builtin ones.
+//!
+//! The agent relies on every tool honouring the same contract: an OpenAI
+//! function definition whose parameters are an object, a strict form of that
+//! schema, and a `ToolResult` envelope from `execute` even when the arguments
+//! are unusable. The builtin tools are held to it by the crate's own contract
+//! tests, which are written with these helpers; custom tools and MCP schemas
+//! can be checked the same way. Build with `--features testing`:
+//!
+//! ```ignore
+//! use carrycode_coreapi::testing::{FakeWorkspace, ToolHarness};
+//!
+//! let workspace = FakeWorkspace::new().file("src/lib.rs", "pub fn a() {}\n").build()?;
+//! let harness = ToolHarness::new(Box::new(MyTool::default())).full_access();
+//! harness.assert_contract();
+//! let result = harness.call(&serde_json::json!({ "path": workspace.join("src/lib.rs") }));
+//! assert!(result.success, "{}", result.stderr);
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde_json::{json, Value};
+
+use crate::llm::models::strict_schema;
+use crate::llm::tools::list_available_tools;
+use crate::llm::utils::tool_access::{with_tool_access, ToolAccessLevel};
+
+pub use crate::llm::tools::tool_trait::{
+    Tool, ToolAdapter, ToolKind, ToolOperation, ToolResult, ToolSpec, TOOL_RESULT_VERSION,
+};
+
+/// Runs one tool the way the agent does and checks what comes back
+pub struct ToolHarness {
+    tool: Box<dyn Tool>,
+    access: ToolAccessLevel,
+}
+
+impl ToolHarness {
+    pub fn new(tool: Box<dyn Tool>) -> Self {
+        Self {
+            tool,
+            access: ToolAccessLevel::Workspace,
+        }
+    }
+
+    /// The builtin tool called `name`; panics if there is none
+    pub fn builtin(name: &str) -> Self {
+        let tool = list_available_tools()
+            .into_iter()
+            .find(|t| t.name() == name)
+            .unwrap_or_else(|| panic!("no builtin tool {}", name));
+        Self::new(tool)
+    }
+
+    /// One harness per builtin tool, in registration order
+    pub fn builtins() -> Vec<Self> {
+        list_available_tools().into_iter().map(Self::new).collect()
+    }
+
+    /// Let calls reach paths outside the current directory, such as a
+    /// workspace from `FakeWorkspace::build`, as in a session with full access
+    pub fn full_access(mut self) -> Self {
+        self.access = ToolAccessLevel::Full;
+        self
+    }
+
+    pub fn tool(&self) -> &dyn Tool {
+        self.tool.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        self.tool.name()
+    }
+
+    /// Call the tool with `arguments` and return its checked result
+    pub fn call(&self, arguments: &Value) -> ToolResult {
+        self.call_raw(&arguments.to_string())
+    }
+
+    /// Call the tool with arguments as the model sent them, valid JSON or not.
+    /// Panics if `execute` fails or its output is not a `ToolResult` of this tool.
+    pub fn call_raw(&self, arguments: &str) -> ToolResult {
+        let raw = with_tool_access(self.access, || self.tool.execute(arguments))
+            .unwrap_or_else(|e| panic!("tool {} failed instead of returning a result: {:#}", self.name(), e));
+        let result: ToolResult = serde_json::from_str(&raw)
+            .unwrap_or_else(|e| panic!("tool {} returned no ToolResult ({}): {}", self.name(), e, raw));
+        assert_eq!(result.version, TOOL_RESULT_VERSION, "tool {}", self.name());
+        assert_eq!(result.tool_name, self.name(), "tool {} reported another name", self.name());
+        assert_eq!(result.kind, self.tool.kind(), "tool {}", self.name());
+        result
+    }
+
+    /// Every check a builtin tool passes
+    pub fn assert_contract(&self) {
+        self.assert_definition();
+        self.assert_rejects_invalid_arguments();
+    }
+
+    /// The definition is a function named after the tool, and has a strict
+    /// form if the tool says it supports one
+    pub fn assert_definition(&self) {
+        let definition = self.tool.to_tool_definition();
+        assert_eq!(
+            definition.pointer("/function/name").and_then(Value::as_str),
+            Some(self.name()),
+            "definition of tool {}",
+            self.name()
+        );
+        if self.tool.supports_strict_schema() {
+            assert_schema_round_trips(&definition);
+        } else {
+            assert_function_shape(&definition);
+        }
+    }
+
+    /// Arguments that are not JSON give a failed result with an error, not an `Err`
+    pub fn assert_rejects_invalid_arguments(&self) {
+        let result = self.call_raw("not json");
+        assert!(!result.success, "tool {} should fail on invalid json", self.name());
+        assert!(!result.stderr.is_empty(), "tool {} failed without saying why", self.name());
+    }
+}
+
+/// `definition` has the OpenAI function shape with object parameters
+pub fn assert_function_shape(definition: &Value) {
+    let name = definition.pointer("/function/name").and_then(Value::as_str);
+    assert!(name.is_some_and(|n| !n.is_empty()), "definition has no function name: {}", definition);
+    assert_eq!(definition["type"], "function", "definition of {:?}", name);
+    assert_eq!(
+        definition.pointer("/function/parameters/type").and_then(Value::as_str),
+        Some("object"),
+        "parameters of {:?}",
+        name
+    );
+}
+
+/// `definition` has the function shape, survives serialization unchanged, and
+/// tightens to a strict schema that is itself stable. Works for MCP tool
+/// schemas wrapped as `{"type": "function", "function": {..}}` too.
+pub fn assert_schema_round_trips(definition: &Value) {
+    assert_function_shape(definition);
+    let name = definition.pointer("/function/name").cloned().unwrap_or_default();
+    let reparsed: Value = serde_json::from_str(&definition.to_string()).expect("definition serializes");
+    assert_eq!(&reparsed, definition, "definition of {} changed on a round trip", name);
+
+    let strict = strict_schema::tighten(definition)
+        .unwrap_or_else(|e| panic!("definition of {} has no strict form: {}", name, e));
+    assert_function_shape(&strict);
+    assert_eq!(
+        strict.pointer("/function/parameters/additionalProperties"),
+        Some(&json!(false)),
+        "strict form of {}",
+        name
+    );
+    let again = strict_schema::tighten(&strict).expect("a strict schema tightens again");
+    assert_eq!(again, strict, "tightening {} twice changed it", name);
+}
+
+/// Files to lay out in a scratch directory
+#[derive(Debug, Default)]
+pub struct FakeWorkspace {
+    files: Vec<(PathBuf, Vec<u8>)>,
+    dirs: Vec<PathBuf>,
+}
+
+impl FakeWorkspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A file at the relative `path`, creating its parents
+    pub fn file(mut self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Self {
+        self.files.push((path.as_ref().to_path_buf(), contents.as_ref().to_vec()));
+        self
+    }
+
+    /// An empty directory at the relative `path`
+    pub fn dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.dirs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Create the directory under the system temp dir
+    pub fn build(self) -> io::Result<TempWorkspace> {
+        static NEXT: AtomicU32 = AtomicU32::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "carrycode-workspace-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+        // Canonical, so paths match what the tools resolve them to (/tmp is a link on macOS)
+        let workspace = TempWorkspace { root: fs::canonicalize(&dir)? };
+        for path in &self.dirs {
+            fs::create_dir_all(workspace.join(path))?;
+        }
+        for (path, contents) in &self.files {
+            let path = workspace.join(path);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, contents)?;
+        }
+        Ok(workspace)
+    }
+}
+
+/// A scratch directory, removed when dropped
+#[derive(Debug)]
+pub struct TempWorkspace {
+    root: PathBuf,
+}
+
+impl TempWorkspace {
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.root.join(path)
+    }
+
+    /// Contents of the file at the relative `path`
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        fs::read_to_string(self.join(path))
+    }
+}
+
+impl Drop for TempWorkspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Points `CARRY_HOME` at a scratch directory while it lives, so saved
+/// sessions and config land there rather than in the user's. The variable is
+/// shared by the whole process, so tests holding one run one at a time.
+#[cfg(test)]
+pub(crate) struct TestHome {
+    home: TempWorkspace,
+    previous: Option<std::ffi::OsString>,
+    _serial: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl TestHome {
+    pub(crate) fn new() -> Self {
+        static SERIAL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let home = FakeWorkspace::new().build().expect("scratch home");
+        let previous = std::env::var_os(crate::paths::HOME_ENV);
+        std::env::set_var(crate::paths::HOME_ENV, home.path());
+        Self { home, previous, _serial: serial }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.home.path()
+    }
+
+    /// `sessions/<namespace>/<session_id>` under the home
+    pub(crate) fn session_dir(&self, namespace: &str, session_id: &str) -> PathBuf {
+        self.home.join("sessions").join(namespace).join(session_id)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestHome {
+    fn drop(&mut self) {
+        // Queued writes resolve their paths now, so they must land before the home goes back
+        crate::api::flush_sessions();
+        match self.previous.take() {
+            Some(previous) => std::env::set_var(crate::paths::HOME_ENV, previous),
+            None => std::env::remove_var(crate::paths::HOME_ENV),
+        }
+    }
+}
+
+/// What a `FakeProvider` sends back for one request
+#[cfg(test)]
+pub(crate) enum FakeResponse {
+    /// A complete JSON body, as a non-streaming completion returns
+    Json(Value),
+    /// Server-sent events with these `data:` payloads; `dropped` closes the
+    /// connection after them, mid-response, instead of ending the stream
+    Events { data: Vec<Value>, dropped: bool },
+}
+
+/// An HTTP endpoint on localhost standing in for a model provider. Each
+/// request gets the next response, the last one repeating; request bodies
+/// are kept for the test to inspect.
+#[cfg(test)]
+pub(crate) struct FakeProvider {
+    base_url: String,
+    requests: std::sync::Arc<std::sync::Mutex<Vec<Value>>>,
+}
+
+#[cfg(test)]
+impl FakeProvider {
+    pub(crate) fn start(responses: Vec<FakeResponse>) -> Self {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake provider");
+        let base_url = format!("http://{}", listener.local_addr().expect("fake provider address"));
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let received = std::sync::Arc::clone(&requests);
+        // Left running; it ends with the test process
+        std::thread::spawn(move || {
+            for (served, stream) in listener.incoming().enumerate() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().expect("clone connection"));
+                let mut content_length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line != "\r\n" {
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                    line.clear();
+                }
+                let mut body = vec![0; content_length];
+                if reader.read_exact(&mut body).is_err() {
+                    continue;
+                }
+                received.lock().unwrap().push(serde_json::from_slice(&body).unwrap_or(Value::Null));
+
+                let reply = match &responses[served.min(responses.len() - 1)] {
+                    FakeResponse::Json(body) => {
+                        let body = body.to_string();
+                        format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                             Connection: close\r\n\r\n{}",
+                            body.len(),
+                            body
+                        )
+                    }
+                    FakeResponse::Events { data, dropped } => {
+                        let mut reply = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                                         Transfer-Encoding: chunked\r\nConnection: close\r\n\r\n"
+                            .to_string();
+                        for event in data {
+                            let frame = format!("data: {}\n\n", event);
+                            reply.push_str(&format!("{:x}\r\n{}\r\n", frame.len(), frame));
+                        }
+                        // Without the last chunk the body ends early, as when the connection drops
+                        if !dropped {
+                            reply.push_str("0\r\n\r\n");
+                        }
+                        reply
+                    }
+                };
+                let _ = stream.write_all(reply.as_bytes());
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+            }
+        });
+        Self { base_url, requests }
+    }
+
+    pub(crate) fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Bodies of the requests received so far, parsed as JSON
+    pub(crate) fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harness_runs_builtins_against_a_fake_workspace() {
+        let workspace = FakeWorkspace::new()
+            .file("src/lib.rs", "pub fn answer() -> u32 {\n    42\n}\n")
+            .dir("empty")
+            .build()
+            .unwrap();
+        assert!(workspace.join("empty").is_dir());
+        assert_eq!(workspace.read("src/lib.rs").unwrap().lines().count(), 3);
+
+        let view = ToolHarness::builtin("view").full_access();
+        view.assert_contract();
+        let result = view.call(&json!({ "file_path": workspace.join("src/lib.rs") }));
+        assert!(result.success, "{}", result.stderr);
+        assert!(result.stdout.contains("42"));
+
+        let root = workspace.path().to_path_buf();
+        drop(workspace);
+        assert!(!root.exists());
+
+        // An MCP schema wrapped as a function definition
+        assert_schema_round_trips(&json!({
+            "type": "function",
+            "function": {
+                "name": "lookup",
+                "description": "Look up a ticket",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "id": { "type": "string" } },
+                    "required": ["id"]
+                }
+            }
+        }));
+    }
+}
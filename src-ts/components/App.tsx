@@ -9,6 +9,7 @@ import { OutputArea } from './OutputArea.js';
 import { ProcessArea } from './ProcessArea.js';
 import { ToolConfirmMenu } from './ToolConfirmMenu.js';
 import type {
+  CoreConfirmDecisionKind,
   CoreConfirmationRequest,
   CoreEvent,
   Message,
@@ -61,12 +62,12 @@ export function App() {
     };
   }, [stdout]);
 
-  async function handleConfirmation(decision: string) {
+  async function handleConfirmation(decision: CoreConfirmDecisionKind, message?: string) {
     if (!confirmationRequest) return;
     const requestId = confirmationRequest.requestId;
     setConfirmationRequest(null);
     try {
-      await confirmTool(sessionId, { requestId, decision });
+      await confirmTool(sessionId, { requestId, decision, message: message ?? null });
     } catch {
     }
   }
@@ -501,8 +502,28 @@ export function App() {
             handleStreamEnd();
             return;
           }
+          if (eventType === 'PartialResponse') {
+            appendSegment({
+              stage: '__ANSWERING__',
+              title: t('status.error'),
+              content: String(event.displayText ?? 'The response was cut short'),
+              tools: [],
+            });
+            return;
+          }
           if (eventType === 'Error') {
-            const msg = String(event.errorMessage ?? 'Unknown error');
+            // Quota, credit and key errors come with a code; say what to do about them
+            const providerError = event.error;
+            const msg = providerError
+              ? [
+                  t(`provider_errors.${providerError.code}`, { provider: providerError.provider }),
+                  providerError.detail ?? '',
+                  ...providerError.suggestions.map(s => t(`provider_errors.${s.action}`, {
+                    provider: providerError.provider,
+                    models: s.models.join(', '),
+                  })),
+                ].filter(Boolean).join('\n')
+              : String(event.errorMessage ?? 'Unknown error');
             appendSegment({
               stage: '__ANSWERING__',
               title: t('status.error'),
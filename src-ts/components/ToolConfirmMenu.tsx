@@ -2,15 +2,18 @@ import React, { useEffect, useMemo, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 import { Box, Text, useInput } from 'ink';
 import { useTheme } from '../theme/index.js';
+import type { CoreConfirmDecisionKind } from '../types/index.js';
 
 interface ToolConfirmMenuProps {
   request: {
     toolName?: string;
+    toolDisplayName?: string;
     arguments?: string;
+    summary?: string;
     kind?: string;
     keyPath?: string;
   };
-  onConfirm: (decision: string) => void;
+  onConfirm: (decision: CoreConfirmDecisionKind, message?: string) => void;
 }
 
 export function ToolConfirmMenu({ request, onConfirm }: ToolConfirmMenuProps) {
@@ -18,12 +21,14 @@ export function ToolConfirmMenu({ request, onConfirm }: ToolConfirmMenuProps) {
   const { theme } = useTheme();
   const [selectedIndex, setSelectedIndex] = useState(0);
   const [isSubmitting, setIsSubmitting] = useState(false);
+  // Set while the user types what to do instead of the denied call
+  const [reason, setReason] = useState<string | null>(null);
 
   const options = useMemo(
     () => [
-      { id: '1', label: t('confirm.yes_execute') },
-      { id: '2', label: t('confirm.yes_session') },
-      { id: '3', label: t('confirm.no_differently') },
+      { id: '1', decision: 'AllowOnce' as const, label: t('confirm.yes_execute') },
+      { id: '2', decision: 'AllowForSession' as const, label: t('confirm.yes_session') },
+      { id: '3', decision: 'Deny' as const, label: t('confirm.no_differently') },
     ],
     [t],
   );
@@ -31,18 +36,41 @@ export function ToolConfirmMenu({ request, onConfirm }: ToolConfirmMenuProps) {
   useEffect(() => {
     setSelectedIndex(0);
     setIsSubmitting(false);
+    setReason(null);
   }, [request.toolName, request.arguments, request.kind, request.keyPath]);
 
-  const submit = (id: string) => {
+  const submit = (decision: CoreConfirmDecisionKind, message?: string) => {
     if (isSubmitting) return;
     setIsSubmitting(true);
     setSelectedIndex(0);
-    onConfirm(id);
+    onConfirm(decision, message);
+  };
+
+  // Denying first asks what to do instead; an empty answer just denies
+  const choose = (decision: CoreConfirmDecisionKind) => {
+    if (decision === 'Deny') {
+      setReason('');
+      return;
+    }
+    submit(decision);
   };
 
   useInput(
     (input, key) => {
       if (isSubmitting) return;
+      if (reason !== null) {
+        if (key.escape) {
+          setReason(null);
+        } else if (key.return) {
+          const text = reason.trim();
+          submit(text ? 'DenyWithMessage' : 'Deny', text || undefined);
+        } else if (key.backspace || key.delete) {
+          setReason(reason.slice(0, -1));
+        } else if (input && !key.ctrl && !key.meta) {
+          setReason(reason + input);
+        }
+        return;
+      }
       if (key.upArrow) {
         setSelectedIndex((prev) => Math.max(0, prev - 1));
         return;
@@ -52,11 +80,12 @@ export function ToolConfirmMenu({ request, onConfirm }: ToolConfirmMenuProps) {
         return;
       }
       if (key.return) {
-        submit(options[selectedIndex]?.id ?? '1');
+        choose(options[selectedIndex]?.decision ?? 'AllowOnce');
         return;
       }
-      if (input === '1' || input === '2' || input === '3') {
-        submit(input);
+      const option = options.find((o) => o.id === input);
+      if (option) {
+        choose(option.decision);
       }
     },
     { isActive: !isSubmitting },
@@ -65,14 +94,29 @@ export function ToolConfirmMenu({ request, onConfirm }: ToolConfirmMenuProps) {
   return (
     <Box flexDirection="column" borderStyle="round" borderColor={theme.colors.warning} paddingX={1}>
       <Text wrap="truncate">
-        {t('confirm.tool')} <Text color={theme.colors.primary}>{String(request.toolName ?? '')}</Text>
+        {t('confirm.tool')} <Text color={theme.colors.primary}>{String(request.toolDisplayName || request.toolName || '')}</Text>
       </Text>
       {request.keyPath && request.keyPath !== '*' && (
         <Text wrap="truncate">
           {t('confirm.target')} <Text color={theme.colors.secondary}>{request.keyPath}</Text>
         </Text>
       )}
-      {options.map((option, index) => (
+      {request.summary && request.summary !== request.keyPath && (
+        <Text wrap="truncate" color={theme.colors.secondary}>
+          {request.summary}
+        </Text>
+      )}
+      {reason !== null && (
+        <Box flexDirection="column">
+          <Text color={theme.colors.secondary}>{t('confirm.reason_prompt')}</Text>
+          <Text wrap="wrap">
+            {'> '}
+            {reason}
+            <Text color={theme.colors.primary}>█</Text>
+          </Text>
+        </Box>
+      )}
+      {reason === null && options.map((option, index) => (
         <Text
           key={option.id}
           color={index === selectedIndex ? theme.colors.success : theme.colors.text}
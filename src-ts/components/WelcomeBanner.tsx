@@ -1,16 +1,16 @@
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import { useTranslation } from 'react-i18next';
 import { Box, Text, useStdout } from 'ink';
 import Gradient from 'ink-gradient';
 import { useTheme } from '../theme/index.js';
 import { useRustBridge } from '../hooks/useRustBridge.js';
-import { Message } from '../types/index.js';
+import { Message, PreflightFinding } from '../types/index.js';
 import { BANNER_BEGONIA } from '../theme/themes.js';
 
 export const WelcomeBanner: React.FC = () => {
   const { t } = useTranslation();
   const { theme } = useTheme();
-  const { getAppConfig } = useRustBridge();
+  const { getAppConfig, runPreflight } = useRustBridge();
   const { stdout } = useStdout();
   const terminalWidth = stdout?.columns || 80;
   
@@ -25,6 +25,20 @@ export const WelcomeBanner: React.FC = () => {
     }
   });
 
+  // Checks that failed when the first session opened, with how to fix them
+  const [problems, setProblems] = useState<PreflightFinding[]>([]);
+  useEffect(() => {
+    let mounted = true;
+    runPreflight()
+      .then(findings => {
+        if (mounted) {
+          setProblems(findings.filter(f => f.status === 'warning' || f.status === 'error'));
+        }
+      })
+      .catch(() => {});
+    return () => { mounted = false; };
+  }, []);
+
   // Use BANNER_BEGONIA as the banner text
   const bannerLines = BANNER_BEGONIA.trim().split('\n');
   
@@ -67,6 +81,21 @@ export const WelcomeBanner: React.FC = () => {
             <Text color={theme.colors.dimText}>{horizontalLine}</Text>
           </Box>
         )}
+        {problems.length > 0 && (
+          <Box flexDirection="column" marginTop={1}>
+            <Text color={theme.colors.warning}>{t('welcome.preflight_title')}</Text>
+            {problems.map((problem, index) => (
+              <Box key={index} flexDirection="column">
+                <Text color={problem.status === 'error' ? theme.colors.error : theme.colors.warning}>
+                  {"● "}{problem.check}: {problem.message}
+                </Text>
+                {problem.remediation && (
+                  <Text color={theme.colors.dimText}>{"  "}{problem.remediation}</Text>
+                )}
+              </Box>
+            ))}
+          </Box>
+        )}
     </Box>
   );
 };
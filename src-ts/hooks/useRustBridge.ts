@@ -1,5 +1,5 @@
 import { logger } from '../utils/logger.js';
-import type { CoreConfirmDecision, CoreEvent, CoreConfirmationRequest, AvailableModel, LatencyInfo, AppConfig } from '../types/index.js';
+import type { CoreConfirmDecision, CoreEvent, CoreConfirmationRequest, AvailableModel, LatencyInfo, AppConfig, PreflightFinding } from '../types/index.js';
 import { loadCoreApi } from '../utils/loadCoreApi.js';
 
 const coreapi = loadCoreApi();
@@ -116,6 +116,10 @@ export function useRustBridge() {
       return session.checkLatency();
   }
 
+  async function runPreflight(): Promise<PreflightFinding[]> {
+      return coreapi.runPreflight();
+  }
+
   async function askAgent(
     sessionId: string, 
     prompt: string, 
@@ -227,6 +231,7 @@ export function useRustBridge() {
     getApprovalMode,
     setApprovalMode,
     checkLatency,
+    runPreflight,
     sessionDescs,
     sessionInstances,
   };
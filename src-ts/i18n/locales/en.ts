@@ -17,6 +17,14 @@ export const en = {
     idle: "Idle",
     submitting: "Submitting",
   },
+  provider_errors: {
+    QuotaExceeded: "The {{provider}} API key has used up its quota.",
+    CreditExhausted: "The {{provider}} account is out of credit.",
+    InvalidApiKey: "The {{provider}} API key was rejected.",
+    add_credits: "Check the plan and billing of your {{provider}} account.",
+    update_api_key: "Update the API key of {{provider}} in /config.",
+    switch_model: "Or switch to another model with /model: {{models}}",
+  },
   session: {
     summary_title: "Session summary",
     id: "Session ID",
@@ -48,7 +56,8 @@ export const en = {
     tips: [
         "Run /help to view the relevant help!",
         "Run /exit or 'Ctrl + C' to exit!"
-    ]
+    ],
+    preflight_title: "Setup checks found problems:"
   },
   agent_mode: {
     plan: "Plan",
@@ -58,6 +67,7 @@ export const en = {
     yes_execute: "Yes, execute",
     yes_session: "Yes, don't ask again (session)",
     no_differently: "No, tell CarryCode differently",
+    reason_prompt: "What should CarryCode do instead? (Enter to send, empty to just deny, Esc to go back)",
     tool: "Tool:",
     target: "Target:",
   },
@@ -17,6 +17,14 @@ export const zhCN = {
     idle: "空闲",
     submitting: "提交中",
   },
+  provider_errors: {
+    QuotaExceeded: "{{provider}} 的 API Key 配额已用尽。",
+    CreditExhausted: "{{provider}} 账户余额不足。",
+    InvalidApiKey: "{{provider}} 的 API Key 无效。",
+    add_credits: "请检查 {{provider}} 账户的套餐和账单。",
+    update_api_key: "请通过 /config 更新 {{provider}} 的 API Key。",
+    switch_model: "或通过 /model 切换到其他模型：{{models}}",
+  },
   session: {
     summary_title: "会话概要",
     id: "SessionID",
@@ -48,7 +56,8 @@ export const zhCN = {
     tips: [
         "运行 /help 查看相关帮助",
         "运行 /exit 或按 'Ctrl + C' 退出"
-    ]
+    ],
+    preflight_title: "环境检查发现以下问题："
   },
   agent_mode: {
     plan: "Plan",
@@ -58,6 +67,7 @@ export const zhCN = {
     yes_execute: "是，执行",
     yes_session: "是，本会话不再询问",
     no_differently: "不，告诉 CarryCode 换种方式",
+    reason_prompt: "CarryCode 应该怎么做？（回车发送，留空则直接拒绝，Esc 返回）",
     tool: "工具:",
     target: "目标:",
   },
@@ -106,7 +106,7 @@ async function runOnce(prompt: string, timeoutMs: number) {
         if (!sawConfirmation) {
           sawConfirmation = true;
         }
-        void session.confirmTool({ requestId: event.confirm.requestId, decision: '3' });
+        void session.confirmTool({ requestId: event.confirm.requestId, decision: 'Deny' });
       }
       if (debugEvents) {
         eventCount += 1;
@@ -150,6 +150,13 @@ async function runOnce(prompt: string, timeoutMs: number) {
   }
 }
 
+process.on('exit', () => {
+  try {
+    loadCoreApi().shutdown();
+  } catch {
+  }
+});
+
 const onceArgs = parseOnceArgs(process.argv.slice(2));
 if (onceArgs !== null) {
   void runOnce(onceArgs.prompt, onceArgs.timeoutMs);
@@ -1,23 +1,29 @@
 import type {
   CoreConfirmDecision,
+  CoreConfirmDecisionKind,
   CoreConfirmationRequest,
+  CoreError,
   CoreEvent,
   CoreEventType,
   ResponseStage,
   ToolOperation,
   AvailableModel,
   LatencyInfo,
+  PreflightFinding,
 } from 'carrycode-coreapi';
 
 export type {
   CoreConfirmDecision,
+  CoreConfirmDecisionKind,
   CoreConfirmationRequest,
+  CoreError,
   CoreEvent,
   CoreEventType,
   ResponseStage,
   ToolOperation,
   AvailableModel,
   LatencyInfo,
+  PreflightFinding,
 };
 
 export interface ToolCallLog {
@@ -66,10 +72,12 @@ export interface AppConfig {
       command: string;
       args: string[];
       env: Record<string, string>;
+      key_paths?: Record<string, string>;
     }
     | {
       url: string;
       headers: Record<string, string>;
+      key_paths?: Record<string, string>;
     }
   >;
 }
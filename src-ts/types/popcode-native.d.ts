@@ -2,7 +2,242 @@ declare module 'carrycode-coreapi' {
   export function createSessionId(): string;
   export function getAppConfig(): string;
   export function listAvailableModels(): AvailableModel[];
+  export function listModelAliases(): ModelAlias[];
   export function getDefaultModel(): string | null;
+  // Snapshots are written in the background; flush before the process exits.
+  export function flushSessions(): void;
+  // Flushes snapshots and stops every MCP server process; call on exit.
+  export function shutdown(): void;
+  // Functions taking a session id also take the namespace it was opened in (default:
+  // "default"); the same id in another namespace is another session.
+  // Cancels the session's turn and stops its MCP servers; false if it was not open.
+  export function closeSession(sessionId: string, namespace?: string | null): boolean;
+  // Anonymous usage metrics; nothing is counted until enabled.
+  export function setTelemetryEnabled(enabled: boolean): void;
+  export function getTelemetryMetrics(): TelemetryMetrics;
+  // Settings bundle for moving to another machine; API keys travel only
+  // with a passphrase, encrypted with it
+  export function exportSettings(path: string, passphrase?: string | null): SettingsBundleInfo;
+  export function importSettings(path: string, passphrase?: string | null): SettingsBundleInfo;
+  // Event protocol: events are downleveled to the negotiated version and features
+  // (all of that version's if none are named); call before subscribing
+  export function getProtocolInfo(): ProtocolInfo;
+  export function negotiateProtocol(version: number, features?: string[] | null): ProtocolInfo;
+  // Report focus changes; [notify] only_when_away holds notifications back while present
+  export function setUserPresent(present: boolean): void;
+  export function dispatchCommand(sessionId: string, input: string, namespace?: string | null): Promise<CommandResult>;
+  // Pinned messages are kept verbatim when the history is compacted
+  export function pinMessage(sessionId: string, index: number, namespace?: string | null): Promise<void>;
+  export function unpinMessage(sessionId: string, index: number, namespace?: string | null): Promise<void>;
+  export function getPinned(sessionId: string, namespace?: string | null): Promise<PinnedMessage[]>;
+  // Title (at most 120 characters) and notes (4000) shown in saved sessions; empty removes them
+  export function renameSession(sessionId: string, title: string, namespace?: string | null): Promise<void>;
+  export function setSessionNotes(sessionId: string, text: string, namespace?: string | null): Promise<void>;
+  // Variables (e.g. a ticket id) filled into {{KEY}} in the system prompt and exported to bash
+  // commands and MCP servers started for the session; an empty value removes one
+  export function setSessionVar(
+    sessionId: string,
+    key: string,
+    value: string,
+    namespace?: string | null,
+  ): Promise<void>;
+  export function getSessionVars(sessionId: string, namespace?: string | null): SessionVar[];
+  // Skills the user enables in the session, replacing the previous ones; the workspace's
+  // default skills ([skills] in .carry/carrycode.json) are active regardless
+  export function setSessionSkills(sessionId: string, names: string[], namespace?: string | null): Promise<void>;
+  export function getSessionSkills(sessionId: string, namespace?: string | null): SessionSkill[];
+  // Tools the active skills' allowed-tools let the session call, and the skill that
+  // denies each of the others (see the ToolDeniedBySkill event)
+  export function getAllowedTools(sessionId: string, namespace?: string | null): Promise<AllowedToolsInfo>;
+  export function getShellState(sessionId: string, namespace?: string | null): ShellStateInfo;
+  // Events logged for a session, open or saved (tool calls, confirmations, errors, usage),
+  // oldest first
+  export function getSessionEvents(
+    sessionId: string,
+    filter?: CoreEventFilter | null,
+    namespace?: string | null,
+  ): CoreEvent[];
+  // Tool calls of the session so far, longest total time first
+  export function getToolStats(sessionId: string, namespace?: string | null): CoreToolStat[];
+  // Timing of the latest turns (up to 100), oldest first
+  export function getTurnTimings(sessionId: string, namespace?: string | null): CoreTurnTiming[];
+  export function lockFile(path: string, owner: string): boolean;
+  export function unlockFile(path: string, owner: string): boolean;
+  export function listTrash(sessionId: string, namespace?: string | null): TrashEntryInfo[];
+  export function restoreFromTrash(sessionId: string, entryId: string, namespace?: string | null): TrashEntryInfo;
+  export function purgeTrash(sessionId?: string | null, namespace?: string | null): number;
+  // Files between the workspace and a session's remote execution target. A push over
+  // changes made on the target (or a pull over local ones) since the last sync is refused
+  // unless forced. The write tool pushes what it writes.
+  export function pushFiles(
+    sessionId: string,
+    paths: string[],
+    force?: boolean | null,
+    namespace?: string | null,
+  ): Promise<SyncStatusInfo[]>;
+  export function pullFiles(
+    sessionId: string,
+    paths: string[],
+    force?: boolean | null,
+    namespace?: string | null,
+  ): Promise<SyncStatusInfo[]>;
+  // Every file synced so far when paths is empty or omitted
+  export function getSyncStatus(
+    sessionId: string,
+    paths?: string[] | null,
+    namespace?: string | null,
+  ): Promise<SyncStatusInfo[]>;
+  // Review of the files build-mode turns changed (see the TurnDiffReady event); an empty list
+  // means all of them. Reverting refuses files changed again since the last turn ended.
+  export function acceptTurnChanges(sessionId: string, files: string[], namespace?: string | null): string[];
+  export function revertTurnChanges(sessionId: string, files: string[], namespace?: string | null): string[];
+  // Edits of paths matching these gitignore-style globs (relative to the workspace) run
+  // without confirmation in read-only mode; others still ask. An empty list clears them.
+  export function setAutoAcceptPaths(sessionId: string, globs: string[], namespace?: string | null): void;
+  export function getAutoAcceptPaths(sessionId: string, namespace?: string | null): string[];
+  // In propose mode edit, delete and move calls are held back as proposals; the
+  // pending ones arrive in a Proposals event at the end of the turn.
+  export function setProposeMode(sessionId: string, enabled: boolean, namespace?: string | null): void;
+  export function getProposeMode(sessionId: string, namespace?: string | null): boolean;
+  export function getProposals(sessionId: string, namespace?: string | null): CoreProposal[];
+  // A response longer than these (0 is no limit) is stopped and kept as truncated, with a
+  // PartialResponse event; null goes back to [sessions] max_output_chars / max_output_tokens
+  export function setOutputLimit(
+    sessionId: string,
+    limit?: OutputLimitSettings | null,
+    namespace?: string | null,
+  ): void;
+  export function getOutputLimit(sessionId: string, namespace?: string | null): OutputLimitSettings;
+  // Apply the given pending proposals (all if ids is empty) in the order they were
+  // proposed; each comes back with status 'applied' or 'failed'
+  export function applyProposals(sessionId: string, ids: string[], namespace?: string | null): Promise<CoreProposal[]>;
+  // Returns the number discarded
+  export function discardProposals(sessionId: string, ids: string[], namespace?: string | null): number;
+  // Answer the question of a UserInputRequested event; false if it is no longer waiting
+  export function answerQuestion(
+    sessionId: string,
+    requestId: string,
+    answer: string,
+    namespace?: string | null,
+  ): boolean;
+  // Cancel one tool call by the toolCallId of its events; the turn goes on. False if it already ended
+  export function cancelTool(sessionId: string, requestId: string, namespace?: string | null): boolean;
+  export function setOverlayMode(sessionId: string, enabled: boolean, namespace?: string | null): void;
+  export function getOverlayMode(sessionId: string, namespace?: string | null): boolean;
+  export function getOverlayChanges(sessionId: string, namespace?: string | null): OverlayChangeInfo[];
+  export function materializeChanges(sessionId: string, namespace?: string | null): OverlayMaterializeResult;
+  export function discardChanges(sessionId: string, namespace?: string | null): number;
+  export function getWorkspaceTrust(path?: string | null): WorkspaceTrustInfo;
+  export function trustWorkspace(path: string, level: 'trusted' | 'untrusted'): WorkspaceTrustInfo;
+  export function getLspStatus(): Promise<LspServerStatus[]>;
+  export function getCoreStatus(): Promise<CoreStatus>;
+  // Environment checks run when the first session opened; waits for them if still running
+  export function runPreflight(): Promise<PreflightFinding[]>;
+
+  export interface SyncStatusInfo {
+    path: string;
+    state: 'in_sync' | 'local_ahead' | 'remote_ahead' | 'conflict';
+    syncedAtMs?: number | null;
+  }
+
+  export interface TrashEntryInfo {
+    id: string;
+    originalPath: string;
+    isDir: boolean;
+    deletedAtMs: number;
+  }
+
+  export interface OverlayChangeInfo {
+    path: string;
+    change: 'write' | 'delete' | 'mkdir';
+    diff?: string | null;
+    conflict: boolean;
+  }
+
+  export interface OverlayMaterializeResult {
+    applied: string[];
+    conflicts: string[];
+  }
+
+  export interface WorkspaceTrustInfo {
+    path: string;
+    level?: 'trusted' | 'untrusted' | null;
+    hasProjectConfig: boolean;
+  }
+
+  export interface LspServerStatus {
+    workspace: string;
+    name: string;
+    state: 'running' | 'unresponsive' | 'exited' | 'restarting' | 'failed';
+    restarts: number;
+    lastError?: string | null;
+    openFiles: number;
+  }
+
+  export interface SessionStatusInfo {
+    sessionId: string;
+    namespace: string;
+    subscribed: boolean;
+  }
+
+  export interface McpServerStatus {
+    name: string;
+    state: 'running' | 'failed';
+    toolCount: number;
+    lastError?: string | null;
+    restarts: number;
+    processes: number;
+  }
+
+  export interface SubsystemError {
+    subsystem: 'llm' | 'mcp' | 'lsp' | 'shell' | 'persistence' | 'telemetry' | 'notifier';
+    message: string;
+    tsMs: number;
+  }
+
+  export interface CoreStatus {
+    version: string;
+    eventProtocolVersion: number;
+    snapshotVersion: number;
+    activeSessions: number;
+    sessions: SessionStatusInfo[];
+    mcpServers: McpServerStatus[];
+    lspServers: LspServerStatus[];
+    shellStarted: boolean;
+    shellAlive: boolean;
+    lastErrors: SubsystemError[];
+    // A server failed or a session shell died
+    degraded: boolean;
+  }
+
+  export interface OutputLimitSettings {
+    maxChars: number;
+    // Estimated at about 4 characters per token
+    maxTokens: number;
+  }
+
+  export interface PreflightFinding {
+    // 'config_dir' | 'data_dir' | 'log_dir' | 'executable:<name>' | 'provider' | 'clock' | 'disk'
+    check: string;
+    status: 'ok' | 'warning' | 'error' | 'skipped';
+    message: string;
+    // What the user can do about it
+    remediation?: string | null;
+  }
+
+  export interface ShellStateInfo {
+    cwd: string;
+    envChanges: Record<string, string>;
+  }
+
+  export interface CommandResult {
+    handled: boolean;
+    command?: string | null;
+    success: boolean;
+    message: string;
+    action?: string | null;
+    data?: string | null;
+  }
 
   export type ResponseStage = '__THINKING__' | '__ANSWERING__' | '__END__';
 
@@ -15,21 +250,193 @@ declare module 'carrycode-coreapi' {
     | 'ToolStart'
     | 'ToolOutput'
     | 'ToolEnd'
+    // With [answer_format] on, text is the post-processed answer and fileReferences the
+    // file:line references in it
     | 'End'
     | 'ConfirmationRequested'
-    | 'Error';
+    | 'Error'
+    | 'Warning'
+    | 'FileConflict'
+    // warning.code 'stream_restarted': discard the partial response text;
+    // 'stream_continued': keep it, the response continues where it stopped
+    | 'Resuming'
+    | 'PlanStepStart'
+    | 'PlanStepEnd'
+    | 'Checkpoint'
+    // The provider blocked the prompt or stopped the response; see stopDetails
+    | 'Blocked'
+    | 'Citations'
+    // warning.code 'prompt_injection': fetched or MCP output carried instruction-like
+    // text; warning.items lists rule (subject) and matched text (reason)
+    | 'Security'
+    // End of a turn; toolStats lists the tools it ran, turnTiming where the time went,
+    // displayText summarizes both
+    | 'Usage'
+    // End of a turn in propose mode; proposals lists the changes waiting for review
+    | 'Proposals'
+    // The model asked the user a question; see question, answer with answerQuestion
+    | 'UserInputRequested'
+    // End of a build-mode turn with changed files to review, including any left
+    // unreviewed before; turnDiff has them, review with acceptTurnChanges / revertTurnChanges
+    | 'TurnDiffReady'
+    // The response was cut short and what it had was kept; warning.code 'stream_failed':
+    // the stream failed after a substantial answer, 'output_limit': it passed the
+    // session's output limit. displayText says so, errorMessage why
+    | 'PartialResponse'
+    | 'ToolDeniedBySkill';
 
   export interface CoreConfirmationRequest {
     requestId: string;
     toolName: string;
+    // Name to show for the tool, e.g. "create_issue (github)" for an MCP tool
+    toolDisplayName: string;
     arguments: string;
+    // One line saying what the call would do, e.g. the command or "src/a.rs (−3 +5 lines)"
+    summary: string;
     kind: string;
     keyPath: string;
+    // Unified diff of the changes, e.g. for rename_symbol
+    preview?: string | null;
   }
 
+  export type CoreConfirmDecisionKind =
+    | 'AllowOnce'
+    // Also allow later calls of the tool on the same target without asking
+    | 'AllowForSession'
+    | 'Deny'
+    // Deny, with message passed on to the model
+    | 'DenyWithMessage';
+
   export interface CoreConfirmDecision {
     requestId: string;
-    decision: string;
+    decision: CoreConfirmDecisionKind;
+    // Why the call was denied or what to do instead; used with DenyWithMessage
+    message?: string | null;
+  }
+
+  export interface CoreWarningItem {
+    subject: string;
+    reason: string;
+  }
+
+  export interface CoreDiffStats {
+    filePath: string;
+    additions: number;
+    removals: number;
+    hunks: number;
+    label: string;
+  }
+
+  export interface CoreStopDetails {
+    reason: string;
+    promptBlocked: boolean;
+    categories: string[];
+    message: string;
+  }
+
+  export interface CoreCitation {
+    uri?: string | null;
+    title?: string | null;
+    license?: string | null;
+    startIndex?: number | null;
+    endIndex?: number | null;
+  }
+
+  // Unset fields match every event
+  export interface CoreEventFilter {
+    // e.g. ['ToolStart', 'ToolEnd']
+    eventTypes?: string[] | null;
+    toolName?: string | null;
+    sinceMs?: number | null;
+    untilMs?: number | null;
+    // Keep the latest this many matching events
+    limit?: number | null;
+  }
+
+  export interface CoreToolStat {
+    toolName: string;
+    calls: number;
+    failures: number;
+    successRate: number;
+    durationMs: number;
+  }
+
+  export interface CoreTurnTiming {
+    provider: string;
+    model: string;
+    startedAtMs: number;
+    // null when the model replied with tool calls only
+    firstTokenMs?: number | null;
+    durationMs: number;
+    modelMs: number;
+    toolMs: number;
+  }
+
+  // A file:line reference in a final answer; startIndex/endIndex are character offsets
+  // into the End event's text
+  export interface CoreFileReference {
+    path: string;
+    absolutePath: string;
+    line: number;
+    column?: number | null;
+    startIndex: number;
+    endIndex: number;
+  }
+
+  // A question the model asked with the ask_user tool; choices are suggestions,
+  // any text is a valid answer
+  export interface CoreUserQuestion {
+    requestId: string;
+    question: string;
+    choices: string[];
+  }
+
+  export interface CoreTurnFileDiff {
+    // Relative to the workspace, as acceptTurnChanges / revertTurnChanges take it
+    path: string;
+    change: 'added' | 'modified' | 'deleted';
+    diff: string;
+    diffStats: CoreDiffStats;
+  }
+
+  // A change held back for review in propose mode
+  export interface CoreProposal {
+    // Pass to applyProposals / discardProposals, e.g. 'p3'
+    id: string;
+    toolName: string;
+    summary: string;
+    keyPath: string;
+    // Unified diff, when known before the change is applied
+    diff?: string | null;
+    diffStats?: CoreDiffStats | null;
+    status: 'pending' | 'applied' | 'failed' | 'discarded';
+    error?: string | null;
+  }
+
+  // Provider errors that retrying does not fix
+  export type CoreErrorCode = 'QuotaExceeded' | 'CreditExhausted' | 'InvalidApiKey';
+
+  export interface CoreErrorSuggestion {
+    action: 'switch_model' | 'add_credits' | 'update_api_key';
+    // For switch_model: models of other providers as 'provider:model', for setModel
+    models: string[];
+  }
+
+  export interface CoreError {
+    code: CoreErrorCode;
+    // English; show localized text for code instead
+    message: string;
+    provider: string;
+    // The provider's own message, without the rest of its response
+    detail?: string | null;
+    // Most direct first
+    suggestions: CoreErrorSuggestion[];
+  }
+
+  export interface CoreWarning {
+    code: string;
+    message: string;
+    items: CoreWarningItem[];
   }
 
   export interface CoreEvent {
@@ -42,6 +449,7 @@ declare module 'carrycode-coreapi' {
     stage?: ResponseStage | null;
     toolOperation?: ToolOperation | null;
     toolName?: string | null;
+    toolDisplayName?: string | null;
     keyPath?: string | null;
     kind?: string | null;
     argsSummary?: string | null;
@@ -50,11 +458,88 @@ declare module 'carrycode-coreapi' {
     success?: boolean | null;
     confirm?: CoreConfirmationRequest | null;
     errorMessage?: string | null;
+    warning?: CoreWarning | null;
+    diffStats?: CoreDiffStats | null;
+    planStep?: CorePlanStep | null;
+    stopDetails?: CoreStopDetails | null;
+    citations?: CoreCitation[] | null;
+    // Set on ToolEnd when the call ran past its [tool_timeouts] limit
+    timeoutMs?: number | null;
+    // Set on Usage: the tools the turn ran
+    toolStats?: CoreToolStat[] | null;
+    turnTiming?: CoreTurnTiming | null;
+    // Set on Proposals: the pending changes, oldest first
+    proposals?: CoreProposal[] | null;
+    // Set on End when [answer_format] is on: references in the post-processed answer in text
+    fileReferences?: CoreFileReference[] | null;
+    // Set on UserInputRequested: the question waiting for answerQuestion
+    question?: CoreUserQuestion | null;
+    // Set on TurnDiffReady: the changed files not yet reviewed
+    turnDiff?: CoreTurnFileDiff[] | null;
+    // Set on tool events and ConfirmationRequested: the call's id for cancelTool
+    toolCallId?: string | null;
+    // Set on Error for provider errors the user has to act on (quota, credit, API key)
+    error?: CoreError | null;
+    // Set on ToolDeniedBySkill: the active skill that does not allow toolName
+    skill?: string | null;
+  }
+
+  // Streamed response text, delivered on the text channel of subscribeChannels
+  export interface CoreTextEvent {
+    protocolVersion: number;
+    sessionId: string;
+    tsMs: number;
+    // Shared with CoreEvent.seq on the control channel
+    seq: number;
+    text: string;
+  }
+
+  export interface CorePlanStep {
+    index: number;
+    total: number;
+    title: string;
+  }
+
+  export interface AutoModeOptions {
+    budgetMinutes: number;
+    checkpointMinutes?: number | null;
+    steerWaitSeconds?: number | null;
+  }
+
+  export interface AutoRunResult {
+    content: string;
+    toolsUsed: boolean;
+    cancelled: boolean;
+    budgetExhausted: boolean;
+    checkpoints: number;
+    elapsedMs: number;
+  }
+
+  export interface PlanRunResult {
+    status: 'completed' | 'paused' | 'rejected' | 'cancelled' | 'no_plan';
+    plan: string;
+    steps: string[];
+    completedSteps: number;
+    failedStep?: number | null;
+    errorMessage?: string | null;
+  }
+
+  // A turn the host process stopped in the middle of, found on open
+  export interface InterruptedTurnInfo {
+    startedAtMs: number;
+    prompt: string;
+    completedRounds: number;
+    partialText: string;
   }
 
   export interface AgentResult {
     content: string;
     tools_used: boolean;
+    cancelled: boolean;
+    stopDetails?: CoreStopDetails | null;
+    // The provider stream failed; content is the partial answer it had produced
+    truncated: boolean;
+    citations: CoreCitation[];
   }
 
   export interface AvailableModel {
@@ -62,6 +547,53 @@ declare module 'carrycode-coreapi' {
     model: string;
   }
 
+  export interface SessionVar {
+    key: string;
+    value: string;
+  }
+
+  export interface SessionSkill {
+    name: string;
+    description: string;
+    // 'user' | 'workspace'
+    source: string;
+    active: boolean;
+    // Enabled by the workspace config; cannot be turned off
+    default: boolean;
+  }
+
+  export interface DeniedTool {
+    toolName: string;
+    skill: string;
+  }
+
+  export interface AllowedToolsInfo {
+    // False when no active skill limits the tools; allowed then lists them all
+    restricted: boolean;
+    allowed: string[];
+    denied: DeniedTool[];
+  }
+
+  export interface PinnedMessage {
+    index: number;
+    role: string;
+    content: string;
+  }
+
+  export interface ModelAlias {
+    name: string;
+    provider: string;
+    model: string;
+  }
+
+  export interface ToolChoiceSettings {
+    /** "auto", "any", "tool" (call `toolName`) or "none"; Claude models only */
+    mode: string;
+    toolName?: string | null;
+    /** At most one tool call per response; also sent to OpenAI-compatible providers */
+    disableParallelToolUse: boolean;
+  }
+
   export interface LatencyInfo {
     latencyMs: number;
     modelName: string;
@@ -72,28 +604,123 @@ declare module 'carrycode-coreapi' {
     content: string;
   }
 
+  export interface TelemetryCounter {
+    metric: 'turns' | 'tool_calls' | 'provider_turns' | 'errors' | 'tool_failures' | 'hinted_tool_failures';
+    label: string;
+    count: number;
+  }
+
+  export interface ProtocolInfo {
+    version: number;
+    minVersion: number;
+    // warnings, planSteps, checkpoints, stopDetails, citations, usage, proposals,
+    // userQuestions, turnDiffs, toolDetails, fileReferences, toolCallIds, errorCodes,
+    // partialResponses
+    features: string[];
+    negotiatedVersion: number;
+    negotiatedFeatures: string[];
+  }
+
+  export interface SettingsBundleInfo {
+    userConfig: boolean;
+    runtimeConfig: boolean;
+    skills: number;
+    apiKeys: number;
+  }
+
+  export interface TelemetryMetrics {
+    enabled: boolean;
+    endpoint?: string | null;
+    counters: TelemetryCounter[];
+  }
+
   export interface SavedSessionInfo {
     sessionId: string;
     createdAtMs: number;
     updatedAtMs: number;
     messageCount: number;
+    // Workspace the session works in; unset for sessions saved before it was recorded
+    workspace?: string | null;
+    // Set with renameSession / setSessionNotes
+    title?: string | null;
+    notes?: string | null;
+  }
+
+  export interface SavedSessionQuery {
+    // 'updated' (default), 'created', 'title' or 'message_count'
+    sortBy?: string | null;
+    // Oldest, shortest or Z to A first
+    reverse?: boolean | null;
+    // Only sessions that work in the workspace at this path
+    workspace?: string | null;
+    offset?: number | null;
+    // All sessions by default
+    limit?: number | null;
+  }
+
+  export interface SavedSessionPage {
+    sessions: SavedSessionInfo[];
+    // Sessions matching the query, on all pages
+    total: number;
   }
 
   export class Session {
-    static open(sessionId: string): Session;
-    static getSavedSessions(): SavedSessionInfo[];
-    execute(prompt: string): Promise<AgentResult>;
+    // Sessions in different namespaces (default: "default") are kept apart,
+    // on disk under sessions/<namespace>/ in the data directory.
+    static open(sessionId: string, namespace?: string | null): Session;
+    static getSessions(namespace?: string | null): string[];
+    static getSavedSessions(namespace?: string | null): SavedSessionInfo[];
+    // Saved sessions of the workspace at path. Opening one saved in another workspace warns
+    // or fails per [security] workspace_mismatch.
+    static listSavedSessionsForWorkspace(path: string, namespace?: string | null): SavedSessionInfo[];
+    static querySavedSessions(query?: SavedSessionQuery | null, namespace?: string | null): SavedSessionPage;
+    // Closes the session if open and removes its saved files, events, trash and overlay;
+    // false if it did not exist
+    static deleteSession(sessionId: string, namespace?: string | null): boolean;
+    // Deletes as many as it can; throws naming the ones that failed. Returns the number deleted
+    static deleteSessions(sessionIds: string[], namespace?: string | null): number;
+    // Saves a copy under a new id, titled as a copy
+    static duplicateSession(sessionId: string, namespace?: string | null): SavedSessionInfo;
+    readonly namespace: string;
+    // Calls repeating an idempotencyKey (e.g. a UUID per user action) within 10 minutes get
+    // the first call's result instead of running again; likewise for confirmTool.
+    execute(prompt: string, idempotencyKey?: string | null): Promise<AgentResult>;
+    executeAuto(prompt: string, options: AutoModeOptions): Promise<AutoRunResult>;
+    steer(message: string): boolean;
+    executePlanThenBuild(prompt: string): Promise<PlanRunResult>;
+    resumePlan(): Promise<PlanRunResult>;
+    getInterruptedTurn(): InterruptedTurnInfo | null;
+    resumeInterruptedTurn(): Promise<AgentResult>;
+    discardInterruptedTurn(): boolean;
+    cancel(): Promise<boolean>;
+    // Stops the session's MCP servers; false if it was already closed
+    close(): boolean;
     clearHistory(): Promise<void>;
     getHistory(): Promise<ProviderMessage[]>;
-    confirmTool(decision: CoreConfirmDecision): Promise<void>;
+    confirmTool(decision: CoreConfirmDecision, idempotencyKey?: string | null): Promise<void>;
     subscribe(onEvent: (err: unknown, event?: CoreEvent | null) => void): void;
+    // Text and control events on separate callbacks. Both share one seq counter,
+    // starting at 1 per subscription; control events are never dropped.
+    subscribeChannels(onText: (event: CoreTextEvent) => void, onControl: (event: CoreEvent) => void): void;
     unsubscribe(): void;
     getAvailableModels(): Promise<AvailableModel[]>;
+    // An empty provider takes `model` as an alias or model name
     setModel(provider: string, model: string): Promise<void>;
+    setToolChoice(settings: ToolChoiceSettings): Promise<void>;
+    getToolChoice(): Promise<ToolChoiceSettings>;
     checkLatency(): Promise<LatencyInfo>;
+    // Internal tasks run on the auxiliary model (`auxiliary_model`, [models.routes])
+    generateTitle(): Promise<string>;
+    summarizeHistory(): Promise<string>;
+    // Replaces the history with a summary plus the pinned messages; resolves to the number summarized
+    compactHistory(): Promise<number>;
+    generateCommitMessage(): Promise<string>;
     getAgentMode(): 'plan' | 'build';
     setAgentMode(mode: 'plan' | 'build'): Promise<void>;
     getApprovalMode(): 'read-only' | 'agent' | 'agent-full';
     setApprovalMode(mode: 'read-only' | 'agent' | 'agent-full'): void;
+    // Where bash, ls, view and edit run; remote targets use the [remote] settings
+    getExecutionTarget(): 'local' | 'ssh' | 'docker' | 'devcontainer';
+    setExecutionTarget(target: 'local' | 'ssh' | 'docker' | 'devcontainer'): void;
   }
 }